@@ -130,6 +130,15 @@ fn demo_metric_streaming() {
                 name, value, threshold
             );
         }
+        MetricEvent::AlertTransition(name, state, value, _) => {
+            println!("  [ALERT {}] {} = {:.2}", state.as_str(), name, value);
+        }
+        MetricEvent::Anomaly(name, value, mean, z_score) => {
+            println!(
+                "  [ANOMALY] {} = {:.2} (mean {:.2}, z {:.1})",
+                name, value, mean, z_score
+            );
+        }
     });
 
     // Publish metrics
@@ -1,6 +1,9 @@
 //! Performance overhead benchmarks for observability components
 
-use embeddenator_obs::{create_span, measure_n, metrics, HiResTimer, TestMetrics};
+use embeddenator_obs::{
+    create_span, measure_n, metrics, HiResMetrics, HiResTimer, HiResTimestamp, Picoseconds,
+    TestMetrics,
+};
 use std::time::Instant;
 
 fn main() {
@@ -10,6 +13,7 @@ fn main() {
     benchmark_tracing_overhead();
     benchmark_test_metrics_overhead();
     benchmark_hires_timing_overhead();
+    benchmark_hires_batch_recording();
 }
 
 fn benchmark_metrics_overhead() {
@@ -117,3 +121,64 @@ fn benchmark_hires_timing_overhead() {
 
     drop(results); // Prevent optimization
 }
+
+fn benchmark_hires_batch_recording() {
+    println!("5. Hi-Res Metrics Batch Recording");
+    println!("   Comparing per-sample record() with record_slice()...");
+
+    let samples: Vec<Picoseconds> = (0..10_000u64)
+        .map(|i| 50_000 + (i * 7919) % 100_000)
+        .collect();
+    let rounds = 200;
+    let total_samples = (samples.len() * rounds) as u128;
+
+    let metrics = HiResMetrics::new();
+    let start = Instant::now();
+    for _ in 0..rounds {
+        for &ps in &samples {
+            metrics.record(HiResTimestamp::from_picos(ps, 0));
+        }
+    }
+    let per_sample_ns = start.elapsed().as_nanos() / total_samples;
+
+    let metrics = HiResMetrics::new();
+    let start = Instant::now();
+    for _ in 0..rounds {
+        metrics.record_slice(std::hint::black_box(&samples));
+    }
+    let batched_ns = (start.elapsed().as_nanos() * 1000 / total_samples) as f64 / 1000.0;
+
+    // Contended: 4 threads recording into shared metrics
+    let shared = HiResMetrics::new();
+    let contended = |batched: bool| {
+        let start = Instant::now();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..rounds / 4 {
+                        if batched {
+                            shared.record_slice(&samples);
+                        } else {
+                            for &ps in &samples {
+                                shared.record(HiResTimestamp::from_picos(ps, 0));
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        start.elapsed()
+    };
+    let contended_single = contended(false);
+    let contended_batched = contended(true);
+
+    println!("   record():       {}ns per sample", per_sample_ns);
+    println!("   record_slice(): {:.3}ns per sample", batched_ns);
+    println!(
+        "   4 threads:      {:?} per-sample vs {:?} batched ({:.0}x)",
+        contended_single,
+        contended_batched,
+        contended_single.as_secs_f64() / contended_batched.as_secs_f64().max(1e-9)
+    );
+    println!("   ✓ Target: batch <5ns per sample\n");
+}
@@ -0,0 +1,73 @@
+//! Drive the full pipeline with simulated traffic.
+//!
+//! Runs a compressed day of query load (one step per simulated minute)
+//! through telemetry, a metric stream with alerts, and span export, then
+//! prints a summary and the Prometheus exposition.
+//!
+//! ```text
+//! cargo run --example simulate -- [seed]
+//! ```
+
+use embeddenator_obs::simulate::Simulation;
+use embeddenator_obs::{
+    MetricEvent, MetricStream, OtelExporter, PrometheusExporter, Telemetry, ThresholdAlert,
+};
+use std::time::Duration;
+
+fn main() {
+    let seed = std::env::args()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(42);
+
+    let mut telemetry = Telemetry::default_config();
+    let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+    stream.add_alert(ThresholdAlert::above("query_load_rps", 180.0).for_samples(3));
+    stream.add_alert(ThresholdAlert::below("cache_hit_ratio", 0.5));
+    stream.subscribe(|event| {
+        if let MetricEvent::AlertTransition(name, state, value, threshold) = event {
+            println!(
+                "alert {:<16} {:<8} value {:.2} threshold {}",
+                name,
+                state.as_str(),
+                value,
+                threshold
+            );
+        }
+    });
+
+    let mut sim = Simulation::new(seed)
+        .with_cache_warmup(Duration::from_secs(20 * 60), 0.9)
+        .with_spikes(0.01, Duration::from_secs(10 * 60), 8.0);
+    let mut spans = Vec::new();
+    let mut spiking_minutes = 0;
+    for _ in 0..24 * 60 {
+        let step = sim.step(Duration::from_secs(60), &mut telemetry);
+        step.publish(&stream);
+        spiking_minutes += u32::from(step.spiking);
+        spans.extend(step.spans);
+    }
+
+    let snapshot = telemetry.snapshot();
+    println!(
+        "\nsimulated {:?} ({} minutes spiking)",
+        sim.elapsed(),
+        spiking_minutes
+    );
+    println!("queries: {}", snapshot.counters["queries_total"]);
+    println!("errors:  {}", snapshot.counters["query_errors_total"]);
+    if let Some(query) = snapshot.operation_stats.get("query") {
+        println!(
+            "query latency p50 {}us p99 {}us",
+            query.median_us(),
+            query.p99_us()
+        );
+    }
+
+    let otlp = OtelExporter::new()
+        .with_service_name("embeddenator-sim")
+        .export_spans(&spans);
+    println!("{} spans, {} bytes of OTLP JSON\n", spans.len(), otlp.len());
+
+    print!("{}", PrometheusExporter::new("sim").export(&snapshot));
+}
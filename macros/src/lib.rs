@@ -0,0 +1,184 @@
+//! # embeddenator-obs-macros
+//!
+//! Attribute macros for [embeddenator-obs](https://docs.rs/embeddenator-obs).
+//! Use them through the `macros` feature of that crate, which re-exports
+//! them; the expansion refers to `::embeddenator_obs`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::parse::Parser;
+use syn::{parse_macro_input, Expr, FnArg, Ident, ItemFn, LitStr, Pat, ReturnType};
+
+/// Options of `#[trace(...)]`.
+#[derive(Default)]
+struct TraceArgs {
+    name: Option<LitStr>,
+    telemetry: Option<Expr>,
+    skip: Vec<Ident>,
+    err: bool,
+}
+
+impl TraceArgs {
+    fn parse(attr: TokenStream) -> syn::Result<Self> {
+        let mut args = TraceArgs::default();
+        let parser = syn::meta::parser(|meta| {
+            if meta.path.is_ident("name") {
+                args.name = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("telemetry") {
+                args.telemetry = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("skip") {
+                meta.parse_nested_meta(|nested| {
+                    match nested.path.get_ident() {
+                        Some(ident) => args.skip.push(ident.clone()),
+                        None => return Err(nested.error("expected an argument name")),
+                    }
+                    Ok(())
+                })?;
+            } else if meta.path.is_ident("err") {
+                args.err = true;
+            } else {
+                return Err(meta.error("expected `name`, `telemetry`, `skip` or `err`"));
+            }
+            Ok(())
+        });
+        parser.parse(attr)?;
+        Ok(args)
+    }
+}
+
+/// Instrument a function with a span and call timing.
+///
+/// Each call runs inside an `OtelSpan` named after the function, child of
+/// the enclosing instrumented call on the same thread. Arguments become
+/// span attributes: strings, integers, floats and bools typed, anything
+/// else as its `Debug` output. The span ends when the function returns
+/// or unwinds and flows to the installed `TracerProvider`.
+///
+/// Options:
+///
+/// - `name = "..."`: span and operation name instead of the function name
+/// - `telemetry = expr`: record the call duration into an
+///   `Arc<Mutex<Telemetry>>` (or anything dereferencing to one) as
+///   operation timing
+/// - `skip(a, b)`: leave arguments out, e.g. large buffers or types
+///   without `Debug`
+/// - `err`: for functions returning `Result`, end the span with error
+///   status and the error's `Display` output on `Err`; not supported on
+///   `async fn`
+///
+/// ```rust,ignore
+/// use embeddenator_obs as obs;
+///
+/// #[obs::trace(telemetry = TELEMETRY, skip(payload), err)]
+/// fn ingest(shard: u32, payload: &[u8]) -> Result<(), IngestError> {
+///     // ...
+/// }
+/// ```
+///
+/// `async fn` bodies are traced too, but do not become the parent of
+/// instrumented calls made inside them, since they may resume on another
+/// thread.
+#[proc_macro_attribute]
+pub fn trace(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = match TraceArgs::parse(attr) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let function = parse_macro_input!(item as ItemFn);
+    match expand(args, function) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(args: TraceArgs, function: ItemFn) -> syn::Result<TokenStream2> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = function;
+    let is_async = sig.asyncness.is_some();
+    if args.err && is_async {
+        return Err(syn::Error::new_spanned(
+            sig.fn_token,
+            "`err` is not supported on async functions",
+        ));
+    }
+
+    let name = match &args.name {
+        Some(name) => name.value(),
+        None => sig.ident.to_string(),
+    };
+    let attributes = sig.inputs.iter().filter_map(|input| {
+        let FnArg::Typed(typed) = input else {
+            return None;
+        };
+        let Pat::Ident(pat) = typed.pat.as_ref() else {
+            return None;
+        };
+        if args.skip.contains(&pat.ident) {
+            return None;
+        }
+        let ident = &pat.ident;
+        let key = ident.to_string();
+        Some(quote! { (#key, ::embeddenator_obs::arg_attribute!(#ident)) })
+    });
+
+    let enter = if is_async {
+        quote!(detached)
+    } else {
+        quote!(enter)
+    };
+    let telemetry = args.telemetry.as_ref().map(|telemetry| {
+        quote! {
+            .with_telemetry(::std::sync::Arc::clone(&#telemetry))
+        }
+    });
+    let call = quote! {
+        #[allow(unused_mut)]
+        let mut __obs_call = ::embeddenator_obs::instrument::TracedCall::#enter(
+            #name,
+            ::std::vec![#(#attributes),*],
+        ) #telemetry;
+    };
+
+    let stmts = &block.stmts;
+    let body = if args.err {
+        // The closure keeps early `return`s and `?` inside the body
+        // observable as the call's result
+        let annotation = match &sig.output {
+            ReturnType::Type(arrow, ty) if closure_can_name(ty) => quote!(#arrow #ty),
+            _ => quote!(),
+        };
+        quote! {
+            #call
+            #[allow(clippy::redundant_closure_call)]
+            let __obs_result = (|| #annotation { #(#stmts)* })();
+            if let ::std::result::Result::Err(error) = &__obs_result {
+                __obs_call.fail(error);
+            }
+            __obs_result
+        }
+    } else {
+        quote! {
+            #call
+            #(#stmts)*
+        }
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            #body
+        }
+    })
+}
+
+/// Whether `ty` can be written as a closure's return type: no `impl
+/// Trait`, references or lifetimes.
+fn closure_can_name(ty: &syn::Type) -> bool {
+    let tokens = ty.to_token_stream().to_string();
+    !(tokens.contains("impl") || tokens.contains('&') || tokens.contains('\''))
+}
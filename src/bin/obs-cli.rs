@@ -0,0 +1,535 @@
+//! Query live telemetry of a running process.
+//!
+//! Talks to a [`QueryServer`] socket or a [`HealthServer`] with a query
+//! handler, and formats the answers for a terminal:
+//!
+//! ```text
+//! obs-cli [--socket <path> | --http <host:port>] <command>
+//!
+//!   top [-n <count>]                 operations with the slowest p99
+//!   watch <metric> [-i <seconds>]    print a metric until interrupted
+//!   snapshot [--json]                telemetry summary, or the raw snapshot
+//!   health                           readiness and its checks
+//!   spans                            recently ended spans
+//!   reload                           reload the config file, list changes
+//!
+//! obs-cli history <db> <name> [--last <duration>]
+//! obs-cli sql <db> <query>
+//! ```
+//!
+//! Without `--socket` or `--http`, the socket path is read from
+//! `EMBEDDENATOR_OBS_SOCKET`. `history` and `sql` read a
+//! [`SqliteStore`] database instead of a running process, and need the
+//! `sqlite-store` feature: `history` lists an operation's statistics (or
+//! a counter's or gauge's values) per stored snapshot over the trailing
+//! window (`90s`, `15m`, `6h`, `7d`; default `1h`).
+//!
+//!
+//! [`QueryServer`]: embeddenator_obs::query_socket::QueryServer
+//! [`HealthServer`]: embeddenator_obs::health::HealthServer
+//! [`SqliteStore`]: embeddenator_obs::sqlite_store::SqliteStore
+
+use serde_json::Value;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "usage: obs-cli [--socket <path> | --http <host:port>] <command>
+
+commands:
+  top [-n <count>]                 operations with the slowest p99
+  watch <metric> [-i <seconds>]    print a metric until interrupted
+  snapshot [--json]                telemetry summary, or the raw snapshot
+  health                           readiness and its checks
+  spans                            recently ended spans
+  reload                           reload the config file, list changes
+
+local history (feature sqlite-store):
+  obs-cli history <db> <name> [--last <duration>]
+  obs-cli sql <db> <query>";
+
+/// Where the queried process listens.
+enum Endpoint {
+    #[cfg(unix)]
+    Socket(String),
+    Http(String),
+}
+
+impl Endpoint {
+    /// Send one command and return its JSON answer.
+    fn query(&self, command: &str) -> io::Result<String> {
+        let response = match self {
+            #[cfg(unix)]
+            Endpoint::Socket(path) => {
+                use std::io::{BufRead, BufReader};
+                let mut stream = std::os::unix::net::UnixStream::connect(path)?;
+                stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+                writeln!(stream, "{}", command)?;
+                let mut line = String::new();
+                BufReader::new(stream).read_line(&mut line)?;
+                line
+            }
+            Endpoint::Http(addr) => {
+                let mut stream = TcpStream::connect(addr)?;
+                stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+                write!(
+                    stream,
+                    "GET /query/{} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+                    command.replace(' ', "/"),
+                    addr
+                )?;
+                let mut raw = String::new();
+                stream.read_to_string(&mut raw)?;
+                let (head, body) = raw.split_once("\r\n\r\n").unwrap_or((&raw, ""));
+                if !head.starts_with("HTTP/1.1 200") {
+                    let status = head.lines().next().unwrap_or("no response");
+                    return Err(io::Error::other(format!("{}: {}", addr, status)));
+                }
+                body.to_string()
+            }
+        };
+        Ok(response.trim_end().to_string())
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.starts_with("usage") => {
+            eprintln!("{}", e);
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    match args {
+        [command, db, name] if command == "history" => {
+            return store::history(db, name, Duration::from_secs(3600))
+        }
+        [command, db, name, flag, last] if command == "history" && flag == "--last" => {
+            return store::history(db, name, parse_window(last).ok_or(USAGE)?)
+        }
+        [command, db, sql] if command == "sql" => return store::sql(db, sql),
+        _ => {}
+    }
+    let (endpoint, args) = endpoint(args)?;
+    let (command, options) = args.split_first().ok_or(USAGE)?;
+    match (command.as_str(), options) {
+        ("top", []) => print_top(&endpoint, 10),
+        ("top", [flag, n]) if flag == "-n" => {
+            print_top(&endpoint, n.parse().map_err(|_| USAGE.to_string())?)
+        }
+        ("watch", [metric]) => watch(&endpoint, metric, Duration::from_secs(1)),
+        ("watch", [metric, flag, secs]) if flag == "-i" => {
+            let secs: f64 = secs.parse().map_err(|_| USAGE.to_string())?;
+            let interval = Duration::try_from_secs_f64(secs).map_err(|_| USAGE.to_string())?;
+            watch(&endpoint, metric, interval)
+        }
+        ("snapshot", []) => {
+            let answer = query(&endpoint, "summary")?;
+            print!("{}", field_str(&answer, "summary")?);
+            Ok(())
+        }
+        ("snapshot", [flag]) if flag == "--json" => {
+            println!("{}", query(&endpoint, "snapshot")?.source);
+            Ok(())
+        }
+        ("health", []) => print_health(&endpoint),
+        ("spans", []) => print_spans(&endpoint),
+        ("reload", []) => print_reload(&endpoint),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn endpoint(args: &[String]) -> Result<(Endpoint, &[String]), String> {
+    match args {
+        #[cfg(unix)]
+        [flag, path, rest @ ..] if flag == "--socket" => Ok((Endpoint::Socket(path.clone()), rest)),
+        [flag, addr, rest @ ..] if flag == "--http" => Ok((Endpoint::Http(addr.clone()), rest)),
+        #[cfg(unix)]
+        _ => match std::env::var("EMBEDDENATOR_OBS_SOCKET") {
+            Ok(path) => Ok((Endpoint::Socket(path), args)),
+            Err(_) => Err(USAGE.to_string()),
+        },
+        #[cfg(not(unix))]
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+/// A parsed answer, with the line it came from.
+struct Answer {
+    source: String,
+    json: Value,
+}
+
+fn query(endpoint: &Endpoint, command: &str) -> Result<Answer, String> {
+    let source = endpoint.query(command).map_err(|e| e.to_string())?;
+    parse_answer(source)
+}
+
+/// Parse an answer line; an `{"error": ...}` answer becomes `Err`.
+fn parse_answer(source: String) -> Result<Answer, String> {
+    let json: Value =
+        serde_json::from_str(&source).map_err(|e| format!("invalid answer ({}): {}", e, source))?;
+    if let Some(error) = json.get("error").and_then(Value::as_str) {
+        return Err(error.to_string());
+    }
+    Ok(Answer { source, json })
+}
+
+fn field_str<'a>(answer: &'a Answer, key: &str) -> Result<&'a str, String> {
+    answer
+        .json
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("unexpected answer: {}", answer.source))
+}
+
+fn print_top(endpoint: &Endpoint, n: usize) -> Result<(), String> {
+    let answer = query(endpoint, &format!("top {}", n))?;
+    let operations = answer
+        .json
+        .get("operations")
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("unexpected answer: {}", answer.source))?;
+
+    let columns = [
+        "name", "count", "mean_us", "p50_us", "p95_us", "p99_us", "max_us",
+    ];
+    let rows: Vec<Vec<String>> = operations
+        .iter()
+        .map(|op| {
+            columns
+                .iter()
+                .map(|column| match op.get(column) {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Number(v)) => format!("{:.0}", v.as_f64().unwrap_or(0.0)),
+                    _ => String::new(),
+                })
+                .collect()
+        })
+        .collect();
+    print!("{}", format_table(&columns, &rows));
+    Ok(())
+}
+
+fn watch(endpoint: &Endpoint, metric: &str, interval: Duration) -> Result<(), String> {
+    loop {
+        let answer = query(endpoint, &format!("metric {}", metric))?;
+        let values = match answer.json.get("values") {
+            Some(Value::Object(values)) => values,
+            _ => return Err(format!("unexpected answer: {}", answer.source)),
+        };
+        let values: Vec<String> = values
+            .iter()
+            .map(|(key, value)| match value.as_f64() {
+                Some(v) => format!("{}={}", key, v),
+                None => format!("{}={}", key, value),
+            })
+            .collect();
+        println!("{}  {}", clock_time(SystemTime::now()), values.join("  "));
+        std::thread::sleep(interval);
+    }
+}
+
+fn print_health(endpoint: &Endpoint) -> Result<(), String> {
+    let answer = query(endpoint, "health")?;
+    println!(
+        "status: {}",
+        answer
+            .json
+            .get("status")
+            .and_then(Value::as_str)
+            .unwrap_or("?")
+    );
+    for check in answer
+        .json
+        .get("checks")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+    {
+        let text = |key| check.get(key).and_then(Value::as_str).unwrap_or("");
+        let details = text("details");
+        if details.is_empty() {
+            println!("  {}: {}", text("name"), text("status"));
+        } else {
+            println!("  {}: {} ({})", text("name"), text("status"), details);
+        }
+    }
+    Ok(())
+}
+
+fn print_spans(endpoint: &Endpoint) -> Result<(), String> {
+    let answer = query(endpoint, "spans")?;
+    let spans = answer
+        .json
+        .get("spans")
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("unexpected answer: {}", answer.source))?;
+    let rows: Vec<Vec<String>> = spans
+        .iter()
+        .map(|span| {
+            let text = |key| {
+                span.get(key)
+                    .and_then(Value::as_str)
+                    .unwrap_or("")
+                    .to_string()
+            };
+            let duration_ns = span
+                .get("duration_ns")
+                .and_then(Value::as_f64)
+                .unwrap_or(0.0);
+            vec![
+                text("name"),
+                format!("{:.0}", duration_ns / 1000.0),
+                text("status"),
+                text("trace_id"),
+            ]
+        })
+        .collect();
+    print!(
+        "{}",
+        format_table(&["name", "duration_us", "status", "trace_id"], &rows)
+    );
+    Ok(())
+}
+
+fn print_reload(endpoint: &Endpoint) -> Result<(), String> {
+    let answer = query(endpoint, "reload")?;
+    let list = |key| {
+        answer
+            .json
+            .get(key)
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    };
+    let (applied, rejected) = (list("applied"), list("rejected"));
+    if applied.is_empty() && rejected.is_empty() {
+        println!("no changes");
+    }
+    for key in applied.iter().filter_map(Value::as_str) {
+        println!("applied   {}", key);
+    }
+    for change in rejected {
+        let text = |key| change.get(key).and_then(Value::as_str).unwrap_or("");
+        println!("rejected  {} ({})", text("key"), text("reason"));
+    }
+    Ok(())
+}
+
+/// `90s`, `15m`, `6h` or `7d`.
+fn parse_window(text: &str) -> Option<Duration> {
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let amount: u64 = text[..split].parse().ok()?;
+    let unit_secs = match &text[split..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        _ => return None,
+    };
+    amount.checked_mul(unit_secs).map(Duration::from_secs)
+}
+
+#[cfg(feature = "sqlite-store")]
+mod store {
+    use super::{clock_time, format_table};
+    use embeddenator_obs::sqlite_store::SqliteStore;
+    use std::time::Duration;
+
+    fn open(db: &str) -> Result<SqliteStore, String> {
+        SqliteStore::open(db).map_err(|e| format!("{}: {}", db, e))
+    }
+
+    /// Per-snapshot history of an operation, or else of a metric.
+    pub(super) fn history(db: &str, name: &str, last: Duration) -> Result<(), String> {
+        let store = open(db)?;
+        let operations = store
+            .operation_history(name, last)
+            .map_err(|e| e.to_string())?;
+        if !operations.is_empty() {
+            let rows: Vec<Vec<String>> = operations
+                .iter()
+                .map(|point| {
+                    vec![
+                        clock_time(point.timestamp),
+                        point.count.to_string(),
+                        format!("{:.0}", point.mean_us),
+                        point.p50_us.to_string(),
+                        point.p95_us.to_string(),
+                        point.p99_us.to_string(),
+                        point.max_us.to_string(),
+                    ]
+                })
+                .collect();
+            let columns = [
+                "time", "count", "mean_us", "p50_us", "p95_us", "p99_us", "max_us",
+            ];
+            print!("{}", format_table(&columns, &rows));
+            return Ok(());
+        }
+
+        let metrics = store
+            .metric_history(name, last)
+            .map_err(|e| e.to_string())?;
+        if metrics.is_empty() {
+            return Err(format!("no history for {} in {}", name, db));
+        }
+        let rows: Vec<Vec<String>> = metrics
+            .iter()
+            .map(|point| {
+                vec![
+                    clock_time(point.timestamp),
+                    point.labels.clone(),
+                    point.value.to_string(),
+                ]
+            })
+            .collect();
+        print!("{}", format_table(&["time", "labels", "value"], &rows));
+        Ok(())
+    }
+
+    /// Run a read query and print its rows.
+    pub(super) fn sql(db: &str, sql: &str) -> Result<(), String> {
+        let result = open(db)?.query(sql).map_err(|e| e.to_string())?;
+        print!("{}", result.format_table());
+        eprintln!("({} rows)", result.rows.len());
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "sqlite-store"))]
+mod store {
+    use std::time::Duration;
+
+    const DISABLED: &str = "obs-cli was built without the sqlite-store feature";
+
+    pub(super) fn history(_db: &str, _name: &str, _last: Duration) -> Result<(), String> {
+        Err(DISABLED.to_string())
+    }
+
+    pub(super) fn sql(_db: &str, _sql: &str) -> Result<(), String> {
+        Err(DISABLED.to_string())
+    }
+}
+
+/// Aligned text table with a header row.
+fn format_table(columns: &[&str], rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([columns[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut output = String::new();
+    let header: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+    for row in std::iter::once(&header).chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &w)| format!("{:<w$}", cell, w = w))
+            .collect();
+        output.push_str(cells.join("  ").trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+/// `HH:MM:SS` (UTC).
+fn clock_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_query_answers() {
+        let answer = parse_answer(
+            r#"{"operations":[{"name":"q\"1\u00e9\t","count":3}],"summary":"a\nb"}"#.to_string(),
+        )
+        .unwrap();
+        let op = &answer.json["operations"][0];
+        assert_eq!(op["name"].as_str(), Some("q\"1é\t"));
+        assert_eq!(op["count"].as_f64(), Some(3.0));
+        assert_eq!(field_str(&answer, "summary").unwrap(), "a\nb");
+        assert!(field_str(&answer, "missing").is_err());
+
+        assert_eq!(
+            parse_answer(r#"{"error":"unknown command: x"}"#.to_string()).err(),
+            Some("unknown command: x".to_string())
+        );
+        assert!(parse_answer(r#"{"a":1"#.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_format_table() {
+        let rows = vec![vec!["query".to_string(), "7".to_string()]];
+        assert_eq!(
+            format_table(&["name", "p99_us"], &rows),
+            "name   p99_us\nquery  7\n"
+        );
+        assert_eq!(
+            clock_time(UNIX_EPOCH + Duration::from_secs(3_723)),
+            "01:02:03"
+        );
+    }
+
+    #[test]
+    fn test_parse_window() {
+        assert_eq!(parse_window("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_window("6h"), Some(Duration::from_secs(6 * 3600)));
+        assert_eq!(parse_window("7d"), Some(Duration::from_secs(7 * 86_400)));
+        assert_eq!(parse_window("5"), None);
+        assert_eq!(parse_window("5w"), None);
+        assert_eq!(parse_window(&format!("{}d", u64::MAX)), None);
+    }
+
+    #[cfg(feature = "sqlite-store")]
+    #[test]
+    fn test_history_reads_store() {
+        use embeddenator_obs::sqlite_store::SqliteStore;
+        use embeddenator_obs::telemetry::Telemetry;
+
+        let path = std::env::temp_dir().join(format!("obs_cli_history_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut telemetry = Telemetry::default_config();
+        telemetry.record_operation("query", 120);
+        telemetry.set_gauge("depth", 2.0);
+        SqliteStore::open(&path)
+            .unwrap()
+            .insert_snapshot(&telemetry.snapshot())
+            .unwrap();
+
+        let db = path.to_str().unwrap();
+        assert!(store::history(db, "query", Duration::from_secs(60)).is_ok());
+        assert!(store::history(db, "depth", Duration::from_secs(60)).is_ok());
+        assert!(store::history(db, "missing", Duration::from_secs(60))
+            .unwrap_err()
+            .starts_with("no history for missing"));
+        assert!(store::sql(db, "SELECT count(*) FROM operations").is_ok());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -25,6 +25,16 @@
 //! - `opentelemetry`: Enable OpenTelemetry distributed tracing
 //! - `streaming`: Enable real-time metric streaming with callbacks
 //! - `advanced-stats`: Enable advanced statistical analysis (percentiles, std dev)
+//! - `alloc-tracking`: Enable the counting global allocator for per-operation allocation stats
+//! - `remote-write`: Enable Prometheus remote-write push export
+//! - `parquet`: Enable partitioned Parquet snapshot export
+//! - `sqlite-store`: Enable the embedded SQLite metrics store
+//! - `ws-streaming`: Enable the WebSocket live metrics server
+//! - `test-util`: Enable in-memory exporters for integration tests
+//! - `tui`: Enable the terminal live dashboard
+//! - `cli`: Build the `obs-cli` binary for querying a running process
+//! - `macros`: Enable the `#[trace]` function instrumentation attribute
+//! - `serde`: Enable `Serialize`/`Deserialize` for timing result types
 //! - `full`: Enable all features
 //!
 //! ## Quick Start
@@ -80,12 +90,16 @@ pub use obs::*;
 
 // Re-export commonly used types for convenience
 pub use obs::{
-    create_span, init_tracing, metrics, EventLevel, HiResMetrics, HiResTimer, HiResTimestamp,
-    MetricEvent, MetricStream, Metrics, MetricsSnapshot, OperationStats, OtelExporter, OtelSpan,
-    PrometheusExporter, SpanGuard, SpanKind, SpanStatus, Telemetry, TelemetryConfig,
-    TelemetrySnapshot, TestMetrics, ThresholdAlert, TimingStats,
+    create_span, init_tracing, metrics, quality, EventLevel, HiResMetrics, HiResTimer,
+    HiResTimestamp, MetricEvent, MetricStream, Metrics, MetricsSnapshot, OperationStats,
+    OtelExporter, OtelSpan, PrometheusExporter, QualityRecorder, RegressionReport, SpanGuard,
+    SpanKind, SpanStatus, Telemetry, TelemetryConfig, TelemetrySnapshot, TestMetrics,
+    ThresholdAlert, TimingStats,
 };
 
+#[cfg(feature = "macros")]
+pub use embeddenator_obs_macros::trace;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,419 @@
+//! Alert Rules from Configuration Files
+//!
+//! Loads [`ThresholdAlert`]s from a TOML rules file into a
+//! [`MetricStream`], optionally watching the file and swapping the rules
+//! in when it changes, so thresholds can be tuned without recompiling or
+//! restarting.
+//!
+//! # Format
+//!
+//! A subset of TOML: one `[[rule]]` table per alert with string, number
+//! and duration (`"250ms"`, `"90s"`, `"5m"`, `"1h"`, `"1d"`) values.
+//!
+//! ```toml
+//! # Sustained queue backlog
+//! [[rule]]
+//! metric = "queue_depth"      # substring of the metric name
+//! above = 100                 # or: below = 0.5
+//! for = "1m"                  # optional sustain window
+//! for_samples = 3             # optional consecutive samples
+//! repeat_interval = "1h"      # optional re-notification interval
+//! cooldown = "5m"             # optional quiet period after resolving
+//! severity = "critical"       # default: "warning"
+//! ```
+//!
+//! # Reloading
+//!
+//! Alerts whose metric, threshold and direction are unchanged keep their
+//! state across a reload, so a firing alert still resolves. A file that
+//! fails to parse is logged and the previous rules stay active.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::alert_config::AlertConfig;
+//!
+//! let _reloader = AlertConfig::watch("/etc/embeddenator/alerts.toml", &mut stream,
+//!     Duration::from_secs(5))?;
+//! ```
+
+use crate::obs::logging;
+use crate::obs::process::{spawn_periodic, CollectorHandle};
+use crate::obs::streaming::{MetricStream, ThresholdAlert};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Alert rules parsed from a rules file.
+#[derive(Debug, Clone, Default)]
+pub struct AlertConfig {
+    pub alerts: Vec<ThresholdAlert>,
+}
+
+impl AlertConfig {
+    /// Parse rules file contents.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut alerts = Vec::new();
+        let mut current: Option<RuleBuilder> = None;
+
+        for (index, raw) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == "[[rule]]" {
+                if let Some(rule) = current.take() {
+                    alerts.push(rule.build()?);
+                }
+                current = Some(RuleBuilder::new(line_no));
+                continue;
+            }
+            let Some(rule) = current.as_mut() else {
+                return Err(invalid(line_no, "expected [[rule]] before keys"));
+            };
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(invalid(line_no, "expected key = value"));
+            };
+            rule.set(key.trim(), value.trim())
+                .map_err(|msg| invalid(line_no, &msg))?;
+        }
+        if let Some(rule) = current {
+            alerts.push(rule.build()?);
+        }
+        Ok(Self { alerts })
+    }
+
+    /// Read and parse a rules file.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+
+    /// Replace the stream's alerts with these rules.
+    pub fn apply(&self, stream: &mut MetricStream) {
+        stream.replace_alerts(self.alerts.clone());
+    }
+
+    /// Load `path` into `stream` now, then check it every `interval` and
+    /// reload it when it changes, until the handle is stopped.
+    ///
+    /// A change is picked up once the file stays the same for one
+    /// interval, so a rules file being written is not read half-way;
+    /// renaming a complete file into place is safest. Fails if the initial
+    /// load fails; later errors are logged.
+    pub fn watch(
+        path: impl Into<PathBuf>,
+        stream: &mut MetricStream,
+        interval: Duration,
+    ) -> io::Result<CollectorHandle> {
+        let path = path.into();
+        let mut loaded = file_version(&path);
+        let mut seen = loaded;
+        Self::from_file(&path)?.apply(stream);
+
+        let alerts = stream.alerts_handle();
+        Ok(spawn_periodic("obs-alert-config", interval, move || {
+            let current = file_version(&path);
+            if current != seen {
+                seen = current;
+                return;
+            }
+            if current == loaded {
+                return;
+            }
+            loaded = current;
+            match Self::from_file(&path) {
+                Ok(config) => {
+                    logging::debug(&format!(
+                        "reloaded {} alert rules from {}",
+                        config.alerts.len(),
+                        path.display()
+                    ));
+                    alerts.replace(config.alerts);
+                }
+                Err(e) => logging::warn(&format!(
+                    "keeping previous alert rules, reload failed: {}",
+                    e
+                )),
+            }
+        }))
+    }
+}
+
+/// Modification time and size; size catches rewrites within the
+/// filesystem's timestamp granularity.
+pub(crate) fn file_version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+pub(crate) fn invalid(line: usize, msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line, msg),
+    )
+}
+
+/// Drop a `#` comment that is not inside a string.
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // An escaped character, `\"` included, never ends the string
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Fields of one `[[rule]]` table.
+pub(crate) struct RuleBuilder {
+    line: usize,
+    metric: Option<String>,
+    threshold: Option<(f64, bool)>,
+    for_duration: Option<Duration>,
+    for_samples: Option<u32>,
+    repeat_interval: Option<Duration>,
+    cooldown: Option<Duration>,
+    severity: Option<String>,
+}
+
+impl RuleBuilder {
+    pub(crate) fn new(line: usize) -> Self {
+        Self {
+            line,
+            metric: None,
+            threshold: None,
+            for_duration: None,
+            for_samples: None,
+            repeat_interval: None,
+            cooldown: None,
+            severity: None,
+        }
+    }
+
+    pub(crate) fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "metric" => self.metric = Some(parse_string(value)?),
+            "above" | "below" => {
+                if self.threshold.is_some() {
+                    return Err("only one of above/below per rule".to_string());
+                }
+                let threshold = value
+                    .parse()
+                    .map_err(|_| format!("invalid threshold {}", value))?;
+                self.threshold = Some((threshold, key == "above"));
+            }
+            "for" => self.for_duration = Some(parse_duration(&parse_string(value)?)?),
+            "for_samples" => {
+                self.for_samples = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid sample count {}", value))?,
+                )
+            }
+            "repeat_interval" => {
+                self.repeat_interval = Some(parse_duration(&parse_string(value)?)?)
+            }
+            "cooldown" => self.cooldown = Some(parse_duration(&parse_string(value)?)?),
+            "severity" => self.severity = Some(parse_string(value)?),
+            _ => return Err(format!("unknown key {}", key)),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn build(self) -> io::Result<ThresholdAlert> {
+        let metric = self
+            .metric
+            .ok_or_else(|| invalid(self.line, "rule is missing metric"))?;
+        let (threshold, above) = self
+            .threshold
+            .ok_or_else(|| invalid(self.line, "rule is missing above or below"))?;
+        let mut alert = ThresholdAlert::new(metric, threshold, above);
+        if let Some(duration) = self.for_duration {
+            alert = alert.for_duration(duration);
+        }
+        if let Some(samples) = self.for_samples {
+            alert = alert.for_samples(samples);
+        }
+        if let Some(interval) = self.repeat_interval {
+            alert = alert.repeat_interval(interval);
+        }
+        if let Some(cooldown) = self.cooldown {
+            alert = alert.cooldown(cooldown);
+        }
+        if let Some(severity) = self.severity {
+            alert = alert.severity(severity);
+        }
+        Ok(alert)
+    }
+}
+
+/// `"..."` with `\\`, `\"`, `\n` and `\t` escapes.
+pub(crate) fn parse_string(value: &str) -> Result<String, String> {
+    let invalid = || format!("expected a quoted string, got {}", value);
+    let mut chars = value.strip_prefix('"').ok_or_else(invalid)?.chars();
+    let mut parsed = String::new();
+    loop {
+        match chars.next().ok_or_else(invalid)? {
+            '"' if chars.as_str().is_empty() => return Ok(parsed),
+            '"' => return Err(invalid()),
+            '\\' => match chars.next() {
+                Some('\\') => parsed.push('\\'),
+                Some('"') => parsed.push('"'),
+                Some('n') => parsed.push('\n'),
+                Some('t') => parsed.push('\t'),
+                Some(c) => return Err(format!("unsupported escape \\{} in {}", c, value)),
+                None => return Err(invalid()),
+            },
+            c => parsed.push(c),
+        }
+    }
+}
+
+/// `250ms`, `90s`, `5m`, `1h` or `1d`.
+pub(crate) fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration {:?}", value);
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let amount: u64 = value[..split].parse().map_err(|_| invalid())?;
+    let unit_secs = match &value[split..] {
+        "ms" => return Ok(Duration::from_millis(amount)),
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        _ => return Err(invalid()),
+    };
+    amount
+        .checked_mul(unit_secs)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration {:?} too large", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obs::streaming::AlertState;
+
+    const RULES: &str = r#"
+# Sustained queue backlog
+[[rule]]
+metric = "queue_depth"
+above = 100
+for = "1m"
+repeat_interval = "1h"
+severity = "critical"   # pages
+
+[[rule]]
+metric = "cache_hit_ratio"
+below = 0.5
+for_samples = 3
+cooldown = "250ms"
+"#;
+
+    #[test]
+    fn test_parse_rules() {
+        let config = AlertConfig::parse(RULES).unwrap();
+        assert_eq!(config.alerts.len(), 2);
+
+        let queue = &config.alerts[0];
+        assert_eq!(queue.metric_pattern, "queue_depth");
+        assert_eq!((queue.threshold, queue.above), (100.0, true));
+        assert_eq!(queue.for_duration, Some(Duration::from_secs(60)));
+        assert_eq!(queue.repeat_interval, Some(Duration::from_secs(3600)));
+        assert_eq!(queue.severity, "critical");
+
+        let cache = &config.alerts[1];
+        assert_eq!((cache.threshold, cache.above), (0.5, false));
+        assert_eq!(cache.for_samples, 3);
+        assert_eq!(cache.cooldown, Some(Duration::from_millis(250)));
+        assert_eq!(cache.severity, "warning");
+
+        let err =
+            AlertConfig::parse("[[rule]]\nmetric = \"cpu\"\nabove = 1\nbelow = 0\n").unwrap_err();
+        assert!(err.to_string().starts_with("line 4:"));
+        assert!(AlertConfig::parse("[[rule]]\nabove = 1\n").is_err());
+        assert!(
+            AlertConfig::parse("[[rule]]\nmetric = \"cpu\"\nabove = 1\nfor = \"5x\"\n").is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_edge_cases() {
+        assert_eq!(
+            strip_comment(r##"severity = "a\"#b" # note"##),
+            r##"severity = "a\"#b" "##
+        );
+        assert_eq!(strip_comment(r#"path = "c:\\" # x"#), r#"path = "c:\\" "#);
+
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(2 * 86_400)));
+        assert!(parse_duration(&format!("{}d", u64::MAX / 1000)).is_err());
+        assert!(parse_duration("99999999999999999999s").is_err());
+
+        assert_eq!(parse_string(r#""a\\\"b""#), Ok("a\\\"b".to_string()));
+        assert_eq!(parse_string(r#""c:\\""#), Ok("c:\\".to_string()));
+        assert_eq!(parse_string(r#""x\ty\nz""#), Ok("x\ty\nz".to_string()));
+        assert_eq!(parse_string(r#""""#), Ok(String::new()));
+        assert!(parse_string(r#""a" "b""#).is_err());
+        assert!(parse_string(r#""a"b""#).is_err());
+        assert!(parse_string(r#""a\""#).is_err());
+        assert!(parse_string(r#""a"#).is_err());
+        assert!(parse_string("a").is_err());
+        assert_eq!(
+            parse_string(r#""\q""#),
+            Err(r#"unsupported escape \q in "\q""#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_watch_reloads_and_keeps_state() {
+        let path = std::env::temp_dir().join(format!(
+            "embeddenator_obs_alerts_{}.toml",
+            std::process::id()
+        ));
+        let replace = |contents: &str| {
+            let staged = path.with_extension("toml.tmp");
+            fs::write(&staged, contents).unwrap();
+            fs::rename(&staged, &path).unwrap();
+        };
+        replace("[[rule]]\nmetric = \"cpu\"\nabove = 80\n");
+
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        let handle = AlertConfig::watch(&path, &mut stream, Duration::from_millis(10)).unwrap();
+        stream.publish_gauge("cpu", 95.0);
+        assert_eq!(stream.alert_state("cpu"), AlertState::Firing);
+
+        // Unchanged rule moves to index 1 and keeps firing
+        replace(
+            "[[rule]]\nmetric = \"memory\"\nbelow = 10\n\n[[rule]]\nmetric = \"cpu\"\nabove = 80\n",
+        );
+        for _ in 0..200 {
+            if stream.threshold_alerts().len() == 2 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(stream.threshold_alerts()[0].metric_pattern, "memory");
+        assert_eq!(stream.alert_state("cpu"), AlertState::Firing);
+
+        // A broken file keeps the previous rules
+        replace("[[rule]]\nmetric = cpu\n");
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(stream.threshold_alerts().len(), 2);
+
+        handle.stop();
+        let _ = fs::remove_file(&path);
+    }
+}
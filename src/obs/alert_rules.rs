@@ -0,0 +1,452 @@
+//! Prometheus Alerting Rule Generator
+//!
+//! Renders a Prometheus rule file (YAML, routed by Alertmanager through its
+//! labels) from the same threshold alerts and service level objectives
+//! the process evaluates itself, so in-process and cluster-level alerting
+//! come from one source of truth.
+//!
+//! # Rules
+//!
+//! - Threshold alerts: one rule per [`ThresholdAlert`], matching every
+//!   exported gauge whose name contains the pattern (the same matching
+//!   [`MetricStream`] uses), with the alert's own `for_duration` or a
+//!   `for:` of 5 minutes by default
+//! - SLOs: multi-window burn-rate rules: a critical fast burn (14.4x the
+//!   error budget over 1h and 5m, `for: 2m`) and a warning slow burn (6x
+//!   over 6h and 30m, `for: 15m`)
+//!
+//! Metric names follow the [`PrometheusExporter`] with the same prefix.
+//! Latency SLO thresholds must be one of the operation's histogram bucket
+//! bounds, since the rule reads the `le` bucket directly.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::alert_rules::{AlertRuleGenerator, Slo};
+//!
+//! let yaml = AlertRuleGenerator::new("embeddenator")
+//!     .with_label("team", "search")
+//!     .with_thresholds_from(&stream)
+//!     .with_slo(Slo::latency("query-latency", "retrieval_query", Duration::from_millis(10), 0.99))
+//!     .with_slo(Slo::error_ratio("availability", "query_errors", "queries", 0.999))
+//!     .generate();
+//! std::fs::write("embeddenator.rules.yml", yaml)?;
+//! ```
+//!
+//! [`PrometheusExporter`]: crate::obs::prometheus::PrometheusExporter
+
+use crate::obs::prometheus::sanitize_name;
+use crate::obs::streaming::{MetricStream, ThresholdAlert};
+use crate::obs::telemetry::escape_json;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// What a service level objective measures.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SloIndicator {
+    /// Share of operation timings slower than `threshold_us`
+    Latency {
+        operation: String,
+        threshold_us: u64,
+    },
+    /// Ratio of two counters, `errors / total`
+    ErrorRatio { errors: String, total: String },
+}
+
+/// Service level objective: `objective` of events must be good.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slo {
+    pub name: String,
+    /// Target good ratio, e.g. `0.999`
+    pub objective: f64,
+    pub indicator: SloIndicator,
+}
+
+impl Slo {
+    /// `objective` of `operation` timings complete within `threshold`.
+    pub fn latency(
+        name: impl Into<String>,
+        operation: impl Into<String>,
+        threshold: Duration,
+        objective: f64,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            objective,
+            indicator: SloIndicator::Latency {
+                operation: operation.into(),
+                threshold_us: threshold.as_micros() as u64,
+            },
+        }
+    }
+
+    /// At most `1 - objective` of `total` counter increments are `errors`.
+    pub fn error_ratio(
+        name: impl Into<String>,
+        errors: impl Into<String>,
+        total: impl Into<String>,
+        objective: f64,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            objective,
+            indicator: SloIndicator::ErrorRatio {
+                errors: errors.into(),
+                total: total.into(),
+            },
+        }
+    }
+
+    /// Allowed bad ratio.
+    pub fn error_budget(&self) -> f64 {
+        (1.0 - self.objective).max(0.0)
+    }
+}
+
+/// Burn-rate windows: (factor, long window, short window, for, severity).
+const BURN_RATES: [(f64, &str, &str, &str, &str); 2] = [
+    (14.4, "1h", "5m", "2m", "critical"),
+    (6.0, "6h", "30m", "15m", "warning"),
+];
+
+struct Rule {
+    alert: String,
+    expr: String,
+    for_duration: String,
+    labels: Vec<(String, String)>,
+    summary: String,
+    description: String,
+}
+
+/// Prometheus alerting rule file generator.
+#[derive(Debug, Clone)]
+pub struct AlertRuleGenerator {
+    group: String,
+    prefix: String,
+    threshold_for: Duration,
+    labels: Vec<(String, String)>,
+    thresholds: Vec<ThresholdAlert>,
+    slos: Vec<Slo>,
+}
+
+impl AlertRuleGenerator {
+    /// Rule group `group` for metrics exported with the `embeddenator`
+    /// prefix.
+    pub fn new(group: impl Into<String>) -> Self {
+        Self {
+            group: group.into(),
+            prefix: "embeddenator".to_string(),
+            threshold_for: Duration::from_secs(300),
+            labels: Vec::new(),
+            thresholds: Vec::new(),
+            slos: Vec::new(),
+        }
+    }
+
+    /// Prefix passed to [`PrometheusExporter::new`](crate::obs::prometheus::PrometheusExporter::new).
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// How long a threshold must stay exceeded before firing, for alerts
+    /// without their own `for_duration` (default 5m).
+    pub fn with_threshold_for(mut self, duration: Duration) -> Self {
+        self.threshold_for = duration;
+        self
+    }
+
+    /// Label added to every rule, e.g. for Alertmanager routing.
+    pub fn with_label(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_threshold(mut self, alert: ThresholdAlert) -> Self {
+        self.thresholds.push(alert);
+        self
+    }
+
+    /// Every threshold alert configured on `stream`.
+    pub fn with_thresholds_from(mut self, stream: &MetricStream) -> Self {
+        self.thresholds.extend(stream.threshold_alerts());
+        self
+    }
+
+    pub fn with_slo(mut self, slo: Slo) -> Self {
+        self.slos.push(slo);
+        self
+    }
+
+    fn labels(&self, severity: &str, extra: Option<(&str, &str)>) -> Vec<(String, String)> {
+        let mut labels = vec![("severity".to_string(), severity.to_string())];
+        if let Some((name, value)) = extra {
+            labels.push((name.to_string(), value.to_string()));
+        }
+        labels.extend(self.labels.iter().cloned());
+        labels
+    }
+
+    fn threshold_rule(&self, alert: &ThresholdAlert) -> Rule {
+        let pattern = sanitize_name(&alert.metric_pattern);
+        let for_duration = alert.for_duration.unwrap_or(self.threshold_for);
+        let (op, word) = if alert.above {
+            (">", "above")
+        } else {
+            ("<", "below")
+        };
+        Rule {
+            alert: format!(
+                "{}{}",
+                camel_case(&pattern),
+                if alert.above { "High" } else { "Low" }
+            ),
+            expr: format!(
+                r#"{{__name__=~"{}_.*{}.*"}} {} {}"#,
+                self.prefix,
+                pattern,
+                op,
+                format_number(alert.threshold)
+            ),
+            for_duration: format_duration(for_duration),
+            labels: self.labels(&alert.severity, None),
+            summary: format!(
+                "{{{{ $labels.__name__ }}}} {} {}",
+                word,
+                format_number(alert.threshold)
+            ),
+            description: format!(
+                "{{{{ $labels.__name__ }}}} is {{{{ $value }}}}, {} the threshold of {} for {}.",
+                word,
+                format_number(alert.threshold),
+                format_duration(for_duration)
+            ),
+        }
+    }
+
+    /// PromQL for the bad-event ratio of `slo` over `window`.
+    fn bad_ratio(&self, slo: &Slo, window: &str) -> String {
+        match &slo.indicator {
+            SloIndicator::Latency {
+                operation,
+                threshold_us,
+            } => {
+                let name = format!("{}_{}_duration_us", self.prefix, sanitize_name(operation));
+                format!(
+                    r#"(1 - sum(rate({0}_bucket{{le="{1}"}}[{2}])) / sum(rate({0}_count[{2}])))"#,
+                    name, threshold_us, window
+                )
+            }
+            SloIndicator::ErrorRatio { errors, total } => format!(
+                "(sum(rate({0}_{1}[{3}])) / sum(rate({0}_{2}[{3}])))",
+                self.prefix,
+                sanitize_name(errors),
+                sanitize_name(total),
+                window
+            ),
+        }
+    }
+
+    fn slo_rules(&self, slo: &Slo) -> Vec<Rule> {
+        let budget = format_number(slo.error_budget());
+        BURN_RATES
+            .iter()
+            .map(|&(factor, long, short, for_duration, severity)| Rule {
+                alert: format!(
+                    "{}ErrorBudget{}Burn",
+                    camel_case(&sanitize_name(&slo.name)),
+                    if severity == "critical" {
+                        "Fast"
+                    } else {
+                        "Slow"
+                    }
+                ),
+                expr: format!(
+                    "{} > ({} * {}) and {} > ({} * {})",
+                    self.bad_ratio(slo, long),
+                    format_number(factor),
+                    budget,
+                    self.bad_ratio(slo, short),
+                    format_number(factor),
+                    budget
+                ),
+                for_duration: for_duration.to_string(),
+                labels: self.labels(severity, Some(("slo", &slo.name))),
+                summary: format!(
+                    "SLO {} burning error budget {}x too fast",
+                    slo.name,
+                    format_number(factor)
+                ),
+                description: format!(
+                    "SLO {} ({}% objective) has burned its error budget at over {}x the sustainable rate for the last {} and {}.",
+                    slo.name,
+                    format_number(slo.objective * 100.0),
+                    format_number(factor),
+                    long,
+                    short
+                ),
+            })
+            .collect()
+    }
+
+    /// Render the rule file.
+    pub fn generate(&self) -> String {
+        let rules: Vec<Rule> = self
+            .thresholds
+            .iter()
+            .map(|alert| self.threshold_rule(alert))
+            .chain(self.slos.iter().flat_map(|slo| self.slo_rules(slo)))
+            .collect();
+
+        let mut yaml = String::new();
+        writeln!(yaml, "groups:").unwrap();
+        writeln!(yaml, "  - name: {}", yaml_quote(&self.group)).unwrap();
+        if rules.is_empty() {
+            writeln!(yaml, "    rules: []").unwrap();
+            return yaml;
+        }
+        writeln!(yaml, "    rules:").unwrap();
+        for rule in rules {
+            writeln!(yaml, "      - alert: {}", yaml_quote(&rule.alert)).unwrap();
+            writeln!(yaml, "        expr: {}", yaml_quote(&rule.expr)).unwrap();
+            writeln!(yaml, "        for: {}", rule.for_duration).unwrap();
+            writeln!(yaml, "        labels:").unwrap();
+            for (name, value) in &rule.labels {
+                writeln!(
+                    yaml,
+                    "          {}: {}",
+                    sanitize_name(name),
+                    yaml_quote(value)
+                )
+                .unwrap();
+            }
+            writeln!(yaml, "        annotations:").unwrap();
+            writeln!(yaml, "          summary: {}", yaml_quote(&rule.summary)).unwrap();
+            writeln!(
+                yaml,
+                "          description: {}",
+                yaml_quote(&rule.description)
+            )
+            .unwrap();
+        }
+        yaml
+    }
+}
+
+/// Double-quoted YAML scalar; JSON string escapes are valid YAML ones.
+fn yaml_quote(s: &str) -> String {
+    format!("\"{}\"", escape_json(s))
+}
+
+/// `queue_depth` -> `QueueDepth`.
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Shortest decimal form without float noise (`0.0010000000000000009` -> `0.001`).
+fn format_number(value: f64) -> String {
+    let fixed = format!("{:.9}", value);
+    fixed
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Prometheus duration string, e.g. `5m`, `90s`, `500ms`.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes) = (secs / 3600, secs / 60);
+    if duration.subsec_millis() != 0 || secs == 0 {
+        format!("{}ms", duration.as_millis())
+    } else if hours * 3600 == secs {
+        format!("{}h", hours)
+    } else if minutes * 60 == secs {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_rules_from_stream() {
+        let mut stream = MetricStream::new();
+        stream.add_threshold_alert("queue_depth", 100.0, true);
+        stream.add_alert(
+            ThresholdAlert::below("free-ratio", 0.05).for_duration(Duration::from_secs(600)),
+        );
+
+        let yaml = AlertRuleGenerator::new("search")
+            .with_prefix("test")
+            .with_label("team", "retrieval")
+            .with_threshold_for(Duration::from_secs(90))
+            .with_thresholds_from(&stream)
+            .generate();
+
+        assert!(yaml.starts_with("groups:\n  - name: \"search\"\n    rules:\n"));
+        assert!(yaml.contains(
+            "      - alert: \"QueueDepthHigh\"\n        expr: \"{__name__=~\\\"test_.*queue_depth.*\\\"} > 100\"\n        for: 90s\n        labels:\n          severity: \"warning\"\n          team: \"retrieval\"\n"
+        ));
+        assert!(yaml.contains("alert: \"FreeRatioLow\""));
+        assert!(
+            yaml.contains("\"{__name__=~\\\"test_.*free_ratio.*\\\"} < 0.05\"\n        for: 10m\n")
+        );
+    }
+
+    #[test]
+    fn test_slo_burn_rate_rules() {
+        let yaml = AlertRuleGenerator::new("search")
+            .with_slo(Slo::latency(
+                "query-latency",
+                "retrieval_query",
+                Duration::from_millis(10),
+                0.99,
+            ))
+            .with_slo(Slo::error_ratio(
+                "availability",
+                "query_errors",
+                "queries",
+                0.999,
+            ))
+            .generate();
+
+        assert_eq!(yaml.matches("- alert:").count(), 4);
+        assert!(yaml.contains("alert: \"QueryLatencyErrorBudgetFastBurn\""));
+        assert!(yaml.contains("alert: \"AvailabilityErrorBudgetSlowBurn\""));
+        assert!(yaml.contains(
+            "(1 - sum(rate(embeddenator_retrieval_query_duration_us_bucket{le=\\\"10000\\\"}[1h])) / sum(rate(embeddenator_retrieval_query_duration_us_count[1h]))) > (14.4 * 0.01)"
+        ));
+        assert!(yaml.contains(
+            "(sum(rate(embeddenator_query_errors[30m])) / sum(rate(embeddenator_queries[30m]))) > (6 * 0.001)"
+        ));
+        assert!(yaml.contains("        for: 2m\n        labels:\n          severity: \"critical\"\n          slo: \"availability\"\n"));
+        assert!(yaml.contains("(99.9% objective)"));
+    }
+
+    #[test]
+    fn test_empty_group_and_formatting() {
+        assert_eq!(
+            AlertRuleGenerator::new("none").generate(),
+            "groups:\n  - name: \"none\"\n    rules: []\n"
+        );
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1500ms");
+        assert_eq!(format_number(1.0 - 0.999), "0.001");
+        assert_eq!(
+            yaml_quote("a \"b\"\\\n\tc\r\u{7}\u{7f}"),
+            r#""a \"b\"\\\n\tc\r\u0007\u007f""#
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Allocation Tracking
+//!
+//! A counting [`GlobalAlloc`] wrapper that lets `TestMetrics` report
+//! allocations and bytes allocated per timed operation, so allocation
+//! regressions in hot paths show up alongside latency.
+//!
+//! # Usage
+//!
+//! Install the allocator once in the binary (or test/bench harness):
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::alloc_tracking::CountingAllocator;
+//!
+//! #[global_allocator]
+//! static GLOBAL: CountingAllocator = CountingAllocator::system();
+//! ```
+//!
+//! `TestMetrics::start_timing`/`stop_timing` then record one allocation
+//! sample per measurement automatically.
+//!
+//! # Implementation Notes
+//!
+//! Counters are kept both process-wide (atomics) and per-thread (const
+//! thread-locals, which never allocate), so per-operation deltas are not
+//! polluted by other threads.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static BYTES_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static BYTES_DEALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static THREAD_ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static THREAD_DEALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static THREAD_BYTES_ALLOCATED: Cell<u64> = const { Cell::new(0) };
+    static THREAD_BYTES_DEALLOCATED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Counting wrapper around another global allocator.
+pub struct CountingAllocator<A = System> {
+    inner: A,
+}
+
+impl CountingAllocator<System> {
+    /// Wrap the system allocator.
+    pub const fn system() -> Self {
+        Self { inner: System }
+    }
+}
+
+impl<A> CountingAllocator<A> {
+    /// Wrap an arbitrary allocator.
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            count_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            count_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        count_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            count_dealloc(layout.size());
+            count_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+#[inline]
+fn count_alloc(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES_ALLOCATED.fetch_add(size as u64, Ordering::Relaxed);
+    // try_with: thread-locals may already be torn down during thread exit
+    let _ = THREAD_ALLOCATIONS.try_with(|c| c.set(c.get() + 1));
+    let _ = THREAD_BYTES_ALLOCATED.try_with(|c| c.set(c.get() + size as u64));
+}
+
+#[inline]
+fn count_dealloc(size: usize) {
+    DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES_DEALLOCATED.fetch_add(size as u64, Ordering::Relaxed);
+    let _ = THREAD_DEALLOCATIONS.try_with(|c| c.set(c.get() + 1));
+    let _ = THREAD_BYTES_DEALLOCATED.try_with(|c| c.set(c.get() + size as u64));
+}
+
+/// Allocation counters at a point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    pub allocations: u64,
+    pub deallocations: u64,
+    pub bytes_allocated: u64,
+    pub bytes_deallocated: u64,
+}
+
+impl AllocStats {
+    /// Bytes currently live (allocated minus freed).
+    pub fn live_bytes(&self) -> u64 {
+        self.bytes_allocated.saturating_sub(self.bytes_deallocated)
+    }
+}
+
+impl std::ops::Sub for AllocStats {
+    type Output = AllocStats;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        AllocStats {
+            allocations: self.allocations.saturating_sub(rhs.allocations),
+            deallocations: self.deallocations.saturating_sub(rhs.deallocations),
+            bytes_allocated: self.bytes_allocated.saturating_sub(rhs.bytes_allocated),
+            bytes_deallocated: self.bytes_deallocated.saturating_sub(rhs.bytes_deallocated),
+        }
+    }
+}
+
+/// Process-wide allocation counters.
+pub fn alloc_stats() -> AllocStats {
+    AllocStats {
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+        bytes_allocated: BYTES_ALLOCATED.load(Ordering::Relaxed),
+        bytes_deallocated: BYTES_DEALLOCATED.load(Ordering::Relaxed),
+    }
+}
+
+/// Allocation counters for the current thread only.
+pub fn thread_alloc_stats() -> AllocStats {
+    AllocStats {
+        allocations: THREAD_ALLOCATIONS.with(Cell::get),
+        deallocations: THREAD_DEALLOCATIONS.with(Cell::get),
+        bytes_allocated: THREAD_BYTES_ALLOCATED.with(Cell::get),
+        bytes_deallocated: THREAD_BYTES_DEALLOCATED.with(Cell::get),
+    }
+}
+
+/// Whether a `CountingAllocator` is installed as the global allocator.
+///
+/// Detected by observing at least one counted allocation; any Rust program
+/// allocates during startup, so this is reliable after `main` begins.
+pub fn is_installed() -> bool {
+    ALLOCATIONS.load(Ordering::Relaxed) > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator::system();
+
+    #[test]
+    fn test_counts_allocations_on_current_thread() {
+        assert!(is_installed());
+
+        let before = thread_alloc_stats();
+        let v: Vec<u8> = Vec::with_capacity(4096);
+        std::hint::black_box(&v);
+        drop(v);
+        let delta = thread_alloc_stats() - before;
+
+        assert_eq!(delta.allocations, 1);
+        assert_eq!(delta.bytes_allocated, 4096);
+        assert_eq!(delta.deallocations, 1);
+        assert_eq!(delta.live_bytes(), 0);
+    }
+
+    #[test]
+    fn test_test_metrics_records_allocations() {
+        let mut metrics = crate::TestMetrics::new("alloc");
+        metrics.time_operation(|| {
+            let v = vec![0u8; 1024];
+            std::hint::black_box(v);
+        });
+
+        assert_eq!(metrics.alloc_counts, vec![1]);
+        assert_eq!(metrics.alloc_bytes, vec![1024]);
+        assert!(metrics.summary().contains("Allocations"));
+    }
+}
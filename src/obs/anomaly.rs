@@ -0,0 +1,223 @@
+//! Anomaly Detection on Streamed Metrics
+//!
+//! Flags values that deviate from a metric's recent behavior, catching
+//! regressions in metrics without static thresholds. Each metric keeps an
+//! exponentially weighted moving average (EWMA) and variance; a value more
+//! than `sigma` standard deviations from the average yields
+//! [`MetricEvent::Anomaly`] with its z-score.
+//!
+//! Gauges and timings are tracked; counters are cumulative and skipped.
+//! No anomalies are reported during the warm-up samples of each metric,
+//! and anomalous values still update the average, so a lasting level
+//! shift stops alerting once it becomes the norm.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::anomaly::AnomalyDetector;
+//!
+//! let detector = AnomalyDetector::new().with_sigma(4.0).watch("query");
+//! detector.attach(&mut stream);
+//!
+//! stream.subscribe(|event| {
+//!     if let MetricEvent::Anomaly(name, value, mean, z) = event {
+//!         logging::warn(&format!("{} = {} (mean {:.1}, z {:.1})", name, value, mean, z));
+//!     }
+//! });
+//! ```
+
+use crate::obs::streaming::{MetricEvent, MetricStream, SubscriptionId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Moving statistics of one metric.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EwmaStats {
+    pub mean: f64,
+    pub variance: f64,
+    pub samples: u64,
+}
+
+impl EwmaStats {
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+/// EWMA / z-score anomaly detector; clones share the same statistics.
+#[derive(Debug, Clone)]
+pub struct AnomalyDetector {
+    stats: Arc<Mutex<HashMap<String, EwmaStats>>>,
+    alpha: f64,
+    sigma: f64,
+    warmup: u64,
+    patterns: Vec<String>,
+}
+
+impl Default for AnomalyDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnomalyDetector {
+    /// Detector with smoothing 0.1, a 3 sigma band and 30 warm-up samples.
+    pub fn new() -> Self {
+        Self {
+            stats: Arc::new(Mutex::new(HashMap::new())),
+            alpha: 0.1,
+            sigma: 3.0,
+            warmup: 30,
+            patterns: Vec::new(),
+        }
+    }
+
+    /// EWMA smoothing factor in (0, 1]; larger adapts faster.
+    pub fn with_alpha(mut self, alpha: f64) -> Self {
+        self.alpha = alpha.clamp(f64::EPSILON, 1.0);
+        self
+    }
+
+    /// Standard deviations from the mean that count as anomalous.
+    pub fn with_sigma(mut self, sigma: f64) -> Self {
+        self.sigma = sigma;
+        self
+    }
+
+    /// Samples per metric before anomalies are reported.
+    pub fn with_warmup(mut self, samples: u64) -> Self {
+        self.warmup = samples;
+        self
+    }
+
+    /// Only track metrics whose name contains `pattern` (default: all).
+    pub fn watch(mut self, pattern: impl Into<String>) -> Self {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    fn watches(&self, name: &str) -> bool {
+        self.patterns.is_empty() || self.patterns.iter().any(|p| name.contains(p.as_str()))
+    }
+
+    /// Feed one value; returns an anomaly event if it deviates beyond the
+    /// sigma band of the statistics before this value.
+    pub fn observe(&self, name: &str, value: f64) -> Option<MetricEvent> {
+        if !value.is_finite() || !self.watches(name) {
+            return None;
+        }
+        let mut stats = self.stats.lock().ok()?;
+        let Some(entry) = stats.get_mut(name) else {
+            stats.insert(
+                name.to_string(),
+                EwmaStats {
+                    mean: value,
+                    variance: 0.0,
+                    samples: 1,
+                },
+            );
+            return None;
+        };
+
+        let diff = value - entry.mean;
+        // Floor keeps a perfectly flat history from dividing by zero
+        let std_dev = entry
+            .std_dev()
+            .max(f64::EPSILON * entry.mean.abs().max(1.0));
+        let z_score = diff / std_dev;
+        let anomaly = (entry.samples >= self.warmup && z_score.abs() > self.sigma)
+            .then(|| MetricEvent::Anomaly(name.to_string(), value, entry.mean, z_score));
+
+        let increment = self.alpha * diff;
+        entry.mean += increment;
+        entry.variance = (1.0 - self.alpha) * (entry.variance + diff * increment);
+        entry.samples += 1;
+        anomaly
+    }
+
+    /// Feed gauge and timing events; other events are ignored.
+    pub fn observe_event(&self, event: &MetricEvent) -> Option<MetricEvent> {
+        match event {
+            MetricEvent::Gauge(name, value) => self.observe(name, *value),
+            MetricEvent::Timing(name, us) => self.observe(name, *us as f64),
+            _ => None,
+        }
+    }
+
+    /// Current statistics of a metric.
+    pub fn stats(&self, name: &str) -> Option<EwmaStats> {
+        self.stats.lock().ok()?.get(name).copied()
+    }
+
+    /// Forget a metric's history, e.g. after an intended change.
+    pub fn reset(&self, name: &str) {
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.remove(name);
+        }
+    }
+
+    /// Watch `stream` and publish [`MetricEvent::Anomaly`] back into it.
+    pub fn attach(&self, stream: &mut MetricStream) -> SubscriptionId {
+        let detector = self.clone();
+        let emitter = stream.emitter();
+        stream.subscribe(move |event| {
+            if let Some(anomaly) = detector.observe_event(event) {
+                emitter.emit(&anomaly);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_flags_spike_after_warmup() {
+        let detector = AnomalyDetector::new().with_warmup(20);
+        for i in 0..40 {
+            let value = 100.0 + (i % 5) as f64;
+            assert!(detector.observe("latency", value).is_none());
+        }
+        let stats = detector.stats("latency").unwrap();
+        assert!((stats.mean - 102.0).abs() < 2.0);
+
+        let Some(MetricEvent::Anomaly(name, value, mean, z)) = detector.observe("latency", 180.0)
+        else {
+            panic!("spike not flagged");
+        };
+        assert_eq!((name.as_str(), value), ("latency", 180.0));
+        assert!((mean - 102.0).abs() < 2.0);
+        assert!(z > 3.0);
+
+        // Nothing during warm-up of another metric, and outside watch patterns
+        assert!(detector.observe("other", 1.0).is_none());
+        assert!(detector.observe("other", 1000.0).is_none());
+        let watched = AnomalyDetector::new().with_warmup(0).watch("query");
+        watched.observe("cpu", 1.0);
+        assert!(watched.stats("cpu").is_none());
+    }
+
+    #[test]
+    fn test_attach_emits_into_stream() {
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        AnomalyDetector::new().with_warmup(10).attach(&mut stream);
+        let anomalies = Arc::new(Mutex::new(Vec::new()));
+        let sink = anomalies.clone();
+        stream.subscribe(move |event| {
+            if let MetricEvent::Anomaly(name, ..) = event {
+                sink.lock().unwrap().push(name.clone());
+            }
+        });
+
+        for i in 0..20 {
+            stream.publish_timing("query", 1000 + (i % 3) * 10);
+            stream.publish_counter("requests", i * 1000);
+        }
+        assert!(anomalies.lock().unwrap().is_empty());
+
+        stream.publish_timing("query", 50_000);
+        assert_eq!(*anomalies.lock().unwrap(), vec!["query".to_string()]);
+    }
+}
@@ -0,0 +1,343 @@
+//! cgroup v2 Resource Metrics
+//!
+//! Inside limits-constrained containers, host-level `/proc` figures mislead:
+//! a pod can be CPU-throttled on an idle host, or near its memory limit with
+//! plenty of host memory free. This collector reads the process's own
+//! cgroup v2 files instead.
+//!
+//! # Collected Gauges
+//!
+//! - `cgroup_cpu_usage_seconds_total`, `cgroup_cpu_periods_total`,
+//!   `cgroup_cpu_throttled_periods_total`, `cgroup_cpu_throttled_seconds_total`
+//! - `cgroup_cpu_limit_cores` (only when a quota is set)
+//! - `cgroup_memory_current_bytes`, `cgroup_memory_max_bytes` (only when limited)
+//! - `cgroup_pressure_avg10/avg60/avg300{resource,kind}` and
+//!   `cgroup_pressure_stall_seconds_total{resource,kind}` for cpu, memory, io
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::cgroup::CgroupCollector;
+//!
+//! if let Some(collector) = CgroupCollector::detect() {
+//!     let _handle = collector.spawn(Duration::from_secs(15), telemetry.clone());
+//! }
+//! ```
+
+use crate::obs::process::{spawn_collector, CollectorHandle};
+use crate::obs::telemetry::Telemetry;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Resources with pressure stall information.
+pub const PRESSURE_RESOURCES: [&str; 3] = ["cpu", "memory", "io"];
+
+/// Stall averages for one line of a PSI file.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PressureLine {
+    /// Percentage of time stalled over the last 10 seconds
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+    /// Total stall time in microseconds
+    pub total_us: u64,
+}
+
+/// Parsed PSI file (`cpu.pressure`, `/proc/pressure/io`, ...).
+///
+/// `some`: at least one task stalled; `full`: all non-idle tasks stalled.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PressureStats {
+    pub some: Option<PressureLine>,
+    pub full: Option<PressureLine>,
+}
+
+impl PressureStats {
+    /// Parse PSI format: `some avg10=0.00 avg60=0.00 avg300=0.00 total=0`.
+    pub fn parse(content: &str) -> Self {
+        let mut stats = PressureStats::default();
+        for line in content.lines() {
+            let mut fields = line.split_whitespace();
+            let slot = match fields.next() {
+                Some("some") => &mut stats.some,
+                Some("full") => &mut stats.full,
+                _ => continue,
+            };
+            let mut parsed = PressureLine::default();
+            for field in fields {
+                match field.split_once('=') {
+                    Some(("avg10", v)) => parsed.avg10 = v.parse().unwrap_or(0.0),
+                    Some(("avg60", v)) => parsed.avg60 = v.parse().unwrap_or(0.0),
+                    Some(("avg300", v)) => parsed.avg300 = v.parse().unwrap_or(0.0),
+                    Some(("total", v)) => parsed.total_us = v.parse().unwrap_or(0),
+                    _ => {}
+                }
+            }
+            *slot = Some(parsed);
+        }
+        stats
+    }
+
+    /// Lines present, labelled `"some"` / `"full"`.
+    pub fn lines(&self) -> impl Iterator<Item = (&'static str, PressureLine)> {
+        [("some", self.some), ("full", self.full)]
+            .into_iter()
+            .filter_map(|(kind, line)| line.map(|line| (kind, line)))
+    }
+
+    /// Write `<prefix>_avg10/avg60/avg300{resource,kind}` and
+    /// `<prefix>_stall_seconds_total{resource,kind}` gauges.
+    pub(crate) fn record_into(&self, telemetry: &mut Telemetry, prefix: &str, resource: &str) {
+        for (kind, line) in self.lines() {
+            let labels = [("resource", resource), ("kind", kind)];
+            telemetry.set_gauge_with_labels(&format!("{}_avg10", prefix), &labels, line.avg10);
+            telemetry.set_gauge_with_labels(&format!("{}_avg60", prefix), &labels, line.avg60);
+            telemetry.set_gauge_with_labels(&format!("{}_avg300", prefix), &labels, line.avg300);
+            telemetry.set_gauge_with_labels(
+                &format!("{}_stall_seconds_total", prefix),
+                &labels,
+                line.total_us as f64 / 1_000_000.0,
+            );
+        }
+    }
+}
+
+/// One sample of cgroup resource usage. `None` = file missing or unlimited.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CgroupStats {
+    pub cpu_usage_usec: Option<u64>,
+    pub cpu_periods: Option<u64>,
+    pub cpu_throttled_periods: Option<u64>,
+    pub cpu_throttled_usec: Option<u64>,
+    /// CPU quota in cores (`cpu.max` quota / period)
+    pub cpu_limit_cores: Option<f64>,
+    pub memory_current_bytes: Option<u64>,
+    pub memory_max_bytes: Option<u64>,
+    /// PSI per resource, in [`PRESSURE_RESOURCES`] order
+    pub pressure: [Option<PressureStats>; 3],
+}
+
+/// Collector for the current process's cgroup v2 metrics.
+#[derive(Debug, Clone)]
+pub struct CgroupCollector {
+    dir: PathBuf,
+}
+
+impl CgroupCollector {
+    /// Locate the process's cgroup v2 directory; `None` outside cgroup v2.
+    pub fn detect() -> Option<Self> {
+        cgroup_dir().map(Self::with_dir)
+    }
+
+    /// Read from an explicit cgroup directory.
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The cgroup directory being read.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Take one sample of cgroup resource usage.
+    pub fn sample(&self) -> CgroupStats {
+        let mut stats = CgroupStats::default();
+
+        if let Some(cpu_stat) = self.read("cpu.stat") {
+            let field = |key: &str| {
+                cpu_stat.lines().find_map(|line| {
+                    let (k, v) = line.split_once(' ')?;
+                    (k == key).then(|| v.trim().parse().ok()).flatten()
+                })
+            };
+            stats.cpu_usage_usec = field("usage_usec");
+            stats.cpu_periods = field("nr_periods");
+            stats.cpu_throttled_periods = field("nr_throttled");
+            stats.cpu_throttled_usec = field("throttled_usec");
+        }
+
+        if let Some(cpu_max) = self.read("cpu.max") {
+            let mut parts = cpu_max.split_whitespace();
+            if let (Some(Ok(quota)), Some(Ok(period))) = (
+                parts.next().map(str::parse::<f64>),
+                parts.next().map(str::parse::<f64>),
+            ) {
+                if period > 0.0 {
+                    stats.cpu_limit_cores = Some(quota / period);
+                }
+            }
+        }
+
+        stats.memory_current_bytes = self.read_u64("memory.current");
+        // "max" (unlimited) fails to parse and stays None
+        stats.memory_max_bytes = self.read_u64("memory.max");
+
+        for (slot, resource) in stats.pressure.iter_mut().zip(PRESSURE_RESOURCES) {
+            *slot = self
+                .read(&format!("{}.pressure", resource))
+                .map(|content| PressureStats::parse(&content));
+        }
+
+        stats
+    }
+
+    /// Sample and write available values into telemetry gauges.
+    pub fn collect_into(&self, telemetry: &mut Telemetry) -> CgroupStats {
+        let stats = self.sample();
+        let usec_to_secs = |v: u64| v as f64 / 1_000_000.0;
+        let gauges = [
+            (
+                "cgroup_cpu_usage_seconds_total",
+                stats.cpu_usage_usec.map(usec_to_secs),
+            ),
+            (
+                "cgroup_cpu_periods_total",
+                stats.cpu_periods.map(|v| v as f64),
+            ),
+            (
+                "cgroup_cpu_throttled_periods_total",
+                stats.cpu_throttled_periods.map(|v| v as f64),
+            ),
+            (
+                "cgroup_cpu_throttled_seconds_total",
+                stats.cpu_throttled_usec.map(usec_to_secs),
+            ),
+            ("cgroup_cpu_limit_cores", stats.cpu_limit_cores),
+            (
+                "cgroup_memory_current_bytes",
+                stats.memory_current_bytes.map(|v| v as f64),
+            ),
+            (
+                "cgroup_memory_max_bytes",
+                stats.memory_max_bytes.map(|v| v as f64),
+            ),
+        ];
+        for (name, value) in gauges {
+            if let Some(value) = value {
+                telemetry.set_gauge(name, value);
+            }
+        }
+
+        for (pressure, resource) in stats.pressure.iter().zip(PRESSURE_RESOURCES) {
+            if let Some(pressure) = pressure {
+                pressure.record_into(telemetry, "cgroup_pressure", resource);
+            }
+        }
+
+        stats
+    }
+
+    /// Collect into shared telemetry every `interval` on a background thread.
+    pub fn spawn(self, interval: Duration, telemetry: Arc<Mutex<Telemetry>>) -> CollectorHandle {
+        spawn_collector(
+            "obs-cgroup-collector",
+            interval,
+            telemetry,
+            move |telemetry| {
+                self.collect_into(telemetry);
+            },
+        )
+    }
+
+    fn read(&self, file: &str) -> Option<String> {
+        std::fs::read_to_string(self.dir.join(file)).ok()
+    }
+
+    fn read_u64(&self, file: &str) -> Option<u64> {
+        self.read(file)?.trim().parse().ok()
+    }
+}
+
+/// The process's cgroup v2 directory, from the `0::<path>` line of
+/// `/proc/self/cgroup`.
+#[cfg(target_os = "linux")]
+fn cgroup_dir() -> Option<PathBuf> {
+    let membership = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    let relative = membership
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))?
+        .trim_start_matches('/');
+    let dir = Path::new("/sys/fs/cgroup").join(relative);
+    dir.join("cgroup.controllers").exists().then_some(dir)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cgroup_dir() -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_cgroup(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "embeddenator_obs_cgroup_{}_{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("cpu.stat"),
+            "usage_usec 2500000\nuser_usec 2000000\nsystem_usec 500000\n\
+             nr_periods 100\nnr_throttled 7\nthrottled_usec 350000\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("cpu.max"), "150000 100000\n").unwrap();
+        std::fs::write(dir.join("memory.current"), "104857600\n").unwrap();
+        std::fs::write(dir.join("memory.max"), "max\n").unwrap();
+        std::fs::write(
+            dir.join("io.pressure"),
+            "some avg10=1.50 avg60=0.75 avg300=0.20 total=123456\n\
+             full avg10=0.50 avg60=0.25 avg300=0.10 total=65432\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_pressure() {
+        let stats =
+            PressureStats::parse("some avg10=2.04 avg60=0.75 avg300=0.40 total=157656722\n");
+        let some = stats.some.unwrap();
+        assert_eq!(some.avg10, 2.04);
+        assert_eq!(some.total_us, 157656722);
+        assert!(stats.full.is_none());
+    }
+
+    #[test]
+    fn test_sample_fake_cgroup() {
+        let dir = fake_cgroup("sample");
+        let stats = CgroupCollector::with_dir(&dir).sample();
+
+        assert_eq!(stats.cpu_throttled_periods, Some(7));
+        assert_eq!(stats.cpu_limit_cores, Some(1.5));
+        assert_eq!(stats.memory_current_bytes, Some(104_857_600));
+        assert_eq!(stats.memory_max_bytes, None);
+        assert!(stats.pressure[0].is_none());
+        assert_eq!(stats.pressure[2].unwrap().full.unwrap().avg60, 0.25);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_collect_into_telemetry() {
+        let dir = fake_cgroup("collect");
+        let mut telemetry = Telemetry::default_config();
+        CgroupCollector::with_dir(&dir).collect_into(&mut telemetry);
+
+        let gauges = telemetry.snapshot().gauges;
+        assert_eq!(
+            gauges.get("cgroup_cpu_throttled_seconds_total"),
+            Some(&0.35)
+        );
+        assert!(!gauges.contains_key("cgroup_memory_max_bytes"));
+        assert_eq!(
+            gauges.get(r#"cgroup_pressure_avg10{resource="io",kind="some"}"#),
+            Some(&1.5)
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,238 @@
+//! Chrome Trace Event Export
+//!
+//! Converts finished [`OtelSpan`]s into Chrome Trace Event JSON, which
+//! Perfetto (<https://ui.perfetto.dev>) and `chrome://tracing` open as a
+//! timeline, to see how concurrent operations overlap.
+//!
+//! # Layout
+//!
+//! - one process row per trace, named after the service and trace ID
+//! - spans as complete (`"ph": "X"`) events, timestamps relative to the
+//!   earliest span
+//! - overlapping spans that do not nest (concurrent siblings) on separate
+//!   thread rows, so the viewer draws them side by side
+//! - span events as instant (`"ph": "i"`) events
+//! - attributes, IDs, kind and status in `args`
+//!
+//! Unsampled and unfinished spans are skipped.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::chrome_trace::ChromeTraceExporter;
+//!
+//! let exporter = ChromeTraceExporter::new().with_service_name("indexer");
+//! exporter.write_file("trace.json", &spans)?;
+//! // open trace.json in ui.perfetto.dev
+//! ```
+
+use crate::obs::opentelemetry::OtelSpan;
+use crate::obs::privacy;
+use crate::obs::telemetry::escape_json;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Chrome Trace Event JSON exporter.
+#[derive(Debug, Clone)]
+pub struct ChromeTraceExporter {
+    service_name: String,
+}
+
+impl Default for ChromeTraceExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChromeTraceExporter {
+    pub fn new() -> Self {
+        Self {
+            service_name: "embeddenator".to_string(),
+        }
+    }
+
+    /// Name shown on each trace's process row.
+    pub fn with_service_name(mut self, name: impl Into<String>) -> Self {
+        self.service_name = name.into();
+        self
+    }
+
+    /// Trace Event JSON for `spans`.
+    pub fn export(&self, spans: &[OtelSpan]) -> String {
+        let spans = privacy::enforce_spans(spans);
+        let spans: Vec<&OtelSpan> = spans
+            .iter()
+            .filter(|s| s.sampled && s.end_time_ns >= s.start_time_ns && s.end_time_ns != 0)
+            .collect();
+        let origin = spans.iter().map(|s| s.start_time_ns).min().unwrap_or(0);
+
+        // Traces in order of first appearance
+        let mut traces: BTreeMap<u128, Vec<&OtelSpan>> = BTreeMap::new();
+        let mut order = Vec::new();
+        for span in &spans {
+            traces
+                .entry(span.trace_id)
+                .or_insert_with(|| {
+                    order.push(span.trace_id);
+                    Vec::new()
+                })
+                .push(span);
+        }
+
+        let mut events = Vec::new();
+        for (index, trace_id) in order.iter().enumerate() {
+            let pid = index + 1;
+            events.push(format!(
+                r#"{{"name": "process_name", "ph": "M", "pid": {}, "args": {{"name": "{} trace {:08x}"}}}}"#,
+                pid,
+                escape_json(&self.service_name),
+                (trace_id >> 96) as u32
+            ));
+            let mut trace = traces.remove(trace_id).unwrap_or_default();
+            trace.sort_by_key(|s| (s.start_time_ns, std::cmp::Reverse(s.end_time_ns)));
+            let mut lanes: Vec<Vec<(u64, u64)>> = Vec::new();
+            for span in trace {
+                let tid = assign_lane(&mut lanes, span.start_time_ns, span.end_time_ns) + 1;
+                events.push(complete_event(span, pid, tid, origin));
+                for event in &span.events {
+                    events.push(format!(
+                        r#"{{"name": "{}", "cat": "event", "ph": "i", "s": "t", "ts": {}, "pid": {}, "tid": {}, "args": {}}}"#,
+                        escape_json(&event.name),
+                        micros(event.timestamp_ns.saturating_sub(origin)),
+                        pid,
+                        tid,
+                        args_json(&event.attributes, &[])
+                    ));
+                }
+            }
+        }
+
+        let mut json = String::from("{\"traceEvents\": [\n");
+        for (i, event) in events.iter().enumerate() {
+            let comma = if i + 1 < events.len() { "," } else { "" };
+            writeln!(json, "  {}{}", event, comma).ok();
+        }
+        json.push_str("], \"displayTimeUnit\": \"ms\"}\n");
+        json
+    }
+
+    /// Write [`export`](Self::export) output to `path`.
+    pub fn write_file(&self, path: impl AsRef<Path>, spans: &[OtelSpan]) -> io::Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        file.write_all(self.export(spans).as_bytes())
+    }
+}
+
+/// Lane whose open spans all contain `[start, end]`, opening a new lane
+/// if none does. Lanes hold a stack of open intervals.
+fn assign_lane(lanes: &mut Vec<Vec<(u64, u64)>>, start: u64, end: u64) -> usize {
+    for (index, lane) in lanes.iter_mut().enumerate() {
+        while lane.last().is_some_and(|&(_, open_end)| open_end <= start) {
+            lane.pop();
+        }
+        if lane.last().is_none_or(|&(_, open_end)| end <= open_end) {
+            lane.push((start, end));
+            return index;
+        }
+    }
+    lanes.push(vec![(start, end)]);
+    lanes.len() - 1
+}
+
+fn complete_event(span: &OtelSpan, pid: usize, tid: usize, origin: u64) -> String {
+    let ids = [
+        ("span_id", format!("{:016x}", span.span_id)),
+        ("parent_span_id", format!("{:016x}", span.parent_span_id)),
+        ("kind", format!("{:?}", span.kind)),
+        ("status", format!("{:?}", span.status)),
+    ];
+    format!(
+        r#"{{"name": "{}", "cat": "span", "ph": "X", "ts": {}, "dur": {}, "pid": {}, "tid": {}, "args": {}}}"#,
+        escape_json(&span.name),
+        micros(span.start_time_ns - origin),
+        micros(span.end_time_ns - span.start_time_ns),
+        pid,
+        tid,
+        args_json(&span.attributes, &ids)
+    )
+}
+
+/// Nanoseconds as fractional microseconds, the Trace Event time unit.
+fn micros(ns: u64) -> String {
+    format!("{}.{:03}", ns / 1000, ns % 1000)
+}
+
+fn args_json<V: std::fmt::Display>(
+    attributes: &std::collections::HashMap<String, V>,
+    extra: &[(&str, String)],
+) -> String {
+    let mut sorted: Vec<(&str, String)> = attributes
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.to_string()))
+        .collect();
+    sorted.sort();
+    let fields: Vec<String> = extra
+        .iter()
+        .map(|(k, v)| (*k, v.clone()))
+        .chain(sorted)
+        .map(|(k, v)| format!(r#""{}": "{}""#, escape_json(k), escape_json(&v)))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(name: &str, parent: Option<&OtelSpan>, start_us: u64, end_us: u64) -> OtelSpan {
+        let mut span = match parent {
+            Some(parent) => OtelSpan::new_child(name, parent),
+            None => OtelSpan::new(name),
+        };
+        span.start_time_ns = 1_000_000_000 + start_us * 1000;
+        span.end_time_ns = 1_000_000_000 + end_us * 1000;
+        span
+    }
+
+    #[test]
+    fn test_concurrent_siblings_get_separate_lanes() {
+        let root = span("query", None, 0, 100);
+        let a = span("shard_a", Some(&root), 10, 60);
+        let b = span("shard_b", Some(&root), 20, 80);
+        let merge = span("merge", Some(&root), 85, 95);
+        let json = ChromeTraceExporter::new()
+            .with_service_name("indexer")
+            .export(&[merge, b, a, root]);
+
+        assert!(json.starts_with("{\"traceEvents\": ["));
+        assert!(json.contains(r#""ph": "M", "pid": 1, "args": {"name": "indexer trace "#));
+        assert!(json.contains(r#""name": "query", "cat": "span", "ph": "X", "ts": 0.000, "dur": 100.000, "pid": 1, "tid": 1"#));
+        // shard_a nests in query; shard_b overlaps shard_a without nesting
+        assert!(json.contains(r#""name": "shard_a", "cat": "span", "ph": "X", "ts": 10.000, "dur": 50.000, "pid": 1, "tid": 1"#));
+        assert!(json.contains(r#""name": "shard_b", "cat": "span", "ph": "X", "ts": 20.000, "dur": 60.000, "pid": 1, "tid": 2"#));
+        assert!(json.contains(r#""name": "merge", "cat": "span", "ph": "X", "ts": 85.000, "dur": 10.000, "pid": 1, "tid": 1"#));
+    }
+
+    #[test]
+    fn test_events_attributes_and_skipped_spans() {
+        let mut root = span("ingest \"batch\"", None, 0, 50);
+        root.set_attribute("path", "/data/a.bin");
+        root.add_event("flushed");
+        root.events[0].timestamp_ns = 1_000_000_000 + 40_000;
+        let mut unsampled = span("hidden", None, 0, 10);
+        unsampled.sampled = false;
+        let mut open = span("open", None, 0, 0);
+        open.end_time_ns = 0;
+        let other = span("other_trace", None, 5, 7);
+
+        let json = ChromeTraceExporter::new().export(&[root, unsampled, open, other]);
+        assert!(json.contains(r#""name": "ingest \"batch\"""#));
+        assert!(json.contains(r#""path": "/data/a.bin""#));
+        assert!(json.contains(r#""name": "flushed", "cat": "event", "ph": "i", "s": "t", "ts": 40.000, "pid": 1, "tid": 1"#));
+        assert!(json.contains(r#""name": "other_trace", "cat": "span", "ph": "X", "ts": 5.000, "dur": 2.000, "pid": 2"#));
+        assert!(!json.contains("hidden"));
+        assert!(!json.contains(r#""name": "open""#));
+    }
+}
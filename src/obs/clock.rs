@@ -0,0 +1,146 @@
+//! Pluggable Clock Sources
+//!
+//! Components that measure elapsed time ([`HiResTimer`], [`Telemetry`]
+//! and the [`MetricStream`] rate limiter and threshold alerts) read it
+//! through a [`Clock`], so tests can substitute a [`MockClock`] and
+//! advance time by hand instead of sleeping.
+//!
+//! [`SystemClock`] is the default everywhere and reads
+//! `std::time::Instant` / `SystemTime` directly.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::clock::MockClock;
+//!
+//! let clock = Arc::new(MockClock::new());
+//! let mut stream = MetricStream::new().with_clock(clock.clone());
+//! stream.add_alert(ThresholdAlert::above("cpu", 80.0).for_duration(Duration::from_secs(30)));
+//!
+//! stream.publish_gauge("cpu", 95.0); // pending
+//! clock.advance(Duration::from_secs(31));
+//! stream.publish_gauge("cpu", 95.0); // firing
+//! ```
+//!
+//! [`HiResTimer`]: crate::obs::hires_timing::HiResTimer
+//! [`Telemetry`]: crate::obs::telemetry::Telemetry
+//! [`MetricStream`]: crate::obs::streaming::MetricStream
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Source of the current time.
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// Monotonic time, for measuring intervals.
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time, for timestamps.
+    fn system_time(&self) -> SystemTime;
+}
+
+/// The real clock.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Shared [`SystemClock`], the default clock of every component.
+pub fn system_clock() -> Arc<dyn Clock> {
+    static SYSTEM: OnceLock<Arc<dyn Clock>> = OnceLock::new();
+    SYSTEM.get_or_init(|| Arc::new(SystemClock)).clone()
+}
+
+/// Clock that only moves when advanced.
+///
+/// Both readings start at the moment of creation and move together.
+/// Share it through an `Arc` and keep a handle to advance it.
+#[derive(Debug)]
+pub struct MockClock {
+    origin: Instant,
+    system_origin: SystemTime,
+    /// Time advanced since creation, in nanoseconds
+    offset_ns: AtomicU64,
+}
+
+impl MockClock {
+    /// Mock clock frozen at the current time.
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            system_origin: SystemTime::now(),
+            offset_ns: AtomicU64::new(0),
+        }
+    }
+
+    /// Mock clock whose wall-clock reading starts at `system_time`.
+    pub fn at(system_time: SystemTime) -> Self {
+        Self {
+            system_origin: system_time,
+            ..Self::new()
+        }
+    }
+
+    /// Move time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let ns = duration.as_nanos().min(u64::MAX as u128) as u64;
+        self.offset_ns.fetch_add(ns, Ordering::Relaxed);
+    }
+
+    /// Total time advanced since creation.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.offset_ns.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.origin + self.elapsed()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.system_origin + self.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_advances_only_when_told() {
+        let clock = MockClock::at(SystemTime::UNIX_EPOCH);
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now() - start, Duration::from_millis(1500));
+        assert_eq!(
+            clock.system_time(),
+            SystemTime::UNIX_EPOCH + Duration::from_millis(1500)
+        );
+        assert_eq!(clock.elapsed(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_system_clock_is_monotonic() {
+        let clock = system_clock();
+        let first = clock.now();
+        assert!(clock.now() >= first);
+    }
+}
@@ -0,0 +1,414 @@
+//! Statistical Comparison of Measurement Runs
+//!
+//! Decides whether two timing runs differ by more than noise, for A/B
+//! performance claims:
+//!
+//! - [`compare_measurements`]: Welch's t-test on two
+//!   [`HiResMetricsSnapshot`]s (count, mean and standard deviation)
+//! - [`compare_samples`]: Welch's t-test plus a Mann-Whitney U test on raw
+//!   samples; the U test needs no normality assumption, which latency
+//!   distributions with long tails rarely satisfy
+//! - [`TimingStats::compare`](crate::obs::test_metrics::TimingStats::compare)
+//!   for test metrics
+//!
+//! Differences are `b - a`: a positive difference means `b` is slower.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! let before = Benchmark::new().run(|| old_lookup(key));
+//! let after = Benchmark::new().run(|| new_lookup(key));
+//! let comparison = compare_samples(&before.samples, &after.samples);
+//! if comparison.significant {
+//!     println!("{}", comparison.format());
+//! }
+//! ```
+
+use crate::obs::hires_timing::HiResMetricsSnapshot;
+
+/// Significance level of the tests and `1 - confidence` of the interval.
+pub const COMPARISON_ALPHA: f64 = 0.05;
+
+/// Outcome of comparing run `a` against run `b`.
+///
+/// Values are in the unit of the inputs: picoseconds for
+/// [`HiResMetricsSnapshot`]s and samples from [`HiResTimer`], nanoseconds
+/// for [`TimingStats`](crate::obs::test_metrics::TimingStats).
+///
+/// [`HiResTimer`]: crate::obs::hires_timing::HiResTimer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Comparison {
+    pub mean_a: f64,
+    pub mean_b: f64,
+    /// `mean_b - mean_a`
+    pub difference: f64,
+    /// `difference / mean_a`
+    pub relative_change: f64,
+    /// 95% confidence interval of `difference`
+    pub ci_low: f64,
+    pub ci_high: f64,
+    /// Welch's t statistic
+    pub t_statistic: f64,
+    /// Welch-Satterthwaite degrees of freedom
+    pub degrees_of_freedom: f64,
+    /// Two-sided p-value of the t-test
+    pub p_value: f64,
+    /// Mann-Whitney U test, for raw samples only
+    pub mann_whitney: Option<MannWhitney>,
+    /// Whether the difference is significant at [`COMPARISON_ALPHA`]: by
+    /// the U test when available, else by the t-test
+    pub significant: bool,
+}
+
+/// Mann-Whitney U test (normal approximation with tie and continuity
+/// corrections).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MannWhitney {
+    /// U statistic of sample `a`
+    pub u: f64,
+    pub z: f64,
+    /// Two-sided p-value
+    pub p_value: f64,
+}
+
+impl Comparison {
+    /// Welch's t-test from summary statistics.
+    pub fn welch(
+        count_a: u64,
+        mean_a: f64,
+        stddev_a: f64,
+        count_b: u64,
+        mean_b: f64,
+        stddev_b: f64,
+    ) -> Self {
+        let difference = mean_b - mean_a;
+        let relative_change = if mean_a != 0.0 {
+            difference / mean_a
+        } else {
+            0.0
+        };
+        let (na, nb) = (count_a as f64, count_b as f64);
+
+        let mut comparison = Comparison {
+            mean_a,
+            mean_b,
+            difference,
+            relative_change,
+            ci_low: difference,
+            ci_high: difference,
+            t_statistic: 0.0,
+            degrees_of_freedom: 0.0,
+            p_value: 1.0,
+            mann_whitney: None,
+            significant: false,
+        };
+        // Variance needs two samples per side
+        if count_a < 2 || count_b < 2 {
+            return comparison;
+        }
+
+        let (va, vb) = (stddev_a * stddev_a / na, stddev_b * stddev_b / nb);
+        let standard_error = (va + vb).sqrt();
+        if standard_error == 0.0 {
+            // No noise at all: any difference is real
+            comparison.p_value = if difference == 0.0 { 1.0 } else { 0.0 };
+            comparison.significant = difference != 0.0;
+            return comparison;
+        }
+        let df = (va + vb).powi(2) / (va * va / (na - 1.0) + vb * vb / (nb - 1.0));
+        let t = difference / standard_error;
+        let margin = t_critical(df, COMPARISON_ALPHA) * standard_error;
+
+        comparison.t_statistic = t;
+        comparison.degrees_of_freedom = df;
+        comparison.p_value = t_two_sided_p(t, df);
+        comparison.ci_low = difference - margin;
+        comparison.ci_high = difference + margin;
+        comparison.significant = comparison.p_value < COMPARISON_ALPHA;
+        comparison
+    }
+
+    /// One-line summary, e.g. `+12.4% (95% CI +9.8..+15.0) p=0.0001 significant`.
+    pub fn format(&self) -> String {
+        let percent = |value: f64| {
+            if self.mean_a == 0.0 {
+                0.0
+            } else {
+                value / self.mean_a * 100.0
+            }
+        };
+        let p_value = self.mann_whitney.map_or(self.p_value, |u| u.p_value);
+        format!(
+            "{:+.1}% (95% CI {:+.1}..{:+.1}) p={:.4} {}",
+            self.relative_change * 100.0,
+            percent(self.ci_low),
+            percent(self.ci_high),
+            p_value,
+            if self.significant {
+                "significant"
+            } else {
+                "not significant"
+            }
+        )
+    }
+}
+
+/// Compare two high-resolution runs with Welch's t-test.
+pub fn compare_measurements(a: &HiResMetricsSnapshot, b: &HiResMetricsSnapshot) -> Comparison {
+    Comparison::welch(
+        a.count,
+        a.mean_ps as f64,
+        a.stddev_ps as f64,
+        b.count,
+        b.mean_ps as f64,
+        b.stddev_ps as f64,
+    )
+}
+
+/// Compare two runs of raw samples with Welch's t-test and the
+/// Mann-Whitney U test; significance follows the U test.
+pub fn compare_samples(a: &[u64], b: &[u64]) -> Comparison {
+    let (mean_a, stddev_a) = mean_stddev(a);
+    let (mean_b, stddev_b) = mean_stddev(b);
+    let mut comparison = Comparison::welch(
+        a.len() as u64,
+        mean_a,
+        stddev_a,
+        b.len() as u64,
+        mean_b,
+        stddev_b,
+    );
+    comparison.mann_whitney = mann_whitney(a, b);
+    if let Some(u) = comparison.mann_whitney {
+        comparison.significant = u.p_value < COMPARISON_ALPHA;
+    }
+    comparison
+}
+
+/// Sample mean and standard deviation (n - 1).
+fn mean_stddev(samples: &[u64]) -> (f64, f64) {
+    let n = samples.len() as f64;
+    if samples.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mean = samples.iter().map(|&s| s as f64).sum::<f64>() / n;
+    if samples.len() < 2 {
+        return (mean, 0.0);
+    }
+    let squares: f64 = samples.iter().map(|&s| (s as f64 - mean).powi(2)).sum();
+    (mean, (squares / (n - 1.0)).sqrt())
+}
+
+fn mann_whitney(a: &[u64], b: &[u64]) -> Option<MannWhitney> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let (na, nb) = (a.len() as f64, b.len() as f64);
+    let n = na + nb;
+
+    // Rank the pooled samples, ties sharing their mean rank
+    let mut pooled: Vec<(u64, bool)> = a
+        .iter()
+        .map(|&s| (s, true))
+        .chain(b.iter().map(|&s| (s, false)))
+        .collect();
+    pooled.sort_unstable_by_key(|&(value, _)| value);
+    let mut rank_sum_a = 0.0;
+    let mut tie_term = 0.0;
+    let mut i = 0;
+    while i < pooled.len() {
+        let j = i + pooled[i..]
+            .iter()
+            .take_while(|&&(value, _)| value == pooled[i].0)
+            .count();
+        let mean_rank = (i + j + 1) as f64 / 2.0;
+        rank_sum_a += mean_rank * pooled[i..j].iter().filter(|&&(_, in_a)| in_a).count() as f64;
+        let ties = (j - i) as f64;
+        tie_term += ties * ties * ties - ties;
+        i = j;
+    }
+
+    let u = rank_sum_a - na * (na + 1.0) / 2.0;
+    let mean_u = na * nb / 2.0;
+    let variance = na * nb / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)).max(1.0));
+    if variance <= 0.0 {
+        return Some(MannWhitney {
+            u,
+            z: 0.0,
+            p_value: 1.0,
+        });
+    }
+    let deviation = (u - mean_u).abs() - 0.5;
+    let z = deviation.max(0.0).copysign(u - mean_u) / variance.sqrt();
+    Some(MannWhitney {
+        u,
+        z,
+        p_value: erfc(z.abs() / std::f64::consts::SQRT_2).min(1.0),
+    })
+}
+
+/// Two-sided p-value of Student's t distribution.
+fn t_two_sided_p(t: f64, df: f64) -> f64 {
+    incomplete_beta(df / 2.0, 0.5, df / (df + t * t)).clamp(0.0, 1.0)
+}
+
+/// `t` with two-sided tail probability `alpha`, by bisection.
+fn t_critical(df: f64, alpha: f64) -> f64 {
+    let (mut low, mut high) = (0.0, 1_000.0);
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if t_two_sided_p(mid, df) > alpha {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Regularized incomplete beta function `I_x(a, b)`.
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges fast on this side
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Continued fraction of the incomplete beta function (modified Lentz).
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+        for numerator in [
+            m * (b - m) * x / ((a + m2 - 1.0) * (a + m2)),
+            -(a + m) * (a + b + m) * x / ((a + m2) * (a + m2 + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    h
+}
+
+/// `ln Γ(x)` (Lanczos approximation, g = 7).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, &c)| {
+            sum + c / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// Complementary error function (Chebyshev fit, relative error < 1.2e-7).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let result = t * poly.exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distributions() {
+        // t = 2.228 is the 97.5th percentile at 10 degrees of freedom
+        assert!((t_two_sided_p(2.228, 10.0) - 0.05).abs() < 1e-3);
+        assert!((t_critical(1_000.0, 0.05) - 1.962).abs() < 1e-3);
+        assert!((erfc(1.0) - 0.157_299_2).abs() < 1e-6);
+        assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_compare_samples() {
+        let a: Vec<u64> = (0..200).map(|i| 1_000 + (i * 37) % 100).collect();
+        let slower: Vec<u64> = a.iter().map(|s| s + 30).collect();
+        let comparison = compare_samples(&a, &slower);
+        assert!(comparison.significant, "{}", comparison.format());
+        assert!(comparison.mann_whitney.unwrap().p_value < 1e-6);
+        assert!(comparison.ci_low > 20.0 && comparison.ci_high < 40.0);
+        assert!(comparison.format().starts_with("+2.9%"));
+
+        let same = compare_samples(&a, &a);
+        assert!(!same.significant);
+        assert!((same.p_value - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_compare_measurements() {
+        let snapshot = |mean_ps, stddev_ps| HiResMetricsSnapshot {
+            count: 50,
+            mean_ps,
+            stddev_ps,
+            ..Default::default()
+        };
+        let noisy = compare_measurements(&snapshot(1_000, 400), &snapshot(1_050, 400));
+        assert!(!noisy.significant);
+        assert!(noisy.mann_whitney.is_none());
+        let clear = compare_measurements(&snapshot(1_000, 20), &snapshot(1_050, 20));
+        assert!(clear.significant);
+        assert!((clear.degrees_of_freedom - 98.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,369 @@
+//! Concurrency-Limit Advisor
+//!
+//! Tracks in-flight requests and latency per operation and estimates the
+//! concurrency at which each operation gets the most throughput, to size
+//! thread pools and semaphores from live data.
+//!
+//! # Estimate
+//!
+//! Every evaluation window, per operation:
+//!
+//! - observed concurrency from Little's law, `L = throughput * latency`
+//! - baseline latency: the lowest window latency seen, i.e. latency
+//!   without queueing
+//! - gradient `baseline / latency`, clamped to 0.5 to 1.0: 1.0 while
+//!   latency stays at baseline, lower as requests start to queue
+//! - new limit `limit * gradient + sqrt(limit)`, smoothed, so the limit
+//!   probes upward while latency holds and backs off when it rises
+//!
+//! The limit only grows while the operation actually used at least half
+//! of it, so an idle operation does not drift to the maximum.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::concurrency::ConcurrencyAdvisor;
+//!
+//! let advisor = ConcurrencyAdvisor::new()
+//!     .on_recommendation(|rec| pool.set_size(rec.limit));
+//! let _handle = advisor.clone().spawn(Duration::from_secs(10));
+//!
+//! let _in_flight = advisor.begin("retrieval_query");
+//! run_query()?;
+//! // latency recorded when the guard drops
+//! ```
+
+use crate::obs::process::{spawn_periodic, CollectorHandle};
+use crate::obs::telemetry::Telemetry;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Recommended concurrency for one operation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConcurrencyRecommendation {
+    pub operation: String,
+    /// Recommended maximum in-flight requests
+    pub limit: usize,
+    /// Completions per second in the last window
+    pub throughput_per_sec: f64,
+    /// Mean latency in the last window
+    pub latency_us: f64,
+    /// Lowest window latency seen
+    pub baseline_latency_us: f64,
+    /// Mean in-flight requests by Little's law
+    pub observed_concurrency: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    initial: f64,
+    min: f64,
+    max: f64,
+    min_samples: u64,
+}
+
+struct OperationState {
+    in_flight: usize,
+    limit: f64,
+    baseline_latency_us: Option<f64>,
+    window_start: Instant,
+    completed: u64,
+    latency_sum_us: f64,
+    peak_in_flight: usize,
+    last: Option<ConcurrencyRecommendation>,
+}
+
+impl OperationState {
+    fn new(limits: &Limits) -> Self {
+        Self {
+            in_flight: 0,
+            limit: limits.initial,
+            baseline_latency_us: None,
+            window_start: Instant::now(),
+            completed: 0,
+            latency_sum_us: 0.0,
+            peak_in_flight: 0,
+            last: None,
+        }
+    }
+}
+
+type RecommendationCallback = Arc<dyn Fn(&ConcurrencyRecommendation) + Send + Sync>;
+
+/// Per-operation concurrency-limit estimator; clones share state.
+#[derive(Clone)]
+pub struct ConcurrencyAdvisor {
+    operations: Arc<Mutex<HashMap<String, OperationState>>>,
+    limits: Limits,
+    callbacks: Vec<RecommendationCallback>,
+}
+
+impl Default for ConcurrencyAdvisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConcurrencyAdvisor {
+    /// Advisor starting every operation at a limit of 10, bounded to 1
+    /// to 1000.
+    pub fn new() -> Self {
+        Self {
+            operations: Arc::new(Mutex::new(HashMap::new())),
+            limits: Limits {
+                initial: 10.0,
+                min: 1.0,
+                max: 1000.0,
+                min_samples: 10,
+            },
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Starting limit for operations seen for the first time.
+    pub fn with_initial_limit(mut self, limit: usize) -> Self {
+        self.limits.initial = limit as f64;
+        self
+    }
+
+    /// Bounds for recommendations.
+    pub fn with_bounds(mut self, min: usize, max: usize) -> Self {
+        self.limits.min = min.max(1) as f64;
+        self.limits.max = max.max(min.max(1)) as f64;
+        self
+    }
+
+    /// Completions a window needs before it is evaluated (default: 10).
+    pub fn with_min_samples(mut self, samples: u64) -> Self {
+        self.limits.min_samples = samples.max(1);
+        self
+    }
+
+    /// Call `callback` whenever an operation's recommended limit changes.
+    pub fn on_recommendation<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&ConcurrencyRecommendation) + Send + Sync + 'static,
+    {
+        self.callbacks.push(Arc::new(callback));
+        self
+    }
+
+    /// Count a request of `operation` as in flight until the guard drops,
+    /// then record its latency.
+    pub fn begin(&self, operation: &str) -> InFlightGuard {
+        if let Ok(mut operations) = self.operations.lock() {
+            let state = operations
+                .entry(operation.to_string())
+                .or_insert_with(|| OperationState::new(&self.limits));
+            state.in_flight += 1;
+            state.peak_in_flight = state.peak_in_flight.max(state.in_flight);
+        }
+        InFlightGuard {
+            operations: self.operations.clone(),
+            operation: operation.to_string(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Record a completed request measured by the caller, which ran with
+    /// `in_flight` requests of `operation` outstanding.
+    pub fn record(&self, operation: &str, in_flight: usize, latency: Duration) {
+        if let Ok(mut operations) = self.operations.lock() {
+            let state = operations
+                .entry(operation.to_string())
+                .or_insert_with(|| OperationState::new(&self.limits));
+            state.peak_in_flight = state.peak_in_flight.max(in_flight);
+            state.completed += 1;
+            state.latency_sum_us += latency.as_secs_f64() * 1e6;
+        }
+    }
+
+    /// Requests of `operation` currently in flight.
+    pub fn in_flight(&self, operation: &str) -> usize {
+        self.operations
+            .lock()
+            .ok()
+            .and_then(|operations| operations.get(operation).map(|s| s.in_flight))
+            .unwrap_or(0)
+    }
+
+    /// Latest recommendation for `operation`.
+    pub fn recommendation(&self, operation: &str) -> Option<ConcurrencyRecommendation> {
+        let operations = self.operations.lock().ok()?;
+        operations.get(operation)?.last.clone()
+    }
+
+    /// Close the current window of every operation with enough samples,
+    /// update its limit and return the recommendations that changed.
+    pub fn evaluate(&self) -> Vec<ConcurrencyRecommendation> {
+        let limits = self.limits;
+        let mut changed = Vec::new();
+        if let Ok(mut operations) = self.operations.lock() {
+            for (name, state) in operations.iter_mut() {
+                if state.completed < limits.min_samples {
+                    continue;
+                }
+                let elapsed = state.window_start.elapsed().as_secs_f64().max(1e-6);
+                let latency_us = state.latency_sum_us / state.completed as f64;
+                let throughput = state.completed as f64 / elapsed;
+                let baseline = state
+                    .baseline_latency_us
+                    .map_or(latency_us, |b| b.min(latency_us));
+                state.baseline_latency_us = Some(baseline);
+
+                let gradient = if latency_us > 0.0 {
+                    (baseline / latency_us).clamp(0.5, 1.0)
+                } else {
+                    1.0
+                };
+                let mut target = state.limit * gradient + state.limit.sqrt();
+                // Only probe upward when the current limit is in use
+                if (state.peak_in_flight as f64) < state.limit / 2.0 {
+                    target = target.min(state.limit);
+                }
+                state.limit = (0.8 * state.limit + 0.2 * target).clamp(limits.min, limits.max);
+
+                let recommendation = ConcurrencyRecommendation {
+                    operation: name.clone(),
+                    limit: state.limit.round() as usize,
+                    throughput_per_sec: throughput,
+                    latency_us,
+                    baseline_latency_us: baseline,
+                    observed_concurrency: throughput * latency_us / 1e6,
+                };
+                if state.last.as_ref().map(|last| last.limit) != Some(recommendation.limit) {
+                    changed.push(recommendation.clone());
+                }
+                state.last = Some(recommendation);
+
+                state.window_start = Instant::now();
+                state.completed = 0;
+                state.latency_sum_us = 0.0;
+                state.peak_in_flight = state.in_flight;
+            }
+        }
+        changed.sort_by(|a, b| a.operation.cmp(&b.operation));
+        for recommendation in &changed {
+            for callback in &self.callbacks {
+                callback(recommendation);
+            }
+        }
+        changed
+    }
+
+    /// Evaluate every `interval` on a background thread.
+    pub fn spawn(self, interval: Duration) -> CollectorHandle {
+        spawn_periodic("obs-concurrency-advisor", interval, move || {
+            self.evaluate();
+        })
+    }
+
+    /// Write `concurrency_in_flight{operation}` and
+    /// `concurrency_limit_recommended{operation}` gauges.
+    pub fn record_into(&self, telemetry: &mut Telemetry) {
+        let Ok(operations) = self.operations.lock() else {
+            return;
+        };
+        for (name, state) in operations.iter() {
+            let labels = [("operation", name.as_str())];
+            telemetry.set_gauge_with_labels(
+                "concurrency_in_flight",
+                &labels,
+                state.in_flight as f64,
+            );
+            if let Some(last) = &state.last {
+                telemetry.set_gauge_with_labels(
+                    "concurrency_limit_recommended",
+                    &labels,
+                    last.limit as f64,
+                );
+            }
+        }
+    }
+}
+
+/// In-flight request of one operation; records its latency on drop.
+pub struct InFlightGuard {
+    operations: Arc<Mutex<HashMap<String, OperationState>>>,
+    operation: String,
+    started: Instant,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let latency_us = self.started.elapsed().as_secs_f64() * 1e6;
+        if let Ok(mut operations) = self.operations.lock() {
+            if let Some(state) = operations.get_mut(&self.operation) {
+                state.in_flight = state.in_flight.saturating_sub(1);
+                state.completed += 1;
+                state.latency_sum_us += latency_us;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_flight_guard() {
+        let advisor = ConcurrencyAdvisor::new().with_min_samples(1);
+        let first = advisor.begin("query");
+        let second = advisor.begin("query");
+        assert_eq!(advisor.in_flight("query"), 2);
+        drop(first);
+        assert_eq!(advisor.in_flight("query"), 1);
+        drop(second);
+        assert_eq!(advisor.in_flight("query"), 0);
+
+        let changed = advisor.evaluate();
+        assert_eq!(changed.len(), 1);
+        assert!(changed[0].throughput_per_sec > 0.0);
+
+        let mut telemetry = Telemetry::default_config();
+        advisor.record_into(&mut telemetry);
+        let gauges = telemetry.snapshot().gauges;
+        assert_eq!(gauges[r#"concurrency_in_flight{operation="query"}"#], 0.0);
+        assert!(gauges.contains_key(r#"concurrency_limit_recommended{operation="query"}"#));
+    }
+
+    #[test]
+    fn test_limit_follows_latency() {
+        let recommended = Arc::new(Mutex::new(Vec::new()));
+        let sink = recommended.clone();
+        let advisor = ConcurrencyAdvisor::new()
+            .with_initial_limit(10)
+            .on_recommendation(move |rec| sink.lock().unwrap().push(rec.limit));
+        let window = |in_flight: usize, latency_ms: u64| {
+            for _ in 0..20 {
+                advisor.record("index", in_flight, Duration::from_millis(latency_ms));
+            }
+            advisor.evaluate();
+            advisor.recommendation("index").unwrap().limit
+        };
+
+        // Saturated at baseline latency: probe upward
+        let mut limit = 10;
+        for _ in 0..5 {
+            limit = window(limit, 5);
+        }
+        assert!(limit > 10, "limit {}", limit);
+
+        // Queueing doubles latency: back off
+        let grown = limit;
+        for _ in 0..5 {
+            limit = window(limit, 10);
+        }
+        assert!(limit < grown, "limit {} after {}", limit, grown);
+
+        // Mostly idle: no upward drift
+        let idle = limit;
+        for _ in 0..5 {
+            limit = window(1, 5);
+        }
+        assert!(limit <= idle);
+        assert!(!recommended.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,1324 @@
+//! Observability Config File with Hot Reload
+//!
+//! Reads observability settings from a TOML or YAML file and, through
+//! [`ConfigWatcher`], applies edits at runtime without a restart.
+//!
+//! # Format
+//!
+//! A subset of TOML; every key is optional.
+//!
+//! ```toml
+//! [logging]
+//! level = "info"              # error, warn, info, debug or trace
+//! filter = "info,hyper=warn"  # subscriber filter, as EMBEDDENATOR_LOG
+//! format = "json"             # compact, pretty or json (restart)
+//! output = "journald"         # stderr, journald or syslog (restart)
+//! file = "/var/log/obs.log"   # also log to this file (restart)
+//! rotate = "daily"            # daily, never or a size like "64MB" (restart)
+//! keep_files = 5              # rotated files kept (restart)
+//!
+//! [telemetry]
+//! enabled = true
+//! sample_rate = 0.25
+//! snapshot_interval = "60s"          # (restart)
+//! max_history_entries = 100          # (restart)
+//! max_label_sets_per_metric = 1000   # (restart)
+//! export_interval = "5s"             # batched span export (restart)
+//!
+//! [sinks]
+//! statsd = false              # exporters switched on or off
+//!
+//! [exporters]                 # endpoints
+//! prometheus = "0.0.0.0:9898" # (restart)
+//! otlp = "http://collector:4318"
+//!
+//! [histogram]                 # bucket bounds in microseconds (restart)
+//! buckets_us = [100, 500, 1000, 5000, 10000, 50000, 100000]
+//!
+//! [histogram.operations]
+//! retrieval_query = [10, 20, 40, 80, 160]
+//!
+//! [[alert]]                   # same keys as alert rules files
+//! metric = "queue_depth"
+//! above = 100
+//! for = "1m"
+//! ```
+//!
+//! Files ending in `.yaml` or `.yml` hold the same sections as nested
+//! mappings, with `alert` a list:
+//!
+//! ```yaml
+//! logging:
+//!   level: info
+//! histogram:
+//!   operations:
+//!     retrieval_query: [10, 20, 40, 80, 160]
+//! alert:
+//!   - metric: queue_depth
+//!     above: 100
+//! ```
+//!
+//! # Environment Overrides
+//!
+//! [`ObservabilityConfig::from_file`] applies environment variables on
+//! top of the file: `EMBEDDENATOR_OBS_<SECTION>_<KEY>` sets
+//! `section.key` (e.g. `EMBEDDENATOR_OBS_TELEMETRY_SAMPLE_RATE=0.1`,
+//! `EMBEDDENATOR_OBS_EXPORTERS_OTLP=http://collector:4318`). The older
+//! `EMBEDDENATOR_LOG_FORMAT`, `EMBEDDENATOR_LOG_OUTPUT`,
+//! `EMBEDDENATOR_LOG_FILE`, `EMBEDDENATOR_LOG_ROTATE` and
+//! `EMBEDDENATOR_LOG_KEEP` set their `logging` keys, below the
+//! `EMBEDDENATOR_OBS_` form. [`ObservabilityConfig::from_env`] reads the
+//! variables alone, for deployments without a file.
+//!
+//! # Reloading
+//!
+//! On reload the file is compared with the active config key by key.
+//! Safe changes (log level and filter, telemetry sampling, sink toggles,
+//! exporter endpoints, alert thresholds) are applied and logged as a
+//! `config change applied` audit event. Changes marked *(restart)* are
+//! rejected with a `config change rejected` warning giving the reason,
+//! and the active value stays in effect until the process restarts; so
+//! does an exporter endpoint no [`ConfigWatcher::on_exporter`] hook
+//! accepts. A file that fails to parse is logged and ignored.
+//!
+//! `logging.level` only narrows what the subscriber's filter lets
+//! through; change `logging.filter` to make it more verbose.
+//!
+//! Besides the file watch, a [`ConfigReloader`] reloads on demand: from
+//! code, from the `reload` query command
+//! ([`QueryHandler::with_reloader`](crate::obs::query_socket::QueryHandler::with_reloader))
+//! or, on Unix, on `SIGHUP`.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::config::ConfigWatcher;
+//!
+//! let config = ObservabilityConfig::from_file("/etc/embeddenator/obs.toml")?;
+//! let _obs = Observability::builder().with_config(&config).init()?;
+//!
+//! let _watcher = ConfigWatcher::new("/etc/embeddenator/obs.toml")
+//!     .with_telemetry(telemetry.clone())
+//!     .with_stream(&stream)
+//!     .on_sink("statsd", move |enabled| statsd_enabled.store(enabled, Ordering::Relaxed))
+//!     .on_exporter("statsd", move |addr| statsd.connect(addr.unwrap_or("127.0.0.1:8125")))
+//!     .watch(Duration::from_secs(5))?;
+//!
+//! // Or reload on demand as well
+//! let reloader = ConfigReloader::new(ConfigWatcher::new("/etc/embeddenator/obs.toml"))
+//!     .reload_on_sighup();
+//! let _watch = reloader.watch(Duration::from_secs(5))?;
+//! let report = reloader.reload()?;
+//! ```
+
+use crate::obs::alert_config::{
+    file_version, invalid, parse_duration, parse_string, strip_comment, RuleBuilder,
+};
+use crate::obs::logging::{self, LogFile, LogOutput, Rotation};
+use crate::obs::process::{spawn_periodic, CollectorHandle};
+use crate::obs::prometheus::{PrometheusExporter, DEFAULT_BUCKETS_US};
+use crate::obs::streaming::{AlertsHandle, MetricStream, ThresholdAlert};
+use crate::obs::telemetry::{escape_json, Telemetry, TelemetryConfig};
+use crate::obs::tracing::{record_event, EventLevel};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Settings read from an observability config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObservabilityConfig {
+    /// `logging.level`: most verbose level emitted
+    pub log_level: EventLevel,
+    /// `logging.filter`: subscriber filter replacing `EMBEDDENATOR_LOG`
+    pub log_filter: Option<String>,
+    /// `logging.format`: compact, pretty or json
+    pub log_format: String,
+    /// `logging.output`: stderr, journald or syslog
+    pub log_output: LogOutput,
+    /// `logging.file`: log file written in addition to stderr
+    pub log_file: Option<PathBuf>,
+    /// `logging.rotate`
+    pub log_rotation: Rotation,
+    /// `logging.keep_files`
+    pub log_keep_files: usize,
+    /// `telemetry.enabled`
+    pub telemetry_enabled: bool,
+    /// `telemetry.sample_rate` (0.0 to 1.0)
+    pub sample_rate: f64,
+    /// `telemetry.snapshot_interval`
+    pub snapshot_interval: Duration,
+    /// `telemetry.max_history_entries`
+    pub max_history_entries: usize,
+    /// `telemetry.max_label_sets_per_metric`
+    pub max_label_sets_per_metric: usize,
+    /// `telemetry.export_interval`: interval of batched span export
+    pub export_interval: Duration,
+    /// `[sinks]`: exporters switched on or off; unlisted sinks are on
+    pub sinks: BTreeMap<String, bool>,
+    /// `[exporters]`: endpoint per exporter, e.g. `prometheus`, `otlp`
+    pub exporters: BTreeMap<String, String>,
+    /// `histogram.buckets_us`: bucket bounds for operations without their own
+    pub histogram_buckets: Vec<u64>,
+    /// `[histogram.operations]`: bucket bounds per operation
+    pub operation_buckets: BTreeMap<String, Vec<u64>>,
+    /// `[[alert]]` tables
+    pub alerts: Vec<ThresholdAlert>,
+}
+
+impl Default for ObservabilityConfig {
+    fn default() -> Self {
+        let telemetry = TelemetryConfig::default();
+        let log_file = LogFile::new("");
+        Self {
+            log_level: EventLevel::Trace,
+            log_filter: None,
+            log_format: "compact".to_string(),
+            log_output: LogOutput::default(),
+            log_file: None,
+            log_rotation: log_file.rotation,
+            log_keep_files: log_file.keep,
+            telemetry_enabled: telemetry.enabled,
+            sample_rate: telemetry.sample_rate,
+            snapshot_interval: telemetry.snapshot_interval,
+            max_history_entries: telemetry.max_history_entries,
+            max_label_sets_per_metric: telemetry.max_label_sets_per_metric,
+            export_interval: Duration::from_secs(5),
+            sinks: BTreeMap::new(),
+            exporters: BTreeMap::new(),
+            histogram_buckets: DEFAULT_BUCKETS_US.to_vec(),
+            operation_buckets: BTreeMap::new(),
+            alerts: Vec::new(),
+        }
+    }
+}
+
+/// Keys, or sections, that only take effect at startup, with the reason.
+const RESTART_KEYS: &[(&str, &str)] = &[
+    (
+        "logging.format",
+        "the log subscriber is installed once at startup",
+    ),
+    (
+        "logging.output",
+        "the log subscriber is installed once at startup",
+    ),
+    (
+        "logging.file",
+        "the log subscriber is installed once at startup",
+    ),
+    (
+        "logging.rotate",
+        "the log subscriber is installed once at startup",
+    ),
+    (
+        "logging.keep_files",
+        "the log subscriber is installed once at startup",
+    ),
+    (
+        "telemetry.snapshot_interval",
+        "telemetry is configured once at startup",
+    ),
+    (
+        "telemetry.max_history_entries",
+        "telemetry is configured once at startup",
+    ),
+    (
+        "telemetry.max_label_sets_per_metric",
+        "label sets already admitted are not re-evaluated",
+    ),
+    (
+        "telemetry.export_interval",
+        "span export is started once at startup",
+    ),
+    (
+        "exporters.prometheus",
+        "the metrics endpoint keeps its listening socket",
+    ),
+    ("histogram", "the metrics exporter is built once at startup"),
+];
+
+/// Prefix of variables overriding config keys.
+pub const ENV_PREFIX: &str = "EMBEDDENATOR_OBS_";
+
+/// Sections settable from the environment, by variable name part.
+const ENV_SECTIONS: &[(&str, &str)] = &[
+    ("histogram_operations", "histogram.operations"),
+    ("logging", "logging"),
+    ("telemetry", "telemetry"),
+    ("sinks", "sinks"),
+    ("exporters", "exporters"),
+    ("histogram", "histogram"),
+];
+
+/// Variables read before config files existed, and the keys they set.
+const LEGACY_VARS: &[(&str, &str, &str)] = &[
+    ("EMBEDDENATOR_LOG_FORMAT", "logging", "format"),
+    ("EMBEDDENATOR_LOG_OUTPUT", "logging", "output"),
+    ("EMBEDDENATOR_LOG_FILE", "logging", "file"),
+    ("EMBEDDENATOR_LOG_ROTATE", "logging", "rotate"),
+    ("EMBEDDENATOR_LOG_KEEP", "logging", "keep_files"),
+];
+
+impl ObservabilityConfig {
+    /// Parse config file contents.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut config = Self::default();
+        let mut section = String::new();
+        let mut alert: Option<RuleBuilder> = None;
+
+        for (index, raw) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                if let Some(rule) = alert.take() {
+                    config.alerts.push(rule.build()?);
+                }
+                section = match line {
+                    "[[alert]]" => {
+                        alert = Some(RuleBuilder::new(line_no));
+                        "alert".to_string()
+                    }
+                    "[logging]"
+                    | "[telemetry]"
+                    | "[sinks]"
+                    | "[exporters]"
+                    | "[histogram]"
+                    | "[histogram.operations]" => {
+                        line.trim_matches(|c| c == '[' || c == ']').to_string()
+                    }
+                    _ => return Err(invalid(line_no, &format!("unknown section {}", line))),
+                };
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(invalid(line_no, "expected key = value"));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match section.as_str() {
+                "alert" => {
+                    if let Some(rule) = alert.as_mut() {
+                        rule.set(key, value).map_err(|msg| invalid(line_no, &msg))?;
+                    }
+                }
+                "" => return Err(invalid(line_no, "expected a [section] before keys")),
+                _ => config
+                    .set(&section, key, value)
+                    .map_err(|msg| invalid(line_no, &msg))?,
+            }
+        }
+        if let Some(rule) = alert {
+            config.alerts.push(rule.build()?);
+        }
+        Ok(config)
+    }
+
+    /// Parse YAML config contents: the sections of the TOML form as
+    /// nested mappings, with `alert` a list of mappings. Plain scalars
+    /// need no quotes; flow sequences (`[1, 2]`) hold bucket bounds.
+    pub fn parse_yaml(text: &str) -> io::Result<Self> {
+        let mut config = Self::default();
+        // Open mappings with their indentation
+        let mut path: Vec<(usize, String)> = Vec::new();
+        let mut alert: Option<RuleBuilder> = None;
+
+        for (index, raw) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let stripped = strip_comment(raw).trim_end();
+            let mut line = stripped.trim_start();
+            if line.is_empty() || line == "---" {
+                continue;
+            }
+            let item = line.strip_prefix("- ");
+            // Items may sit at the indentation of their key
+            let indent = stripped.len() - line.len() + usize::from(item.is_some());
+            while path.last().is_some_and(|(open, _)| *open >= indent) {
+                path.pop();
+            }
+            let section = path
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(".");
+            if let Some(item) = item {
+                if section != "alert" {
+                    return Err(invalid(line_no, "lists are only supported under alert"));
+                }
+                if let Some(rule) = alert.replace(RuleBuilder::new(line_no)) {
+                    config.alerts.push(rule.build()?);
+                }
+                line = item.trim_start();
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                return Err(invalid(line_no, "expected key: value"));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            if value.is_empty() {
+                match (section.as_str(), key) {
+                    (
+                        "",
+                        "logging" | "telemetry" | "sinks" | "exporters" | "histogram" | "alert",
+                    )
+                    | ("histogram", "operations") => path.push((indent, key.to_string())),
+                    _ => return Err(invalid(line_no, &format!("unknown section {}", key))),
+                }
+                continue;
+            }
+            let value = toml_value(value);
+            match section.as_str() {
+                "alert" => {
+                    let Some(rule) = alert.as_mut() else {
+                        return Err(invalid(line_no, "expected - before alert keys"));
+                    };
+                    rule.set(key, &value)
+                        .map_err(|msg| invalid(line_no, &msg))?;
+                }
+                "" => return Err(invalid(line_no, "expected a section before keys")),
+                _ => config
+                    .set(&section, key, &value)
+                    .map_err(|msg| invalid(line_no, &msg))?,
+            }
+        }
+        if let Some(rule) = alert {
+            config.alerts.push(rule.build()?);
+        }
+        Ok(config)
+    }
+
+    /// Read and parse a config file, YAML if it ends in `.yaml` or `.yml`
+    /// and TOML otherwise, then apply environment overrides.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let config = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => Self::parse_yaml(&text),
+            _ => Self::parse(&text),
+        };
+        config
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?
+            .with_env_overrides()
+    }
+
+    /// Defaults with environment overrides, for running without a file.
+    pub fn from_env() -> io::Result<Self> {
+        Self::default().with_env_overrides()
+    }
+
+    /// Apply `EMBEDDENATOR_OBS_<SECTION>_<KEY>` and the older
+    /// `EMBEDDENATOR_LOG_*` variables; see the [module docs](self).
+    pub fn with_env_overrides(self) -> io::Result<Self> {
+        self.with_overrides(std::env::vars())
+    }
+
+    fn with_overrides(
+        mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> io::Result<Self> {
+        let vars: BTreeMap<String, String> = vars.into_iter().collect();
+        let mut overrides = Vec::new();
+        for (var, section, key) in LEGACY_VARS {
+            if let Some(value) = vars.get(*var) {
+                overrides.push((*var, section.to_string(), key.to_string(), value));
+            }
+        }
+        for (var, value) in &vars {
+            let Some(name) = var.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let name = name.to_ascii_lowercase();
+            // Other EMBEDDENATOR_OBS_ variables are not config keys
+            let Some((section, key)) = ENV_SECTIONS.iter().find_map(|(prefix, section)| {
+                let key = name.strip_prefix(prefix)?.strip_prefix('_')?;
+                Some((section.to_string(), key.to_string()))
+            }) else {
+                continue;
+            };
+            overrides.push((var, section, key, value));
+        }
+        for (var, section, key, value) in overrides {
+            self.set(&section, &key, &toml_value(value))
+                .map_err(|msg| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", var, msg))
+                })?;
+        }
+        Ok(self)
+    }
+
+    fn set(&mut self, section: &str, key: &str, value: &str) -> Result<(), String> {
+        match (section, key) {
+            ("logging", "level") => {
+                let level = parse_string(value)?;
+                self.log_level = EventLevel::parse(&level)
+                    .ok_or_else(|| format!("unknown log level {}", level))?;
+            }
+            ("logging", "filter") => self.log_filter = Some(parse_string(value)?),
+            ("logging", "format") => {
+                let format = parse_string(value)?;
+                if !matches!(format.as_str(), "compact" | "pretty" | "json") {
+                    return Err(format!("unknown log format {}", format));
+                }
+                self.log_format = format;
+            }
+            ("logging", "output") => {
+                let output = parse_string(value)?;
+                self.log_output = LogOutput::parse(&output)
+                    .ok_or_else(|| format!("unknown log output {}", output))?;
+            }
+            ("logging", "file") => self.log_file = Some(PathBuf::from(parse_string(value)?)),
+            ("logging", "rotate") => {
+                let rotate = parse_string(value)?;
+                self.log_rotation = Rotation::parse(&rotate)
+                    .ok_or_else(|| format!("invalid rotation {}", rotate))?;
+            }
+            ("logging", "keep_files") => {
+                self.log_keep_files = value
+                    .parse()
+                    .map_err(|_| format!("invalid file count {}", value))?;
+            }
+            ("telemetry", "enabled") => self.telemetry_enabled = parse_bool(value)?,
+            ("telemetry", "sample_rate") => {
+                let rate: f64 = value
+                    .parse()
+                    .map_err(|_| format!("invalid sample rate {}", value))?;
+                if !(0.0..=1.0).contains(&rate) {
+                    return Err(format!("sample rate {} is outside 0.0 to 1.0", value));
+                }
+                self.sample_rate = rate;
+            }
+            ("telemetry", "snapshot_interval") => {
+                self.snapshot_interval = parse_duration(&parse_string(value)?)?
+            }
+            ("telemetry", "max_history_entries") => {
+                self.max_history_entries = value
+                    .parse()
+                    .map_err(|_| format!("invalid history size {}", value))?;
+            }
+            ("telemetry", "max_label_sets_per_metric") => {
+                self.max_label_sets_per_metric = value
+                    .parse()
+                    .map_err(|_| format!("invalid label set limit {}", value))?;
+            }
+            ("telemetry", "export_interval") => {
+                self.export_interval = parse_duration(&parse_string(value)?)?
+            }
+            ("sinks", sink) => {
+                self.sinks.insert(sink.to_string(), parse_bool(value)?);
+            }
+            ("exporters", exporter) => {
+                self.exporters
+                    .insert(exporter.to_string(), parse_string(value)?);
+            }
+            ("histogram", "buckets_us") => self.histogram_buckets = parse_buckets(value)?,
+            ("histogram.operations", operation) => {
+                self.operation_buckets
+                    .insert(operation.to_string(), parse_buckets(value)?);
+            }
+            _ => return Err(format!("unknown key {}.{}", section, key)),
+        }
+        Ok(())
+    }
+
+    /// Log file settings, if `logging.file` is set; pass to
+    /// [`logging::init_with_file`].
+    pub fn log_file(&self) -> Option<LogFile> {
+        self.log_file.as_ref().map(|path| {
+            LogFile::new(path)
+                .rotation(self.log_rotation)
+                .keep(self.log_keep_files)
+        })
+    }
+
+    /// Whether sink `name` is switched on.
+    pub fn sink_enabled(&self, name: &str) -> bool {
+        self.sinks.get(name).copied().unwrap_or(true)
+    }
+
+    /// Endpoint of exporter `name`, if configured.
+    pub fn exporter(&self, name: &str) -> Option<&str> {
+        self.exporters.get(name).map(String::as_str)
+    }
+
+    /// Telemetry settings; pass to [`Telemetry::new`].
+    pub fn telemetry_config(&self) -> TelemetryConfig {
+        TelemetryConfig {
+            enabled: self.telemetry_enabled,
+            sample_rate: self.sample_rate,
+            snapshot_interval: self.snapshot_interval,
+            max_history_entries: self.max_history_entries,
+            max_label_sets_per_metric: self.max_label_sets_per_metric,
+        }
+    }
+
+    /// Prometheus exporter with the configured histogram buckets.
+    pub fn prometheus_exporter(&self, prefix: impl Into<String>) -> PrometheusExporter {
+        self.operation_buckets.iter().fold(
+            PrometheusExporter::new(prefix).with_buckets(&self.histogram_buckets),
+            |exporter, (operation, buckets)| {
+                exporter.with_operation_buckets(operation.clone(), buckets)
+            },
+        )
+    }
+
+    /// Settings that differ in `other`, in file order.
+    pub fn diff(&self, other: &Self) -> Vec<ConfigChange> {
+        let mut changes = Vec::new();
+        let mut push = |key: String, old: String, new: String| {
+            if old != new {
+                changes.push(ConfigChange { key, old, new });
+            }
+        };
+        push(
+            "logging.level".to_string(),
+            self.log_level.as_str().to_lowercase(),
+            other.log_level.as_str().to_lowercase(),
+        );
+        push(
+            "logging.filter".to_string(),
+            self.log_filter.clone().unwrap_or_default(),
+            other.log_filter.clone().unwrap_or_default(),
+        );
+        push(
+            "logging.format".to_string(),
+            self.log_format.clone(),
+            other.log_format.clone(),
+        );
+        push(
+            "logging.output".to_string(),
+            self.log_output.as_str().to_string(),
+            other.log_output.as_str().to_string(),
+        );
+        let file = |config: &Self| {
+            config
+                .log_file
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default()
+        };
+        push("logging.file".to_string(), file(self), file(other));
+        push(
+            "logging.rotate".to_string(),
+            self.log_rotation.to_string(),
+            other.log_rotation.to_string(),
+        );
+        push(
+            "logging.keep_files".to_string(),
+            self.log_keep_files.to_string(),
+            other.log_keep_files.to_string(),
+        );
+        push(
+            "telemetry.enabled".to_string(),
+            self.telemetry_enabled.to_string(),
+            other.telemetry_enabled.to_string(),
+        );
+        push(
+            "telemetry.sample_rate".to_string(),
+            self.sample_rate.to_string(),
+            other.sample_rate.to_string(),
+        );
+        push(
+            "telemetry.snapshot_interval".to_string(),
+            format!("{:?}", self.snapshot_interval),
+            format!("{:?}", other.snapshot_interval),
+        );
+        push(
+            "telemetry.max_history_entries".to_string(),
+            self.max_history_entries.to_string(),
+            other.max_history_entries.to_string(),
+        );
+        push(
+            "telemetry.max_label_sets_per_metric".to_string(),
+            self.max_label_sets_per_metric.to_string(),
+            other.max_label_sets_per_metric.to_string(),
+        );
+        push(
+            "telemetry.export_interval".to_string(),
+            format!("{:?}", self.export_interval),
+            format!("{:?}", other.export_interval),
+        );
+        for sink in union(&self.sinks, &other.sinks) {
+            push(
+                format!("sinks.{}", sink),
+                self.sink_enabled(sink).to_string(),
+                other.sink_enabled(sink).to_string(),
+            );
+        }
+        for exporter in union(&self.exporters, &other.exporters) {
+            push(
+                format!("exporters.{}", exporter),
+                self.exporter(exporter).unwrap_or_default().to_string(),
+                other.exporter(exporter).unwrap_or_default().to_string(),
+            );
+        }
+        push(
+            "histogram.buckets_us".to_string(),
+            format!("{:?}", self.histogram_buckets),
+            format!("{:?}", other.histogram_buckets),
+        );
+        for operation in union(&self.operation_buckets, &other.operation_buckets) {
+            let buckets = |config: &Self| {
+                config
+                    .operation_buckets
+                    .get(operation)
+                    .map(|buckets| format!("{:?}", buckets))
+                    .unwrap_or_default()
+            };
+            push(
+                format!("histogram.operations.{}", operation),
+                buckets(self),
+                buckets(other),
+            );
+        }
+        if self.alerts != other.alerts {
+            changes.push(ConfigChange {
+                key: "alerts".to_string(),
+                old: format!("{} rules", self.alerts.len()),
+                new: format!("{} rules", other.alerts.len()),
+            });
+        }
+        changes
+    }
+
+    /// Put back `key`, as named by [`diff`](Self::diff), from `from`.
+    fn restore(&mut self, from: &Self, key: &str) {
+        fn entry<V: Clone>(to: &mut BTreeMap<String, V>, from: &BTreeMap<String, V>, name: &str) {
+            match from.get(name) {
+                Some(value) => to.insert(name.to_string(), value.clone()),
+                None => to.remove(name),
+            };
+        }
+        match key {
+            "logging.level" => self.log_level = from.log_level,
+            "logging.filter" => self.log_filter = from.log_filter.clone(),
+            "logging.format" => self.log_format = from.log_format.clone(),
+            "logging.output" => self.log_output = from.log_output,
+            "logging.file" => self.log_file = from.log_file.clone(),
+            "logging.rotate" => self.log_rotation = from.log_rotation,
+            "logging.keep_files" => self.log_keep_files = from.log_keep_files,
+            "telemetry.enabled" => self.telemetry_enabled = from.telemetry_enabled,
+            "telemetry.sample_rate" => self.sample_rate = from.sample_rate,
+            "telemetry.snapshot_interval" => self.snapshot_interval = from.snapshot_interval,
+            "telemetry.max_history_entries" => self.max_history_entries = from.max_history_entries,
+            "telemetry.max_label_sets_per_metric" => {
+                self.max_label_sets_per_metric = from.max_label_sets_per_metric
+            }
+            "telemetry.export_interval" => self.export_interval = from.export_interval,
+            "histogram.buckets_us" => self.histogram_buckets = from.histogram_buckets.clone(),
+            "alerts" => self.alerts = from.alerts.clone(),
+            key => {
+                if let Some(sink) = key.strip_prefix("sinks.") {
+                    entry(&mut self.sinks, &from.sinks, sink);
+                } else if let Some(exporter) = key.strip_prefix("exporters.") {
+                    entry(&mut self.exporters, &from.exporters, exporter);
+                } else if let Some(operation) = key.strip_prefix("histogram.operations.") {
+                    entry(
+                        &mut self.operation_buckets,
+                        &from.operation_buckets,
+                        operation,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Keys of either map, sorted.
+fn union<'a, V>(a: &'a BTreeMap<String, V>, b: &'a BTreeMap<String, V>) -> BTreeSet<&'a String> {
+    a.keys().chain(b.keys()).collect()
+}
+
+/// Plain YAML or environment value as TOML: numbers, booleans, arrays
+/// and double-quoted strings pass through, anything else is quoted.
+fn toml_value(value: &str) -> String {
+    let value = value.trim();
+    if value.starts_with('"')
+        || value.starts_with('[')
+        || matches!(value, "true" | "false")
+        || value.parse::<f64>().is_ok()
+    {
+        return value.to_string();
+    }
+    let value = value
+        .strip_prefix('\'')
+        .and_then(|v| v.strip_suffix('\''))
+        .unwrap_or(value);
+    // JSON string escapes are valid TOML basic-string ones
+    format!("\"{}\"", escape_json(value))
+}
+
+/// `[100, 500, 1000]`: sorted, deduplicated bucket bounds.
+fn parse_buckets(value: &str) -> Result<Vec<u64>, String> {
+    let invalid = || format!("expected an array of bucket bounds, got {}", value);
+    let inner = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or_else(invalid)?;
+    let mut buckets = inner
+        .split(',')
+        .map(str::trim)
+        .filter(|bound| !bound.is_empty())
+        .map(|bound| bound.parse().map_err(|_| invalid()))
+        .collect::<Result<Vec<u64>, _>>()?;
+    if buckets.is_empty() {
+        return Err(invalid());
+    }
+    buckets.sort_unstable();
+    buckets.dedup();
+    Ok(buckets)
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("expected true or false, got {}", value)),
+    }
+}
+
+/// One setting that changed between two configs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigChange {
+    /// `section.key`, or `alerts` for the alert rules
+    pub key: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl ConfigChange {
+    /// Why this change cannot be applied at runtime, if it cannot.
+    pub fn restart_reason(&self) -> Option<&'static str> {
+        RESTART_KEYS
+            .iter()
+            .find(|(key, _)| {
+                self.key
+                    .strip_prefix(key)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .map(|(_, reason)| *reason)
+    }
+}
+
+/// A change left unapplied, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedChange {
+    pub change: ConfigChange,
+    pub reason: String,
+}
+
+/// Outcome of a [`ConfigWatcher::reload`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReloadReport {
+    pub applied: Vec<ConfigChange>,
+    pub rejected: Vec<RejectedChange>,
+}
+
+type SinkCallback = Box<dyn Fn(bool) + Send>;
+type ExporterCallback = Box<dyn Fn(Option<&str>) -> io::Result<()> + Send>;
+type ChangeCallback = Box<dyn Fn(&ConfigChange) + Send>;
+
+/// Applies a config file to the running process and keeps it in sync.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    active: Option<ObservabilityConfig>,
+    telemetry: Option<Arc<Mutex<Telemetry>>>,
+    alerts: Option<AlertsHandle>,
+    sinks: HashMap<String, Vec<SinkCallback>>,
+    exporters: HashMap<String, Vec<ExporterCallback>>,
+    on_change: Vec<ChangeCallback>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            active: None,
+            telemetry: None,
+            alerts: None,
+            sinks: HashMap::new(),
+            exporters: HashMap::new(),
+            on_change: Vec::new(),
+        }
+    }
+
+    /// Apply `[telemetry]` settings to `telemetry`.
+    pub fn with_telemetry(mut self, telemetry: Arc<Mutex<Telemetry>>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Apply `[[alert]]` tables to `stream`'s threshold alerts.
+    pub fn with_stream(mut self, stream: &MetricStream) -> Self {
+        self.alerts = Some(stream.alerts_handle());
+        self
+    }
+
+    /// Call `callback` with the state of sink `name` on load and whenever
+    /// it is toggled.
+    pub fn on_sink<F>(mut self, name: impl Into<String>, callback: F) -> Self
+    where
+        F: Fn(bool) + Send + 'static,
+    {
+        self.sinks
+            .entry(name.into())
+            .or_default()
+            .push(Box::new(callback));
+        self
+    }
+
+    /// Call `callback` with the endpoint of exporter `name` (`None` when
+    /// removed) on load and whenever it changes.
+    ///
+    /// An error rejects the change and keeps the previous endpoint active;
+    /// changes to exporters without a callback are rejected too.
+    pub fn on_exporter<F>(mut self, name: impl Into<String>, callback: F) -> Self
+    where
+        F: Fn(Option<&str>) -> io::Result<()> + Send + 'static,
+    {
+        self.exporters
+            .entry(name.into())
+            .or_default()
+            .push(Box::new(callback));
+        self
+    }
+
+    /// Call `callback` for every applied change.
+    pub fn on_change<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&ConfigChange) + Send + 'static,
+    {
+        self.on_change.push(Box::new(callback));
+        self
+    }
+
+    /// Config currently in effect, once loaded.
+    pub fn active(&self) -> Option<&ObservabilityConfig> {
+        self.active.as_ref()
+    }
+
+    /// Read the file and apply all of it, including restart-only settings
+    /// the caller reads from [`active`](Self::active) at startup.
+    pub fn load(&mut self) -> io::Result<()> {
+        let config = ObservabilityConfig::from_file(&self.path)?;
+        logging::set_max_level(config.log_level);
+        if let Some(filter) = &config.log_filter {
+            if let Err(e) = logging::set_filter(filter) {
+                logging::warn(&format!("log filter {:?} not applied: {}", filter, e));
+            }
+        }
+        if let Some(telemetry) = &self.telemetry {
+            if let Ok(mut telemetry) = telemetry.lock() {
+                telemetry.set_enabled(config.telemetry_enabled);
+                telemetry.set_sample_rate(config.sample_rate);
+            }
+        }
+        for (name, callbacks) in &self.sinks {
+            let enabled = config.sink_enabled(name);
+            callbacks.iter().for_each(|callback| callback(enabled));
+        }
+        for (name, callbacks) in &self.exporters {
+            for callback in callbacks {
+                callback(config.exporter(name))?;
+            }
+        }
+        // Keep alerts added in code unless the file defines its own
+        if !config.alerts.is_empty() {
+            if let Some(alerts) = &self.alerts {
+                alerts.replace(config.alerts.clone());
+            }
+        }
+        self.active = Some(config);
+        Ok(())
+    }
+
+    /// Re-read the file, apply safe changes and reject the ones that need
+    /// a restart or that fail to apply. Loads the file if it was not
+    /// loaded yet.
+    pub fn reload(&mut self) -> io::Result<ReloadReport> {
+        let Some(active) = self.active.clone() else {
+            self.load()?;
+            return Ok(ReloadReport::default());
+        };
+        let mut next = ObservabilityConfig::from_file(&self.path)?;
+
+        let mut report = ReloadReport::default();
+        for change in active.diff(&next) {
+            let rejected = match change.restart_reason() {
+                Some(reason) => Err(format!("requires restart: {}", reason)),
+                None => self.apply(&change, &next),
+            };
+            if let Err(reason) = rejected {
+                record_event(
+                    EventLevel::Warn,
+                    "config change rejected",
+                    &[
+                        ("key", &change.key),
+                        ("old", &change.old),
+                        ("new", &change.new),
+                        ("reason", &reason),
+                    ],
+                );
+                // Left unapplied, so the active value stays
+                next.restore(&active, &change.key);
+                report.rejected.push(RejectedChange { change, reason });
+                continue;
+            }
+            record_event(
+                EventLevel::Info,
+                "config change applied",
+                &[
+                    ("key", &change.key),
+                    ("old", &change.old),
+                    ("new", &change.new),
+                ],
+            );
+            self.on_change.iter().for_each(|callback| callback(&change));
+            report.applied.push(change);
+        }
+        self.active = Some(next);
+        Ok(report)
+    }
+
+    fn apply(&self, change: &ConfigChange, config: &ObservabilityConfig) -> Result<(), String> {
+        match change.key.as_str() {
+            "logging.level" => logging::set_max_level(config.log_level),
+            "logging.filter" => {
+                let Some(filter) = &config.log_filter else {
+                    return Err("the startup filter returns on restart".to_string());
+                };
+                logging::set_filter(filter).map_err(|e| e.to_string())?;
+            }
+            "telemetry.enabled" | "telemetry.sample_rate" => {
+                if let Some(telemetry) = &self.telemetry {
+                    if let Ok(mut telemetry) = telemetry.lock() {
+                        telemetry.set_enabled(config.telemetry_enabled);
+                        telemetry.set_sample_rate(config.sample_rate);
+                    }
+                }
+            }
+            "alerts" => {
+                if let Some(alerts) = &self.alerts {
+                    alerts.replace(config.alerts.clone());
+                }
+            }
+            key => {
+                if let Some(sink) = key.strip_prefix("sinks.") {
+                    let enabled = config.sink_enabled(sink);
+                    for callback in self.sinks.get(sink).into_iter().flatten() {
+                        callback(enabled);
+                    }
+                } else if let Some(exporter) = key.strip_prefix("exporters.") {
+                    let callbacks = self
+                        .exporters
+                        .get(exporter)
+                        .ok_or_else(|| format!("no reload hook for exporter {}", exporter))?;
+                    for callback in callbacks {
+                        callback(config.exporter(exporter)).map_err(|e| e.to_string())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the file now, then check it every `interval` and reload it
+    /// when it changes, until the handle is stopped.
+    ///
+    /// As with [`AlertConfig::watch`](crate::obs::alert_config::AlertConfig::watch),
+    /// a change is picked up once the file stays the same for one
+    /// interval. Fails if the initial load fails; later errors are logged.
+    pub fn watch(self, interval: Duration) -> io::Result<CollectorHandle> {
+        ConfigReloader::new(self).watch(interval)
+    }
+}
+
+/// `SIGHUP`s received since [`ConfigReloader::reload_on_sighup`] first
+/// installed the handler.
+#[cfg(unix)]
+static SIGHUPS: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(unix)]
+extern "C" fn count_sighup(_signal: libc::c_int) {
+    SIGHUPS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A [`ConfigWatcher`] shared between its watch thread and callers that
+/// reload on demand. Clones share the watcher.
+#[derive(Clone)]
+pub struct ConfigReloader {
+    watcher: Arc<Mutex<ConfigWatcher>>,
+    #[cfg(unix)]
+    on_sighup: bool,
+}
+
+impl ConfigReloader {
+    pub fn new(watcher: ConfigWatcher) -> Self {
+        Self {
+            watcher: Arc::new(Mutex::new(watcher)),
+            #[cfg(unix)]
+            on_sighup: false,
+        }
+    }
+
+    /// Also reload from [`watch`](Self::watch) when the process receives
+    /// `SIGHUP`, within one interval. Installs a process-wide handler
+    /// replacing the default action, which would terminate the process.
+    #[cfg(unix)]
+    pub fn reload_on_sighup(mut self) -> Self {
+        static INSTALL: std::sync::Once = std::sync::Once::new();
+        INSTALL.call_once(|| {
+            let handler: extern "C" fn(libc::c_int) = count_sighup;
+            // SAFETY: the handler only increments an atomic, which is
+            // async-signal-safe.
+            unsafe {
+                libc::signal(libc::SIGHUP, handler as libc::sighandler_t);
+            }
+        });
+        self.on_sighup = true;
+        self
+    }
+
+    /// Reload now; see [`ConfigWatcher::reload`].
+    pub fn reload(&self) -> io::Result<ReloadReport> {
+        self.watcher
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .reload()
+    }
+
+    /// Config currently in effect, once loaded.
+    pub fn active(&self) -> Option<ObservabilityConfig> {
+        self.watcher
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .active()
+            .cloned()
+    }
+
+    /// Load the file now, then reload it every `interval` if it changed
+    /// or `SIGHUP` arrived, until the handle is stopped; see
+    /// [`ConfigWatcher::watch`].
+    pub fn watch(&self, interval: Duration) -> io::Result<CollectorHandle> {
+        let path = {
+            let mut watcher = self.watcher.lock().unwrap_or_else(|e| e.into_inner());
+            watcher.load()?;
+            watcher.path.clone()
+        };
+        let mut loaded = file_version(&path);
+        let mut seen = loaded;
+        #[cfg(unix)]
+        let mut hups = SIGHUPS.load(Ordering::Relaxed);
+        let reloader = self.clone();
+
+        Ok(spawn_periodic("obs-config", interval, move || {
+            let current = file_version(&path);
+            #[cfg(unix)]
+            let signaled = reloader.on_sighup && hups != SIGHUPS.load(Ordering::Relaxed);
+            #[cfg(not(unix))]
+            let signaled = false;
+            if !signaled {
+                if current != seen {
+                    seen = current;
+                    return;
+                }
+                if current == loaded {
+                    return;
+                }
+            }
+            #[cfg(unix)]
+            {
+                hups = SIGHUPS.load(Ordering::Relaxed);
+            }
+            (loaded, seen) = (current, current);
+            if let Err(e) = reloader.reload() {
+                logging::warn(&format!("keeping previous config, reload failed: {}", e));
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const CONFIG: &str = r#"
+[logging]
+level = "trace"
+format = "json"   # restart only
+file = "/var/log/obs.log"
+rotate = "daily"
+
+[telemetry]
+sample_rate = 0.5
+
+[sinks]
+statsd = false
+
+[[alert]]
+metric = "queue_depth"
+above = 100
+"#;
+
+    #[test]
+    fn test_parse_and_diff() {
+        let config = ObservabilityConfig::parse(CONFIG).unwrap();
+        assert_eq!(config.log_level, EventLevel::Trace);
+        assert_eq!(config.log_format, "json");
+        assert_eq!(
+            config.log_file(),
+            Some(LogFile::new("/var/log/obs.log").rotation(Rotation::Daily))
+        );
+        assert_eq!(config.sample_rate, 0.5);
+        assert!(config.telemetry_enabled);
+        assert!(!config.sink_enabled("statsd"));
+        assert!(config.sink_enabled("remote_write"));
+        assert_eq!(config.alerts[0].metric_pattern, "queue_depth");
+
+        let changes = ObservabilityConfig::default().diff(&config);
+        let keys: Vec<&str> = changes.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "logging.format",
+                "logging.file",
+                "logging.rotate",
+                "telemetry.sample_rate",
+                "sinks.statsd",
+                "alerts"
+            ]
+        );
+        assert!(changes[0].restart_reason().is_some());
+        assert!(changes[2].restart_reason().is_some());
+        assert!(changes[3].restart_reason().is_none());
+
+        assert!(ObservabilityConfig::parse("[telemetry]\nsample_rate = 2\n").is_err());
+        let err = ObservabilityConfig::parse("[tracing]\n").unwrap_err();
+        assert!(err.to_string().contains("unknown section"));
+        assert!(ObservabilityConfig::parse("level = \"info\"\n").is_err());
+    }
+
+    #[test]
+    fn test_yaml_and_env_overrides() {
+        let yaml = r#"
+logging:
+  level: info
+  output: syslog
+telemetry:
+  sample_rate: 0.5
+  export_interval: 10s
+exporters:
+  otlp: "http://collector:4318"   # quoted or not
+histogram:
+  buckets_us: [1000, 100, 500]
+  operations:
+    query: [10, 20]
+alert:
+- metric: queue_depth
+  above: 100
+  for: 1m
+- metric: cache_hit_ratio
+  below: 0.5
+"#;
+        let config = ObservabilityConfig::parse_yaml(yaml).unwrap();
+        assert_eq!(config.log_level, EventLevel::Info);
+        assert_eq!(config.log_output, LogOutput::Syslog);
+        assert_eq!(config.export_interval, Duration::from_secs(10));
+        assert_eq!(config.exporter("otlp"), Some("http://collector:4318"));
+        assert_eq!(config.histogram_buckets, [100, 500, 1000]);
+        assert_eq!(config.operation_buckets["query"], [10, 20]);
+        assert_eq!(config.alerts.len(), 2);
+        assert_eq!(config.alerts[0].for_duration, Some(Duration::from_secs(60)));
+        assert!(!config.alerts[1].above);
+
+        let toml = "[histogram.operations]\nquery = [10, 20]\n";
+        assert_eq!(
+            ObservabilityConfig::parse(toml).unwrap().operation_buckets,
+            config.operation_buckets
+        );
+        let err = ObservabilityConfig::parse_yaml("tracing:\n  level: info\n").unwrap_err();
+        assert!(err.to_string().contains("unknown section"));
+
+        let vars = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let config = config
+            .with_overrides(vars(&[
+                ("EMBEDDENATOR_LOG_FORMAT", "pretty"),
+                ("EMBEDDENATOR_OBS_LOGGING_FORMAT", "json"),
+                ("EMBEDDENATOR_OBS_TELEMETRY_SAMPLE_RATE", "0.1"),
+                ("EMBEDDENATOR_OBS_EXPORTERS_PROMETHEUS", "0.0.0.0:9898"),
+                ("EMBEDDENATOR_OBS_HISTOGRAM_OPERATIONS_INSERT", "[5]"),
+                ("EMBEDDENATOR_OBS_SOCKET", "/run/obs.sock"),
+            ]))
+            .unwrap();
+        assert_eq!(config.log_format, "json");
+        assert_eq!(config.sample_rate, 0.1);
+        assert_eq!(config.exporter("prometheus"), Some("0.0.0.0:9898"));
+        assert_eq!(config.operation_buckets["insert"], [5]);
+        assert_eq!(config.telemetry_config().sample_rate, 0.1);
+
+        let err = ObservabilityConfig::default()
+            .with_overrides(vars(&[("EMBEDDENATOR_OBS_TELEMETRY_SAMPLE_RATE", "2")]))
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("EMBEDDENATOR_OBS_TELEMETRY_SAMPLE_RATE: sample rate 2"));
+        assert_eq!(toml_value("'say \"hi\"'"), r#""say \"hi\"""#);
+        assert_eq!(toml_value("a\tb\r\u{1}"), r#""a\tb\r\u0001""#);
+    }
+
+    #[test]
+    fn test_reload_applies_safe_changes() {
+        let path = std::env::temp_dir().join(format!(
+            "embeddenator_obs_config_{}.toml",
+            std::process::id()
+        ));
+        fs::write(&path, CONFIG).unwrap();
+
+        let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+        let stream = MetricStream::with_rate_limit(Duration::ZERO);
+        let statsd = Arc::new(AtomicBool::new(true));
+        let toggle = statsd.clone();
+        let mut watcher = ConfigWatcher::new(&path)
+            .with_telemetry(telemetry.clone())
+            .with_stream(&stream)
+            .on_sink("statsd", move |on| toggle.store(on, Ordering::SeqCst));
+        watcher.load().unwrap();
+        assert_eq!(telemetry.lock().unwrap().config().sample_rate, 0.5);
+        assert!(!statsd.load(Ordering::SeqCst));
+        assert_eq!(stream.threshold_alerts().len(), 1);
+
+        fs::write(
+            &path,
+            CONFIG
+                .replace("0.5", "0.1")
+                .replace("\"json\"", "\"pretty\"")
+                .replace("statsd = false", "statsd = true")
+                .replace("above = 100", "above = 50"),
+        )
+        .unwrap();
+        let report = watcher.reload().unwrap();
+        let applied: Vec<&str> = report.applied.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(applied, ["telemetry.sample_rate", "sinks.statsd", "alerts"]);
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.rejected[0].change.key, "logging.format");
+        assert!(report.rejected[0].reason.starts_with("requires restart"));
+
+        assert_eq!(telemetry.lock().unwrap().config().sample_rate, 0.1);
+        assert!(statsd.load(Ordering::SeqCst));
+        assert_eq!(stream.threshold_alerts()[0].threshold, 50.0);
+        // The rejected format stays as loaded
+        assert_eq!(watcher.active().unwrap().log_format, "json");
+
+        let _ = fs::remove_file(&path);
+    }
+}
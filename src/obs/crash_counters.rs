@@ -0,0 +1,449 @@
+//! Crash-Consistent Counters File
+//!
+//! Periodically persists the raw built-in counter values to a small
+//! fixed-size file so that metrics from a process killed by the OOM
+//! killer (or any other `SIGKILL`) are not completely lost.
+//!
+//! # Format
+//!
+//! The file holds two fixed-size slots written alternately. Each slot
+//! carries a sequence number and a CRC32 checksum, so a write torn by the
+//! kill can never corrupt the last good record: on load, the valid slot
+//! with the highest sequence number wins.
+//!
+//! Records are written with positioned writes into the page cache, which
+//! survive process death exactly like stores into a shared mapping would,
+//! without requiring `unsafe` mmap handling.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::crash_counters::CountersFile;
+//!
+//! // Logs a "previous run ended abnormally" warning if applicable
+//! let (file, previous) = CountersFile::open("/var/lib/embeddenator/counters.bin")?;
+//! let handle = file.spawn(Duration::from_secs(5));
+//!
+//! // ... run ...
+//!
+//! handle.shutdown(); // final write + clean-shutdown marker
+//! ```
+
+use crate::obs::logging;
+use crate::obs::metrics::{metrics, MetricsSnapshot};
+use crate::obs::process::sleep_unless_stopped;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 8] = b"EOBSCNT1";
+
+/// Names of the persisted counters, in record order.
+pub const COUNTER_NAMES: [&str; 21] = [
+    "poison_recoveries_total",
+    "poison_path_inodes",
+    "poison_inodes",
+    "poison_inode_paths",
+    "poison_directories",
+    "poison_file_cache",
+    "sub_cache_hits",
+    "sub_cache_misses",
+    "sub_cache_evictions",
+    "index_cache_hits",
+    "index_cache_misses",
+    "index_cache_evictions",
+    "retrieval_query_calls",
+    "retrieval_query_ns_total",
+    "retrieval_query_ns_max",
+    "rerank_calls",
+    "rerank_ns_total",
+    "rerank_ns_max",
+    "hier_query_calls",
+    "hier_query_ns_total",
+    "hier_query_ns_max",
+];
+
+/// Header: magic(8) + seq(8) + timestamp(8) + pid(4) + clean(4) + count(4)
+const HEADER_LEN: usize = 36;
+const SLOT_LEN: usize = HEADER_LEN + COUNTER_NAMES.len() * 8 + 4;
+
+fn counter_values(s: &MetricsSnapshot) -> [u64; COUNTER_NAMES.len()] {
+    [
+        s.poison_recoveries_total,
+        s.poison_path_inodes,
+        s.poison_inodes,
+        s.poison_inode_paths,
+        s.poison_directories,
+        s.poison_file_cache,
+        s.sub_cache_hits,
+        s.sub_cache_misses,
+        s.sub_cache_evictions,
+        s.index_cache_hits,
+        s.index_cache_misses,
+        s.index_cache_evictions,
+        s.retrieval_query_calls,
+        s.retrieval_query_ns_total,
+        s.retrieval_query_ns_max,
+        s.rerank_calls,
+        s.rerank_ns_total,
+        s.rerank_ns_max,
+        s.hier_query_calls,
+        s.hier_query_ns_total,
+        s.hier_query_ns_max,
+    ]
+}
+
+/// Last record left behind by a previous run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviousRun {
+    /// Process ID of the previous run
+    pub pid: u32,
+    /// Time of the last write (seconds since epoch)
+    pub timestamp_secs: u64,
+    /// False if the previous run never recorded a clean shutdown
+    pub clean_shutdown: bool,
+    /// Counter values by name
+    pub counters: Vec<(&'static str, u64)>,
+}
+
+impl PreviousRun {
+    /// Whether the previous run ended without a clean shutdown.
+    pub fn ended_abnormally(&self) -> bool {
+        !self.clean_shutdown
+    }
+
+    /// Look up a counter value by name.
+    pub fn counter(&self, name: &str) -> Option<u64> {
+        self.counters
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+    }
+}
+
+/// Fixed-size counters file with alternating checksummed slots.
+pub struct CountersFile {
+    file: File,
+    seq: u64,
+}
+
+impl CountersFile {
+    /// Open (or create) the counters file, returning the previous run's
+    /// last record if one is present.
+    ///
+    /// If the previous run ended abnormally a warning with its last
+    /// counter values is logged.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<(Self, Option<PreviousRun>)> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut buf = vec![0u8; SLOT_LEN * 2];
+        let read = read_at(&file, &mut buf, 0)?;
+        buf.truncate(read);
+
+        let previous = [0, SLOT_LEN]
+            .iter()
+            .filter_map(|&offset| buf.get(offset..offset + SLOT_LEN))
+            .filter_map(decode_slot)
+            .max_by_key(|(seq, _)| *seq);
+
+        file.set_len((SLOT_LEN * 2) as u64)?;
+        let mut counters_file = Self {
+            file,
+            seq: previous.as_ref().map(|(seq, _)| *seq).unwrap_or(0),
+        };
+
+        let previous = previous.map(|(_, run)| run);
+        if let Some(run) = previous.as_ref().filter(|run| run.ended_abnormally()) {
+            let values: Vec<String> = run
+                .counters
+                .iter()
+                .filter(|(_, v)| *v > 0)
+                .map(|(n, v)| format!("{}={}", n, v))
+                .collect();
+            logging::warn(&format!(
+                "previous run ended abnormally (pid {}, last write at {}): {}",
+                run.pid,
+                run.timestamp_secs,
+                values.join(", ")
+            ));
+        }
+
+        // Mark this run as in progress immediately
+        counters_file.write_snapshot(&MetricsSnapshot::default())?;
+        Ok((counters_file, previous))
+    }
+
+    /// Persist a snapshot's counter values.
+    pub fn write_snapshot(&mut self, snapshot: &MetricsSnapshot) -> io::Result<()> {
+        self.write_record(snapshot, false)
+    }
+
+    /// Persist a final snapshot and record a clean shutdown.
+    pub fn mark_clean_shutdown(&mut self, snapshot: &MetricsSnapshot) -> io::Result<()> {
+        self.write_record(snapshot, true)
+    }
+
+    /// Persist the global metrics every `interval` on a background thread.
+    pub fn spawn(self, interval: Duration) -> CountersFileHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+
+        let thread = std::thread::Builder::new()
+            .name("obs-crash-counters".to_string())
+            .spawn(move || {
+                let mut file = self;
+                while !stop_flag.load(Ordering::Relaxed) {
+                    let _ = file.write_snapshot(&metrics().snapshot());
+                    sleep_unless_stopped(interval, &stop_flag);
+                }
+                let _ = file.mark_clean_shutdown(&metrics().snapshot());
+            })
+            .expect("failed to spawn crash counters thread");
+
+        CountersFileHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    fn write_record(&mut self, snapshot: &MetricsSnapshot, clean: bool) -> io::Result<()> {
+        self.seq += 1;
+        let slot = encode_slot(
+            self.seq,
+            unix_secs(),
+            std::process::id(),
+            clean,
+            &counter_values(snapshot),
+        );
+        let offset = if self.seq & 1 == 0 { SLOT_LEN } else { 0 };
+        write_at(&self.file, &slot, offset as u64)
+    }
+}
+
+/// Handle to the background writer; records a clean shutdown when stopped.
+pub struct CountersFileHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl CountersFileHandle {
+    /// Stop the writer, performing a final write with the clean marker.
+    pub fn shutdown(mut self) {
+        self.stop_thread();
+    }
+
+    fn stop_thread(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for CountersFileHandle {
+    fn drop(&mut self) {
+        self.stop_thread();
+    }
+}
+
+fn encode_slot(seq: u64, timestamp: u64, pid: u32, clean: bool, values: &[u64]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(SLOT_LEN);
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&seq.to_le_bytes());
+    buf.extend_from_slice(&timestamp.to_le_bytes());
+    buf.extend_from_slice(&pid.to_le_bytes());
+    buf.extend_from_slice(&u32::from(clean).to_le_bytes());
+    buf.extend_from_slice(&(values.len() as u32).to_le_bytes());
+    for v in values {
+        buf.extend_from_slice(&v.to_le_bytes());
+    }
+    let crc = crc32(&buf);
+    buf.extend_from_slice(&crc.to_le_bytes());
+    buf
+}
+
+fn decode_slot(slot: &[u8]) -> Option<(u64, PreviousRun)> {
+    if slot.len() < HEADER_LEN + 4 || &slot[0..8] != MAGIC {
+        return None;
+    }
+    let u64_at = |i: usize| u64::from_le_bytes(slot[i..i + 8].try_into().unwrap());
+    let u32_at = |i: usize| u32::from_le_bytes(slot[i..i + 4].try_into().unwrap());
+
+    let count = u32_at(32) as usize;
+    if count != COUNTER_NAMES.len() {
+        return None;
+    }
+    let body_len = HEADER_LEN + count * 8;
+    if crc32(&slot[..body_len]) != u32_at(body_len) {
+        return None;
+    }
+
+    let counters = COUNTER_NAMES
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (*name, u64_at(HEADER_LEN + i * 8)))
+        .collect();
+
+    Some((
+        u64_at(8),
+        PreviousRun {
+            pid: u32_at(24),
+            timestamp_secs: u64_at(16),
+            clean_shutdown: u32_at(28) != 0,
+            counters,
+        },
+    ))
+}
+
+/// CRC-32 (IEEE 802.3), bitwise; records are only a few hundred bytes.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(buf, offset)
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::os::unix::fs::FileExt;
+    let mut total = 0;
+    while total < buf.len() {
+        match file.read_at(&mut buf[total..], offset + total as u64)? {
+            0 => break,
+            n => total += n,
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(not(unix))]
+fn write_at(mut file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    use std::io::{Seek, SeekFrom, Write};
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(buf)
+}
+
+#[cfg(not(unix))]
+fn read_at(mut file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::io::{Read, Seek, SeekFrom};
+    file.seek(SeekFrom::Start(offset))?;
+    let mut total = 0;
+    while total < buf.len() {
+        match file.read(&mut buf[total..])? {
+            0 => break,
+            n => total += n,
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "embeddenator_obs_{}_{}.bin",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_crc32_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_abnormal_end_detected() {
+        let path = temp_path("abnormal");
+        std::fs::remove_file(&path).ok();
+
+        let (mut file, previous) = CountersFile::open(&path).unwrap();
+        assert!(previous.is_none());
+
+        let snapshot = MetricsSnapshot {
+            sub_cache_hits: 42,
+            rerank_calls: 7,
+            ..Default::default()
+        };
+        file.write_snapshot(&snapshot).unwrap();
+        drop(file); // simulate being killed: no clean marker
+
+        let (_file, previous) = CountersFile::open(&path).unwrap();
+        let previous = previous.unwrap();
+        assert!(previous.ended_abnormally());
+        assert_eq!(previous.pid, std::process::id());
+        assert_eq!(previous.counter("sub_cache_hits"), Some(42));
+        assert_eq!(previous.counter("rerank_calls"), Some(7));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_clean_shutdown_and_torn_slot() {
+        let path = temp_path("clean");
+        std::fs::remove_file(&path).ok();
+
+        let (mut file, _) = CountersFile::open(&path).unwrap();
+        let snapshot = MetricsSnapshot {
+            index_cache_hits: 3,
+            ..Default::default()
+        };
+        file.mark_clean_shutdown(&snapshot).unwrap();
+        let clean_seq = file.seq;
+
+        // Corrupt the other slot (as if a later write was torn)
+        let other = if clean_seq & 1 == 0 { 0 } else { SLOT_LEN };
+        write_at(&file.file, &[0xFF; 16], (other + HEADER_LEN) as u64).unwrap();
+        drop(file);
+
+        let (_file, previous) = CountersFile::open(&path).unwrap();
+        let previous = previous.unwrap();
+        assert!(!previous.ended_abnormally());
+        assert_eq!(previous.counter("index_cache_hits"), Some(3));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_background_writer_marks_clean() {
+        let path = temp_path("spawn");
+        std::fs::remove_file(&path).ok();
+
+        let (file, _) = CountersFile::open(&path).unwrap();
+        file.spawn(Duration::from_secs(60)).shutdown();
+
+        let (_file, previous) = CountersFile::open(&path).unwrap();
+        assert!(previous.unwrap().clean_shutdown);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,400 @@
+//! Criterion-Compatible Output
+//!
+//! Emits `TestMetrics` results in the JSON formats understood by
+//! Criterion's tooling, so existing benchmark comparison scripts can
+//! consume internal measurements without reformatting.
+//!
+//! # Formats
+//!
+//! - `cargo-criterion --message-format=json` "benchmark-complete" messages
+//! - On-disk `estimates.json` / `benchmark.json` / `sample.json` files in
+//!   Criterion's directory layout (read by `critcmp`)
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::criterion::CriterionAdapter;
+//!
+//! let adapter = CriterionAdapter::new(&metrics).group("retrieval");
+//! println!("{}", adapter.to_message_json());
+//!
+//! // critcmp target/criterion -- compares "main" against other baselines
+//! adapter.write_baseline("target/criterion", "main")?;
+//! ```
+//!
+//! Each recorded timing sample is treated as one iteration.
+
+use crate::obs::telemetry::escape_json;
+use crate::obs::test_metrics::TestMetrics;
+use std::fmt::Write;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// z-score for the 95% confidence intervals Criterion reports by default.
+const Z_95: f64 = 1.96;
+
+/// Scale factor making MAD a consistent estimator of standard deviation
+/// (matches Criterion's `median_abs_dev`).
+const MAD_SCALE: f64 = 1.4826;
+
+/// Adapter producing Criterion-format output for a `TestMetrics`.
+pub struct CriterionAdapter<'a> {
+    metrics: &'a TestMetrics,
+    group: Option<String>,
+}
+
+/// A point estimate with a 95% confidence interval (nanoseconds).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CriterionEstimate {
+    pub point_estimate: f64,
+    pub lower_bound: f64,
+    pub upper_bound: f64,
+    pub standard_error: f64,
+}
+
+/// Estimates in the shape of Criterion's `estimates.json`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CriterionEstimates {
+    pub mean: CriterionEstimate,
+    pub median: CriterionEstimate,
+    pub median_abs_dev: CriterionEstimate,
+    pub std_dev: CriterionEstimate,
+}
+
+impl<'a> CriterionAdapter<'a> {
+    /// Create adapter for a metrics collector.
+    pub fn new(metrics: &'a TestMetrics) -> Self {
+        Self {
+            metrics,
+            group: None,
+        }
+    }
+
+    /// Place the benchmark in a Criterion group (`group/name` id).
+    pub fn group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Full benchmark id (`group/name` or `name`).
+    pub fn full_id(&self) -> String {
+        match &self.group {
+            Some(group) => format!("{}/{}", group, self.metrics.name),
+            None => self.metrics.name.clone(),
+        }
+    }
+
+    /// Directory name Criterion would use for this benchmark.
+    pub fn directory_name(&self) -> String {
+        self.full_id()
+            .split('/')
+            .map(sanitize_path_component)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Compute Criterion-style estimates from the recorded samples.
+    pub fn estimates(&self) -> CriterionEstimates {
+        let samples: Vec<f64> = self.metrics.timings_ns.iter().map(|&t| t as f64).collect();
+        if samples.is_empty() {
+            return CriterionEstimates::default();
+        }
+
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = if samples.len() > 1 {
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        let std_dev = variance.sqrt();
+        let mean_se = std_dev / n.sqrt();
+
+        let mut sorted = samples.clone();
+        sorted.sort_by(f64::total_cmp);
+        let median = median_of_sorted(&sorted);
+        // Rank-based CI for the median: n/2 ± z*sqrt(n)/2
+        let half_width = Z_95 * n.sqrt() / 2.0;
+        let lo_idx = ((n / 2.0 - half_width).floor().max(0.0)) as usize;
+        let hi_idx = ((n / 2.0 + half_width).ceil() as usize).min(sorted.len() - 1);
+        // Asymptotic SE of the median for a normal distribution
+        let median_se = 1.2533 * mean_se;
+
+        let mut deviations: Vec<f64> = sorted.iter().map(|x| (x - median).abs()).collect();
+        deviations.sort_by(f64::total_cmp);
+        let mad = median_of_sorted(&deviations) * MAD_SCALE;
+
+        // SE of the standard deviation: s / sqrt(2(n-1))
+        let std_se = if samples.len() > 1 {
+            std_dev / (2.0 * (n - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+
+        CriterionEstimates {
+            mean: CriterionEstimate::symmetric(mean, mean_se),
+            median: CriterionEstimate {
+                point_estimate: median,
+                lower_bound: sorted[lo_idx].min(median),
+                upper_bound: sorted[hi_idx].max(median),
+                standard_error: median_se,
+            },
+            median_abs_dev: CriterionEstimate::symmetric(mad, std_se * MAD_SCALE),
+            std_dev: CriterionEstimate::symmetric(std_dev, std_se),
+        }
+    }
+
+    /// `benchmark-complete` message as emitted by
+    /// `cargo criterion --message-format=json` (single line).
+    pub fn to_message_json(&self) -> String {
+        let est = self.estimates();
+        let mut json = String::new();
+        write!(json, r#"{{"reason":"benchmark-complete","#).unwrap();
+        write!(json, r#""id":"{}","#, escape_json(&self.full_id())).unwrap();
+        write!(
+            json,
+            r#""report_directory":"target/criterion/reports/{}","#,
+            escape_json(&self.directory_name())
+        )
+        .unwrap();
+        write!(json, r#""iteration_count":["#).unwrap();
+        write_joined(&mut json, self.metrics.timings_ns.iter().map(|_| 1u64));
+        write!(json, r#"],"measured_values":["#).unwrap();
+        write_joined(&mut json, self.metrics.timings_ns.iter());
+        write!(json, r#"],"unit":"ns","throughput":[],"#).unwrap();
+        write!(json, r#""typical":{},"#, message_estimate(&est.mean)).unwrap();
+        write!(json, r#""mean":{},"#, message_estimate(&est.mean)).unwrap();
+        write!(json, r#""median":{},"#, message_estimate(&est.median)).unwrap();
+        write!(
+            json,
+            r#""median_abs_dev":{},"#,
+            message_estimate(&est.median_abs_dev)
+        )
+        .unwrap();
+        write!(json, r#""slope":null,"change":null}}"#).unwrap();
+        json
+    }
+
+    /// Contents of Criterion's `estimates.json`.
+    pub fn estimates_json(&self) -> String {
+        let est = self.estimates();
+        format!(
+            r#"{{"mean":{},"median":{},"median_abs_dev":{},"slope":null,"std_dev":{}}}"#,
+            file_estimate(&est.mean),
+            file_estimate(&est.median),
+            file_estimate(&est.median_abs_dev),
+            file_estimate(&est.std_dev),
+        )
+    }
+
+    /// Contents of Criterion's `benchmark.json`.
+    pub fn benchmark_json(&self) -> String {
+        let (group_id, function_id) = match &self.group {
+            Some(group) => (
+                escape_json(group),
+                format!(r#""{}""#, escape_json(&self.metrics.name)),
+            ),
+            None => (escape_json(&self.metrics.name), "null".to_string()),
+        };
+        format!(
+            r#"{{"group_id":"{}","function_id":{},"value_str":null,"throughput":null,"full_id":"{}","directory_name":"{}","title":"{}"}}"#,
+            group_id,
+            function_id,
+            escape_json(&self.full_id()),
+            escape_json(&self.directory_name()),
+            escape_json(&self.full_id()),
+        )
+    }
+
+    /// Contents of Criterion's `sample.json`.
+    pub fn sample_json(&self) -> String {
+        let mut json = String::from(r#"{"sampling_mode":"Flat","iters":["#);
+        write_joined(
+            &mut json,
+            self.metrics.timings_ns.iter().map(|_| "1.0".to_string()),
+        );
+        json.push_str(r#"],"times":["#);
+        write_joined(
+            &mut json,
+            self.metrics
+                .timings_ns
+                .iter()
+                .map(|&t| format!("{:.1}", t as f64)),
+        );
+        json.push_str("]}");
+        json
+    }
+
+    /// Write `estimates.json`, `benchmark.json` and `sample.json` under
+    /// `<criterion_dir>/<directory_name>/<baseline>/`, as `critcmp` expects.
+    ///
+    /// Returns the baseline directory that was written.
+    pub fn write_baseline(
+        &self,
+        criterion_dir: impl AsRef<Path>,
+        baseline: &str,
+    ) -> io::Result<PathBuf> {
+        let dir = criterion_dir
+            .as_ref()
+            .join(self.directory_name())
+            .join(sanitize_path_component(baseline));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("estimates.json"), self.estimates_json())?;
+        std::fs::write(dir.join("benchmark.json"), self.benchmark_json())?;
+        std::fs::write(dir.join("sample.json"), self.sample_json())?;
+        Ok(dir)
+    }
+}
+
+impl CriterionEstimate {
+    fn symmetric(point: f64, standard_error: f64) -> Self {
+        Self {
+            point_estimate: point,
+            lower_bound: (point - Z_95 * standard_error).max(0.0),
+            upper_bound: point + Z_95 * standard_error,
+            standard_error,
+        }
+    }
+}
+
+fn median_of_sorted(sorted: &[f64]) -> f64 {
+    let n = sorted.len();
+    if n == 0 {
+        0.0
+    } else if n % 2 == 1 {
+        sorted[n / 2]
+    } else {
+        (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+    }
+}
+
+fn message_estimate(e: &CriterionEstimate) -> String {
+    format!(
+        r#"{{"estimate":{:?},"lower_bound":{:?},"upper_bound":{:?},"unit":"ns"}}"#,
+        e.point_estimate, e.lower_bound, e.upper_bound
+    )
+}
+
+fn file_estimate(e: &CriterionEstimate) -> String {
+    format!(
+        r#"{{"confidence_interval":{{"confidence_level":0.95,"lower_bound":{:?},"upper_bound":{:?}}},"point_estimate":{:?},"standard_error":{:?}}}"#,
+        e.lower_bound, e.upper_bound, e.point_estimate, e.standard_error
+    )
+}
+
+fn write_joined<T: std::fmt::Display>(out: &mut String, items: impl Iterator<Item = T>) {
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write!(out, "{}", item).unwrap();
+    }
+}
+
+/// Replace characters Criterion does not allow in directory names, and
+/// runs of dots, so no component can be `.` or `..` and leave the
+/// Criterion directory.
+fn sanitize_path_component(s: &str) -> String {
+    let mut sanitized = String::with_capacity(s.len());
+    let mut chars = s.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' if chars.peek() == Some(&'.') => {
+                while chars.next_if_eq(&'.').is_some() {}
+                sanitized.push('_');
+            }
+            '?' | '"' | '/' | '\\' | '*' | '<' | '>' | ':' | '|' | '^' => sanitized.push('_'),
+            _ => sanitized.push(c),
+        }
+    }
+    match sanitized.as_str() {
+        "" | "." => "_".to_string(),
+        _ => sanitized,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_metrics() -> TestMetrics {
+        let mut metrics = TestMetrics::new("lookup");
+        metrics.timings_ns = vec![100, 200, 300, 400, 500];
+        metrics
+    }
+
+    #[test]
+    fn test_estimates() {
+        let metrics = sample_metrics();
+        let est = CriterionAdapter::new(&metrics).estimates();
+
+        assert_eq!(est.mean.point_estimate, 300.0);
+        assert_eq!(est.median.point_estimate, 300.0);
+        assert!(est.mean.lower_bound < 300.0 && est.mean.upper_bound > 300.0);
+        assert!(est.std_dev.point_estimate > 0.0);
+        assert!((est.median_abs_dev.point_estimate - 100.0 * MAD_SCALE).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_message_json() {
+        let metrics = sample_metrics();
+        let json = CriterionAdapter::new(&metrics)
+            .group("index")
+            .to_message_json();
+
+        assert!(json.starts_with(r#"{"reason":"benchmark-complete","id":"index/lookup""#));
+        assert!(json.contains(r#""measured_values":[100,200,300,400,500]"#));
+        assert!(json.contains(r#""iteration_count":[1,1,1,1,1]"#));
+        assert!(json.contains(r#""typical":{"estimate":300.0"#));
+        assert!(!json.contains('\n'));
+    }
+
+    #[test]
+    fn test_write_baseline() {
+        let dir =
+            std::env::temp_dir().join(format!("embeddenator_obs_crit_{}", std::process::id()));
+        let metrics = sample_metrics();
+        let written = CriterionAdapter::new(&metrics)
+            .group("index")
+            .write_baseline(&dir, "main")
+            .unwrap();
+
+        assert_eq!(written, dir.join("index").join("lookup").join("main"));
+        let estimates = std::fs::read_to_string(written.join("estimates.json")).unwrap();
+        assert!(estimates.contains(r#""point_estimate":300.0"#));
+        let benchmark = std::fs::read_to_string(written.join("benchmark.json")).unwrap();
+        assert!(benchmark.contains(r#""full_id":"index/lookup""#));
+        assert!(written.join("sample.json").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_directory_name_stays_inside() {
+        let metrics = TestMetrics::new("../../etc");
+        let adapter = CriterionAdapter::new(&metrics).group("/tmp/../x");
+        assert_eq!(adapter.directory_name(), "_/tmp/_/x/_/_/etc");
+        assert!(adapter
+            .directory_name()
+            .split('/')
+            .all(|part| !matches!(part, "" | "." | "..")));
+
+        let metrics = TestMetrics::new("v1.2 lookup: a\\b");
+        assert_eq!(
+            CriterionAdapter::new(&metrics).directory_name(),
+            "v1.2 lookup_ a_b"
+        );
+        let metrics = TestMetrics::new(".");
+        assert_eq!(CriterionAdapter::new(&metrics).directory_name(), "_");
+    }
+
+    #[test]
+    fn test_empty_metrics() {
+        let metrics = TestMetrics::new("empty");
+        let adapter = CriterionAdapter::new(&metrics);
+        assert_eq!(adapter.estimates(), CriterionEstimates::default());
+        assert!(adapter
+            .to_message_json()
+            .contains(r#""measured_values":[]"#));
+    }
+}
@@ -0,0 +1,277 @@
+//! Alert Notification Digest
+//!
+//! Batches alerts over a window and delivers one summary per window instead
+//! of one notification per alert, reducing alert fatigue from noisy
+//! metrics. Each alert key is summarized with its count, first and last
+//! occurrence, and latest/min/max value.
+//!
+//! The digest is a cloneable handle: feed it from any alert source
+//! (threshold events, disk or pressure callbacks) and deliver the summary
+//! through [`on_digest`](AlertDigest::on_digest) callbacks, e.g. a webhook
+//! or mail relay. Empty windows produce no notification.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::digest::AlertDigest;
+//!
+//! let digest = AlertDigest::new().on_digest(|d| send_webhook(&d.format()));
+//!
+//! let feed = digest.clone();
+//! let pressure = PressureCollector::new()
+//!     .alert_above("io", 20.0)
+//!     .on_alert(move |a| feed.record(format!("pressure_{}", a.resource), a.avg10, "PSI above threshold"));
+//!
+//! let stream_feed = digest.clone();
+//! stream.subscribe(move |event| stream_feed.record_event(event));
+//!
+//! let _handle = digest.spawn(Duration::from_secs(300));
+//! ```
+
+use crate::obs::process::{spawn_sink, CollectorHandle};
+use crate::obs::streaming::MetricEvent;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Summary of one alert key within a window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigestEntry {
+    pub key: String,
+    pub count: u64,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+    /// Value reported by the most recent alert
+    pub last_value: f64,
+    pub min_value: f64,
+    pub max_value: f64,
+    /// Message of the most recent alert
+    pub message: String,
+}
+
+/// Alerts batched over one window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Digest {
+    pub window_start: SystemTime,
+    pub window_end: SystemTime,
+    /// Entries sorted by key
+    pub entries: Vec<DigestEntry>,
+}
+
+impl Digest {
+    /// Alerts across all keys.
+    pub fn total_alerts(&self) -> u64 {
+        self.entries.iter().map(|e| e.count).sum()
+    }
+
+    /// Format as human-readable summary; times are offsets from the window
+    /// start.
+    pub fn format(&self) -> String {
+        let offset = |t: SystemTime| {
+            t.duration_since(self.window_start)
+                .unwrap_or_default()
+                .as_secs_f64()
+        };
+        let mut output = format!(
+            "=== Alert digest: {} alerts across {} keys in {:.0}s ===\n",
+            self.total_alerts(),
+            self.entries.len(),
+            offset(self.window_end)
+        );
+        for entry in &self.entries {
+            output.push_str(&format!(
+                "  {}: {}x, first +{:.1}s, last +{:.1}s, value {:.2} (min {:.2}, max {:.2}) - {}\n",
+                entry.key,
+                entry.count,
+                offset(entry.first_seen),
+                offset(entry.last_seen),
+                entry.last_value,
+                entry.min_value,
+                entry.max_value,
+                entry.message
+            ));
+        }
+        output
+    }
+}
+
+/// Callback invoked with each non-empty digest.
+pub type DigestCallback = Arc<dyn Fn(&Digest) + Send + Sync>;
+
+struct DigestState {
+    window_start: SystemTime,
+    entries: BTreeMap<String, DigestEntry>,
+}
+
+/// Cloneable alert batcher; clones share the same window.
+#[derive(Clone)]
+pub struct AlertDigest {
+    state: Arc<Mutex<DigestState>>,
+    callbacks: Vec<DigestCallback>,
+}
+
+impl Default for AlertDigest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlertDigest {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(DigestState {
+                window_start: SystemTime::now(),
+                entries: BTreeMap::new(),
+            })),
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Invoke `callback` with each non-empty digest on [`flush`](Self::flush).
+    pub fn on_digest<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Digest) + Send + Sync + 'static,
+    {
+        self.callbacks.push(Arc::new(callback));
+        self
+    }
+
+    /// Add one alert occurrence to the current window.
+    pub fn record(&self, key: impl Into<String>, value: f64, message: impl Into<String>) {
+        let now = SystemTime::now();
+        let key = key.into();
+        let message = message.into();
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        state
+            .entries
+            .entry(key.clone())
+            .and_modify(|entry| {
+                entry.count += 1;
+                entry.last_seen = now;
+                entry.last_value = value;
+                entry.min_value = entry.min_value.min(value);
+                entry.max_value = entry.max_value.max(value);
+                entry.message.clone_from(&message);
+            })
+            .or_insert_with(|| DigestEntry {
+                key,
+                count: 1,
+                first_seen: now,
+                last_seen: now,
+                last_value: value,
+                min_value: value,
+                max_value: value,
+                message,
+            });
+    }
+
+    /// Record [`MetricEvent::ThresholdExceeded`] events; others are ignored.
+    pub fn record_event(&self, event: &MetricEvent) {
+        if let MetricEvent::ThresholdExceeded(metric, value, threshold) = event {
+            self.record(
+                metric.as_str(),
+                *value,
+                format!("threshold {} exceeded", threshold),
+            );
+        }
+    }
+
+    /// Alerts recorded in the current window.
+    pub fn pending(&self) -> u64 {
+        self.state
+            .lock()
+            .map(|s| s.entries.values().map(|e| e.count).sum())
+            .unwrap_or(0)
+    }
+
+    /// Close the current window and deliver its digest to the callbacks.
+    ///
+    /// Returns `None` (and notifies nobody) when no alerts were recorded.
+    pub fn flush(&self) -> Option<Digest> {
+        let now = SystemTime::now();
+        let digest = {
+            let mut state = self.state.lock().ok()?;
+            let window_start = std::mem::replace(&mut state.window_start, now);
+            let entries = std::mem::take(&mut state.entries);
+            if entries.is_empty() {
+                return None;
+            }
+            Digest {
+                window_start,
+                window_end: now,
+                entries: entries.into_values().collect(),
+            }
+        };
+        for callback in &self.callbacks {
+            callback(&digest);
+        }
+        Some(digest)
+    }
+
+    /// Flush every `window` on a background thread.
+    pub fn spawn(self, window: Duration) -> CollectorHandle {
+        spawn_sink("obs-alert-digest", window, move || {
+            self.flush();
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_batches_alerts_per_key() {
+        let digest = AlertDigest::new();
+        digest.record("cpu", 91.0, "cpu high");
+        digest.record("cpu", 97.5, "cpu very high");
+        digest.record("cpu", 93.0, "cpu high");
+        digest.record("disk", 0.04, "low space");
+        assert_eq!(digest.pending(), 4);
+
+        let report = digest.flush().unwrap();
+        assert_eq!(report.total_alerts(), 4);
+        let cpu = &report.entries[0];
+        assert_eq!((cpu.key.as_str(), cpu.count), ("cpu", 3));
+        assert_eq!(
+            (cpu.min_value, cpu.max_value, cpu.last_value),
+            (91.0, 97.5, 93.0)
+        );
+        assert_eq!(cpu.message, "cpu high");
+        assert!(cpu.first_seen <= cpu.last_seen);
+        assert!(report.format().contains("4 alerts across 2 keys"));
+        assert!(report.format().contains("  cpu: 3x"));
+
+        assert_eq!(digest.pending(), 0);
+    }
+
+    #[test]
+    fn test_one_notification_per_window() {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let sent_clone = sent.clone();
+        let digest = AlertDigest::new().on_digest(move |d| {
+            sent_clone.fetch_add(d.entries.len(), Ordering::Relaxed);
+        });
+
+        assert!(digest.flush().is_none());
+        assert_eq!(sent.load(Ordering::Relaxed), 0);
+
+        let feed = digest.clone();
+        for _ in 0..50 {
+            feed.record_event(&MetricEvent::ThresholdExceeded(
+                "latency".into(),
+                250.0,
+                200.0,
+            ));
+        }
+        feed.record_event(&MetricEvent::Gauge("latency".into(), 250.0));
+
+        let report = digest.flush().unwrap();
+        assert_eq!(report.total_alerts(), 50);
+        assert_eq!(report.entries[0].message, "threshold 200 exceeded");
+        assert_eq!(sent.load(Ordering::Relaxed), 1);
+    }
+}
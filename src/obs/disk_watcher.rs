@@ -0,0 +1,334 @@
+//! Disk Space and Inode Monitoring
+//!
+//! Watches index and cache directories: free space, inode usage, and write
+//! latency measured by a tiny probe write, with low-space alerts. Running
+//! out of inodes or disk mid-build corrupts indexes; a slow probe write is
+//! an early sign of a degraded volume.
+//!
+//! # Collected Gauges (labeled by `path`)
+//!
+//! - `disk_total_bytes`, `disk_available_bytes`, `disk_available_ratio`
+//! - `disk_inodes_free`, `disk_inode_used_ratio`
+//! - `disk_write_probe_seconds` (when probing is enabled)
+//! - `disk_alerts_total{path,kind}` counter
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::disk_watcher::DiskWatcher;
+//!
+//! let watcher = DiskWatcher::new()
+//!     .watch("/var/lib/embeddenator/index")
+//!     .watch("/var/cache/embeddenator")
+//!     .low_space_below(0.10)
+//!     .on_alert(|alert| eprintln!("{:?}", alert));
+//!
+//! let _handle = watcher.spawn(Duration::from_secs(60), telemetry.clone());
+//! ```
+
+use crate::obs::host::{disk_usage, DiskUsage};
+use crate::obs::logging;
+use crate::obs::process::{spawn_collector, CollectorHandle};
+use crate::obs::telemetry::Telemetry;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Name of the probe file written (and removed) in each watched directory.
+pub const PROBE_FILE_NAME: &str = ".embeddenator-obs-probe";
+
+/// What a disk alert is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiskAlertKind {
+    /// Available space fraction below threshold
+    LowSpace,
+    /// Free inode fraction below threshold
+    LowInodes,
+}
+
+impl DiskAlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiskAlertKind::LowSpace => "low_space",
+            DiskAlertKind::LowInodes => "low_inodes",
+        }
+    }
+}
+
+/// Raised when a watched path crosses a low-space or low-inode threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskAlert {
+    pub path: PathBuf,
+    pub kind: DiskAlertKind,
+    /// Remaining fraction (space or inodes)
+    pub free_ratio: f64,
+    pub threshold: f64,
+}
+
+/// Callback invoked on disk alerts.
+pub type DiskAlertCallback = Arc<dyn Fn(&DiskAlert) + Send + Sync>;
+
+/// One sample of a watched path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskSample {
+    pub usage: DiskUsage,
+    /// Time to write, sync and remove the probe file
+    pub probe_latency: Option<Duration>,
+}
+
+impl DiskSample {
+    /// Fraction of space available to unprivileged users.
+    pub fn available_ratio(&self) -> f64 {
+        1.0 - self.usage.used_ratio()
+    }
+
+    /// Fraction of inodes free (1.0 without inode limits).
+    pub fn inode_free_ratio(&self) -> f64 {
+        1.0 - self.usage.inode_used_ratio()
+    }
+}
+
+struct WatchedPath {
+    path: PathBuf,
+    low_space_firing: bool,
+    low_inodes_firing: bool,
+}
+
+/// Periodic disk space, inode and write-latency watcher.
+pub struct DiskWatcher {
+    paths: Vec<WatchedPath>,
+    low_space_ratio: f64,
+    low_inode_ratio: f64,
+    probe: bool,
+    callbacks: Vec<DiskAlertCallback>,
+}
+
+impl Default for DiskWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiskWatcher {
+    /// Watcher alerting below 10% available space or 5% free inodes, with
+    /// write probes enabled.
+    pub fn new() -> Self {
+        Self {
+            paths: Vec::new(),
+            low_space_ratio: 0.10,
+            low_inode_ratio: 0.05,
+            probe: true,
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Watch a directory.
+    pub fn watch(mut self, path: impl Into<PathBuf>) -> Self {
+        self.paths.push(WatchedPath {
+            path: path.into(),
+            low_space_firing: false,
+            low_inodes_firing: false,
+        });
+        self
+    }
+
+    /// Alert when the available space fraction drops below `ratio`.
+    pub fn low_space_below(mut self, ratio: f64) -> Self {
+        self.low_space_ratio = ratio;
+        self
+    }
+
+    /// Alert when the free inode fraction drops below `ratio`.
+    pub fn low_inodes_below(mut self, ratio: f64) -> Self {
+        self.low_inode_ratio = ratio;
+        self
+    }
+
+    /// Enable or disable the probe write (default: enabled).
+    pub fn with_probe(mut self, enabled: bool) -> Self {
+        self.probe = enabled;
+        self
+    }
+
+    /// Invoke `callback` when an alert fires.
+    pub fn on_alert<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&DiskAlert) + Send + Sync + 'static,
+    {
+        self.callbacks.push(Arc::new(callback));
+        self
+    }
+
+    /// Sample one path; `None` if its filesystem cannot be queried.
+    pub fn sample(&self, path: &Path) -> Option<DiskSample> {
+        Some(DiskSample {
+            usage: disk_usage(path)?,
+            probe_latency: if self.probe { probe_write(path) } else { None },
+        })
+    }
+
+    /// Sample all watched paths, write gauges, and evaluate thresholds.
+    ///
+    /// Alerts fire once per crossing and re-arm once the path recovers.
+    pub fn collect_into(&mut self, telemetry: &mut Telemetry) -> Vec<DiskAlert> {
+        let mut fired = Vec::new();
+        let samples: Vec<_> = self.paths.iter().map(|w| self.sample(&w.path)).collect();
+
+        for (watched, sample) in self.paths.iter_mut().zip(samples) {
+            let Some(sample) = sample else {
+                continue;
+            };
+            let path = watched.path.to_string_lossy().into_owned();
+            let labels = [("path", path.as_str())];
+            let usage = &sample.usage;
+
+            telemetry.set_gauge_with_labels("disk_total_bytes", &labels, usage.total_bytes as f64);
+            telemetry.set_gauge_with_labels(
+                "disk_available_bytes",
+                &labels,
+                usage.available_bytes as f64,
+            );
+            telemetry.set_gauge_with_labels(
+                "disk_available_ratio",
+                &labels,
+                sample.available_ratio(),
+            );
+            telemetry.set_gauge_with_labels("disk_inodes_free", &labels, usage.free_inodes as f64);
+            telemetry.set_gauge_with_labels(
+                "disk_inode_used_ratio",
+                &labels,
+                usage.inode_used_ratio(),
+            );
+            if let Some(latency) = sample.probe_latency {
+                telemetry.set_gauge_with_labels(
+                    "disk_write_probe_seconds",
+                    &labels,
+                    latency.as_secs_f64(),
+                );
+            }
+
+            let checks = [
+                (
+                    DiskAlertKind::LowSpace,
+                    sample.available_ratio(),
+                    self.low_space_ratio,
+                    &mut watched.low_space_firing,
+                ),
+                (
+                    DiskAlertKind::LowInodes,
+                    sample.inode_free_ratio(),
+                    self.low_inode_ratio,
+                    &mut watched.low_inodes_firing,
+                ),
+            ];
+            for (kind, free_ratio, threshold, firing) in checks {
+                if free_ratio >= threshold {
+                    *firing = false;
+                    continue;
+                }
+                if *firing {
+                    continue;
+                }
+                *firing = true;
+
+                telemetry.add_to_counter_with_labels(
+                    "disk_alerts_total",
+                    &[("path", path.as_str()), ("kind", kind.as_str())],
+                    1,
+                );
+                logging::warn(&format!(
+                    "{} on {}: {:.1}% free (threshold {:.1}%)",
+                    kind.as_str(),
+                    path,
+                    free_ratio * 100.0,
+                    threshold * 100.0
+                ));
+                fired.push(DiskAlert {
+                    path: watched.path.clone(),
+                    kind,
+                    free_ratio,
+                    threshold,
+                });
+            }
+        }
+
+        for alert in &fired {
+            for callback in &self.callbacks {
+                callback(alert);
+            }
+        }
+        fired
+    }
+
+    /// Collect into shared telemetry every `interval` on a background thread.
+    pub fn spawn(
+        mut self,
+        interval: Duration,
+        telemetry: Arc<Mutex<Telemetry>>,
+    ) -> CollectorHandle {
+        spawn_collector("obs-disk-watcher", interval, telemetry, move |telemetry| {
+            self.collect_into(telemetry);
+        })
+    }
+}
+
+/// Write, sync and remove a small probe file; `None` if any step fails.
+fn probe_write(dir: &Path) -> Option<Duration> {
+    let path = dir.join(PROBE_FILE_NAME);
+    let started = Instant::now();
+    let result = std::fs::File::create(&path).and_then(|mut file| {
+        file.write_all(&[0u8; 512])?;
+        file.sync_data()
+    });
+    let elapsed = started.elapsed();
+    let _ = std::fs::remove_file(&path);
+    result.ok().map(|_| elapsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_labeled_gauges_and_probe() {
+        let dir = std::env::temp_dir();
+        let mut watcher = DiskWatcher::new().watch(&dir).low_space_below(0.0);
+        let mut telemetry = Telemetry::default_config();
+
+        assert!(watcher.collect_into(&mut telemetry).is_empty());
+        assert!(!dir.join(PROBE_FILE_NAME).exists());
+
+        let gauges = telemetry.snapshot().gauges;
+        let key = |name: &str| format!("{}{{path=\"{}\"}}", name, dir.display());
+        assert!(gauges[&key("disk_total_bytes")] > 0.0);
+        assert!(gauges.contains_key(&key("disk_write_probe_seconds")));
+        assert!(gauges.contains_key(&key("disk_inode_used_ratio")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_low_space_alert_fires_once() {
+        let dir = std::env::temp_dir();
+        // Any filesystem has less than 100% available
+        let mut watcher = DiskWatcher::new()
+            .watch(&dir)
+            .with_probe(false)
+            .low_space_below(1.01);
+        let mut telemetry = Telemetry::default_config();
+
+        let fired = watcher.collect_into(&mut telemetry);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].kind, DiskAlertKind::LowSpace);
+        assert!(watcher.collect_into(&mut telemetry).is_empty());
+    }
+
+    #[test]
+    fn test_missing_path_is_skipped() {
+        let mut watcher = DiskWatcher::new().watch("/definitely/not/a/path");
+        let mut telemetry = Telemetry::default_config();
+        assert!(watcher.collect_into(&mut telemetry).is_empty());
+        assert!(telemetry.snapshot().gauges.is_empty());
+    }
+}
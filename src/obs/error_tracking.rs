@@ -0,0 +1,603 @@
+//! Error Event Tracking
+//!
+//! [`ErrorTracker`] records error occurrences (type, message, backtrace
+//! and context attributes) and groups them by fingerprint, so a failure
+//! repeated a million times is one group with a count rather than a
+//! million log lines. The most frequent groups of the global
+//! [`error_tracker`] appear in every
+//! [`TelemetrySnapshot`](crate::obs::telemetry::TelemetrySnapshot) as
+//! `errors`, and [`SentryExporter`] forwards recorded occurrences to a
+//! Sentry-compatible endpoint.
+//!
+//! # Fingerprints
+//!
+//! By default a fingerprint is FNV-1a over the error type, the message
+//! with digit runs replaced by `#` (`shard 3` and `shard 17` group
+//! together) and the first application frames of the backtrace.
+//! [`ErrorEvent::with_fingerprint`] groups by an explicit key instead.
+//!
+//! Messages and attributes pass through the installed
+//! [`Redactor`](crate::obs::redaction::Redactor) when recorded, and
+//! exports honour the installed
+//! [`PrivacyPolicy`](crate::obs::privacy::PrivacyPolicy).
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::error_tracking::{error_tracker, ErrorEvent, SentryExporter};
+//!
+//! if let Err(e) = load_shard(3) {
+//!     error_tracker().record(
+//!         ErrorEvent::from_error(&e)
+//!             .capture_backtrace()
+//!             .with_attribute("shard", 3),
+//!     );
+//! }
+//!
+//! // Periodically
+//! let sentry = SentryExporter::new("http://public_key@sentry:9000/42")?
+//!     .with_environment("production");
+//! sentry.push(&error_tracker().take_pending())?;
+//! ```
+
+use crate::obs::clock::{system_clock, Clock};
+use crate::obs::http::{Backoff, HttpEndpoint};
+use crate::obs::opentelemetry::{AttributeValue, IdGenerator};
+use crate::obs::privacy;
+use crate::obs::redaction::Redactor;
+use crate::obs::telemetry::{escape_json, rfc3339};
+use std::backtrace::Backtrace;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Groups kept by a tracker unless changed with
+/// [`ErrorTracker::with_max_groups`]; the least recently seen is evicted.
+pub const DEFAULT_MAX_ERROR_GROUPS: usize = 256;
+
+/// Occurrences kept for [`ErrorTracker::take_pending`]; older ones are
+/// dropped first.
+pub const MAX_PENDING_ERRORS: usize = 100;
+
+/// Groups included in telemetry snapshots.
+pub const TOP_ERRORS: usize = 10;
+
+/// Backtrace frames contributing to the default fingerprint.
+const FINGERPRINT_FRAMES: usize = 3;
+
+static ERROR_TRACKER: OnceLock<ErrorTracker> = OnceLock::new();
+
+/// Process-wide tracker feeding telemetry snapshots.
+pub fn error_tracker() -> &'static ErrorTracker {
+    ERROR_TRACKER.get_or_init(ErrorTracker::new)
+}
+
+/// One error occurrence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorEvent {
+    /// Error type, e.g. `std::io::Error`
+    pub error_type: String,
+    pub message: String,
+    pub backtrace: Option<String>,
+    pub attributes: Vec<(String, AttributeValue)>,
+    /// Set when recorded
+    pub timestamp: SystemTime,
+    fingerprint_key: Option<String>,
+}
+
+impl ErrorEvent {
+    pub fn new(error_type: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            error_type: error_type.into(),
+            message: message.into(),
+            backtrace: None,
+            attributes: Vec::new(),
+            timestamp: UNIX_EPOCH,
+            fingerprint_key: None,
+        }
+    }
+
+    /// Event for `error`: its type name, and its message followed by
+    /// those of its sources, separated by `: `.
+    pub fn from_error<E: std::error::Error + ?Sized>(error: &E) -> Self {
+        let mut message = error.to_string();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            message.push_str(": ");
+            message.push_str(&cause.to_string());
+            source = cause.source();
+        }
+        Self::new(std::any::type_name::<E>(), message)
+    }
+
+    /// Attach the current backtrace, regardless of `RUST_BACKTRACE`.
+    pub fn capture_backtrace(self) -> Self {
+        self.with_backtrace(Backtrace::force_capture().to_string())
+    }
+
+    /// Attach a backtrace captured elsewhere.
+    pub fn with_backtrace(mut self, backtrace: impl Into<String>) -> Self {
+        self.backtrace = Some(backtrace.into());
+        self
+    }
+
+    /// Add a context attribute.
+    pub fn with_attribute(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<AttributeValue>,
+    ) -> Self {
+        self.attributes.push((key.into(), value.into()));
+        self
+    }
+
+    /// Group by `key` instead of type, message and backtrace.
+    pub fn with_fingerprint(mut self, key: impl Into<String>) -> Self {
+        self.fingerprint_key = Some(key.into());
+        self
+    }
+
+    /// Grouping key, see the module docs.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash = Fnv::new();
+        match &self.fingerprint_key {
+            Some(key) => hash.write(key),
+            None => {
+                hash.write(&self.error_type);
+                hash.write(&normalize_message(&self.message));
+                if let Some(backtrace) = &self.backtrace {
+                    for frame in app_frames(backtrace).take(FINGERPRINT_FRAMES) {
+                        hash.write(frame);
+                    }
+                }
+            }
+        }
+        hash.0
+    }
+}
+
+/// FNV-1a with a separator after each part.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, part: &str) {
+        for byte in part.bytes().chain([0xff]) {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// `message` with each run of digits replaced by `#`.
+fn normalize_message(message: &str) -> String {
+    let mut normalized = String::with_capacity(message.len());
+    let mut in_digits = false;
+    for c in message.chars() {
+        if c.is_ascii_digit() {
+            if !in_digits {
+                normalized.push('#');
+            }
+            in_digits = true;
+        } else {
+            normalized.push(c);
+            in_digits = false;
+        }
+    }
+    normalized
+}
+
+/// Function names of a `std::backtrace::Backtrace` rendering, without
+/// runtime, std and tracker frames or symbol hashes.
+fn app_frames(backtrace: &str) -> impl Iterator<Item = &str> {
+    const SKIPPED: [&str; 6] = [
+        "std::",
+        "core::",
+        "alloc::",
+        "__rust",
+        "rust_begin_unwind",
+        "embeddenator_obs::obs::error_tracking::",
+    ];
+    backtrace
+        .lines()
+        .filter_map(|line| {
+            let (index, function) = line.trim_start().split_once(": ")?;
+            index.parse::<u32>().ok()?;
+            Some(match function.rsplit_once("::h") {
+                Some((name, hash))
+                    if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) =>
+                {
+                    name
+                }
+                _ => function,
+            })
+        })
+        .filter(|function| !SKIPPED.iter().any(|prefix| function.starts_with(prefix)))
+}
+
+/// Occurrences sharing one fingerprint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorGroup {
+    pub fingerprint: u64,
+    pub error_type: String,
+    /// Message of the latest occurrence
+    pub message: String,
+    pub count: u64,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+}
+
+/// Records and deduplicates error occurrences.
+#[derive(Debug)]
+pub struct ErrorTracker {
+    groups: Mutex<HashMap<u64, ErrorGroup>>,
+    pending: Mutex<VecDeque<ErrorEvent>>,
+    max_groups: usize,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for ErrorTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ErrorTracker {
+    pub fn new() -> Self {
+        Self {
+            groups: Mutex::new(HashMap::new()),
+            pending: Mutex::new(VecDeque::new()),
+            max_groups: DEFAULT_MAX_ERROR_GROUPS,
+            clock: system_clock(),
+        }
+    }
+
+    /// Keep at most `max_groups` groups (at least one).
+    pub fn with_max_groups(mut self, max_groups: usize) -> Self {
+        self.max_groups = max_groups.max(1);
+        self
+    }
+
+    /// Timestamp occurrences from `clock`.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record an occurrence and return its fingerprint.
+    pub fn record(&self, mut event: ErrorEvent) -> u64 {
+        if let Some(redactor) = Redactor::installed() {
+            event.message = redactor.redact_text(&event.message).into_owned();
+            for (key, value) in event.attributes.iter_mut() {
+                *value = redactor.redact_attribute(key, value);
+            }
+        }
+        event.timestamp = self.clock.system_time();
+        let fingerprint = event.fingerprint();
+
+        let mut groups = self.groups.lock().unwrap_or_else(|e| e.into_inner());
+        if !groups.contains_key(&fingerprint) && groups.len() >= self.max_groups {
+            let stalest = groups
+                .values()
+                .min_by_key(|group| group.last_seen)
+                .map(|group| group.fingerprint);
+            if let Some(stalest) = stalest {
+                groups.remove(&stalest);
+            }
+        }
+        let group = groups.entry(fingerprint).or_insert_with(|| ErrorGroup {
+            fingerprint,
+            error_type: event.error_type.clone(),
+            message: String::new(),
+            count: 0,
+            first_seen: event.timestamp,
+            last_seen: event.timestamp,
+        });
+        group.count += 1;
+        group.message = event.message.clone();
+        group.last_seen = event.timestamp;
+        drop(groups);
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.len() >= MAX_PENDING_ERRORS {
+            pending.pop_front();
+        }
+        pending.push_back(event);
+        fingerprint
+    }
+
+    /// Record `error` without backtrace or attributes.
+    pub fn record_error<E: std::error::Error + ?Sized>(&self, error: &E) -> u64 {
+        self.record(ErrorEvent::from_error(error))
+    }
+
+    /// Up to `n` groups, most frequent first (ties: most recent first).
+    pub fn top(&self, n: usize) -> Vec<ErrorGroup> {
+        let mut groups: Vec<ErrorGroup> = self
+            .groups
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        groups.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(b.last_seen.cmp(&a.last_seen))
+                .then(a.fingerprint.cmp(&b.fingerprint))
+        });
+        groups.truncate(n);
+        groups
+    }
+
+    pub fn group(&self, fingerprint: u64) -> Option<ErrorGroup> {
+        self.groups
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&fingerprint)
+            .cloned()
+    }
+
+    /// Occurrences recorded since the last call, oldest first, for
+    /// export.
+    pub fn take_pending(&self) -> Vec<ErrorEvent> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain(..)
+            .collect()
+    }
+
+    /// Forget all groups and pending occurrences.
+    pub fn clear(&self) {
+        self.groups
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+/// Sends error occurrences to a Sentry-compatible store endpoint
+/// (Sentry, GlitchTip) as JSON events.
+///
+/// Events carry the type and message as an exception, the fingerprint
+/// as Sentry's grouping `fingerprint`, attributes as `extra` and the
+/// backtrace as `extra.backtrace`. Only plain `http://` DSNs are
+/// supported.
+pub struct SentryExporter {
+    endpoint: HttpEndpoint,
+    public_key: String,
+    environment: Option<String>,
+    release: Option<String>,
+    timeout: Duration,
+    backoff: Backoff,
+    ids: IdGenerator,
+}
+
+impl SentryExporter {
+    /// Exporter for a DSN such as `http://<public_key>@sentry:9000/42`,
+    /// with a 10 second timeout and 3 retries backing off from 500 ms up
+    /// to 30 seconds.
+    pub fn new(dsn: &str) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, msg.to_string());
+        let (scheme, rest) = dsn.split_once("://").unwrap_or(("http", dsn));
+        let (credentials, location) = rest
+            .split_once('@')
+            .ok_or_else(|| invalid("DSN has no public key"))?;
+        let public_key = credentials.split(':').next().unwrap_or_default();
+        let (prefix, project) = location
+            .trim_end_matches('/')
+            .rsplit_once('/')
+            .ok_or_else(|| invalid("DSN has no project id"))?;
+        if public_key.is_empty() || project.is_empty() {
+            return Err(invalid("DSN has no public key or project id"));
+        }
+        let url = format!("{}://{}/api/{}/store/", scheme, prefix, project);
+        Ok(Self {
+            endpoint: HttpEndpoint::parse(&url)?,
+            public_key: public_key.to_string(),
+            environment: None,
+            release: None,
+            timeout: Duration::from_secs(10),
+            backoff: Backoff {
+                max_retries: 3,
+                initial: Duration::from_millis(500),
+                max: Duration::from_secs(30),
+            },
+            ids: IdGenerator::new(),
+        })
+    }
+
+    /// Deployment environment, e.g. `production`.
+    pub fn with_environment(mut self, environment: impl Into<String>) -> Self {
+        self.environment = Some(environment.into());
+        self
+    }
+
+    /// Release (code version) the events belong to.
+    pub fn with_release(mut self, release: impl Into<String>) -> Self {
+        self.release = Some(release.into());
+        self
+    }
+
+    /// Connect, write and read timeout per attempt.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Retry a failed push up to `max_retries` times.
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.backoff.max_retries = max_retries;
+        self
+    }
+
+    /// Sentry event JSON for `event`.
+    pub fn encode(&self, event: &ErrorEvent) -> String {
+        // Grouping follows the raw event, not its redacted form
+        let fingerprint = event.fingerprint();
+        let event = privacy::enforce_error(event);
+        let mut fields = vec![
+            format!(r#""event_id":"{:032x}""#, self.ids.next_trace_id()),
+            format!(r#""timestamp":"{}""#, rfc3339(event.timestamp)),
+            r#""platform":"other""#.to_string(),
+            r#""level":"error""#.to_string(),
+            r#""logger":"embeddenator-obs""#.to_string(),
+            format!(
+                r#""exception":{{"values":[{{"type":"{}","value":"{}"}}]}}"#,
+                escape_json(&event.error_type),
+                escape_json(&event.message)
+            ),
+            format!(r#""fingerprint":["{:016x}"]"#, fingerprint),
+        ];
+        if let Some(environment) = &self.environment {
+            fields.push(format!(r#""environment":"{}""#, escape_json(environment)));
+        }
+        if let Some(release) = &self.release {
+            fields.push(format!(r#""release":"{}""#, escape_json(release)));
+        }
+        let mut extra: Vec<String> = event
+            .attributes
+            .iter()
+            .map(|(key, value)| format!(r#""{}":{}"#, escape_json(key), value.to_json()))
+            .collect();
+        if let Some(backtrace) = &event.backtrace {
+            extra.push(format!(r#""backtrace":"{}""#, escape_json(backtrace)));
+        }
+        if !extra.is_empty() {
+            fields.push(format!(r#""extra":{{{}}}"#, extra.join(",")));
+        }
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// Send each of `events`, retrying transient failures; stops at the
+    /// first event that cannot be delivered.
+    pub fn push(&self, events: &[ErrorEvent]) -> io::Result<()> {
+        let headers = [
+            ("Content-Type".to_string(), "application/json".to_string()),
+            (
+                "X-Sentry-Auth".to_string(),
+                format!(
+                    "Sentry sentry_version=7, sentry_client=embeddenator-obs/{}, sentry_key={}",
+                    env!("CARGO_PKG_VERSION"),
+                    self.public_key
+                ),
+            ),
+        ];
+        for event in events {
+            let body = self.encode(event);
+            self.backoff.retry("sentry export", || {
+                self.endpoint.post(&headers, body.as_bytes(), self.timeout)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obs::clock::MockClock;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_fingerprint_groups_occurrences() {
+        let clock = Arc::new(MockClock::at(UNIX_EPOCH));
+        let tracker = ErrorTracker::new()
+            .with_max_groups(2)
+            .with_clock(clock.clone());
+        let backtrace = "   0: std::backtrace::Backtrace::force_capture::h0123456789abcdef\n\
+                         \x20            at /rustc/library/std/src/backtrace.rs:312:13\n\
+                         \x20  1: indexer::load_shard::hfedcba9876543210\n";
+
+        let first = tracker.record(
+            ErrorEvent::new("io::Error", "shard 3 truncated at 4096").with_backtrace(backtrace),
+        );
+        clock.advance(Duration::from_secs(1));
+        let again = tracker.record(
+            ErrorEvent::new("io::Error", "shard 17 truncated at 512")
+                .with_backtrace(backtrace.replace("hfedcba9876543210", "h00000000000000ff")),
+        );
+        assert_eq!(first, again);
+        assert_ne!(
+            first,
+            ErrorEvent::new("io::Error", "shard 3 truncated at 4096")
+                .with_backtrace("   0: indexer::rebuild::h0000000000000000")
+                .fingerprint()
+        );
+
+        clock.advance(Duration::from_secs(1));
+        tracker.record(ErrorEvent::new("ParseError", "bad header"));
+        let top = tracker.top(TOP_ERRORS);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].count, 2);
+        assert_eq!(top[0].message, "shard 17 truncated at 512");
+        assert_eq!(top[0].first_seen, UNIX_EPOCH);
+        assert_eq!(top[0].last_seen, UNIX_EPOCH + Duration::from_secs(1));
+
+        // A third group evicts the least recently seen one
+        clock.advance(Duration::from_secs(1));
+        tracker.record(ErrorEvent::new("Timeout", "x").with_fingerprint("timeouts"));
+        assert!(tracker.group(first).is_none());
+        assert_eq!(tracker.take_pending().len(), 4);
+        assert!(tracker.take_pending().is_empty());
+    }
+
+    #[test]
+    fn test_sentry_push() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let dsn = format!("http://abc123@{}/42", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(len) = line.strip_prefix("Content-Length: ") {
+                    content_length = len.trim().parse().unwrap();
+                }
+                head.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body).unwrap();
+            write!(reader.get_mut(), "HTTP/1.1 200 OK\r\n\r\n").unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+
+        let error = io::Error::new(io::ErrorKind::NotFound, "shard \"3\" missing");
+        let event = ErrorEvent::from_error(&error).with_attribute("shard", 3);
+        let exporter = SentryExporter::new(&dsn)
+            .unwrap()
+            .with_environment("test")
+            .with_retries(0);
+        exporter.push(std::slice::from_ref(&event)).unwrap();
+
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("POST /api/42/store/ HTTP/1.1\r\n"));
+        assert!(head.contains("sentry_key=abc123\r\n"));
+        assert!(body.contains(
+            r#""exception":{"values":[{"type":"std::io::error::Error","value":"shard \"3\" missing"}]}"#
+        ));
+        assert!(body.contains(&format!(
+            r#""fingerprint":["{:016x}"]"#,
+            event.fingerprint()
+        )));
+        assert!(body.contains(r#""environment":"test""#));
+        assert!(body.contains(r#""extra":{"shard":3}"#));
+        assert!(SentryExporter::new("http://sentry:9000/42").is_err());
+    }
+}
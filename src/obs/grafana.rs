@@ -0,0 +1,448 @@
+//! Grafana Dashboard Generator
+//!
+//! Builds a ready-to-import Grafana dashboard from the metric catalog
+//! (see [`Telemetry::schema_registry`]), using the same names the
+//! [`PrometheusExporter`] writes, so new deployments get dashboards without
+//! hand-building them.
+//!
+//! # Panels
+//!
+//! One row per source module, then per metric:
+//!
+//! - Counters: per-second rate, split by label
+//! - Gauges: current value, split by label
+//! - Histograms (operations): latency heatmap of `_bucket` rates and a
+//!   p50/p95/p99 panel
+//! - Cache ratios: hit ratio for every `<x>_hits` / `<x>_misses` counter
+//!   pair, plus the built-in sub-cache counters
+//!
+//! Units come from descriptors (`bytes`, `seconds`, `ratio`); operation
+//! latencies are in microseconds.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::grafana::GrafanaDashboard;
+//!
+//! let json = GrafanaDashboard::new("Embeddenator")
+//!     .with_prefix("embeddenator")
+//!     .with_sources(&["application", "process"])
+//!     .generate(&telemetry.schema_registry());
+//! std::fs::write("dashboard.json", json)?;
+//! ```
+//!
+//! [`Telemetry::schema_registry`]: crate::obs::telemetry::Telemetry::schema_registry
+//! [`PrometheusExporter`]: crate::obs::prometheus::PrometheusExporter
+
+use crate::obs::prometheus::sanitize_name;
+use crate::obs::registry::{MetricDescriptor, MetricKind, MetricRegistry};
+use crate::obs::telemetry::escape_json;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+const PANEL_HEIGHT: u32 = 8;
+const PANEL_WIDTH: u32 = 12;
+
+struct Target {
+    expr: String,
+    legend: String,
+}
+
+struct Panel {
+    kind: &'static str,
+    title: String,
+    description: String,
+    unit: &'static str,
+    targets: Vec<Target>,
+}
+
+/// Grafana dashboard JSON generator.
+#[derive(Debug, Clone)]
+pub struct GrafanaDashboard {
+    title: String,
+    uid: Option<String>,
+    prefix: String,
+    sources: Vec<String>,
+    refresh: String,
+}
+
+impl GrafanaDashboard {
+    /// Dashboard for metrics exported with the `embeddenator` prefix.
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            uid: None,
+            prefix: "embeddenator".to_string(),
+            sources: Vec::new(),
+            refresh: "30s".to_string(),
+        }
+    }
+
+    /// Prefix passed to [`PrometheusExporter::new`](crate::obs::prometheus::PrometheusExporter::new).
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Stable dashboard uid, so re-imports replace the dashboard.
+    pub fn with_uid(mut self, uid: impl Into<String>) -> Self {
+        self.uid = Some(uid.into());
+        self
+    }
+
+    /// Only include descriptors from these sources (default: all). The
+    /// built-in cache ratio panel is kept when `metrics` is listed.
+    pub fn with_sources(mut self, sources: &[&str]) -> Self {
+        self.sources = sources.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Auto-refresh interval (default `30s`).
+    pub fn with_refresh(mut self, refresh: impl Into<String>) -> Self {
+        self.refresh = refresh.into();
+        self
+    }
+
+    fn includes(&self, source: &str) -> bool {
+        self.sources.is_empty() || self.sources.iter().any(|s| s == source)
+    }
+
+    /// Exported sample name of a counter or gauge.
+    fn series_name(&self, descriptor: &MetricDescriptor) -> String {
+        format!(
+            "{}_{}",
+            self.prefix,
+            sanitize_name(&descriptor.unit_suffixed(&descriptor.name))
+        )
+    }
+
+    fn by(labels: &[String]) -> (String, String) {
+        if labels.is_empty() {
+            return (String::new(), "{{instance}}".to_string());
+        }
+        (
+            format!(" by ({})", labels.join(", ")),
+            labels
+                .iter()
+                .map(|l| format!("{{{{{}}}}}", l))
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+
+    fn metric_panels(&self, descriptor: &MetricDescriptor) -> Vec<Panel> {
+        let unit = match descriptor.unit.as_deref() {
+            Some("bytes") => "bytes",
+            Some("seconds") => "s",
+            Some("ratio") => "percentunit",
+            _ => "short",
+        };
+        let (by, legend) = Self::by(&descriptor.labels);
+        let description = descriptor.help.clone();
+
+        match descriptor.kind {
+            MetricKind::Counter => vec![Panel {
+                kind: "timeseries",
+                title: format!("{} rate", descriptor.name),
+                description,
+                unit: match unit {
+                    "bytes" => "Bps",
+                    "short" => "ops",
+                    other => other,
+                },
+                targets: vec![Target {
+                    expr: format!(
+                        "sum{}(rate({}[$__rate_interval]))",
+                        by,
+                        self.series_name(descriptor)
+                    ),
+                    legend,
+                }],
+            }],
+            MetricKind::Gauge => vec![Panel {
+                kind: "timeseries",
+                title: descriptor.name.clone(),
+                description,
+                unit,
+                targets: vec![Target {
+                    expr: if descriptor.labels.is_empty() {
+                        self.series_name(descriptor)
+                    } else {
+                        format!("sum{}({})", by, self.series_name(descriptor))
+                    },
+                    legend,
+                }],
+            }],
+            MetricKind::Histogram | MetricKind::Summary => {
+                let name = format!(
+                    "{}_{}_duration_us",
+                    self.prefix,
+                    sanitize_name(&descriptor.name)
+                );
+                let quantiles = [0.5, 0.95, 0.99]
+                    .iter()
+                    .map(|q| Target {
+                        expr: match descriptor.kind {
+                            MetricKind::Summary => {
+                                format!("max({}{{quantile=\"{}\"}})", name, q)
+                            }
+                            _ => format!(
+                                "histogram_quantile({}, sum by (le) (rate({}_bucket[$__rate_interval])))",
+                                q, name
+                            ),
+                        },
+                        legend: format!("p{}", (q * 100.0) as u32),
+                    })
+                    .collect();
+                let mut panels = Vec::new();
+                if descriptor.kind == MetricKind::Histogram {
+                    panels.push(Panel {
+                        kind: "heatmap",
+                        title: format!("{} latency heatmap", descriptor.name),
+                        description: description.clone(),
+                        unit: "µs",
+                        targets: vec![Target {
+                            expr: format!(
+                                "sum by (le) (increase({}_bucket[$__rate_interval]))",
+                                name
+                            ),
+                            legend: "{{le}}".to_string(),
+                        }],
+                    });
+                }
+                panels.push(Panel {
+                    kind: "timeseries",
+                    title: format!("{} latency quantiles", descriptor.name),
+                    description,
+                    unit: "µs",
+                    targets: quantiles,
+                });
+                panels
+            }
+        }
+    }
+
+    fn ratio_panel(&self, title: &str, hits: &str, misses: &str) -> Panel {
+        let rate = |name: &str| format!("sum(rate({}[$__rate_interval]))", name);
+        Panel {
+            kind: "timeseries",
+            title: title.to_string(),
+            description: format!("{} / ({} + {})", hits, hits, misses),
+            unit: "percentunit",
+            targets: vec![Target {
+                expr: format!("{0} / ({0} + {1})", rate(hits), rate(misses)),
+                legend: "hit ratio".to_string(),
+            }],
+        }
+    }
+
+    /// Cache ratio panels for `<x>_hits[_total]` / `<x>_misses[_total]`
+    /// counter pairs.
+    fn cache_ratio_panels(&self, counters: &[&MetricDescriptor]) -> Vec<Panel> {
+        let mut panels = Vec::new();
+        if self.includes("metrics") {
+            panels.push(self.ratio_panel(
+                "sub_cache hit ratio",
+                &format!("{}_sub_cache_hits", self.prefix),
+                &format!("{}_sub_cache_misses", self.prefix),
+            ));
+        }
+        for hits in counters {
+            let Some(base) = hits
+                .name
+                .strip_suffix("_hits_total")
+                .or_else(|| hits.name.strip_suffix("_hits"))
+            else {
+                continue;
+            };
+            let misses = counters.iter().find(|d| {
+                d.name == format!("{}_misses_total", base) || d.name == format!("{}_misses", base)
+            });
+            if let Some(misses) = misses {
+                panels.push(self.ratio_panel(
+                    &format!("{} hit ratio", base),
+                    &self.series_name(hits),
+                    &self.series_name(misses),
+                ));
+            }
+        }
+        panels
+    }
+
+    /// Render the dashboard JSON for every descriptor in `registry`.
+    pub fn generate(&self, registry: &MetricRegistry) -> String {
+        let mut sections: BTreeMap<&str, Vec<&MetricDescriptor>> = BTreeMap::new();
+        for descriptor in registry.iter() {
+            let source = descriptor.source.as_deref().unwrap_or("application");
+            if self.includes(source) {
+                sections.entry(source).or_default().push(descriptor);
+            }
+        }
+        let counters: Vec<&MetricDescriptor> = sections
+            .values()
+            .flatten()
+            .copied()
+            .filter(|d| d.kind == MetricKind::Counter)
+            .collect();
+
+        let mut rows: Vec<(String, Vec<Panel>)> = Vec::new();
+        let ratios = self.cache_ratio_panels(&counters);
+        if !ratios.is_empty() {
+            rows.push(("caches".to_string(), ratios));
+        }
+        for (source, descriptors) in &sections {
+            let panels = descriptors
+                .iter()
+                .flat_map(|d| self.metric_panels(d))
+                .collect();
+            rows.push((source.to_string(), panels));
+        }
+
+        let mut panels_json = Vec::new();
+        let mut id = 1;
+        let mut y = 0;
+        for (title, panels) in rows {
+            panels_json.push(format!(
+                r#"    {{"id": {}, "type": "row", "title": "{}", "collapsed": false, "gridPos": {{"h": 1, "w": 24, "x": 0, "y": {}}}, "panels": []}}"#,
+                id,
+                escape_json(&title),
+                y
+            ));
+            id += 1;
+            y += 1;
+            for (i, panel) in panels.iter().enumerate() {
+                let x = if i % 2 == 0 { 0 } else { PANEL_WIDTH };
+                panels_json.push(self.panel_json(panel, id, x, y));
+                id += 1;
+                if i % 2 == 1 || i + 1 == panels.len() {
+                    y += PANEL_HEIGHT;
+                }
+            }
+        }
+
+        let uid = self
+            .uid
+            .clone()
+            .unwrap_or_else(|| sanitize_name(&self.title.to_lowercase()));
+        let mut json = String::new();
+        writeln!(json, "{{").unwrap();
+        writeln!(json, r#"  "title": "{}","#, escape_json(&self.title)).unwrap();
+        writeln!(json, r#"  "uid": "{}","#, escape_json(&uid)).unwrap();
+        writeln!(json, r#"  "tags": ["{}"],"#, escape_json(&self.prefix)).unwrap();
+        writeln!(json, r#"  "schemaVersion": 39,"#).unwrap();
+        writeln!(json, r#"  "version": 1,"#).unwrap();
+        writeln!(json, r#"  "editable": true,"#).unwrap();
+        writeln!(json, r#"  "refresh": "{}","#, escape_json(&self.refresh)).unwrap();
+        writeln!(json, r#"  "time": {{"from": "now-1h", "to": "now"}},"#).unwrap();
+        writeln!(
+            json,
+            r#"  "templating": {{"list": [{{"name": "datasource", "label": "Data source", "type": "datasource", "query": "prometheus"}}]}},"#
+        )
+        .unwrap();
+        writeln!(json, r#"  "panels": ["#).unwrap();
+        writeln!(json, "{}", panels_json.join(",\n")).unwrap();
+        writeln!(json, "  ]").unwrap();
+        writeln!(json, "}}").unwrap();
+        json
+    }
+
+    fn panel_json(&self, panel: &Panel, id: u32, x: u32, y: u32) -> String {
+        let targets: Vec<String> = panel
+            .targets
+            .iter()
+            .enumerate()
+            .map(|(i, target)| {
+                format!(
+                    r#"{{"refId": "{}", "expr": "{}", "legendFormat": "{}"{}}}"#,
+                    (b'A' + i as u8) as char,
+                    escape_json(&target.expr),
+                    escape_json(&target.legend),
+                    if panel.kind == "heatmap" {
+                        r#", "format": "heatmap""#
+                    } else {
+                        ""
+                    }
+                )
+            })
+            .collect();
+        let options = if panel.kind == "heatmap" {
+            format!(
+                r#"{{"calculate": false, "yAxis": {{"unit": "{}"}}, "cellGap": 1}}"#,
+                panel.unit
+            )
+        } else {
+            r#"{"legend": {"displayMode": "list", "placement": "bottom"}, "tooltip": {"mode": "multi"}}"#
+                .to_string()
+        };
+        format!(
+            r#"    {{"id": {}, "type": "{}", "title": "{}", "description": "{}", "datasource": {{"type": "prometheus", "uid": "${{datasource}}"}}, "gridPos": {{"h": {}, "w": {}, "x": {}, "y": {}}}, "fieldConfig": {{"defaults": {{"unit": "{}"}}, "overrides": []}}, "options": {}, "targets": [{}]}}"#,
+            id,
+            panel.kind,
+            escape_json(&panel.title),
+            escape_json(&panel.description),
+            PANEL_HEIGHT,
+            PANEL_WIDTH,
+            x,
+            y,
+            panel.unit,
+            options,
+            targets.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obs::telemetry::Telemetry;
+
+    fn app_registry() -> MetricRegistry {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.describe(
+            MetricDescriptor::counter("read_total", "Bytes read")
+                .with_unit("bytes")
+                .with_labels(&["tier"]),
+        );
+        telemetry.add_to_counter("index_hits_total", 1);
+        telemetry.add_to_counter("index_misses_total", 1);
+        telemetry.set_gauge("queue_depth", 3.0);
+        telemetry.record_operation("query", 120);
+        telemetry.schema_registry()
+    }
+
+    #[test]
+    fn test_queries_match_exporter_names() {
+        let json = GrafanaDashboard::new("Embeddenator")
+            .with_prefix("test")
+            .with_sources(&["application"])
+            .generate(&app_registry());
+
+        assert!(json
+            .contains(r#""expr": "sum by (tier)(rate(test_read_bytes_total[$__rate_interval]))""#));
+        assert!(json.contains(r#""legendFormat": "{{tier}}""#));
+        assert!(json.contains(r#""expr": "test_queue_depth""#));
+        assert!(json.contains(r#""type": "heatmap""#));
+        assert!(json
+            .contains("sum by (le) (increase(test_query_duration_us_bucket[$__rate_interval]))"));
+        assert!(json.contains("histogram_quantile(0.99, sum by (le) (rate(test_query_duration_us_bucket[$__rate_interval])))"));
+        assert!(json.contains(r#""title": "index hit ratio""#));
+        // Built-in collectors are filtered out
+        assert!(!json.contains("process_open_fds"));
+        assert!(!json.contains("sub_cache hit ratio"));
+    }
+
+    #[test]
+    fn test_layout_is_two_columns() {
+        let json = GrafanaDashboard::new("Test Board")
+            .with_sources(&["application"])
+            .generate(&app_registry());
+
+        assert!(json.contains(r#""uid": "test_board""#));
+        // Row header, then panels alternate between columns
+        assert!(json.contains(r#""gridPos": {"h": 1, "w": 24, "x": 0, "y": 0}"#));
+        assert!(json.contains(r#""gridPos": {"h": 8, "w": 12, "x": 0, "y": 10}"#));
+        assert!(json.contains(r#""gridPos": {"h": 8, "w": 12, "x": 12, "y": 10}"#));
+        let ids = json.matches(r#"{"id": "#).count();
+        assert_eq!(ids, json.matches(r#""gridPos""#).count());
+    }
+}
@@ -0,0 +1,333 @@
+//! Version Handshake and Shared Registry
+//!
+//! When components (core, FS, retrieval) link different versions of this
+//! crate, each copy has its own [`metrics()`](crate::metrics::metrics)
+//! singleton, and counters silently split between them. The handshake
+//! makes every linked copy visible:
+//!
+//! - each component calls [`register_component`] once at startup
+//! - a copy that finds another version (or another copy of the same
+//!   version) logs a `Warn` event naming both
+//! - [`record_build_info`] exports `obs_build_info{version,component}`
+//!   per linked copy
+//! - [`shared_metrics`] sums the built-in metrics of every copy, and
+//!   [`Telemetry::snapshot`](crate::telemetry::Telemetry::snapshot) uses
+//!   it, so whichever copy exports reports process-wide totals
+//!
+//! # Shared Registry
+//!
+//! Copies of different versions share no Rust types, so they meet through
+//! the `EMBEDDENATOR_OBS_INSTANCES` environment variable. It holds
+//! `;`-separated entries of the form
+//!
+//! ```text
+//! abi|pid|version|component|export
+//! ```
+//!
+//! - `abi`: entry format version, currently `1`
+//! - `pid`: registering process; entries inherited by child processes
+//!   are ignored and dropped on the next registration
+//! - `export`: address (hex) of a `fn() -> Vec<(String, u64)>` returning
+//!   the copy's scalar built-in metrics by field name
+//!
+//! The format only ever gains fields at the end, so older copies keep
+//! reading newer entries. Per-shape timings are not shared. Register
+//! before starting threads: the environment is not synchronized with
+//! concurrent `getenv` calls in C code.
+//!
+//! Export from one copy per process; every copy reports the same totals.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::handshake;
+//!
+//! // In each component's init
+//! handshake::register_component("embeddenator-fs");
+//!
+//! handshake::record_build_info(&mut telemetry);
+//! ```
+
+use crate::obs::metrics::{metrics, MetricsSnapshot};
+use crate::obs::telemetry::Telemetry;
+use crate::obs::tracing::{record_event, EventLevel};
+
+/// Version of this copy of the crate.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Environment variable holding the shared registry.
+pub const INSTANCES_VAR: &str = "EMBEDDENATOR_OBS_INSTANCES";
+
+const ABI: u32 = 1;
+
+/// One copy of the crate linked into this process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkedInstance {
+    pub version: String,
+    /// Components registered through this copy
+    pub components: Vec<String>,
+    /// Whether this is the calling copy
+    pub is_self: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    abi: u32,
+    pid: u32,
+    version: String,
+    component: String,
+    export: usize,
+}
+
+impl Entry {
+    fn format(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{:x}",
+            self.abi, self.pid, self.version, self.component, self.export
+        )
+    }
+}
+
+fn parse_entries(value: &str) -> Vec<Entry> {
+    value
+        .split(';')
+        .filter_map(|entry| {
+            let mut fields = entry.split('|');
+            let abi = fields.next()?.parse().ok()?;
+            let pid = fields.next()?.parse().ok()?;
+            let version = fields.next()?.to_string();
+            let component = fields.next()?.to_string();
+            let export = usize::from_str_radix(fields.next()?, 16).ok()?;
+            Some(Entry {
+                abi,
+                pid,
+                version,
+                component,
+                export,
+            })
+        })
+        .collect()
+}
+
+/// Entries registered by this process.
+fn entries() -> Vec<Entry> {
+    let pid = std::process::id();
+    std::env::var(INSTANCES_VAR)
+        .map(|value| parse_entries(&value))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|entry| entry.pid == pid && entry.abi >= 1 && entry.export != 0)
+        .collect()
+}
+
+fn self_export() -> usize {
+    export_metrics as fn() -> Vec<(String, u64)> as usize
+}
+
+/// Register `component` as using this copy and check the other copies
+/// linked into the process, warning on each mismatch.
+pub fn register_component(component: &str) -> Vec<LinkedInstance> {
+    let component = component.replace(['|', ';'], "_");
+    let mut entries = entries();
+    let own = self_export();
+
+    for other in entries.iter().filter(|entry| entry.export != own) {
+        let message = if other.version == VERSION {
+            "duplicate embeddenator-obs copy linked"
+        } else {
+            "embeddenator-obs version mismatch"
+        };
+        record_event(
+            EventLevel::Warn,
+            message,
+            &[
+                ("component", &component),
+                ("version", VERSION),
+                ("other_component", &other.component),
+                ("other_version", &other.version),
+            ],
+        );
+    }
+
+    let entry = Entry {
+        abi: ABI,
+        pid: std::process::id(),
+        version: VERSION.to_string(),
+        component,
+        export: own,
+    };
+    if !entries.contains(&entry) {
+        entries.push(entry);
+    }
+    let value: Vec<String> = entries.iter().map(Entry::format).collect();
+    std::env::set_var(INSTANCES_VAR, value.join(";"));
+    linked_instances()
+}
+
+/// Copies registered in this process, the calling copy first.
+pub fn linked_instances() -> Vec<LinkedInstance> {
+    let own = self_export();
+    let mut instances: Vec<(usize, LinkedInstance)> = Vec::new();
+    for entry in entries() {
+        match instances
+            .iter_mut()
+            .find(|(export, _)| *export == entry.export)
+        {
+            Some((_, instance)) => instance.components.push(entry.component),
+            None => instances.push((
+                entry.export,
+                LinkedInstance {
+                    version: entry.version,
+                    components: vec![entry.component],
+                    is_self: entry.export == own,
+                },
+            )),
+        }
+    }
+    instances.sort_by_key(|(_, instance)| !instance.is_self);
+    instances
+        .into_iter()
+        .map(|(_, instance)| instance)
+        .collect()
+}
+
+/// Whether every registered copy has the same version.
+pub fn versions_consistent() -> bool {
+    linked_instances()
+        .iter()
+        .all(|instance| instance.version == VERSION)
+}
+
+/// Built-in metrics summed over every registered copy (maxima take the
+/// largest value); this copy's metrics when no other copy registered.
+pub fn shared_metrics() -> MetricsSnapshot {
+    let mut snapshot = metrics().snapshot();
+    let own = self_export();
+    let mut seen = vec![own];
+    for entry in entries() {
+        if seen.contains(&entry.export) {
+            continue;
+        }
+        seen.push(entry.export);
+        // SAFETY: entries are filtered to this process, and `export` was
+        // written by `register_component` of a copy linked into it from
+        // a `fn() -> Vec<(String, u64)>`, a signature fixed by the ABI.
+        let export: fn() -> Vec<(String, u64)> =
+            unsafe { std::mem::transmute::<usize, fn() -> Vec<(String, u64)>>(entry.export) };
+        for (name, value) in export() {
+            merge_field(&mut snapshot, &name, value);
+        }
+    }
+    snapshot
+}
+
+/// Write `obs_build_info{version,component}` (1 per registered component)
+/// and `obs_linked_copies` gauges.
+pub fn record_build_info(telemetry: &mut Telemetry) {
+    let instances = linked_instances();
+    for instance in &instances {
+        for component in &instance.components {
+            telemetry.set_gauge_with_labels(
+                "obs_build_info",
+                &[("version", &instance.version), ("component", component)],
+                1.0,
+            );
+        }
+    }
+    telemetry.set_gauge("obs_linked_copies", instances.len().max(1) as f64);
+}
+
+macro_rules! scalar_fields {
+    ($($field:ident),* $(,)?) => {
+        /// This copy's scalar built-in metrics by field name.
+        fn export_metrics() -> Vec<(String, u64)> {
+            let snapshot = metrics().snapshot();
+            vec![$((stringify!($field).to_string(), snapshot.$field)),*]
+        }
+
+        fn merge_field(snapshot: &mut MetricsSnapshot, name: &str, value: u64) {
+            match name {
+                $(stringify!($field) => {
+                    if name.ends_with("_max") {
+                        snapshot.$field = snapshot.$field.max(value);
+                    } else {
+                        snapshot.$field += value;
+                    }
+                })*
+                // Fields added by newer copies
+                _ => {}
+            }
+        }
+    };
+}
+
+scalar_fields!(
+    poison_recoveries_total,
+    poison_path_inodes,
+    poison_inodes,
+    poison_inode_paths,
+    poison_directories,
+    poison_file_cache,
+    sub_cache_hits,
+    sub_cache_misses,
+    sub_cache_evictions,
+    sub_cache_evictions_capacity,
+    sub_cache_evictions_ttl,
+    sub_cache_evictions_invalidation,
+    sub_cache_evictions_memory_pressure,
+    index_cache_hits,
+    index_cache_misses,
+    index_cache_evictions,
+    index_cache_evictions_capacity,
+    index_cache_evictions_ttl,
+    index_cache_evictions_invalidation,
+    index_cache_evictions_memory_pressure,
+    retrieval_query_calls,
+    retrieval_query_ns_total,
+    retrieval_query_ns_max,
+    rerank_calls,
+    rerank_ns_total,
+    rerank_ns_max,
+    hier_query_calls,
+    hier_query_ns_total,
+    hier_query_ns_max,
+    panics_total,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_roundtrip() {
+        let entry = Entry {
+            abi: 1,
+            pid: 42,
+            version: "0.20.3".to_string(),
+            component: "embeddenator-fs".to_string(),
+            export: 0xdead_beef,
+        };
+        let value = format!("{};garbage;2|42|0.22.0|core|ff|extra", entry.format());
+        let parsed = parse_entries(&value);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0], entry);
+        // Newer entry formats append fields
+        assert_eq!(parsed[1].abi, 2);
+        assert_eq!(parsed[1].export, 0xff);
+    }
+
+    #[test]
+    fn test_merge_fields() {
+        let mut snapshot = MetricsSnapshot::default();
+        merge_field(&mut snapshot, "sub_cache_hits", 3);
+        merge_field(&mut snapshot, "sub_cache_hits", 4);
+        merge_field(&mut snapshot, "rerank_ns_max", 10);
+        merge_field(&mut snapshot, "rerank_ns_max", 7);
+        merge_field(&mut snapshot, "added_in_a_later_version", 1);
+        assert_eq!(snapshot.sub_cache_hits, 7);
+        assert_eq!(snapshot.rerank_ns_max, 10);
+        assert!(export_metrics()
+            .iter()
+            .any(|(name, _)| name == "hier_query_ns_max"));
+    }
+}
@@ -0,0 +1,573 @@
+//! Health Checks and Liveness/Readiness Probes
+//!
+//! Components register named checks in a [`HealthRegistry`]; probes run
+//! them and aggregate the worst status. Liveness checks answer "should
+//! this process be restarted", readiness checks "should it receive
+//! traffic"; readiness runs both kinds.
+//!
+//! # Status Codes
+//!
+//! `Healthy` and `Degraded` answer `200`, `Unhealthy` answers `503`, so a
+//! degraded service (e.g. serving from a stale index) stays in rotation
+//! while reporting why. A check that panics counts as unhealthy.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::health::{CheckResult, HealthRegistry, HealthServer};
+//!
+//! let health = HealthRegistry::new();
+//! health.register_liveness("event_loop", || CheckResult::healthy());
+//! health.register_readiness("index", move || match index.state() {
+//!     State::Loaded => CheckResult::healthy(),
+//!     State::Stale(age) => CheckResult::degraded(format!("index {}s old", age)),
+//!     State::Loading => CheckResult::unhealthy("index loading"),
+//! });
+//!
+//! // GET /healthz, /readyz and /metrics on one port
+//! let _handle = HealthServer::bind("0.0.0.0:9898")?
+//!     .with_registry(health.clone())
+//!     .with_metrics(telemetry.clone(), PrometheusExporter::new("embeddenator"))
+//!     .with_query(QueryHandler::new().with_telemetry(telemetry.clone()))
+//!     .spawn();
+//! ```
+
+use crate::obs::logging;
+use crate::obs::process::{spawn_periodic, CollectorHandle};
+use crate::obs::prometheus::PrometheusExporter;
+use crate::obs::query_socket::QueryHandler;
+use crate::obs::telemetry::{escape_json, Telemetry};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Longest request line plus headers read from one connection.
+pub(crate) const MAX_REQUEST_BYTES: u64 = 16 * 1024;
+
+/// Time a client gets to send its whole request, and to take the response.
+pub(crate) const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connections served at once; further ones are closed unanswered.
+const MAX_CONNECTIONS: usize = 32;
+
+/// Outcome of a health check, ordered from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum HealthStatus {
+    #[default]
+    Healthy,
+    /// Working with reduced capacity or quality
+    Degraded,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthStatus::Healthy => "healthy",
+            HealthStatus::Degraded => "degraded",
+            HealthStatus::Unhealthy => "unhealthy",
+        }
+    }
+
+    /// HTTP status for probes: 503 only when unhealthy.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            HealthStatus::Unhealthy => 503,
+            _ => 200,
+        }
+    }
+}
+
+/// Result of one check run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub status: HealthStatus,
+    pub details: String,
+}
+
+impl CheckResult {
+    pub fn healthy() -> Self {
+        Self {
+            status: HealthStatus::Healthy,
+            details: String::new(),
+        }
+    }
+
+    pub fn degraded(details: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Degraded,
+            details: details.into(),
+        }
+    }
+
+    pub fn unhealthy(details: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Unhealthy,
+            details: details.into(),
+        }
+    }
+}
+
+/// Result of one check within a [`HealthReport`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckReport {
+    pub name: String,
+    pub result: CheckResult,
+    pub duration: Duration,
+}
+
+/// Aggregated probe result.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HealthReport {
+    /// Worst status of all checks; healthy when there are none
+    pub status: HealthStatus,
+    pub checks: Vec<CheckReport>,
+}
+
+impl HealthReport {
+    /// JSON body for probe endpoints.
+    pub fn to_json(&self) -> String {
+        let checks: Vec<String> = self
+            .checks
+            .iter()
+            .map(|check| {
+                format!(
+                    r#"{{"name":"{}","status":"{}","details":"{}","duration_ms":{:.3}}}"#,
+                    escape_json(&check.name),
+                    check.result.status.as_str(),
+                    escape_json(&check.result.details),
+                    check.duration.as_secs_f64() * 1000.0
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"status":"{}","checks":[{}]}}"#,
+            self.status.as_str(),
+            checks.join(",")
+        )
+    }
+}
+
+type CheckFn = Arc<dyn Fn() -> CheckResult + Send + Sync>;
+
+struct RegisteredCheck {
+    name: String,
+    liveness: bool,
+    check: CheckFn,
+}
+
+/// Named health checks; clones share the same checks.
+#[derive(Clone, Default)]
+pub struct HealthRegistry {
+    checks: Arc<RwLock<Vec<RegisteredCheck>>>,
+}
+
+impl HealthRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a check run by both liveness and readiness probes,
+    /// replacing any check with the same name.
+    pub fn register_liveness<F>(&self, name: impl Into<String>, check: F)
+    where
+        F: Fn() -> CheckResult + Send + Sync + 'static,
+    {
+        self.insert(name.into(), true, Arc::new(check));
+    }
+
+    /// Register a check run only by readiness probes, replacing any check
+    /// with the same name.
+    pub fn register_readiness<F>(&self, name: impl Into<String>, check: F)
+    where
+        F: Fn() -> CheckResult + Send + Sync + 'static,
+    {
+        self.insert(name.into(), false, Arc::new(check));
+    }
+
+    fn insert(&self, name: String, liveness: bool, check: CheckFn) {
+        if let Ok(mut checks) = self.checks.write() {
+            checks.retain(|c| c.name != name);
+            checks.push(RegisteredCheck {
+                name,
+                liveness,
+                check,
+            });
+        }
+    }
+
+    /// Remove a check; returns whether it existed.
+    pub fn unregister(&self, name: &str) -> bool {
+        let Ok(mut checks) = self.checks.write() else {
+            return false;
+        };
+        let before = checks.len();
+        checks.retain(|c| c.name != name);
+        checks.len() != before
+    }
+
+    /// Names of registered checks.
+    pub fn check_names(&self) -> Vec<String> {
+        self.checks
+            .read()
+            .map(|checks| checks.iter().map(|c| c.name.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Run liveness checks.
+    pub fn liveness(&self) -> HealthReport {
+        self.run(true)
+    }
+
+    /// Run liveness and readiness checks.
+    pub fn readiness(&self) -> HealthReport {
+        self.run(false)
+    }
+
+    fn run(&self, liveness_only: bool) -> HealthReport {
+        // Run outside the lock so checks may register or unregister
+        let selected: Vec<(String, CheckFn)> = match self.checks.read() {
+            Ok(checks) => checks
+                .iter()
+                .filter(|c| c.liveness || !liveness_only)
+                .map(|c| (c.name.clone(), c.check.clone()))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        let mut report = HealthReport::default();
+        for (name, check) in selected {
+            let start = Instant::now();
+            let result = catch_unwind(AssertUnwindSafe(|| check()))
+                .unwrap_or_else(|_| CheckResult::unhealthy("check panicked"));
+            report.status = report.status.max(result.status);
+            report.checks.push(CheckReport {
+                name,
+                result,
+                duration: start.elapsed(),
+            });
+        }
+        report
+    }
+
+    /// Answer a probe request path (`/healthz` or `/readyz`, query
+    /// ignored) with an HTTP status and JSON body, for mounting in an
+    /// existing server.
+    pub fn handle(&self, path: &str) -> Option<(u16, String)> {
+        let report = match path.split('?').next() {
+            Some("/healthz") | Some("/livez") => self.liveness(),
+            Some("/readyz") => self.readiness(),
+            _ => return None,
+        };
+        Some((report.status.http_status(), report.to_json()))
+    }
+}
+
+/// Minimal HTTP server for `/healthz`, `/livez` and `/readyz`, and
+/// optionally `/metrics` and `/query/<command>`.
+pub struct HealthServer {
+    listener: TcpListener,
+    registry: HealthRegistry,
+    metrics: Option<(Arc<Mutex<Telemetry>>, PrometheusExporter)>,
+    query: Option<QueryHandler>,
+}
+
+impl HealthServer {
+    /// Bind the listening socket.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            registry: HealthRegistry::new(),
+            metrics: None,
+            query: None,
+        })
+    }
+
+    /// Serve probes from an existing registry.
+    pub fn with_registry(mut self, registry: HealthRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Also serve `GET /metrics` from `telemetry`.
+    pub fn with_metrics(
+        mut self,
+        telemetry: Arc<Mutex<Telemetry>>,
+        exporter: PrometheusExporter,
+    ) -> Self {
+        self.metrics = Some((telemetry, exporter));
+        self
+    }
+
+    /// Also answer `GET /query/<command>` from `handler`, with `/`
+    /// separating arguments (`/query/top/5`).
+    pub fn with_query(mut self, handler: QueryHandler) -> Self {
+        self.query = Some(handler);
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Registry probed by this server; stays valid after
+    /// [`spawn`](Self::spawn).
+    pub fn registry(&self) -> HealthRegistry {
+        self.registry.clone()
+    }
+
+    /// Accept connections on a background thread and serve each on its
+    /// own thread, so a stalled client cannot hold up probes.
+    pub fn spawn(self) -> CollectorHandle {
+        let server = Arc::new(self);
+        let active = Arc::new(AtomicUsize::new(0));
+        spawn_periodic("obs-health-server", Duration::from_millis(50), move || {
+            while let Ok((stream, peer)) = server.listener.accept() {
+                if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    logging::debug(&format!("health server busy, dropping {}", peer));
+                    continue;
+                }
+                let server = server.clone();
+                let connection_active = active.clone();
+                let spawned = std::thread::Builder::new()
+                    .name("obs-health-conn".to_string())
+                    .spawn(move || {
+                        if let Err(e) = server.serve_connection(stream) {
+                            logging::debug(&format!("health request from {} failed: {}", peer, e));
+                        }
+                        connection_active.fetch_sub(1, Ordering::SeqCst);
+                    });
+                if spawned.is_err() {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+        })
+    }
+
+    fn serve_connection(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+        let deadline = DeadlineReader::new(stream.try_clone()?, REQUEST_TIMEOUT);
+        let mut reader = BufReader::new(deadline).take(MAX_REQUEST_BYTES);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        loop {
+            let mut line = String::new();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 || line.trim_end().is_empty() {
+                break;
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or("/"));
+        let (status, content_type, body) = if method != "GET" {
+            (405, "text/plain", "method not allowed\n".to_string())
+        } else if let Some((status, body)) = self.registry.handle(path) {
+            (status, "application/json", body)
+        } else if let (Some(handler), Some(command)) = (
+            &self.query,
+            path.split('?')
+                .next()
+                .and_then(|p| p.strip_prefix("/query/")),
+        ) {
+            let body = handler.handle(&command.replace('/', " "));
+            (200, "application/json", body + "\n")
+        } else {
+            match (&self.metrics, path.split('?').next()) {
+                (Some((telemetry, exporter)), Some("/metrics")) => {
+                    let snapshot = telemetry
+                        .lock()
+                        .map_err(|_| io::Error::other("telemetry lock poisoned"))?
+                        .snapshot();
+                    (200, exporter.content_type(), exporter.export(&snapshot))
+                }
+                _ => (404, "text/plain", "not found\n".to_string()),
+            }
+        };
+
+        let reason = match status {
+            200 => "OK",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Service Unavailable",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+            status,
+            reason,
+            content_type,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+}
+
+/// Socket reader that fails once the whole-request deadline passes,
+/// however slowly the client trickles bytes in.
+pub(crate) struct DeadlineReader<S = TcpStream> {
+    stream: S,
+    deadline: Instant,
+}
+
+impl<S: TimeoutStream> DeadlineReader<S> {
+    /// Read from `stream` for at most `timeout` from now.
+    pub(crate) fn new(stream: S, timeout: Duration) -> Self {
+        Self {
+            stream,
+            deadline: Instant::now() + timeout,
+        }
+    }
+}
+
+impl<S: TimeoutStream> Read for DeadlineReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "request deadline passed",
+            ));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Stream whose blocking reads can time out.
+pub(crate) trait TimeoutStream: Read {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl TimeoutStream for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(unix)]
+impl TimeoutStream for std::os::unix::net::UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        std::os::unix::net::UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn test_aggregates_worst_status() {
+        let health = HealthRegistry::new();
+        assert_eq!(health.readiness().status, HealthStatus::Healthy);
+
+        health.register_liveness("loop", CheckResult::healthy);
+        health.register_readiness("index", || CheckResult::degraded("stale \"v3\""));
+        let report = health.liveness();
+        assert_eq!(report.status, HealthStatus::Healthy);
+        assert_eq!(report.checks.len(), 1);
+
+        let report = health.readiness();
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert_eq!(report.status.http_status(), 200);
+        assert!(report
+            .to_json()
+            .starts_with(r#"{"status":"degraded","checks":[{"name":"loop","status":"healthy","#));
+        assert!(report.to_json().contains(r#""details":"stale \"v3\"""#));
+
+        health.register_readiness("panics", || panic!("boom"));
+        let (status, body) = health.handle("/readyz?verbose").unwrap();
+        assert_eq!(status, 503);
+        assert!(body.contains(r#""details":"check panicked""#));
+        assert!(health.unregister("panics"));
+        assert_eq!(health.handle("/readyz").unwrap().0, 200);
+        assert!(health.handle("/other").is_none());
+    }
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: test\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_server_probes_and_metrics() {
+        let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+        telemetry.lock().unwrap().set_gauge("queue_depth", 3.0);
+        let ready = Arc::new(AtomicBool::new(false));
+        let flag = ready.clone();
+
+        let server = HealthServer::bind("127.0.0.1:0")
+            .unwrap()
+            .with_metrics(telemetry.clone(), PrometheusExporter::new("test"))
+            .with_query(QueryHandler::new().with_telemetry(telemetry));
+        let addr = server.local_addr().unwrap();
+        server.registry().register_readiness("warmup", move || {
+            if flag.load(Ordering::SeqCst) {
+                CheckResult::healthy()
+            } else {
+                CheckResult::unhealthy("warming up")
+            }
+        });
+        let handle = server.spawn();
+
+        assert!(get(addr, "/healthz").starts_with("HTTP/1.1 200 OK\r\n"));
+        let response = get(addr, "/readyz");
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.contains(r#""status":"unhealthy","details":"warming up""#));
+        ready.store(true, Ordering::SeqCst);
+        assert!(get(addr, "/readyz").starts_with("HTTP/1.1 200 OK\r\n"));
+
+        assert!(get(addr, "/metrics").contains("queue_depth 3"));
+        assert!(get(addr, "/query/metric/queue_depth")
+            .ends_with("{\"metric\":\"queue_depth\",\"values\":{\"queue_depth\":3.0}}\n"));
+        assert!(get(addr, "/nope").starts_with("HTTP/1.1 404"));
+        handle.stop();
+    }
+
+    #[test]
+    fn test_stalled_client_does_not_block_probes() {
+        let server = HealthServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.spawn();
+
+        // Trickles a request that never ends
+        let mut stalled = TcpStream::connect(addr).unwrap();
+        stalled
+            .write_all(b"GET /healthz HTTP/1.1\r\nX-Slow: ")
+            .unwrap();
+        // Sends an endless header line past the request size bound
+        let mut oversized = TcpStream::connect(addr).unwrap();
+        oversized
+            .write_all(&vec![b'a'; MAX_REQUEST_BYTES as usize + 1])
+            .unwrap();
+
+        let started = Instant::now();
+        assert!(get(addr, "/healthz").starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // Answered (or reset, with bytes left unread) well before the deadline
+        oversized
+            .set_read_timeout(Some(REQUEST_TIMEOUT * 2))
+            .unwrap();
+        let _ = oversized.read_to_end(&mut Vec::new());
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let started = Instant::now();
+        handle.stop();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        drop(stalled);
+    }
+}
@@ -11,26 +11,53 @@
 //! - Known TSC frequency for conversion
 //! - Invariant TSC support (modern CPUs)
 //!
-//! On Linux, we use `clock_gettime(CLOCK_MONOTONIC_RAW)` which provides
-//! nanosecond granularity. For sub-nanosecond estimation, we perform
-//! multiple measurements and statistical analysis.
+//! On ARM64 the virtual counter (`CNTVCT_EL0`) takes the place of the TSC,
+//! with its frequency read from `CNTFRQ_EL0` instead of calibrated.
+//!
+//! Without the invariant bit (CPUID `0x8000_0007`, often masked by
+//! hypervisors) the TSC rate can follow CPU frequency scaling, so
+//! converted picoseconds are unreliable. [`HiResTimer::clock_quality`]
+//! reports which case applies, and a warning is logged once when timing
+//! runs on a non-invariant TSC.
+//!
+//! Without a usable counter, timers read the platform clock at its own
+//! tick resolution: `QueryPerformanceCounter` on Windows,
+//! `mach_absolute_time` on macOS and `std::time::Instant` (nanoseconds)
+//! elsewhere. For sub-nanosecond estimation, we perform multiple
+//! measurements and statistical analysis.
 //!
 //! # Performance Notes
 //!
 //! TSC frequency is cached after first calibration to avoid repeated
-//! file I/O and calibration overhead on timer creation.
+//! file I/O and calibration overhead on timer creation. [`calibrate`]
+//! replaces it with a longer measurement when accuracy matters more than
+//! startup time; [`calibration`] reports the frequency in use and its
+//! estimated error.
+//!
+//! A start/elapsed pair costs tens of nanoseconds itself;
+//! [`HiResTimer::compensated`] subtracts that overhead for measurements
+//! of similarly short operations.
+//!
+//! [`HiResTimer::start_with_clock`] reads a [`Clock`] instead, so tests
+//! can drive timers with a [`MockClock`](crate::obs::clock::MockClock).
 
+use crate::obs::clock::Clock;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Cached TSC frequency (Hz) - computed once on first use
-#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+#[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
 static CACHED_TSC_FREQ: AtomicU64 = AtomicU64::new(0);
 
 /// Sentinel value indicating TSC freq needs calibration
-#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+#[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
 const TSC_UNCALIBRATED: u64 = 0;
 
+/// Calibration behind `CACHED_TSC_FREQ`, with its error estimate
+#[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
+static CALIBRATION: std::sync::Mutex<Option<Calibration>> = std::sync::Mutex::new(None);
+
 /// Picosecond timestamp (1 ps = 10^-12 seconds)
 /// We store as u64 picoseconds, giving us ~213 days of range
 pub type Picoseconds = u64;
@@ -45,7 +72,13 @@ pub const PS_PER_MS: u64 = 1_000_000_000;
 pub const PS_PER_SEC: u64 = 1_000_000_000_000;
 
 /// High-resolution timing result with uncertainty bounds
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///
+/// Ordered by picoseconds first, then by the remaining fields. Adding
+/// timestamps adds their uncertainties; dividing by a count averages
+/// them, so `samples.iter().copied().sum::<HiResTimestamp>() / n` is a
+/// mean with the mean uncertainty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HiResTimestamp {
     /// Measured time in picoseconds
     pub picoseconds: Picoseconds,
@@ -125,6 +158,82 @@ impl HiResTimestamp {
     }
 }
 
+impl std::ops::Add for HiResTimestamp {
+    type Output = HiResTimestamp;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        HiResTimestamp {
+            picoseconds: self.picoseconds.saturating_add(rhs.picoseconds),
+            uncertainty_low: self.uncertainty_low.saturating_add(rhs.uncertainty_low),
+            uncertainty_high: self.uncertainty_high.saturating_add(rhs.uncertainty_high),
+            is_estimated: self.is_estimated || rhs.is_estimated,
+        }
+    }
+}
+
+impl std::ops::AddAssign for HiResTimestamp {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+/// Divide time and uncertainties by a count, e.g. to average a sum.
+///
+/// Panics if `rhs` is zero.
+impl std::ops::Div<u64> for HiResTimestamp {
+    type Output = HiResTimestamp;
+
+    fn div(self, rhs: u64) -> Self::Output {
+        HiResTimestamp {
+            picoseconds: self.picoseconds / rhs,
+            uncertainty_low: self.uncertainty_low / rhs,
+            uncertainty_high: self.uncertainty_high / rhs,
+            is_estimated: self.is_estimated,
+        }
+    }
+}
+
+impl std::iter::Sum for HiResTimestamp {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(HiResTimestamp::from(Duration::ZERO), |sum, t| sum + t)
+    }
+}
+
+/// Exact: a `Duration` has nanosecond resolution and no uncertainty.
+/// Saturates past ~213 days.
+impl From<Duration> for HiResTimestamp {
+    fn from(duration: Duration) -> Self {
+        HiResTimestamp {
+            picoseconds: duration
+                .as_nanos()
+                .saturating_mul(PS_PER_NS as u128)
+                .min(u64::MAX as u128) as u64,
+            uncertainty_low: 0,
+            uncertainty_high: 0,
+            is_estimated: false,
+        }
+    }
+}
+
+/// Truncates to whole nanoseconds.
+impl From<HiResTimestamp> for Duration {
+    fn from(timestamp: HiResTimestamp) -> Self {
+        Duration::from_nanos(timestamp.as_nanos())
+    }
+}
+
+/// [`format`](HiResTimestamp::format); `{:#}` adds the uncertainty like
+/// [`format_with_uncertainty`](HiResTimestamp::format_with_uncertainty).
+impl std::fmt::Display for HiResTimestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            f.write_str(&self.format_with_uncertainty())
+        } else {
+            f.write_str(&self.format())
+        }
+    }
+}
+
 impl std::ops::Sub for HiResTimestamp {
     type Output = HiResTimestamp;
 
@@ -139,49 +248,326 @@ impl std::ops::Sub for HiResTimestamp {
 }
 
 /// High-resolution timer using best available clock source
+///
+/// Time spent between [`pause`](Self::pause) and [`resume`](Self::resume)
+/// is excluded from [`elapsed`](Self::elapsed).
 pub struct HiResTimer {
-    /// Start instant for std timing
-    start_instant: Instant,
-    /// Start TSC value (if available)
-    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    /// Start of the current running segment on the platform clock
+    start_os_ticks: u64,
+    /// Start counter value (TSC or CNTVCT, if available)
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
     start_tsc: u64,
-    /// TSC frequency in Hz (calibrated)
-    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    /// Counter frequency in Hz (calibrated)
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
     tsc_freq_hz: u64,
+    /// Counter reads fenced against reordering (`start_serialized`)
+    serialized: bool,
+    /// Read overhead subtracted from each segment (`compensated`)
+    overhead: Option<HiResTimestamp>,
+    /// Active time of segments closed by `pause`
+    accumulated: Option<HiResTimestamp>,
+    paused: bool,
+    /// Active time at each `split`
+    splits: Vec<HiResTimestamp>,
+    /// Named checkpoints recorded by `lap`
+    laps: Vec<Lap>,
+    /// Clock replacing the counters (`start_with_clock`), with the start
+    /// of the current running segment
+    clock: Option<(Arc<dyn Clock>, Instant)>,
+}
+
+/// Named checkpoint recorded by [`HiResTimer::lap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lap {
+    pub name: &'static str,
+    /// Active time since the previous lap (or timer start)
+    pub delta: HiResTimestamp,
+    /// Active time since timer start
+    pub total: HiResTimestamp,
 }
 
 impl HiResTimer {
     /// Create and start a new high-resolution timer
     #[inline]
     pub fn start() -> Self {
-        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
         {
             let start_tsc = rdtsc();
-            let start_instant = Instant::now();
+            let start_os_ticks = os_ticks();
             let tsc_freq_hz = get_tsc_frequency();
 
             HiResTimer {
-                start_instant,
+                start_os_ticks,
                 start_tsc,
                 tsc_freq_hz,
+                serialized: false,
+                overhead: None,
+                accumulated: None,
+                paused: false,
+                splits: Vec::new(),
+                laps: Vec::new(),
+                clock: None,
             }
         }
 
-        #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64")))]
         {
             HiResTimer {
-                start_instant: Instant::now(),
+                start_os_ticks: os_ticks(),
+                serialized: false,
+                overhead: None,
+                accumulated: None,
+                paused: false,
+                splits: Vec::new(),
+                laps: Vec::new(),
+                clock: None,
             }
         }
     }
 
-    /// Get elapsed time with picosecond resolution (where possible)
+    /// Create and start a timer whose counter reads cannot be reordered
+    /// around the measured code.
+    ///
+    /// On x86 the start read is `lfence; rdtsc; lfence` and every later
+    /// read `rdtscp; lfence` (falling back to `lfence; rdtsc` on CPUs
+    /// without RDTSCP), so instructions before the start or after the end
+    /// cannot execute inside the measured window. ARM64 counter reads are
+    /// always preceded by `isb`. Costs a few dozen cycles per read; use
+    /// it for microbenchmarks of sub-100ns operations.
+    #[inline]
+    pub fn start_serialized() -> Self {
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
+        {
+            // Calibrate before the fenced read, not inside the window
+            let tsc_freq_hz = get_tsc_frequency();
+            let start_os_ticks = os_ticks();
+            let start_tsc = read_counter_serialized_start();
+
+            HiResTimer {
+                start_os_ticks,
+                start_tsc,
+                tsc_freq_hz,
+                serialized: true,
+                overhead: None,
+                accumulated: None,
+                paused: false,
+                splits: Vec::new(),
+                laps: Vec::new(),
+                clock: None,
+            }
+        }
+
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64")))]
+        {
+            let mut timer = Self::start();
+            timer.serialized = true;
+            timer
+        }
+    }
+
+    /// Create and start a timer reading `clock` instead of the hardware
+    /// counters, e.g. a [`MockClock`](crate::obs::clock::MockClock) in
+    /// tests. Readings have the clock's nanosecond resolution.
+    pub fn start_with_clock(clock: Arc<dyn Clock>) -> Self {
+        let mut timer = Self::start();
+        let start = clock.now();
+        timer.clock = Some((clock, start));
+        timer
+    }
+
+    /// Whether counter reads are serialized ([`start_serialized`](Self::start_serialized)).
+    pub fn is_serialized(&self) -> bool {
+        self.serialized
+    }
+
+    /// Get elapsed active time with picosecond resolution (where possible)
     #[inline]
     pub fn elapsed(&self) -> HiResTimestamp {
-        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+        match (self.accumulated, self.paused) {
+            (Some(accumulated), true) => accumulated,
+            (Some(accumulated), false) => accumulated + self.segment(),
+            (None, _) => self.segment(),
+        }
+    }
+
+    /// Stop accumulating time until [`resume`](Self::resume).
+    ///
+    /// Pausing a paused timer has no effect.
+    pub fn pause(&mut self) {
+        if self.paused {
+            return;
+        }
+        let segment = self.segment();
+        self.accumulated = Some(match self.accumulated {
+            Some(accumulated) => accumulated + segment,
+            None => segment,
+        });
+        self.paused = true;
+    }
+
+    /// Continue accumulating time after [`pause`](Self::pause).
+    ///
+    /// Resuming a running timer has no effect.
+    pub fn resume(&mut self) {
+        if !self.paused {
+            return;
+        }
+        self.restart_segment();
+        self.paused = false;
+    }
+
+    /// Subtract the timer's own overhead ([`overhead`](Self::overhead))
+    /// from every running segment and widen the uncertainty bounds by its
+    /// spread, so operations of a few dozen nanoseconds are not dominated
+    /// by the timer.
+    ///
+    /// Call it on a freshly started timer: the first call measures the
+    /// overhead once per process, and the current segment restarts
+    /// afterwards. Results are clamped at zero. Timers reading a
+    /// [`Clock`] are returned unchanged.
+    pub fn compensated(mut self) -> Self {
+        if self.clock.is_some() {
+            return self;
+        }
+        self.overhead = Some(if self.serialized {
+            static SERIALIZED: std::sync::OnceLock<HiResTimestamp> = std::sync::OnceLock::new();
+            *SERIALIZED.get_or_init(|| measure_overhead(HiResTimer::start_serialized))
+        } else {
+            HiResTimer::overhead()
+        });
+        if !self.paused {
+            self.restart_segment();
+        }
+        self
+    }
+
+    /// Time an empty `start()` / `elapsed()` pair reports on this host:
+    /// the median of 1000 runs, with half the 10th-90th percentile spread
+    /// as uncertainty. Measured once per process.
+    pub fn overhead() -> HiResTimestamp {
+        static PLAIN: std::sync::OnceLock<HiResTimestamp> = std::sync::OnceLock::new();
+        *PLAIN.get_or_init(|| measure_overhead(HiResTimer::start))
+    }
+
+    /// Whether [`compensated`](Self::compensated) applies.
+    pub fn is_compensated(&self) -> bool {
+        self.overhead.is_some()
+    }
+
+    fn restart_segment(&mut self) {
+        if let Some((clock, start)) = &mut self.clock {
+            *start = clock.now();
+            return;
+        }
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
+        {
+            self.start_tsc = if self.serialized {
+                read_counter_serialized_start()
+            } else {
+                rdtsc()
+            };
+        }
+        self.start_os_ticks = os_ticks();
+    }
+
+    /// Whether the timer is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Record and return the active time elapsed so far.
+    pub fn split(&mut self) -> HiResTimestamp {
+        let elapsed = self.elapsed();
+        self.splits.push(elapsed);
+        elapsed
+    }
+
+    /// Active time at each [`split`](Self::split), in order.
+    pub fn splits(&self) -> &[HiResTimestamp] {
+        &self.splits
+    }
+
+    /// Record a named checkpoint and return the active time since the
+    /// previous lap (or timer start).
+    ///
+    /// Names are `&'static str` so laps in tight loops don't allocate
+    /// beyond the lap list itself.
+    pub fn lap(&mut self, name: &'static str) -> HiResTimestamp {
+        let total = self.elapsed();
+        let delta = match self.laps.last() {
+            Some(previous) => total - previous.total,
+            None => total,
+        };
+        self.laps.push(Lap { name, delta, total });
+        delta
+    }
+
+    /// Laps recorded so far, in order.
+    pub fn laps(&self) -> &[Lap] {
+        &self.laps
+    }
+
+    /// Format laps as a table of deltas, cumulative times and shares of
+    /// the last lap's total.
+    pub fn lap_report(&self) -> String {
+        let Some(last) = self.laps.last() else {
+            return "no laps".to_string();
+        };
+        let width = self
+            .laps
+            .iter()
+            .map(|lap| lap.name.len())
+            .max()
+            .unwrap_or(0);
+        let mut report = String::new();
+        for lap in &self.laps {
+            let share = if last.total.picoseconds > 0 {
+                lap.delta.picoseconds as f64 / last.total.picoseconds as f64 * 100.0
+            } else {
+                0.0
+            };
+            report.push_str(&format!(
+                "{:<width$}  +{:>12}  {:>12}  {:5.1}%\n",
+                lap.name,
+                lap.delta.format(),
+                lap.total.format(),
+                share,
+                width = width
+            ));
+        }
+        report
+    }
+
+    /// Time since the current running segment started, less the timer
+    /// overhead if compensated.
+    #[inline]
+    fn segment(&self) -> HiResTimestamp {
+        let raw = self.raw_segment();
+        match self.overhead {
+            Some(overhead) => HiResTimestamp {
+                picoseconds: raw.picoseconds.saturating_sub(overhead.picoseconds),
+                uncertainty_low: raw.uncertainty_low + overhead.uncertainty_low,
+                uncertainty_high: raw.uncertainty_high + overhead.uncertainty_high,
+                is_estimated: true,
+            },
+            None => raw,
+        }
+    }
+
+    #[inline]
+    fn raw_segment(&self) -> HiResTimestamp {
+        if let Some((clock, start)) = &self.clock {
+            return HiResTimestamp::from(clock.now().saturating_duration_since(*start));
+        }
+
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
         {
             if self.tsc_freq_hz > 0 {
-                let end_tsc = rdtsc();
+                let end_tsc = if self.serialized {
+                    read_counter_serialized_end()
+                } else {
+                    rdtsc()
+                };
                 let cycles = end_tsc.saturating_sub(self.start_tsc);
 
                 // Convert cycles to picoseconds: (cycles * 10^12) / freq_hz
@@ -196,9 +582,20 @@ impl HiResTimer {
             }
         }
 
-        // Fallback to std::time::Instant (nanosecond resolution)
-        let elapsed = self.start_instant.elapsed();
-        HiResTimestamp::from_nanos(elapsed.as_nanos().min(u64::MAX as u128) as u64)
+        // Fallback to the platform clock
+        let ticks = os_ticks().saturating_sub(self.start_os_ticks);
+        let freq = os_tick_frequency();
+        if freq == 1_000_000_000 {
+            return HiResTimestamp::from_nanos(ticks);
+        }
+        let ps = (ticks as u128 * PS_PER_SEC as u128 / freq as u128).min(u64::MAX as u128) as u64;
+        HiResTimestamp {
+            picoseconds: ps,
+            // Half a tick either way
+            uncertainty_low: PS_PER_SEC / freq / 2,
+            uncertainty_high: PS_PER_SEC / freq / 2,
+            is_estimated: false,
+        }
     }
 
     /// Get elapsed nanoseconds (convenience method)
@@ -214,6 +611,72 @@ impl HiResTimer {
     }
 }
 
+/// How far [`HiResTimer`] readings on this host can be trusted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ClockQuality {
+    /// Constant-rate hardware counter: TSC with the invariant bit, or the
+    /// ARM64 generic timer
+    InvariantTsc,
+    /// TSC whose rate may follow CPU frequency changes or stop in sleep
+    /// states; picosecond figures can be off by the frequency ratio
+    NonInvariantTsc,
+    /// No usable counter; the platform clock (`QueryPerformanceCounter`,
+    /// `mach_absolute_time` or `std::time::Instant`) at its tick resolution
+    FallbackMonotonic,
+}
+
+impl ClockQuality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClockQuality::InvariantTsc => "invariant_tsc",
+            ClockQuality::NonInvariantTsc => "non_invariant_tsc",
+            ClockQuality::FallbackMonotonic => "fallback_monotonic",
+        }
+    }
+
+    /// Whether readings are accurate to the reported uncertainty.
+    pub fn is_trustworthy(&self) -> bool {
+        !matches!(self, ClockQuality::NonInvariantTsc)
+    }
+}
+
+impl HiResTimer {
+    /// Quality of the clock backing timers on this host.
+    ///
+    /// The first timer on a host with a non-invariant TSC also logs a
+    /// warning.
+    pub fn clock_quality() -> ClockQuality {
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
+        {
+            if get_tsc_frequency() == 0 {
+                ClockQuality::FallbackMonotonic
+            } else if counter_is_invariant() {
+                ClockQuality::InvariantTsc
+            } else {
+                ClockQuality::NonInvariantTsc
+            }
+        }
+
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64")))]
+        {
+            ClockQuality::FallbackMonotonic
+        }
+    }
+}
+
+/// Median reading of an empty timer from `start`.
+fn measure_overhead(start: fn() -> HiResTimer) -> HiResTimestamp {
+    const SAMPLES: usize = 1_000;
+    // Calibrate and warm caches outside the samples
+    std::hint::black_box(start().elapsed());
+    let mut samples: Vec<Picoseconds> = (0..SAMPLES)
+        .map(|_| std::hint::black_box(start()).elapsed().picoseconds)
+        .collect();
+    samples.sort_unstable();
+    let spread = samples[SAMPLES * 9 / 10] - samples[SAMPLES / 10];
+    HiResTimestamp::from_picos(samples[SAMPLES / 2], spread / 2)
+}
+
 /// Read TSC (Time Stamp Counter) on x86/x86_64
 #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
 #[inline]
@@ -234,7 +697,7 @@ fn rdtsc() -> u64 {
 /// This is the critical optimization - we only calibrate once and cache
 /// the result in a static atomic, avoiding expensive file I/O on every
 /// timer creation.
-#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+#[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
 #[inline]
 fn get_tsc_frequency() -> u64 {
     // Fast path: return cached value
@@ -243,58 +706,475 @@ fn get_tsc_frequency() -> u64 {
         return cached;
     }
 
-    // Slow path: calibrate and cache
-    let freq = calibrate_tsc_frequency();
+    // Slow path: calibrate and cache, unless another thread or an
+    // explicit `calibrate` got there first
+    let mut current = CALIBRATION.lock().unwrap_or_else(|e| e.into_inner());
+    let calibration = *current.get_or_insert_with(calibrate_tsc_frequency);
+    publish_frequency(calibration.frequency_hz);
+    calibration.frequency_hz
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
+fn store_calibration(calibration: Calibration) {
+    let mut current = CALIBRATION.lock().unwrap_or_else(|e| e.into_inner());
+    *current = Some(calibration);
+    // Under the lock, so the cached frequency matches `CALIBRATION`
+    publish_frequency(calibration.frequency_hz);
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
+fn publish_frequency(freq: u64) {
     CACHED_TSC_FREQ.store(freq, Ordering::Relaxed);
-    freq
+    if freq > 0 && !counter_is_invariant() {
+        warn_non_invariant(freq);
+    }
+}
+
+/// Where the counter frequency came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CalibrationSource {
+    /// Kernel-reported TSC frequency (`/sys/.../tsc_freq_khz`)
+    Kernel,
+    /// `cpu MHz` from `/proc/cpuinfo`: the core clock, which turbo and
+    /// frequency scaling move away from the TSC rate
+    CpuInfo,
+    /// Counter ticks measured against the monotonic clock
+    Measured,
+    /// Fixed by the architecture (`CNTFRQ_EL0` on ARM64)
+    Architectural,
+}
+
+impl CalibrationSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CalibrationSource::Kernel => "kernel",
+            CalibrationSource::CpuInfo => "cpuinfo",
+            CalibrationSource::Measured => "measured",
+            CalibrationSource::Architectural => "architectural",
+        }
+    }
+}
+
+/// Counter frequency used to convert ticks to picoseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    /// Counter frequency (Hz); zero if the counter is unusable
+    pub frequency_hz: u64,
+    /// Estimated relative error (parts per million); timings inherit it
+    pub error_ppm: f64,
+    pub source: CalibrationSource,
+}
+
+impl Calibration {
+    /// Estimated frequency error (Hz).
+    pub fn error_hz(&self) -> u64 {
+        (self.frequency_hz as f64 * self.error_ppm / 1e6).ceil() as u64
+    }
+}
+
+/// Measure the counter frequency over `duration` and use it for timers
+/// started from now on.
+///
+/// The implicit calibration prefers kernel-reported frequencies and falls
+/// back to a 1ms measurement, which can be off by several percent. Call
+/// this at startup with 100ms or more for sub-0.1% error, and again after
+/// suspend/resume or VM migration, which can change the TSC rate. Blocks
+/// the calling thread for `duration`; timers already running keep their
+/// frequency.
+///
+/// On ARM64 the frequency is architectural and returned without waiting.
+/// Returns `None` on targets without a counter backend.
+pub fn calibrate(duration: Duration) -> Option<Calibration> {
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    {
+        let calibration = measure_counter_frequency(duration);
+        store_calibration(calibration);
+        Some(calibration)
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        let _ = duration;
+        let calibration = calibrate_tsc_frequency();
+        store_calibration(calibration);
+        Some(calibration)
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64")))]
+    {
+        let _ = duration;
+        None
+    }
+}
+
+/// Calibration currently used by new timers, performing the implicit one
+/// if none ran yet. `None` when timers fall back to the platform clock.
+pub fn calibration() -> Option<Calibration> {
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
+    {
+        if get_tsc_frequency() == 0 {
+            return None;
+        }
+        *CALIBRATION.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64")))]
+    {
+        None
+    }
+}
+
+/// Log once that TSC-derived timings are untrustworthy on this host.
+#[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
+fn warn_non_invariant(freq: u64) {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| {
+        crate::obs::tracing::record_event(
+            crate::obs::tracing::EventLevel::Warn,
+            "TSC is not invariant; hi-res timings may drift with CPU frequency and across cores",
+            &[("tsc_freq_hz", &freq.to_string())],
+        );
+    });
+}
+
+/// Whether the TSC ticks at a constant rate through frequency changes and
+/// deep sleep states (CPUID leaf `0x8000_0007`, EDX bit 8), detected once.
+///
+/// Hypervisors often hide the bit, so many virtual machines report a
+/// non-invariant TSC even when the host has one.
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+fn counter_is_invariant() -> bool {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::__cpuid;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::__cpuid;
+
+    static INVARIANT: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *INVARIANT.get_or_init(|| {
+        // SAFETY: as in `has_rdtscp`.
+        #[allow(unused_unsafe)]
+        unsafe {
+            __cpuid(0x8000_0000).eax >= 0x8000_0007 && __cpuid(0x8000_0007).edx & (1 << 8) != 0
+        }
+    })
+}
+
+/// The ARM generic timer runs at a fixed frequency by architecture.
+#[cfg(target_arch = "aarch64")]
+fn counter_is_invariant() -> bool {
+    true
+}
+
+/// Counter read that later instructions cannot start before and earlier
+/// ones cannot finish after.
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+#[inline]
+fn read_counter_serialized_start() -> u64 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::{_mm_lfence, _rdtsc};
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::{_mm_lfence, _rdtsc};
+
+    // SAFETY: LFENCE is part of SSE2, present on every x86_64 CPU and on
+    // any x86 CPU with an invariant TSC worth measuring with.
+    unsafe {
+        _mm_lfence();
+        let ticks = _rdtsc();
+        _mm_lfence();
+        ticks
+    }
+}
+
+/// Counter read that waits for all earlier instructions to finish.
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+#[inline]
+fn read_counter_serialized_end() -> u64 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::{__rdtscp, _mm_lfence, _rdtsc};
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::{__rdtscp, _mm_lfence, _rdtsc};
+
+    // SAFETY: RDTSCP is only issued when the CPU reports it; LFENCE as
+    // in `read_counter_serialized_start`.
+    unsafe {
+        let ticks = if has_rdtscp() {
+            let mut aux = 0u32;
+            __rdtscp(&mut aux)
+        } else {
+            _mm_lfence();
+            _rdtsc()
+        };
+        _mm_lfence();
+        ticks
+    }
+}
+
+/// Whether the CPU supports RDTSCP (CPUID leaf `0x8000_0001`, EDX bit 27),
+/// detected once.
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+fn has_rdtscp() -> bool {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::__cpuid;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::__cpuid;
+
+    static RDTSCP: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *RDTSCP.get_or_init(|| {
+        // SAFETY: CPUID is available on every CPU this code runs on;
+        // extended leaves are only queried when reported. (`__cpuid` is
+        // safe from Rust 1.87; the block keeps the MSRV building.)
+        #[allow(unused_unsafe)]
+        unsafe {
+            __cpuid(0x8000_0000).eax >= 0x8000_0001 && __cpuid(0x8000_0001).edx & (1 << 27) != 0
+        }
+    })
+}
+
+/// ARM64 counter reads are already preceded by `isb`.
+#[cfg(target_arch = "aarch64")]
+#[inline]
+fn read_counter_serialized_start() -> u64 {
+    rdtsc()
+}
+
+#[cfg(target_arch = "aarch64")]
+#[inline]
+fn read_counter_serialized_end() -> u64 {
+    rdtsc()
+}
+
+/// Read the virtual counter (`CNTVCT_EL0`) on ARM64.
+///
+/// The `isb` keeps the read from being hoisted above earlier
+/// instructions, matching the ordering of a plain `rdtsc` on x86.
+#[cfg(target_arch = "aarch64")]
+#[inline]
+fn rdtsc() -> u64 {
+    let ticks: u64;
+    // SAFETY: CNTVCT_EL0 is readable from EL0 on Linux and macOS, which
+    // enable user access to the virtual counter.
+    unsafe {
+        std::arch::asm!("isb", "mrs {}, cntvct_el0", out(reg) ticks, options(nomem, nostack));
+    }
+    ticks
+}
+
+/// Counter frequency from `CNTFRQ_EL0`, set by firmware (24 MHz on
+/// Apple Silicon, 1 GHz on Graviton 3). Zero disables the counter
+/// backend.
+#[cfg(target_arch = "aarch64")]
+fn calibrate_tsc_frequency() -> Calibration {
+    let freq: u64;
+    // SAFETY: CNTFRQ_EL0 is readable from EL0 whenever CNTVCT_EL0 is.
+    unsafe {
+        std::arch::asm!("mrs {}, cntfrq_el0", out(reg) freq, options(nomem, nostack));
+    }
+    Calibration {
+        frequency_hz: freq,
+        error_ppm: 0.0,
+        source: CalibrationSource::Architectural,
+    }
 }
 
 /// Actually calibrate the TSC frequency (called once)
 #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
-fn calibrate_tsc_frequency() -> u64 {
+fn calibrate_tsc_frequency() -> Calibration {
     // Try to read from sysfs (Linux) - fastest path
     if let Ok(content) = std::fs::read_to_string("/sys/devices/system/cpu/cpu0/tsc_freq_khz") {
         if let Ok(khz) = content.trim().parse::<u64>() {
-            return khz * 1000;
+            return Calibration {
+                frequency_hz: khz * 1000,
+                // Rounded to whole kHz
+                error_ppm: 500.0 * 1e6 / (khz * 1000).max(1) as f64,
+                source: CalibrationSource::Kernel,
+            };
         }
     }
 
+    // macOS publishes the TSC rate it measured at boot
+    #[cfg(target_os = "macos")]
+    if let Some(hz) = sysctl_u64(c"machdep.tsc.frequency").filter(|&hz| hz > 0) {
+        return Calibration {
+            frequency_hz: hz,
+            error_ppm: 0.5 * 1e6 / hz as f64,
+            source: CalibrationSource::Kernel,
+        };
+    }
+
     // Try cpuinfo for CPU MHz (less accurate but available)
     if let Ok(content) = std::fs::read_to_string("/proc/cpuinfo") {
         for line in content.lines() {
             if line.starts_with("cpu MHz") {
                 if let Some(mhz_str) = line.split(':').nth(1) {
                     if let Ok(mhz) = mhz_str.trim().parse::<f64>() {
-                        return (mhz * 1_000_000.0) as u64;
+                        return Calibration {
+                            frequency_hz: (mhz * 1_000_000.0) as u64,
+                            // Core clock, not the TSC rate; a conservative 5%
+                            error_ppm: 50_000.0,
+                            source: CalibrationSource::CpuInfo,
+                        };
                     }
                 }
             }
         }
     }
 
-    // Calibration fallback: measure TSC ticks over known duration
-    // Use shorter calibration for faster startup (1ms instead of 10ms)
-    let calibration_ns = 1_000_000; // 1ms calibration
-    let start_tsc = rdtsc();
-    let start = Instant::now();
+    // Calibration fallback: short for fast startup; `calibrate` measures
+    // longer on request
+    measure_counter_frequency(Duration::from_millis(1))
+}
+
+#[cfg(all(target_os = "macos", any(target_arch = "x86_64", target_arch = "x86")))]
+fn sysctl_u64(name: &std::ffi::CStr) -> Option<u64> {
+    let mut value: u64 = 0;
+    let mut len = std::mem::size_of::<u64>();
+    // SAFETY: `value` is a writable u64 and `len` its size; no new value
+    // is set.
+    let rc = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr(),
+            &mut value as *mut u64 as *mut libc::c_void,
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    (rc == 0 && len == std::mem::size_of::<u64>()).then_some(value)
+}
 
-    // Busy-wait for calibration period
-    while start.elapsed().as_nanos() < calibration_ns as u128 {
+/// Count TSC ticks across `duration` of the monotonic clock.
+///
+/// Error is the uncertainty in pairing each counter read with a clock
+/// read, relative to the measured span.
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+fn measure_counter_frequency(duration: Duration) -> Calibration {
+    let (start_tsc, start, start_window_ns) = paired_read();
+    if duration >= Duration::from_millis(10) {
+        std::thread::sleep(duration);
+    }
+    while start.elapsed() < duration {
         std::hint::spin_loop();
     }
+    let (end_tsc, end, end_window_ns) = paired_read();
 
-    let end_tsc = rdtsc();
-    let actual_ns = start.elapsed().as_nanos() as u64;
+    let actual_ns = end.duration_since(start).as_nanos().max(1);
     let cycles = end_tsc.saturating_sub(start_tsc);
+    Calibration {
+        // freq = cycles / time = cycles * 10^9 / ns
+        frequency_hz: (cycles as u128 * 1_000_000_000 / actual_ns) as u64,
+        error_ppm: (start_window_ns + end_window_ns).max(1) as f64 * 1e6 / actual_ns as f64,
+        source: CalibrationSource::Measured,
+    }
+}
+
+/// Counter read bracketed by clock reads: the tightest of a few attempts,
+/// as (ticks, bracket midpoint, bracket width in ns).
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+fn paired_read() -> (u64, Instant, u64) {
+    (0..8)
+        .map(|_| {
+            let before = Instant::now();
+            let ticks = rdtsc();
+            let window = before.elapsed();
+            (ticks, before + window / 2, window.as_nanos() as u64)
+        })
+        .min_by_key(|&(_, _, window_ns)| window_ns)
+        .expect("non-empty attempts")
+}
+
+/// Sub-buckets per power of two in the quantile sketch; values are
+/// reported within 1/32 (~3%) of the true quantile.
+const SKETCH_SUB_BUCKETS: u32 = 16;
+const SKETCH_SUB_BITS: u32 = SKETCH_SUB_BUCKETS.trailing_zeros();
 
-    // freq = cycles / time = cycles * 10^9 / ns
-    (cycles as u128 * 1_000_000_000 / actual_ns as u128) as u64
+/// Exact buckets for values below `SKETCH_SUB_BUCKETS`, then
+/// `SKETCH_SUB_BUCKETS` per power of two up to 2^64.
+const SKETCH_BUCKETS: usize = ((64 - SKETCH_SUB_BITS + 1) * SKETCH_SUB_BUCKETS) as usize;
+
+/// Log-linear histogram of picosecond values: one atomic increment per
+/// sample and fixed memory (~8 KiB), no raw samples kept.
+struct QuantileSketch {
+    buckets: [AtomicU64; SKETCH_BUCKETS],
+}
+
+impl QuantileSketch {
+    const fn new() -> Self {
+        QuantileSketch {
+            buckets: [const { AtomicU64::new(0) }; SKETCH_BUCKETS],
+        }
+    }
+
+    #[inline]
+    fn bucket(ps: Picoseconds) -> usize {
+        if ps < SKETCH_SUB_BUCKETS as u64 {
+            return ps as usize;
+        }
+        let exponent = 63 - ps.leading_zeros();
+        let shift = exponent - SKETCH_SUB_BITS;
+        let sub = (ps >> shift) as u32 & (SKETCH_SUB_BUCKETS - 1);
+        ((shift + 1) * SKETCH_SUB_BUCKETS + sub) as usize
+    }
+
+    /// Midpoint of the values falling in `bucket`.
+    fn value(bucket: usize) -> Picoseconds {
+        let bucket = bucket as u32;
+        if bucket < SKETCH_SUB_BUCKETS {
+            return bucket as u64;
+        }
+        let shift = bucket / SKETCH_SUB_BUCKETS - 1;
+        let sub = bucket % SKETCH_SUB_BUCKETS;
+        let lower = ((SKETCH_SUB_BUCKETS + sub) as u64) << shift;
+        lower + ((1u64 << shift) >> 1)
+    }
+
+    #[inline]
+    fn add(&self, bucket: usize, count: u64) {
+        self.buckets[bucket].fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Value at quantile `q` (0.0..=1.0), or `None` without samples.
+    fn quantile(&self, q: f64) -> Option<Picoseconds> {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        counts
+            .iter()
+            .position(|&count| {
+                seen += count;
+                seen >= rank
+            })
+            .map(Self::value)
+    }
+
+    fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn merge(&self, other: &QuantileSketch) {
+        for (bucket, theirs) in self.buckets.iter().zip(&other.buckets) {
+            let count = theirs.load(Ordering::Relaxed);
+            if count > 0 {
+                bucket.fetch_add(count, Ordering::Relaxed);
+            }
+        }
+    }
 }
 
 /// High-resolution metrics accumulator
 ///
 /// Tracks timing statistics at picosecond granularity with
-/// proper statistical aggregation.
+/// proper statistical aggregation, and quantiles through a fixed-size
+/// log-bucketed sketch.
 pub struct HiResMetrics {
     /// Number of samples
     pub count: AtomicU64,
@@ -307,6 +1187,8 @@ pub struct HiResMetrics {
     /// Sum of squares for variance calculation (in units of ns²)
     /// We use ns² to avoid overflow while maintaining reasonable precision
     pub sum_sq_ns2: AtomicU64,
+    /// Distribution for quantiles
+    sketch: QuantileSketch,
 }
 
 impl HiResMetrics {
@@ -317,6 +1199,7 @@ impl HiResMetrics {
             min_ps: AtomicU64::new(u64::MAX),
             max_ps: AtomicU64::new(0),
             sum_sq_ns2: AtomicU64::new(0),
+            sketch: QuantileSketch::new(),
         }
     }
 
@@ -359,6 +1242,54 @@ impl HiResMetrics {
         // Add to sum of squares (ns² to avoid overflow)
         self.sum_sq_ns2
             .fetch_add(ns.saturating_mul(ns), Ordering::Relaxed);
+
+        self.sketch.add(QuantileSketch::bucket(ps), 1);
+    }
+
+    /// Record many measurements at once.
+    ///
+    /// Aggregates count, sum, min, max and sum of squares locally, then
+    /// applies five atomic operations in total, instead of five or more
+    /// per sample, plus one per run of consecutive samples in the same
+    /// quantile bucket. Equivalent to calling [`record`](Self::record) for
+    /// each sample.
+    pub fn record_slice(&self, samples: &[Picoseconds]) {
+        if samples.is_empty() {
+            return;
+        }
+
+        let mut total_ps = 0u64;
+        let mut min_ps = u64::MAX;
+        let mut max_ps = 0u64;
+        let mut sum_sq_ns2 = 0u64;
+        let mut run: Option<(usize, u64)> = None;
+        for &ps in samples {
+            let ns = ps / PS_PER_NS;
+            total_ps = total_ps.wrapping_add(ps);
+            min_ps = min_ps.min(ps);
+            max_ps = max_ps.max(ps);
+            sum_sq_ns2 = sum_sq_ns2.wrapping_add(ns.saturating_mul(ns));
+
+            let bucket = QuantileSketch::bucket(ps);
+            run = match run {
+                Some((current, count)) if current == bucket => Some((bucket, count + 1)),
+                Some((current, count)) => {
+                    self.sketch.add(current, count);
+                    Some((bucket, 1))
+                }
+                None => Some((bucket, 1)),
+            };
+        }
+        if let Some((bucket, count)) = run {
+            self.sketch.add(bucket, count);
+        }
+
+        self.count
+            .fetch_add(samples.len() as u64, Ordering::Relaxed);
+        self.total_ps.fetch_add(total_ps, Ordering::Relaxed);
+        self.min_ps.fetch_min(min_ps, Ordering::Relaxed);
+        self.max_ps.fetch_max(max_ps, Ordering::Relaxed);
+        self.sum_sq_ns2.fetch_add(sum_sq_ns2, Ordering::Relaxed);
     }
 
     /// Record from HiResTimer
@@ -366,6 +1297,27 @@ impl HiResMetrics {
         self.record(timer.elapsed());
     }
 
+    /// Start a timer that records into these metrics when dropped, on
+    /// every return path.
+    ///
+    /// ```rust,ignore
+    /// static LOOKUP: HiResMetrics = HiResMetrics::new();
+    ///
+    /// fn lookup(key: u64) -> Option<u64> {
+    ///     let _scope = LOOKUP.scope();
+    ///     let slot = index(key)?; // recorded here too
+    ///     Some(slot)
+    /// }
+    /// ```
+    #[inline]
+    pub fn scope(&self) -> HiResScope<'_> {
+        HiResScope {
+            metrics: self,
+            timer: HiResTimer::start(),
+            cancelled: false,
+        }
+    }
+
     /// Get snapshot of metrics
     pub fn snapshot(&self) -> HiResMetricsSnapshot {
         let count = self.count.load(Ordering::Relaxed);
@@ -374,7 +1326,7 @@ impl HiResMetrics {
         let max_ps = self.max_ps.load(Ordering::Relaxed);
         let sum_sq_ns2 = self.sum_sq_ns2.load(Ordering::Relaxed);
 
-        let mean_ps = if count > 0 { total_ps / count } else { 0 };
+        let mean_ps = total_ps.checked_div(count).unwrap_or(0);
 
         // Variance = E[X²] - E[X]² (computed in ns for numerical stability)
         let mean_ns = mean_ps / PS_PER_NS;
@@ -389,16 +1341,56 @@ impl HiResMetrics {
         let variance_ps = variance_ns2.saturating_mul(PS_PER_NS * PS_PER_NS);
         let stddev_ps = (variance_ps as f64).sqrt() as u64;
 
+        let min_ps = if min_ps == u64::MAX { 0 } else { min_ps };
+        // Bucket midpoints can fall outside the observed range
+        let quantile = |q| {
+            self.sketch
+                .quantile(q)
+                .map_or(0, |ps: Picoseconds| ps.clamp(min_ps, max_ps.max(min_ps)))
+        };
+
         HiResMetricsSnapshot {
             count,
             total_ps,
-            min_ps: if min_ps == u64::MAX { 0 } else { min_ps },
+            min_ps,
             max_ps,
             mean_ps,
             stddev_ps,
+            p50_ps: quantile(0.50),
+            p95_ps: quantile(0.95),
+            p99_ps: quantile(0.99),
+            p999_ps: quantile(0.999),
         }
     }
 
+    /// Value at quantile `q` (0.0..=1.0) within ~3%, or `None` without
+    /// samples.
+    pub fn quantile(&self, q: f64) -> Option<HiResTimestamp> {
+        let ps = self.sketch.quantile(q)?;
+        // Half the bucket width either way
+        let uncertainty = ps >> (SKETCH_SUB_BITS + 1);
+        Some(HiResTimestamp::from_picos(ps, uncertainty))
+    }
+
+    /// Add `other`'s samples to these metrics, e.g. per-thread collectors
+    /// into one report. Exact, quantiles included.
+    pub fn merge(&self, other: &HiResMetrics) {
+        let count = other.count.load(Ordering::Relaxed);
+        if count == 0 {
+            return;
+        }
+        self.count.fetch_add(count, Ordering::Relaxed);
+        self.total_ps
+            .fetch_add(other.total_ps.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.min_ps
+            .fetch_min(other.min_ps.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.max_ps
+            .fetch_max(other.max_ps.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.sum_sq_ns2
+            .fetch_add(other.sum_sq_ns2.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.sketch.merge(&other.sketch);
+    }
+
     /// Reset all metrics
     pub fn reset(&self) {
         self.count.store(0, Ordering::Relaxed);
@@ -406,6 +1398,7 @@ impl HiResMetrics {
         self.min_ps.store(u64::MAX, Ordering::Relaxed);
         self.max_ps.store(0, Ordering::Relaxed);
         self.sum_sq_ns2.store(0, Ordering::Relaxed);
+        self.sketch.reset();
     }
 }
 
@@ -415,6 +1408,123 @@ impl Default for HiResMetrics {
     }
 }
 
+/// Timer recording its elapsed time into a [`HiResMetrics`] on drop,
+/// created by [`HiResMetrics::scope`].
+#[must_use = "the measurement is recorded when the scope is dropped"]
+pub struct HiResScope<'a> {
+    metrics: &'a HiResMetrics,
+    timer: HiResTimer,
+    cancelled: bool,
+}
+
+impl HiResScope<'_> {
+    /// Elapsed time so far.
+    #[inline]
+    pub fn elapsed(&self) -> HiResTimestamp {
+        self.timer.elapsed()
+    }
+
+    /// The underlying timer, for pauses and laps.
+    pub fn timer_mut(&mut self) -> &mut HiResTimer {
+        &mut self.timer
+    }
+
+    /// Drop without recording, e.g. for a cache hit that should not count
+    /// as a lookup.
+    pub fn cancel(mut self) {
+        self.cancelled = true;
+    }
+}
+
+impl Drop for HiResScope<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        if !self.cancelled {
+            self.metrics.record(self.timer.elapsed());
+        }
+    }
+}
+
+/// Named [`HiResMetrics`] shared across components.
+///
+/// Metrics registered here are included in every
+/// [`TelemetrySnapshot`](crate::obs::telemetry::TelemetrySnapshot) and so
+/// in its JSON and Prometheus exports.
+pub struct HiResRegistry {
+    metrics: std::sync::RwLock<std::collections::BTreeMap<String, std::sync::Arc<HiResMetrics>>>,
+}
+
+impl HiResRegistry {
+    pub const fn new() -> Self {
+        HiResRegistry {
+            metrics: std::sync::RwLock::new(std::collections::BTreeMap::new()),
+        }
+    }
+
+    /// Metrics registered under `name`, created on first use; components
+    /// registering the same name share one instance.
+    pub fn register(&self, name: &str) -> std::sync::Arc<HiResMetrics> {
+        if let Some(metrics) = self.get(name) {
+            return metrics;
+        }
+        let mut all = self.metrics.write().unwrap_or_else(|e| e.into_inner());
+        all.entry(name.to_string()).or_default().clone()
+    }
+
+    pub fn get(&self, name: &str) -> Option<std::sync::Arc<HiResMetrics>> {
+        let all = self.metrics.read().unwrap_or_else(|e| e.into_inner());
+        all.get(name).cloned()
+    }
+
+    /// Stop exporting `name`; holders keep recording into their handle.
+    pub fn unregister(&self, name: &str) -> Option<std::sync::Arc<HiResMetrics>> {
+        let mut all = self.metrics.write().unwrap_or_else(|e| e.into_inner());
+        all.remove(name)
+    }
+
+    /// Registered names, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let all = self.metrics.read().unwrap_or_else(|e| e.into_inner());
+        all.keys().cloned().collect()
+    }
+
+    /// Snapshots of all registered metrics, sorted by name.
+    pub fn snapshot_all(&self) -> Vec<(String, HiResMetricsSnapshot)> {
+        let all = self.metrics.read().unwrap_or_else(|e| e.into_inner());
+        all.iter()
+            .map(|(name, metrics)| (name.clone(), metrics.snapshot()))
+            .collect()
+    }
+
+    /// Reset every registered metric, keeping registrations.
+    pub fn reset_all(&self) {
+        let all = self.metrics.read().unwrap_or_else(|e| e.into_inner());
+        for metrics in all.values() {
+            metrics.reset();
+        }
+    }
+}
+
+impl Default for HiResRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static HIRES_REGISTRY: HiResRegistry = HiResRegistry::new();
+
+/// Global registry of named high-resolution metrics.
+///
+/// ```rust,ignore
+/// static DECODE: LazyLock<Arc<HiResMetrics>> =
+///     LazyLock::new(|| hires_registry().register("shard_decode"));
+///
+/// let _scope = DECODE.scope();
+/// ```
+pub fn hires_registry() -> &'static HiResRegistry {
+    &HIRES_REGISTRY
+}
+
 /// Snapshot of high-resolution metrics
 #[derive(Clone, Copy, Debug, Default)]
 pub struct HiResMetricsSnapshot {
@@ -430,9 +1540,53 @@ pub struct HiResMetricsSnapshot {
     pub mean_ps: Picoseconds,
     /// Standard deviation (picoseconds)
     pub stddev_ps: Picoseconds,
+    /// Median (picoseconds, within ~3%)
+    pub p50_ps: Picoseconds,
+    /// 95th percentile (picoseconds, within ~3%)
+    pub p95_ps: Picoseconds,
+    /// 99th percentile (picoseconds, within ~3%)
+    pub p99_ps: Picoseconds,
+    /// 99.9th percentile (picoseconds, within ~3%)
+    pub p999_ps: Picoseconds,
 }
 
 impl HiResMetricsSnapshot {
+    /// Combine with a snapshot of disjoint samples.
+    ///
+    /// Count, total, min, max and mean are exact and the standard
+    /// deviation combines by the parallel variance formula. Quantiles
+    /// cannot be merged from summaries; each becomes the larger of the
+    /// two, an upper bound on the merged quantile. Merge the
+    /// [`HiResMetrics`] instead for exact quantiles.
+    pub fn merge(&mut self, other: &HiResMetricsSnapshot) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        let (na, nb) = (self.count as f64, other.count as f64);
+        let n = na + nb;
+        let (mean_a, mean_b) = (self.mean_ps as f64, other.mean_ps as f64);
+        let delta = mean_b - mean_a;
+        // Population variances, as in `HiResMetrics::snapshot`
+        let m2 = (self.stddev_ps as f64).powi(2) * na
+            + (other.stddev_ps as f64).powi(2) * nb
+            + delta * delta * na * nb / n;
+
+        self.count += other.count;
+        self.total_ps = self.total_ps.saturating_add(other.total_ps);
+        self.min_ps = self.min_ps.min(other.min_ps);
+        self.max_ps = self.max_ps.max(other.max_ps);
+        self.mean_ps = self.total_ps / self.count;
+        self.stddev_ps = (m2 / n).sqrt() as u64;
+        self.p50_ps = self.p50_ps.max(other.p50_ps);
+        self.p95_ps = self.p95_ps.max(other.p95_ps);
+        self.p99_ps = self.p99_ps.max(other.p99_ps);
+        self.p999_ps = self.p999_ps.max(other.p999_ps);
+    }
+
     /// Format a comprehensive summary
     pub fn format(&self) -> String {
         if self.count == 0 {
@@ -440,12 +1594,15 @@ impl HiResMetricsSnapshot {
         }
 
         format!(
-            "n={} mean={} min={} max={} stddev={}",
+            "n={} mean={} min={} max={} stddev={} p50={} p99={} p999={}",
             self.count,
             HiResTimestamp::from_picos(self.mean_ps, 0).format(),
             HiResTimestamp::from_picos(self.min_ps, 0).format(),
             HiResTimestamp::from_picos(self.max_ps, 0).format(),
             HiResTimestamp::from_picos(self.stddev_ps, 0).format(),
+            HiResTimestamp::from_picos(self.p50_ps, 0).format(),
+            HiResTimestamp::from_picos(self.p99_ps, 0).format(),
+            HiResTimestamp::from_picos(self.p999_ps, 0).format(),
         )
     }
 
@@ -466,6 +1623,207 @@ impl HiResMetricsSnapshot {
     }
 }
 
+/// Self-test of the active timing backend, see [`probe_resolution`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResolutionReport {
+    /// `"tsc"` (x86), `"cntvct"` (ARM64), or without a usable counter
+    /// `"qpc"` (Windows), `"mach_absolute_time"` (macOS) or `"monotonic"`
+    /// (`std::time::Instant`)
+    pub backend: &'static str,
+    /// Calibrated TSC frequency, when the TSC backend is active
+    pub tsc_freq_hz: Option<u64>,
+    /// Smallest non-zero delta between two back-to-back readings
+    pub min_delta: HiResTimestamp,
+    /// Mean cost of one `HiResTimer::start()` + `elapsed()` pair
+    pub overhead: HiResTimestamp,
+    /// Raw readings that went backwards
+    pub monotonicity_violations: u64,
+    pub samples: u64,
+    pub clock_quality: ClockQuality,
+}
+
+impl ResolutionReport {
+    /// Format as a single line
+    pub fn format(&self) -> String {
+        format!(
+            "backend={} resolution={} overhead={} violations={}/{} clock={}",
+            self.backend,
+            self.min_delta.format(),
+            self.overhead.format(),
+            self.monotonicity_violations,
+            self.samples,
+            self.clock_quality.as_str()
+        )
+    }
+}
+
+/// Measure the minimum observable delta, per-measurement overhead and
+/// monotonicity violations of the backend `HiResTimer` uses on this host.
+///
+/// Takes roughly a millisecond; record the result alongside benchmark
+/// results so numbers from hosts with coarse or unstable clocks can be
+/// told apart.
+pub fn probe_resolution() -> ResolutionReport {
+    const SAMPLES: u64 = 10_000;
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
+    let tsc_freq_hz = Some(get_tsc_frequency()).filter(|&hz| hz > 0);
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64")))]
+    let tsc_freq_hz: Option<u64> = None;
+
+    let mut min_delta: Option<HiResTimestamp> = None;
+    for _ in 0..SAMPLES {
+        let delta = HiResTimer::start().elapsed();
+        if delta.picoseconds > 0 && min_delta.is_none_or(|min| delta.picoseconds < min.picoseconds)
+        {
+            min_delta = Some(delta);
+        }
+    }
+
+    let started = Instant::now();
+    for _ in 0..SAMPLES {
+        std::hint::black_box(HiResTimer::start().elapsed());
+    }
+    let overhead_ps = (started.elapsed().as_nanos() * PS_PER_NS as u128 / SAMPLES as u128) as u64;
+
+    let mut violations = 0;
+    let mut previous = raw_ticks();
+    for _ in 0..SAMPLES {
+        let current = raw_ticks();
+        if current < previous {
+            violations += 1;
+        }
+        previous = current;
+    }
+
+    ResolutionReport {
+        backend: if tsc_freq_hz.is_some() {
+            COUNTER_BACKEND
+        } else {
+            OS_CLOCK_BACKEND
+        },
+        tsc_freq_hz,
+        min_delta: min_delta.unwrap_or(HiResTimestamp::from_picos(0, 0)),
+        overhead: HiResTimestamp::from_picos(overhead_ps, PS_PER_NS),
+        monotonicity_violations: violations,
+        samples: SAMPLES,
+        clock_quality: HiResTimer::clock_quality(),
+    }
+}
+
+/// Name of the hardware counter backend of this target.
+const COUNTER_BACKEND: &str = if cfg!(target_arch = "aarch64") {
+    "cntvct"
+} else {
+    "tsc"
+};
+
+/// Name of the platform clock used without a hardware counter.
+const OS_CLOCK_BACKEND: &str = if cfg!(windows) {
+    "qpc"
+} else if cfg!(target_os = "macos") {
+    "mach_absolute_time"
+} else {
+    "monotonic"
+};
+
+/// Raw reading of the timer backend, for monotonicity checks.
+#[inline]
+fn raw_ticks() -> u64 {
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"))]
+    {
+        if get_tsc_frequency() > 0 {
+            return rdtsc();
+        }
+    }
+
+    os_ticks()
+}
+
+/// `QueryPerformanceCounter` ticks: the highest-resolution Windows clock,
+/// consistent across cores (usually 10 MHz).
+#[cfg(windows)]
+#[inline]
+fn os_ticks() -> u64 {
+    let mut ticks: i64 = 0;
+    // SAFETY: writes one i64; cannot fail on Windows XP and later.
+    unsafe {
+        QueryPerformanceCounter(&mut ticks);
+    }
+    ticks as u64
+}
+
+#[cfg(windows)]
+fn os_tick_frequency() -> u64 {
+    static FREQUENCY: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
+    *FREQUENCY.get_or_init(|| {
+        let mut freq: i64 = 0;
+        // SAFETY: writes one i64; fixed at boot.
+        unsafe {
+            QueryPerformanceFrequency(&mut freq);
+        }
+        (freq as u64).max(1)
+    })
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn QueryPerformanceCounter(count: *mut i64) -> i32;
+    fn QueryPerformanceFrequency(frequency: *mut i64) -> i32;
+}
+
+/// `mach_absolute_time` ticks: nanoseconds on Intel, the 24 MHz system
+/// counter on Apple Silicon. Unlike `clock_gettime_nsec_np`, no
+/// conversion rounds the reading.
+#[cfg(target_os = "macos")]
+#[inline]
+fn os_ticks() -> u64 {
+    // SAFETY: no arguments, no failure mode.
+    unsafe { mach_absolute_time() }
+}
+
+#[cfg(target_os = "macos")]
+fn os_tick_frequency() -> u64 {
+    static FREQUENCY: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
+    *FREQUENCY.get_or_init(|| {
+        let mut timebase = MachTimebaseInfo { numer: 0, denom: 0 };
+        // SAFETY: writes one `mach_timebase_info_data_t`.
+        let rc = unsafe { mach_timebase_info(&mut timebase) };
+        if rc != 0 || timebase.numer == 0 {
+            return 1_000_000_000;
+        }
+        // Ticks convert to ns as ticks * numer / denom
+        (1_000_000_000 * timebase.denom as u64 / timebase.numer as u64).max(1)
+    })
+}
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+struct MachTimebaseInfo {
+    numer: u32,
+    denom: u32,
+}
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    fn mach_absolute_time() -> u64;
+    fn mach_timebase_info(info: *mut MachTimebaseInfo) -> libc::c_int;
+}
+
+/// Nanoseconds of `Instant` since the first reading.
+#[cfg(not(any(windows, target_os = "macos")))]
+#[inline]
+fn os_ticks() -> u64 {
+    static ANCHOR: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+    ANCHOR.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn os_tick_frequency() -> u64 {
+    1_000_000_000
+}
+
 /// Measure a closure with picosecond timing
 #[inline]
 pub fn measure<F, R>(f: F) -> (R, HiResTimestamp)
@@ -478,6 +1836,9 @@ where
 }
 
 /// Measure a closure N times and return statistics
+///
+/// Every call is measured, including cold-cache first iterations; use
+/// [`Benchmark`] for warmup and outlier rejection.
 pub fn measure_n<F, R>(n: usize, mut f: F) -> (Vec<R>, HiResMetricsSnapshot)
 where
     F: FnMut() -> R,
@@ -494,6 +1855,199 @@ where
     (results, metrics.snapshot())
 }
 
+/// How many measured iterations a [`Benchmark`] runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Iterations {
+    Fixed(usize),
+    Budget(Duration),
+}
+
+/// Small benchmarking harness around [`HiResTimer`]: warmup, fixed or
+/// time-budgeted iteration counts and MAD-based outlier rejection.
+///
+/// ```rust,ignore
+/// let report = Benchmark::new()
+///     .warmup(100)
+///     .time_budget(Duration::from_millis(200))
+///     .run(|| index.lookup(black_box(key)));
+/// println!("{}", report.cleaned.format());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Benchmark {
+    warmup: usize,
+    iterations: Iterations,
+    outlier_mads: Option<f64>,
+}
+
+impl Benchmark {
+    /// 10 warmup iterations, then 1000 measured, rejecting samples more
+    /// than 5 scaled MADs from the median.
+    pub fn new() -> Self {
+        Self {
+            warmup: 10,
+            iterations: Iterations::Fixed(1000),
+            outlier_mads: Some(5.0),
+        }
+    }
+
+    /// Unmeasured iterations run first to warm caches and branch
+    /// predictors.
+    pub fn warmup(mut self, iterations: usize) -> Self {
+        self.warmup = iterations;
+        self
+    }
+
+    /// Measure exactly `n` iterations (at least one).
+    pub fn iterations(mut self, n: usize) -> Self {
+        self.iterations = Iterations::Fixed(n.max(1));
+        self
+    }
+
+    /// Measure as many iterations as fit in `budget` of wall time (at
+    /// least one), instead of a fixed count.
+    pub fn time_budget(mut self, budget: Duration) -> Self {
+        self.iterations = Iterations::Budget(budget);
+        self
+    }
+
+    /// Reject samples further than `mads` median absolute deviations
+    /// (scaled by 1.4826 to estimate one standard deviation) from the
+    /// median. When over half the samples are identical the MAD is zero,
+    /// and the mean absolute deviation (scaled by 1.2533) is used instead.
+    pub fn outlier_threshold(mut self, mads: f64) -> Self {
+        self.outlier_mads = Some(mads);
+        self
+    }
+
+    /// Keep all samples in the cleaned statistics.
+    pub fn keep_outliers(mut self) -> Self {
+        self.outlier_mads = None;
+        self
+    }
+
+    /// Run `f` for the warmup, then for the measured iterations.
+    pub fn run<F, R>(&self, mut f: F) -> BenchmarkReport
+    where
+        F: FnMut() -> R,
+    {
+        for _ in 0..self.warmup {
+            std::hint::black_box(f());
+        }
+
+        let mut samples = Vec::new();
+        let mut measure_once = |samples: &mut Vec<Picoseconds>| {
+            let timer = HiResTimer::start();
+            std::hint::black_box(f());
+            samples.push(timer.elapsed().picoseconds);
+        };
+        match self.iterations {
+            Iterations::Fixed(n) => {
+                samples.reserve(n);
+                for _ in 0..n {
+                    measure_once(&mut samples);
+                }
+            }
+            Iterations::Budget(budget) => {
+                let started = Instant::now();
+                loop {
+                    measure_once(&mut samples);
+                    if started.elapsed() >= budget {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut sorted = samples.clone();
+        sorted.sort_unstable();
+        let median_ps = median_sorted(&sorted);
+        let mut deviations: Vec<Picoseconds> =
+            sorted.iter().map(|&ps| ps.abs_diff(median_ps)).collect();
+        deviations.sort_unstable();
+        let mad_ps = median_sorted(&deviations);
+
+        let raw = HiResMetrics::new();
+        raw.record_slice(&samples);
+        // Coarse clocks can make most samples identical and the MAD zero;
+        // fall back to the mean absolute deviation (scaled the same way)
+        let sigma = if mad_ps > 0 {
+            1.4826 * mad_ps as f64
+        } else {
+            1.2533 * deviations.iter().map(|&d| d as f64).sum::<f64>()
+                / deviations.len().max(1) as f64
+        };
+        let kept: Vec<Picoseconds> = match self.outlier_mads {
+            // With no spread every deviation would count as an outlier
+            Some(mads) if sigma > 0.0 => {
+                let limit = mads * sigma;
+                samples
+                    .iter()
+                    .copied()
+                    .filter(|&ps| ps.abs_diff(median_ps) as f64 <= limit)
+                    .collect()
+            }
+            _ => samples.clone(),
+        };
+        let cleaned = HiResMetrics::new();
+        cleaned.record_slice(&kept);
+
+        BenchmarkReport {
+            raw: raw.snapshot(),
+            cleaned: cleaned.snapshot(),
+            warmup: self.warmup,
+            outliers: samples.len() - kept.len(),
+            median_ps,
+            mad_ps,
+            samples,
+        }
+    }
+}
+
+impl Default for Benchmark {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn median_sorted(sorted: &[Picoseconds]) -> Picoseconds {
+    match sorted.len() {
+        0 => 0,
+        n if n % 2 == 1 => sorted[n / 2],
+        n => sorted[n / 2 - 1] / 2 + sorted[n / 2] / 2,
+    }
+}
+
+/// Result of a [`Benchmark`] run.
+#[derive(Clone, Debug)]
+pub struct BenchmarkReport {
+    /// Statistics over every measured iteration
+    pub raw: HiResMetricsSnapshot,
+    /// Statistics with outliers removed
+    pub cleaned: HiResMetricsSnapshot,
+    /// Warmup iterations run before measuring
+    pub warmup: usize,
+    /// Samples rejected as outliers
+    pub outliers: usize,
+    /// Exact median of the measured samples (picoseconds)
+    pub median_ps: Picoseconds,
+    /// Median absolute deviation from the median (picoseconds)
+    pub mad_ps: Picoseconds,
+    /// Measured durations in run order (picoseconds)
+    pub samples: Vec<Picoseconds>,
+}
+
+impl BenchmarkReport {
+    pub fn format(&self) -> String {
+        format!(
+            "{} (raw {}; {} outliers, {} warmup)",
+            self.cleaned.format(),
+            self.raw.format(),
+            self.outliers,
+            self.warmup
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -506,6 +2060,31 @@ mod tests {
         assert!(!ts.is_estimated);
     }
 
+    #[test]
+    fn test_timestamp_arithmetic_and_conversions() {
+        let a = HiResTimestamp::from_picos(1_500, 100);
+        let b = HiResTimestamp::from(Duration::from_nanos(2));
+        assert_eq!((b.picoseconds, b.uncertainty_high), (2_000, 0));
+        assert!(!b.is_estimated);
+
+        let mut total = a + b;
+        assert_eq!(total.picoseconds, 3_500);
+        assert_eq!(total.uncertainty_high, 100);
+        assert!(total.is_estimated);
+        total += b;
+        assert_eq!((total / 2).picoseconds, 2_750);
+        let mean = [a, b, b].into_iter().sum::<HiResTimestamp>() / 3;
+        assert_eq!(mean.picoseconds, 1_833);
+
+        assert!(a < b);
+        assert_eq!([b, a].iter().max(), Some(&b));
+        assert_eq!(Duration::from(a), Duration::from_nanos(1));
+        let duration: Duration = b.into();
+        assert_eq!(duration, Duration::from_nanos(2));
+        assert_eq!(format!("{}", a), "1.500ns");
+        assert_eq!(format!("{:#}", a), "1.500ns (±100ps)");
+    }
+
     #[test]
     fn test_timestamp_formatting() {
         assert_eq!(HiResTimestamp::from_picos(500, 0).format(), "500ps");
@@ -543,6 +2122,254 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serialized_timer() {
+        let mut timer = HiResTimer::start_serialized();
+        assert!(timer.is_serialized());
+        assert!(!HiResTimer::start().is_serialized());
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let first = timer.split();
+        timer.pause();
+        timer.resume();
+        let second = timer.elapsed();
+        assert!(first.as_micros() >= 1_500, "{}", first.format());
+        assert!(second.picoseconds >= first.picoseconds);
+    }
+
+    #[test]
+    fn test_benchmark_rejects_outliers() {
+        let mut calls = 0;
+        let report = Benchmark::new().warmup(5).iterations(200).run(|| {
+            calls += 1;
+            // One slow iteration among fast ones
+            if calls == 100 {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            std::hint::black_box((0..50u64).sum::<u64>())
+        });
+        assert_eq!(calls, 205);
+        assert_eq!(report.samples.len(), 200);
+        assert_eq!(report.raw.count, 200);
+        assert!(report.outliers >= 1, "{}", report.format());
+        assert_eq!(report.cleaned.count, 200 - report.outliers as u64);
+        assert!(report.cleaned.max_ps < PS_PER_MS * 5);
+        assert!(report.raw.max_ps >= PS_PER_MS * 5);
+
+        let budgeted = Benchmark::new()
+            .warmup(0)
+            .keep_outliers()
+            .time_budget(Duration::from_millis(5))
+            .run(|| std::hint::black_box(1 + 1));
+        assert!(budgeted.samples.len() > 10);
+        assert_eq!(budgeted.outliers, 0);
+    }
+
+    #[test]
+    fn test_merge_metrics_and_snapshots() {
+        let first: Vec<Picoseconds> = (1..=100).map(|ns| ns * PS_PER_NS).collect();
+        let second: Vec<Picoseconds> = (101..=300).map(|ns| ns * PS_PER_NS).collect();
+        let (a, b, all) = (
+            HiResMetrics::new(),
+            HiResMetrics::new(),
+            HiResMetrics::new(),
+        );
+        a.record_slice(&first);
+        b.record_slice(&second);
+        all.record_slice(&first);
+        all.record_slice(&second);
+        let expected = all.snapshot();
+
+        let mut merged = a.snapshot();
+        merged.merge(&b.snapshot());
+        assert_eq!(merged.count, 300);
+        assert_eq!(merged.total_ps, expected.total_ps);
+        assert_eq!((merged.min_ps, merged.max_ps), (PS_PER_NS, 300 * PS_PER_NS));
+        assert_eq!(merged.mean_ps, expected.mean_ps);
+        // Snapshot stddev is computed in whole ns²; the merge is exact
+        let stddev_error = merged.stddev_ps.abs_diff(expected.stddev_ps);
+        assert!(
+            stddev_error < PS_PER_NS,
+            "{} vs {}",
+            merged.stddev_ps,
+            expected.stddev_ps
+        );
+        assert!(merged.p99_ps >= expected.p99_ps);
+
+        a.merge(&b);
+        let exact = a.snapshot();
+        assert_eq!(exact.count, 300);
+        assert_eq!(exact.p50_ps, expected.p50_ps);
+        assert_eq!(exact.stddev_ps, expected.stddev_ps);
+
+        let mut empty = HiResMetricsSnapshot::default();
+        empty.merge(&expected);
+        assert_eq!(empty.count, 300);
+    }
+
+    #[test]
+    fn test_hires_registry() {
+        let registry = HiResRegistry::new();
+        let a = registry.register("decode");
+        let b = registry.register("decode");
+        assert!(std::sync::Arc::ptr_eq(&a, &b));
+        a.record(HiResTimestamp::from_nanos(40));
+        registry.register("encode");
+
+        let all = registry.snapshot_all();
+        assert_eq!(registry.names(), ["decode", "encode"]);
+        assert_eq!(all[0].0, "decode");
+        assert_eq!(all[0].1.count, 1);
+        assert_eq!(all[1].1.count, 0);
+
+        registry.reset_all();
+        assert_eq!(b.snapshot().count, 0);
+        assert!(registry.unregister("encode").is_some());
+        assert_eq!(registry.names(), ["decode"]);
+    }
+
+    #[test]
+    fn test_quantile_sketch() {
+        for ps in [0, 1, 15, 16, 17, 1_000, 123_456_789, u64::MAX] {
+            let bucket = QuantileSketch::bucket(ps);
+            assert!(bucket < SKETCH_BUCKETS);
+            let value = QuantileSketch::value(bucket) as f64;
+            assert!(
+                (value - ps as f64).abs() <= ps as f64 / 32.0 + 0.5,
+                "{}",
+                ps
+            );
+        }
+
+        let metrics = HiResMetrics::new();
+        assert!(metrics.quantile(0.5).is_none());
+        // 1..=1000 ns
+        let samples: Vec<Picoseconds> = (1..=1_000).map(|ns| ns * PS_PER_NS).collect();
+        metrics.record_slice(&samples);
+        let snapshot = metrics.snapshot();
+        for (actual, expected) in [
+            (snapshot.p50_ps, 500_000.0),
+            (snapshot.p95_ps, 950_000.0),
+            (snapshot.p99_ps, 990_000.0),
+            (snapshot.p999_ps, 999_000.0),
+        ] {
+            let error = (actual as f64 - expected).abs() / expected;
+            assert!(error < 0.035, "{} vs {}", actual, expected);
+        }
+        assert!(snapshot.p999_ps <= snapshot.max_ps);
+
+        metrics.reset();
+        assert_eq!(metrics.snapshot().p99_ps, 0);
+    }
+
+    #[test]
+    fn test_overhead_compensation() {
+        let overhead = HiResTimer::overhead();
+        assert!(overhead.picoseconds > 0);
+        assert!(overhead.picoseconds < PS_PER_MS, "{}", overhead.format());
+
+        let timer = HiResTimer::start().compensated();
+        assert!(timer.is_compensated());
+        let empty = timer.elapsed();
+        assert!(empty.uncertainty_high >= overhead.uncertainty_high);
+
+        let mut timer = HiResTimer::start_serialized().compensated();
+        std::thread::sleep(Duration::from_millis(2));
+        timer.pause();
+        assert!(timer.elapsed().as_micros() >= 1_500);
+        assert!(!HiResTimer::start().is_compensated());
+    }
+
+    #[test]
+    fn test_explicit_calibration() {
+        let Some(measured) = calibrate(Duration::from_millis(20)) else {
+            assert!(calibration().is_none());
+            return;
+        };
+        assert!(measured.frequency_hz > 0);
+        assert!(measured.error_ppm < 10_000.0, "{:?}", measured);
+        assert!(measured.error_hz() <= measured.frequency_hz / 100);
+        assert_eq!(calibration(), Some(measured));
+    }
+
+    #[test]
+    fn test_pause_excludes_paused_time() {
+        let mut timer = HiResTimer::start();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        timer.pause();
+        assert!(timer.is_paused());
+        let at_pause = timer.elapsed();
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(timer.elapsed(), at_pause);
+        timer.pause();
+        assert_eq!(timer.elapsed(), at_pause);
+
+        timer.resume();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let total = timer.elapsed();
+        assert!(total.picoseconds >= 4 * PS_PER_MS, "{}", total.format());
+        // The 20ms pause is excluded
+        assert!(total.picoseconds < 20 * PS_PER_MS, "{}", total.format());
+    }
+
+    #[test]
+    fn test_timer_on_mock_clock() {
+        let clock = Arc::new(crate::obs::clock::MockClock::new());
+        let mut timer = HiResTimer::start_with_clock(clock.clone()).compensated();
+        assert!(!timer.is_compensated());
+        assert_eq!(timer.elapsed(), HiResTimestamp::from(Duration::ZERO));
+
+        // Mock readings are exact
+        clock.advance(Duration::from_micros(250));
+        assert_eq!(
+            timer.lap("parse"),
+            HiResTimestamp::from(Duration::from_micros(250))
+        );
+        timer.pause();
+        clock.advance(Duration::from_secs(1));
+        timer.resume();
+        clock.advance(Duration::from_micros(50));
+        assert_eq!(timer.elapsed_nanos(), 300_000);
+    }
+
+    #[test]
+    fn test_splits_are_monotonic() {
+        let mut timer = HiResTimer::start();
+        let first = timer.split();
+        std::thread::sleep(std::time::Duration::from_micros(200));
+        timer.pause();
+        let second = timer.split();
+        let third = timer.split();
+
+        assert_eq!(timer.splits(), &[first, second, third]);
+        assert!(second.picoseconds >= first.picoseconds + 200 * PS_PER_US);
+        assert_eq!(second, third);
+    }
+
+    #[test]
+    fn test_laps_record_deltas() {
+        let mut timer = HiResTimer::start();
+        std::thread::sleep(std::time::Duration::from_micros(300));
+        let parse = timer.lap("parse");
+        std::thread::sleep(std::time::Duration::from_micros(300));
+        let score = timer.lap("score");
+
+        let laps = timer.laps();
+        assert_eq!(laps.len(), 2);
+        assert_eq!((laps[0].name, laps[0].delta), ("parse", parse));
+        assert_eq!(laps[1].delta, score);
+        assert_eq!(
+            laps[1].total.picoseconds,
+            laps[0].total.picoseconds + score.picoseconds
+        );
+        assert!(score.picoseconds >= 300 * PS_PER_US);
+
+        let report = timer.lap_report();
+        assert_eq!(report.lines().count(), 2);
+        assert!(report.starts_with("parse  +"));
+        assert_eq!(HiResTimer::start().lap_report(), "no laps");
+    }
+
     #[test]
     fn test_hires_metrics_accumulation() {
         let metrics = HiResMetrics::new();
@@ -559,6 +2386,74 @@ mod tests {
         assert_eq!(snapshot.mean_ps, 200 * PS_PER_NS);
     }
 
+    #[test]
+    fn test_scope_records_on_every_path() {
+        static METRICS: HiResMetrics = HiResMetrics::new();
+
+        fn lookup(key: u64) -> Option<u64> {
+            let _scope = METRICS.scope();
+            if key == 0 {
+                return None;
+            }
+            Some(key * 2)
+        }
+
+        assert_eq!(lookup(0), None);
+        assert_eq!(lookup(4), Some(8));
+        {
+            let mut scope = METRICS.scope();
+            scope.timer_mut().pause();
+            scope.cancel();
+        }
+        assert_eq!(METRICS.snapshot().count, 2);
+    }
+
+    #[test]
+    fn test_record_slice_matches_record() {
+        let samples: Vec<Picoseconds> = (1..=1000).map(|i| i * 1_337 * PS_PER_NS / 7).collect();
+
+        let one_by_one = HiResMetrics::new();
+        for &ps in &samples {
+            one_by_one.record(HiResTimestamp::from_picos(ps, 0));
+        }
+        let batched = HiResMetrics::new();
+        batched.record_slice(&samples[..400]);
+        batched.record_slice(&samples[400..]);
+        batched.record_slice(&[]);
+
+        let (a, b) = (one_by_one.snapshot(), batched.snapshot());
+        assert_eq!(
+            (a.count, a.min_ps, a.max_ps, a.mean_ps, a.stddev_ps),
+            (b.count, b.min_ps, b.max_ps, b.mean_ps, b.stddev_ps)
+        );
+    }
+
+    #[test]
+    fn test_probe_resolution() {
+        let report = probe_resolution();
+        assert_eq!(report.samples, 10_000);
+        assert!(report.min_delta.picoseconds > 0, "{}", report.format());
+        assert!(report.overhead.picoseconds > 0);
+        // Anything coarser than 1ms would make the timer useless
+        assert!(
+            report.min_delta.picoseconds < PS_PER_MS,
+            "{}",
+            report.format()
+        );
+        assert_eq!(
+            report.backend == COUNTER_BACKEND,
+            report.tsc_freq_hz.is_some()
+        );
+        assert!(report.format().starts_with("backend="));
+        assert_eq!(
+            report.clock_quality == ClockQuality::FallbackMonotonic,
+            report.tsc_freq_hz.is_none()
+        );
+        assert!(report
+            .format()
+            .ends_with(&format!("clock={}", report.clock_quality.as_str())));
+    }
+
     #[test]
     fn test_measure_closure() {
         let (result, timing) = measure(|| {
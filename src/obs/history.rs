@@ -0,0 +1,552 @@
+//! Telemetry History File
+//!
+//! Appends periodic [`TelemetrySnapshot`]s to a local file so metrics
+//! survive restarts and can be inspected after a crash, without a
+//! database. Read it back with [`Telemetry::load_history`].
+//!
+//! # Format
+//!
+//! An 8-byte magic `EOBSHST1`, then one record per snapshot:
+//!
+//! ```text
+//! len: u32 | crc32(payload): u32 | payload
+//! payload: timestamp_ms u64 | uptime_secs u64
+//!          | operations: n u32, (name, count, total_us, min_us, max_us,
+//!            last_us, sum_of_squares f64, histogram: n u32, u64...)...
+//!          | counters: n u32, (key, u64)... | gauges: n u32, (key, f64)...
+//! ```
+//!
+//! All integers are little-endian and strings are a `u32` length plus
+//! UTF-8. A record torn by a crash fails its checksum; loading stops
+//! there and the next [`HistoryFile::open`] truncates it away.
+//!
+//! Only the telemetry's own operations, counters and gauges are kept;
+//! built-in metrics, quality, high-resolution metrics and errors are not.
+//! Snapshots are written under the installed
+//! [`PrivacyPolicy`](crate::obs::privacy::PrivacyPolicy).
+//!
+//! # Retention
+//!
+//! [`with_retention`](HistoryFile::with_retention) and
+//! [`with_max_entries`](HistoryFile::with_max_entries) bound the file.
+//! Expired records are removed in batches, by rewriting the file once a
+//! quarter of it is due, so appends stay cheap.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::history::HistoryFile;
+//!
+//! let handle = HistoryFile::open("/var/lib/embeddenator/history.bin")?
+//!     .with_retention(Duration::from_secs(7 * 86_400))
+//!     .spawn(telemetry.clone(), Duration::from_secs(60));
+//!
+//! // After a restart
+//! for entry in Telemetry::load_history("/var/lib/embeddenator/history.bin")? {
+//!     println!("{:?}: {:?}", entry.timestamp, entry.snapshot.counters);
+//! }
+//! ```
+//!
+//! [`Telemetry::load_history`]: crate::obs::telemetry::Telemetry::load_history
+
+use crate::obs::clock::{system_clock, Clock};
+use crate::obs::crash_counters::crc32;
+use crate::obs::logging;
+use crate::obs::metrics::MetricsSnapshot;
+use crate::obs::privacy;
+use crate::obs::process::{spawn_sink, CollectorHandle};
+use crate::obs::quality::QualitySnapshot;
+use crate::obs::registry::MetricRegistry;
+use crate::obs::telemetry::{OperationStats, Telemetry, TelemetrySnapshot};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 8] = b"EOBSHST1";
+
+/// Record header: payload length and checksum.
+const RECORD_HEADER_LEN: usize = 8;
+
+/// One persisted snapshot.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    /// When the snapshot was appended
+    pub timestamp: SystemTime,
+    pub snapshot: TelemetrySnapshot,
+}
+
+/// Append-only snapshot history.
+pub struct HistoryFile {
+    path: PathBuf,
+    /// Closed while the file is replaced (required on Windows)
+    file: Option<File>,
+    /// Timestamps (ms) of the records in the file, oldest first
+    timestamps: VecDeque<u64>,
+    retention: Option<Duration>,
+    max_entries: Option<usize>,
+    clock: Arc<dyn Clock>,
+}
+
+impl HistoryFile {
+    /// Open (or create) a history file for appending, dropping a record
+    /// torn by a previous crash.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        let (entries, valid_len) = if data.is_empty() {
+            file.write_all(MAGIC)?;
+            (Vec::new(), MAGIC.len())
+        } else {
+            decode_file(&data)?
+        };
+        if valid_len < data.len() {
+            file.set_len(valid_len as u64)?;
+            file.seek(SeekFrom::End(0))?;
+        }
+        Ok(Self {
+            path,
+            file: Some(file),
+            timestamps: entries
+                .iter()
+                .map(|entry| unix_ms(entry.timestamp))
+                .collect(),
+            retention: None,
+            max_entries: None,
+            clock: system_clock(),
+        })
+    }
+
+    /// Drop records older than `retention` (default: keep everything).
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Keep at most the newest `max_entries` records.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries.max(1));
+        self
+    }
+
+    /// Timestamp records and apply retention with `clock`.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Records currently in the file.
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// Append `snapshot`, then compact if enough records have expired.
+    pub fn append(&mut self, snapshot: &TelemetrySnapshot) -> io::Result<()> {
+        let timestamp_ms = unix_ms(self.clock.system_time());
+        let snapshot = privacy::enforce_snapshot(snapshot);
+        let record = encode_record(timestamp_ms, &snapshot);
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self
+                .file
+                .insert(OpenOptions::new().append(true).open(&self.path)?),
+        };
+        file.write_all(&record)?;
+        file.flush()?;
+        self.timestamps.push_back(timestamp_ms);
+
+        let due = self.due();
+        if due > 0 && due >= self.timestamps.len() / 4 {
+            self.compact(due)?;
+        }
+        Ok(())
+    }
+
+    /// Append a snapshot of `telemetry` now and every `interval` on a
+    /// background thread, plus a final one when stopped.
+    /// [`force_flush`](crate::obs::process::force_flush) appends one too.
+    pub fn spawn(self, telemetry: Arc<Mutex<Telemetry>>, interval: Duration) -> CollectorHandle {
+        let mut writer = HistoryWriter {
+            file: self,
+            telemetry,
+        };
+        spawn_sink("obs-history", interval, move || writer.append())
+    }
+
+    /// Number of oldest records past retention or the entry limit.
+    fn due(&self) -> usize {
+        let over_limit = self
+            .max_entries
+            .map_or(0, |max| self.timestamps.len().saturating_sub(max));
+        let expired = self.retention.map_or(0, |retention| {
+            let cutoff =
+                unix_ms(self.clock.system_time()).saturating_sub(retention.as_millis() as u64);
+            self.timestamps
+                .iter()
+                .take_while(|&&ts| ts < cutoff)
+                .count()
+        });
+        over_limit.max(expired)
+    }
+
+    /// Rewrite the file without its `drop` oldest records.
+    fn compact(&mut self, drop: usize) -> io::Result<()> {
+        let data = fs::read(&self.path)?;
+        let mut offset = MAGIC.len();
+        for _ in 0..drop {
+            match next_record(&data, offset) {
+                Some((_, next)) => offset = next,
+                None => break,
+            }
+        }
+        let temp = self.path.with_extension("compact");
+        {
+            let mut out = File::create(&temp)?;
+            out.write_all(MAGIC)?;
+            out.write_all(&data[offset..])?;
+            out.sync_all()?;
+        }
+        self.file = None;
+        fs::rename(&temp, &self.path)?;
+        self.file = Some(OpenOptions::new().append(true).open(&self.path)?);
+        self.timestamps.drain(..drop.min(self.timestamps.len()));
+        Ok(())
+    }
+}
+
+/// Background appender; dropped with its thread, appending once more.
+struct HistoryWriter {
+    file: HistoryFile,
+    telemetry: Arc<Mutex<Telemetry>>,
+}
+
+impl HistoryWriter {
+    fn append(&mut self) {
+        let snapshot = match self.telemetry.lock() {
+            Ok(telemetry) => telemetry.snapshot(),
+            Err(poisoned) => poisoned.into_inner().snapshot(),
+        };
+        if let Err(e) = self.file.append(&snapshot) {
+            logging::warn(&format!(
+                "history append to {} failed: {}",
+                self.file.path.display(),
+                e
+            ));
+        }
+    }
+}
+
+impl Drop for HistoryWriter {
+    fn drop(&mut self) {
+        self.append();
+    }
+}
+
+/// Every intact record of a history file, oldest first.
+pub(crate) fn load(path: &Path) -> io::Result<Vec<HistoryEntry>> {
+    let data = fs::read(path)?;
+    decode_file(&data).map(|(entries, _)| entries)
+}
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Intact records and the length of the file they span.
+fn decode_file(data: &[u8]) -> io::Result<(Vec<HistoryEntry>, usize)> {
+    if data.get(..MAGIC.len()) != Some(MAGIC.as_slice()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a telemetry history file",
+        ));
+    }
+    let mut entries = Vec::new();
+    let mut offset = MAGIC.len();
+    while let Some((payload, next)) = next_record(data, offset) {
+        match decode_payload(payload) {
+            Some(entry) => entries.push(entry),
+            None => break,
+        }
+        offset = next;
+    }
+    Ok((entries, offset))
+}
+
+/// Payload of the checksummed record at `offset` and the offset after it.
+fn next_record(data: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let header = data.get(offset..offset + RECORD_HEADER_LEN)?;
+    let len = u32::from_le_bytes(header[..4].try_into().ok()?) as usize;
+    let crc = u32::from_le_bytes(header[4..].try_into().ok()?);
+    let start = offset + RECORD_HEADER_LEN;
+    let payload = data.get(start..start.checked_add(len)?)?;
+    (crc32(payload) == crc).then_some((payload, start + len))
+}
+
+fn encode_record(timestamp_ms: u64, snapshot: &TelemetrySnapshot) -> Vec<u8> {
+    let mut payload = Vec::new();
+    put_u64(&mut payload, timestamp_ms);
+    put_u64(&mut payload, snapshot.uptime_secs);
+
+    let mut operations: Vec<_> = snapshot.operation_stats.iter().collect();
+    operations.sort_by(|a, b| a.0.cmp(b.0));
+    put_u32(&mut payload, operations.len() as u32);
+    for (name, stats) in operations {
+        put_str(&mut payload, name);
+        for value in [
+            stats.count,
+            stats.total_us,
+            stats.min_us,
+            stats.max_us,
+            stats.last_us,
+        ] {
+            put_u64(&mut payload, value);
+        }
+        put_u64(&mut payload, stats.sum_of_squares.to_bits());
+        put_u32(&mut payload, stats.histogram.len() as u32);
+        for &sample in &stats.histogram {
+            put_u64(&mut payload, sample);
+        }
+    }
+
+    let mut counters: Vec<_> = snapshot.counters.iter().collect();
+    counters.sort();
+    put_u32(&mut payload, counters.len() as u32);
+    for (key, &value) in counters {
+        put_str(&mut payload, key);
+        put_u64(&mut payload, value);
+    }
+
+    let mut gauges: Vec<_> = snapshot.gauges.iter().collect();
+    gauges.sort_by(|a, b| a.0.cmp(b.0));
+    put_u32(&mut payload, gauges.len() as u32);
+    for (key, value) in gauges {
+        put_str(&mut payload, key);
+        put_u64(&mut payload, value.to_bits());
+    }
+
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+    put_u32(&mut record, payload.len() as u32);
+    put_u32(&mut record, crc32(&payload));
+    record.extend_from_slice(&payload);
+    record
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_str(buf: &mut Vec<u8>, value: &str) {
+    put_u32(buf, value.len() as u32);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Cursor over a record payload.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> Option<&[u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).ok()
+    }
+}
+
+fn decode_payload(payload: &[u8]) -> Option<HistoryEntry> {
+    let mut reader = Reader(payload);
+    let timestamp = UNIX_EPOCH + Duration::from_millis(reader.u64()?);
+    let uptime_secs = reader.u64()?;
+
+    let mut operation_stats = HashMap::new();
+    for _ in 0..reader.u32()? {
+        let name = reader.string()?;
+        let [count, total_us, min_us, max_us, last_us] = [
+            reader.u64()?,
+            reader.u64()?,
+            reader.u64()?,
+            reader.u64()?,
+            reader.u64()?,
+        ];
+        let sum_of_squares = f64::from_bits(reader.u64()?);
+        let samples = reader.u32()? as usize;
+        let histogram = (0..samples)
+            .map(|_| reader.u64())
+            .collect::<Option<Vec<u64>>>()?;
+        operation_stats.insert(
+            name,
+            OperationStats {
+                count,
+                total_us,
+                min_us,
+                max_us,
+                last_us,
+                histogram,
+                sum_of_squares,
+                exemplar: None,
+            },
+        );
+    }
+
+    let mut counters = HashMap::new();
+    for _ in 0..reader.u32()? {
+        let key = reader.string()?;
+        counters.insert(key, reader.u64()?);
+    }
+
+    let mut gauges = HashMap::new();
+    for _ in 0..reader.u32()? {
+        let key = reader.string()?;
+        gauges.insert(key, f64::from_bits(reader.u64()?));
+    }
+
+    Some(HistoryEntry {
+        timestamp,
+        snapshot: TelemetrySnapshot {
+            timestamp_secs: uptime_secs,
+            uptime_secs,
+            since_last_snapshot_secs: 0,
+            operation_stats,
+            counters,
+            gauges,
+            metrics: MetricsSnapshot::default(),
+            quality: QualitySnapshot::default(),
+            hires: Vec::new(),
+            errors: Vec::new(),
+            registry: Arc::new(MetricRegistry::new()),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obs::clock::MockClock;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("embeddenator_obs_{}_{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_round_trip_and_torn_tail() {
+        let path = temp_path("history_round_trip");
+        let _ = fs::remove_file(&path);
+        let clock = Arc::new(MockClock::at(UNIX_EPOCH + Duration::from_secs(1000)));
+        let mut telemetry = Telemetry::default_config();
+        telemetry.record_operation("query", 120);
+        telemetry.record_operation("query", 80);
+        telemetry.add_to_counter_with_labels("hits_total", &[("tier", "l1")], 4);
+        telemetry.set_gauge("depth", 1.5);
+
+        let mut history = HistoryFile::open(&path).unwrap().with_clock(clock.clone());
+        history.append(&telemetry.snapshot()).unwrap();
+        clock.advance(Duration::from_secs(60));
+        telemetry.increment_counter("restarts_total");
+        history.append(&telemetry.snapshot()).unwrap();
+        drop(history);
+
+        // A crash mid-write leaves a torn record behind
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[200, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+
+        let entries = Telemetry::load_history(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].timestamp, UNIX_EPOCH + Duration::from_secs(1060));
+        let snapshot = &entries[1].snapshot;
+        assert_eq!(snapshot.operation_stats["query"].count, 2);
+        assert_eq!(snapshot.operation_stats["query"].histogram, vec![120, 80]);
+        assert_eq!(snapshot.counters[r#"hits_total{tier="l1"}"#], 4);
+        assert_eq!(snapshot.counters["restarts_total"], 1);
+        assert_eq!(snapshot.gauges["depth"], 1.5);
+
+        // Reopening drops the torn tail so new records stay reachable
+        let mut history = HistoryFile::open(&path).unwrap();
+        assert_eq!(history.len(), 2);
+        history.append(&telemetry.snapshot()).unwrap();
+        assert_eq!(Telemetry::load_history(&path).unwrap().len(), 3);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_retention_compacts_in_batches() {
+        let path = temp_path("history_retention");
+        let _ = fs::remove_file(&path);
+        let clock = Arc::new(MockClock::at(UNIX_EPOCH + Duration::from_secs(1000)));
+        let snapshot = Telemetry::default_config().snapshot();
+        let mut history = HistoryFile::open(&path)
+            .unwrap()
+            .with_clock(clock.clone())
+            .with_retention(Duration::from_secs(100))
+            .with_max_entries(8);
+
+        for _ in 0..12 {
+            history.append(&snapshot).unwrap();
+            clock.advance(Duration::from_secs(1));
+        }
+        // Over the limit by 4 of 12: compacted down to 8
+        assert_eq!(history.len(), 8);
+        assert_eq!(Telemetry::load_history(&path).unwrap().len(), 8);
+
+        clock.advance(Duration::from_secs(200));
+        history.append(&snapshot).unwrap();
+        let entries = Telemetry::load_history(&path).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].timestamp, UNIX_EPOCH + Duration::from_secs(1212));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_spawned_writer_flushes_and_appends_on_stop() {
+        let path = temp_path("history_spawn");
+        let _ = fs::remove_file(&path);
+        let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+        let handle = HistoryFile::open(&path)
+            .unwrap()
+            .spawn(telemetry.clone(), Duration::from_secs(3600));
+
+        telemetry.lock().unwrap().increment_counter("starts");
+        let report = crate::obs::process::force_flush(Duration::from_secs(5));
+        assert!(report.flushed.iter().any(|name| name == "obs-history"));
+        let entries = Telemetry::load_history(&path).unwrap();
+        assert_eq!(entries.last().unwrap().snapshot.counters["starts"], 1);
+
+        let before = entries.len();
+        handle.stop();
+        assert_eq!(Telemetry::load_history(&path).unwrap().len(), before + 1);
+        let _ = fs::remove_file(&path);
+    }
+}
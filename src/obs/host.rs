@@ -0,0 +1,327 @@
+//! Host Metrics Collector
+//!
+//! Basic host-level metrics for deployments without a node exporter:
+//! load average, disk usage of configured paths, and per-interface network
+//! byte counters, written into [`Telemetry`] gauges.
+//!
+//! # Collected Gauges
+//!
+//! - `host_load1` / `host_load5` / `host_load15`
+//! - `host_disk_total_bytes{path}` / `host_disk_available_bytes{path}` /
+//!   `host_disk_used_ratio{path}`
+//! - `host_network_receive_bytes{interface}` /
+//!   `host_network_transmit_bytes{interface}`
+//!
+//! Load average and disk usage are available on Unix; network counters are
+//! read from `/proc/net/dev` on Linux. Unavailable values are skipped.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::host::HostCollector;
+//!
+//! let collector = HostCollector::new()
+//!     .with_disk_path("/")
+//!     .with_disk_path("/var/lib/embeddenator");
+//!
+//! // Periodic (stops when the handle is dropped)
+//! let _handle = collector.spawn(Duration::from_secs(30), telemetry.clone());
+//! ```
+
+use crate::obs::process::{spawn_collector, CollectorHandle};
+use crate::obs::telemetry::Telemetry;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Disk usage of the filesystem containing a path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskUsage {
+    pub path: PathBuf,
+    pub total_bytes: u64,
+    /// Free bytes, including blocks reserved for root
+    pub free_bytes: u64,
+    /// Free bytes available to unprivileged users
+    pub available_bytes: u64,
+    pub total_inodes: u64,
+    pub free_inodes: u64,
+}
+
+impl DiskUsage {
+    /// Fraction of the filesystem in use, as seen by unprivileged users.
+    pub fn used_ratio(&self) -> f64 {
+        let reserved = self.free_bytes.saturating_sub(self.available_bytes);
+        let usable = self.total_bytes.saturating_sub(reserved);
+        if usable == 0 {
+            0.0
+        } else {
+            self.total_bytes.saturating_sub(self.free_bytes) as f64 / usable as f64
+        }
+    }
+
+    /// Fraction of inodes in use (0.0 on filesystems without inode limits).
+    pub fn inode_used_ratio(&self) -> f64 {
+        if self.total_inodes == 0 {
+            0.0
+        } else {
+            self.total_inodes.saturating_sub(self.free_inodes) as f64 / self.total_inodes as f64
+        }
+    }
+}
+
+/// Cumulative byte counters of one network interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkCounters {
+    pub interface: String,
+    pub receive_bytes: u64,
+    pub transmit_bytes: u64,
+}
+
+/// One sample of host metrics.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostStats {
+    /// 1, 5 and 15 minute load averages
+    pub load_average: Option<[f64; 3]>,
+    pub disks: Vec<DiskUsage>,
+    pub network: Vec<NetworkCounters>,
+}
+
+/// Collector for host metrics.
+#[derive(Debug, Clone, Default)]
+pub struct HostCollector {
+    disk_paths: Vec<PathBuf>,
+    include_loopback: bool,
+}
+
+impl HostCollector {
+    /// Collector for load average and network counters; add disk paths with
+    /// [`with_disk_path`](Self::with_disk_path).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report disk usage of the filesystem containing `path`.
+    pub fn with_disk_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.disk_paths.push(path.into());
+        self
+    }
+
+    /// Include the loopback interface in network counters (default: false).
+    pub fn with_loopback(mut self, include: bool) -> Self {
+        self.include_loopback = include;
+        self
+    }
+
+    /// Take one sample of host metrics.
+    pub fn sample(&self) -> HostStats {
+        let mut network = network_counters();
+        if !self.include_loopback {
+            network.retain(|counters| counters.interface != "lo");
+        }
+
+        HostStats {
+            load_average: load_average(),
+            disks: self
+                .disk_paths
+                .iter()
+                .filter_map(|path| disk_usage(path))
+                .collect(),
+            network,
+        }
+    }
+
+    /// Sample and write available values into telemetry gauges.
+    pub fn collect_into(&self, telemetry: &mut Telemetry) -> HostStats {
+        let stats = self.sample();
+
+        if let Some([load1, load5, load15]) = stats.load_average {
+            telemetry.set_gauge("host_load1", load1);
+            telemetry.set_gauge("host_load5", load5);
+            telemetry.set_gauge("host_load15", load15);
+        }
+
+        for disk in &stats.disks {
+            let path = disk.path.to_string_lossy();
+            let labels = [("path", path.as_ref())];
+            telemetry.set_gauge_with_labels(
+                "host_disk_total_bytes",
+                &labels,
+                disk.total_bytes as f64,
+            );
+            telemetry.set_gauge_with_labels(
+                "host_disk_available_bytes",
+                &labels,
+                disk.available_bytes as f64,
+            );
+            telemetry.set_gauge_with_labels("host_disk_used_ratio", &labels, disk.used_ratio());
+        }
+
+        for counters in &stats.network {
+            let labels = [("interface", counters.interface.as_str())];
+            telemetry.set_gauge_with_labels(
+                "host_network_receive_bytes",
+                &labels,
+                counters.receive_bytes as f64,
+            );
+            telemetry.set_gauge_with_labels(
+                "host_network_transmit_bytes",
+                &labels,
+                counters.transmit_bytes as f64,
+            );
+        }
+
+        stats
+    }
+
+    /// Collect into shared telemetry every `interval` on a background thread.
+    pub fn spawn(self, interval: Duration, telemetry: Arc<Mutex<Telemetry>>) -> CollectorHandle {
+        spawn_collector(
+            "obs-host-collector",
+            interval,
+            telemetry,
+            move |telemetry| {
+                self.collect_into(telemetry);
+            },
+        )
+    }
+}
+
+/// Disk usage of the filesystem containing `path`, if it can be queried.
+#[cfg(unix)]
+pub fn disk_usage(path: &Path) -> Option<DiskUsage> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: c_path is NUL-terminated and stat points to writable storage
+    if unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: statvfs returned 0, so the struct is initialised
+    let stat = unsafe { stat.assume_init() };
+
+    // Field widths vary by platform (u32 on macOS and 32-bit targets)
+    #[allow(clippy::unnecessary_cast)]
+    let (fragment, blocks, bfree, bavail, files, ffree) = (
+        stat.f_frsize as u64,
+        stat.f_blocks as u64,
+        stat.f_bfree as u64,
+        stat.f_bavail as u64,
+        stat.f_files as u64,
+        stat.f_ffree as u64,
+    );
+    Some(DiskUsage {
+        path: path.to_path_buf(),
+        total_bytes: blocks * fragment,
+        free_bytes: bfree * fragment,
+        available_bytes: bavail * fragment,
+        total_inodes: files,
+        free_inodes: ffree,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn disk_usage(_path: &Path) -> Option<DiskUsage> {
+    None
+}
+
+#[cfg(unix)]
+fn load_average() -> Option<[f64; 3]> {
+    let mut loads = [0f64; 3];
+    // SAFETY: loads has room for the 3 requested samples
+    let n = unsafe { libc::getloadavg(loads.as_mut_ptr(), 3) };
+    (n == 3).then_some(loads)
+}
+
+#[cfg(not(unix))]
+fn load_average() -> Option<[f64; 3]> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn network_counters() -> Vec<NetworkCounters> {
+    std::fs::read_to_string("/proc/net/dev")
+        .map(|content| parse_net_dev(&content))
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn network_counters() -> Vec<NetworkCounters> {
+    Vec::new()
+}
+
+/// Parse `/proc/net/dev`: two header lines, then `iface: rx_bytes ... tx_bytes ...`
+/// where transmit bytes is the 9th value.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_net_dev(content: &str) -> Vec<NetworkCounters> {
+    content
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (interface, values) = line.split_once(':')?;
+            let values: Vec<u64> = values
+                .split_whitespace()
+                .filter_map(|v| v.parse().ok())
+                .collect();
+            Some(NetworkCounters {
+                interface: interface.trim().to_string(),
+                receive_bytes: *values.first()?,
+                transmit_bytes: *values.get(8)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_net_dev() {
+        let content = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  1000      10    0    0    0     0          0         0     1000      10    0    0    0     0       0          0
+  eth0: 123456    100    0    0    0     0          0         0    654321     90    0    0    0     0       0          0
+";
+        let counters = parse_net_dev(content);
+        assert_eq!(counters.len(), 2);
+        assert_eq!(counters[1].interface, "eth0");
+        assert_eq!(counters[1].receive_bytes, 123456);
+        assert_eq!(counters[1].transmit_bytes, 654321);
+    }
+
+    #[test]
+    fn test_used_ratio_excludes_reserved_blocks() {
+        let disk = DiskUsage {
+            path: PathBuf::from("/"),
+            total_bytes: 100,
+            free_bytes: 30,
+            available_bytes: 20,
+            total_inodes: 10,
+            free_inodes: 4,
+        };
+        // 70 used of 90 usable
+        assert!((disk.used_ratio() - 70.0 / 90.0).abs() < 1e-9);
+        assert!((disk.inode_used_ratio() - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_collect_into_telemetry() {
+        let mut telemetry = Telemetry::default_config();
+        let stats = HostCollector::new()
+            .with_disk_path("/")
+            .with_disk_path("/definitely/not/a/path")
+            .collect_into(&mut telemetry);
+
+        assert_eq!(stats.disks.len(), 1);
+        assert!(stats.disks[0].total_bytes > 0);
+        assert!(stats.network.iter().all(|c| c.interface != "lo"));
+
+        let gauges = telemetry.snapshot().gauges;
+        assert!(gauges.contains_key(r#"host_disk_total_bytes{path="/"}"#));
+        assert!(gauges.contains_key("host_load1"));
+    }
+}
@@ -0,0 +1,234 @@
+//! Minimal HTTP/1.1 client for push exporters and notifiers.
+//!
+//! Only plain `http://` endpoints are supported; put a TLS-terminating
+//! proxy or agent in front of `https` receivers.
+
+use crate::obs::health::DeadlineReader;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Response bytes read before closing; only the status line is used.
+const MAX_RESPONSE_BYTES: u64 = 64 * 1024;
+
+/// Parsed `http://host[:port]/path` endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HttpEndpoint {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) path: String,
+}
+
+impl HttpEndpoint {
+    /// Parse an `http://` URL; the scheme may be omitted.
+    pub(crate) fn parse(url: &str) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, msg.to_string());
+        let rest = match url.split_once("://") {
+            Some(("http", rest)) => rest,
+            Some((_, _)) => return Err(invalid("only http:// endpoints are supported")),
+            None => url,
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+            // IPv6 literal: [::1]:9009
+            let (host, after) = bracketed
+                .split_once(']')
+                .ok_or_else(|| invalid("unterminated IPv6 address"))?;
+            (host, after.strip_prefix(':'))
+        } else {
+            match authority.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            }
+        };
+        let port = match port {
+            Some(port) => port.parse().map_err(|_| invalid("invalid port"))?,
+            None => 80,
+        };
+        if host.is_empty() {
+            return Err(invalid("missing host"));
+        }
+        if path.contains(|c: char| c.is_ascii_control() || c == ' ') {
+            return Err(invalid("path contains whitespace or control characters"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// `host:port` for the `Host` header, with IPv6 literals bracketed.
+    pub(crate) fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    /// Send one `POST` and return the HTTP status code.
+    ///
+    /// `timeout` applies to connect, write and read separately. Headers
+    /// whose name or value holds CR or LF are rejected before connecting.
+    pub(crate) fn post(
+        &self,
+        headers: &[(String, String)],
+        body: &[u8],
+        timeout: Duration,
+    ) -> io::Result<u16> {
+        if let Some((name, _)) = headers
+            .iter()
+            .find(|(name, value)| name.contains(['\r', '\n']) || value.contains(['\r', '\n']))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("header {:?} contains CR or LF", name),
+            ));
+        }
+        let addr = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address resolved"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             User-Agent: embeddenator-obs/{}\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n",
+            self.path,
+            self.authority(),
+            env!("CARGO_PKG_VERSION"),
+            body.len()
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        let deadline = DeadlineReader::new(stream, timeout);
+        let mut reader = BufReader::new(deadline).take(MAX_RESPONSE_BYTES);
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line)?;
+        let status = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "malformed HTTP status line")
+            })?;
+        // Drain a bounded amount so the server sees a clean close
+        let _ = io::copy(&mut reader, &mut io::sink());
+        Ok(status)
+    }
+}
+
+/// Exponential backoff for retried requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Backoff {
+    pub(crate) max_retries: u32,
+    pub(crate) initial: Duration,
+    pub(crate) max: Duration,
+}
+
+impl Backoff {
+    /// Run `send` until it returns a 2xx status. Connection errors, 429
+    /// and 5xx are retried; other statuses fail at once, as the receiver
+    /// will never accept the same request.
+    pub(crate) fn retry(
+        &self,
+        what: &str,
+        mut send: impl FnMut() -> io::Result<u16>,
+    ) -> io::Result<()> {
+        let mut backoff = self.initial;
+        let mut attempt = 0;
+        loop {
+            let error = match send() {
+                Ok(status) if (200..300).contains(&status) => return Ok(()),
+                Ok(status) => {
+                    let error = io::Error::other(format!("{} rejected: HTTP {}", what, status));
+                    if status != 429 && status < 500 {
+                        return Err(error);
+                    }
+                    error
+                }
+                Err(error) => error,
+            };
+
+            if attempt >= self.max_retries {
+                return Err(error);
+            }
+            attempt += 1;
+            std::thread::sleep(backoff);
+            backoff = (backoff * 2).min(self.max);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_parsing() {
+        let endpoint = HttpEndpoint::parse("http://[::1]:9009/api/v1/push").unwrap();
+        assert_eq!((endpoint.host.as_str(), endpoint.port), ("::1", 9009));
+        assert_eq!(endpoint.authority(), "[::1]:9009");
+        let endpoint = HttpEndpoint::parse("http://collector:4318/v1/traces").unwrap();
+        assert_eq!(endpoint.authority(), "collector:4318");
+        let endpoint = HttpEndpoint::parse("vm/api/v1/write").unwrap();
+        assert_eq!(
+            (endpoint.port, endpoint.path.as_str()),
+            (80, "/api/v1/write")
+        );
+        assert_eq!(HttpEndpoint::parse("http://hooks:8080").unwrap().path, "/");
+        assert!(HttpEndpoint::parse("https://mimir/api/v1/push").is_err());
+        assert!(HttpEndpoint::parse("http://:80/").is_err());
+        assert!(HttpEndpoint::parse("http://hooks/a b").is_err());
+        assert!(HttpEndpoint::parse("http://hooks/a\r\nX: y").is_err());
+    }
+
+    #[test]
+    fn test_post_rejects_header_injection() {
+        let endpoint = HttpEndpoint::parse("http://127.0.0.1:9/").unwrap();
+        for header in [
+            ("X-Token".to_string(), "a\r\nX-Injected: 1".to_string()),
+            ("X-Token\n".to_string(), "a".to_string()),
+        ] {
+            let err = endpoint
+                .post(&[header], b"", Duration::from_secs(1))
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_post_stops_reading_endless_response() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            let _ = stream.write_all(b"HTTP/1.1 202 Accepted\r\n");
+            // Keeps sending until the client hangs up
+            while stream.write_all(&[b'x'; 4096]).is_ok() {}
+        });
+
+        let endpoint = HttpEndpoint::parse(&format!("http://127.0.0.1:{}/", port)).unwrap();
+        let status = endpoint.post(&[], b"{}", Duration::from_secs(5)).unwrap();
+        assert_eq!(status, 202);
+        server.join().unwrap();
+    }
+}
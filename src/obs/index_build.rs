@@ -0,0 +1,383 @@
+//! Index Build Progress and Stage Telemetry
+//!
+//! Visibility into long-running index builds: per-stage timings, items
+//! processed, bytes written, an overall progress gauge, and a span
+//! hierarchy (one root span per build, one child span per stage).
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::index_build::{BuildStage, IndexBuildObserver};
+//!
+//! let observer = IndexBuildObserver::new("main_index", Some(1_000_000));
+//!
+//! {
+//!     let _stage = observer.begin_stage(BuildStage::Scan);
+//!     for chunk in chunks {
+//!         observer.add_items(BuildStage::Scan, chunk.len() as u64);
+//!     }
+//! } // stage duration recorded, stage span ended
+//!
+//! observer.record_into(&mut telemetry); // gauges + per-stage timings
+//! let report = observer.finish();
+//! println!("{}", report.summary());
+//! ```
+
+use crate::obs::opentelemetry::OtelSpan;
+use crate::obs::telemetry::Telemetry;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Stages of an index build, in execution order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuildStage {
+    /// Scanning source documents
+    Scan,
+    /// Computing embeddings
+    Embed,
+    /// Clustering / partitioning vectors
+    Cluster,
+    /// Writing index files
+    Write,
+}
+
+impl BuildStage {
+    /// All stages, in execution order.
+    pub const ALL: [BuildStage; 4] = [
+        BuildStage::Scan,
+        BuildStage::Embed,
+        BuildStage::Cluster,
+        BuildStage::Write,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuildStage::Scan => "scan",
+            BuildStage::Embed => "embed",
+            BuildStage::Cluster => "cluster",
+            BuildStage::Write => "write",
+        }
+    }
+}
+
+/// Observer collecting progress and timing for one index build.
+pub struct IndexBuildObserver {
+    name: String,
+    total_items: Option<u64>,
+    started: Instant,
+    items: [AtomicU64; 4],
+    bytes_written: AtomicU64,
+    state: Mutex<BuildState>,
+}
+
+struct BuildState {
+    root_span: OtelSpan,
+    stage_durations: [Duration; 4],
+    completed: [bool; 4],
+    reported: [bool; 4],
+    finished_spans: Vec<OtelSpan>,
+}
+
+/// RAII guard for an active build stage.
+///
+/// Records the stage duration and ends the stage span on drop.
+pub struct StageGuard<'a> {
+    observer: &'a IndexBuildObserver,
+    stage: BuildStage,
+    started: Instant,
+    span: Option<OtelSpan>,
+    #[cfg(feature = "tracing")]
+    _tracing_span: tracing::span::EnteredSpan,
+}
+
+impl IndexBuildObserver {
+    /// Start observing a build. `total_items` enables progress estimation.
+    pub fn new(name: impl Into<String>, total_items: Option<u64>) -> Self {
+        let name = name.into();
+        let mut root_span = OtelSpan::new("index_build");
+        root_span.set_attribute("index.name", name.clone());
+        if let Some(total) = total_items {
+            root_span.set_attribute("index.total_items", total);
+        }
+
+        Self {
+            name,
+            total_items,
+            started: Instant::now(),
+            items: [const { AtomicU64::new(0) }; 4],
+            bytes_written: AtomicU64::new(0),
+            state: Mutex::new(BuildState {
+                root_span,
+                stage_durations: [Duration::ZERO; 4],
+                completed: [false; 4],
+                reported: [false; 4],
+                finished_spans: Vec::new(),
+            }),
+        }
+    }
+
+    /// Begin a stage; the returned guard ends it when dropped.
+    pub fn begin_stage(&self, stage: BuildStage) -> StageGuard<'_> {
+        let span = {
+            let state = self.state.lock().unwrap();
+            let mut span =
+                OtelSpan::new_child(format!("index_build.{}", stage.as_str()), &state.root_span);
+            span.set_attribute("index.name", self.name.clone());
+            span
+        };
+
+        StageGuard {
+            observer: self,
+            stage,
+            started: Instant::now(),
+            span: Some(span),
+            #[cfg(feature = "tracing")]
+            _tracing_span: crate::obs::tracing::create_span(
+                &format!("index_build.{}", stage.as_str()),
+                &[("index", &self.name)],
+            )
+            .entered(),
+        }
+    }
+
+    /// Add to the items processed by a stage.
+    pub fn add_items(&self, stage: BuildStage, count: u64) {
+        self.items[stage as usize].fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Add to the bytes written by the build.
+    pub fn add_bytes_written(&self, bytes: u64) {
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Items processed by a stage so far.
+    pub fn items(&self, stage: BuildStage) -> u64 {
+        self.items[stage as usize].load(Ordering::Relaxed)
+    }
+
+    /// Bytes written so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Overall progress in `[0.0, 1.0]`.
+    ///
+    /// Each stage contributes an equal share: completed stages count fully,
+    /// in-progress stages count `items / total_items` (when known).
+    pub fn progress(&self) -> f64 {
+        let state = self.state.lock().unwrap();
+        let per_stage: f64 = BuildStage::ALL
+            .iter()
+            .map(|&stage| {
+                if state.completed[stage as usize] {
+                    1.0
+                } else {
+                    match self.total_items {
+                        Some(total) if total > 0 => {
+                            (self.items(stage) as f64 / total as f64).min(1.0)
+                        }
+                        _ => 0.0,
+                    }
+                }
+            })
+            .sum();
+        per_stage / BuildStage::ALL.len() as f64
+    }
+
+    /// Accumulated duration of a stage.
+    pub fn stage_duration(&self, stage: BuildStage) -> Duration {
+        self.state.lock().unwrap().stage_durations[stage as usize]
+    }
+
+    /// Publish current progress into telemetry gauges and counters.
+    ///
+    /// Gauges: `index_build_progress`, `index_build_bytes_written`,
+    /// `index_build_<stage>_items`. Operations: `index_build_<stage>` (µs).
+    pub fn record_into(&self, telemetry: &mut Telemetry) {
+        telemetry.set_gauge("index_build_progress", self.progress());
+        telemetry.set_gauge("index_build_bytes_written", self.bytes_written() as f64);
+        for stage in BuildStage::ALL {
+            telemetry.set_gauge(
+                &format!("index_build_{}_items", stage.as_str()),
+                self.items(stage) as f64,
+            );
+        }
+
+        let mut state = self.state.lock().unwrap();
+        for stage in BuildStage::ALL {
+            let idx = stage as usize;
+            // Record each completed stage only once
+            if state.completed[idx] && !state.reported[idx] {
+                telemetry.record_operation(
+                    &format!("index_build_{}", stage.as_str()),
+                    state.stage_durations[idx].as_micros() as u64,
+                );
+                state.reported[idx] = true;
+            }
+        }
+    }
+
+    /// Finish the build, ending the root span.
+    pub fn finish(self) -> IndexBuildReport {
+        let total_duration = self.started.elapsed();
+        let items = BuildStage::ALL.map(|stage| self.items(stage));
+        let bytes_written = self.bytes_written();
+        let progress = self.progress();
+
+        let mut state = self.state.into_inner().unwrap();
+        state
+            .root_span
+            .set_attribute("index.bytes_written", bytes_written);
+        state.root_span.end();
+
+        let mut spans = vec![state.root_span];
+        spans.append(&mut state.finished_spans);
+
+        IndexBuildReport {
+            name: self.name,
+            total_duration,
+            stage_durations: state.stage_durations,
+            items,
+            bytes_written,
+            progress,
+            spans,
+        }
+    }
+
+    fn end_stage(&self, stage: BuildStage, elapsed: Duration, mut span: OtelSpan) {
+        span.set_attribute("index.items", self.items(stage));
+        span.end();
+
+        let mut state = self.state.lock().unwrap();
+        state.stage_durations[stage as usize] += elapsed;
+        state.completed[stage as usize] = true;
+        state.finished_spans.push(span);
+    }
+}
+
+impl Drop for StageGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(span) = self.span.take() {
+            self.observer
+                .end_stage(self.stage, self.started.elapsed(), span);
+        }
+    }
+}
+
+/// Final report of an index build.
+#[derive(Debug, Clone)]
+pub struct IndexBuildReport {
+    pub name: String,
+    pub total_duration: Duration,
+    /// Duration per stage, indexed by `BuildStage as usize`
+    pub stage_durations: [Duration; 4],
+    /// Items processed per stage, indexed by `BuildStage as usize`
+    pub items: [u64; 4],
+    pub bytes_written: u64,
+    pub progress: f64,
+    /// Root span followed by stage spans, ready for `OtelExporter`
+    pub spans: Vec<OtelSpan>,
+}
+
+impl IndexBuildReport {
+    /// Format as human-readable summary.
+    pub fn summary(&self) -> String {
+        let mut output = format!(
+            "=== Index Build '{}' ({:.2}s, {:.0}% complete) ===\n",
+            self.name,
+            self.total_duration.as_secs_f64(),
+            self.progress * 100.0
+        );
+        for stage in BuildStage::ALL {
+            let idx = stage as usize;
+            output.push_str(&format!(
+                "  {}: {:.2}s, items={}\n",
+                stage.as_str(),
+                self.stage_durations[idx].as_secs_f64(),
+                self.items[idx]
+            ));
+        }
+        output.push_str(&format!("  bytes_written={}\n", self.bytes_written));
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obs::opentelemetry::AttributeValue;
+
+    #[test]
+    fn test_stage_timing_and_items() {
+        let observer = IndexBuildObserver::new("test_index", Some(100));
+
+        {
+            let _stage = observer.begin_stage(BuildStage::Scan);
+            observer.add_items(BuildStage::Scan, 100);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        assert_eq!(observer.items(BuildStage::Scan), 100);
+        assert!(observer.stage_duration(BuildStage::Scan) >= Duration::from_millis(2));
+        assert_eq!(observer.stage_duration(BuildStage::Embed), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_progress() {
+        let observer = IndexBuildObserver::new("progress", Some(100));
+        assert_eq!(observer.progress(), 0.0);
+
+        {
+            let _stage = observer.begin_stage(BuildStage::Scan);
+            observer.add_items(BuildStage::Scan, 100);
+        }
+        assert_eq!(observer.progress(), 0.25);
+
+        observer.add_items(BuildStage::Embed, 50);
+        assert_eq!(observer.progress(), 0.375);
+    }
+
+    #[test]
+    fn test_span_hierarchy() {
+        let observer = IndexBuildObserver::new("spans", None);
+        drop(observer.begin_stage(BuildStage::Scan));
+        drop(observer.begin_stage(BuildStage::Write));
+        observer.add_bytes_written(4096);
+
+        let report = observer.finish();
+        assert_eq!(report.spans.len(), 3);
+
+        let root = &report.spans[0];
+        assert!(root.is_root());
+        assert_eq!(
+            root.attributes.get("index.bytes_written"),
+            Some(&AttributeValue::I64(4096))
+        );
+        for child in &report.spans[1..] {
+            assert_eq!(child.trace_id, root.trace_id);
+            assert_eq!(child.parent_span_id, root.span_id);
+        }
+        assert_eq!(report.spans[2].name, "index_build.write");
+        assert!(report.summary().contains("bytes_written=4096"));
+    }
+
+    #[test]
+    fn test_record_into_telemetry() {
+        let observer = IndexBuildObserver::new("telemetry", Some(10));
+        {
+            let _stage = observer.begin_stage(BuildStage::Embed);
+            observer.add_items(BuildStage::Embed, 10);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let mut telemetry = Telemetry::default_config();
+        observer.record_into(&mut telemetry);
+        observer.record_into(&mut telemetry);
+
+        let snapshot = telemetry.snapshot();
+        assert_eq!(snapshot.gauges.get("index_build_progress"), Some(&0.25));
+        assert_eq!(snapshot.gauges.get("index_build_embed_items"), Some(&10.0));
+        assert_eq!(snapshot.operation_stats["index_build_embed"].count, 1);
+    }
+}
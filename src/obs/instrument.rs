@@ -0,0 +1,244 @@
+//! Function Instrumentation Runtime
+//!
+//! Support code for the `#[trace]` attribute macro (feature `macros`),
+//! usable by hand as well. A [`TracedCall`] is an [`OtelSpan`] for one
+//! function call:
+//!
+//! - the parent is the innermost `TracedCall` active on this thread, so
+//!   nested instrumented functions form one trace
+//! - arguments become attributes, typed where possible
+//! - on drop the span ends (with error status if [`fail`](TracedCall::fail)
+//!   was called or the call panicked, with the panic message under
+//!   [`install_panic_hook`](crate::obs::panic_hook::install_panic_hook)),
+//!   flowing to the installed
+//!   [`TracerProvider`](crate::obs::span_processor::TracerProvider), and
+//!   the call duration is recorded into the attached [`Telemetry`]
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs as obs;
+//!
+//! #[obs::trace(telemetry = TELEMETRY, skip(buf), err)]
+//! fn decode(shard: u32, buf: &[u8]) -> Result<Index, DecodeError> {
+//!     // span "decode" with attribute shard; duration recorded as
+//!     // operation "decode"; an Err marks the span failed
+//! }
+//!
+//! // By hand
+//! let mut call = TracedCall::enter("rebuild", vec![("shards", 8.into())]);
+//! ```
+
+use crate::obs::opentelemetry::{AttributeValue, OtelSpan};
+use crate::obs::telemetry::Telemetry;
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+thread_local! {
+    /// Spans of the `TracedCall`s active on this thread, innermost last.
+    static ACTIVE_CALLS: RefCell<Vec<OtelSpan>> = const { RefCell::new(Vec::new()) };
+}
+
+thread_local! {
+    /// Message of the panic unwinding this thread, set by the panic hook.
+    static PANIC_MESSAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Note that this thread is panicking with `message`: active calls end
+/// with it as their error while unwinding. Under `panic = "abort"`
+/// nothing unwinds, so the innermost active call's span ends here.
+pub(crate) fn note_panic(message: &str) {
+    let _ = PANIC_MESSAGE.try_with(|panic| {
+        if let Ok(mut panic) = panic.try_borrow_mut() {
+            *panic = Some(message.to_string());
+        }
+    });
+    #[cfg(panic = "abort")]
+    {
+        let innermost = ACTIVE_CALLS
+            .try_with(|calls| calls.try_borrow().ok().and_then(|c| c.last().cloned()))
+            .ok()
+            .flatten();
+        if let Some(mut span) = innermost {
+            span.end_with_error(message);
+        }
+    }
+}
+
+fn panic_message() -> String {
+    PANIC_MESSAGE
+        .try_with(|panic| panic.borrow().clone())
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "panicked".to_string())
+}
+
+/// Span and timer for one instrumented call; ends on drop.
+#[must_use = "the call is recorded when the guard is dropped"]
+pub struct TracedCall {
+    span: OtelSpan,
+    started: Instant,
+    telemetry: Option<Arc<Mutex<Telemetry>>>,
+    error: Option<String>,
+    /// Whether the span is on this thread's active stack
+    stacked: bool,
+}
+
+impl TracedCall {
+    /// Start a span named `name`, child of the innermost active call on
+    /// this thread, and make it the active call until dropped.
+    pub fn enter(name: &str, attributes: Vec<(&str, AttributeValue)>) -> Self {
+        let mut call = Self::detached(name, attributes);
+        let span = call.span.clone();
+        ACTIVE_CALLS.with(|calls| calls.borrow_mut().push(span));
+        call.stacked = true;
+        call
+    }
+
+    /// Like [`enter`](Self::enter), without becoming the active call;
+    /// for `async` bodies, which may resume on other threads.
+    pub fn detached(name: &str, attributes: Vec<(&str, AttributeValue)>) -> Self {
+        let parent = ACTIVE_CALLS.with(|calls| calls.borrow().last().cloned());
+        let mut builder = OtelSpan::builder(name);
+        if let Some(parent) = &parent {
+            builder = builder.parent(parent);
+        }
+        for (key, value) in attributes {
+            builder = builder.attr(key, value);
+        }
+        Self {
+            span: builder.start(),
+            started: Instant::now(),
+            telemetry: None,
+            error: None,
+            stacked: false,
+        }
+    }
+
+    /// Record the call duration into `telemetry` as operation timing
+    /// named after the span.
+    pub fn with_telemetry(mut self, telemetry: Arc<Mutex<Telemetry>>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// End the span with error status and `error` as message.
+    pub fn fail(&mut self, error: &dyn fmt::Display) {
+        self.error = Some(error.to_string());
+    }
+
+    pub fn span(&self) -> &OtelSpan {
+        &self.span
+    }
+
+    pub fn span_mut(&mut self) -> &mut OtelSpan {
+        &mut self.span
+    }
+}
+
+impl Drop for TracedCall {
+    fn drop(&mut self) {
+        if self.stacked {
+            let span_id = self.span.span_id;
+            ACTIVE_CALLS.with(|calls| {
+                let mut calls = calls.borrow_mut();
+                if let Some(at) = calls.iter().rposition(|s| s.span_id == span_id) {
+                    calls.truncate(at);
+                }
+            });
+        }
+        let error = match self.error.take() {
+            Some(error) => Some(error),
+            None if std::thread::panicking() => Some(panic_message()),
+            None => None,
+        };
+        match error {
+            Some(error) => self.span.end_with_error(error),
+            None => self.span.end(),
+        }
+        if let Some(telemetry) = &self.telemetry {
+            if let Ok(mut telemetry) = telemetry.lock() {
+                let duration_us = self.started.elapsed().as_micros() as u64;
+                telemetry.record_operation(&self.span.name, duration_us);
+            }
+        }
+    }
+}
+
+/// Wraps an argument for [`arg_attribute`]'s method dispatch.
+pub struct Arg<'a, T: ?Sized>(pub &'a T);
+
+/// Arguments convertible to a typed [`AttributeValue`].
+pub trait ArgAsValue {
+    fn arg_attribute(&self) -> AttributeValue;
+}
+
+impl<T: Clone + Into<AttributeValue>> ArgAsValue for Arg<'_, T> {
+    fn arg_attribute(&self) -> AttributeValue {
+        self.0.clone().into()
+    }
+}
+
+/// Fallback for other arguments: their `Debug` output.
+pub trait ArgAsDebug {
+    fn arg_attribute(&self) -> AttributeValue;
+}
+
+impl<T: fmt::Debug + ?Sized> ArgAsDebug for &Arg<'_, T> {
+    fn arg_attribute(&self) -> AttributeValue {
+        AttributeValue::String(format!("{:?}", self.0))
+    }
+}
+
+/// Attribute value for an argument: typed for strings, integers, floats,
+/// bools and their `Vec`s, `Debug` output otherwise.
+///
+/// Dispatch happens on the concrete type at the call site; with both
+/// traits in scope, `(&Arg(&value)).arg_attribute()` picks
+/// [`ArgAsValue`] when it applies.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! arg_attribute {
+    ($value:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::obs::instrument::{ArgAsDebug as _, ArgAsValue as _};
+        (&$crate::obs::instrument::Arg(&$value)).arg_attribute()
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_calls_share_trace() {
+        let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+        let (outer_span, inner_span) = {
+            let outer = TracedCall::enter("outer", vec![("shard", arg_attribute!(3u32))])
+                .with_telemetry(telemetry.clone());
+            let inner_span = {
+                let mut inner = TracedCall::enter("inner", vec![("path", arg_attribute!("a/b"))]);
+                inner.fail(&"bad header");
+                inner.span().clone()
+            };
+            (outer.span().clone(), inner_span)
+        };
+
+        assert_eq!(inner_span.trace_id, outer_span.trace_id);
+        assert_eq!(inner_span.parent_span_id, outer_span.span_id);
+        assert_eq!(outer_span.attributes["shard"], AttributeValue::I64(3));
+        assert_eq!(inner_span.attributes["path"], "a/b");
+        assert!(ACTIVE_CALLS.with(|calls| calls.borrow().is_empty()));
+        let snapshot = telemetry.lock().unwrap().snapshot();
+        assert_eq!(snapshot.operation_stats["outer"].count, 1);
+    }
+
+    #[test]
+    fn test_arg_attribute_dispatch() {
+        assert_eq!(arg_attribute!(true), AttributeValue::Bool(true));
+        assert_eq!(arg_attribute!(String::from("x")), "x");
+        assert_eq!(arg_attribute!(std::time::Duration::from_millis(5)), "5ms");
+    }
+}
@@ -0,0 +1,171 @@
+//! Recent Log Records
+//!
+//! [`LogRingBuffer`] keeps the last N records in memory at every level,
+//! including those [`set_max_level`] filters out, so a crash dump can show
+//! what led up to a failure even when it was never written to stderr.
+//! [`logging`] feeds one process-wide buffer; see
+//! [`dump_recent`] and [`install_crash_dump`].
+//!
+//! Writers claim a slot with one atomic increment and never wait: if the
+//! slot is still held by a slower writer or a dump, the record is dropped
+//! and counted in [`LogRingBuffer::dropped`].
+//!
+//! [`set_max_level`]: crate::obs::logging::set_max_level
+//! [`logging`]: crate::obs::logging
+//! [`dump_recent`]: crate::obs::logging::dump_recent
+//! [`install_crash_dump`]: crate::obs::logging::install_crash_dump
+
+use crate::obs::tracing::EventLevel;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Records kept by the process-wide buffer unless changed with
+/// [`set_recent_capacity`](crate::obs::logging::set_recent_capacity).
+pub const DEFAULT_RECENT_CAPACITY: usize = 256;
+
+/// One remembered log record, already redacted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecentRecord {
+    pub timestamp: SystemTime,
+    pub level: EventLevel,
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+}
+
+impl fmt::Display for RecentRecord {
+    /// `<rfc3339> LEVEL message key=value ...`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:5} {}",
+            crate::obs::telemetry::rfc3339(self.timestamp),
+            self.level.as_str(),
+            self.message
+        )?;
+        for (key, value) in &self.fields {
+            write!(f, " {}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fixed-capacity ring of the most recent records.
+#[derive(Debug)]
+pub struct LogRingBuffer {
+    /// Record and its sequence number
+    slots: Vec<Mutex<Option<(u64, RecentRecord)>>>,
+    next: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl LogRingBuffer {
+    /// Buffer keeping the last `capacity` records (at least one).
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| Mutex::new(None)).collect(),
+            next: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Remember `record`, overwriting the oldest.
+    pub fn push(&self, record: RecentRecord) {
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let slot = &self.slots[(seq % self.slots.len() as u64) as usize];
+        match slot.try_lock() {
+            Ok(mut slot) => {
+                // A writer that lapped us may have stored a newer record
+                if slot.as_ref().is_none_or(|(held, _)| *held < seq) {
+                    *slot = Some((seq, record));
+                }
+            }
+            Err(std::sync::TryLockError::Poisoned(poisoned)) => {
+                *poisoned.into_inner() = Some((seq, record));
+            }
+            Err(std::sync::TryLockError::WouldBlock) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Remembered records, oldest first.
+    pub fn records(&self) -> Vec<RecentRecord> {
+        let mut records: Vec<(u64, RecentRecord)> = self
+            .slots
+            .iter()
+            .filter_map(|slot| slot.lock().unwrap_or_else(|e| e.into_inner()).clone())
+            .collect();
+        records.sort_by_key(|(seq, _)| *seq);
+        records.into_iter().map(|(_, record)| record).collect()
+    }
+
+    /// Records lost to slot contention.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Forget all records.
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            *slot.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn record(message: &str) -> RecentRecord {
+        RecentRecord {
+            timestamp: SystemTime::UNIX_EPOCH,
+            level: EventLevel::Debug,
+            message: message.to_string(),
+            fields: vec![("shard".to_string(), "3".to_string())],
+        }
+    }
+
+    #[test]
+    fn test_ring_keeps_latest_in_order() {
+        let ring = LogRingBuffer::new(3);
+        for i in 0..5 {
+            ring.push(record(&format!("m{}", i)));
+        }
+        let messages: Vec<String> = ring.records().into_iter().map(|r| r.message).collect();
+        assert_eq!(messages, ["m2", "m3", "m4"]);
+        assert_eq!(
+            record("m0").to_string(),
+            "1970-01-01T00:00:00.000Z DEBUG m0 shard=3"
+        );
+
+        ring.clear();
+        assert!(ring.records().is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let ring = Arc::new(LogRingBuffer::new(64));
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let ring = ring.clone();
+                std::thread::spawn(move || {
+                    for i in 0..1000 {
+                        ring.push(record(&format!("{}-{}", t, i)));
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        // Every slot was claimed, whichever writer won it
+        assert_eq!(ring.records().len(), 64);
+    }
+}
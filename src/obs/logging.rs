@@ -10,6 +10,8 @@
 //! - Multiple output formats (compact, pretty, JSON)
 //! - Zero-cost when disabled
 //! - Standard error and warning helpers
+//! - Records become span events inside
+//!   [`capture_logs`](crate::obs::opentelemetry::capture_logs)
 //!
 //! # Configuration
 //!
@@ -21,58 +23,664 @@
 //! - `EMBEDDENATOR_LOG_FORMAT="json"` - structured JSON output
 //! - `EMBEDDENATOR_LOG_FORMAT="pretty"` - pretty-printed output
 //! - `EMBEDDENATOR_LOG_FORMAT="compact"` - compact output (default)
+//!
+//! Also write to a file, in addition to stderr:
+//! - `EMBEDDENATOR_LOG_FILE="/var/log/embeddenator.log"` - log file path
+//! - `EMBEDDENATOR_LOG_ROTATE="daily"` - rotate at UTC midnight, or at a
+//!   size such as `"64MB"` (default), or `"never"`
+//! - `EMBEDDENATOR_LOG_KEEP="5"` - rotated files kept as `<path>.1`
+//!   (newest) to `<path>.5`
+//!
+//! Or send records to the system logger instead of stderr:
+//! - `EMBEDDENATOR_LOG_OUTPUT="journald"` - systemd journal, with fields
+//! - `EMBEDDENATOR_LOG_OUTPUT="syslog"` - local syslog (RFC 5424)
+//! - `EMBEDDENATOR_LOG_OUTPUT="stderr"` - standard error (default)
+//!
+//! See [`system_log`](crate::obs::system_log) for the formats.
+//!
+//! The file settings can come from the `[logging]` section of an
+//! [`ObservabilityConfig`](crate::obs::config::ObservabilityConfig) via
+//! [`init_with_file`], or all of them through
+//! [`ObservabilityBuilder::with_config`](crate::obs::observability::ObservabilityBuilder::with_config);
+//! the variables above then override the file.
+//!
+//! [`info_kv`], [`warn_kv`], [`error_kv`] and [`debug_kv`] take typed
+//! fields as well: nested under `fields` with the message in the json
+//! format, appended as `key=value` context to the message otherwise.
+//!
+//! [`set_max_level`] lowers verbosity at runtime on top of the filter,
+//! e.g. from a reloaded config file, and [`set_filter`] replaces the
+//! filter itself.
+//!
+//! The last [`recent_capacity`] records at every level, including those
+//! filtered out, stay in memory for post-mortem context:
+//! [`dump_recent`] writes them to stderr, and [`install_crash_dump`] does
+//! so whenever a thread panics.
+
+use crate::obs::clock::{system_clock, Clock};
+use crate::obs::log_buffer::{LogRingBuffer, RecentRecord, DEFAULT_RECENT_CAPACITY};
+use crate::obs::opentelemetry::{record_log, AttributeValue};
+use crate::obs::redaction::{redact_attributes, redact_record, redact_text, RedactedRecord};
+use crate::obs::system_log::{Journald, Syslog, SystemLog};
+use crate::obs::tracing::EventLevel;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "logging")]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+#[cfg(feature = "logging")]
+use std::sync::OnceLock;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::UNIX_EPOCH;
+
+/// Whether [`init`] installed the json format, so typed fields are
+/// emitted for splicing instead of as message context.
+#[cfg(feature = "logging")]
+static JSON_FORMAT: AtomicBool = AtomicBool::new(false);
+
+/// Swaps the filter of the subscriber installed by [`init`].
+#[cfg(feature = "logging")]
+type FilterReload = Box<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+#[cfg(feature = "logging")]
+static FILTER_RELOAD: OnceLock<FilterReload> = OnceLock::new();
+
+/// Replace the filter of the subscriber installed by [`init`] with
+/// `directives` in `EMBEDDENATOR_LOG` syntax (e.g.
+/// `"info,embeddenator=debug"`), without a restart.
+///
+/// Fails on invalid directives, or if [`init`] did not install the
+/// global subscriber.
+#[cfg(feature = "logging")]
+pub fn set_filter(directives: &str) -> io::Result<()> {
+    let reload = FILTER_RELOAD
+        .get()
+        .ok_or_else(|| io::Error::other("no log subscriber installed by init"))?;
+    reload(directives).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// Replace the log filter; a no-op without the `logging` feature.
+#[cfg(not(feature = "logging"))]
+pub fn set_filter(_directives: &str) -> io::Result<()> {
+    Ok(())
+}
+
+/// Most verbose level emitted; everything by default.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(EventLevel::Trace as u8);
+
+/// Drop records more verbose than `level`, process-wide.
+pub fn set_max_level(level: EventLevel) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Most verbose level currently emitted.
+pub fn max_level() -> EventLevel {
+    match MAX_LEVEL.load(Ordering::Relaxed) {
+        0 => EventLevel::Error,
+        1 => EventLevel::Warn,
+        2 => EventLevel::Info,
+        3 => EventLevel::Debug,
+        _ => EventLevel::Trace,
+    }
+}
+
+/// Whether records at `level` pass [`set_max_level`].
+pub(crate) fn level_enabled(level: EventLevel) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Capacity of the recent-records buffer; 0 disables it.
+static RECENT_CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_RECENT_CAPACITY);
+/// Created on the first record.
+static RECENT: RwLock<Option<Arc<LogRingBuffer>>> = RwLock::new(None);
+
+/// Keep the last `capacity` records in memory (see [`dump_recent`]),
+/// discarding those kept so far; 0 stops keeping them.
+pub fn set_recent_capacity(capacity: usize) {
+    RECENT_CAPACITY.store(capacity, Ordering::Relaxed);
+    *RECENT.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Records kept in memory, [`DEFAULT_RECENT_CAPACITY`] by default.
+pub fn recent_capacity() -> usize {
+    RECENT_CAPACITY.load(Ordering::Relaxed)
+}
+
+/// The last records at every level, oldest first, redacted.
+pub fn recent_records() -> Vec<RecentRecord> {
+    match &*RECENT.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(buffer) => buffer.records(),
+        None => Vec::new(),
+    }
+}
+
+/// Write [`recent_records`] to stderr, for post-mortem context.
+pub fn dump_recent() {
+    let records = recent_records();
+    let mut stderr = io::stderr().lock();
+    let _ = writeln!(stderr, "--- last {} log records ---", records.len());
+    for record in &records {
+        let _ = writeln!(stderr, "{}", record);
+    }
+    let _ = writeln!(stderr, "--- end of log records ---");
+}
+
+/// Call [`dump_recent`] after the current panic hook whenever a thread
+/// panics. Installs once; later calls do nothing.
+pub fn install_crash_dump() {
+    static INSTALLED: std::sync::Once = std::sync::Once::new();
+    INSTALLED.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            previous(info);
+            dump_recent();
+        }));
+    });
+}
+
+/// Whether records are kept even when filtered out.
+pub(crate) fn remembering() -> bool {
+    RECENT_CAPACITY.load(Ordering::Relaxed) > 0
+}
+
+/// Keep a redacted record in the recent-records buffer.
+pub(crate) fn remember(level: EventLevel, message: &str, fields: &[(&str, &str)]) {
+    let capacity = RECENT_CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return;
+    }
+    let buffer = RECENT.read().unwrap_or_else(|e| e.into_inner()).clone();
+    let buffer = match buffer {
+        Some(buffer) => buffer,
+        None => RECENT
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(|| Arc::new(LogRingBuffer::new(capacity)))
+            .clone(),
+    };
+    buffer.push(RecentRecord {
+        timestamp: system_clock().system_time(),
+        level,
+        message: message.to_string(),
+        fields: fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    });
+}
+
+/// Redact a record and remember it; `None` if `level` is filtered out.
+pub(crate) fn admit<'a>(
+    level: EventLevel,
+    message: &'a str,
+    fields: &[(&'a str, &'a str)],
+) -> Option<RedactedRecord<'a>> {
+    let enabled = level_enabled(level);
+    if !enabled && !remembering() {
+        return None;
+    }
+    let (message, fields) = redact_record(message, fields);
+    if remembering() {
+        let pairs: Vec<(&str, &str)> = fields.iter().map(|(k, v)| (*k, v.as_ref())).collect();
+        remember(level, &message, &pairs);
+    }
+    enabled.then_some((message, fields))
+}
+
+/// Connected system logger, if the output is not stderr.
+static SYSTEM_LOG: RwLock<Option<SystemLog>> = RwLock::new(None);
+
+/// Where log records go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogOutput {
+    /// Standard error, and the log file if configured
+    #[default]
+    Stderr,
+    /// systemd journal, see [`Journald`]
+    Journald,
+    /// Local syslog daemon, see [`Syslog`]
+    Syslog,
+}
+
+impl LogOutput {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogOutput::Stderr => "stderr",
+            LogOutput::Journald => "journald",
+            LogOutput::Syslog => "syslog",
+        }
+    }
+
+    /// Output named `name`, case-insensitive.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "stderr" => Some(LogOutput::Stderr),
+            "journald" | "journal" => Some(LogOutput::Journald),
+            "syslog" => Some(LogOutput::Syslog),
+            _ => None,
+        }
+    }
+
+    /// Output from `EMBEDDENATOR_LOG_OUTPUT`; stderr if unset or unknown.
+    pub fn from_env() -> Self {
+        std::env::var("EMBEDDENATOR_LOG_OUTPUT")
+            .ok()
+            .and_then(|v| Self::parse(&v))
+            .unwrap_or_default()
+    }
+}
+
+/// Send records to `output` from now on.
+///
+/// Connects to the system logger for journald and syslog; on failure the
+/// previous output stays in effect. Records go to the system logger with
+/// their fields as they pass [`set_max_level`]; `EMBEDDENATOR_LOG` only
+/// filters what [`init`] writes through `tracing`.
+pub fn set_output(output: LogOutput) -> io::Result<()> {
+    let log = match output {
+        LogOutput::Stderr => None,
+        LogOutput::Journald => Some(SystemLog::Journald(Journald::connect()?)),
+        LogOutput::Syslog => Some(SystemLog::Syslog(Syslog::connect()?)),
+    };
+    *SYSTEM_LOG.write().unwrap_or_else(|e| e.into_inner()) = log;
+    Ok(())
+}
+
+/// Current output.
+pub fn output() -> LogOutput {
+    match &*SYSTEM_LOG.read().unwrap_or_else(|e| e.into_inner()) {
+        None => LogOutput::Stderr,
+        Some(SystemLog::Journald(_)) => LogOutput::Journald,
+        Some(SystemLog::Syslog(_)) => LogOutput::Syslog,
+    }
+}
+
+/// Send a record to the system logger; false if the output is stderr or
+/// sending failed, so the caller falls back to stderr.
+pub(crate) fn forward(level: EventLevel, message: &str, fields: &[(&str, &str)]) -> bool {
+    match &*SYSTEM_LOG.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(log) => log.send(level, message, fields).is_ok(),
+        None => false,
+    }
+}
+
+/// When a [`LogFile`] starts a new file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// Never; the file grows without bound
+    Never,
+    /// Before a write would take the file past this many bytes
+    Size(u64),
+    /// On the first write after UTC midnight
+    Daily,
+}
+
+impl Rotation {
+    /// Parse `never`, `daily` or a size: bytes, or with a `KB`, `MB` or
+    /// `GB` suffix (powers of 1024).
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "never" => Some(Rotation::Never),
+            "daily" => Some(Rotation::Daily),
+            size => {
+                let split = size
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(size.len());
+                let amount: u64 = size[..split].parse().ok()?;
+                let unit = match size[split..].trim() {
+                    "" | "b" => 1,
+                    "k" | "kb" | "kib" => 1 << 10,
+                    "m" | "mb" | "mib" => 1 << 20,
+                    "g" | "gb" | "gib" => 1 << 30,
+                    _ => return None,
+                };
+                (amount > 0).then(|| Rotation::Size(amount.saturating_mul(unit)))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Rotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rotation::Never => write!(f, "never"),
+            Rotation::Daily => write!(f, "daily"),
+            Rotation::Size(bytes) => write!(f, "{}", bytes),
+        }
+    }
+}
+
+/// Log file settings: path, rotation and how many rotated files to keep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub rotation: Rotation,
+    /// Rotated files kept as `<path>.1` (newest) to `<path>.<keep>`
+    pub keep: usize,
+}
+
+impl LogFile {
+    /// Log to `path`, rotating at 64 MiB and keeping 5 old files.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            rotation: Rotation::Size(64 << 20),
+            keep: 5,
+        }
+    }
+
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn keep(mut self, keep: usize) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    /// Settings from `EMBEDDENATOR_LOG_FILE`, `EMBEDDENATOR_LOG_ROTATE` and
+    /// `EMBEDDENATOR_LOG_KEEP`; `None` without a path. Unparseable rotation
+    /// or keep values fall back to the defaults.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("EMBEDDENATOR_LOG_FILE").ok()?;
+        if path.is_empty() {
+            return None;
+        }
+        let mut file = Self::new(path);
+        if let Some(rotation) = std::env::var("EMBEDDENATOR_LOG_ROTATE")
+            .ok()
+            .and_then(|v| Rotation::parse(&v))
+        {
+            file.rotation = rotation;
+        }
+        if let Some(keep) = std::env::var("EMBEDDENATOR_LOG_KEEP")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+        {
+            file.keep = keep;
+        }
+        Some(file)
+    }
+
+    /// Open (appending) the file for writing.
+    pub fn open(&self) -> io::Result<RotatingFile> {
+        RotatingFile::open(self.clone(), system_clock())
+    }
+}
+
+/// Appending file writer that rotates per its [`LogFile`] settings.
+///
+/// Each `write` goes to one file whole, so records written with a single
+/// `write_all` are never split across a rotation. Rotation renames
+/// `<path>.N` to `<path>.N+1`, dropping files past `keep`, then moves the
+/// active file to `<path>.1`.
+pub struct RotatingFile {
+    settings: LogFile,
+    clock: Arc<dyn Clock>,
+    state: Mutex<ActiveFile>,
+}
+
+struct ActiveFile {
+    /// Closed during rotation (files cannot be renamed while open on
+    /// Windows), and reopened by the next write if reopening failed
+    file: Option<File>,
+    size: u64,
+    /// UTC day (days since the epoch) the file was started
+    day: u64,
+}
+
+impl RotatingFile {
+    /// Open `settings.path` for appending, reading time from `clock`.
+    pub fn open(settings: LogFile, clock: Arc<dyn Clock>) -> io::Result<Self> {
+        if let Some(dir) = settings.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = open_append(&settings.path)?;
+        let metadata = file.metadata()?;
+        // An existing file belongs to the day it was last written
+        let day = metadata
+            .modified()
+            .map(utc_day)
+            .unwrap_or_else(|_| utc_day(clock.system_time()));
+        Ok(Self {
+            state: Mutex::new(ActiveFile {
+                file: Some(file),
+                size: metadata.len(),
+                day,
+            }),
+            settings,
+            clock,
+        })
+    }
+
+    /// Path of the active file.
+    pub fn path(&self) -> &Path {
+        &self.settings.path
+    }
+
+    fn rotate(&self, active: &mut ActiveFile, today: u64) -> io::Result<()> {
+        if let Some(mut file) = active.file.take() {
+            file.flush()?;
+        }
+        let path = &self.settings.path;
+        let rotated = |n: usize| {
+            let mut name = path.as_os_str().to_owned();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+        if self.settings.keep == 0 {
+            fs::remove_file(path)?;
+        } else {
+            fs::remove_file(rotated(self.settings.keep)).ok();
+            for n in (1..self.settings.keep).rev() {
+                fs::rename(rotated(n), rotated(n + 1)).ok();
+            }
+            fs::rename(path, rotated(1))?;
+        }
+        active.size = 0;
+        active.day = today;
+        active.file = Some(open_append(path)?);
+        Ok(())
+    }
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut active = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let today = utc_day(self.clock.system_time());
+        let due = match self.settings.rotation {
+            Rotation::Never => false,
+            Rotation::Size(limit) => active.size > 0 && active.size + buf.len() as u64 > limit,
+            Rotation::Daily => today != active.day,
+        };
+        if due {
+            self.rotate(&mut active, today)?;
+        }
+        let mut file = match active.file.take() {
+            Some(file) => file,
+            None => open_append(&self.settings.path)?,
+        };
+        file.write_all(buf)?;
+        active.file = Some(file);
+        active.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut active = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match active.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn utc_day(time: std::time::SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86_400)
+        .unwrap_or(0)
+}
+
+/// Writer for one record: stderr unless a system logger takes its
+/// place, plus the log file if any.
+#[cfg(feature = "logging")]
+struct TeeWriter {
+    stderr: bool,
+    file: Option<Arc<RotatingFile>>,
+}
 
 #[cfg(feature = "logging")]
-use std::io;
+impl Write for TeeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.stderr {
+            let _ = io::stderr().write_all(buf);
+        }
+        if let Some(file) = &self.file {
+            (&**file).write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.stderr {
+            let _ = io::stderr().flush();
+        }
+        match &self.file {
+            Some(file) => (&**file).flush(),
+            None => Ok(()),
+        }
+    }
+}
 
 /// Initialize structured logging.
 ///
 /// Behavior:
 /// - With `--features logging`: installs a `tracing_subscriber` configured by
-///   `EMBEDDENATOR_LOG` or `RUST_LOG`.
-/// - Without the feature: no-op.
+///   `EMBEDDENATOR_LOG` or `RUST_LOG`, writing to stderr and to the file
+///   named by `EMBEDDENATOR_LOG_FILE`, if set.
+/// - `EMBEDDENATOR_LOG_OUTPUT` selects journald or syslog in place of
+///   stderr (see [`set_output`]), with or without the feature.
+/// - Without the feature: otherwise a no-op.
 ///
-/// By default (no env var), logging is disabled.
-#[cfg(feature = "logging")]
+/// By default (no env var), logging is disabled. A log file or system
+/// logger that cannot be opened is reported on stderr and logging
+/// continues to stderr.
 pub fn init() {
-    use tracing_subscriber::fmt;
+    let output = LogOutput::from_env();
+    if let Err(e) = set_output(output) {
+        eprintln!(
+            "WARN: {} unavailable, logging to stderr: {}",
+            output.as_str(),
+            e
+        );
+    }
+    if let Err(e) = init_with_file(LogFile::from_env()) {
+        eprintln!("WARN: log file unavailable, logging to stderr only: {}", e);
+    }
+}
+
+/// Like [`init`], with the log file given explicitly (e.g.
+/// `LogFile::from_env().or(config.log_file())`) instead of read from the
+/// environment.
+///
+/// If the file cannot be opened the subscriber is still installed for
+/// stderr and the error returned. Under a system logger ([`set_output`],
+/// called first) formatted output goes to the file only, and `tracing`
+/// events from other crates are forwarded through
+/// [`SystemLogLayer`](crate::obs::system_log::SystemLogLayer).
+pub fn init_with_file(log_file: Option<LogFile>) -> io::Result<()> {
+    init_subscriber(log_file, "off", "compact")
+}
+
+/// [`init_with_file`] with the filter used when neither
+/// `EMBEDDENATOR_LOG` nor `RUST_LOG` is set, and the format used when
+/// `EMBEDDENATOR_LOG_FORMAT` is not.
+#[cfg(feature = "logging")]
+pub(crate) fn init_subscriber(
+    log_file: Option<LogFile>,
+    default_filter: &str,
+    default_format: &str,
+) -> io::Result<()> {
+    use crate::obs::system_log::SystemLogLayer;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
 
     let filter = std::env::var("EMBEDDENATOR_LOG")
         .ok()
         .or_else(|| std::env::var("RUST_LOG").ok())
-        .unwrap_or_else(|| "off".to_string());
+        .unwrap_or_else(|| default_filter.to_string());
 
     let format = std::env::var("EMBEDDENATOR_LOG_FORMAT")
         .ok()
-        .unwrap_or_else(|| "compact".to_string());
+        .unwrap_or_else(|| default_format.to_string());
 
-    match format.as_str() {
+    let (file, result) = match log_file.map(|settings| settings.open()).transpose() {
+        Ok(file) => (file.map(Arc::new), Ok(())),
+        Err(e) => (None, Err(e)),
+    };
+    let system_log = output() != LogOutput::Stderr;
+    let stderr = !system_log;
+    let formatted = stderr || file.is_some();
+    // No color escapes in files
+    let ansi = file.is_none();
+    let writer = move || TeeWriter {
+        stderr,
+        file: file.clone(),
+    };
+
+    let fmt_layer: Box<dyn Layer<Registry> + Send + Sync> = match format.as_str() {
         "json" => {
-            let _ = fmt()
+            JSON_FORMAT.store(true, Ordering::Relaxed);
+            fmt::layer()
                 .json()
-                .with_env_filter(filter)
-                .with_writer(io::stderr)
-                .try_init();
-        }
-        "pretty" => {
-            let _ = fmt()
-                .pretty()
-                .with_env_filter(filter)
-                .with_writer(io::stderr)
-                .try_init();
-        }
-        _ => {
-            let _ = fmt()
-                .compact()
-                .with_env_filter(filter)
-                .with_writer(io::stderr)
-                .try_init();
+                .map_event_format(|format| Redacted(SplicedFields(format)))
+                .with_writer(writer)
+                .boxed()
         }
+        "pretty" => fmt::layer()
+            .pretty()
+            .map_event_format(Redacted)
+            .with_ansi(ansi)
+            .with_writer(writer)
+            .boxed(),
+        _ => fmt::layer()
+            .compact()
+            .map_event_format(Redacted)
+            .with_ansi(ansi)
+            .with_writer(writer)
+            .boxed(),
+    };
+    let (filter_layer, handle) = reload::Layer::new(EnvFilter::new(filter));
+    let installed = tracing_subscriber::registry()
+        .with(formatted.then_some(fmt_layer))
+        .with(system_log.then(SystemLogLayer::new))
+        .with(filter_layer)
+        .try_init()
+        .is_ok();
+    if installed {
+        let _ = FILTER_RELOAD.set(Box::new(move |directives| {
+            let filter = EnvFilter::try_new(directives).map_err(|e| e.to_string())?;
+            handle.reload(filter).map_err(|e| e.to_string())
+        }));
     }
+    result
 }
 
 #[cfg(not(feature = "logging"))]
-pub fn init() {}
+pub(crate) fn init_subscriber(
+    _log_file: Option<LogFile>,
+    _default_filter: &str,
+    _default_format: &str,
+) -> io::Result<()> {
+    Ok(())
+}
 
 /// Emit a warning in the best available way.
 ///
@@ -81,45 +689,318 @@ pub fn init() {}
 /// become structured `tracing` events.
 #[cfg(feature = "logging")]
 pub fn warn(message: &str) {
+    let Some((message, _)) = admit(EventLevel::Warn, message, &[]) else {
+        return;
+    };
+    let message = &*message;
+    record_log("WARN", message, &[]);
+    forward(EventLevel::Warn, message, &[]);
     tracing::warn!(message = %message);
 }
 
 #[cfg(not(feature = "logging"))]
 pub fn warn(message: &str) {
-    eprintln!("WARN: {}", message);
+    let Some((message, _)) = admit(EventLevel::Warn, message, &[]) else {
+        return;
+    };
+    let message = &*message;
+    record_log("WARN", message, &[]);
+    if !forward(EventLevel::Warn, message, &[]) {
+        eprintln!("WARN: {}", message);
+    }
 }
 
 /// Emit an error message.
 #[cfg(feature = "logging")]
 pub fn error(message: &str) {
+    let Some((message, _)) = admit(EventLevel::Error, message, &[]) else {
+        return;
+    };
+    let message = &*message;
+    record_log("ERROR", message, &[]);
+    forward(EventLevel::Error, message, &[]);
     tracing::error!(message = %message);
 }
 
 #[cfg(not(feature = "logging"))]
 pub fn error(message: &str) {
-    eprintln!("ERROR: {}", message);
+    let Some((message, _)) = admit(EventLevel::Error, message, &[]) else {
+        return;
+    };
+    let message = &*message;
+    record_log("ERROR", message, &[]);
+    if !forward(EventLevel::Error, message, &[]) {
+        eprintln!("ERROR: {}", message);
+    }
 }
 
 /// Emit an info message.
 #[cfg(feature = "logging")]
 pub fn info(message: &str) {
+    let Some((message, _)) = admit(EventLevel::Info, message, &[]) else {
+        return;
+    };
+    let message = &*message;
+    record_log("INFO", message, &[]);
+    forward(EventLevel::Info, message, &[]);
     tracing::info!(message = %message);
 }
 
 #[cfg(not(feature = "logging"))]
-pub fn info(_message: &str) {
-    // No-op without logging feature
+pub fn info(message: &str) {
+    let Some((message, _)) = admit(EventLevel::Info, message, &[]) else {
+        return;
+    };
+    let message = &*message;
+    record_log("INFO", message, &[]);
+    forward(EventLevel::Info, message, &[]);
 }
 
 /// Emit a debug message.
 #[cfg(feature = "logging")]
 pub fn debug(message: &str) {
+    let Some((message, _)) = admit(EventLevel::Debug, message, &[]) else {
+        return;
+    };
+    let message = &*message;
+    record_log("DEBUG", message, &[]);
+    forward(EventLevel::Debug, message, &[]);
     tracing::debug!(message = %message);
 }
 
 #[cfg(not(feature = "logging"))]
-pub fn debug(_message: &str) {
-    // No-op without logging feature
+pub fn debug(message: &str) {
+    let Some((message, _)) = admit(EventLevel::Debug, message, &[]) else {
+        return;
+    };
+    let message = &*message;
+    record_log("DEBUG", message, &[]);
+    forward(EventLevel::Debug, message, &[]);
+}
+
+/// Typed fields of one record, formatted as `key=value` context
+/// (`Display`) or as `"key":value` JSON members (`Debug`, spliced into
+/// the json format's `fields` by [`SplicedFields`]).
+struct KvFields<'a>(&'a [(&'a str, AttributeValue)]);
+
+impl std::fmt::Display for KvFields<'_> {
+    /// logfmt style: strings are quoted when empty or containing spaces,
+    /// quotes or `=`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (key, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            match value {
+                AttributeValue::String(text)
+                    if text.is_empty()
+                        || text.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') =>
+                {
+                    write!(f, "{}={:?}", key, text)?
+                }
+                value => write!(f, "{}={}", key, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for KvFields<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (key, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(
+                f,
+                "\"{}\":{}",
+                crate::obs::telemetry::escape_json(key),
+                value.to_json()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// json event format that replaces the string-valued `kv` field of
+/// [`log_kv`] records with the typed members it encodes.
+#[cfg(feature = "logging")]
+struct SplicedFields<F>(F);
+
+#[cfg(feature = "logging")]
+impl<S, N, F> tracing_subscriber::fmt::FormatEvent<S, N> for SplicedFields<F>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    N: for<'a> tracing_subscriber::fmt::FormatFields<'a> + 'static,
+    F: tracing_subscriber::fmt::FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &tracing_subscriber::fmt::FmtContext<'_, S, N>,
+        mut writer: tracing_subscriber::fmt::format::Writer<'_>,
+        event: &tracing::Event<'_>,
+    ) -> std::fmt::Result {
+        struct Members(Option<String>);
+        impl tracing::field::Visit for Members {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "kv" {
+                    self.0 = Some(format!("{:?}", value));
+                }
+            }
+        }
+
+        if event.metadata().target() != module_path!() {
+            return self.0.format_event(ctx, writer, event);
+        }
+        let mut members = Members(None);
+        event.record(&mut members);
+        let Some(members) = members.0 else {
+            return self.0.format_event(ctx, writer, event);
+        };
+        let mut line = String::new();
+        self.0.format_event(
+            ctx,
+            tracing_subscriber::fmt::format::Writer::new(&mut line),
+            event,
+        )?;
+        writer.write_str(&splice_members(&line, &members))
+    }
+}
+
+/// Event format that applies the installed redactor's patterns to each
+/// formatted line, covering `tracing` events from other crates.
+#[cfg(feature = "logging")]
+struct Redacted<F>(F);
+
+#[cfg(feature = "logging")]
+impl<S, N, F> tracing_subscriber::fmt::FormatEvent<S, N> for Redacted<F>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    N: for<'a> tracing_subscriber::fmt::FormatFields<'a> + 'static,
+    F: tracing_subscriber::fmt::FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &tracing_subscriber::fmt::FmtContext<'_, S, N>,
+        mut writer: tracing_subscriber::fmt::format::Writer<'_>,
+        event: &tracing::Event<'_>,
+    ) -> std::fmt::Result {
+        if !crate::obs::redaction::active() {
+            return self.0.format_event(ctx, writer, event);
+        }
+        let mut line = String::new();
+        self.0.format_event(
+            ctx,
+            tracing_subscriber::fmt::format::Writer::new(&mut line),
+            event,
+        )?;
+        writer.write_str(&redact_text(&line))
+    }
+}
+
+/// Replace `,"kv":"<escaped>"` in a formatted json line with
+/// `,<members>` (or nothing if there are none).
+#[cfg(feature = "logging")]
+fn splice_members(line: &str, members: &str) -> String {
+    const FIELD: &str = ",\"kv\":\"";
+    let Some(start) = line.find(FIELD) else {
+        return line.to_string();
+    };
+    let mut end = None;
+    let mut escaped = false;
+    for (offset, c) in line[start + FIELD.len()..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => {
+                end = Some(start + FIELD.len() + offset + 1);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let Some(end) = end else {
+        return line.to_string();
+    };
+    let separator = if members.is_empty() { "" } else { "," };
+    format!("{}{}{}{}", &line[..start], separator, members, &line[end..])
+}
+
+/// Emit a record with typed fields at `level`.
+///
+/// ```rust,ignore
+/// logging::info_kv("shard rebuilt", &[("shard", 3.into()), ("path", path.into())]);
+/// // json:    {"fields":{"message":"shard rebuilt","shard":3,"path":"/data/3"},...}
+/// // compact: INFO ...: shard rebuilt shard=3 path=/data/3
+/// ```
+pub fn log_kv(level: EventLevel, message: &str, fields: &[(&str, AttributeValue)]) {
+    let enabled = level_enabled(level);
+    if !enabled && !remembering() {
+        return;
+    }
+    let message = &*redact_text(message);
+    let fields = &*redact_attributes(fields);
+    let text: Vec<String> = fields.iter().map(|(_, value)| value.to_string()).collect();
+    let pairs: Vec<(&str, &str)> = fields
+        .iter()
+        .zip(&text)
+        .map(|((key, _), value)| (*key, value.as_str()))
+        .collect();
+    remember(level, message, &pairs);
+    if !enabled {
+        return;
+    }
+    record_log(level.as_str(), message, &pairs);
+    let forwarded = forward(level, message, &pairs);
+    emit_kv(level, message, &KvFields(fields), forwarded);
+}
+
+#[cfg(feature = "logging")]
+fn emit_kv(level: EventLevel, message: &str, kv: &KvFields<'_>, _forwarded: bool) {
+    if JSON_FORMAT.load(Ordering::Relaxed) {
+        match level {
+            EventLevel::Error => tracing::error!(message = %message, kv = ?kv),
+            EventLevel::Warn => tracing::warn!(message = %message, kv = ?kv),
+            EventLevel::Info => tracing::info!(message = %message, kv = ?kv),
+            EventLevel::Debug => tracing::debug!(message = %message, kv = ?kv),
+            EventLevel::Trace => tracing::trace!(message = %message, kv = ?kv),
+        }
+    } else {
+        match level {
+            EventLevel::Error => tracing::error!("{} {}", message, kv),
+            EventLevel::Warn => tracing::warn!("{} {}", message, kv),
+            EventLevel::Info => tracing::info!("{} {}", message, kv),
+            EventLevel::Debug => tracing::debug!("{} {}", message, kv),
+            EventLevel::Trace => tracing::trace!("{} {}", message, kv),
+        }
+    }
+}
+
+#[cfg(not(feature = "logging"))]
+fn emit_kv(level: EventLevel, message: &str, kv: &KvFields<'_>, forwarded: bool) {
+    if !forwarded && matches!(level, EventLevel::Error | EventLevel::Warn) {
+        eprintln!("{}: {} {}", level.as_str(), message, kv);
+    }
+}
+
+/// [`warn`] with typed fields, see [`log_kv`].
+pub fn warn_kv(message: &str, fields: &[(&str, AttributeValue)]) {
+    log_kv(EventLevel::Warn, message, fields);
+}
+
+/// [`error`] with typed fields, see [`log_kv`].
+pub fn error_kv(message: &str, fields: &[(&str, AttributeValue)]) {
+    log_kv(EventLevel::Error, message, fields);
+}
+
+/// [`info`] with typed fields, see [`log_kv`].
+pub fn info_kv(message: &str, fields: &[(&str, AttributeValue)]) {
+    log_kv(EventLevel::Info, message, fields);
+}
+
+/// [`debug`] with typed fields, see [`log_kv`].
+pub fn debug_kv(message: &str, fields: &[(&str, AttributeValue)]) {
+    log_kv(EventLevel::Debug, message, fields);
 }
 
 #[cfg(test)]
@@ -131,6 +1012,143 @@ mod tests {
         init();
     }
 
+    fn temp_log(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "embeddenator_obs_log_{}_{}",
+            name,
+            std::process::id()
+        ));
+        fs::remove_dir_all(&dir).ok();
+        dir.join("app.log")
+    }
+
+    #[test]
+    fn test_kv_formatting() {
+        let fields = [
+            ("shard", AttributeValue::from(3)),
+            ("path", AttributeValue::from(Path::new("/data/shard 3"))),
+            ("ratio", AttributeValue::from(0.5)),
+        ];
+        let kv = KvFields(&fields);
+        assert_eq!(kv.to_string(), r#"shard=3 path="/data/shard 3" ratio=0.5"#);
+        let members = format!("{:?}", kv);
+        assert_eq!(members, r#""shard":3,"path":"/data/shard 3","ratio":0.5"#);
+
+        info_kv("kv smoke test", &fields);
+    }
+
+    #[cfg(feature = "logging")]
+    #[test]
+    fn test_json_format_nests_typed_fields() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let sink = buffer.clone();
+        let layer = tracing_subscriber::fmt::layer()
+            .json()
+            .map_event_format(SplicedFields)
+            .with_writer(move || SharedBuffer(sink.clone()));
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        JSON_FORMAT.store(true, Ordering::Relaxed);
+        tracing::subscriber::with_default(subscriber, || {
+            warn_kv(
+                "slow \"query\"",
+                &[("shard", 3.into()), ("cached", false.into())],
+            );
+        });
+        let line = String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+        assert!(
+            line.contains(r#""fields":{"message":"slow \"query\"","shard":3,"cached":false}"#),
+            "{}",
+            line
+        );
+
+        assert_eq!(
+            splice_members(r#"{"fields":{"message":"m","kv":""}}"#, ""),
+            r#"{"fields":{"message":"m"}}"#
+        );
+    }
+
+    #[cfg(feature = "logging")]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    #[cfg(feature = "logging")]
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_log_output_parse() {
+        assert_eq!(LogOutput::parse("Journald"), Some(LogOutput::Journald));
+        assert_eq!(LogOutput::parse("syslog"), Some(LogOutput::Syslog));
+        assert_eq!(LogOutput::parse("stderr"), Some(LogOutput::Stderr));
+        assert_eq!(LogOutput::parse("kafka"), None);
+        assert_eq!(LogOutput::default().as_str(), "stderr");
+    }
+
+    #[test]
+    fn test_rotation_parse() {
+        assert_eq!(Rotation::parse("daily"), Some(Rotation::Daily));
+        assert_eq!(Rotation::parse("Never"), Some(Rotation::Never));
+        assert_eq!(Rotation::parse("64MB"), Some(Rotation::Size(64 << 20)));
+        assert_eq!(Rotation::parse("512"), Some(Rotation::Size(512)));
+        assert_eq!(Rotation::parse("10 kb"), Some(Rotation::Size(10 << 10)));
+        assert_eq!(Rotation::parse("0"), None);
+        assert_eq!(Rotation::parse("weekly"), None);
+    }
+
+    #[test]
+    fn test_size_rotation_keeps_n_files() {
+        let path = temp_log("size");
+        let settings = LogFile::new(&path).rotation(Rotation::Size(10)).keep(2);
+        let file = settings.open().unwrap();
+        for record in ["one\n", "two\n", "three\n", "four\n", "five\n"] {
+            (&file).write_all(record.as_bytes()).unwrap();
+        }
+
+        // A record that would pass 10 bytes starts a new file
+        let read = |suffix: &str| {
+            let mut name = path.clone().into_os_string();
+            name.push(suffix);
+            fs::read_to_string(name).ok()
+        };
+        assert_eq!(read("").as_deref(), Some("four\nfive\n"));
+        assert_eq!(read(".1").as_deref(), Some("three\n"));
+        assert_eq!(read(".2").as_deref(), Some("one\ntwo\n"));
+        assert_eq!(read(".3"), None);
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_daily_rotation() {
+        let path = temp_log("daily");
+        let clock = Arc::new(crate::obs::clock::MockClock::new());
+        let settings = LogFile::new(&path).rotation(Rotation::Daily);
+        let file = RotatingFile::open(settings, clock.clone()).unwrap();
+
+        (&file).write_all(b"today\n").unwrap();
+        clock.advance(std::time::Duration::from_secs(86_400));
+        (&file).write_all(b"tomorrow\n").unwrap();
+        (&file).write_all(b"still tomorrow\n").unwrap();
+
+        let mut first = path.clone().into_os_string();
+        first.push(".1");
+        assert_eq!(fs::read_to_string(first).unwrap(), "today\n");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "tomorrow\nstill tomorrow\n"
+        );
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
     #[test]
     fn test_warn() {
         warn("test warning");
@@ -0,0 +1,355 @@
+//! Memory-Pressure Watchdog
+//!
+//! Watches memory usage against a limit and, when usage crosses a
+//! high-water mark, emits a critical alert and writes a flight-recorder
+//! dump — the last useful signal before the kernel OOM killer strikes.
+//!
+//! # Limits
+//!
+//! - [`MemoryLimit::Bytes`]: process RSS against a fixed byte limit
+//! - [`MemoryLimit::Cgroup`]: cgroup v2 `memory.current` against
+//!   `memory.max` of the process's own cgroup (Linux)
+//!
+//! # Dump Contents
+//!
+//! Plain-text file `memory-pressure-<pid>-<unix_secs>.txt` with usage and
+//! limit, process resource stats, the global metrics snapshot, the attached
+//! telemetry summary, and — with the `alloc-tracking` feature and
+//! [`heap_profile`](MemoryWatchdog::heap_profile) enabled — allocator
+//! counters from `alloc_tracking`.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::memory_watchdog::{MemoryLimit, MemoryWatchdog};
+//!
+//! let _handle = MemoryWatchdog::new(MemoryLimit::Cgroup)
+//!     .high_water(0.9)
+//!     .dump_dir("/var/tmp/embeddenator")
+//!     .on_pressure(|event| page_oncall(event))
+//!     .spawn(Duration::from_secs(1));
+//! ```
+
+use crate::obs::cgroup::CgroupCollector;
+use crate::obs::logging;
+use crate::obs::process::{spawn_periodic, CollectorHandle, ProcessCollector};
+use crate::obs::telemetry::Telemetry;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Memory limit the watchdog compares usage against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryLimit {
+    /// Process resident set size against a fixed limit
+    Bytes(u64),
+    /// cgroup v2 `memory.current` against `memory.max`
+    Cgroup,
+}
+
+/// Where a usage reading came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemorySource {
+    Rss,
+    Cgroup,
+}
+
+/// Usage reading at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryReading {
+    pub source: MemorySource,
+    pub usage_bytes: u64,
+    pub limit_bytes: u64,
+}
+
+impl MemoryReading {
+    /// Usage as a fraction of the limit.
+    pub fn ratio(&self) -> f64 {
+        if self.limit_bytes == 0 {
+            0.0
+        } else {
+            self.usage_bytes as f64 / self.limit_bytes as f64
+        }
+    }
+}
+
+/// Raised when usage crosses the high-water mark.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryPressureEvent {
+    pub reading: MemoryReading,
+    pub high_water: f64,
+    /// Flight-recorder dump, if one was written
+    pub dump_path: Option<PathBuf>,
+}
+
+/// Callback invoked on memory pressure.
+pub type PressureCallback = Arc<dyn Fn(&MemoryPressureEvent) + Send + Sync>;
+
+/// Memory-pressure watchdog.
+pub struct MemoryWatchdog {
+    limit: MemoryLimit,
+    high_water: f64,
+    rearm: f64,
+    dump_dir: Option<PathBuf>,
+    heap_profile: bool,
+    telemetry: Option<Arc<Mutex<Telemetry>>>,
+    callbacks: Vec<PressureCallback>,
+    /// True once fired; cleared when usage drops below the re-arm ratio
+    tripped: bool,
+}
+
+impl MemoryWatchdog {
+    /// Watchdog with a 0.9 high-water mark, re-arming below 0.8.
+    pub fn new(limit: MemoryLimit) -> Self {
+        Self {
+            limit,
+            high_water: 0.9,
+            rearm: 0.8,
+            dump_dir: None,
+            heap_profile: false,
+            telemetry: None,
+            callbacks: Vec::new(),
+            tripped: false,
+        }
+    }
+
+    /// Fraction of the limit that triggers the alert.
+    pub fn high_water(mut self, ratio: f64) -> Self {
+        self.high_water = ratio;
+        self.rearm = self.rearm.min(ratio);
+        self
+    }
+
+    /// Fraction of the limit usage must drop below before the alert can fire again.
+    pub fn rearm_below(mut self, ratio: f64) -> Self {
+        self.rearm = ratio.min(self.high_water);
+        self
+    }
+
+    /// Write flight-recorder dumps into `dir` (no dump without it).
+    pub fn dump_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dump_dir = Some(dir.into());
+        self
+    }
+
+    /// Include allocator counters in dumps (requires `alloc-tracking`).
+    pub fn heap_profile(mut self, enabled: bool) -> Self {
+        self.heap_profile = enabled;
+        self
+    }
+
+    /// Publish `memory_usage_ratio` into telemetry and include its summary in dumps.
+    pub fn with_telemetry(mut self, telemetry: Arc<Mutex<Telemetry>>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Invoke `callback` when the high-water mark is crossed.
+    pub fn on_pressure<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&MemoryPressureEvent) + Send + Sync + 'static,
+    {
+        self.callbacks.push(Arc::new(callback));
+        self
+    }
+
+    /// Read current usage against the configured limit.
+    pub fn read(&self) -> Option<MemoryReading> {
+        match self.limit {
+            MemoryLimit::Bytes(limit_bytes) => Some(MemoryReading {
+                source: MemorySource::Rss,
+                usage_bytes: ProcessCollector::new().sample().resident_memory_bytes?,
+                limit_bytes,
+            }),
+            MemoryLimit::Cgroup => cgroup_reading(),
+        }
+    }
+
+    /// Evaluate once; returns the event if the alert fired on this check.
+    pub fn check(&mut self) -> Option<MemoryPressureEvent> {
+        let reading = self.read()?;
+        let ratio = reading.ratio();
+
+        if let Some(telemetry) = &self.telemetry {
+            if let Ok(mut telemetry) = telemetry.lock() {
+                telemetry.set_gauge("memory_usage_ratio", ratio);
+            }
+        }
+
+        if self.tripped {
+            if ratio < self.rearm {
+                self.tripped = false;
+            }
+            return None;
+        }
+        if ratio < self.high_water {
+            return None;
+        }
+        self.tripped = true;
+
+        logging::error(&format!(
+            "CRITICAL: memory usage {} of {} bytes ({:.1}%) crossed high-water mark {:.1}%",
+            reading.usage_bytes,
+            reading.limit_bytes,
+            ratio * 100.0,
+            self.high_water * 100.0
+        ));
+
+        let dump_path =
+            self.dump_dir
+                .as_ref()
+                .and_then(|dir| match self.write_dump(dir, &reading) {
+                    Ok(path) => Some(path),
+                    Err(e) => {
+                        logging::warn(&format!("failed to write memory-pressure dump: {}", e));
+                        None
+                    }
+                });
+
+        let event = MemoryPressureEvent {
+            reading,
+            high_water: self.high_water,
+            dump_path,
+        };
+        for callback in &self.callbacks {
+            callback(&event);
+        }
+        Some(event)
+    }
+
+    /// Check every `interval` on a background thread.
+    pub fn spawn(mut self, interval: Duration) -> CollectorHandle {
+        spawn_periodic("obs-memory-watchdog", interval, move || {
+            self.check();
+        })
+    }
+
+    fn write_dump(
+        &self,
+        dir: &std::path::Path,
+        reading: &MemoryReading,
+    ) -> std::io::Result<PathBuf> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!(
+            "memory-pressure-{}-{}.txt",
+            std::process::id(),
+            now
+        ));
+
+        let mut dump = String::with_capacity(4096);
+        writeln!(dump, "=== Memory Pressure Dump ===").ok();
+        writeln!(dump, "pid: {}", std::process::id()).ok();
+        writeln!(dump, "timestamp_secs: {}", now).ok();
+        writeln!(dump, "source: {:?}", reading.source).ok();
+        writeln!(dump, "usage_bytes: {}", reading.usage_bytes).ok();
+        writeln!(dump, "limit_bytes: {}", reading.limit_bytes).ok();
+        writeln!(dump, "ratio: {:.4}", reading.ratio()).ok();
+        writeln!(dump, "high_water: {:.4}", self.high_water).ok();
+
+        writeln!(dump, "\n=== Process ===").ok();
+        writeln!(dump, "{:#?}", ProcessCollector::new().sample()).ok();
+
+        writeln!(dump, "\n=== Metrics ===").ok();
+        writeln!(dump, "{:#?}", crate::metrics::metrics().snapshot()).ok();
+
+        if let Some(telemetry) = &self.telemetry {
+            if let Ok(telemetry) = telemetry.lock() {
+                writeln!(dump, "\n{}", telemetry.snapshot().summary()).ok();
+            }
+        }
+
+        if self.heap_profile {
+            writeln!(dump, "\n=== Heap ===").ok();
+            #[cfg(feature = "alloc-tracking")]
+            {
+                use crate::obs::alloc_tracking;
+                if alloc_tracking::is_installed() {
+                    let stats = alloc_tracking::alloc_stats();
+                    writeln!(dump, "{:#?}", stats).ok();
+                    writeln!(dump, "live_bytes: {}", stats.live_bytes()).ok();
+                } else {
+                    writeln!(dump, "CountingAllocator not installed").ok();
+                }
+            }
+            #[cfg(not(feature = "alloc-tracking"))]
+            writeln!(dump, "unavailable (alloc-tracking feature disabled)").ok();
+        }
+
+        std::fs::write(&path, dump)?;
+        Ok(path)
+    }
+}
+
+fn cgroup_reading() -> Option<MemoryReading> {
+    let stats = CgroupCollector::detect()?.sample();
+    Some(MemoryReading {
+        source: MemorySource::Cgroup,
+        usage_bytes: stats.memory_current_bytes?,
+        // None when memory.max is "max" (unlimited)
+        limit_bytes: stats.memory_max_bytes?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_reading_ratio() {
+        let reading = MemoryReading {
+            source: MemorySource::Rss,
+            usage_bytes: 90,
+            limit_bytes: 100,
+        };
+        assert!((reading.ratio() - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_fires_once_and_writes_dump() {
+        let dir = std::env::temp_dir().join(format!("embeddenator_obs_oom_{}", std::process::id()));
+        let fired = Arc::new(AtomicUsize::new(0));
+        let fired_cb = fired.clone();
+        let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+
+        // A 1-byte limit is always exceeded
+        let mut watchdog = MemoryWatchdog::new(MemoryLimit::Bytes(1))
+            .dump_dir(&dir)
+            .heap_profile(true)
+            .with_telemetry(telemetry.clone())
+            .on_pressure(move |_| {
+                fired_cb.fetch_add(1, Ordering::SeqCst);
+            });
+
+        let event = watchdog.check().expect("alert should fire");
+        assert!(watchdog.check().is_none(), "no re-fire until re-armed");
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+
+        let dump = std::fs::read_to_string(event.dump_path.unwrap()).unwrap();
+        assert!(dump.contains("=== Memory Pressure Dump ==="));
+        assert!(dump.contains("limit_bytes: 1"));
+        assert!(dump.contains("=== Heap ==="));
+        assert!(telemetry
+            .lock()
+            .unwrap()
+            .snapshot()
+            .gauges
+            .contains_key("memory_usage_ratio"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_below_high_water_is_quiet() {
+        let mut watchdog = MemoryWatchdog::new(MemoryLimit::Bytes(u64::MAX));
+        assert!(watchdog.read().is_some());
+        assert!(watchdog.check().is_none());
+    }
+}
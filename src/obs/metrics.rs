@@ -1,6 +1,188 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
+/// Why a cache entry was evicted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EvictionReason {
+    /// Cache reached its capacity limit
+    Capacity,
+    /// Entry outlived its time-to-live
+    Ttl,
+    /// Entry was explicitly invalidated
+    Invalidation,
+    /// Entry was dropped to relieve memory pressure
+    MemoryPressure,
+}
+
+impl EvictionReason {
+    /// All reasons, in export order.
+    pub const ALL: [EvictionReason; 4] = [
+        EvictionReason::Capacity,
+        EvictionReason::Ttl,
+        EvictionReason::Invalidation,
+        EvictionReason::MemoryPressure,
+    ];
+
+    /// Label value used in exports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EvictionReason::Capacity => "capacity",
+            EvictionReason::Ttl => "ttl",
+            EvictionReason::Invalidation => "invalidation",
+            EvictionReason::MemoryPressure => "memory_pressure",
+        }
+    }
+}
+
+/// Structured workload dimensions of a retrieval or rerank call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkloadShape {
+    /// Embedding vector dimension
+    pub dim: usize,
+    /// Number of queries in the batch
+    pub batch_size: usize,
+    /// Number of results requested
+    pub k: usize,
+}
+
+impl WorkloadShape {
+    pub fn new(dim: usize, batch_size: usize, k: usize) -> Self {
+        Self { dim, batch_size, k }
+    }
+}
+
+/// Inclusive upper bounds of the vector-dimension buckets (plus overflow).
+pub const DIM_BUCKETS: [usize; 4] = [128, 384, 768, 1536];
+/// Inclusive upper bounds of the batch-size buckets (plus overflow).
+pub const BATCH_BUCKETS: [usize; 3] = [1, 8, 64];
+/// Inclusive upper bounds of the k buckets (plus overflow).
+pub const K_BUCKETS: [usize; 2] = [10, 100];
+
+#[cfg(feature = "metrics")]
+fn shape_bucket(bounds: &[usize], value: usize) -> usize {
+    bounds
+        .iter()
+        .position(|&bound| value <= bound)
+        .unwrap_or(bounds.len())
+}
+
+/// Label for bucket `idx` of `bounds` ("le_768", "gt_1536").
+pub fn shape_bucket_label(bounds: &[usize], idx: usize) -> String {
+    match bounds.get(idx) {
+        Some(bound) => format!("le_{}", bound),
+        None => format!("gt_{}", bounds.last().copied().unwrap_or(0)),
+    }
+}
+
+/// Call count and duration totals for one bucket.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DurationStats {
+    pub calls: u64,
+    pub ns_total: u64,
+    pub ns_max: u64,
+}
+
+impl DurationStats {
+    /// Mean duration in nanoseconds.
+    pub fn mean_ns(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.ns_total as f64 / self.calls as f64
+        }
+    }
+}
+
+/// Per-bucket timings for each workload dimension, aggregated independently.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShapeTimingsSnapshot {
+    pub dim: [DurationStats; DIM_BUCKETS.len() + 1],
+    pub batch_size: [DurationStats; BATCH_BUCKETS.len() + 1],
+    pub k: [DurationStats; K_BUCKETS.len() + 1],
+}
+
+impl ShapeTimingsSnapshot {
+    /// Iterate `(dimension, bucket_label, stats)` over all buckets.
+    pub fn labeled(&self) -> impl Iterator<Item = (&'static str, String, DurationStats)> + '_ {
+        let dim = self
+            .dim
+            .iter()
+            .enumerate()
+            .map(|(i, s)| ("dim", shape_bucket_label(&DIM_BUCKETS, i), *s));
+        let batch = self
+            .batch_size
+            .iter()
+            .enumerate()
+            .map(|(i, s)| ("batch_size", shape_bucket_label(&BATCH_BUCKETS, i), *s));
+        let k = self
+            .k
+            .iter()
+            .enumerate()
+            .map(|(i, s)| ("k", shape_bucket_label(&K_BUCKETS, i), *s));
+        dim.chain(batch).chain(k)
+    }
+}
+
+struct DurationCell {
+    calls: AtomicU64,
+    ns_total: AtomicU64,
+    ns_max: AtomicU64,
+}
+
+impl DurationCell {
+    const fn new() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+            ns_total: AtomicU64::new(0),
+            ns_max: AtomicU64::new(0),
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn record(&self, dur: Duration) {
+        record_duration(&self.calls, &self.ns_total, &self.ns_max, dur);
+    }
+
+    fn snapshot(&self) -> DurationStats {
+        DurationStats {
+            calls: self.calls.load(Ordering::Relaxed),
+            ns_total: self.ns_total.load(Ordering::Relaxed),
+            ns_max: self.ns_max.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct ShapeTimings {
+    dim: [DurationCell; DIM_BUCKETS.len() + 1],
+    batch_size: [DurationCell; BATCH_BUCKETS.len() + 1],
+    k: [DurationCell; K_BUCKETS.len() + 1],
+}
+
+impl ShapeTimings {
+    const fn new() -> Self {
+        Self {
+            dim: [const { DurationCell::new() }; DIM_BUCKETS.len() + 1],
+            batch_size: [const { DurationCell::new() }; BATCH_BUCKETS.len() + 1],
+            k: [const { DurationCell::new() }; K_BUCKETS.len() + 1],
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn record(&self, shape: WorkloadShape, dur: Duration) {
+        self.dim[shape_bucket(&DIM_BUCKETS, shape.dim)].record(dur);
+        self.batch_size[shape_bucket(&BATCH_BUCKETS, shape.batch_size)].record(dur);
+        self.k[shape_bucket(&K_BUCKETS, shape.k)].record(dur);
+    }
+
+    fn snapshot(&self) -> ShapeTimingsSnapshot {
+        ShapeTimingsSnapshot {
+            dim: std::array::from_fn(|i| self.dim[i].snapshot()),
+            batch_size: std::array::from_fn(|i| self.batch_size[i].snapshot()),
+            k: std::array::from_fn(|i| self.k[i].snapshot()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct MetricsSnapshot {
     pub poison_recoveries_total: u64,
@@ -14,22 +196,34 @@ pub struct MetricsSnapshot {
     pub sub_cache_hits: u64,
     pub sub_cache_misses: u64,
     pub sub_cache_evictions: u64,
+    pub sub_cache_evictions_capacity: u64,
+    pub sub_cache_evictions_ttl: u64,
+    pub sub_cache_evictions_invalidation: u64,
+    pub sub_cache_evictions_memory_pressure: u64,
 
     pub index_cache_hits: u64,
     pub index_cache_misses: u64,
     pub index_cache_evictions: u64,
+    pub index_cache_evictions_capacity: u64,
+    pub index_cache_evictions_ttl: u64,
+    pub index_cache_evictions_invalidation: u64,
+    pub index_cache_evictions_memory_pressure: u64,
 
     pub retrieval_query_calls: u64,
     pub retrieval_query_ns_total: u64,
     pub retrieval_query_ns_max: u64,
+    pub retrieval_query_by_shape: ShapeTimingsSnapshot,
 
     pub rerank_calls: u64,
     pub rerank_ns_total: u64,
     pub rerank_ns_max: u64,
+    pub rerank_by_shape: ShapeTimingsSnapshot,
 
     pub hier_query_calls: u64,
     pub hier_query_ns_total: u64,
     pub hier_query_ns_max: u64,
+
+    pub panics_total: u64,
 }
 
 pub struct Metrics {
@@ -44,22 +238,28 @@ pub struct Metrics {
     sub_cache_hits: AtomicU64,
     sub_cache_misses: AtomicU64,
     sub_cache_evictions: AtomicU64,
+    sub_cache_evictions_by_reason: [AtomicU64; 4],
 
     index_cache_hits: AtomicU64,
     index_cache_misses: AtomicU64,
     index_cache_evictions: AtomicU64,
+    index_cache_evictions_by_reason: [AtomicU64; 4],
 
     retrieval_query_calls: AtomicU64,
     retrieval_query_ns_total: AtomicU64,
     retrieval_query_ns_max: AtomicU64,
+    retrieval_query_by_shape: ShapeTimings,
 
     rerank_calls: AtomicU64,
     rerank_ns_total: AtomicU64,
     rerank_ns_max: AtomicU64,
+    rerank_by_shape: ShapeTimings,
 
     hier_query_calls: AtomicU64,
     hier_query_ns_total: AtomicU64,
     hier_query_ns_max: AtomicU64,
+
+    panics_total: AtomicU64,
 }
 
 impl Default for Metrics {
@@ -82,22 +282,28 @@ impl Metrics {
             sub_cache_hits: AtomicU64::new(0),
             sub_cache_misses: AtomicU64::new(0),
             sub_cache_evictions: AtomicU64::new(0),
+            sub_cache_evictions_by_reason: [const { AtomicU64::new(0) }; 4],
 
             index_cache_hits: AtomicU64::new(0),
             index_cache_misses: AtomicU64::new(0),
             index_cache_evictions: AtomicU64::new(0),
+            index_cache_evictions_by_reason: [const { AtomicU64::new(0) }; 4],
 
             retrieval_query_calls: AtomicU64::new(0),
             retrieval_query_ns_total: AtomicU64::new(0),
             retrieval_query_ns_max: AtomicU64::new(0),
+            retrieval_query_by_shape: ShapeTimings::new(),
 
             rerank_calls: AtomicU64::new(0),
             rerank_ns_total: AtomicU64::new(0),
             rerank_ns_max: AtomicU64::new(0),
+            rerank_by_shape: ShapeTimings::new(),
 
             hier_query_calls: AtomicU64::new(0),
             hier_query_ns_total: AtomicU64::new(0),
             hier_query_ns_max: AtomicU64::new(0),
+
+            panics_total: AtomicU64::new(0),
         }
     }
 
@@ -114,22 +320,41 @@ impl Metrics {
             sub_cache_hits: self.sub_cache_hits.load(Ordering::Relaxed),
             sub_cache_misses: self.sub_cache_misses.load(Ordering::Relaxed),
             sub_cache_evictions: self.sub_cache_evictions.load(Ordering::Relaxed),
+            sub_cache_evictions_capacity: self.sub_cache_evictions_by_reason[0]
+                .load(Ordering::Relaxed),
+            sub_cache_evictions_ttl: self.sub_cache_evictions_by_reason[1].load(Ordering::Relaxed),
+            sub_cache_evictions_invalidation: self.sub_cache_evictions_by_reason[2]
+                .load(Ordering::Relaxed),
+            sub_cache_evictions_memory_pressure: self.sub_cache_evictions_by_reason[3]
+                .load(Ordering::Relaxed),
 
             index_cache_hits: self.index_cache_hits.load(Ordering::Relaxed),
             index_cache_misses: self.index_cache_misses.load(Ordering::Relaxed),
             index_cache_evictions: self.index_cache_evictions.load(Ordering::Relaxed),
+            index_cache_evictions_capacity: self.index_cache_evictions_by_reason[0]
+                .load(Ordering::Relaxed),
+            index_cache_evictions_ttl: self.index_cache_evictions_by_reason[1]
+                .load(Ordering::Relaxed),
+            index_cache_evictions_invalidation: self.index_cache_evictions_by_reason[2]
+                .load(Ordering::Relaxed),
+            index_cache_evictions_memory_pressure: self.index_cache_evictions_by_reason[3]
+                .load(Ordering::Relaxed),
 
             retrieval_query_calls: self.retrieval_query_calls.load(Ordering::Relaxed),
             retrieval_query_ns_total: self.retrieval_query_ns_total.load(Ordering::Relaxed),
             retrieval_query_ns_max: self.retrieval_query_ns_max.load(Ordering::Relaxed),
+            retrieval_query_by_shape: self.retrieval_query_by_shape.snapshot(),
 
             rerank_calls: self.rerank_calls.load(Ordering::Relaxed),
             rerank_ns_total: self.rerank_ns_total.load(Ordering::Relaxed),
             rerank_ns_max: self.rerank_ns_max.load(Ordering::Relaxed),
+            rerank_by_shape: self.rerank_by_shape.snapshot(),
 
             hier_query_calls: self.hier_query_calls.load(Ordering::Relaxed),
             hier_query_ns_total: self.hier_query_ns_total.load(Ordering::Relaxed),
             hier_query_ns_max: self.hier_query_ns_max.load(Ordering::Relaxed),
+
+            panics_total: self.panics_total.load(Ordering::Relaxed),
         }
     }
 
@@ -194,6 +419,17 @@ impl Metrics {
         }
     }
 
+    /// Record a sub-cache eviction attributed to `reason`.
+    ///
+    /// Also increments the unlabeled `sub_cache_evictions` total.
+    pub fn inc_sub_cache_eviction_for(&self, _reason: EvictionReason) {
+        #[cfg(feature = "metrics")]
+        {
+            self.sub_cache_evictions.fetch_add(1, Ordering::Relaxed);
+            self.sub_cache_evictions_by_reason[_reason as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
     pub fn inc_index_cache_hit(&self) {
         #[cfg(feature = "metrics")]
         {
@@ -215,6 +451,17 @@ impl Metrics {
         }
     }
 
+    /// Record an index-cache eviction attributed to `reason`.
+    ///
+    /// Also increments the unlabeled `index_cache_evictions` total.
+    pub fn inc_index_cache_eviction_for(&self, _reason: EvictionReason) {
+        #[cfg(feature = "metrics")]
+        {
+            self.index_cache_evictions.fetch_add(1, Ordering::Relaxed);
+            self.index_cache_evictions_by_reason[_reason as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
     pub fn record_retrieval_query(&self, _dur: Duration) {
         #[cfg(feature = "metrics")]
         {
@@ -239,6 +486,37 @@ impl Metrics {
         }
     }
 
+    /// Record a retrieval query along with its workload shape.
+    ///
+    /// Also updates the unlabeled retrieval query totals.
+    pub fn record_retrieval_query_shaped(&self, _dur: Duration, _shape: WorkloadShape) {
+        #[cfg(feature = "metrics")]
+        {
+            self.record_retrieval_query(_dur);
+            self.retrieval_query_by_shape.record(_shape, _dur);
+        }
+    }
+
+    /// Record a rerank pass along with its workload shape.
+    ///
+    /// Also updates the unlabeled rerank totals.
+    pub fn record_rerank_shaped(&self, _dur: Duration, _shape: WorkloadShape) {
+        #[cfg(feature = "metrics")]
+        {
+            self.record_rerank(_dur);
+            self.rerank_by_shape.record(_shape, _dur);
+        }
+    }
+
+    /// Count a panic, see
+    /// [`install_panic_hook`](crate::obs::panic_hook::install_panic_hook).
+    pub fn inc_panics(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            self.panics_total.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
     pub fn record_hier_query(&self, _dur: Duration) {
         #[cfg(feature = "metrics")]
         {
@@ -267,6 +545,28 @@ fn record_duration(calls: &AtomicU64, total_ns: &AtomicU64, max_ns: &AtomicU64,
     }
 }
 
+impl MetricsSnapshot {
+    /// Sub-cache evictions attributed to `reason`.
+    pub fn sub_cache_evictions_by_reason(&self, reason: EvictionReason) -> u64 {
+        match reason {
+            EvictionReason::Capacity => self.sub_cache_evictions_capacity,
+            EvictionReason::Ttl => self.sub_cache_evictions_ttl,
+            EvictionReason::Invalidation => self.sub_cache_evictions_invalidation,
+            EvictionReason::MemoryPressure => self.sub_cache_evictions_memory_pressure,
+        }
+    }
+
+    /// Index-cache evictions attributed to `reason`.
+    pub fn index_cache_evictions_by_reason(&self, reason: EvictionReason) -> u64 {
+        match reason {
+            EvictionReason::Capacity => self.index_cache_evictions_capacity,
+            EvictionReason::Ttl => self.index_cache_evictions_ttl,
+            EvictionReason::Invalidation => self.index_cache_evictions_invalidation,
+            EvictionReason::MemoryPressure => self.index_cache_evictions_memory_pressure,
+        }
+    }
+}
+
 static METRICS: Metrics = Metrics::new();
 
 pub fn metrics() -> &'static Metrics {
@@ -302,4 +602,86 @@ mod tests {
             assert_eq!(after, before);
         }
     }
+
+    #[test]
+    fn shaped_timings_land_in_buckets() {
+        let m = Metrics::new();
+
+        m.record_retrieval_query_shaped(Duration::from_micros(10), WorkloadShape::new(768, 1, 10));
+        m.record_retrieval_query_shaped(
+            Duration::from_micros(30),
+            WorkloadShape::new(4096, 32, 50),
+        );
+        m.record_rerank_shaped(Duration::from_micros(5), WorkloadShape::new(128, 100, 1000));
+
+        let snap = m.snapshot();
+
+        #[cfg(feature = "metrics")]
+        {
+            assert_eq!(snap.retrieval_query_calls, 2);
+            let by_shape = snap.retrieval_query_by_shape;
+            assert_eq!(by_shape.dim[2].calls, 1); // le_768
+            assert_eq!(by_shape.dim[4].calls, 1); // gt_1536
+            assert_eq!(by_shape.dim[4].ns_max, 30_000);
+            assert_eq!(by_shape.batch_size[0].calls, 1); // le_1
+            assert_eq!(by_shape.batch_size[2].calls, 1); // le_64
+            assert_eq!(by_shape.k[0].calls, 1);
+            assert_eq!(by_shape.k[1].calls, 1);
+
+            assert_eq!(snap.rerank_calls, 1);
+            assert_eq!(snap.rerank_by_shape.k[2].calls, 1); // gt_100
+            assert_eq!(snap.rerank_by_shape.batch_size[3].calls, 1); // gt_64
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        {
+            assert_eq!(snap, MetricsSnapshot::default());
+        }
+    }
+
+    #[test]
+    fn shape_bucket_labels() {
+        assert_eq!(shape_bucket_label(&DIM_BUCKETS, 0), "le_128");
+        assert_eq!(shape_bucket_label(&DIM_BUCKETS, 4), "gt_1536");
+
+        let labels: Vec<_> = ShapeTimingsSnapshot::default()
+            .labeled()
+            .map(|(dimension, bucket, _)| format!("{}:{}", dimension, bucket))
+            .collect();
+        assert_eq!(labels.len(), 12);
+        assert!(labels.contains(&"batch_size:gt_64".to_string()));
+        assert!(labels.contains(&"k:le_10".to_string()));
+    }
+
+    #[test]
+    fn eviction_reasons_are_attributed() {
+        let m = Metrics::new();
+
+        m.inc_sub_cache_eviction_for(EvictionReason::Ttl);
+        m.inc_sub_cache_eviction_for(EvictionReason::Ttl);
+        m.inc_index_cache_eviction_for(EvictionReason::MemoryPressure);
+        m.inc_index_cache_eviction();
+
+        let snap = m.snapshot();
+
+        #[cfg(feature = "metrics")]
+        {
+            assert_eq!(snap.sub_cache_evictions, 2);
+            assert_eq!(snap.sub_cache_evictions_by_reason(EvictionReason::Ttl), 2);
+            assert_eq!(
+                snap.sub_cache_evictions_by_reason(EvictionReason::Capacity),
+                0
+            );
+            assert_eq!(snap.index_cache_evictions, 2);
+            assert_eq!(
+                snap.index_cache_evictions_by_reason(EvictionReason::MemoryPressure),
+                1
+            );
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        {
+            assert_eq!(snap, MetricsSnapshot::default());
+        }
+    }
 }
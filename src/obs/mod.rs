@@ -1,19 +1,142 @@
+pub mod alert_config;
+pub mod alert_rules;
+#[cfg(feature = "alloc-tracking")]
+pub mod alloc_tracking;
+pub mod anomaly;
+pub mod cgroup;
+pub mod chrome_trace;
+pub mod clock;
+pub mod comparison;
+pub mod concurrency;
+pub mod config;
+pub mod crash_counters;
+pub mod criterion;
+pub mod digest;
+pub mod disk_watcher;
+pub mod error_tracking;
+pub mod grafana;
+pub mod handshake;
+pub mod health;
 pub mod hires_timing;
+pub mod history;
+pub mod host;
+pub(crate) mod http;
+pub mod index_build;
+pub mod instrument;
+pub mod log_buffer;
 pub mod logging;
+pub mod memory_watchdog;
 pub mod metrics;
+pub mod notifier;
+#[cfg(feature = "tracing")]
+pub mod obs_layer;
+pub mod observability;
 pub mod opentelemetry;
+pub mod panic_hook;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod pressure;
+pub mod privacy;
+pub mod process;
 pub mod prometheus;
+pub mod propagation;
+pub mod quality;
+pub mod query_socket;
+pub mod reachability;
+pub mod redaction;
+pub mod registry;
+#[cfg(feature = "remote-write")]
+pub mod remote_write;
+pub mod resource;
+pub mod sampling;
+pub mod self_benchmark;
+pub mod simulate;
+pub mod soak;
+pub mod span_processor;
+#[cfg(feature = "sqlite-store")]
+pub mod sqlite_store;
+pub mod sse;
+pub mod statsd;
 pub mod streaming;
+pub mod system_log;
 pub mod telemetry;
 pub mod test_metrics;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 pub mod tracing;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod watchdog;
+#[cfg(feature = "ws-streaming")]
+pub mod ws_streaming;
+pub mod zipkin;
 
+pub use alert_config::*;
+pub use alert_rules::*;
+pub use anomaly::*;
+pub use cgroup::*;
+pub use chrome_trace::*;
+pub use clock::*;
+pub use comparison::*;
+pub use concurrency::*;
+pub use config::*;
+pub use crash_counters::*;
+pub use criterion::*;
+pub use digest::*;
+pub use disk_watcher::*;
+pub use error_tracking::*;
+pub use grafana::*;
+pub use handshake::*;
+pub use health::*;
 pub use hires_timing::*;
+pub use history::*;
+pub use host::*;
+pub use index_build::*;
+pub use instrument::*;
+pub use log_buffer::*;
 pub use logging::*;
+pub use memory_watchdog::*;
 pub use metrics::*;
+pub use notifier::*;
+#[cfg(feature = "tracing")]
+pub use obs_layer::*;
+pub use observability::*;
 pub use opentelemetry::*;
+pub use panic_hook::*;
+#[cfg(feature = "parquet")]
+pub use parquet::*;
+pub use pressure::*;
+pub use privacy::*;
+pub use process::*;
 pub use prometheus::*;
+pub use propagation::*;
+pub use quality::*;
+pub use query_socket::*;
+pub use reachability::*;
+pub use redaction::*;
+pub use registry::*;
+#[cfg(feature = "remote-write")]
+pub use remote_write::*;
+pub use resource::*;
+pub use sampling::*;
+pub use self_benchmark::*;
+pub use simulate::*;
+pub use soak::*;
+pub use span_processor::*;
+#[cfg(feature = "sqlite-store")]
+pub use sqlite_store::*;
+pub use sse::*;
+pub use statsd::*;
 pub use streaming::*;
+pub use system_log::*;
 pub use telemetry::*;
 pub use test_metrics::*;
+#[cfg(feature = "test-util")]
+pub use test_util::*;
 pub use tracing::*;
+#[cfg(feature = "tui")]
+pub use tui::*;
+pub use watchdog::*;
+#[cfg(feature = "ws-streaming")]
+pub use ws_streaming::*;
+pub use zipkin::*;
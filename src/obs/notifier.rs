@@ -0,0 +1,400 @@
+//! Webhook Alert Notifications
+//!
+//! Posts firing and resolved threshold alerts to webhook URLs, using the
+//! Alertmanager webhook payload (version 4) so existing receivers (chat
+//! bridges, incident tools, Alertmanager-compatible relays) accept them
+//! unchanged.
+//!
+//! # Events
+//!
+//! Attached to a [`MetricStream`], the notifier sends:
+//!
+//! - `firing` for every [`MetricEvent::ThresholdExceeded`], so the alert's
+//!   repeat interval controls how often a firing alert is re-sent
+//! - `resolved` for [`MetricEvent::AlertTransition`] to
+//!   [`AlertState::Resolved`], with `startsAt` from the first firing
+//!
+//! Delivery runs on a background thread with retries (connection errors,
+//! 429 and 5xx), so publishing never blocks on a slow webhook. Only plain
+//! `http://` URLs are supported.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::notifier::AlertNotifier;
+//!
+//! stream.add_alert(ThresholdAlert::above("queue_depth", 100.0)
+//!     .for_duration(Duration::from_secs(60))
+//!     .repeat_interval(Duration::from_secs(3600)));
+//!
+//! let _notifier = AlertNotifier::new("http://hooks.internal:9000/alerts")?
+//!     .with_label("team", "search")
+//!     .attach(&mut stream);
+//! ```
+
+use crate::obs::http::{Backoff, HttpEndpoint};
+use crate::obs::logging;
+use crate::obs::process::{spawn_sink, CollectorHandle};
+use crate::obs::streaming::{AlertState, MetricEvent, MetricStream};
+use crate::obs::telemetry::{escape_json, rfc3339};
+use std::collections::HashMap;
+use std::io;
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// One alert notification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookAlert {
+    pub metric: String,
+    /// [`AlertState::Firing`] or [`AlertState::Resolved`]
+    pub state: AlertState,
+    pub value: f64,
+    pub threshold: f64,
+    /// `severity` label, from [`ThresholdAlert::severity`](crate::obs::streaming::ThresholdAlert::severity)
+    pub severity: String,
+    pub starts_at: SystemTime,
+    /// Set when resolved
+    pub ends_at: Option<SystemTime>,
+}
+
+/// Alertmanager-compatible webhook sender.
+#[derive(Debug, Clone)]
+pub struct AlertNotifier {
+    webhooks: Vec<HttpEndpoint>,
+    receiver: String,
+    labels: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    backoff: Backoff,
+    queue_capacity: usize,
+}
+
+impl AlertNotifier {
+    /// Notifier posting to `url`, with a 10 second timeout and 3 retries
+    /// backing off from 1 second up to 30 seconds.
+    pub fn new(url: &str) -> io::Result<Self> {
+        Ok(Self {
+            webhooks: vec![HttpEndpoint::parse(url)?],
+            receiver: "embeddenator-obs".to_string(),
+            labels: Vec::new(),
+            headers: Vec::new(),
+            timeout: Duration::from_secs(10),
+            backoff: Backoff {
+                max_retries: 3,
+                initial: Duration::from_secs(1),
+                max: Duration::from_secs(30),
+            },
+            queue_capacity: 1024,
+        })
+    }
+
+    /// Also post to `url`.
+    pub fn with_webhook(mut self, url: &str) -> io::Result<Self> {
+        self.webhooks.push(HttpEndpoint::parse(url)?);
+        Ok(self)
+    }
+
+    /// `receiver` field of the payload.
+    pub fn with_receiver(mut self, receiver: impl Into<String>) -> Self {
+        self.receiver = receiver.into();
+        self
+    }
+
+    /// Label added to every alert, e.g. for routing.
+    pub fn with_label(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((name.into(), value.into()));
+        self
+    }
+
+    /// Add an HTTP header (auth tokens).
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Connect, write and read timeout per attempt.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Retry a failed post up to `max_retries` times.
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.backoff.max_retries = max_retries;
+        self
+    }
+
+    /// Backoff before the first retry, doubling up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff.initial = initial;
+        self.backoff.max = max;
+        self
+    }
+
+    /// Notifications queued for delivery before new ones are dropped.
+    pub fn with_queue_capacity(mut self, notifications: usize) -> Self {
+        self.queue_capacity = notifications.max(1);
+        self
+    }
+
+    fn alert_labels(&self, alert: &WebhookAlert) -> Vec<(String, String)> {
+        let mut labels = vec![
+            ("alertname".to_string(), "ThresholdExceeded".to_string()),
+            ("metric".to_string(), alert.metric.clone()),
+            ("severity".to_string(), alert.severity.clone()),
+        ];
+        labels.extend(self.labels.iter().cloned());
+        labels.sort();
+        labels.dedup_by(|a, b| a.0 == b.0);
+        labels
+    }
+
+    /// Alertmanager webhook (version 4) JSON for one alert.
+    pub fn payload(&self, alert: &WebhookAlert) -> String {
+        let object = |pairs: &[(String, String)]| {
+            let fields: Vec<String> = pairs
+                .iter()
+                .map(|(k, v)| format!(r#""{}":"{}""#, escape_json(k), escape_json(v)))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        };
+        let labels = self.alert_labels(alert);
+        let status = match alert.state {
+            AlertState::Resolved => "resolved",
+            _ => "firing",
+        };
+        let annotations = [
+            (
+                "summary".to_string(),
+                format!(
+                    "{} = {} past threshold {}",
+                    alert.metric, alert.value, alert.threshold
+                ),
+            ),
+            ("threshold".to_string(), alert.threshold.to_string()),
+            ("value".to_string(), alert.value.to_string()),
+        ];
+        let ends_at = alert
+            .ends_at
+            .map(rfc3339)
+            .unwrap_or_else(|| "0001-01-01T00:00:00Z".to_string());
+        let group_key = format!(
+            "{{}}:{{{}}}",
+            labels
+                .iter()
+                .map(|(k, v)| format!("{}={:?}", k, v))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+
+        format!(
+            concat!(
+                r#"{{"version":"4","groupKey":"{}","truncatedAlerts":0,"status":"{}","receiver":"{}","#,
+                r#""groupLabels":{{}},"commonLabels":{},"commonAnnotations":{},"externalURL":"","#,
+                r#""alerts":[{{"status":"{}","labels":{},"annotations":{},"startsAt":"{}","endsAt":"{}","#,
+                r#""generatorURL":"","fingerprint":"{:016x}"}}]}}"#
+            ),
+            escape_json(&group_key),
+            status,
+            escape_json(&self.receiver),
+            object(&labels),
+            object(&annotations),
+            status,
+            object(&labels),
+            object(&annotations),
+            rfc3339(alert.starts_at),
+            ends_at,
+            fingerprint(&labels)
+        )
+    }
+
+    /// Post one alert to every webhook, retrying transient failures.
+    ///
+    /// All webhooks are attempted; the first error is returned.
+    pub fn notify(&self, alert: &WebhookAlert) -> io::Result<()> {
+        let body = self.payload(alert);
+        let mut headers = vec![("Content-Type".to_string(), "application/json".to_string())];
+        headers.extend(self.headers.iter().cloned());
+
+        let mut result = Ok(());
+        for webhook in &self.webhooks {
+            let sent = self.backoff.retry("webhook", || {
+                webhook.post(&headers, body.as_bytes(), self.timeout)
+            });
+            if let Err(e) = sent {
+                logging::warn(&format!(
+                    "alert webhook {}{} failed: {}",
+                    webhook.host, webhook.path, e
+                ));
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Subscribe to `stream` and deliver notifications on a background
+    /// thread until the handle is stopped.
+    pub fn attach(self, stream: &mut MetricStream) -> CollectorHandle {
+        let (queue, pending) = mpsc::sync_channel::<MetricEvent>(self.queue_capacity);
+        let queue = Mutex::new(queue);
+        stream.subscribe(move |event| {
+            let wanted = matches!(
+                event,
+                MetricEvent::ThresholdExceeded(..)
+                    | MetricEvent::AlertTransition(_, AlertState::Resolved, ..)
+            );
+            if !wanted {
+                return;
+            }
+            if let Ok(queue) = queue.lock() {
+                if let Err(TrySendError::Full(_)) = queue.try_send(event.clone()) {
+                    logging::warn("alert notification queue full, dropping notification");
+                }
+            }
+        });
+
+        let alerts = stream.alerts_handle();
+        // Start of each firing alert, for startsAt on later notifications
+        let mut firing: HashMap<String, SystemTime> = HashMap::new();
+        spawn_sink(
+            "obs-alert-notifier",
+            Duration::from_millis(100),
+            move || {
+                while let Ok(event) = pending.try_recv() {
+                    let now = SystemTime::now();
+                    let severity = |metric: &str, threshold: f64| {
+                        alerts
+                            .severity(metric, threshold)
+                            .unwrap_or_else(|| "warning".to_string())
+                    };
+                    let alert = match event {
+                        MetricEvent::ThresholdExceeded(metric, value, threshold) => WebhookAlert {
+                            severity: severity(&metric, threshold),
+                            starts_at: *firing.entry(metric.clone()).or_insert(now),
+                            metric,
+                            state: AlertState::Firing,
+                            value,
+                            threshold,
+                            ends_at: None,
+                        },
+                        MetricEvent::AlertTransition(metric, _, value, threshold) => WebhookAlert {
+                            severity: severity(&metric, threshold),
+                            starts_at: firing.remove(&metric).unwrap_or(now),
+                            metric,
+                            state: AlertState::Resolved,
+                            value,
+                            threshold,
+                            ends_at: Some(now),
+                        },
+                        _ => continue,
+                    };
+                    // Failures are logged by notify
+                    let _ = self.notify(&alert);
+                }
+            },
+        )
+    }
+}
+
+/// FNV-1a over the sorted label set, as a stable alert identity.
+fn fingerprint(labels: &[(String, String)]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (name, value) in labels {
+        for byte in name
+            .bytes()
+            .chain([0xff])
+            .chain(value.bytes())
+            .chain([0xff])
+        {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obs::streaming::ThresholdAlert;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::time::UNIX_EPOCH;
+
+    /// Accept one request and answer with `status`; returns the body.
+    fn serve_one(listener: &TcpListener, status: &str) -> String {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(len) = line.strip_prefix("Content-Length: ") {
+                content_length = len.trim().parse().unwrap();
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body).unwrap();
+        write!(reader.get_mut(), "HTTP/1.1 {}\r\n\r\n", status).unwrap();
+        String::from_utf8(body).unwrap()
+    }
+
+    #[test]
+    fn test_alertmanager_payload() {
+        let notifier = AlertNotifier::new("http://hooks:9000/alerts")
+            .unwrap()
+            .with_label("team", "search");
+        let alert = WebhookAlert {
+            metric: "queue_depth".to_string(),
+            state: AlertState::Resolved,
+            value: 12.0,
+            threshold: 100.0,
+            severity: "warning".to_string(),
+            starts_at: UNIX_EPOCH + Duration::from_secs(1_792_108_800),
+            ends_at: Some(UNIX_EPOCH + Duration::from_secs(1_792_109_100)),
+        };
+        let payload = notifier.payload(&alert);
+
+        assert!(payload.starts_with(r#"{"version":"4","groupKey":"{}:{alertname=\"ThresholdExceeded\",metric=\"queue_depth\",severity=\"warning\",team=\"search\"}","#));
+        assert!(payload.contains(r#""status":"resolved","receiver":"embeddenator-obs""#));
+        assert!(payload.contains(r#""labels":{"alertname":"ThresholdExceeded","metric":"queue_depth","severity":"warning","team":"search"}"#));
+        assert!(payload.contains(
+            r#""startsAt":"2026-10-16T00:00:00.000Z","endsAt":"2026-10-16T00:05:00.000Z""#
+        ));
+        assert!(payload.contains(r#""value":"12""#));
+    }
+
+    #[test]
+    fn test_posts_firing_and_resolved_with_retry() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            ["503 Service Unavailable", "200 OK", "200 OK"]
+                .map(|status| serve_one(&listener, status))
+        });
+
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        stream.add_alert(ThresholdAlert::above("cpu", 80.0));
+        let handle = AlertNotifier::new(&format!("http://{}/alerts", addr))
+            .unwrap()
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(1))
+            .attach(&mut stream);
+
+        stream.publish_gauge("cpu", 95.0);
+        stream.publish_gauge("cpu", 20.0);
+
+        let bodies = server.join().unwrap();
+        handle.stop();
+        assert_eq!(bodies[0], bodies[1]);
+        assert!(bodies[1].contains(r#""status":"firing""#));
+        assert!(bodies[1].contains(r#""endsAt":"0001-01-01T00:00:00Z""#));
+        assert!(bodies[2].contains(r#""status":"resolved""#));
+        assert!(bodies[2].contains(r#""value":"20""#));
+    }
+}
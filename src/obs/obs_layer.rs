@@ -0,0 +1,245 @@
+//! `tracing` to OpenTelemetry Bridge
+//!
+//! [`ObsLayer`] is a `tracing_subscriber` layer that mirrors every
+//! `tracing` span (from [`create_span`](crate::obs::tracing::create_span),
+//! `#[instrument]` or `span!`) as an [`OtelSpan`]. The spans flow through
+//! the installed [`TracerProvider`](crate::obs::span_processor::TracerProvider)
+//! when they close, so code instrumented with `tracing` reaches the same
+//! exporters and processors as spans created directly.
+//!
+//! # Mapping
+//!
+//! - name: the `otel.name` field if recorded, the `name` field of
+//!   [`create_span`](crate::obs::tracing::create_span) and its debug and
+//!   trace variants, else the span's name
+//! - parent: the enclosing `tracing` span's [`OtelSpan`], so trace ids and
+//!   parentage follow the `tracing` hierarchy
+//! - fields: typed attributes (`i64`, `u64`, `f64`, `bool`, strings;
+//!   anything else as its `Debug` output), including later `record` calls
+//! - `otel.kind`: `server`, `client`, `producer`, `consumer` or `internal`
+//! - events inside a span: span events named after their message, with
+//!   `level` and the event's fields; an `ERROR` event marks the span failed
+//! - the span ends when its last handle closes
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::obs_layer::ObsLayer;
+//! use tracing_subscriber::prelude::*;
+//!
+//! tracing_subscriber::registry()
+//!     .with(tracing_subscriber::fmt::layer())
+//!     .with(ObsLayer::new())
+//!     .init();
+//!
+//! #[tracing::instrument]
+//! fn handle(query: &str) { /* becomes an OtelSpan with a `query` attribute */ }
+//! ```
+
+use crate::obs::opentelemetry::{AttributeValue, OtelSpan, SpanKind, SpanStatus};
+use std::collections::HashMap;
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Span names of this crate's `create_*span` helpers, which carry the
+/// operation name in their `name` field.
+const HELPER_SPANS: [&str; 3] = ["op", "debug_op", "trace_op"];
+
+/// Layer converting `tracing` spans into [`OtelSpan`]s.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ObsLayer {
+    _private: (),
+}
+
+impl ObsLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Collects span fields as attributes.
+struct SpanFields<'a> {
+    span: &'a mut OtelSpan,
+    name: Option<String>,
+    helper: bool,
+}
+
+impl SpanFields<'_> {
+    fn set(&mut self, field: &Field, value: AttributeValue) {
+        match field.name() {
+            "otel.name" => self.name = Some(value.to_string()),
+            "name" if self.helper => self.name = Some(value.to_string()),
+            "otel.kind" => {
+                if let Some(kind) = parse_kind(&value.to_string()) {
+                    self.span.set_kind(kind);
+                }
+            }
+            name => self.span.set_attribute(name, value),
+        }
+    }
+}
+
+impl Visit for SpanFields<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.set(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.set(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.set(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.set(field, format!("{:?}", value).into());
+    }
+}
+
+/// Collects event fields; `message` becomes the event name.
+#[derive(Default)]
+struct EventFields {
+    message: Option<String>,
+    attributes: HashMap<String, String>,
+}
+
+impl Visit for EventFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, format!("{:?}", value));
+    }
+}
+
+impl EventFields {
+    fn record(&mut self, field: &Field, value: String) {
+        if field.name() == "message" {
+            self.message = Some(value);
+        } else {
+            self.attributes.insert(field.name().to_string(), value);
+        }
+    }
+}
+
+fn parse_kind(kind: &str) -> Option<SpanKind> {
+    match kind.to_ascii_lowercase().as_str() {
+        "internal" => Some(SpanKind::Internal),
+        "server" => Some(SpanKind::Server),
+        "client" => Some(SpanKind::Client),
+        "producer" => Some(SpanKind::Producer),
+        "consumer" => Some(SpanKind::Consumer),
+        _ => None,
+    }
+}
+
+impl<S> Layer<S> for ObsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let metadata = attrs.metadata();
+        let parent = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<OtelSpan>().cloned());
+        let mut builder = OtelSpan::builder(metadata.name());
+        if let Some(parent) = &parent {
+            builder = builder.parent(parent);
+        }
+        // Started before the final name is known; processors see the
+        // `tracing` name on start
+        let mut otel = builder.start();
+        let mut fields = SpanFields {
+            span: &mut otel,
+            name: None,
+            helper: HELPER_SPANS.contains(&metadata.name()),
+        };
+        attrs.record(&mut fields);
+        if let Some(name) = fields.name {
+            otel.name = name;
+        }
+        span.extensions_mut().insert(otel);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let helper = HELPER_SPANS.contains(&span.metadata().name());
+        let mut extensions = span.extensions_mut();
+        if let Some(otel) = extensions.get_mut::<OtelSpan>() {
+            let mut fields = SpanFields {
+                span: otel,
+                name: None,
+                helper,
+            };
+            values.record(&mut fields);
+            if let Some(name) = fields.name {
+                otel.name = name;
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(otel) = extensions.get_mut::<OtelSpan>() else {
+            return;
+        };
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+        let level = *event.metadata().level();
+        fields
+            .attributes
+            .insert("level".to_string(), level.as_str().to_ascii_lowercase());
+        let name = fields
+            .message
+            .unwrap_or_else(|| event.metadata().name().to_string());
+        if level == Level::ERROR {
+            otel.status = SpanStatus::Error;
+            otel.set_attribute("error.message", name.clone());
+        }
+        otel.add_event_with_attributes(name, fields.attributes);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let otel = span.extensions_mut().remove::<OtelSpan>();
+        if let Some(mut otel) = otel {
+            otel.end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!(parse_kind("SERVER"), Some(SpanKind::Server));
+        assert_eq!(parse_kind("consumer"), Some(SpanKind::Consumer));
+        assert_eq!(parse_kind("sideways"), None);
+    }
+}
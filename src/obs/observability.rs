@@ -0,0 +1,473 @@
+//! Unified Initialization
+//!
+//! [`Observability::builder`] wires logging, telemetry, span export,
+//! process metrics and the health/metrics endpoint in one place, and
+//! returns an [`ObservabilityGuard`] that shuts them down in order when
+//! dropped:
+//!
+//! 1. every export pipeline is flushed once ([`force_flush`])
+//! 2. the tracer provider is uninstalled, exporting queued spans
+//! 3. background threads (collectors, servers) are stopped
+//!
+//! Settings can also come from a config file through
+//! [`with_config`](ObservabilityBuilder::with_config), or through
+//! [`with_config_file`](ObservabilityBuilder::with_config_file), which
+//! also reloads the file while running: when it changes, on `SIGHUP`
+//! (Unix), on the `reload` query command and on
+//! [`ObservabilityGuard::reload_config`].
+//!
+//! Subsystems not asked for are left alone, so the builder can be adopted
+//! piecemeal next to code that still initializes some of them by hand. The
+//! one shared piece is the [`TracerProvider`]: it is installed, replacing
+//! any other, when spans are exported or served (`spans` query command).
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::observability::Observability;
+//! use embeddenator_obs::tracing::EventLevel;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let obs = Observability::builder()
+//!         .with_service_name("indexer")
+//!         .with_logging(EventLevel::Info)
+//!         .with_prometheus("0.0.0.0:9898")
+//!         .with_otlp("http://collector:4318")
+//!         .with_process_metrics()
+//!         .init()?;
+//!
+//!     obs.health().register_readiness("index", || CheckResult::healthy());
+//!     obs.telemetry().lock().unwrap().increment_counter("starts");
+//!     // ...
+//!     Ok(())
+//! } // flushed and stopped here
+//! ```
+
+use crate::obs::config::{ConfigReloader, ConfigWatcher, ObservabilityConfig, ReloadReport};
+use crate::obs::health::{HealthRegistry, HealthServer};
+use crate::obs::http::{Backoff, HttpEndpoint};
+use crate::obs::logging::{self, LogFile, LogOutput};
+use crate::obs::opentelemetry::OtelExporter;
+use crate::obs::panic_hook::install_panic_hook;
+use crate::obs::process::{force_flush, CollectorHandle, FlushReport, ProcessCollector};
+use crate::obs::prometheus::PrometheusExporter;
+use crate::obs::query_socket::QueryHandler;
+use crate::obs::span_processor::{BatchSpanProcessor, RecentSpans, TracerProvider};
+use crate::obs::telemetry::{Telemetry, TelemetryConfig};
+use crate::obs::tracing::EventLevel;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// Entry point for [`ObservabilityBuilder`].
+pub struct Observability;
+
+impl Observability {
+    pub fn builder() -> ObservabilityBuilder {
+        ObservabilityBuilder::default()
+    }
+}
+
+/// Collects the subsystems to start; nothing runs until
+/// [`init`](Self::init).
+pub struct ObservabilityBuilder {
+    service_name: String,
+    log_level: Option<EventLevel>,
+    log_format: String,
+    log_output: LogOutput,
+    log_file: Option<LogFile>,
+    telemetry: Option<Arc<Mutex<Telemetry>>>,
+    telemetry_config: TelemetryConfig,
+    health: HealthRegistry,
+    prometheus_addr: Option<String>,
+    exporter: PrometheusExporter,
+    otlp_endpoint: Option<String>,
+    export_interval: Duration,
+    process_interval: Option<Duration>,
+    panic_hook: bool,
+    #[cfg(unix)]
+    query_socket: Option<PathBuf>,
+    config_file: Option<PathBuf>,
+    config_error: Option<io::Error>,
+    config_poll: Duration,
+    flush_timeout: Duration,
+}
+
+impl Default for ObservabilityBuilder {
+    fn default() -> Self {
+        Self {
+            service_name: "embeddenator".to_string(),
+            log_level: None,
+            log_format: "compact".to_string(),
+            log_output: LogOutput::default(),
+            log_file: None,
+            telemetry: None,
+            telemetry_config: TelemetryConfig::default(),
+            health: HealthRegistry::new(),
+            prometheus_addr: None,
+            exporter: PrometheusExporter::default(),
+            otlp_endpoint: None,
+            export_interval: Duration::from_secs(5),
+            process_interval: None,
+            panic_hook: false,
+            #[cfg(unix)]
+            query_socket: None,
+            config_file: None,
+            config_error: None,
+            config_poll: Duration::from_secs(5),
+            flush_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ObservabilityBuilder {
+    /// Service name reported with exported spans (default: `embeddenator`).
+    pub fn with_service_name(mut self, name: impl Into<String>) -> Self {
+        self.service_name = name.into();
+        self
+    }
+
+    /// Take settings from a config file: logging format, output and file,
+    /// telemetry sampling, the `prometheus` and `otlp` exporter endpoints,
+    /// export interval and histogram buckets. Logging starts at
+    /// `logging.level` if the file sets it (below trace) or a log file.
+    /// Later `with_*` calls override single settings.
+    pub fn with_config(mut self, config: &ObservabilityConfig) -> Self {
+        if config.log_level != EventLevel::Trace || config.log_file.is_some() {
+            self.log_level = Some(config.log_level);
+        }
+        self.log_format = config.log_format.clone();
+        self.log_output = config.log_output;
+        self.log_file = config.log_file();
+        self.telemetry_config = config.telemetry_config();
+        if let Some(addr) = config.exporter("prometheus") {
+            self.prometheus_addr = Some(addr.to_string());
+        }
+        if let Some(endpoint) = config.exporter("otlp") {
+            self.otlp_endpoint = Some(endpoint.to_string());
+        }
+        self.export_interval = config.export_interval;
+        self.exporter = config.prometheus_exporter("embeddenator");
+        self
+    }
+
+    /// Read settings from the config file at `path` as
+    /// [`with_config`](Self::with_config) does, then keep them in sync
+    /// while running: the file is checked every 5s
+    /// ([`with_config_poll`](Self::with_config_poll)) and reloaded on
+    /// change, on `SIGHUP` (Unix), on the `reload` query command and on
+    /// [`ObservabilityGuard::reload_config`]. Besides the settings
+    /// [`ConfigWatcher`] applies, the OTLP endpoint follows the file.
+    ///
+    /// A file that cannot be read fails [`init`](Self::init).
+    pub fn with_config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        match ObservabilityConfig::from_file(&path) {
+            Ok(config) => self = self.with_config(&config),
+            Err(e) => self.config_error = Some(e),
+        }
+        self.config_file = Some(path);
+        self
+    }
+
+    /// Interval the config file is checked for changes (default: 5s).
+    pub fn with_config_poll(mut self, interval: Duration) -> Self {
+        self.config_poll = interval;
+        self
+    }
+
+    /// Log at `level` and above. Installs the subscriber as
+    /// [`logging::init`] does; `EMBEDDENATOR_LOG`/`RUST_LOG`, when set,
+    /// still take precedence for `tracing` events.
+    pub fn with_logging(mut self, level: EventLevel) -> Self {
+        self.log_level = Some(level);
+        self
+    }
+
+    /// Also write logs to `file` (implies logging at info if
+    /// [`with_logging`](Self::with_logging) is not called).
+    pub fn with_log_file(mut self, file: LogFile) -> Self {
+        self.log_file = Some(file);
+        self
+    }
+
+    /// Aggregate into `telemetry` instead of a new one.
+    pub fn with_telemetry(mut self, telemetry: Arc<Mutex<Telemetry>>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Probe checks from `registry` instead of a new empty one.
+    pub fn with_health(mut self, registry: HealthRegistry) -> Self {
+        self.health = registry;
+        self
+    }
+
+    /// Serve `/metrics`, `/healthz`, `/readyz` and `/query/<command>` on
+    /// `addr` (e.g. `0.0.0.0:9898`).
+    pub fn with_prometheus(mut self, addr: impl Into<String>) -> Self {
+        self.prometheus_addr = Some(addr.into());
+        self
+    }
+
+    /// Export sampled spans as OTLP/HTTP JSON to `endpoint`; a bare
+    /// `http://host:port` gets the standard `/v1/traces` path.
+    pub fn with_otlp(mut self, endpoint: impl Into<String>) -> Self {
+        self.otlp_endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Interval of batched span export (default: 5s).
+    pub fn with_export_interval(mut self, interval: Duration) -> Self {
+        self.export_interval = interval;
+        self
+    }
+
+    /// Collect process CPU, memory and I/O gauges every 10s.
+    pub fn with_process_metrics(self) -> Self {
+        self.with_process_metrics_every(Duration::from_secs(10))
+    }
+
+    /// Collect process gauges every `interval`.
+    pub fn with_process_metrics_every(mut self, interval: Duration) -> Self {
+        self.process_interval = Some(interval);
+        self
+    }
+
+    /// Report panics as metrics, logs and spans ([`install_panic_hook`]).
+    pub fn with_panic_hook(mut self) -> Self {
+        self.panic_hook = true;
+        self
+    }
+
+    /// Answer query commands on a Unix socket at `path`.
+    #[cfg(unix)]
+    pub fn with_query_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.query_socket = Some(path.into());
+        self
+    }
+
+    /// Time the guard waits for export pipelines on drop (default: 5s).
+    pub fn with_flush_timeout(mut self, timeout: Duration) -> Self {
+        self.flush_timeout = timeout;
+        self
+    }
+
+    /// Start everything configured. On error, whatever was already started
+    /// is shut down again.
+    pub fn init(self) -> io::Result<ObservabilityGuard> {
+        if let Some(e) = self.config_error {
+            return Err(e);
+        }
+        let telemetry = self
+            .telemetry
+            .unwrap_or_else(|| Arc::new(Mutex::new(Telemetry::new(self.telemetry_config))));
+        let mut guard = ObservabilityGuard {
+            telemetry: telemetry.clone(),
+            health: self.health.clone(),
+            metrics_addr: None,
+            reloader: None,
+            tracer_installed: false,
+            handles: Vec::new(),
+            flush_timeout: self.flush_timeout,
+        };
+
+        if self.log_level.is_some() || self.log_file.is_some() {
+            let level = self.log_level.unwrap_or(EventLevel::Info);
+            logging::set_max_level(level);
+            if self.log_output != LogOutput::Stderr {
+                logging::set_output(self.log_output)?;
+            }
+            logging::init_subscriber(
+                self.log_file,
+                &level.as_str().to_ascii_lowercase(),
+                &self.log_format,
+            )?;
+        }
+        if self.panic_hook {
+            install_panic_hook();
+        }
+
+        #[cfg(unix)]
+        let serves_queries = self.prometheus_addr.is_some() || self.query_socket.is_some();
+        #[cfg(not(unix))]
+        let serves_queries = self.prometheus_addr.is_some();
+        let recent = RecentSpans::default();
+        let mut otlp = None;
+        if serves_queries || self.otlp_endpoint.is_some() {
+            let mut provider = TracerProvider::new().with_processor(recent.clone());
+            if let Some(endpoint) = &self.otlp_endpoint {
+                let exporter = Arc::new(OtlpPush::new(endpoint, &self.service_name)?);
+                otlp = Some(exporter.clone());
+                let batcher = BatchSpanProcessor::new(move |spans| exporter.push(spans));
+                guard.handles.push(batcher.spawn(self.export_interval));
+                provider = provider.with_processor(batcher);
+            }
+            provider.install();
+            guard.tracer_installed = true;
+        }
+
+        let mut query = QueryHandler::new()
+            .with_telemetry(telemetry.clone())
+            .with_health(self.health.clone())
+            .with_spans(recent);
+        if let Some(path) = &self.config_file {
+            let watcher = ConfigWatcher::new(path)
+                .with_telemetry(telemetry.clone())
+                .on_exporter("otlp", move |endpoint| match (&otlp, endpoint) {
+                    (Some(otlp), Some(endpoint)) => otlp.set_endpoint(endpoint),
+                    (Some(_), None) => Err(io::Error::other("OTLP export stops on restart")),
+                    (None, Some(_)) => Err(io::Error::other("OTLP export starts on restart")),
+                    (None, None) => Ok(()),
+                });
+            let reloader = ConfigReloader::new(watcher);
+            #[cfg(unix)]
+            let reloader = reloader.reload_on_sighup();
+            guard.handles.push(reloader.watch(self.config_poll)?);
+            query = query.with_reloader(reloader.clone());
+            guard.reloader = Some(reloader);
+        }
+        if let Some(addr) = &self.prometheus_addr {
+            let server = HealthServer::bind(addr.as_str())?
+                .with_registry(self.health.clone())
+                .with_metrics(telemetry.clone(), self.exporter)
+                .with_query(query.clone());
+            guard.metrics_addr = Some(server.local_addr()?);
+            guard.handles.push(server.spawn());
+        }
+        #[cfg(unix)]
+        if let Some(path) = &self.query_socket {
+            let server = crate::obs::query_socket::QueryServer::bind(path)?.with_handler(query);
+            guard.handles.push(server.spawn());
+        }
+        if let Some(interval) = self.process_interval {
+            guard
+                .handles
+                .push(ProcessCollector::new().spawn(interval, telemetry));
+        }
+        Ok(guard)
+    }
+}
+
+/// Keeps the started subsystems running; flushes and stops them on drop.
+pub struct ObservabilityGuard {
+    telemetry: Arc<Mutex<Telemetry>>,
+    health: HealthRegistry,
+    metrics_addr: Option<SocketAddr>,
+    reloader: Option<ConfigReloader>,
+    tracer_installed: bool,
+    handles: Vec<CollectorHandle>,
+    flush_timeout: Duration,
+}
+
+impl ObservabilityGuard {
+    /// Telemetry that exporters and endpoints read.
+    pub fn telemetry(&self) -> Arc<Mutex<Telemetry>> {
+        self.telemetry.clone()
+    }
+
+    /// Registry probed by `/healthz` and `/readyz`.
+    pub fn health(&self) -> HealthRegistry {
+        self.health.clone()
+    }
+
+    /// Bound address of the metrics endpoint, if started.
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        self.metrics_addr
+    }
+
+    /// Reload the config file given to
+    /// [`with_config_file`](ObservabilityBuilder::with_config_file) now.
+    pub fn reload_config(&self) -> io::Result<ReloadReport> {
+        match &self.reloader {
+            Some(reloader) => reloader.reload(),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no config file to reload",
+            )),
+        }
+    }
+
+    /// Flush and stop now instead of on drop; reports sinks that missed
+    /// the flush timeout.
+    pub fn shutdown(mut self) -> FlushReport {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> FlushReport {
+        let report = force_flush(self.flush_timeout);
+        if std::mem::take(&mut self.tracer_installed) {
+            TracerProvider::uninstall();
+        }
+        for handle in self.handles.drain(..).rev() {
+            handle.stop();
+        }
+        report
+    }
+}
+
+impl Drop for ObservabilityGuard {
+    fn drop(&mut self) {
+        let report = self.stop();
+        if !report.is_complete() {
+            logging::warn(&format!(
+                "observability shutdown: flush timed out for {}",
+                report.timed_out.join(", ")
+            ));
+        }
+    }
+}
+
+/// Pushes spans as OTLP/HTTP JSON.
+struct OtlpPush {
+    endpoint: RwLock<HttpEndpoint>,
+    exporter: OtelExporter,
+    backoff: Backoff,
+}
+
+impl OtlpPush {
+    fn new(url: &str, service_name: &str) -> io::Result<Self> {
+        Ok(Self {
+            endpoint: RwLock::new(Self::parse(url)?),
+            exporter: OtelExporter::new().with_service_name(service_name),
+            backoff: Backoff {
+                max_retries: 3,
+                initial: Duration::from_millis(500),
+                max: Duration::from_secs(30),
+            },
+        })
+    }
+
+    fn parse(url: &str) -> io::Result<HttpEndpoint> {
+        let mut endpoint = HttpEndpoint::parse(url)?;
+        if endpoint.path == "/" {
+            endpoint.path = "/v1/traces".to_string();
+        }
+        Ok(endpoint)
+    }
+
+    /// Send later batches to `url`.
+    fn set_endpoint(&self, url: &str) -> io::Result<()> {
+        let endpoint = Self::parse(url)?;
+        *self.endpoint.write().unwrap_or_else(|e| e.into_inner()) = endpoint;
+        Ok(())
+    }
+
+    fn push(&self, spans: &[crate::obs::opentelemetry::OtelSpan]) -> io::Result<()> {
+        if !spans.iter().any(|span| span.sampled) {
+            return Ok(());
+        }
+        let body = self.exporter.export_spans(spans);
+        let headers = [("Content-Type".to_string(), "application/json".to_string())];
+        let endpoint = self
+            .endpoint
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        self.backoff.retry("otlp export", || {
+            endpoint.post(&headers, body.as_bytes(), Duration::from_secs(10))
+        })
+    }
+}
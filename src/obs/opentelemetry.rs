@@ -5,11 +5,17 @@
 //!
 //! # Features
 //!
-//! - Span context propagation (W3C Trace Context)
-//! - OTLP-compatible span export
+//! - Span context propagation (W3C Trace Context, B3 single and multi)
+//! - OTLP-compatible span export, with a detected resource block
+//!   ([`Resource`])
 //! - Distributed trace IDs
 //! - Parent-child span relationships
-//! - Span attributes and events
+//! - Typed span attributes (string, int, float, bool, arrays) and events
+//! - Span builder ([`OtelSpan::builder`])
+//! - Automatic attributes on every span: process-wide defaults
+//!   ([`AutoAttributes`]) and request-scoped ones ([`scoped_attributes`])
+//! - Log records emitted during a span as span events ([`capture_logs`])
+//! - Terminal Gantt rendering of traces (`SpanTree::render_ascii`)
 //!
 //! # Usage
 //!
@@ -21,17 +27,112 @@
 //! span.add_event("checkpoint");
 //! span.end();
 //!
+//! let mut span = OtelSpan::builder("fetch")
+//!     .kind(SpanKind::Client)
+//!     .attr("retry", 3)
+//!     .attr("cached", true)
+//!     .start();
+//!
 //! let exporter = OtelExporter::new();
 //! let json = exporter.export_spans(&[span]);
+//!
+//! // Once at startup: attributes for every span
+//! AutoAttributes::new()
+//!     .with_env("deployment.environment", "DEPLOY_ENV")
+//!     .with_env("cloud.region", "AWS_REGION")
+//!     .with("service.version", env!("CARGO_PKG_VERSION"))
+//!     .install();
+//!
+//! // Per request: spans created on this thread carry the tenant
+//! let _scope = scoped_attributes(&[("tenant.id", tenant)]);
 //! ```
 
+use crate::obs::privacy;
+use crate::obs::resource::Resource;
+use crate::obs::span_processor;
+use crate::obs::telemetry::escape_json;
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{OnceLock, RwLock};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-/// Global trace ID counter for generating unique IDs.
-static TRACE_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
-static SPAN_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
+/// Process-wide ID generator used by [`OtelSpan`].
+static ID_GENERATOR: OnceLock<IdGenerator> = OnceLock::new();
+
+fn ids() -> &'static IdGenerator {
+    ID_GENERATOR.get_or_init(IdGenerator::new)
+}
+
+/// Trace and span ID generator.
+///
+/// IDs are a bijective mix (splitmix64) of a per-generator random seed and
+/// a counter: unique within a generator until the counter wraps, and
+/// uncorrelated across processes, so worker processes sharing a trace do
+/// not hand out colliding span IDs.
+#[derive(Debug)]
+pub struct IdGenerator {
+    seed: u64,
+    counter: AtomicU64,
+}
+
+impl Default for IdGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdGenerator {
+    /// Generator seeded from process-unique entropy (std's per-process
+    /// random hash keys, pid, and the current time).
+    pub fn new() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        std::process::id().hash(&mut hasher);
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0)
+            .hash(&mut hasher);
+        Self::with_seed(hasher.finish())
+    }
+
+    /// Deterministic generator, e.g. for reproducible tests.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Next non-zero 64-bit span ID.
+    pub fn next_span_id(&self) -> u64 {
+        loop {
+            let n = self.counter.fetch_add(1, Ordering::Relaxed);
+            let id = splitmix64(self.seed.wrapping_add(n));
+            if id != 0 {
+                return id;
+            }
+        }
+    }
+
+    /// Next non-zero 128-bit trace ID.
+    pub fn next_trace_id(&self) -> u128 {
+        let hi = self.next_span_id() as u128;
+        let lo = splitmix64(hi as u64 ^ self.seed.rotate_left(32)) as u128;
+        (hi << 64) | lo
+    }
+}
+
+/// Bijective 64-bit finalizer; distinct inputs give distinct outputs.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
 
 /// OpenTelemetry span status.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -62,8 +163,8 @@ pub enum SpanKind {
 /// OpenTelemetry span with full tracing context.
 #[derive(Debug, Clone)]
 pub struct OtelSpan {
-    /// Unique trace ID (128-bit in production, 64-bit here for simplicity)
-    pub trace_id: u64,
+    /// Trace ID (128-bit, W3C Trace Context)
+    pub trace_id: u128,
     /// Unique span ID
     pub span_id: u64,
     /// Parent span ID (0 if root)
@@ -78,10 +179,12 @@ pub struct OtelSpan {
     pub end_time_ns: u64,
     /// Span status
     pub status: SpanStatus,
-    /// Span attributes (key-value pairs)
-    pub attributes: HashMap<String, String>,
+    /// Span attributes
+    pub attributes: HashMap<String, AttributeValue>,
     /// Span events
     pub events: Vec<SpanEvent>,
+    /// Sampled trace flag; unsampled spans are propagated but not exported
+    pub sampled: bool,
 }
 
 /// Span event (checkpoint within a span).
@@ -95,11 +198,319 @@ pub struct SpanEvent {
     pub attributes: HashMap<String, String>,
 }
 
+/// Typed span attribute value, as in the OpenTelemetry data model.
+///
+/// Arrays are homogeneous.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    I64(i64),
+    F64(f64),
+    Bool(bool),
+    StringArray(Vec<String>),
+    I64Array(Vec<i64>),
+    F64Array(Vec<f64>),
+    BoolArray(Vec<bool>),
+}
+
+impl AttributeValue {
+    /// The value of a string attribute.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AttributeValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// OTLP JSON `AnyValue`; 64-bit integers are strings per the OTLP
+    /// JSON mapping.
+    pub fn to_otlp_json(&self) -> String {
+        fn array<T>(values: &[T], item: impl Fn(&T) -> String) -> String {
+            let values: Vec<String> = values.iter().map(item).collect();
+            format!(r#"{{"arrayValue": {{"values": [{}]}}}}"#, values.join(", "))
+        }
+        match self {
+            AttributeValue::String(value) => {
+                format!(r#"{{"stringValue": "{}"}}"#, escape_json(value))
+            }
+            AttributeValue::I64(value) => format!(r#"{{"intValue": "{}"}}"#, value),
+            AttributeValue::F64(value) => format!(r#"{{"doubleValue": {}}}"#, json_f64(*value)),
+            AttributeValue::Bool(value) => format!(r#"{{"boolValue": {}}}"#, value),
+            AttributeValue::StringArray(values) => {
+                array(values, |v| AttributeValue::String(v.clone()).to_otlp_json())
+            }
+            AttributeValue::I64Array(values) => {
+                array(values, |v| AttributeValue::I64(*v).to_otlp_json())
+            }
+            AttributeValue::F64Array(values) => {
+                array(values, |v| AttributeValue::F64(*v).to_otlp_json())
+            }
+            AttributeValue::BoolArray(values) => {
+                array(values, |v| AttributeValue::Bool(*v).to_otlp_json())
+            }
+        }
+    }
+
+    /// Plain JSON value: string, number, boolean or array.
+    pub fn to_json(&self) -> String {
+        fn array<T>(values: &[T], item: impl Fn(&T) -> String) -> String {
+            let values: Vec<String> = values.iter().map(item).collect();
+            format!("[{}]", values.join(","))
+        }
+        match self {
+            AttributeValue::String(value) => format!("\"{}\"", escape_json(value)),
+            AttributeValue::I64(value) => value.to_string(),
+            AttributeValue::F64(value) => json_f64(*value),
+            AttributeValue::Bool(value) => value.to_string(),
+            AttributeValue::StringArray(values) => {
+                array(values, |v| format!("\"{}\"", escape_json(v)))
+            }
+            AttributeValue::I64Array(values) => array(values, i64::to_string),
+            AttributeValue::F64Array(values) => array(values, |v| json_f64(*v)),
+            AttributeValue::BoolArray(values) => array(values, bool::to_string),
+        }
+    }
+}
+
+/// JSON number for `value`; non-finite values, which JSON cannot hold,
+/// become strings as in the OTLP JSON mapping.
+pub(crate) fn json_f64(value: f64) -> String {
+    if value.is_finite() {
+        format!("{:?}", value)
+    } else if value.is_nan() {
+        "\"NaN\"".to_string()
+    } else if value > 0.0 {
+        "\"Infinity\"".to_string()
+    } else {
+        "\"-Infinity\"".to_string()
+    }
+}
+
+impl std::fmt::Display for AttributeValue {
+    /// Strings as-is, arrays as `[a, b]`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn list<T: std::fmt::Display>(
+            f: &mut std::fmt::Formatter<'_>,
+            values: &[T],
+        ) -> std::fmt::Result {
+            write!(f, "[")?;
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", value)?;
+            }
+            write!(f, "]")
+        }
+        match self {
+            AttributeValue::String(value) => f.write_str(value),
+            AttributeValue::I64(value) => write!(f, "{}", value),
+            AttributeValue::F64(value) => write!(f, "{}", value),
+            AttributeValue::Bool(value) => write!(f, "{}", value),
+            AttributeValue::StringArray(values) => list(f, values),
+            AttributeValue::I64Array(values) => list(f, values),
+            AttributeValue::F64Array(values) => list(f, values),
+            AttributeValue::BoolArray(values) => list(f, values),
+        }
+    }
+}
+
+impl PartialEq<str> for AttributeValue {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == Some(other)
+    }
+}
+
+impl PartialEq<&str> for AttributeValue {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == Some(*other)
+    }
+}
+
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> Self {
+        AttributeValue::String(value.to_string())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        AttributeValue::String(value)
+    }
+}
+
+impl From<&String> for AttributeValue {
+    fn from(value: &String) -> Self {
+        AttributeValue::String(value.clone())
+    }
+}
+
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> Self {
+        AttributeValue::I64(value)
+    }
+}
+
+impl From<i32> for AttributeValue {
+    fn from(value: i32) -> Self {
+        AttributeValue::I64(value.into())
+    }
+}
+
+impl From<u32> for AttributeValue {
+    fn from(value: u32) -> Self {
+        AttributeValue::I64(value.into())
+    }
+}
+
+impl From<u64> for AttributeValue {
+    /// Values above `i64::MAX` saturate.
+    fn from(value: u64) -> Self {
+        AttributeValue::I64(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<usize> for AttributeValue {
+    fn from(value: usize) -> Self {
+        (value as u64).into()
+    }
+}
+
+impl From<f64> for AttributeValue {
+    fn from(value: f64) -> Self {
+        AttributeValue::F64(value)
+    }
+}
+
+impl From<&std::path::Path> for AttributeValue {
+    fn from(value: &std::path::Path) -> Self {
+        AttributeValue::String(value.display().to_string())
+    }
+}
+
+impl From<&std::path::PathBuf> for AttributeValue {
+    fn from(value: &std::path::PathBuf) -> Self {
+        value.as_path().into()
+    }
+}
+
+impl From<std::path::PathBuf> for AttributeValue {
+    fn from(value: std::path::PathBuf) -> Self {
+        value.as_path().into()
+    }
+}
+
+impl From<bool> for AttributeValue {
+    fn from(value: bool) -> Self {
+        AttributeValue::Bool(value)
+    }
+}
+
+impl From<Vec<String>> for AttributeValue {
+    fn from(values: Vec<String>) -> Self {
+        AttributeValue::StringArray(values)
+    }
+}
+
+impl From<Vec<&str>> for AttributeValue {
+    fn from(values: Vec<&str>) -> Self {
+        AttributeValue::StringArray(values.into_iter().map(String::from).collect())
+    }
+}
+
+impl From<Vec<i64>> for AttributeValue {
+    fn from(values: Vec<i64>) -> Self {
+        AttributeValue::I64Array(values)
+    }
+}
+
+impl From<Vec<f64>> for AttributeValue {
+    fn from(values: Vec<f64>) -> Self {
+        AttributeValue::F64Array(values)
+    }
+}
+
+impl From<Vec<bool>> for AttributeValue {
+    fn from(values: Vec<bool>) -> Self {
+        AttributeValue::BoolArray(values)
+    }
+}
+
+/// Builder for a span with kind, parent and attributes set before it
+/// starts; see [`OtelSpan::builder`].
+#[derive(Debug, Clone)]
+pub struct SpanBuilder {
+    name: String,
+    kind: SpanKind,
+    parent: Option<(u128, u64, bool)>,
+    attributes: Vec<(String, AttributeValue)>,
+}
+
+impl SpanBuilder {
+    pub fn kind(mut self, kind: SpanKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Start as a child of `parent`.
+    pub fn parent(mut self, parent: &OtelSpan) -> Self {
+        self.parent = Some((parent.trace_id, parent.span_id, parent.sampled));
+        self
+    }
+
+    /// Start as a child of a remote span.
+    pub fn remote_parent(mut self, context: &TraceContext) -> Self {
+        self.parent = Some((context.trace_id, context.parent_id, context.is_sampled()));
+        self
+    }
+
+    /// Set attribute `key`; later values for a key win.
+    pub fn attr(mut self, key: impl Into<String>, value: impl Into<AttributeValue>) -> Self {
+        self.attributes.push((key.into(), value.into()));
+        self
+    }
+
+    /// Start the span now.
+    pub fn start(self) -> OtelSpan {
+        let mut span = match self.parent {
+            Some((trace_id, parent_span_id, sampled)) => OtelSpan {
+                trace_id,
+                parent_span_id,
+                sampled,
+                ..OtelSpan::unstarted(self.name)
+            },
+            None => OtelSpan::unstarted(self.name),
+        };
+        span.kind = self.kind;
+        span.attributes.extend(self.attributes);
+        span_processor::span_started(&span);
+        span
+    }
+}
+
 impl OtelSpan {
+    /// Builder for a span: `OtelSpan::builder("fetch").kind(SpanKind::Client)
+    /// .attr("retry", 3).start()`.
+    pub fn builder(name: impl Into<String>) -> SpanBuilder {
+        SpanBuilder {
+            name: name.into(),
+            kind: SpanKind::Internal,
+            parent: None,
+            attributes: Vec::new(),
+        }
+    }
+
     /// Create new root span.
     pub fn new(name: impl Into<String>) -> Self {
-        let trace_id = TRACE_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
-        let span_id = SPAN_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let span = Self::unstarted(name);
+        span_processor::span_started(&span);
+        span
+    }
+
+    /// Root span not yet announced to span processors.
+    fn unstarted(name: impl Into<String>) -> Self {
+        let trace_id = ids().next_trace_id();
+        let span_id = ids().next_span_id();
 
         Self {
             trace_id,
@@ -110,16 +521,17 @@ impl OtelSpan {
             start_time_ns: system_time_nanos(),
             end_time_ns: 0,
             status: SpanStatus::Unset,
-            attributes: HashMap::new(),
+            attributes: auto_attributes(),
             events: Vec::new(),
+            sampled: ROOT_SAMPLED.load(Ordering::Relaxed),
         }
     }
 
     /// Create child span with parent context.
     pub fn new_child(name: impl Into<String>, parent: &OtelSpan) -> Self {
-        let span_id = SPAN_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let span_id = ids().next_span_id();
 
-        Self {
+        let span = Self {
             trace_id: parent.trace_id,
             span_id,
             parent_span_id: parent.span_id,
@@ -128,9 +540,12 @@ impl OtelSpan {
             start_time_ns: system_time_nanos(),
             end_time_ns: 0,
             status: SpanStatus::Unset,
-            attributes: HashMap::new(),
+            attributes: auto_attributes(),
             events: Vec::new(),
-        }
+            sampled: parent.sampled,
+        };
+        span_processor::span_started(&span);
+        span
     }
 
     /// Set span kind.
@@ -139,7 +554,7 @@ impl OtelSpan {
     }
 
     /// Set span attribute.
-    pub fn set_attribute(&mut self, key: impl Into<String>, value: impl Into<String>) {
+    pub fn set_attribute(&mut self, key: impl Into<String>, value: impl Into<AttributeValue>) {
         self.attributes.insert(key.into(), value.into());
     }
 
@@ -171,13 +586,19 @@ impl OtelSpan {
         if self.status == SpanStatus::Unset {
             self.status = SpanStatus::Ok;
         }
+        #[cfg(feature = "test-util")]
+        crate::obs::test_util::capture_span(self);
+        span_processor::span_ended(self);
     }
 
     /// Mark span as failed.
     pub fn end_with_error(&mut self, error: impl Into<String>) {
         self.end_time_ns = system_time_nanos();
         self.status = SpanStatus::Error;
-        self.set_attribute("error.message", error);
+        self.set_attribute("error.message", error.into());
+        #[cfg(feature = "test-util")]
+        crate::obs::test_util::capture_span(self);
+        span_processor::span_ended(self);
     }
 
     /// Get span duration in nanoseconds.
@@ -196,62 +617,746 @@ impl OtelSpan {
 
     /// Export as W3C Trace Context header (traceparent).
     pub fn to_traceparent(&self) -> String {
-        format!("00-{:032x}-{:016x}-01", self.trace_id, self.span_id)
+        TraceContext {
+            version: 0,
+            trace_id: self.trace_id,
+            parent_id: self.span_id,
+            flags: if self.sampled {
+                TraceContext::FLAG_SAMPLED
+            } else {
+                0
+            },
+        }
+        .to_traceparent()
     }
 
-    /// Parse W3C Trace Context header.
+    /// Parse W3C Trace Context header, creating a child of the remote span.
+    ///
+    /// Returns `None` for any malformed header; never panics.
     pub fn from_traceparent(traceparent: &str, name: impl Into<String>) -> Option<Self> {
-        let parts: Vec<&str> = traceparent.split('-').collect();
-        if parts.len() != 4 || parts[0] != "00" {
+        Some(Self::from_context(&TraceContext::parse(traceparent)?, name))
+    }
+
+    /// Create a child of the remote span described by `context`.
+    pub fn from_context(context: &TraceContext, name: impl Into<String>) -> Self {
+        let span = Self {
+            trace_id: context.trace_id,
+            span_id: ids().next_span_id(),
+            parent_span_id: context.parent_id,
+            name: name.into(),
+            kind: SpanKind::Internal,
+            start_time_ns: system_time_nanos(),
+            end_time_ns: 0,
+            status: SpanStatus::Unset,
+            attributes: auto_attributes(),
+            events: Vec::new(),
+            sampled: context.is_sampled(),
+        };
+        span_processor::span_started(&span);
+        span
+    }
+
+    /// Export as a B3 single header (`b3`).
+    pub fn to_b3_single(&self) -> String {
+        self.b3_context().to_single()
+    }
+
+    /// Export as B3 multi headers (`X-B3-TraceId`, `X-B3-SpanId`, ...).
+    pub fn to_b3_headers(&self) -> Vec<(&'static str, String)> {
+        self.b3_context().to_multi()
+    }
+
+    /// Parse a B3 single header, creating a child of the remote span.
+    ///
+    /// Returns `None` for malformed headers and for sampling-only headers
+    /// (`b3: 0`), which carry no IDs.
+    pub fn from_b3_single(header: &str, name: impl Into<String>) -> Option<Self> {
+        Some(Self::from_b3(B3Context::parse_single(header)?, name))
+    }
+
+    /// Parse B3 multi headers (names matched case-insensitively),
+    /// creating a child of the remote span.
+    pub fn from_b3_headers(headers: &[(&str, &str)], name: impl Into<String>) -> Option<Self> {
+        Some(Self::from_b3(B3Context::parse_multi(headers)?, name))
+    }
+
+    fn b3_context(&self) -> B3Context {
+        B3Context {
+            trace_id: self.trace_id,
+            span_id: self.span_id,
+            parent_span_id: (self.parent_span_id != 0).then_some(self.parent_span_id),
+            sampled: Some(self.sampled),
+            debug: false,
+        }
+    }
+
+    fn from_b3(context: B3Context, name: impl Into<String>) -> Self {
+        Self::from_context(&context.to_trace_context(), name)
+    }
+}
+
+/// Sampled flag of new root spans.
+static ROOT_SAMPLED: AtomicBool = AtomicBool::new(true);
+
+/// Start new root spans sampled (the default) or unsampled, e.g. to shed
+/// tracing cost. Children and remote contexts keep their parent's flag.
+pub fn set_root_spans_sampled(sampled: bool) {
+    ROOT_SAMPLED.store(sampled, Ordering::Relaxed);
+}
+
+/// Whether new root spans start sampled.
+pub fn root_spans_sampled() -> bool {
+    ROOT_SAMPLED.load(Ordering::Relaxed)
+}
+
+/// Process-wide attributes added to every new span.
+static AUTO_ATTRIBUTES: RwLock<Vec<(String, String)>> = RwLock::new(Vec::new());
+
+thread_local! {
+    /// Attributes of the active [`scoped_attributes`] guards on this thread.
+    static SCOPED_ATTRIBUTES: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Initial attributes of a new span: process-wide defaults, then scoped
+/// attributes (innermost scope wins). Attributes set on the span later
+/// override both.
+fn auto_attributes() -> HashMap<String, AttributeValue> {
+    let mut attributes = HashMap::new();
+    let typed = |(key, value): &(String, String)| (key.clone(), AttributeValue::from(value));
+    if let Ok(defaults) = AUTO_ATTRIBUTES.read() {
+        attributes.extend(defaults.iter().map(typed));
+    }
+    SCOPED_ATTRIBUTES.with(|scoped| attributes.extend(scoped.borrow().iter().map(typed)));
+    attributes
+}
+
+/// Builder for attributes applied to every span created afterwards, such
+/// as deployment environment, region and code version.
+#[derive(Debug, Clone, Default)]
+pub struct AutoAttributes {
+    attributes: Vec<(String, String)>,
+}
+
+impl AutoAttributes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.push((key.into(), value.into()));
+        self
+    }
+
+    /// Attribute from environment variable `var`, skipped when unset or
+    /// empty.
+    pub fn with_env(self, key: impl Into<String>, var: &str) -> Self {
+        match std::env::var(var) {
+            Ok(value) if !value.is_empty() => self.with(key, value),
+            _ => self,
+        }
+    }
+
+    /// Replace the process-wide span attributes.
+    pub fn install(self) {
+        if let Ok(mut attributes) = AUTO_ATTRIBUTES.write() {
+            *attributes = self.attributes;
+        }
+    }
+
+    /// Remove all process-wide span attributes.
+    pub fn clear() {
+        Self::new().install();
+    }
+}
+
+/// Add `attributes` to spans created on this thread until the guard drops.
+///
+/// Scopes nest; on key conflicts the innermost scope wins.
+pub fn scoped_attributes(attributes: &[(&str, &str)]) -> AttributeScope {
+    let depth = SCOPED_ATTRIBUTES.with(|scoped| {
+        let mut scoped = scoped.borrow_mut();
+        let depth = scoped.len();
+        scoped.extend(
+            attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string())),
+        );
+        depth
+    });
+    AttributeScope {
+        depth,
+        _not_send: PhantomData,
+    }
+}
+
+/// Guard returned by [`scoped_attributes`]; tied to its thread.
+#[must_use = "attributes are removed when the scope is dropped"]
+pub struct AttributeScope {
+    depth: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for AttributeScope {
+    fn drop(&mut self) {
+        SCOPED_ATTRIBUTES.with(|scoped| scoped.borrow_mut().truncate(self.depth));
+    }
+}
+
+thread_local! {
+    /// Buffers of the active [`capture_logs`] guards on this thread.
+    static LOG_CAPTURES: RefCell<Vec<LogBuffer>> = const { RefCell::new(Vec::new()) };
+}
+
+struct LogBuffer {
+    events: Vec<SpanEvent>,
+    max_events: usize,
+    dropped: u64,
+}
+
+/// Record log records emitted on this thread (through [`logging`] or
+/// [`record_event`]) as span events until the guard is finished or
+/// dropped, keeping at most `max_events`.
+///
+/// Each event is named after the log message and carries a `level`
+/// attribute plus the record's fields, so trace viewers show log lines
+/// inline with span timing. Captures nest; a record goes to the innermost
+/// one.
+///
+/// ```rust,ignore
+/// let mut span = OtelSpan::new("rebuild_index");
+/// let logs = capture_logs(64);
+/// rebuild_index()?; // logging::warn(...) calls become span events
+/// logs.finish(&mut span);
+/// span.end();
+/// ```
+///
+/// [`logging`]: crate::obs::logging
+/// [`record_event`]: crate::obs::tracing::record_event
+pub fn capture_logs(max_events: usize) -> LogCapture {
+    let depth = LOG_CAPTURES.with(|captures| {
+        let mut captures = captures.borrow_mut();
+        captures.push(LogBuffer {
+            events: Vec::new(),
+            max_events,
+            dropped: 0,
+        });
+        captures.len() - 1
+    });
+    LogCapture {
+        depth,
+        _not_send: PhantomData,
+    }
+}
+
+/// Guard returned by [`capture_logs`]; tied to its thread. Dropping it
+/// without [`finish`](Self::finish) discards the captured events.
+#[must_use = "captured logs are discarded unless finished into a span"]
+pub struct LogCapture {
+    depth: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl LogCapture {
+    /// Append the captured events to `span`. Records beyond the cap are
+    /// counted in the `log.dropped_events` attribute.
+    pub fn finish(self, span: &mut OtelSpan) {
+        let buffer = LOG_CAPTURES.with(|captures| {
+            let mut captures = captures.borrow_mut();
+            let buffer = captures.drain(self.depth..).next();
+            buffer
+        });
+        if let Some(buffer) = buffer {
+            span.events.extend(buffer.events);
+            if buffer.dropped > 0 {
+                span.set_attribute("log.dropped_events", buffer.dropped);
+            }
+        }
+    }
+}
+
+impl Drop for LogCapture {
+    fn drop(&mut self) {
+        LOG_CAPTURES.with(|captures| captures.borrow_mut().truncate(self.depth));
+    }
+}
+
+/// Add a log record to the innermost active capture on this thread.
+pub(crate) fn record_log(level: &str, message: &str, fields: &[(&str, &str)]) {
+    #[cfg(feature = "test-util")]
+    crate::obs::test_util::capture_log(level, message, fields);
+    LOG_CAPTURES.with(|captures| {
+        let mut captures = captures.borrow_mut();
+        let Some(buffer) = captures.last_mut() else {
+            return;
+        };
+        if buffer.events.len() >= buffer.max_events {
+            buffer.dropped += 1;
+            return;
+        }
+        let mut attributes = HashMap::with_capacity(fields.len() + 1);
+        attributes.insert("level".to_string(), level.to_string());
+        attributes.extend(fields.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+        buffer.events.push(SpanEvent {
+            name: message.to_string(),
+            timestamp_ns: system_time_nanos(),
+            attributes,
+        });
+    });
+}
+
+/// Parsed W3C `traceparent` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    pub version: u8,
+    pub trace_id: u128,
+    pub parent_id: u64,
+    pub flags: u8,
+}
+
+impl TraceContext {
+    /// `sampled` bit of the trace flags.
+    pub const FLAG_SAMPLED: u8 = 0x01;
+
+    /// Parse a `traceparent` header per the W3C Trace Context spec.
+    ///
+    /// - Fields are lowercase hex: `version-trace_id-parent_id-flags`
+    /// - Version `ff` and all-zero trace or parent IDs are invalid
+    /// - Version `00` must be exactly 55 characters
+    /// - Higher versions may append `-`-prefixed fields, which are ignored
+    pub fn parse(header: &str) -> Option<Self> {
+        let bytes = header.trim_matches([' ', '\t']).as_bytes();
+        if bytes.len() < 55 || bytes[2] != b'-' || bytes[35] != b'-' || bytes[52] != b'-' {
+            return None;
+        }
+
+        let version = parse_hex(&bytes[0..2])? as u8;
+        match version {
+            0xff => return None,
+            0 if bytes.len() != 55 => return None,
+            _ if bytes.len() > 55 && bytes[55] != b'-' => return None,
+            _ => {}
+        }
+
+        let trace_id = parse_hex(&bytes[3..35])?;
+        let parent_id = parse_hex(&bytes[36..52])? as u64;
+        let flags = parse_hex(&bytes[53..55])? as u8;
+        if trace_id == 0 || parent_id == 0 {
             return None;
         }
 
-        let trace_id = u64::from_str_radix(&parts[1][16..32], 16).ok()?;
-        let parent_span_id = u64::from_str_radix(parts[2], 16).ok()?;
-        let span_id = SPAN_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+        Some(Self {
+            version,
+            trace_id,
+            parent_id,
+            flags,
+        })
+    }
+
+    /// Whether the caller sampled this trace.
+    pub fn is_sampled(&self) -> bool {
+        self.flags & Self::FLAG_SAMPLED != 0
+    }
 
+    /// Format as a version `00` header.
+    ///
+    /// Only the flags defined by version `00` are kept.
+    pub fn to_traceparent(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id,
+            self.parent_id,
+            self.flags & Self::FLAG_SAMPLED
+        )
+    }
+}
+
+/// Parsed B3 propagation headers, as used by Zipkin instrumentation.
+///
+/// Trace IDs may be 64 or 128 bits; 64-bit IDs are kept in the low bits
+/// and written back as 16 hex digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct B3Context {
+    pub trace_id: u128,
+    pub span_id: u64,
+    pub parent_span_id: Option<u64>,
+    /// Sampling decision; `None` defers it to the receiver
+    pub sampled: Option<bool>,
+    /// Debug flag, which implies sampling
+    pub debug: bool,
+}
+
+impl B3Context {
+    /// Parse a `b3` single header:
+    /// `{trace_id}-{span_id}[-{sampling}[-{parent_span_id}]]`, sampling
+    /// being `1`, `0` or `d` (debug).
+    pub fn parse_single(header: &str) -> Option<Self> {
+        let mut fields = header.trim_matches([' ', '\t']).split('-');
+        let trace_id = parse_b3_trace_id(fields.next()?)?;
+        let span_id = parse_b3_id(fields.next()?)?;
+        let (sampled, debug) = match fields.next() {
+            None => (None, false),
+            Some("1") => (Some(true), false),
+            Some("0") => (Some(false), false),
+            Some("d") => (Some(true), true),
+            Some(_) => return None,
+        };
+        let parent_span_id = match fields.next() {
+            Some(parent) => Some(parse_b3_id(parent)?),
+            None => None,
+        };
+        if fields.next().is_some() {
+            return None;
+        }
         Some(Self {
             trace_id,
             span_id,
             parent_span_id,
-            name: name.into(),
-            kind: SpanKind::Internal,
-            start_time_ns: system_time_nanos(),
-            end_time_ns: 0,
-            status: SpanStatus::Unset,
-            attributes: HashMap::new(),
-            events: Vec::new(),
+            sampled,
+            debug,
+        })
+    }
+
+    /// Parse `X-B3-*` multi headers; names are matched case-insensitively.
+    pub fn parse_multi(headers: &[(&str, &str)]) -> Option<Self> {
+        let get = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.trim())
+        };
+        let trace_id = parse_b3_trace_id(get("X-B3-TraceId")?)?;
+        let span_id = parse_b3_id(get("X-B3-SpanId")?)?;
+        let parent_span_id = match get("X-B3-ParentSpanId") {
+            Some(parent) => Some(parse_b3_id(parent)?),
+            None => None,
+        };
+        let debug = get("X-B3-Flags") == Some("1");
+        // `true`/`false` predate the spec but are still sent
+        let sampled = match get("X-B3-Sampled") {
+            Some("1") | Some("true") => Some(true),
+            Some("0") | Some("false") => Some(false),
+            None => None,
+            Some(_) => return None,
+        };
+        Some(Self {
+            trace_id,
+            span_id,
+            parent_span_id,
+            sampled: if debug { Some(true) } else { sampled },
+            debug,
         })
     }
+
+    /// Format as a `b3` single header.
+    pub fn to_single(&self) -> String {
+        let mut header = format!(
+            "{}-{:016x}",
+            format_b3_trace_id(self.trace_id),
+            self.span_id
+        );
+        let sampling = match (self.debug, self.sampled) {
+            (true, _) => Some("d"),
+            (false, Some(true)) => Some("1"),
+            (false, Some(false)) => Some("0"),
+            (false, None) => None,
+        };
+        if let Some(sampling) = sampling {
+            header.push('-');
+            header.push_str(sampling);
+            if let Some(parent) = self.parent_span_id {
+                header.push_str(&format!("-{:016x}", parent));
+            }
+        }
+        header
+    }
+
+    /// Equivalent W3C context; a deferred sampling decision takes the
+    /// local default ([`root_spans_sampled`]).
+    pub fn to_trace_context(&self) -> TraceContext {
+        let sampled = self.debug || self.sampled.unwrap_or_else(root_spans_sampled);
+        TraceContext {
+            version: 0,
+            trace_id: self.trace_id,
+            parent_id: self.span_id,
+            flags: if sampled {
+                TraceContext::FLAG_SAMPLED
+            } else {
+                0
+            },
+        }
+    }
+
+    /// Format as `X-B3-*` multi headers.
+    pub fn to_multi(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            ("X-B3-TraceId", format_b3_trace_id(self.trace_id)),
+            ("X-B3-SpanId", format!("{:016x}", self.span_id)),
+        ];
+        if let Some(parent) = self.parent_span_id {
+            headers.push(("X-B3-ParentSpanId", format!("{:016x}", parent)));
+        }
+        if self.debug {
+            headers.push(("X-B3-Flags", "1".to_string()));
+        } else if let Some(sampled) = self.sampled {
+            headers.push(("X-B3-Sampled", if sampled { "1" } else { "0" }.to_string()));
+        }
+        headers
+    }
+}
+
+fn parse_b3_trace_id(id: &str) -> Option<u128> {
+    if id.len() != 16 && id.len() != 32 {
+        return None;
+    }
+    parse_hex(id.as_bytes()).filter(|&id| id != 0)
+}
+
+fn parse_b3_id(id: &str) -> Option<u64> {
+    if id.len() != 16 {
+        return None;
+    }
+    parse_hex(id.as_bytes())
+        .map(|id| id as u64)
+        .filter(|&id| id != 0)
+}
+
+fn format_b3_trace_id(trace_id: u128) -> String {
+    if trace_id >> 64 == 0 {
+        format!("{:016x}", trace_id)
+    } else {
+        format!("{:032x}", trace_id)
+    }
+}
+
+/// Parse up to 32 lowercase hex digits; uppercase is invalid per spec.
+fn parse_hex(digits: &[u8]) -> Option<u128> {
+    digits.iter().try_fold(0u128, |acc, &b| {
+        let nibble = match b {
+            b'0'..=b'9' => b - b'0',
+            b'a'..=b'f' => b - b'a' + 10,
+            _ => return None,
+        };
+        Some((acc << 4) | nibble as u128)
+    })
+}
+
+/// Spans arranged by parent-child relationship, for inspecting a trace.
+///
+/// Spans whose parent is not in the set are treated as roots; siblings are
+/// ordered by start time.
+#[derive(Debug, Clone)]
+pub struct SpanTree {
+    spans: Vec<OtelSpan>,
+    children: Vec<Vec<usize>>,
+    roots: Vec<usize>,
+}
+
+impl SpanTree {
+    /// Build a tree from the spans of one trace.
+    pub fn new(spans: Vec<OtelSpan>) -> Self {
+        let index: HashMap<u64, usize> = spans
+            .iter()
+            .enumerate()
+            .map(|(i, span)| (span.span_id, i))
+            .collect();
+
+        let mut children = vec![Vec::new(); spans.len()];
+        let mut roots = Vec::new();
+        for (i, span) in spans.iter().enumerate() {
+            match index.get(&span.parent_span_id) {
+                Some(&parent) if !span.is_root() && parent != i => children[parent].push(i),
+                _ => roots.push(i),
+            }
+        }
+        let by_start = |a: &usize, b: &usize| spans[*a].start_time_ns.cmp(&spans[*b].start_time_ns);
+        roots.sort_by(by_start);
+        for list in &mut children {
+            list.sort_by(by_start);
+        }
+
+        Self {
+            spans,
+            children,
+            roots,
+        }
+    }
+
+    /// Root spans, by start time.
+    pub fn roots(&self) -> impl Iterator<Item = &OtelSpan> {
+        self.roots.iter().map(|&i| &self.spans[i])
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Render as an indented Gantt chart with 40-column bars.
+    ///
+    /// ```text
+    /// query                |========================================| 12.400ms  shard=3
+    ///   parse              |==                                      | 0.610ms
+    ///   score              |  =================================     | 10.100ms  k=10
+    /// ```
+    pub fn render_ascii(&self) -> String {
+        self.render_ascii_width(40)
+    }
+
+    /// Render with bars `bar_width` columns wide.
+    ///
+    /// Unfinished spans extend to the end of the trace and are marked
+    /// `(open)`; failed spans use `!` instead of `=`.
+    pub fn render_ascii_width(&self, bar_width: usize) -> String {
+        let bar_width = bar_width.max(1);
+        let Some(trace_start) = self.spans.iter().map(|s| s.start_time_ns).min() else {
+            return "(no spans)\n".to_string();
+        };
+        let trace_end = self
+            .spans
+            .iter()
+            .map(|s| s.end_time_ns.max(s.start_time_ns))
+            .max()
+            .unwrap_or(trace_start);
+        let trace_len = trace_end.saturating_sub(trace_start).max(1) as u128;
+
+        let rows = self.depth_first();
+        let label_width = rows
+            .iter()
+            .map(|&(i, depth)| depth * 2 + self.spans[i].name.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let mut output = String::new();
+        for (i, depth) in rows {
+            let span = &self.spans[i];
+            let open = span.end_time_ns == 0;
+            let end = if open { trace_end } else { span.end_time_ns };
+
+            let column = |t: u64| {
+                (t.saturating_sub(trace_start) as u128 * bar_width as u128 / trace_len) as usize
+            };
+            let from = column(span.start_time_ns).min(bar_width - 1);
+            let to = column(end).clamp(from + 1, bar_width);
+            let fill = if span.status == SpanStatus::Error {
+                '!'
+            } else {
+                '='
+            };
+
+            let label = format!("{}{}", "  ".repeat(depth), span.name);
+            let mut line = format!(
+                "{:<label_width$} |{}{}{}| ",
+                label,
+                " ".repeat(from),
+                fill.to_string().repeat(to - from),
+                " ".repeat(bar_width - to),
+                label_width = label_width
+            );
+            if open {
+                line.push_str("(open)");
+            } else {
+                line.push_str(
+                    &crate::obs::hires_timing::HiResTimestamp::from_nanos(span.duration_ns())
+                        .format(),
+                );
+            }
+
+            let mut attributes: Vec<_> = span.attributes.iter().collect();
+            attributes.sort_by(|a, b| a.0.cmp(b.0));
+            for (key, value) in attributes {
+                line.push_str(&format!("  {}={}", key, value));
+            }
+            output.push_str(line.trim_end());
+            output.push('\n');
+        }
+        output
+    }
+
+    /// `(span index, depth)` in render order; spans caught in parent
+    /// cycles are appended as extra roots.
+    fn depth_first(&self) -> Vec<(usize, usize)> {
+        let mut visited = vec![false; self.spans.len()];
+        let mut rows = Vec::with_capacity(self.spans.len());
+        let mut stack: Vec<(usize, usize)> = self.roots.iter().rev().map(|&i| (i, 0)).collect();
+
+        loop {
+            while let Some((i, depth)) = stack.pop() {
+                if std::mem::replace(&mut visited[i], true) {
+                    continue;
+                }
+                rows.push((i, depth));
+                stack.extend(self.children[i].iter().rev().map(|&c| (c, depth + 1)));
+            }
+            match visited.iter().position(|&v| !v) {
+                Some(i) => stack.push((i, 0)),
+                None => return rows,
+            }
+        }
+    }
 }
 
 /// OpenTelemetry exporter for OTLP-compatible output.
 pub struct OtelExporter {
-    /// Service name
-    service_name: String,
+    /// Resource attributes, including `service.name`
+    resource: Resource,
 }
 
 impl OtelExporter {
-    /// Create new OTLP exporter.
+    /// Create new OTLP exporter with a [detected](Resource::detect)
+    /// resource and service name `embeddenator`, unless
+    /// `OTEL_RESOURCE_ATTRIBUTES` names the service.
     pub fn new() -> Self {
-        Self {
-            service_name: "embeddenator".to_string(),
-        }
+        let detected = Resource::detect();
+        let resource = match detected.get("service.name") {
+            Some(_) => detected,
+            None => detected.with_service_name("embeddenator"),
+        };
+        Self { resource }
     }
 
     /// Set service name.
     pub fn with_service_name(mut self, name: impl Into<String>) -> Self {
-        self.service_name = name.into();
+        self.resource = self.resource.with_service_name(name);
         self
     }
 
+    /// Add `resource`'s attributes, replacing existing keys.
+    pub fn with_resource(mut self, resource: Resource) -> Self {
+        self.resource = self.resource.merge(&resource);
+        self
+    }
+
+    /// Set one resource attribute.
+    pub fn with_resource_attribute(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<AttributeValue>,
+    ) -> Self {
+        self.resource = self.resource.with(key, value);
+        self
+    }
+
+    /// Resource emitted with every export.
+    pub fn resource(&self) -> &Resource {
+        &self.resource
+    }
+
     /// Export spans as JSON (simplified OTLP format).
+    ///
+    /// Spans whose trace was not sampled upstream are skipped.
     pub fn export_spans(&self, spans: &[OtelSpan]) -> String {
+        let spans = privacy::enforce_spans(spans);
         let mut output = String::from("{\n  \"resourceSpans\": [\n    {\n");
-        output.push_str(&format!("      \"resource\": {{\"attributes\": [{{\"key\": \"service.name\", \"value\": \"{}\"}}]}},\n", self.service_name));
+        output.push_str(&format!(
+            "      \"resource\": {},\n",
+            self.resource.to_otlp_json()
+        ));
         output.push_str("      \"scopeSpans\": [\n        {\n          \"spans\": [\n");
 
-        for (i, span) in spans.iter().enumerate() {
+        for (i, span) in spans.iter().filter(|span| span.sampled).enumerate() {
             if i > 0 {
                 output.push_str(",\n");
             }
@@ -292,6 +1397,24 @@ impl OtelExporter {
             "              \"endTimeUnixNano\": {},\n",
             span.end_time_ns
         ));
+        if !span.attributes.is_empty() {
+            let mut attributes: Vec<(&String, &AttributeValue)> = span.attributes.iter().collect();
+            attributes.sort_by(|a, b| a.0.cmp(b.0));
+            let attributes: Vec<String> = attributes
+                .into_iter()
+                .map(|(key, value)| {
+                    format!(
+                        r#"{{"key": "{}", "value": {}}}"#,
+                        escape_json(key),
+                        value.to_otlp_json()
+                    )
+                })
+                .collect();
+            json.push_str(&format!(
+                "              \"attributes\": [{}],\n",
+                attributes.join(", ")
+            ));
+        }
         json.push_str(&format!(
             "              \"status\": {{\"code\": {}}}\n",
             span.status as u32
@@ -329,6 +1452,71 @@ mod tests {
         assert_eq!(span.status, SpanStatus::Unset);
     }
 
+    #[test]
+    fn test_auto_and_scoped_attributes() {
+        std::env::set_var("OBS_TEST_REGION", "eu-west-1");
+        AutoAttributes::new()
+            .with("deployment.environment", "staging")
+            .with_env("cloud.region", "OBS_TEST_REGION")
+            .with_env("missing", "OBS_TEST_UNSET_VAR")
+            .install();
+
+        let root = {
+            let _tenant = scoped_attributes(&[("tenant.id", "acme")]);
+            let _inner = scoped_attributes(&[("tenant.id", "beta"), ("request", "7")]);
+            OtelSpan::new("request")
+        };
+        let mut child = OtelSpan::new_child("lookup", &root);
+        child.set_attribute("deployment.environment", "override");
+        AutoAttributes::clear();
+
+        assert_eq!(root.attributes["deployment.environment"], "staging");
+        assert_eq!(root.attributes["cloud.region"], "eu-west-1");
+        assert_eq!(root.attributes["tenant.id"], "beta");
+        assert!(!root.attributes.contains_key("missing"));
+
+        // Scopes ended before the child was created
+        assert!(!child.attributes.contains_key("tenant.id"));
+        assert_eq!(child.attributes["deployment.environment"], "override");
+        assert!(OtelSpan::new("after").attributes.is_empty());
+    }
+
+    #[test]
+    fn test_capture_logs_as_span_events() {
+        use crate::obs::logging;
+        use crate::obs::tracing::{record_event, EventLevel};
+
+        let mut span = OtelSpan::new("rebuild");
+        let logs = capture_logs(3);
+        logging::warn("shard 3 slow");
+        {
+            // Nested capture takes the records emitted inside it
+            let mut child = OtelSpan::new_child("shard", &span);
+            let inner = capture_logs(8);
+            logging::debug("inner only");
+            inner.finish(&mut child);
+            assert_eq!(child.events[0].name, "inner only");
+        }
+        record_event(EventLevel::Info, "merged", &[("segments", "12")]);
+        logging::info("compacted");
+        logging::error("over the cap");
+        logs.finish(&mut span);
+
+        let names: Vec<&str> = span.events.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["shard 3 slow", "merged", "compacted"]);
+        assert_eq!(span.events[0].attributes["level"], "WARN");
+        assert_eq!(span.events[1].attributes["segments"], "12");
+        assert_eq!(
+            span.attributes["log.dropped_events"],
+            AttributeValue::I64(1)
+        );
+
+        // Nothing is captured without an active guard
+        logging::warn("uncaptured");
+        drop(capture_logs(4));
+        assert_eq!(span.events.len(), 3);
+    }
+
     #[test]
     fn test_child_span() {
         let parent = OtelSpan::new("parent");
@@ -346,8 +1534,8 @@ mod tests {
         span.set_attribute("key1", "value1");
         span.set_attribute("key2", "value2");
 
-        assert_eq!(span.attributes.get("key1"), Some(&"value1".to_string()));
-        assert_eq!(span.attributes.get("key2"), Some(&"value2".to_string()));
+        assert_eq!(span.attributes["key1"], "value1");
+        assert_eq!(span.attributes["key2"], "value2");
     }
 
     #[test]
@@ -398,6 +1586,296 @@ mod tests {
         let child = OtelSpan::from_traceparent(&traceparent, "child").unwrap();
         assert_eq!(child.trace_id, parent.trace_id);
         assert_eq!(child.parent_span_id, parent.span_id);
+        assert!(child.sampled);
+    }
+
+    #[test]
+    fn test_traceparent_rejects_malformed() {
+        let valid = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        assert!(TraceContext::parse(valid).is_some());
+
+        let invalid = [
+            "",
+            "00-4bf92f35",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            // Uppercase hex
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            // All-zero IDs
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            // Forbidden version
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            // Version 00 allows no trailing data
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            // Misplaced separators and multibyte characters
+            "00_4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473é-00f067aa0ba902b7-01",
+        ];
+        for header in invalid {
+            assert_eq!(TraceContext::parse(header), None, "{header:?}");
+            assert!(OtelSpan::from_traceparent(header, "x").is_none());
+        }
+    }
+
+    #[test]
+    fn test_builder_typed_attributes() {
+        let parent = OtelSpan::new("request");
+        let span = OtelSpan::builder("fetch")
+            .kind(SpanKind::Client)
+            .parent(&parent)
+            .attr("retry", 3)
+            .attr("cached", true)
+            .attr("score", 0.5)
+            .attr("shards", vec![1i64, 2])
+            .attr("peer", "index-2")
+            .start();
+
+        assert_eq!(span.kind, SpanKind::Client);
+        assert_eq!(span.trace_id, parent.trace_id);
+        assert_eq!(span.parent_span_id, parent.span_id);
+        assert_eq!(span.attributes["retry"], AttributeValue::I64(3));
+        assert_eq!(span.attributes["cached"], AttributeValue::Bool(true));
+        assert_eq!(span.attributes["peer"], "index-2");
+        assert_eq!(span.attributes["shards"].to_string(), "[1, 2]");
+
+        let remote = TraceContext::parse(&parent.to_traceparent()).unwrap();
+        let server = OtelSpan::builder("serve").remote_parent(&remote).start();
+        assert_eq!(server.parent_span_id, parent.span_id);
+    }
+
+    #[test]
+    fn test_otlp_export_keeps_attribute_types() {
+        let mut span = OtelSpan::builder("fetch")
+            .attr("retry", 3)
+            .attr("cached", true)
+            .attr("score", 0.5)
+            .attr("tags", vec!["a", "b\""])
+            .attr("peer", "index-2")
+            .start();
+        span.end();
+        let json = OtelExporter::new().export_spans(&[span]);
+
+        assert!(json.contains(r#"{"key": "retry", "value": {"intValue": "3"}}"#));
+        assert!(json.contains(r#"{"key": "cached", "value": {"boolValue": true}}"#));
+        assert!(json.contains(r#"{"key": "score", "value": {"doubleValue": 0.5}}"#));
+        assert!(json.contains(r#"{"key": "peer", "value": {"stringValue": "index-2"}}"#));
+        assert!(json.contains(
+            r#"{"key": "tags", "value": {"arrayValue": {"values": [{"stringValue": "a"}, {"stringValue": "b\""}]}}}"#
+        ));
+        assert_eq!(
+            AttributeValue::F64(f64::NAN).to_otlp_json(),
+            r#"{"doubleValue": "NaN"}"#
+        );
+    }
+
+    #[test]
+    fn test_b3_roundtrip() {
+        let parent = OtelSpan::new("parent");
+        let mut span = OtelSpan::new_child("child", &parent);
+        span.sampled = false;
+
+        let single = span.to_b3_single();
+        assert_eq!(
+            single,
+            format!(
+                "{:032x}-{:016x}-0-{:016x}",
+                span.trace_id, span.span_id, parent.span_id
+            )
+        );
+        let remote = OtelSpan::from_b3_single(&single, "server").unwrap();
+        assert_eq!(remote.trace_id, span.trace_id);
+        assert_eq!(remote.parent_span_id, span.span_id);
+        assert!(!remote.sampled);
+
+        let headers = span.to_b3_headers();
+        assert_eq!(
+            headers[2],
+            ("X-B3-ParentSpanId", format!("{:016x}", parent.span_id))
+        );
+        assert_eq!(headers[3], ("X-B3-Sampled", "0".to_string()));
+        let lowercase: Vec<(String, String)> = headers
+            .iter()
+            .map(|(k, v)| (k.to_ascii_lowercase(), v.clone()))
+            .collect();
+        let pairs: Vec<(&str, &str)> = lowercase
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let remote = OtelSpan::from_b3_headers(&pairs, "server").unwrap();
+        assert_eq!(remote.trace_id, span.trace_id);
+        assert_eq!(remote.parent_span_id, span.span_id);
+    }
+
+    #[test]
+    fn test_b3_variants() {
+        // 64-bit trace ID, debug flag
+        let context = B3Context::parse_single("463ac35c9f6413ad-a2fb4a1d1a96d312-d").unwrap();
+        assert_eq!(context.trace_id, 0x463ac35c9f6413ad);
+        assert!(context.debug);
+        assert_eq!(context.to_single(), "463ac35c9f6413ad-a2fb4a1d1a96d312-d");
+        // Deferred sampling
+        let context = B3Context::parse_single("463ac35c9f6413ad-a2fb4a1d1a96d312").unwrap();
+        assert_eq!(context.sampled, None);
+        assert_eq!(context.to_multi().len(), 2);
+
+        for header in [
+            "0",
+            "463ac35c9f6413ad",
+            "463ac35c9f6413ad-a2fb4a1d1a96d312-x",
+            "463AC35C9F6413AD-a2fb4a1d1a96d312",
+            "0000000000000000-a2fb4a1d1a96d312",
+            "463ac35c9f6413ad-a2fb4a1d1a96d312-1-0000000000000001-9",
+        ] {
+            assert!(B3Context::parse_single(header).is_none(), "{}", header);
+        }
+
+        let context = B3Context::parse_multi(&[
+            ("X-B3-TraceId", "463ac35c9f6413ad48485a3953bb6124"),
+            ("X-B3-SpanId", "a2fb4a1d1a96d312"),
+            ("X-B3-Sampled", "true"),
+        ])
+        .unwrap();
+        assert_eq!(context.sampled, Some(true));
+        assert_eq!(context.parent_span_id, None);
+        assert!(B3Context::parse_multi(&[("X-B3-SpanId", "a2fb4a1d1a96d312")]).is_none());
+    }
+
+    #[test]
+    fn test_traceparent_flags_and_future_versions() {
+        let unsampled = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00";
+        let span = OtelSpan::from_traceparent(unsampled, "child").unwrap();
+        assert!(!span.sampled);
+        assert_eq!(span.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+        assert!(span.to_traceparent().ends_with("-00"));
+        assert!(!OtelSpan::new_child("grandchild", &span).sampled);
+
+        let exported = OtelExporter::new().export_spans(&[span]);
+        assert!(!exported.contains("\"name\": \"child\""));
+
+        // Future versions may append fields; they parse with known fields only
+        let future = "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-09-what-the-future";
+        let context = TraceContext::parse(future).unwrap();
+        assert_eq!(context.version, 0xcc);
+        assert!(context.is_sampled());
+        assert!(context.to_traceparent().ends_with("-01"));
+        assert!(
+            TraceContext::parse("cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-09x")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_ids_unique_across_simulated_processes() {
+        use std::collections::HashSet;
+
+        // Each generator stands in for one worker process
+        let processes: Vec<IdGenerator> = (0..8).map(|_| IdGenerator::new()).collect();
+        let mut span_ids = HashSet::new();
+        let mut trace_ids = HashSet::new();
+        for generator in &processes {
+            for _ in 0..2000 {
+                let span_id = generator.next_span_id();
+                assert_ne!(span_id, 0);
+                assert!(span_ids.insert(span_id), "span ID collision");
+                assert!(trace_ids.insert(generator.next_trace_id()));
+            }
+        }
+
+        // No generator starts from the old sequential 1, 2, 3... scheme
+        assert!(processes
+            .iter()
+            .all(|generator| IdGenerator::with_seed(generator.seed).next_span_id() > 1 << 20));
+    }
+
+    #[test]
+    fn test_seeded_generator_is_deterministic() {
+        let a = IdGenerator::with_seed(42);
+        let b = IdGenerator::with_seed(42);
+        assert_eq!(a.next_span_id(), b.next_span_id());
+        assert_eq!(a.next_trace_id(), b.next_trace_id());
+        assert_ne!(a.next_span_id(), a.next_span_id());
+    }
+
+    fn span_at(name: &str, parent: Option<&OtelSpan>, start: u64, end: u64) -> OtelSpan {
+        let mut span = match parent {
+            Some(parent) => OtelSpan::new_child(name, parent),
+            None => OtelSpan::new(name),
+        };
+        span.start_time_ns = start;
+        span.end_time_ns = end;
+        span
+    }
+
+    #[test]
+    fn test_span_tree_render_ascii() {
+        let mut root = span_at("query", None, 1_000, 11_000);
+        root.set_attribute("shard", "3");
+        let score = span_at("score", Some(&root), 6_000, 11_000);
+        let parse = span_at("parse", Some(&root), 1_000, 3_500);
+        let mut rank = span_at("rank", Some(&score), 8_500, 0);
+        rank.status = SpanStatus::Error;
+
+        let tree = SpanTree::new(vec![score, rank, root, parse]);
+        assert_eq!(tree.roots().next().unwrap().name, "query");
+
+        let rendered = tree.render_ascii_width(20);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "query    |====================| 10.000µs  shard=3",
+                "  parse  |=====               | 2.500µs",
+                "  score  |          ==========| 5.000µs",
+                "    rank |               !!!!!| (open)",
+            ]
+        );
+        assert_eq!(SpanTree::new(Vec::new()).render_ascii(), "(no spans)\n");
+    }
+
+    #[test]
+    fn test_span_tree_survives_parent_cycles() {
+        let mut a = span_at("a", None, 0, 10);
+        let mut b = span_at("b", None, 5, 10);
+        a.parent_span_id = b.span_id;
+        b.parent_span_id = a.span_id;
+
+        let rendered = SpanTree::new(vec![a, b]).render_ascii();
+        assert_eq!(rendered.lines().count(), 2);
+    }
+
+    mod fuzz {
+        use super::*;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn parse_never_panics(header in "\\PC*") {
+                let _ = TraceContext::parse(&header);
+                let _ = OtelSpan::from_traceparent(&header, "fuzz");
+            }
+
+            #[test]
+            fn parse_near_valid_never_panics(header in "[0-9a-fA-F-]{0,70}") {
+                let _ = TraceContext::parse(&header);
+            }
+
+            #[test]
+            fn valid_contexts_round_trip(
+                trace_id in 1u128..,
+                parent_id in 1u64..,
+                sampled in any::<bool>(),
+            ) {
+                let context = TraceContext {
+                    version: 0,
+                    trace_id,
+                    parent_id,
+                    flags: sampled as u8,
+                };
+                let header = context.to_traceparent();
+                prop_assert_eq!(header.len(), 55);
+                prop_assert_eq!(TraceContext::parse(&header), Some(context));
+            }
+        }
     }
 
     #[test]
@@ -413,4 +1891,19 @@ mod tests {
         assert!(json.contains("test"));
         assert!(json.contains("traceId"));
     }
+
+    #[test]
+    fn test_exporter_resource() {
+        let exporter = OtelExporter::new()
+            .with_service_name("indexer")
+            .with_resource(Resource::new().with_service_version("1.2.3"))
+            .with_resource_attribute("shard.count", 8);
+        let resource = exporter.resource();
+        assert_eq!(resource.get("service.name").unwrap(), "indexer");
+        assert!(resource.get("process.pid").is_some());
+
+        let json = exporter.export_spans(&[]);
+        assert!(json.contains(r#"{"key": "service.version", "value": {"stringValue": "1.2.3"}}"#));
+        assert!(json.contains(r#"{"key": "shard.count", "value": {"intValue": "8"}}"#));
+    }
 }
@@ -0,0 +1,81 @@
+//! Panics as Metrics, Logs and Spans
+//!
+//! [`install_panic_hook`] reports every panic through all three signals,
+//! then runs the previously installed hook (by default the standard
+//! message on stderr):
+//!
+//! - metric: the built-in `panics_total` counter
+//!   ([`MetricsSnapshot::panics_total`])
+//! - log: an `ERROR` record `thread '<name>' panicked: <message>` with
+//!   `location`, `thread` and `backtrace` fields, through
+//!   [`logging::log_kv`], so it reaches every log output, system loggers
+//!   and the recent-records buffer, redacted
+//! - span: the active [`TracedCall`]s on the panicking thread end with
+//!   error status and the panic message as `error.message` as they unwind
+//!   (under `panic = "abort"` the innermost one ends from the hook)
+//!
+//! The backtrace is always captured, regardless of `RUST_BACKTRACE`.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::panic_hook::install_panic_hook;
+//!
+//! fn main() {
+//!     logging::init();
+//!     install_panic_hook();
+//!     // ...
+//! }
+//! ```
+//!
+//! [`MetricsSnapshot::panics_total`]: crate::obs::metrics::MetricsSnapshot::panics_total
+//! [`logging::log_kv`]: crate::obs::logging::log_kv
+//! [`TracedCall`]: crate::obs::instrument::TracedCall
+
+use crate::obs::instrument::note_panic;
+use crate::obs::logging;
+use crate::obs::metrics::metrics;
+use crate::obs::tracing::EventLevel;
+use std::backtrace::Backtrace;
+use std::panic::PanicHookInfo;
+use std::sync::Once;
+
+/// Report panics as metrics, logs and spans, ahead of the current hook.
+/// Installs once; later calls do nothing.
+pub fn install_panic_hook() {
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            record_panic(info);
+            previous(info);
+        }));
+    });
+}
+
+fn record_panic(info: &PanicHookInfo<'_>) {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+    let thread = std::thread::current();
+    let thread = thread.name().unwrap_or("<unnamed>");
+    let location = info
+        .location()
+        .map(|location| location.to_string())
+        .unwrap_or_default();
+
+    metrics().inc_panics();
+    note_panic(message);
+    logging::log_kv(
+        EventLevel::Error,
+        &format!("thread '{}' panicked: {}", thread, message),
+        &[
+            ("location", location.into()),
+            ("thread", thread.into()),
+            ("backtrace", Backtrace::force_capture().to_string().into()),
+        ],
+    );
+}
@@ -0,0 +1,712 @@
+//! Parquet Snapshot Export
+//!
+//! Writes telemetry snapshots as Parquet files for offline analysis of
+//! large benchmark campaigns with DuckDB, Polars or pandas, without custom
+//! parsing.
+//!
+//! # Layout
+//!
+//! Files are Hive-partitioned by table and UTC date, one file per snapshot:
+//!
+//! ```text
+//! <dir>/table=operations/date=2026-10-16/part-1792137600000.parquet
+//! <dir>/table=metrics/date=2026-10-16/part-1792137600000.parquet
+//! ```
+//!
+//! - `operations`: `timestamp`, `operation`, `count`, `total_us`, `min_us`,
+//!   `max_us`, `mean_us`, `p50_us`, `p95_us`, `p99_us`
+//! - `metrics`: `timestamp`, `kind` (`counter`/`gauge`), `name`, `labels`
+//!   (label body, empty when unlabeled), `value`
+//!
+//! [`ParquetExporter::write_samples`] adds a long-format `samples` table,
+//! `timestamp`, `metric`, `labels`, `value`, one row per value. It takes
+//! snapshots ([`ParquetExporter::snapshot_samples`]) and raw benchmark
+//! timings ([`ParquetExporter::test_metrics_samples`]), so both load into
+//! one data frame:
+//!
+//! ```python
+//! df = pl.read_parquet("snapshots/table=samples/**/*.parquet")
+//! df.filter(pl.col("metric") == "duration_ns").group_by("labels").agg(pl.col("value").median())
+//! ```
+//!
+//! ```sql
+//! SELECT operation, avg(p99_us)
+//! FROM read_parquet('snapshots/table=operations/*/*.parquet', hive_partitioning = true)
+//! GROUP BY operation;
+//! ```
+//!
+//! # Format
+//!
+//! The writer is self-contained: one row group per file, one uncompressed
+//! PLAIN-encoded data page per column, all columns required. Readers need
+//! nothing beyond the core Parquet spec.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::parquet::ParquetExporter;
+//!
+//! let exporter = ParquetExporter::new("target/snapshots");
+//! let _handle = exporter.spawn(Duration::from_secs(60), telemetry.clone());
+//!
+//! // After a benchmark run
+//! ParquetExporter::new("target/snapshots")
+//!     .write_samples(&ParquetExporter::test_metrics_samples(&metrics, now_ms))?;
+//! ```
+
+use crate::obs::logging;
+use crate::obs::privacy;
+use crate::obs::process::{spawn_sink, CollectorHandle};
+use crate::obs::telemetry::{
+    civil_from_days, labeled_key, split_labeled_key, Telemetry, TelemetrySnapshot,
+};
+use crate::obs::test_metrics::TestMetrics;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Values of one Parquet column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParquetColumn {
+    Int64(Vec<i64>),
+    Double(Vec<f64>),
+    /// UTF-8 strings (`BYTE_ARRAY` annotated `UTF8`)
+    Utf8(Vec<String>),
+    /// Milliseconds since the Unix epoch (`INT64` annotated `TIMESTAMP_MILLIS`)
+    TimestampMillis(Vec<i64>),
+}
+
+impl ParquetColumn {
+    fn len(&self) -> usize {
+        match self {
+            ParquetColumn::Int64(v) | ParquetColumn::TimestampMillis(v) => v.len(),
+            ParquetColumn::Double(v) => v.len(),
+            ParquetColumn::Utf8(v) => v.len(),
+        }
+    }
+
+    /// Physical type id from the Parquet `Type` enum.
+    fn physical_type(&self) -> i32 {
+        match self {
+            ParquetColumn::Int64(_) | ParquetColumn::TimestampMillis(_) => 2,
+            ParquetColumn::Double(_) => 5,
+            ParquetColumn::Utf8(_) => 6,
+        }
+    }
+
+    /// Converted type id from the Parquet `ConvertedType` enum.
+    fn converted_type(&self) -> Option<i32> {
+        match self {
+            ParquetColumn::Utf8(_) => Some(0),
+            ParquetColumn::TimestampMillis(_) => Some(9),
+            _ => None,
+        }
+    }
+
+    fn plain_encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            ParquetColumn::Int64(values) | ParquetColumn::TimestampMillis(values) => {
+                for v in values {
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            ParquetColumn::Double(values) => {
+                for v in values {
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            ParquetColumn::Utf8(values) => {
+                for v in values {
+                    out.extend_from_slice(&(v.len() as u32).to_le_bytes());
+                    out.extend_from_slice(v.as_bytes());
+                }
+            }
+        }
+        out
+    }
+}
+
+/// In-memory table written as a single-row-group Parquet file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParquetTable {
+    columns: Vec<(String, ParquetColumn)>,
+}
+
+impl ParquetTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a column; all columns must have the same length.
+    pub fn with_column(mut self, name: impl Into<String>, column: ParquetColumn) -> Self {
+        self.columns.push((name.into(), column));
+        self
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.columns.first().map_or(0, |(_, c)| c.len())
+    }
+
+    /// Encode as a complete Parquet file.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let num_rows = self.num_rows();
+        if let Some((name, _)) = self.columns.iter().find(|(_, c)| c.len() != num_rows) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("column '{}' length differs from {} rows", name, num_rows),
+            ));
+        }
+
+        let mut file = b"PAR1".to_vec();
+        let mut chunks = Vec::with_capacity(self.columns.len());
+        for (name, column) in &self.columns {
+            let data = column.plain_encode();
+            let mut header = CompactWriter::default();
+            header.i32_field(1, 0); // DATA_PAGE
+            header.i32_field(2, data.len() as i32);
+            header.i32_field(3, data.len() as i32);
+            header.struct_begin(5);
+            header.i32_field(1, num_rows as i32);
+            header.i32_field(2, 0); // PLAIN
+            header.i32_field(3, 3); // RLE definition levels (unused: required)
+            header.i32_field(4, 3); // RLE repetition levels
+            header.struct_end();
+            header.stop();
+
+            let offset = file.len() as i64;
+            let size = (header.buf.len() + data.len()) as i64;
+            file.extend_from_slice(&header.buf);
+            file.extend_from_slice(&data);
+            chunks.push((name, column, offset, size));
+        }
+
+        let mut meta = CompactWriter::default();
+        meta.i32_field(1, 1); // version
+        meta.list_begin(2, STRUCT, self.columns.len() + 1);
+        // Root schema element
+        meta.list_struct_begin();
+        meta.binary_field(4, b"schema");
+        meta.i32_field(5, self.columns.len() as i32);
+        meta.stop_nested();
+        for (name, column) in &self.columns {
+            meta.list_struct_begin();
+            meta.i32_field(1, column.physical_type());
+            meta.i32_field(3, 0); // REQUIRED
+            meta.binary_field(4, name.as_bytes());
+            if let Some(converted) = column.converted_type() {
+                meta.i32_field(6, converted);
+            }
+            meta.stop_nested();
+        }
+        meta.i64_field(3, num_rows as i64);
+        meta.list_begin(4, STRUCT, 1);
+        meta.list_struct_begin();
+        meta.list_begin(1, STRUCT, chunks.len());
+        let mut total_size = 0;
+        for (name, column, offset, size) in &chunks {
+            total_size += size;
+            meta.list_struct_begin();
+            meta.i64_field(2, *offset);
+            meta.struct_begin(3);
+            meta.i32_field(1, column.physical_type());
+            meta.list_begin(2, I32, 1);
+            meta.list_i32(0); // PLAIN
+            meta.list_begin(3, BINARY, 1);
+            meta.list_binary(name.as_bytes());
+            meta.i32_field(4, 0); // UNCOMPRESSED
+            meta.i64_field(5, num_rows as i64);
+            meta.i64_field(6, *size);
+            meta.i64_field(7, *size);
+            meta.i64_field(9, *offset);
+            meta.struct_end();
+            meta.stop_nested();
+        }
+        meta.i64_field(2, total_size);
+        meta.i64_field(3, num_rows as i64);
+        meta.stop_nested();
+        meta.binary_field(
+            6,
+            format!("embeddenator-obs version {}", env!("CARGO_PKG_VERSION")).as_bytes(),
+        );
+        meta.stop();
+
+        file.extend_from_slice(&meta.buf);
+        file.extend_from_slice(&(meta.buf.len() as u32).to_le_bytes());
+        file.extend_from_slice(b"PAR1");
+        Ok(file)
+    }
+
+    /// Write the Parquet file to `path`, creating parent directories.
+    pub fn write_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let bytes = self.to_bytes()?;
+        // Write then rename so readers never see a partial file
+        let tmp = path.with_extension("parquet.tmp");
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(&bytes)?;
+        file.sync_data()?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+const I32: u8 = 5;
+const BINARY: u8 = 8;
+const STRUCT: u8 = 12;
+
+/// Minimal Thrift compact protocol encoder for Parquet metadata.
+#[derive(Default)]
+struct CompactWriter {
+    buf: Vec<u8>,
+    last_field: i16,
+    /// Field id context of enclosing structs
+    stack: Vec<i16>,
+}
+
+impl CompactWriter {
+    fn field_header(&mut self, id: i16, kind: u8) {
+        let delta = id - self.last_field;
+        if (1..=15).contains(&delta) {
+            self.buf.push(((delta as u8) << 4) | kind);
+        } else {
+            self.buf.push(kind);
+            self.varint(zigzag(id as i64));
+        }
+        self.last_field = id;
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    fn i32_field(&mut self, id: i16, value: i32) {
+        self.field_header(id, I32);
+        self.varint(zigzag(value as i64));
+    }
+
+    fn i64_field(&mut self, id: i16, value: i64) {
+        self.field_header(id, 6);
+        self.varint(zigzag(value));
+    }
+
+    fn binary_field(&mut self, id: i16, value: &[u8]) {
+        self.field_header(id, BINARY);
+        self.list_binary(value);
+    }
+
+    fn struct_begin(&mut self, id: i16) {
+        self.field_header(id, STRUCT);
+        self.stack.push(self.last_field);
+        self.last_field = 0;
+    }
+
+    fn struct_end(&mut self) {
+        self.stop_nested();
+    }
+
+    fn list_begin(&mut self, id: i16, element: u8, len: usize) {
+        self.field_header(id, 9);
+        if len < 15 {
+            self.buf.push(((len as u8) << 4) | element);
+        } else {
+            self.buf.push(0xf0 | element);
+            self.varint(len as u64);
+        }
+    }
+
+    /// Start a struct element of a list.
+    fn list_struct_begin(&mut self) {
+        self.stack.push(self.last_field);
+        self.last_field = 0;
+    }
+
+    fn list_i32(&mut self, value: i32) {
+        self.varint(zigzag(value as i64));
+    }
+
+    fn list_binary(&mut self, value: &[u8]) {
+        self.varint(value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    /// End a nested struct and restore the parent's field context.
+    fn stop_nested(&mut self) {
+        self.buf.push(0);
+        self.last_field = self.stack.pop().unwrap_or(0);
+    }
+
+    fn stop(&mut self) {
+        self.buf.push(0);
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// One row of the long-format `samples` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParquetSample {
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: i64,
+    pub metric: String,
+    /// Label body (`tier="l1"`), empty when unlabeled
+    pub labels: String,
+    pub value: f64,
+}
+
+impl ParquetSample {
+    pub fn new(timestamp_ms: i64, metric: &str, labels: &[(&str, &str)], value: f64) -> Self {
+        let key = labeled_key(metric, labels);
+        let (metric, labels) = split_labeled_key(&key);
+        Self {
+            timestamp_ms,
+            metric: metric.to_string(),
+            labels: labels.unwrap_or("").to_string(),
+            value,
+        }
+    }
+}
+
+/// Writes each snapshot as partitioned Parquet files.
+pub struct ParquetExporter {
+    dir: PathBuf,
+}
+
+impl ParquetExporter {
+    /// Export under `dir` (created on first write).
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Operations table for a snapshot.
+    pub fn operations_table(snapshot: &TelemetrySnapshot, timestamp_ms: i64) -> ParquetTable {
+        let snapshot = privacy::enforce_snapshot(snapshot);
+        let snapshot = snapshot.as_ref();
+        let mut names: Vec<&String> = snapshot.operation_stats.keys().collect();
+        names.sort();
+        let stats: Vec<_> = names
+            .iter()
+            .map(|n| &snapshot.operation_stats[*n])
+            .collect();
+        let int = |f: &dyn Fn(&crate::obs::telemetry::OperationStats) -> u64| {
+            ParquetColumn::Int64(stats.iter().map(|s| f(s) as i64).collect())
+        };
+
+        ParquetTable::new()
+            .with_column(
+                "timestamp",
+                ParquetColumn::TimestampMillis(vec![timestamp_ms; names.len()]),
+            )
+            .with_column(
+                "operation",
+                ParquetColumn::Utf8(names.iter().map(|n| n.to_string()).collect()),
+            )
+            .with_column("count", int(&|s| s.count))
+            .with_column("total_us", int(&|s| s.total_us))
+            .with_column("min_us", int(&|s| if s.count == 0 { 0 } else { s.min_us }))
+            .with_column("max_us", int(&|s| s.max_us))
+            .with_column(
+                "mean_us",
+                ParquetColumn::Double(stats.iter().map(|s| s.avg_us()).collect()),
+            )
+            .with_column("p50_us", int(&|s| s.median_us()))
+            .with_column("p95_us", int(&|s| s.p95_us()))
+            .with_column("p99_us", int(&|s| s.p99_us()))
+    }
+
+    /// Counters and gauges table for a snapshot.
+    pub fn metrics_table(snapshot: &TelemetrySnapshot, timestamp_ms: i64) -> ParquetTable {
+        let snapshot = privacy::enforce_snapshot(snapshot);
+        let snapshot = snapshot.as_ref();
+        let mut rows: Vec<(&str, &str, &str, f64)> = snapshot
+            .counters
+            .iter()
+            .map(|(key, &v)| ("counter", key.as_str(), v as f64))
+            .chain(
+                snapshot
+                    .gauges
+                    .iter()
+                    .map(|(key, &v)| ("gauge", key.as_str(), v)),
+            )
+            .map(|(kind, key, value)| {
+                let (name, labels) = split_labeled_key(key);
+                (kind, name, labels.unwrap_or(""), value)
+            })
+            .collect();
+        rows.sort_by(|a, b| (a.0, a.1, a.2).cmp(&(b.0, b.1, b.2)));
+
+        let text = |column: usize| {
+            ParquetColumn::Utf8(
+                rows.iter()
+                    .map(|r| [r.0, r.1, r.2][column].to_string())
+                    .collect(),
+            )
+        };
+        ParquetTable::new()
+            .with_column(
+                "timestamp",
+                ParquetColumn::TimestampMillis(vec![timestamp_ms; rows.len()]),
+            )
+            .with_column("kind", text(0))
+            .with_column("name", text(1))
+            .with_column("labels", text(2))
+            .with_column(
+                "value",
+                ParquetColumn::Double(rows.iter().map(|r| r.3).collect()),
+            )
+    }
+
+    /// Long-format rows for a snapshot: counters and gauges under their own
+    /// names, and `operation_count`, `operation_mean_us`, `operation_p50_us`,
+    /// `operation_p95_us`, `operation_p99_us`, `operation_max_us` labeled
+    /// with `operation`.
+    pub fn snapshot_samples(snapshot: &TelemetrySnapshot, timestamp_ms: i64) -> Vec<ParquetSample> {
+        let snapshot = privacy::enforce_snapshot(snapshot);
+        let snapshot = snapshot.as_ref();
+        let mut names: Vec<&String> = snapshot.operation_stats.keys().collect();
+        names.sort();
+
+        let mut samples = Vec::new();
+        for name in names {
+            let stats = &snapshot.operation_stats[name];
+            let labels = [("operation", name.as_str())];
+            for (metric, value) in [
+                ("operation_count", stats.count as f64),
+                ("operation_mean_us", stats.avg_us()),
+                ("operation_p50_us", stats.median_us() as f64),
+                ("operation_p95_us", stats.p95_us() as f64),
+                ("operation_p99_us", stats.p99_us() as f64),
+                ("operation_max_us", stats.max_us as f64),
+            ] {
+                samples.push(ParquetSample::new(timestamp_ms, metric, &labels, value));
+            }
+        }
+
+        let mut values: Vec<(&String, f64)> = snapshot
+            .counters
+            .iter()
+            .map(|(key, &v)| (key, v as f64))
+            .chain(snapshot.gauges.iter().map(|(key, &v)| (key, v)))
+            .collect();
+        values.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in values {
+            let (metric, labels) = split_labeled_key(key);
+            samples.push(ParquetSample {
+                timestamp_ms,
+                metric: metric.to_string(),
+                labels: labels.unwrap_or("").to_string(),
+                value,
+            });
+        }
+        samples
+    }
+
+    /// Long-format rows for a benchmark run: every raw timing as
+    /// `duration_ns`, then operation counts and custom metrics under their
+    /// own names, all labeled with `test`.
+    pub fn test_metrics_samples(metrics: &TestMetrics, timestamp_ms: i64) -> Vec<ParquetSample> {
+        let labels = [("test", metrics.name.as_str())];
+        let mut named: Vec<(&String, f64)> = metrics
+            .op_counts
+            .iter()
+            .map(|(name, &v)| (name, v as f64))
+            .chain(metrics.custom_metrics.iter().map(|(name, &v)| (name, v)))
+            .collect();
+        named.sort_by(|a, b| a.0.cmp(b.0));
+
+        metrics
+            .timings_ns
+            .iter()
+            .map(|&ns| ParquetSample::new(timestamp_ms, "duration_ns", &labels, ns as f64))
+            .chain(
+                named
+                    .into_iter()
+                    .map(|(name, value)| ParquetSample::new(timestamp_ms, name, &labels, value)),
+            )
+            .collect()
+    }
+
+    /// Table of long-format rows.
+    pub fn samples_table(samples: &[ParquetSample]) -> ParquetTable {
+        let text = |f: fn(&ParquetSample) -> &str| {
+            ParquetColumn::Utf8(samples.iter().map(|s| f(s).to_string()).collect())
+        };
+        ParquetTable::new()
+            .with_column(
+                "timestamp",
+                ParquetColumn::TimestampMillis(samples.iter().map(|s| s.timestamp_ms).collect()),
+            )
+            .with_column("metric", text(|s| &s.metric))
+            .with_column("labels", text(|s| &s.labels))
+            .with_column(
+                "value",
+                ParquetColumn::Double(samples.iter().map(|s| s.value).collect()),
+            )
+    }
+
+    /// Write long-format rows as one `samples` file, partitioned by the
+    /// first row's date; returns its path.
+    pub fn write_samples(&self, samples: &[ParquetSample]) -> io::Result<PathBuf> {
+        let timestamp_ms = samples.first().map_or_else(now_ms, |s| s.timestamp_ms);
+        let path = self
+            .dir
+            .join("table=samples")
+            .join(format!("date={}", utc_date(timestamp_ms / 1000)))
+            .join(format!("part-{}.parquet", timestamp_ms));
+        Self::samples_table(samples).write_file(&path)?;
+        Ok(path)
+    }
+
+    /// Write one snapshot; returns the files written.
+    pub fn write_snapshot(&self, snapshot: &TelemetrySnapshot) -> io::Result<Vec<PathBuf>> {
+        let timestamp_ms = now_ms();
+        let date = utc_date(timestamp_ms / 1000);
+
+        let mut written = Vec::new();
+        for (table, data) in [
+            ("operations", Self::operations_table(snapshot, timestamp_ms)),
+            ("metrics", Self::metrics_table(snapshot, timestamp_ms)),
+        ] {
+            let path = self
+                .dir
+                .join(format!("table={}", table))
+                .join(format!("date={}", date))
+                .join(format!("part-{}.parquet", timestamp_ms));
+            data.write_file(&path)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+
+    /// Write shared telemetry every `interval` on a background thread.
+    ///
+    /// The lock is held only to take the snapshot.
+    pub fn spawn(self, interval: Duration, telemetry: Arc<Mutex<Telemetry>>) -> CollectorHandle {
+        spawn_sink("obs-parquet-export", interval, move || {
+            let Ok(snapshot) = telemetry.lock().map(|t| t.snapshot()) else {
+                return;
+            };
+            if let Err(e) = self.write_snapshot(&snapshot) {
+                logging::warn(&format!(
+                    "parquet export to {} failed: {}",
+                    self.dir.display(),
+                    e
+                ));
+            }
+        })
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// `YYYY-MM-DD` for Unix seconds (UTC).
+fn utc_date(unix_secs: i64) -> String {
+    let (year, month, day) = civil_from_days(unix_secs.div_euclid(86_400));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc_date() {
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(utc_date(951_782_400), "2000-02-29");
+        assert_eq!(utc_date(1_792_108_800), "2026-10-16");
+    }
+
+    #[test]
+    fn test_file_structure() {
+        let table = ParquetTable::new()
+            .with_column("id", ParquetColumn::Int64(vec![1, 2, 3]))
+            .with_column(
+                "name",
+                ParquetColumn::Utf8(vec!["a".into(), "bb".into(), "".into()]),
+            );
+        let bytes = table.to_bytes().unwrap();
+
+        assert_eq!(&bytes[..4], b"PAR1");
+        assert_eq!(&bytes[bytes.len() - 4..], b"PAR1");
+        let footer_len =
+            u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap());
+        assert!((footer_len as usize) < bytes.len() - 12);
+        // PLAIN-encoded byte arrays are length-prefixed
+        assert!(bytes.windows(6).any(|w| w == [2, 0, 0, 0, b'b', b'b']));
+
+        let ragged = table.with_column("x", ParquetColumn::Double(vec![1.0]));
+        assert!(ragged.to_bytes().is_err());
+    }
+
+    #[test]
+    fn test_write_snapshot_partitions() {
+        let dir =
+            std::env::temp_dir().join(format!("embeddenator_obs_parquet_{}", std::process::id()));
+        let mut telemetry = Telemetry::default_config();
+        telemetry.record_operation("query", 120);
+        telemetry.add_to_counter_with_labels("hits_total", &[("tier", "l1")], 3);
+        telemetry.set_gauge("depth", 2.5);
+
+        let written = ParquetExporter::new(&dir)
+            .write_snapshot(&telemetry.snapshot())
+            .unwrap();
+        assert_eq!(written.len(), 2);
+        for path in &written {
+            assert!(path.exists());
+            assert!(path.to_string_lossy().contains("date="));
+        }
+        assert!(written[0].starts_with(dir.join("table=operations")));
+
+        let metrics = ParquetExporter::metrics_table(&telemetry.snapshot(), 0);
+        assert_eq!(metrics.num_rows(), 2);
+        assert_eq!(
+            metrics.columns[3].1,
+            ParquetColumn::Utf8(vec![r#"tier="l1""#.into(), "".into()])
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_long_format_samples() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.record_operation("query", 120);
+        telemetry.add_to_counter_with_labels("hits_total", &[("tier", "l1")], 3);
+        let samples = ParquetExporter::snapshot_samples(&telemetry.snapshot(), 7);
+        assert_eq!(samples.len(), 7);
+        assert_eq!(
+            samples[0],
+            ParquetSample::new(7, "operation_count", &[("operation", "query")], 1.0)
+        );
+        assert_eq!(samples[0].labels, r#"operation="query""#);
+        assert_eq!(samples[6].metric, "hits_total");
+
+        let mut metrics = TestMetrics::new("encode");
+        metrics.timings_ns = vec![10, 20];
+        metrics.custom_metrics.insert("ratio".into(), 0.5);
+        let samples = ParquetExporter::test_metrics_samples(&metrics, 7);
+        let values: Vec<(&str, f64)> = samples.iter().map(|s| (&*s.metric, s.value)).collect();
+        assert_eq!(
+            values,
+            [("duration_ns", 10.0), ("duration_ns", 20.0), ("ratio", 0.5)]
+        );
+
+        let table = ParquetExporter::samples_table(&samples);
+        assert_eq!(table.num_rows(), 3);
+        assert!(table.to_bytes().is_ok());
+    }
+}
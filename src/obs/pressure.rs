@@ -0,0 +1,249 @@
+//! Pressure Stall Information (PSI)
+//!
+//! System-wide stall averages from `/proc/pressure/{cpu,memory,io}`. IO
+//! and memory stalls directly explain tail latency in mmap-heavy query
+//! paths, often before CPU or RSS graphs show anything.
+//!
+//! # Collected Gauges
+//!
+//! - `pressure_avg10/avg60/avg300{resource,kind}` (percent of wall time)
+//! - `pressure_stall_seconds_total{resource,kind}`
+//! - `pressure_alerts_total{resource}` counter
+//!
+//! `kind` is `some` (at least one task stalled) or `full` (all non-idle
+//! tasks stalled; not reported for cpu on older kernels).
+//!
+//! # Alerts
+//!
+//! Thresholds apply to the `some` avg10 value. An alert fires once when the
+//! value rises above the threshold and re-arms after it drops back below.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::pressure::PressureCollector;
+//!
+//! let collector = PressureCollector::new()
+//!     .alert_above("io", 20.0)
+//!     .alert_above("memory", 10.0)
+//!     .on_alert(|alert| eprintln!("{} stalled {:.1}%", alert.resource, alert.avg10));
+//!
+//! let _handle = collector.spawn(Duration::from_secs(10), telemetry.clone());
+//! ```
+
+use crate::obs::cgroup::{PressureStats, PRESSURE_RESOURCES};
+use crate::obs::logging;
+use crate::obs::process::{spawn_collector, CollectorHandle};
+use crate::obs::telemetry::Telemetry;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Raised when a resource's `some` avg10 crosses its threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PressureAlert {
+    pub resource: &'static str,
+    pub avg10: f64,
+    pub threshold: f64,
+}
+
+/// Callback invoked on pressure alerts.
+pub type PressureAlertCallback = Arc<dyn Fn(&PressureAlert) + Send + Sync>;
+
+struct Threshold {
+    resource: &'static str,
+    avg10: f64,
+    firing: bool,
+}
+
+/// Collector for system-wide PSI.
+pub struct PressureCollector {
+    dir: PathBuf,
+    thresholds: Vec<Threshold>,
+    callbacks: Vec<PressureAlertCallback>,
+}
+
+impl Default for PressureCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PressureCollector {
+    /// Read from `/proc/pressure`.
+    pub fn new() -> Self {
+        Self::with_dir("/proc/pressure")
+    }
+
+    /// Read PSI files from another directory.
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            thresholds: Vec::new(),
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Alert when `resource` ("cpu", "memory", "io") `some` avg10 exceeds
+    /// `avg10_percent`. Unknown resources are ignored.
+    pub fn alert_above(mut self, resource: &str, avg10_percent: f64) -> Self {
+        if let Some(&resource) = PRESSURE_RESOURCES.iter().find(|&&r| r == resource) {
+            self.thresholds.push(Threshold {
+                resource,
+                avg10: avg10_percent,
+                firing: false,
+            });
+        }
+        self
+    }
+
+    /// Invoke `callback` when an alert fires.
+    pub fn on_alert<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&PressureAlert) + Send + Sync + 'static,
+    {
+        self.callbacks.push(Arc::new(callback));
+        self
+    }
+
+    /// Take one sample; `None` for resources without a PSI file.
+    pub fn sample(&self) -> [Option<PressureStats>; 3] {
+        PRESSURE_RESOURCES.map(|resource| {
+            std::fs::read_to_string(self.dir.join(resource))
+                .ok()
+                .map(|content| PressureStats::parse(&content))
+        })
+    }
+
+    /// Sample, write gauges, and evaluate alert thresholds.
+    ///
+    /// Returns the alerts that fired on this call.
+    pub fn collect_into(&mut self, telemetry: &mut Telemetry) -> Vec<PressureAlert> {
+        let stats = self.sample();
+        for (pressure, resource) in stats.iter().zip(PRESSURE_RESOURCES) {
+            if let Some(pressure) = pressure {
+                pressure.record_into(telemetry, "pressure", resource);
+            }
+        }
+
+        let mut fired = Vec::new();
+        for threshold in &mut self.thresholds {
+            let idx = PRESSURE_RESOURCES
+                .iter()
+                .position(|&r| r == threshold.resource)
+                .expect("threshold resources are validated");
+            let Some(some) = stats[idx].and_then(|pressure| pressure.some) else {
+                continue;
+            };
+
+            if some.avg10 <= threshold.avg10 {
+                threshold.firing = false;
+                continue;
+            }
+            if threshold.firing {
+                continue;
+            }
+            threshold.firing = true;
+
+            telemetry.add_to_counter_with_labels(
+                "pressure_alerts_total",
+                &[("resource", threshold.resource)],
+                1,
+            );
+            logging::warn(&format!(
+                "{} pressure stall {:.2}% exceeds {:.2}% (avg10)",
+                threshold.resource, some.avg10, threshold.avg10
+            ));
+            fired.push(PressureAlert {
+                resource: threshold.resource,
+                avg10: some.avg10,
+                threshold: threshold.avg10,
+            });
+        }
+
+        for alert in &fired {
+            for callback in &self.callbacks {
+                callback(alert);
+            }
+        }
+        fired
+    }
+
+    /// Collect into shared telemetry every `interval` on a background thread.
+    pub fn spawn(
+        mut self,
+        interval: Duration,
+        telemetry: Arc<Mutex<Telemetry>>,
+    ) -> CollectorHandle {
+        spawn_collector(
+            "obs-pressure-collector",
+            interval,
+            telemetry,
+            move |telemetry| {
+                self.collect_into(telemetry);
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_io(dir: &std::path::Path, avg10: f64) {
+        std::fs::write(
+            dir.join("io"),
+            format!(
+                "some avg10={:.2} avg60=1.00 avg300=0.50 total=1000000\n\
+                 full avg10=0.10 avg60=0.05 avg300=0.01 total=5000\n",
+                avg10
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_gauges_and_edge_triggered_alerts() {
+        let dir = std::env::temp_dir().join(format!("embeddenator_obs_psi_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_io(&dir, 25.0);
+
+        let mut collector = PressureCollector::with_dir(&dir).alert_above("io", 20.0);
+        let mut telemetry = Telemetry::default_config();
+
+        let fired = collector.collect_into(&mut telemetry);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].resource, "io");
+        assert!(collector.collect_into(&mut telemetry).is_empty());
+
+        write_io(&dir, 5.0);
+        assert!(collector.collect_into(&mut telemetry).is_empty());
+        write_io(&dir, 30.0);
+        assert_eq!(collector.collect_into(&mut telemetry).len(), 1);
+
+        let snapshot = telemetry.snapshot();
+        assert_eq!(
+            snapshot
+                .gauges
+                .get(r#"pressure_avg10{resource="io",kind="some"}"#),
+            Some(&30.0)
+        );
+        assert_eq!(
+            snapshot
+                .counters
+                .get(r#"pressure_alerts_total{resource="io"}"#),
+            Some(&2)
+        );
+        assert!(!snapshot
+            .gauges
+            .contains_key(r#"pressure_avg10{resource="cpu",kind="some"}"#));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_unknown_resource_ignored() {
+        let collector = PressureCollector::new().alert_above("gpu", 1.0);
+        assert!(collector.thresholds.is_empty());
+    }
+}
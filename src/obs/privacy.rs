@@ -0,0 +1,368 @@
+//! Aggregation-Only Export
+//!
+//! Some deployments must not export anything that could identify a user,
+//! a document or a query. An installed [`PrivacyPolicy`] is applied by
+//! every exporter before it serializes a snapshot or spans, so the mode
+//! cannot be bypassed by picking a different backend.
+//!
+//! # What Gets Exported
+//!
+//! - metric and operation names, span names, kinds and IDs
+//! - label values and span attributes replaced with `redacted`, except
+//!   for allowlisted keys; series that become identical are merged
+//! - counters and operation counts rounded down to a bucket (default 10)
+//! - timings, histogram samples, high-resolution metrics and span
+//!   timestamps rounded to a granularity (default 1ms)
+//! - no exemplars and no span events (event names are often log messages)
+//! - error messages and context attributes redacted, no backtraces
+//!
+//! Exporters covered: Prometheus, OTLP JSON, Chrome trace, Zipkin,
+//! snapshot JSON (and so SSE), StatsD, remote write, Parquet, SQLite and
+//! Sentry.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::privacy::PrivacyPolicy;
+//!
+//! PrivacyPolicy::aggregation_only()
+//!     .with_count_bucket(100)
+//!     .with_timing_granularity(Duration::from_millis(10))
+//!     .allow_key("region")
+//!     .install();
+//! ```
+
+use crate::obs::error_tracking::{ErrorEvent, ErrorGroup};
+use crate::obs::hires_timing::{HiResMetricsSnapshot, PS_PER_US};
+use crate::obs::opentelemetry::{AttributeValue, OtelSpan};
+use crate::obs::telemetry::{
+    labeled_key, parse_labels, split_labeled_key, OperationStats, TelemetrySnapshot,
+};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// Replacement for removed label values and attribute values.
+pub const REDACTED: &str = "redacted";
+
+static POLICY: RwLock<Option<PrivacyPolicy>> = RwLock::new(None);
+
+/// Rules for aggregation-only export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrivacyPolicy {
+    count_bucket: u64,
+    timing_granularity_us: u64,
+    allowed_keys: HashSet<String>,
+}
+
+impl Default for PrivacyPolicy {
+    fn default() -> Self {
+        Self::aggregation_only()
+    }
+}
+
+impl PrivacyPolicy {
+    /// Redact all values, bucket counts by 10, round timings to 1ms.
+    pub fn aggregation_only() -> Self {
+        Self {
+            count_bucket: 10,
+            timing_granularity_us: 1000,
+            allowed_keys: HashSet::new(),
+        }
+    }
+
+    /// Round counts down to a multiple of `bucket` (1 keeps exact counts).
+    pub fn with_count_bucket(mut self, bucket: u64) -> Self {
+        self.count_bucket = bucket.max(1);
+        self
+    }
+
+    /// Round timings to the nearest multiple of `granularity` (at least
+    /// 1us).
+    pub fn with_timing_granularity(mut self, granularity: Duration) -> Self {
+        self.timing_granularity_us = (granularity.as_micros() as u64).max(1);
+        self
+    }
+
+    /// Keep values of label or attribute `key`, for low-cardinality keys
+    /// known to be safe such as `region` or `status`.
+    pub fn allow_key(mut self, key: impl Into<String>) -> Self {
+        self.allowed_keys.insert(key.into());
+        self
+    }
+
+    /// Enforce this policy in every exporter, replacing any installed one.
+    pub fn install(self) {
+        if let Ok(mut policy) = POLICY.write() {
+            *policy = Some(self);
+        }
+    }
+
+    /// Return exporters to exporting raw data.
+    pub fn uninstall() {
+        if let Ok(mut policy) = POLICY.write() {
+            *policy = None;
+        }
+    }
+
+    /// The installed policy, if any.
+    pub fn installed() -> Option<Self> {
+        POLICY.read().ok().and_then(|policy| policy.clone())
+    }
+
+    /// `snapshot` as it may be exported under this policy.
+    pub fn apply_snapshot(&self, snapshot: &TelemetrySnapshot) -> TelemetrySnapshot {
+        let mut counters: HashMap<String, u64> = HashMap::new();
+        for (key, &value) in &snapshot.counters {
+            *counters.entry(self.redact_key(key)).or_insert(0) += value;
+        }
+        for value in counters.values_mut() {
+            *value = self.bucket(*value);
+        }
+
+        // Merged gauges keep the largest value; a sum of levels means nothing
+        let mut gauges: HashMap<String, f64> = HashMap::new();
+        for (key, &value) in &snapshot.gauges {
+            gauges
+                .entry(self.redact_key(key))
+                .and_modify(|merged| *merged = merged.max(value))
+                .or_insert(value);
+        }
+
+        let operation_stats = snapshot
+            .operation_stats
+            .iter()
+            .map(|(name, stats)| (name.clone(), self.apply_stats(stats)))
+            .collect();
+
+        TelemetrySnapshot {
+            timestamp_secs: snapshot.timestamp_secs,
+            uptime_secs: snapshot.uptime_secs,
+            since_last_snapshot_secs: snapshot.since_last_snapshot_secs,
+            operation_stats,
+            counters,
+            gauges,
+            metrics: snapshot.metrics,
+            quality: snapshot.quality,
+            hires: snapshot
+                .hires
+                .iter()
+                .map(|(name, stats)| (name.clone(), self.apply_hires(stats)))
+                .collect(),
+            errors: snapshot
+                .errors
+                .iter()
+                .map(|group| ErrorGroup {
+                    message: REDACTED.to_string(),
+                    count: self.bucket(group.count),
+                    ..group.clone()
+                })
+                .collect(),
+            registry: snapshot.registry.clone(),
+        }
+    }
+
+    /// `event` as it may be exported under this policy: message and
+    /// attribute values redacted (except allowlisted keys), no backtrace.
+    pub fn apply_error(&self, event: &ErrorEvent) -> ErrorEvent {
+        let mut event = event.clone();
+        event.message = REDACTED.to_string();
+        event.backtrace = None;
+        for (key, value) in event.attributes.iter_mut() {
+            if !self.allowed_keys.contains(key) {
+                *value = AttributeValue::from(REDACTED);
+            }
+        }
+        event
+    }
+
+    /// `spans` as they may be exported under this policy.
+    pub fn apply_spans(&self, spans: &[OtelSpan]) -> Vec<OtelSpan> {
+        let granularity_ns = self.timing_granularity_us.saturating_mul(1000);
+        spans
+            .iter()
+            .map(|span| {
+                let mut span = span.clone();
+                for (key, value) in span.attributes.iter_mut() {
+                    if !self.allowed_keys.contains(key) {
+                        *value = AttributeValue::from(REDACTED);
+                    }
+                }
+                span.events.clear();
+                if span.end_time_ns != 0 {
+                    let duration = round(span.end_time_ns - span.start_time_ns, granularity_ns);
+                    span.start_time_ns = round(span.start_time_ns, granularity_ns);
+                    span.end_time_ns = span.start_time_ns + duration;
+                } else {
+                    span.start_time_ns = round(span.start_time_ns, granularity_ns);
+                }
+                span
+            })
+            .collect()
+    }
+
+    fn apply_hires(&self, stats: &HiResMetricsSnapshot) -> HiResMetricsSnapshot {
+        let granularity_ps = self.timing_granularity_us.saturating_mul(PS_PER_US);
+        let count = self.bucket(stats.count);
+        let mean_ps = round(stats.mean_ps, granularity_ps);
+        HiResMetricsSnapshot {
+            count,
+            total_ps: mean_ps.saturating_mul(count),
+            min_ps: round(stats.min_ps, granularity_ps),
+            max_ps: round(stats.max_ps, granularity_ps),
+            mean_ps,
+            stddev_ps: round(stats.stddev_ps, granularity_ps),
+            p50_ps: round(stats.p50_ps, granularity_ps),
+            p95_ps: round(stats.p95_ps, granularity_ps),
+            p99_ps: round(stats.p99_ps, granularity_ps),
+            p999_ps: round(stats.p999_ps, granularity_ps),
+        }
+    }
+
+    fn apply_stats(&self, stats: &OperationStats) -> OperationStats {
+        let granularity = self.timing_granularity_us;
+        let histogram: Vec<u64> = stats
+            .histogram
+            .iter()
+            .map(|&sample| round(sample, granularity))
+            .collect();
+        let count = self.bucket(stats.count);
+        // Scale the sample variance up to the bucketed count
+        let sum_of_squares = if histogram.is_empty() {
+            0.0
+        } else {
+            let squares: f64 = histogram.iter().map(|&s| (s as f64) * (s as f64)).sum();
+            squares * count as f64 / histogram.len() as f64
+        };
+        OperationStats {
+            count,
+            total_us: round(stats.avg_us() as u64, granularity) * count,
+            min_us: round(stats.min_us, granularity),
+            max_us: round(stats.max_us, granularity),
+            last_us: round(stats.last_us, granularity),
+            histogram,
+            sum_of_squares,
+            exemplar: None,
+        }
+    }
+
+    fn redact_key(&self, key: &str) -> String {
+        let (name, Some(body)) = split_labeled_key(key) else {
+            return key.to_string();
+        };
+        let labels = parse_labels(body);
+        let labels: Vec<(&str, &str)> = labels
+            .iter()
+            .map(|(label, value)| {
+                if self.allowed_keys.contains(*label) {
+                    (*label, value.as_str())
+                } else {
+                    (*label, REDACTED)
+                }
+            })
+            .collect();
+        labeled_key(name, &labels)
+    }
+
+    fn bucket(&self, count: u64) -> u64 {
+        count / self.count_bucket * self.count_bucket
+    }
+}
+
+/// Nearest multiple of `granularity`.
+fn round(value: u64, granularity: u64) -> u64 {
+    value.saturating_add(granularity / 2) / granularity * granularity
+}
+
+/// `snapshot` under the installed policy; borrowed when none is installed.
+pub(crate) fn enforce_snapshot(snapshot: &TelemetrySnapshot) -> Cow<'_, TelemetrySnapshot> {
+    match POLICY.read().ok().as_deref().and_then(Option::as_ref) {
+        Some(policy) => Cow::Owned(policy.apply_snapshot(snapshot)),
+        None => Cow::Borrowed(snapshot),
+    }
+}
+
+/// `event` under the installed policy; borrowed when none is installed.
+pub(crate) fn enforce_error(event: &ErrorEvent) -> Cow<'_, ErrorEvent> {
+    match POLICY.read().ok().as_deref().and_then(Option::as_ref) {
+        Some(policy) => Cow::Owned(policy.apply_error(event)),
+        None => Cow::Borrowed(event),
+    }
+}
+
+/// `spans` under the installed policy; borrowed when none is installed.
+pub(crate) fn enforce_spans(spans: &[OtelSpan]) -> Cow<'_, [OtelSpan]> {
+    match POLICY.read().ok().as_deref().and_then(Option::as_ref) {
+        Some(policy) => Cow::Owned(policy.apply_spans(spans)),
+        None => Cow::Borrowed(spans),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obs::telemetry::Telemetry;
+
+    #[test]
+    fn test_snapshot_values_redacted_and_bucketed() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.add_to_counter_with_labels("logins_total", &[("user", "alice")], 7);
+        telemetry.add_to_counter_with_labels("logins_total", &[("user", "bob")], 5);
+        telemetry.add_to_counter_with_labels(
+            "logins_total",
+            &[("user", "carol"), ("region", "eu")],
+            12,
+        );
+        telemetry.set_gauge_with_labels("session_bytes", &[("user", "alice")], 10.0);
+        telemetry.set_gauge_with_labels("session_bytes", &[("user", "bob")], 30.0);
+        for us in [1234, 2600, 900] {
+            telemetry.record_operation("query", us);
+        }
+
+        let policy = PrivacyPolicy::aggregation_only().allow_key("region");
+        let snapshot = policy.apply_snapshot(&telemetry.snapshot());
+
+        // alice and bob merge into 12, bucketed to 10
+        assert_eq!(snapshot.counters[r#"logins_total{user="redacted"}"#], 10);
+        assert_eq!(
+            snapshot.counters[r#"logins_total{user="redacted",region="eu"}"#],
+            10
+        );
+        assert_eq!(snapshot.gauges.len(), 1);
+        assert_eq!(snapshot.gauges[r#"session_bytes{user="redacted"}"#], 30.0);
+
+        let stats = &snapshot.operation_stats["query"];
+        assert_eq!(stats.count, 0);
+        assert_eq!(stats.histogram, vec![1000, 3000, 1000]);
+        assert_eq!((stats.min_us, stats.max_us), (1000, 3000));
+        assert!(stats.exemplar.is_none());
+
+        let exact = PrivacyPolicy::aggregation_only().with_count_bucket(1);
+        let snapshot = exact.apply_snapshot(&telemetry.snapshot());
+        assert_eq!(snapshot.counters[r#"logins_total{user="redacted"}"#], 12);
+        assert_eq!(snapshot.operation_stats["query"].count, 3);
+    }
+
+    #[test]
+    fn test_span_attributes_and_events_removed() {
+        let mut span = OtelSpan::new("search");
+        span.set_attribute("query", "alice@example.com");
+        span.set_attribute("region", "eu");
+        span.add_event("cache miss for alice@example.com");
+        span.start_time_ns = 1_000_400_000;
+        span.end_time_ns = 1_002_700_000;
+
+        let policy = PrivacyPolicy::aggregation_only()
+            .with_timing_granularity(Duration::from_millis(1))
+            .allow_key("region");
+        let spans = policy.apply_spans(&[span]);
+
+        assert_eq!(spans[0].name, "search");
+        assert_eq!(spans[0].attributes["query"], REDACTED);
+        assert_eq!(spans[0].attributes["region"], "eu");
+        assert!(spans[0].events.is_empty());
+        assert_eq!(spans[0].start_time_ns, 1_000_000_000);
+        assert_eq!(spans[0].end_time_ns, 1_002_000_000);
+    }
+}
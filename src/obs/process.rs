@@ -0,0 +1,503 @@
+//! Process Resource Collector
+//!
+//! Samples resource usage of the current process and feeds it into
+//! [`Telemetry`] gauges using the standard Prometheus `process_*` names,
+//! so the Prometheus export includes them (under the exporter prefix).
+//!
+//! # Collected Gauges
+//!
+//! - `process_resident_memory_bytes`
+//! - `process_virtual_memory_bytes`
+//! - `process_cpu_seconds_total`
+//! - `process_threads`
+//! - `process_open_fds` / `process_max_fds`
+//! - `process_start_time_seconds`
+//!
+//! Values are read from `/proc/self` on Linux. On other platforms
+//! sampling yields no values and no gauges are set.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::process::ProcessCollector;
+//! use std::sync::{Arc, Mutex};
+//!
+//! let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+//!
+//! // One-shot
+//! ProcessCollector::new().collect_into(&mut telemetry.lock().unwrap());
+//!
+//! // Periodic (stops when the handle is dropped)
+//! let _handle = ProcessCollector::new().spawn(Duration::from_secs(15), telemetry.clone());
+//! ```
+
+use crate::obs::telemetry::Telemetry;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Kernel clock ticks per second (`USER_HZ`), fixed at 100 on Linux.
+#[cfg(target_os = "linux")]
+const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+/// One sample of process resource usage. `None` = unavailable on this platform.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProcessStats {
+    pub resident_memory_bytes: Option<u64>,
+    pub virtual_memory_bytes: Option<u64>,
+    pub cpu_seconds_total: Option<f64>,
+    pub threads: Option<u64>,
+    pub open_fds: Option<u64>,
+    pub max_fds: Option<u64>,
+    pub start_time_seconds: Option<f64>,
+}
+
+/// Collector for process resource metrics.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessCollector {
+    _private: (),
+}
+
+/// Handle to a background collection thread; stops the thread on drop.
+pub struct CollectorHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProcessCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take one sample of process resource usage.
+    pub fn sample(&self) -> ProcessStats {
+        #[cfg(target_os = "linux")]
+        {
+            linux::sample()
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            ProcessStats::default()
+        }
+    }
+
+    /// Sample and write available values into telemetry gauges.
+    pub fn collect_into(&self, telemetry: &mut Telemetry) -> ProcessStats {
+        let stats = self.sample();
+        let gauges = [
+            (
+                "process_resident_memory_bytes",
+                stats.resident_memory_bytes.map(|v| v as f64),
+            ),
+            (
+                "process_virtual_memory_bytes",
+                stats.virtual_memory_bytes.map(|v| v as f64),
+            ),
+            ("process_cpu_seconds_total", stats.cpu_seconds_total),
+            ("process_threads", stats.threads.map(|v| v as f64)),
+            ("process_open_fds", stats.open_fds.map(|v| v as f64)),
+            ("process_max_fds", stats.max_fds.map(|v| v as f64)),
+            ("process_start_time_seconds", stats.start_time_seconds),
+        ];
+        for (name, value) in gauges {
+            if let Some(value) = value {
+                telemetry.set_gauge(name, value);
+            }
+        }
+        stats
+    }
+
+    /// Collect into shared telemetry every `interval` on a background thread.
+    pub fn spawn(self, interval: Duration, telemetry: Arc<Mutex<Telemetry>>) -> CollectorHandle {
+        spawn_collector(
+            "obs-process-collector",
+            interval,
+            telemetry,
+            move |telemetry| {
+                self.collect_into(telemetry);
+            },
+        )
+    }
+}
+
+/// Run `collect` against shared telemetry every `interval` until stopped.
+pub(crate) fn spawn_collector(
+    thread_name: &str,
+    interval: Duration,
+    telemetry: Arc<Mutex<Telemetry>>,
+    mut collect: impl FnMut(&mut Telemetry) + Send + 'static,
+) -> CollectorHandle {
+    spawn_periodic(thread_name, interval, move || {
+        if let Ok(mut telemetry) = telemetry.lock() {
+            collect(&mut telemetry);
+        }
+    })
+}
+
+/// Run `tick` every `interval` on a named thread until stopped.
+///
+/// The first tick happens immediately, before the stop check.
+pub(crate) fn spawn_periodic(
+    thread_name: &str,
+    interval: Duration,
+    tick: impl FnMut() + Send + 'static,
+) -> CollectorHandle {
+    spawn_worker(thread_name, interval, None, tick)
+}
+
+/// [`spawn_periodic`] for export pipelines: [`force_flush`] runs an extra
+/// tick and waits for it.
+pub(crate) fn spawn_sink(
+    thread_name: &str,
+    interval: Duration,
+    tick: impl FnMut() + Send + 'static,
+) -> CollectorHandle {
+    let flush = Arc::new(FlushState::default());
+    if let Ok(mut sinks) = SINKS.lock() {
+        sinks.retain(|(_, sink)| sink.strong_count() > 0);
+        sinks.push((thread_name.to_string(), Arc::downgrade(&flush)));
+    }
+    spawn_worker(thread_name, interval, Some(flush), tick)
+}
+
+/// [`spawn_collector`] registered as a flushable sink.
+pub(crate) fn spawn_sink_collector(
+    thread_name: &str,
+    interval: Duration,
+    telemetry: Arc<Mutex<Telemetry>>,
+    mut collect: impl FnMut(&mut Telemetry) + Send + 'static,
+) -> CollectorHandle {
+    spawn_sink(thread_name, interval, move || {
+        if let Ok(mut telemetry) = telemetry.lock() {
+            collect(&mut telemetry);
+        }
+    })
+}
+
+fn spawn_worker(
+    thread_name: &str,
+    interval: Duration,
+    flush: Option<Arc<FlushState>>,
+    mut tick: impl FnMut() + Send + 'static,
+) -> CollectorHandle {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_flag = stop.clone();
+
+    let thread = std::thread::Builder::new()
+        .name(thread_name.to_string())
+        .spawn(move || loop {
+            // Requests made before this tick started are served by it
+            let requested = flush.as_ref().map(|f| f.requested.load(Ordering::Acquire));
+            tick();
+            match (&flush, requested) {
+                (Some(flush), Some(requested)) => {
+                    flush.complete(requested);
+                    sleep_until_flush(interval, &stop_flag, flush, requested);
+                }
+                _ => sleep_unless_stopped(interval, &stop_flag),
+            }
+            if stop_flag.load(Ordering::Relaxed) {
+                break;
+            }
+        })
+        .expect("failed to spawn collector thread");
+
+    CollectorHandle {
+        stop,
+        thread: Some(thread),
+    }
+}
+
+/// Sleep in short slices so a stop request is honoured promptly.
+pub(crate) fn sleep_unless_stopped(total: Duration, stop: &AtomicBool) {
+    let slice = Duration::from_millis(50);
+    let mut remaining = total;
+    while !remaining.is_zero() && !stop.load(Ordering::Relaxed) {
+        let step = remaining.min(slice);
+        std::thread::sleep(step);
+        remaining -= step;
+    }
+}
+
+/// Like [`sleep_unless_stopped`], but also wakes when a flush newer than
+/// `served` is requested.
+fn sleep_until_flush(total: Duration, stop: &AtomicBool, flush: &FlushState, served: u64) {
+    let slice = Duration::from_millis(5);
+    let deadline = Instant::now() + total;
+    while !stop.load(Ordering::Relaxed) && flush.requested.load(Ordering::Acquire) == served {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        std::thread::sleep(slice.min(deadline - now));
+    }
+}
+
+/// Flush requests of one sink worker.
+#[derive(Default)]
+struct FlushState {
+    requested: AtomicU64,
+    completed: Mutex<u64>,
+    done: Condvar,
+}
+
+impl FlushState {
+    fn complete(&self, requested: u64) {
+        if let Ok(mut completed) = self.completed.lock() {
+            *completed = (*completed).max(requested);
+            self.done.notify_all();
+        }
+    }
+
+    /// Wait until request `id` is served or `deadline` passes.
+    fn wait(&self, id: u64, deadline: Instant) -> bool {
+        let Ok(mut completed) = self.completed.lock() else {
+            return false;
+        };
+        while *completed < id {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            completed = match self.done.wait_timeout(completed, deadline - now) {
+                Ok((completed, _)) => completed,
+                Err(_) => return false,
+            };
+        }
+        true
+    }
+}
+
+/// Export pipelines started with a `spawn` method, by thread name.
+static SINKS: Mutex<Vec<(String, Weak<FlushState>)>> = Mutex::new(Vec::new());
+
+/// Outcome of [`force_flush`], by sink thread name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlushReport {
+    pub flushed: Vec<String>,
+    pub timed_out: Vec<String>,
+}
+
+impl FlushReport {
+    /// Every sink finished within the timeout.
+    pub fn is_complete(&self) -> bool {
+        self.timed_out.is_empty()
+    }
+}
+
+/// Synchronously run every running export pipeline once (remote write,
+/// StatsD, Parquet, SQLite, history file, alert notifier and digest) and wait for
+/// all of them, up to `timeout` in total.
+///
+/// Meant for tests and short-lived CLIs that must not exit with data
+/// still queued. Each sink exports whatever it has at the time of the
+/// call; sinks that are mid-export finish that round first.
+///
+/// ```rust,ignore
+/// let report = embeddenator_obs::force_flush(Duration::from_secs(5));
+/// if !report.is_complete() {
+///     eprintln!("flush timed out: {:?}", report.timed_out);
+/// }
+/// ```
+pub fn force_flush(timeout: Duration) -> FlushReport {
+    let deadline = Instant::now() + timeout;
+    let sinks: Vec<(String, Arc<FlushState>)> = match SINKS.lock() {
+        Ok(mut sinks) => {
+            sinks.retain(|(_, sink)| sink.strong_count() > 0);
+            sinks
+                .iter()
+                .filter_map(|(name, sink)| Some((name.clone(), sink.upgrade()?)))
+                .collect()
+        }
+        Err(_) => Vec::new(),
+    };
+
+    // Request all first so sinks flush in parallel
+    let requests: Vec<u64> = sinks
+        .iter()
+        .map(|(_, sink)| sink.requested.fetch_add(1, Ordering::AcqRel) + 1)
+        .collect();
+
+    let mut report = FlushReport::default();
+    for ((name, sink), id) in sinks.into_iter().zip(requests) {
+        if sink.wait(id, deadline) {
+            report.flushed.push(name);
+        } else {
+            report.timed_out.push(name);
+        }
+    }
+    report
+}
+
+impl CollectorHandle {
+    /// Stop the collection thread and wait for it to exit.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for CollectorHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{ProcessStats, CLOCK_TICKS_PER_SEC};
+
+    pub(super) fn sample() -> ProcessStats {
+        let mut stats = ProcessStats::default();
+
+        if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+            stats.resident_memory_bytes = status_kb(&status, "VmRSS:").map(|kb| kb * 1024);
+            stats.virtual_memory_bytes = status_kb(&status, "VmSize:").map(|kb| kb * 1024);
+            stats.threads = status_kb(&status, "Threads:");
+        }
+
+        if let Ok(stat) = std::fs::read_to_string("/proc/self/stat") {
+            // Fields after the parenthesised command name (which may contain spaces)
+            if let Some(rest) = stat.rsplit_once(')').map(|(_, rest)| rest) {
+                let fields: Vec<&str> = rest.split_whitespace().collect();
+                // rest[0] is field 3 (state); utime=14, stime=15, starttime=22
+                let field = |n: usize| fields.get(n - 3).and_then(|v| v.parse::<u64>().ok());
+                if let (Some(utime), Some(stime)) = (field(14), field(15)) {
+                    stats.cpu_seconds_total = Some((utime + stime) as f64 / CLOCK_TICKS_PER_SEC);
+                }
+                if let (Some(start_ticks), Some(btime)) = (field(22), boot_time()) {
+                    stats.start_time_seconds =
+                        Some(btime as f64 + start_ticks as f64 / CLOCK_TICKS_PER_SEC);
+                }
+            }
+        }
+
+        if let Ok(entries) = std::fs::read_dir("/proc/self/fd") {
+            stats.open_fds = Some(entries.count() as u64);
+        }
+
+        if let Ok(limits) = std::fs::read_to_string("/proc/self/limits") {
+            stats.max_fds = limits
+                .lines()
+                .find(|line| line.starts_with("Max open files"))
+                .and_then(|line| line.split_whitespace().nth(3))
+                .and_then(|soft| soft.parse().ok());
+        }
+
+        stats
+    }
+
+    fn status_kb(status: &str, key: &str) -> Option<u64> {
+        status
+            .lines()
+            .find(|line| line.starts_with(key))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|v| v.parse().ok())
+    }
+
+    fn boot_time() -> Option<u64> {
+        std::fs::read_to_string("/proc/stat")
+            .ok()?
+            .lines()
+            .find(|line| line.starts_with("btime"))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|v| v.parse().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_sample_linux() {
+        let stats = ProcessCollector::new().sample();
+
+        assert!(stats.resident_memory_bytes.unwrap() > 0);
+        assert!(stats.virtual_memory_bytes.unwrap() >= stats.resident_memory_bytes.unwrap());
+        assert!(stats.threads.unwrap() >= 1);
+        assert!(stats.open_fds.unwrap() >= 1);
+        assert!(stats.cpu_seconds_total.is_some());
+        assert!(stats.start_time_seconds.unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_collect_into_telemetry() {
+        let mut telemetry = Telemetry::default_config();
+        let stats = ProcessCollector::new().collect_into(&mut telemetry);
+
+        let snapshot = telemetry.snapshot();
+        assert_eq!(
+            snapshot
+                .gauges
+                .get("process_resident_memory_bytes")
+                .copied(),
+            stats.resident_memory_bytes.map(|v| v as f64)
+        );
+    }
+
+    #[test]
+    fn test_spawn_and_stop() {
+        let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+        let handle = ProcessCollector::new().spawn(Duration::from_secs(60), telemetry.clone());
+
+        // First collection happens immediately, before the stop check
+        handle.stop();
+
+        #[cfg(target_os = "linux")]
+        assert!(telemetry
+            .lock()
+            .unwrap()
+            .snapshot()
+            .gauges
+            .contains_key("process_threads"));
+    }
+
+    #[test]
+    fn test_force_flush_runs_sinks() {
+        use std::sync::atomic::AtomicU32;
+        use std::sync::mpsc;
+
+        let ticks = Arc::new(AtomicU32::new(0));
+        let counter = ticks.clone();
+        let sink = spawn_sink("obs-test-flush", Duration::from_secs(3600), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        // A sink stuck mid-export times out
+        let (release, blocked) = mpsc::channel::<()>();
+        let (started, first_tick) = mpsc::channel::<()>();
+        let mut first = true;
+        let stuck = spawn_sink("obs-test-stuck", Duration::from_secs(3600), move || {
+            if std::mem::take(&mut first) {
+                let _ = started.send(());
+            } else {
+                let _ = blocked.recv();
+            }
+        });
+        first_tick.recv().unwrap();
+        while ticks.load(Ordering::SeqCst) == 0 {
+            std::thread::yield_now();
+        }
+
+        let report = force_flush(Duration::from_millis(200));
+        assert!(report.flushed.contains(&"obs-test-flush".to_string()));
+        assert!(report.timed_out.contains(&"obs-test-stuck".to_string()));
+        assert!(!report.is_complete());
+        assert_eq!(ticks.load(Ordering::SeqCst), 2);
+
+        release.send(()).unwrap();
+        stuck.stop();
+        sink.stop();
+        let report = force_flush(Duration::from_millis(200));
+        assert!(!report.flushed.contains(&"obs-test-flush".to_string()));
+    }
+}
@@ -7,9 +7,11 @@
 //!
 //! - Counter metrics export
 //! - Gauge metrics export
-//! - Histogram buckets for operation timings
+//! - Histogram buckets for operation timings (or summaries with quantiles)
 //! - Label support for metric dimensions
 //! - Text format output (Prometheus standard)
+//! - OpenMetrics output with exemplars and content-type negotiation
+//! - Detection of telemetry names that collide with built-in metrics
 //!
 //! # Usage
 //!
@@ -22,10 +24,71 @@
 //!
 //! // Serve via HTTP endpoint
 //! // GET /metrics -> prometheus_text
+//!
+//! // Or negotiate from the scraper's Accept header
+//! let exporter = exporter.with_format(ExpositionFormat::from_accept(accept_header));
+//! let (body, content_type) = (exporter.export(&snapshot), exporter.content_type());
+//! ```
+//!
+//! # Name Collisions
+//!
+//! A telemetry counter or gauge named like a built-in metric (e.g.
+//! `sub_cache_hits`) would be exported as a second series of the same
+//! family with a conflicting value. The exporter detects this and applies
+//! a [`CollisionPolicy`], logging a warning once per name:
+//!
+//! ```rust,ignore
+//! let exporter = PrometheusExporter::new("embeddenator")
+//!     .with_collision_policy(CollisionPolicy::Error);
+//! let body = exporter.try_export(&snapshot)?;
 //! ```
 
-use crate::obs::telemetry::TelemetrySnapshot;
+use crate::obs::hires_timing::{HiResMetricsSnapshot, PS_PER_NS};
+use crate::obs::metrics::{EvictionReason, ShapeTimingsSnapshot};
+use crate::obs::privacy;
+use crate::obs::quality::{QualitySnapshot, SCORE_BUCKETS};
+use crate::obs::registry::MetricDescriptor;
+use crate::obs::telemetry::{split_labeled_key, Exemplar, TelemetrySnapshot};
+use crate::obs::tracing::{record_event, EventLevel};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Text exposition format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExpositionFormat {
+    /// Prometheus text format 0.0.4
+    #[default]
+    Prometheus,
+    /// OpenMetrics 1.0 text format
+    OpenMetrics,
+}
+
+impl ExpositionFormat {
+    /// Pick the format from an HTTP `Accept` header.
+    ///
+    /// OpenMetrics is chosen only when the scraper asks for it.
+    pub fn from_accept(accept: &str) -> Self {
+        if accept
+            .split(',')
+            .any(|media| media.trim().starts_with("application/openmetrics-text"))
+        {
+            ExpositionFormat::OpenMetrics
+        } else {
+            ExpositionFormat::Prometheus
+        }
+    }
+
+    /// HTTP `Content-Type` for responses in this format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExpositionFormat::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+            ExpositionFormat::OpenMetrics => {
+                "application/openmetrics-text; version=1.0.0; charset=utf-8"
+            }
+        }
+    }
+}
 
 /// Prometheus metrics exporter.
 pub struct PrometheusExporter {
@@ -35,6 +98,130 @@ pub struct PrometheusExporter {
     include_help: bool,
     /// Include type annotations
     include_type: bool,
+    /// Output format
+    format: ExpositionFormat,
+    /// Operations exported as summaries instead of histograms
+    summary_operations: HashSet<String>,
+    /// Export every operation as a summary
+    summary_by_default: bool,
+    /// Summary quantiles (0.0 to 1.0)
+    quantiles: Vec<f64>,
+    /// Histogram bucket bounds (microseconds) for operations without their own
+    default_buckets: Vec<u64>,
+    /// Per-operation histogram bucket bounds (microseconds)
+    operation_buckets: HashMap<String, Vec<u64>>,
+    /// Handling of telemetry names that collide with built-in metrics
+    collision_policy: CollisionPolicy,
+    /// Colliding names already warned about
+    warned_collisions: Mutex<HashSet<String>>,
+}
+
+/// Families exported from built-in [`Metrics`](crate::obs::metrics::Metrics),
+/// quality stats and uptime, before the prefix is applied.
+pub const BUILTIN_FAMILIES: &[&str] = &[
+    "sub_cache_hits",
+    "sub_cache_misses",
+    "index_cache_evictions",
+    "sub_cache_evictions_by_reason",
+    "index_cache_evictions_by_reason",
+    "retrieval_query_shape_calls",
+    "retrieval_query_shape_ns_total",
+    "rerank_shape_calls",
+    "rerank_shape_ns_total",
+    "poison_recoveries_total",
+    "panics_total",
+    "quality_queries",
+    "quality_empty_results",
+    "rerank_queries",
+    "rerank_top1_changes",
+    "query_topk_score",
+    "uptime_seconds",
+];
+
+/// Whether a telemetry counter or gauge `name` (labels ignored) would be
+/// exported under the family of a built-in metric.
+///
+/// `_total` suffixes are ignored, as OpenMetrics strips them from family
+/// names.
+pub fn collides_with_builtin(name: &str) -> bool {
+    let (name, _) = split_labeled_key(name);
+    let name = sanitize_name(name);
+    let family = name.strip_suffix("_total").unwrap_or(&name);
+    BUILTIN_FAMILIES
+        .iter()
+        .any(|builtin| builtin.strip_suffix("_total").unwrap_or(builtin) == family)
+}
+
+/// What the exporter does with a telemetry metric whose name collides
+/// with a built-in metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollisionPolicy {
+    /// Fail [`PrometheusExporter::try_export`]; `export` drops the
+    /// telemetry series
+    Error,
+    /// Export the telemetry series as `user_<name>`
+    #[default]
+    PrefixUser,
+    /// Drop the telemetry series and keep the built-in one
+    PreferBuiltIn,
+}
+
+impl CollisionPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CollisionPolicy::Error => "error",
+            CollisionPolicy::PrefixUser => "prefix-user",
+            CollisionPolicy::PreferBuiltIn => "prefer-built-in",
+        }
+    }
+}
+
+/// Telemetry metrics that collide with built-in metrics, returned by
+/// [`PrometheusExporter::try_export`] under [`CollisionPolicy::Error`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameCollisionError {
+    /// Colliding telemetry names, sorted
+    pub names: Vec<String>,
+}
+
+impl std::fmt::Display for NameCollisionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "telemetry metrics collide with built-in metrics: {}",
+            self.names.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for NameCollisionError {}
+
+/// Default histogram bucket bounds in microseconds: 100us to 100ms.
+pub const DEFAULT_BUCKETS_US: [u64; 7] = [100, 500, 1000, 5000, 10000, 50000, 100000];
+
+/// `count` bucket bounds `start, start + width, ...`.
+pub fn linear_buckets(start: u64, width: u64, count: usize) -> Vec<u64> {
+    (0..count as u64)
+        .map(|i| start.saturating_add(width.saturating_mul(i)))
+        .collect()
+}
+
+/// `count` bucket bounds `start, start * factor, ...`, rounded to whole
+/// microseconds; bounds that round to the same value are merged.
+pub fn exponential_buckets(start: u64, factor: f64, count: usize) -> Vec<u64> {
+    let mut buckets: Vec<u64> = (0..count as i32)
+        .map(|i| (start as f64 * factor.powi(i)).round() as u64)
+        .collect();
+    buckets.dedup();
+    buckets
+}
+
+/// Sorted, deduplicated bucket bounds.
+fn normalize_buckets(buckets: &[u64]) -> Vec<u64> {
+    let mut buckets = buckets.to_vec();
+    buckets.sort_unstable();
+    buckets.dedup();
+    buckets
 }
 
 impl PrometheusExporter {
@@ -44,9 +231,80 @@ impl PrometheusExporter {
             prefix: prefix.into(),
             include_help: true,
             include_type: true,
+            format: ExpositionFormat::Prometheus,
+            summary_operations: HashSet::new(),
+            summary_by_default: false,
+            quantiles: vec![0.5, 0.95, 0.99],
+            default_buckets: DEFAULT_BUCKETS_US.to_vec(),
+            operation_buckets: HashMap::new(),
+            collision_policy: CollisionPolicy::default(),
+            warned_collisions: Mutex::new(HashSet::new()),
         }
     }
 
+    /// Select the output format.
+    pub fn with_format(mut self, format: ExpositionFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Emit OpenMetrics instead of Prometheus text format.
+    pub fn openmetrics(self) -> Self {
+        self.with_format(ExpositionFormat::OpenMetrics)
+    }
+
+    /// HTTP `Content-Type` matching the configured format.
+    pub fn content_type(&self) -> &'static str {
+        self.format.content_type()
+    }
+
+    /// Export `operation` as a summary with client-side quantiles.
+    pub fn summary_for(mut self, operation: impl Into<String>) -> Self {
+        self.summary_operations.insert(operation.into());
+        self
+    }
+
+    /// Export every operation as a summary instead of a histogram.
+    pub fn summaries_by_default(mut self) -> Self {
+        self.summary_by_default = true;
+        self
+    }
+
+    /// Quantiles reported by summaries (default: 0.5, 0.95, 0.99).
+    ///
+    /// Values outside 0.0 to 1.0 are dropped.
+    pub fn with_quantiles(mut self, quantiles: &[f64]) -> Self {
+        self.quantiles = quantiles
+            .iter()
+            .copied()
+            .filter(|q| (0.0..=1.0).contains(q))
+            .collect();
+        self
+    }
+
+    /// Histogram bucket bounds (microseconds) for all operations without
+    /// their own buckets. Default: [`DEFAULT_BUCKETS_US`].
+    pub fn with_buckets(mut self, buckets_us: &[u64]) -> Self {
+        self.default_buckets = normalize_buckets(buckets_us);
+        self
+    }
+
+    /// Histogram bucket bounds (microseconds) for one operation.
+    ///
+    /// ```rust,ignore
+    /// let exporter = PrometheusExporter::new("embeddenator")
+    ///     .with_operation_buckets("retrieval_query", &exponential_buckets(10, 2.0, 12));
+    /// ```
+    pub fn with_operation_buckets(
+        mut self,
+        operation: impl Into<String>,
+        buckets_us: &[u64],
+    ) -> Self {
+        self.operation_buckets
+            .insert(operation.into(), normalize_buckets(buckets_us));
+        self
+    }
+
     /// Disable help text (reduces output size).
     pub fn without_help(mut self) -> Self {
         self.include_help = false;
@@ -59,23 +317,106 @@ impl PrometheusExporter {
         self
     }
 
+    /// Handling of telemetry names that collide with built-in metrics
+    /// (default: [`CollisionPolicy::PrefixUser`]).
+    pub fn with_collision_policy(mut self, policy: CollisionPolicy) -> Self {
+        self.collision_policy = policy;
+        self
+    }
+
+    /// Telemetry counter and gauge names in `snapshot` that collide with
+    /// built-in metrics, sorted.
+    pub fn collisions(&self, snapshot: &TelemetrySnapshot) -> Vec<String> {
+        let mut names: Vec<String> = snapshot
+            .counters
+            .keys()
+            .chain(snapshot.gauges.keys())
+            .map(|key| split_labeled_key(key).0)
+            .filter(|name| {
+                let exported = snapshot
+                    .registry
+                    .get(name)
+                    .map_or_else(|| name.to_string(), |d| d.unit_suffixed(name));
+                collides_with_builtin(&exported)
+            })
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Export snapshot, failing under [`CollisionPolicy::Error`] when a
+    /// telemetry metric collides with a built-in metric.
+    pub fn try_export(&self, snapshot: &TelemetrySnapshot) -> Result<String, NameCollisionError> {
+        if self.collision_policy == CollisionPolicy::Error {
+            let names = self.collisions(snapshot);
+            if !names.is_empty() {
+                self.warn_collisions(&names);
+                return Err(NameCollisionError { names });
+            }
+        }
+        Ok(self.export(snapshot))
+    }
+
     /// Export snapshot to Prometheus text format.
+    ///
+    /// Telemetry metrics colliding with built-in metrics are renamed or
+    /// dropped according to the [`CollisionPolicy`].
     pub fn export(&self, snapshot: &TelemetrySnapshot) -> String {
+        let snapshot = privacy::enforce_snapshot(snapshot);
+        let snapshot = snapshot.as_ref();
         let mut output = String::with_capacity(4096);
+        let collisions = self.collisions(snapshot);
+        self.warn_collisions(&collisions);
 
-        // Export counters
-        for (name, value) in &snapshot.counters {
-            self.write_counter(&mut output, name, *value);
+        // Export counters (label sets of one name share a family)
+        for (name, series) in group_by_family(&snapshot.counters) {
+            let descriptor = snapshot.registry.get(name);
+            let Some(name) = self.resolve_name(name, &collisions) else {
+                continue;
+            };
+            self.write_family(
+                &mut output,
+                &name,
+                "counter",
+                "Counter metric",
+                descriptor,
+                &series,
+            );
         }
 
         // Export gauges
-        for (name, value) in &snapshot.gauges {
-            self.write_gauge(&mut output, name, *value);
+        for (name, series) in group_by_family(&snapshot.gauges) {
+            let descriptor = snapshot.registry.get(name);
+            let Some(name) = self.resolve_name(name, &collisions) else {
+                continue;
+            };
+            self.write_family(
+                &mut output,
+                &name,
+                "gauge",
+                "Gauge metric",
+                descriptor,
+                &series,
+            );
         }
 
-        // Export operation timings as histograms
+        // Export operation timings as histograms (or summaries where selected)
         for (name, stats) in &snapshot.operation_stats {
-            self.write_histogram(&mut output, name, stats);
+            let help = snapshot.registry.get(name).map(|d| d.help.as_str());
+            if self.summary_by_default || self.summary_operations.contains(name) {
+                let help = help.unwrap_or("Operation duration summary");
+                self.write_summary(&mut output, name, help, stats);
+            } else {
+                let help = help.unwrap_or("Operation duration histogram");
+                self.write_histogram(&mut output, name, help, stats);
+            }
+        }
+
+        // Export high-resolution metrics as summaries
+        for (name, stats) in &snapshot.hires {
+            self.write_hires(&mut output, name, stats);
         }
 
         // Export built-in metrics
@@ -94,95 +435,368 @@ impl PrometheusExporter {
             "index_cache_evictions",
             snapshot.metrics.index_cache_evictions,
         );
+        self.write_eviction_reasons(&mut output, "sub_cache_evictions_by_reason", |reason| {
+            snapshot.metrics.sub_cache_evictions_by_reason(reason)
+        });
+        self.write_eviction_reasons(&mut output, "index_cache_evictions_by_reason", |reason| {
+            snapshot.metrics.index_cache_evictions_by_reason(reason)
+        });
+        self.write_shape_timings(
+            &mut output,
+            "retrieval_query",
+            &snapshot.metrics.retrieval_query_by_shape,
+        );
+        self.write_shape_timings(&mut output, "rerank", &snapshot.metrics.rerank_by_shape);
         self.write_counter(
             &mut output,
             "poison_recoveries_total",
             snapshot.metrics.poison_recoveries_total,
         );
+        self.write_counter(&mut output, "panics_total", snapshot.metrics.panics_total);
+
+        // Export retrieval quality metrics
+        self.write_quality(&mut output, &snapshot.quality);
 
         // Export uptime as gauge
         self.write_gauge(&mut output, "uptime_seconds", snapshot.uptime_secs as f64);
 
+        if self.format == ExpositionFormat::OpenMetrics {
+            output.push_str("# EOF\n");
+        }
+
         output
     }
 
-    fn write_counter(&self, output: &mut String, name: &str, value: u64) {
+    /// Exported name of telemetry metric `name`, or `None` when the
+    /// collision policy drops it.
+    fn resolve_name(&self, name: &str, collisions: &[String]) -> Option<String> {
+        if collisions
+            .binary_search_by(|c| c.as_str().cmp(name))
+            .is_err()
+        {
+            return Some(name.to_string());
+        }
+        match self.collision_policy {
+            CollisionPolicy::PrefixUser => Some(format!("user_{}", name)),
+            CollisionPolicy::Error | CollisionPolicy::PreferBuiltIn => None,
+        }
+    }
+
+    /// Log each colliding name once per exporter.
+    fn warn_collisions(&self, collisions: &[String]) {
+        let Ok(mut warned) = self.warned_collisions.lock() else {
+            return;
+        };
+        for name in collisions {
+            if warned.insert(name.clone()) {
+                record_event(
+                    EventLevel::Warn,
+                    "telemetry metric collides with built-in metric",
+                    &[("metric", name), ("policy", self.collision_policy.as_str())],
+                );
+            }
+        }
+    }
+
+    /// Counter family and sample names.
+    ///
+    /// OpenMetrics requires the family name without `_total` and samples
+    /// with it; Prometheus format uses the name as given for both.
+    fn counter_names(&self, name: &str) -> (String, String) {
         let metric_name = format!("{}_{}", self.prefix, sanitize_name(name));
+        match self.format {
+            ExpositionFormat::Prometheus => (metric_name.clone(), metric_name),
+            ExpositionFormat::OpenMetrics => {
+                let family = metric_name
+                    .strip_suffix("_total")
+                    .map(str::to_string)
+                    .unwrap_or(metric_name);
+                let sample = format!("{}_total", family);
+                (family, sample)
+            }
+        }
+    }
 
+    fn write_meta(&self, output: &mut String, family: &str, kind: &str, help: &str) {
         if self.include_help {
-            writeln!(output, "# HELP {} Counter metric", metric_name).ok();
+            writeln!(output, "# HELP {} {}", family, help).ok();
         }
         if self.include_type {
-            writeln!(output, "# TYPE {} counter", metric_name).ok();
+            writeln!(output, "# TYPE {} {}", family, kind).ok();
         }
-        writeln!(output, "{} {}", metric_name, value).ok();
     }
 
-    fn write_gauge(&self, output: &mut String, name: &str, value: f64) {
-        let metric_name = format!("{}_{}", self.prefix, sanitize_name(name));
+    fn write_counter(&self, output: &mut String, name: &str, value: u64) {
+        let (family, sample) = self.counter_names(name);
+        self.write_meta(output, &family, "counter", "Counter metric");
+        writeln!(output, "{} {}", sample, value).ok();
+    }
 
-        if self.include_help {
-            writeln!(output, "# HELP {} Gauge metric", metric_name).ok();
+    fn write_family<V: std::fmt::Display>(
+        &self,
+        output: &mut String,
+        name: &str,
+        kind: &str,
+        default_help: &str,
+        descriptor: Option<&MetricDescriptor>,
+        series: &[(Option<&str>, V)],
+    ) {
+        let name = descriptor.map_or_else(|| name.to_string(), |d| d.unit_suffixed(name));
+        let (family, sample) = if kind == "counter" {
+            self.counter_names(&name)
+        } else {
+            let metric_name = format!("{}_{}", self.prefix, sanitize_name(&name));
+            (metric_name.clone(), metric_name)
+        };
+
+        let help = descriptor.map_or(default_help, |d| d.help.as_str());
+        self.write_meta(output, &family, kind, help);
+        if let Some(unit) = descriptor.and_then(|d| d.unit.as_deref()) {
+            if self.format == ExpositionFormat::OpenMetrics && self.include_type {
+                writeln!(output, "# UNIT {} {}", family, sanitize_name(unit)).ok();
+            }
         }
-        if self.include_type {
-            writeln!(output, "# TYPE {} gauge", metric_name).ok();
+        for (labels, value) in series {
+            match labels {
+                Some(labels) => writeln!(output, "{}{{{}}} {}", sample, labels, value).ok(),
+                None => writeln!(output, "{} {}", sample, value).ok(),
+            };
         }
-        writeln!(output, "{} {}", metric_name, value).ok();
     }
 
-    fn write_histogram(
+    fn write_eviction_reasons(
         &self,
         output: &mut String,
         name: &str,
-        stats: &crate::obs::telemetry::OperationStats,
+        value_for: impl Fn(EvictionReason) -> u64,
     ) {
-        let metric_name = format!("{}_{}_duration_us", self.prefix, sanitize_name(name));
+        let (family, sample) = self.counter_names(name);
 
-        if self.include_help {
+        self.write_meta(output, &family, "counter", "Cache evictions by reason");
+        for reason in EvictionReason::ALL {
             writeln!(
                 output,
-                "# HELP {} Operation duration histogram",
-                metric_name
+                "{}{{reason=\"{}\"}} {}",
+                sample,
+                reason.as_str(),
+                value_for(reason)
             )
             .ok();
         }
-        if self.include_type {
-            writeln!(output, "# TYPE {} histogram", metric_name).ok();
+    }
+
+    fn write_shape_timings(&self, output: &mut String, op: &str, shape: &ShapeTimingsSnapshot) {
+        let (calls_family, calls_sample) = self.counter_names(&format!("{}_shape_calls", op));
+        let (ns_family, ns_sample) = self.counter_names(&format!("{}_shape_ns_total", op));
+
+        self.write_meta(
+            output,
+            &calls_family,
+            "counter",
+            "Calls by workload shape bucket",
+        );
+        for (dimension, bucket, stats) in shape.labeled() {
+            writeln!(
+                output,
+                "{}{{dimension=\"{}\",bucket=\"{}\"}} {}",
+                calls_sample, dimension, bucket, stats.calls
+            )
+            .ok();
         }
 
-        // Histogram buckets (microseconds): 100us, 500us, 1ms, 5ms, 10ms, 50ms, 100ms, +Inf
-        let buckets = [100, 500, 1000, 5000, 10000, 50000, 100000];
-        let mut cumulative = 0u64;
+        self.write_meta(
+            output,
+            &ns_family,
+            "counter",
+            "Total nanoseconds by workload shape bucket",
+        );
+        for (dimension, bucket, stats) in shape.labeled() {
+            writeln!(
+                output,
+                "{}{{dimension=\"{}\",bucket=\"{}\"}} {}",
+                ns_sample, dimension, bucket, stats.ns_total
+            )
+            .ok();
+        }
+    }
 
-        for bucket in &buckets {
-            cumulative += stats.count_below(*bucket);
+    fn write_gauge(&self, output: &mut String, name: &str, value: f64) {
+        let metric_name = format!("{}_{}", self.prefix, sanitize_name(name));
+
+        self.write_meta(output, &metric_name, "gauge", "Gauge metric");
+        writeln!(output, "{} {}", metric_name, value).ok();
+    }
+
+    fn write_quality(&self, output: &mut String, quality: &QualitySnapshot) {
+        self.write_counter(output, "quality_queries", quality.queries);
+        self.write_counter(output, "quality_empty_results", quality.empty_results);
+        self.write_counter(output, "rerank_queries", quality.rerank_queries);
+        self.write_counter(output, "rerank_top1_changes", quality.rerank_top1_changes);
+
+        let metric_name = format!("{}_query_topk_score", self.prefix);
+        self.write_meta(
+            output,
+            &metric_name,
+            "histogram",
+            "Top-k similarity score distribution",
+        );
+        for (bound, cumulative) in SCORE_BUCKETS.iter().zip(quality.cumulative_buckets()) {
             writeln!(
+                output,
+                "{}_bucket{{le=\"{}\"}} {}",
+                metric_name, bound, cumulative
+            )
+            .ok();
+        }
+        writeln!(
+            output,
+            "{}_bucket{{le=\"+Inf\"}} {}",
+            metric_name, quality.score_count
+        )
+        .ok();
+        writeln!(output, "{}_sum {}", metric_name, quality.score_sum).ok();
+        writeln!(output, "{}_count {}", metric_name, quality.score_count).ok();
+    }
+
+    fn write_histogram(
+        &self,
+        output: &mut String,
+        name: &str,
+        help: &str,
+        stats: &crate::obs::telemetry::OperationStats,
+    ) {
+        let metric_name = format!("{}_{}_duration_us", self.prefix, sanitize_name(name));
+
+        self.write_meta(output, &metric_name, "histogram", help);
+
+        let buckets = self
+            .operation_buckets
+            .get(name)
+            .unwrap_or(&self.default_buckets);
+
+        // OpenMetrics exemplar goes on the first bucket containing its value
+        let mut exemplar = stats
+            .exemplar
+            .as_ref()
+            .filter(|_| self.format == ExpositionFormat::OpenMetrics);
+
+        for bucket in buckets {
+            // Buckets are cumulative and inclusive (`le`)
+            let cumulative = stats.count_below(bucket.saturating_add(1));
+            write!(
                 output,
                 "{}_bucket{{le=\"{}\"}} {}",
                 metric_name, bucket, cumulative
             )
             .ok();
+            if let Some(ex) = exemplar.filter(|ex| ex.value_us <= *bucket) {
+                write_exemplar(output, ex);
+                exemplar = None;
+            }
+            output.push('\n');
         }
 
-        writeln!(
+        write!(
             output,
             "{}_bucket{{le=\"+Inf\"}} {}",
             metric_name, stats.count
         )
         .ok();
+        if let Some(ex) = exemplar {
+            write_exemplar(output, ex);
+        }
+        output.push('\n');
+        writeln!(output, "{}_sum {}", metric_name, stats.total_us).ok();
+        writeln!(output, "{}_count {}", metric_name, stats.count).ok();
+    }
+
+    /// Write operation timings as a summary with quantiles from the
+    /// operation's recorded samples.
+    fn write_hires(&self, output: &mut String, name: &str, stats: &HiResMetricsSnapshot) {
+        let metric_name = format!("{}_hires_{}_duration_ns", self.prefix, sanitize_name(name));
+        let ns = |ps: u64| ps as f64 / PS_PER_NS as f64;
+
+        self.write_meta(
+            output,
+            &metric_name,
+            "summary",
+            "High-resolution duration summary",
+        );
+        for (quantile, ps) in [
+            ("0.5", stats.p50_ps),
+            ("0.95", stats.p95_ps),
+            ("0.99", stats.p99_ps),
+            ("0.999", stats.p999_ps),
+        ] {
+            writeln!(
+                output,
+                "{}{{quantile=\"{}\"}} {}",
+                metric_name,
+                quantile,
+                ns(ps)
+            )
+            .ok();
+        }
+        writeln!(output, "{}_sum {}", metric_name, ns(stats.total_ps)).ok();
+        writeln!(output, "{}_count {}", metric_name, stats.count).ok();
+    }
+
+    fn write_summary(
+        &self,
+        output: &mut String,
+        name: &str,
+        help: &str,
+        stats: &crate::obs::telemetry::OperationStats,
+    ) {
+        let metric_name = format!("{}_{}_duration_us", self.prefix, sanitize_name(name));
+
+        self.write_meta(output, &metric_name, "summary", help);
+        for quantile in &self.quantiles {
+            writeln!(
+                output,
+                "{}{{quantile=\"{}\"}} {}",
+                metric_name,
+                quantile,
+                stats.percentile(quantile * 100.0)
+            )
+            .ok();
+        }
         writeln!(output, "{}_sum {}", metric_name, stats.total_us).ok();
         writeln!(output, "{}_count {}", metric_name, stats.count).ok();
     }
 }
 
+/// Append OpenMetrics exemplar syntax: ` # {trace_id="..."} value timestamp`.
+fn write_exemplar(output: &mut String, exemplar: &Exemplar) {
+    write!(
+        output,
+        " # {{trace_id=\"{}\"}} {} {:.3}",
+        exemplar.trace_id, exemplar.value_us, exemplar.timestamp_secs
+    )
+    .ok();
+}
+
 impl Default for PrometheusExporter {
     fn default() -> Self {
         Self::new("embeddenator")
     }
 }
 
+/// Group labeled telemetry keys by metric name, sorted for stable output.
+fn group_by_family<V: Copy>(values: &HashMap<String, V>) -> BTreeMap<&str, Vec<(Option<&str>, V)>> {
+    let mut families: BTreeMap<&str, Vec<(Option<&str>, V)>> = BTreeMap::new();
+    for (key, value) in values {
+        let (name, labels) = split_labeled_key(key);
+        families.entry(name).or_default().push((labels, *value));
+    }
+    for series in families.values_mut() {
+        series.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    families
+}
+
 /// Sanitize metric name for Prometheus (replace invalid chars with underscore).
-fn sanitize_name(name: &str) -> String {
+pub(crate) fn sanitize_name(name: &str) -> String {
     name.chars()
         .map(|c| {
             if c.is_alphanumeric() || c == '_' {
@@ -215,6 +829,37 @@ mod tests {
         assert!(output.contains("test_query_duration_us"));
     }
 
+    #[test]
+    fn test_hires_summary() {
+        let metrics = crate::obs::hires_timing::HiResMetrics::new();
+        metrics.record_slice(&[40_000, 60_000]);
+        let mut snapshot = Telemetry::default_config().snapshot();
+        snapshot.hires = vec![("shard.decode".to_string(), metrics.snapshot())];
+
+        let output = PrometheusExporter::new("test").export(&snapshot);
+
+        assert!(output.contains("# TYPE test_hires_shard_decode_duration_ns summary"));
+        assert!(output.contains("test_hires_shard_decode_duration_ns{quantile=\"0.999\"}"));
+        assert!(output.contains("test_hires_shard_decode_duration_ns_sum 100\n"));
+        assert!(output.contains("test_hires_shard_decode_duration_ns_count 2\n"));
+    }
+
+    #[test]
+    fn test_labeled_gauges_share_family() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.set_gauge_with_labels("disk_free_bytes", &[("path", "/data")], 10.0);
+        telemetry.set_gauge_with_labels("disk_free_bytes", &[("path", "/")], 20.0);
+
+        let output = PrometheusExporter::new("test").export(&telemetry.snapshot());
+
+        assert_eq!(
+            output.matches("# TYPE test_disk_free_bytes gauge").count(),
+            1
+        );
+        assert!(output.contains("test_disk_free_bytes{path=\"/\"} 20"));
+        assert!(output.contains("test_disk_free_bytes{path=\"/data\"} 10"));
+    }
+
     #[test]
     fn test_sanitize_name() {
         assert_eq!(sanitize_name("valid_name"), "valid_name");
@@ -239,6 +884,40 @@ mod tests {
         assert!(output.contains("test_query_duration_us_count 3"));
     }
 
+    #[test]
+    fn test_eviction_reason_labels() {
+        let telemetry = Telemetry::default_config();
+        let snapshot = telemetry.snapshot();
+        let output = PrometheusExporter::new("test").export(&snapshot);
+
+        assert!(output.contains("# TYPE test_sub_cache_evictions_by_reason counter"));
+        assert!(output.contains("test_sub_cache_evictions_by_reason{reason=\"capacity\"}"));
+        assert!(output.contains("test_index_cache_evictions_by_reason{reason=\"memory_pressure\"}"));
+    }
+
+    #[test]
+    fn test_quality_export() {
+        let snapshot = Telemetry::default_config().snapshot();
+        let output = PrometheusExporter::new("test").export(&snapshot);
+
+        assert!(output.contains("# TYPE test_query_topk_score histogram"));
+        assert!(output.contains("test_query_topk_score_bucket{le=\"0.5\"}"));
+        assert!(output.contains("test_query_topk_score_bucket{le=\"+Inf\"}"));
+        assert!(output.contains("test_quality_empty_results"));
+        assert!(output.contains("test_rerank_top1_changes"));
+    }
+
+    #[test]
+    fn test_shape_timings_export() {
+        let snapshot = Telemetry::default_config().snapshot();
+        let output = PrometheusExporter::new("test").export(&snapshot);
+
+        assert!(output.contains("# TYPE test_retrieval_query_shape_calls counter"));
+        assert!(output
+            .contains("test_retrieval_query_shape_calls{dimension=\"dim\",bucket=\"le_768\"}"));
+        assert!(output.contains("test_rerank_shape_ns_total{dimension=\"k\",bucket=\"gt_100\"}"));
+    }
+
     #[test]
     fn test_without_help_and_type() {
         let mut telemetry = Telemetry::default_config();
@@ -252,4 +931,191 @@ mod tests {
         assert!(!output.contains("# TYPE"));
         assert!(output.contains("app_test"));
     }
+
+    #[test]
+    fn test_format_negotiation() {
+        assert_eq!(
+            ExpositionFormat::from_accept(
+                "application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5"
+            ),
+            ExpositionFormat::OpenMetrics
+        );
+        assert_eq!(
+            ExpositionFormat::from_accept("text/plain;version=0.0.4"),
+            ExpositionFormat::Prometheus
+        );
+        assert!(PrometheusExporter::new("t")
+            .openmetrics()
+            .content_type()
+            .starts_with("application/openmetrics-text"));
+    }
+
+    #[test]
+    fn test_openmetrics_counters_and_eof() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.increment_counter("requests");
+        telemetry.add_to_counter("bytes_total", 10);
+
+        let output = PrometheusExporter::new("test")
+            .openmetrics()
+            .export(&telemetry.snapshot());
+
+        assert!(output.contains("# TYPE test_requests counter\n"));
+        assert!(output.contains("test_requests_total 1\n"));
+        assert!(output.contains("# TYPE test_bytes counter\n"));
+        assert!(output.contains("test_bytes_total 10\n"));
+        assert!(output.contains("# TYPE test_poison_recoveries counter"));
+        assert!(output.contains("test_sub_cache_evictions_by_reason_total{reason=\"ttl\"}"));
+        assert!(!output.contains("_total_total"));
+        assert!(output.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_openmetrics_exemplar() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.record_operation_with_exemplar("query", 750, "4bf92f3577b34da6a3ce929d0e0e4736");
+        let snapshot = telemetry.snapshot();
+
+        let om = PrometheusExporter::new("test")
+            .openmetrics()
+            .export(&snapshot);
+        let line = om
+            .lines()
+            .find(|line| line.starts_with("test_query_duration_us_bucket{le=\"1000\"}"))
+            .unwrap();
+        assert!(line.contains(" # {trace_id=\"4bf92f3577b34da6a3ce929d0e0e4736\"} 750 "));
+
+        let prom = PrometheusExporter::new("test").export(&snapshot);
+        assert!(!prom.contains("trace_id"));
+        assert!(!prom.contains("# EOF"));
+    }
+    #[test]
+    fn test_summary_export_per_operation() {
+        let mut telemetry = Telemetry::default_config();
+        for us in 1..=100 {
+            telemetry.record_operation("query", us * 10);
+            telemetry.record_operation("ingest", us);
+        }
+        let snapshot = telemetry.snapshot();
+
+        let output = PrometheusExporter::new("test")
+            .summary_for("query")
+            .export(&snapshot);
+        assert!(output.contains("# TYPE test_query_duration_us summary\n"));
+        assert!(output.contains("test_query_duration_us{quantile=\"0.5\"} 510\n"));
+        assert!(output.contains("test_query_duration_us{quantile=\"0.99\"} 990\n"));
+        assert!(output.contains("test_query_duration_us_count 100\n"));
+        assert!(!output.contains("test_query_duration_us_bucket"));
+        // Other operations keep histograms
+        assert!(output.contains("# TYPE test_ingest_duration_us histogram\n"));
+
+        let output = PrometheusExporter::new("test")
+            .summaries_by_default()
+            .with_quantiles(&[0.9, 1.5])
+            .export(&snapshot);
+        assert!(output.contains("test_ingest_duration_us{quantile=\"0.9\"} 90\n"));
+        assert!(!output.contains("quantile=\"1.5\""));
+        assert!(!output.contains("duration_us_bucket"));
+    }
+    #[test]
+    fn test_configurable_buckets_are_cumulative() {
+        let mut telemetry = Telemetry::default_config();
+        for us in [5, 15, 25, 25, 300] {
+            telemetry.record_operation("retrieval_query", us);
+        }
+        telemetry.record_operation("ingest", 700);
+        let snapshot = telemetry.snapshot();
+
+        let output = PrometheusExporter::new("test")
+            .with_operation_buckets("retrieval_query", &linear_buckets(10, 10, 3))
+            .with_buckets(&[1000, 500])
+            .export(&snapshot);
+
+        assert!(output.contains("test_retrieval_query_duration_us_bucket{le=\"10\"} 1\n"));
+        assert!(output.contains("test_retrieval_query_duration_us_bucket{le=\"20\"} 2\n"));
+        // Inclusive upper bound
+        assert!(output.contains("test_retrieval_query_duration_us_bucket{le=\"30\"} 4\n"));
+        assert!(output.contains("test_retrieval_query_duration_us_bucket{le=\"+Inf\"} 5\n"));
+        assert!(output.contains("test_ingest_duration_us_bucket{le=\"500\"} 0\n"));
+        assert!(output.contains("test_ingest_duration_us_bucket{le=\"1000\"} 1\n"));
+        assert!(!output.contains("test_ingest_duration_us_bucket{le=\"100\"}"));
+    }
+
+    #[test]
+    fn test_bucket_generators() {
+        assert_eq!(linear_buckets(100, 50, 4), vec![100, 150, 200, 250]);
+        assert_eq!(exponential_buckets(10, 2.0, 5), vec![10, 20, 40, 80, 160]);
+        // Rounding collisions are merged
+        assert_eq!(exponential_buckets(1, 1.2, 4), vec![1, 2]);
+    }
+    #[test]
+    fn test_registry_help_and_units() {
+        use crate::obs::registry::MetricDescriptor;
+
+        let mut telemetry = Telemetry::default_config();
+        telemetry.describe(
+            MetricDescriptor::counter("read_total", "Bytes read from index files")
+                .with_unit("bytes"),
+        );
+        telemetry.describe(MetricDescriptor::histogram("query", "Query latency"));
+        telemetry.add_to_counter_with_labels("read_total", &[("shard", "0")], 4096);
+        telemetry.record_operation("query", 120);
+        telemetry.set_gauge("depth", 3.0);
+        let snapshot = telemetry.snapshot();
+
+        let output = PrometheusExporter::new("test").export(&snapshot);
+        assert!(output.contains("# HELP test_read_bytes_total Bytes read from index files\n"));
+        assert!(output.contains("test_read_bytes_total{shard=\"0\"} 4096\n"));
+        assert!(output.contains("# HELP test_query_duration_us Query latency\n"));
+        // Undeclared metrics keep the generic help
+        assert!(output.contains("# HELP test_depth Gauge metric\n"));
+        assert!(!output.contains("# UNIT"));
+
+        let om = PrometheusExporter::new("test")
+            .openmetrics()
+            .export(&snapshot);
+        assert!(om.contains("# TYPE test_read_bytes counter\n# UNIT test_read_bytes bytes\n"));
+    }
+
+    #[test]
+    fn test_builtin_name_collisions() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.add_to_counter("sub_cache_hits", 7);
+        telemetry.add_to_counter_with_labels("poison_recoveries", &[("lock", "index")], 1);
+        telemetry.set_gauge("uptime_seconds", 1.0);
+        telemetry.increment_counter("requests");
+        let snapshot = telemetry.snapshot();
+
+        let exporter = PrometheusExporter::new("test");
+        assert_eq!(
+            exporter.collisions(&snapshot),
+            ["poison_recoveries", "sub_cache_hits", "uptime_seconds"]
+        );
+        let output = exporter.export(&snapshot);
+        assert!(output.contains("test_user_sub_cache_hits 7\n"));
+        assert!(output.contains("test_user_poison_recoveries{lock=\"index\"} 1\n"));
+        assert_eq!(output.matches("# TYPE test_sub_cache_hits ").count(), 1);
+        assert_eq!(output.matches("# TYPE test_uptime_seconds ").count(), 1);
+
+        let output = PrometheusExporter::new("test")
+            .with_collision_policy(CollisionPolicy::PreferBuiltIn)
+            .export(&snapshot);
+        assert!(!output.contains("user_"));
+        assert!(!output.contains("test_sub_cache_hits 7"));
+        assert!(output.contains("test_requests 1\n"));
+    }
+
+    #[test]
+    fn test_collision_error_policy() {
+        let exporter =
+            PrometheusExporter::new("test").with_collision_policy(CollisionPolicy::Error);
+        let mut telemetry = Telemetry::default_config();
+        telemetry.increment_counter("requests");
+        assert!(exporter.try_export(&telemetry.snapshot()).is_ok());
+
+        telemetry.add_to_counter("rerank_queries_total", 2);
+        let err = exporter.try_export(&telemetry.snapshot()).unwrap_err();
+        assert_eq!(err.names, ["rerank_queries_total"]);
+        assert!(err.to_string().contains("rerank_queries_total"));
+    }
 }
@@ -0,0 +1,217 @@
+//! HTTP Header Propagation
+//!
+//! Moves trace context in and out of request headers without handling
+//! `traceparent` or B3 strings by hand. Header maps of any HTTP library
+//! plug in through [`HeaderMapLike`]; it is implemented for `HashMap`,
+//! `BTreeMap` and `Vec` of string pairs, and for hyper/axum/reqwest
+//! `http::HeaderMap` in a few lines:
+//!
+//! ```rust,ignore
+//! struct Headers<'a>(&'a mut http::HeaderMap);
+//!
+//! impl HeaderMapLike for Headers<'_> {
+//!     fn get(&self, name: &str) -> Option<&str> {
+//!         self.0.get(name)?.to_str().ok()
+//!     }
+//!     fn set(&mut self, name: &str, value: String) {
+//!         if let (Ok(name), Ok(value)) = (
+//!             http::HeaderName::try_from(name),
+//!             http::HeaderValue::try_from(value),
+//!         ) {
+//!             self.0.insert(name, value);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! # Formats
+//!
+//! [`extract_context`] accepts W3C `traceparent`, then B3 single (`b3`),
+//! then B3 multi (`X-B3-*`). [`inject_context`] writes `traceparent`;
+//! [`inject_context_as`] writes any set of formats, for peers that only
+//! speak B3.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::propagation::{extract_context, inject_context};
+//!
+//! // Server: continue the caller's trace
+//! let span = match extract_context(&request_headers) {
+//!     Some(context) => OtelSpan::from_context(&context, "handle_query"),
+//!     None => OtelSpan::new("handle_query"),
+//! };
+//!
+//! // Client: pass it on
+//! inject_context(&span, &mut outgoing_headers);
+//! ```
+
+use crate::obs::opentelemetry::{B3Context, OtelSpan, TraceContext};
+use std::collections::{BTreeMap, HashMap};
+
+/// Minimal header map access; names are compared case-insensitively.
+pub trait HeaderMapLike {
+    /// Value of header `name`.
+    fn get(&self, name: &str) -> Option<&str>;
+
+    /// Set header `name`, replacing any existing value.
+    fn set(&mut self, name: &str, value: String);
+}
+
+impl HeaderMapLike for HashMap<String, String> {
+    fn get(&self, name: &str) -> Option<&str> {
+        match HashMap::get(self, name) {
+            Some(value) => Some(value),
+            None => self
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str()),
+        }
+    }
+
+    fn set(&mut self, name: &str, value: String) {
+        self.retain(|key, _| !key.eq_ignore_ascii_case(name));
+        self.insert(name.to_string(), value);
+    }
+}
+
+impl HeaderMapLike for BTreeMap<String, String> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn set(&mut self, name: &str, value: String) {
+        self.retain(|key, _| !key.eq_ignore_ascii_case(name));
+        self.insert(name.to_string(), value);
+    }
+}
+
+impl HeaderMapLike for Vec<(String, String)> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn set(&mut self, name: &str, value: String) {
+        self.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        self.push((name.to_string(), value));
+    }
+}
+
+/// Trace context header format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropagationFormat {
+    /// W3C `traceparent`
+    TraceContext,
+    /// B3 single header (`b3`)
+    B3Single,
+    /// B3 multi headers (`X-B3-TraceId`, ...)
+    B3Multi,
+}
+
+/// B3 multi header names, in the order they are read.
+const B3_HEADERS: [&str; 5] = [
+    "X-B3-TraceId",
+    "X-B3-SpanId",
+    "X-B3-ParentSpanId",
+    "X-B3-Sampled",
+    "X-B3-Flags",
+];
+
+/// Remote trace context from `headers`, trying `traceparent`, `b3` and
+/// `X-B3-*` in that order. Malformed headers are skipped.
+pub fn extract_context(headers: &impl HeaderMapLike) -> Option<TraceContext> {
+    if let Some(context) = headers.get("traceparent").and_then(TraceContext::parse) {
+        return Some(context);
+    }
+    if let Some(context) = headers.get("b3").and_then(B3Context::parse_single) {
+        return Some(context.to_trace_context());
+    }
+    let multi: Vec<(&str, &str)> = B3_HEADERS
+        .iter()
+        .filter_map(|&name| headers.get(name).map(|value| (name, value)))
+        .collect();
+    B3Context::parse_multi(&multi).map(|context| context.to_trace_context())
+}
+
+/// Write `span`'s context as a W3C `traceparent` header.
+pub fn inject_context(span: &OtelSpan, headers: &mut impl HeaderMapLike) {
+    inject_context_as(span, headers, &[PropagationFormat::TraceContext]);
+}
+
+/// Write `span`'s context in each of `formats`.
+pub fn inject_context_as(
+    span: &OtelSpan,
+    headers: &mut impl HeaderMapLike,
+    formats: &[PropagationFormat],
+) {
+    for format in formats {
+        match format {
+            PropagationFormat::TraceContext => headers.set("traceparent", span.to_traceparent()),
+            PropagationFormat::B3Single => headers.set("b3", span.to_b3_single()),
+            PropagationFormat::B3Multi => {
+                for (name, value) in span.to_b3_headers() {
+                    headers.set(name, value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject_extract_roundtrip() {
+        let span = OtelSpan::new("client");
+        let mut headers: HashMap<String, String> = HashMap::new();
+        inject_context(&span, &mut headers);
+        assert_eq!(headers["traceparent"], span.to_traceparent());
+
+        // Header names from the wire may use any case
+        let value = headers.remove("traceparent").unwrap();
+        headers.insert("Traceparent".to_string(), value);
+        let context = extract_context(&headers).unwrap();
+        assert_eq!(context.trace_id, span.trace_id);
+        assert_eq!(context.parent_id, span.span_id);
+
+        let server = OtelSpan::from_context(&context, "server");
+        assert_eq!(server.trace_id, span.trace_id);
+        assert_eq!(server.parent_span_id, span.span_id);
+
+        let empty: Vec<(String, String)> = Vec::new();
+        assert!(extract_context(&empty).is_none());
+    }
+
+    #[test]
+    fn test_b3_formats() {
+        let mut span = OtelSpan::new("client");
+        span.sampled = false;
+
+        let mut single: Vec<(String, String)> = Vec::new();
+        inject_context_as(&span, &mut single, &[PropagationFormat::B3Single]);
+        assert_eq!(single.len(), 1);
+        let context = extract_context(&single).unwrap();
+        assert_eq!(context.trace_id, span.trace_id);
+        assert!(!context.is_sampled());
+
+        let mut multi: BTreeMap<String, String> = BTreeMap::new();
+        inject_context_as(
+            &span,
+            &mut multi,
+            &[PropagationFormat::B3Multi, PropagationFormat::B3Multi],
+        );
+        // Re-injecting replaces rather than duplicates
+        assert_eq!(multi.len(), 3);
+        let context = extract_context(&multi).unwrap();
+        assert_eq!(context.parent_id, span.span_id);
+
+        // A malformed traceparent falls through to B3
+        multi.insert("traceparent".to_string(), "garbage".to_string());
+        assert_eq!(extract_context(&multi).unwrap().trace_id, span.trace_id);
+    }
+}
@@ -0,0 +1,242 @@
+//! Retrieval Quality Metrics
+//!
+//! Domain metrics describing the quality of embedding retrieval results,
+//! complementing the latency counters in [`crate::metrics`]. Performance
+//! numbers without quality context mislead tuning: a faster index that
+//! returns worse neighbours is not an improvement.
+//!
+//! # Recorded Signals
+//!
+//! - Distribution of top-k similarity scores (fixed-bucket histogram)
+//! - Empty-result query counter
+//! - Fraction of reranked queries whose top-1 result changed
+//!
+//! # Usage
+//!
+//! ```rust
+//! use embeddenator_obs::quality::quality;
+//!
+//! // After retrieval
+//! quality().record_query(&[0.92, 0.87, 0.55]);
+//!
+//! // After reranking
+//! quality().record_rerank(true);
+//!
+//! let snapshot = quality().snapshot();
+//! println!("rerank changed top-1 in {:.1}% of queries",
+//!          snapshot.rerank_top1_change_rate() * 100.0);
+//! ```
+
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+/// Upper bounds of the score histogram buckets (cumulative `le` semantics).
+/// Scores above the last bound are only counted in the `+Inf` bucket.
+pub const SCORE_BUCKETS: [f64; 10] = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0];
+
+/// Fixed-point scale used to accumulate score sums atomically.
+const SCORE_SCALE: f64 = 1_000_000.0;
+
+/// Lock-free recorder for retrieval quality signals.
+pub struct QualityRecorder {
+    queries: AtomicU64,
+    empty_results: AtomicU64,
+    rerank_queries: AtomicU64,
+    rerank_top1_changes: AtomicU64,
+    score_count: AtomicU64,
+    score_sum_scaled: AtomicI64,
+    /// Non-cumulative per-bucket counts; the last slot is the overflow bucket
+    score_buckets: [AtomicU64; SCORE_BUCKETS.len() + 1],
+}
+
+/// Point-in-time view of retrieval quality metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QualitySnapshot {
+    pub queries: u64,
+    pub empty_results: u64,
+    pub rerank_queries: u64,
+    pub rerank_top1_changes: u64,
+    pub score_count: u64,
+    pub score_sum: f64,
+    /// Non-cumulative counts per bucket in [`SCORE_BUCKETS`], plus overflow
+    pub score_buckets: [u64; SCORE_BUCKETS.len() + 1],
+}
+
+impl Default for QualityRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QualityRecorder {
+    pub const fn new() -> Self {
+        Self {
+            queries: AtomicU64::new(0),
+            empty_results: AtomicU64::new(0),
+            rerank_queries: AtomicU64::new(0),
+            rerank_top1_changes: AtomicU64::new(0),
+            score_count: AtomicU64::new(0),
+            score_sum_scaled: AtomicI64::new(0),
+            score_buckets: [const { AtomicU64::new(0) }; SCORE_BUCKETS.len() + 1],
+        }
+    }
+
+    /// Record the top-k scores returned by a query.
+    ///
+    /// An empty slice counts as an empty result.
+    pub fn record_query(&self, _top_k_scores: &[f32]) {
+        #[cfg(feature = "metrics")]
+        {
+            self.queries.fetch_add(1, Ordering::Relaxed);
+            if _top_k_scores.is_empty() {
+                self.empty_results.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
+            let mut sum_scaled = 0i64;
+            for &score in _top_k_scores {
+                let score = f64::from(score);
+                self.score_buckets[bucket_index(score)].fetch_add(1, Ordering::Relaxed);
+                sum_scaled += (score * SCORE_SCALE) as i64;
+            }
+            self.score_count
+                .fetch_add(_top_k_scores.len() as u64, Ordering::Relaxed);
+            self.score_sum_scaled
+                .fetch_add(sum_scaled, Ordering::Relaxed);
+        }
+    }
+
+    /// Record a rerank pass and whether it changed the top-1 result.
+    pub fn record_rerank(&self, _top1_changed: bool) {
+        #[cfg(feature = "metrics")]
+        {
+            self.rerank_queries.fetch_add(1, Ordering::Relaxed);
+            if _top1_changed {
+                self.rerank_top1_changes.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> QualitySnapshot {
+        let mut score_buckets = [0u64; SCORE_BUCKETS.len() + 1];
+        for (dst, src) in score_buckets.iter_mut().zip(&self.score_buckets) {
+            *dst = src.load(Ordering::Relaxed);
+        }
+
+        QualitySnapshot {
+            queries: self.queries.load(Ordering::Relaxed),
+            empty_results: self.empty_results.load(Ordering::Relaxed),
+            rerank_queries: self.rerank_queries.load(Ordering::Relaxed),
+            rerank_top1_changes: self.rerank_top1_changes.load(Ordering::Relaxed),
+            score_count: self.score_count.load(Ordering::Relaxed),
+            score_sum: self.score_sum_scaled.load(Ordering::Relaxed) as f64 / SCORE_SCALE,
+            score_buckets,
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn bucket_index(score: f64) -> usize {
+    SCORE_BUCKETS
+        .iter()
+        .position(|&bound| score <= bound)
+        .unwrap_or(SCORE_BUCKETS.len())
+}
+
+impl QualitySnapshot {
+    /// Fraction of queries that returned no results.
+    pub fn empty_result_rate(&self) -> f64 {
+        if self.queries == 0 {
+            0.0
+        } else {
+            self.empty_results as f64 / self.queries as f64
+        }
+    }
+
+    /// Fraction of reranked queries whose top-1 result changed.
+    pub fn rerank_top1_change_rate(&self) -> f64 {
+        if self.rerank_queries == 0 {
+            0.0
+        } else {
+            self.rerank_top1_changes as f64 / self.rerank_queries as f64
+        }
+    }
+
+    /// Mean top-k score across all recorded results.
+    pub fn mean_score(&self) -> f64 {
+        if self.score_count == 0 {
+            0.0
+        } else {
+            self.score_sum / self.score_count as f64
+        }
+    }
+
+    /// Cumulative count of scores `<= SCORE_BUCKETS[i]`.
+    pub fn cumulative_buckets(&self) -> [u64; SCORE_BUCKETS.len()] {
+        let mut out = [0u64; SCORE_BUCKETS.len()];
+        let mut running = 0;
+        for (i, slot) in out.iter_mut().enumerate() {
+            running += self.score_buckets[i];
+            *slot = running;
+        }
+        out
+    }
+}
+
+static QUALITY: QualityRecorder = QualityRecorder::new();
+
+/// Global retrieval quality recorder.
+pub fn quality() -> &'static QualityRecorder {
+    &QUALITY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quality_recording_behaves_under_feature_gate() {
+        let recorder = QualityRecorder::new();
+
+        recorder.record_query(&[0.95, 0.45, 0.05]);
+        recorder.record_query(&[]);
+        recorder.record_rerank(true);
+        recorder.record_rerank(false);
+
+        let snap = recorder.snapshot();
+
+        #[cfg(feature = "metrics")]
+        {
+            assert_eq!(snap.queries, 2);
+            assert_eq!(snap.empty_results, 1);
+            assert_eq!(snap.empty_result_rate(), 0.5);
+            assert_eq!(snap.rerank_top1_change_rate(), 0.5);
+            assert_eq!(snap.score_count, 3);
+            assert!((snap.mean_score() - 0.483333).abs() < 1e-4);
+
+            let cumulative = snap.cumulative_buckets();
+            assert_eq!(cumulative[0], 1); // 0.05
+            assert_eq!(cumulative[4], 2); // + 0.45
+            assert_eq!(cumulative[9], 3); // + 0.95
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        {
+            assert_eq!(snap, QualitySnapshot::default());
+        }
+    }
+
+    #[test]
+    fn out_of_range_scores_use_edge_buckets() {
+        let recorder = QualityRecorder::new();
+        recorder.record_query(&[-0.3, 1.7]);
+
+        let snap = recorder.snapshot();
+
+        #[cfg(feature = "metrics")]
+        {
+            assert_eq!(snap.score_buckets[0], 1);
+            assert_eq!(snap.score_buckets[SCORE_BUCKETS.len()], 1);
+            assert_eq!(snap.cumulative_buckets()[SCORE_BUCKETS.len() - 1], 1);
+        }
+    }
+}
@@ -0,0 +1,656 @@
+//! Unix Socket Query Endpoint
+//!
+//! [`QueryServer`] answers commands on a Unix domain socket, one per line,
+//! with one line of JSON each, so operators can inspect a running process
+//! with `obs-cli`, `socat` or a small client without exposing an HTTP port:
+//!
+//! - `snapshot`: the telemetry snapshot, as [`TelemetrySnapshot::to_json`]
+//! - `summary`: `{"summary":"..."}`, as [`TelemetrySnapshot::summary`]
+//! - `prometheus`: `{"prometheus":"<text exposition>"}`
+//! - `top [n]`: `{"operations":[...]}`, the `n` (default 10) operations with
+//!   the slowest p99
+//! - `metric <name>`: `{"metric":"<name>","values":{...}}`, every label set
+//!   of a counter or gauge, or the statistics of an operation
+//! - `health`: the readiness report, as [`HealthReport::to_json`]
+//! - `spans`: `{"spans":[...]}`, the spans held by a [`RecentSpans`]
+//!   processor, oldest first
+//! - `reload`: `{"applied":[...],"rejected":[...]}`, the outcome of
+//!   reloading the config file through a [`ConfigReloader`]
+//! - `help`: `{"commands":[...]}`, the commands this server answers
+//!
+//! Unknown commands, and commands whose source was not configured, answer
+//! `{"error":"..."}`. A connection may send several commands; it is closed
+//! on EOF or 30s after it was accepted. Each connection is served on its
+//! own thread, up to 16 at once by default.
+//!
+//! The socket file is made owner-only (`0600`) and removed when the server
+//! stops. A stale file left by a crashed process is replaced on bind; one
+//! a live server still listens on is not.
+//!
+//! The commands themselves are answered by a [`QueryHandler`], which
+//! [`HealthServer::with_query`] also serves over HTTP as
+//! `GET /query/<command>`, on every platform.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::query_socket::QueryServer;
+//! use embeddenator_obs::span_processor::{RecentSpans, TracerProvider};
+//!
+//! let recent = RecentSpans::new(512);
+//! TracerProvider::new().with_processor(recent.clone()).install();
+//!
+//! let _handle = QueryServer::bind("/run/indexer/obs.sock")?
+//!     .with_telemetry(telemetry.clone())
+//!     .with_health(health.clone())
+//!     .with_spans(recent)
+//!     .spawn();
+//! ```
+//!
+//! ```text
+//! $ echo snapshot | socat - UNIX-CONNECT:/run/indexer/obs.sock
+//! ```
+//!
+//! [`TelemetrySnapshot::to_json`]: crate::obs::telemetry::TelemetrySnapshot::to_json
+//! [`TelemetrySnapshot::summary`]: crate::obs::telemetry::TelemetrySnapshot::summary
+//! [`HealthReport::to_json`]: crate::obs::health::HealthReport::to_json
+//! [`HealthServer::with_query`]: crate::obs::health::HealthServer::with_query
+
+use crate::obs::config::{ConfigReloader, ReloadReport};
+use crate::obs::health::HealthRegistry;
+use crate::obs::opentelemetry::{json_f64, OtelSpan, SpanStatus};
+use crate::obs::privacy;
+use crate::obs::prometheus::PrometheusExporter;
+use crate::obs::span_processor::RecentSpans;
+use crate::obs::telemetry::{escape_json, split_labeled_key, Telemetry, TelemetrySnapshot};
+use std::sync::{Arc, Mutex};
+
+#[cfg(unix)]
+use crate::obs::health::DeadlineReader;
+#[cfg(unix)]
+use crate::obs::logging;
+#[cfg(unix)]
+use crate::obs::process::{spawn_periodic, CollectorHandle};
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::io::{self, BufRead, BufReader, Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(unix)]
+use std::time::Duration;
+
+/// Operations listed by `top` without a count.
+const DEFAULT_TOP: usize = 10;
+
+/// Longest command line read; longer lines close the connection.
+#[cfg(unix)]
+const MAX_COMMAND_LEN: usize = 1024;
+
+/// Time one connection may stay open, however many commands it sends.
+#[cfg(unix)]
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time a client gets to take one answer.
+#[cfg(unix)]
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Answers query commands from the configured sources. Clones share them.
+#[derive(Clone, Default)]
+pub struct QueryHandler {
+    telemetry: Option<Arc<Mutex<Telemetry>>>,
+    exporter: Arc<PrometheusExporter>,
+    health: Option<HealthRegistry>,
+    spans: Option<RecentSpans>,
+    reloader: Option<ConfigReloader>,
+}
+
+impl QueryHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer `snapshot`, `summary`, `prometheus`, `top` and `metric` from
+    /// `telemetry`.
+    pub fn with_telemetry(mut self, telemetry: Arc<Mutex<Telemetry>>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Format `prometheus` with `exporter` instead of the default one.
+    pub fn with_prometheus(mut self, exporter: PrometheusExporter) -> Self {
+        self.exporter = Arc::new(exporter);
+        self
+    }
+
+    /// Answer `health` from `registry`.
+    pub fn with_health(mut self, registry: HealthRegistry) -> Self {
+        self.health = Some(registry);
+        self
+    }
+
+    /// Answer `spans` from `spans`.
+    pub fn with_spans(mut self, spans: RecentSpans) -> Self {
+        self.spans = Some(spans);
+        self
+    }
+
+    /// Answer `reload` by reloading the config file through `reloader`.
+    pub fn with_reloader(mut self, reloader: ConfigReloader) -> Self {
+        self.reloader = Some(reloader);
+        self
+    }
+
+    /// Answer one command line with a single line of JSON.
+    pub fn handle(&self, line: &str) -> String {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let argument = words.next();
+        match (command, argument) {
+            ("snapshot", None) => match self.snapshot() {
+                Ok(snapshot) => one_line(&snapshot.to_json()),
+                Err(e) => error_json(&e),
+            },
+            ("summary", None) => match self.snapshot() {
+                Ok(snapshot) => format!(
+                    r#"{{"summary":"{}"}}"#,
+                    escape_json(&privacy::enforce_snapshot(&snapshot).summary())
+                ),
+                Err(e) => error_json(&e),
+            },
+            ("prometheus", None) => match self.snapshot() {
+                Ok(snapshot) => format!(
+                    r#"{{"prometheus":"{}"}}"#,
+                    escape_json(&self.exporter.export(&snapshot))
+                ),
+                Err(e) => error_json(&e),
+            },
+            ("top", n) => {
+                let n = match n.map(str::parse::<usize>) {
+                    None => DEFAULT_TOP,
+                    Some(Ok(n)) => n,
+                    Some(Err(_)) => return error_json("usage: top [n]"),
+                };
+                match self.snapshot() {
+                    Ok(snapshot) => top_json(&privacy::enforce_snapshot(&snapshot), n),
+                    Err(e) => error_json(&e),
+                }
+            }
+            ("metric", Some(name)) => match self.snapshot() {
+                Ok(snapshot) => metric_json(&privacy::enforce_snapshot(&snapshot), name),
+                Err(e) => error_json(&e),
+            },
+            ("metric", None) => error_json("usage: metric <name>"),
+            ("health", None) => match &self.health {
+                Some(registry) => one_line(&registry.readiness().to_json()),
+                None => error_json("health checks not configured"),
+            },
+            ("spans", None) => match &self.spans {
+                Some(recent) => spans_json(&recent.spans()),
+                None => error_json("span buffer not configured"),
+            },
+            ("reload", None) => match &self.reloader {
+                Some(reloader) => match reloader.reload() {
+                    Ok(report) => reload_json(&report),
+                    Err(e) => error_json(&e.to_string()),
+                },
+                None => error_json("config reload not configured"),
+            },
+            ("help", None) => {
+                let commands: Vec<String> = self
+                    .commands()
+                    .iter()
+                    .map(|c| format!("\"{}\"", c))
+                    .collect();
+                format!(r#"{{"commands":[{}]}}"#, commands.join(","))
+            }
+            _ => error_json(&format!("unknown command: {}", line.trim())),
+        }
+    }
+
+    fn commands(&self) -> Vec<&'static str> {
+        let mut commands = Vec::new();
+        if self.telemetry.is_some() {
+            commands.extend(["snapshot", "summary", "prometheus", "top", "metric"]);
+        }
+        if self.health.is_some() {
+            commands.push("health");
+        }
+        if self.spans.is_some() {
+            commands.push("spans");
+        }
+        if self.reloader.is_some() {
+            commands.push("reload");
+        }
+        commands.push("help");
+        commands
+    }
+
+    fn snapshot(&self) -> Result<TelemetrySnapshot, String> {
+        let telemetry = self
+            .telemetry
+            .as_ref()
+            .ok_or_else(|| "telemetry not configured".to_string())?;
+        telemetry
+            .lock()
+            .map(|telemetry| telemetry.snapshot())
+            .map_err(|_| "telemetry lock poisoned".to_string())
+    }
+}
+
+/// Newline-delimited JSON query server on a Unix domain socket.
+#[cfg(unix)]
+pub struct QueryServer {
+    listener: UnixListener,
+    path: PathBuf,
+    handler: QueryHandler,
+    max_connections: usize,
+}
+
+#[cfg(unix)]
+impl QueryServer {
+    /// Bind the socket at `path`, replacing a stale socket file.
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is served by another process", path.display()),
+                ));
+            }
+            fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            path,
+            handler: QueryHandler::new(),
+            max_connections: 16,
+        })
+    }
+
+    /// Answer commands with an existing handler.
+    pub fn with_handler(mut self, handler: QueryHandler) -> Self {
+        self.handler = handler;
+        self
+    }
+
+    /// Connections served at once; further ones are closed unanswered
+    /// (default: 16).
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// See [`QueryHandler::with_telemetry`].
+    pub fn with_telemetry(mut self, telemetry: Arc<Mutex<Telemetry>>) -> Self {
+        self.handler = std::mem::take(&mut self.handler).with_telemetry(telemetry);
+        self
+    }
+
+    /// See [`QueryHandler::with_prometheus`].
+    pub fn with_prometheus(mut self, exporter: PrometheusExporter) -> Self {
+        self.handler = std::mem::take(&mut self.handler).with_prometheus(exporter);
+        self
+    }
+
+    /// See [`QueryHandler::with_health`].
+    pub fn with_health(mut self, registry: HealthRegistry) -> Self {
+        self.handler = std::mem::take(&mut self.handler).with_health(registry);
+        self
+    }
+
+    /// See [`QueryHandler::with_spans`].
+    pub fn with_spans(mut self, spans: RecentSpans) -> Self {
+        self.handler = std::mem::take(&mut self.handler).with_spans(spans);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Handler answering this server's commands.
+    pub fn handler(&self) -> &QueryHandler {
+        &self.handler
+    }
+
+    /// Accept connections on a background thread, serving each on its own.
+    pub fn spawn(self) -> CollectorHandle {
+        let active = Arc::new(AtomicUsize::new(0));
+        spawn_periodic("obs-query-socket", Duration::from_millis(50), move || {
+            while let Ok((stream, _)) = self.listener.accept() {
+                if active.fetch_add(1, Ordering::SeqCst) >= self.max_connections {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    logging::debug("query socket busy, dropping connection");
+                    continue;
+                }
+                let handler = self.handler.clone();
+                let connection_active = active.clone();
+                let spawned = std::thread::Builder::new()
+                    .name("obs-query-conn".to_string())
+                    .spawn(move || {
+                        if let Err(e) = serve_connection(&handler, stream) {
+                            logging::debug(&format!("query socket connection failed: {}", e));
+                        }
+                        connection_active.fetch_sub(1, Ordering::SeqCst);
+                    });
+                if spawned.is_err() {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for QueryServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn serve_connection(handler: &QueryHandler, mut stream: UnixStream) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    let deadline = DeadlineReader::new(stream.try_clone()?, CONNECTION_TIMEOUT);
+    let mut reader = BufReader::new(deadline).take(MAX_COMMAND_LEN as u64);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || !line.ends_with('\n') {
+            return Ok(());
+        }
+        reader.set_limit(MAX_COMMAND_LEN as u64);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut response = handler.handle(&line);
+        response.push('\n');
+        stream.write_all(response.as_bytes())?;
+    }
+}
+
+fn error_json(message: &str) -> String {
+    format!(r#"{{"error":"{}"}}"#, escape_json(message))
+}
+
+/// Pretty-printed JSON on one line. Strings never hold raw newlines, so
+/// every line break and the indentation after it is whitespace.
+fn one_line(json: &str) -> String {
+    json.lines().map(str::trim_start).collect()
+}
+
+fn top_json(snapshot: &TelemetrySnapshot, n: usize) -> String {
+    let operations: Vec<String> = snapshot
+        .top_operations(n)
+        .into_iter()
+        .map(|(name, stats)| {
+            format!(
+                r#"{{"name":"{}","count":{},"mean_us":{},"p50_us":{},"p95_us":{},"p99_us":{},"max_us":{}}}"#,
+                escape_json(name),
+                stats.count,
+                json_f64(stats.avg_us()),
+                stats.median_us(),
+                stats.p95_us(),
+                stats.p99_us(),
+                stats.max_us
+            )
+        })
+        .collect();
+    format!(r#"{{"operations":[{}]}}"#, operations.join(","))
+}
+
+fn metric_json(snapshot: &TelemetrySnapshot, name: &str) -> String {
+    let mut values: Vec<(String, f64)> = snapshot
+        .counters
+        .iter()
+        .map(|(key, &v)| (key, v as f64))
+        .chain(snapshot.gauges.iter().map(|(key, &v)| (key, v)))
+        .filter(|(key, _)| split_labeled_key(key).0 == name)
+        .map(|(key, value)| (key.clone(), value))
+        .collect();
+    if let Some(stats) = snapshot.operation_stats.get(name) {
+        values.extend([
+            ("count".to_string(), stats.count as f64),
+            ("mean_us".to_string(), stats.avg_us()),
+            ("p50_us".to_string(), stats.median_us() as f64),
+            ("p95_us".to_string(), stats.p95_us() as f64),
+            ("p99_us".to_string(), stats.p99_us() as f64),
+            ("max_us".to_string(), stats.max_us as f64),
+        ]);
+    } else {
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    if values.is_empty() {
+        return error_json(&format!("no metric named {}", name));
+    }
+    let values: Vec<String> = values
+        .iter()
+        .map(|(key, value)| format!(r#""{}":{}"#, escape_json(key), json_f64(*value)))
+        .collect();
+    format!(
+        r#"{{"metric":"{}","values":{{{}}}}}"#,
+        escape_json(name),
+        values.join(",")
+    )
+}
+
+fn spans_json(spans: &[OtelSpan]) -> String {
+    let spans = privacy::enforce_spans(spans);
+    let spans: Vec<String> = spans
+        .iter()
+        .map(|span| {
+            let mut attributes: Vec<_> = span.attributes.iter().collect();
+            attributes.sort_by(|a, b| a.0.cmp(b.0));
+            let attributes: Vec<String> = attributes
+                .into_iter()
+                .map(|(key, value)| format!(r#""{}":{}"#, escape_json(key), value.to_json()))
+                .collect();
+            let status = match span.status {
+                SpanStatus::Ok => "ok",
+                SpanStatus::Error => "error",
+                SpanStatus::Unset => "unset",
+            };
+            format!(
+                r#"{{"trace_id":"{:032x}","span_id":"{:016x}","parent_span_id":"{:016x}","name":"{}","start_time_ns":{},"duration_ns":{},"status":"{}","attributes":{{{}}}}}"#,
+                span.trace_id,
+                span.span_id,
+                span.parent_span_id,
+                escape_json(&span.name),
+                span.start_time_ns,
+                span.duration_ns(),
+                status,
+                attributes.join(",")
+            )
+        })
+        .collect();
+    format!(r#"{{"spans":[{}]}}"#, spans.join(","))
+}
+
+fn reload_json(report: &ReloadReport) -> String {
+    let applied: Vec<String> = report
+        .applied
+        .iter()
+        .map(|change| format!("\"{}\"", escape_json(&change.key)))
+        .collect();
+    let rejected: Vec<String> = report
+        .rejected
+        .iter()
+        .map(|rejected| {
+            format!(
+                r#"{{"key":"{}","reason":"{}"}}"#,
+                escape_json(&rejected.change.key),
+                escape_json(&rejected.reason)
+            )
+        })
+        .collect();
+    format!(
+        r#"{{"applied":[{}],"rejected":[{}]}}"#,
+        applied.join(","),
+        rejected.join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obs::health::CheckResult;
+    use crate::obs::span_processor::SpanProcessor;
+
+    #[test]
+    fn test_commands_answer_one_json_line() {
+        let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+        telemetry.lock().unwrap().record_operation("query", 120);
+        telemetry
+            .lock()
+            .unwrap()
+            .add_to_counter_with_labels("hits_total", &[("tier", "l1")], 3);
+        let health = HealthRegistry::new();
+        health.register_readiness("index", || CheckResult::degraded("stale"));
+        let recent = RecentSpans::new(4);
+        let mut span = OtelSpan::new("lookup");
+        span.set_attribute("shard", 3i64);
+        span.end();
+        recent.on_end(&span);
+
+        let handler = QueryHandler::new()
+            .with_telemetry(telemetry)
+            .with_health(health)
+            .with_spans(recent);
+        for command in [
+            "snapshot",
+            "summary",
+            "prometheus",
+            "top",
+            "top 1",
+            "metric hits_total",
+            "health",
+            "spans",
+            "help",
+            "nope",
+        ] {
+            let response = handler.handle(command);
+            assert!(!response.contains('\n'), "{}: {}", command, response);
+            serde_json::from_str::<serde_json::Value>(&response)
+                .unwrap_or_else(|e| panic!("{}: {}: {}", command, e, response));
+        }
+        #[cfg(feature = "telemetry")]
+        assert!(handler.handle("snapshot").contains(r#""query": {"#));
+        assert!(handler
+            .handle("summary")
+            .contains(r#"\nOperations:\n  query:"#));
+        assert!(handler
+            .handle("top 1")
+            .starts_with(r#"{"operations":[{"name":"query","count":1,"#));
+        assert_eq!(
+            handler.handle("metric hits_total"),
+            r#"{"metric":"hits_total","values":{"hits_total{tier=\"l1\"}":3.0}}"#
+        );
+        assert!(handler.handle("metric query").contains(r#""max_us":120.0"#));
+        assert!(handler
+            .handle("health")
+            .starts_with(r#"{"status":"degraded""#));
+        assert!(handler
+            .handle("spans")
+            .contains(r#""attributes":{"shard":3}"#));
+        assert_eq!(handler.handle("top x"), r#"{"error":"usage: top [n]"}"#);
+        assert_eq!(
+            handler.handle("nope"),
+            r#"{"error":"unknown command: nope"}"#
+        );
+
+        let awkward = Arc::new(Mutex::new(Telemetry::default_config()));
+        awkward.lock().unwrap().record_operation("op\tx\r\u{1}", 5);
+        let handler = QueryHandler::new().with_telemetry(awkward);
+        for command in ["snapshot", "summary", "prometheus", "top"] {
+            let response = handler.handle(command);
+            serde_json::from_str::<serde_json::Value>(&response)
+                .unwrap_or_else(|e| panic!("{}: {}: {}", command, e, response));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_serves_socket_and_cleans_up() {
+        let path = std::env::temp_dir().join(format!(
+            "embeddenator_obs_query_{}.sock",
+            std::process::id()
+        ));
+        let server = QueryServer::bind(&path).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        let handle = server.spawn();
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.write_all(b"help\n\nsnapshot\n").unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), r#"{"commands":["help"]}"#);
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            r#"{"error":"telemetry not configured"}"#
+        );
+        assert!(lines.next().is_none());
+
+        // A live server keeps its socket
+        assert!(QueryServer::bind(&path).is_err());
+        handle.stop();
+        assert!(!path.exists());
+        // A stale file is replaced
+        fs::write(&path, b"").unwrap();
+        assert!(QueryServer::bind(&path).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stalled_client_does_not_block_others() {
+        let path = std::env::temp_dir().join(format!(
+            "embeddenator_obs_query_stall_{}.sock",
+            std::process::id()
+        ));
+        let handle = QueryServer::bind(&path)
+            .unwrap()
+            .with_max_connections(2)
+            .spawn();
+
+        // Never sends a command
+        let _stalled = UnixStream::connect(&path).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        stream.write_all(b"help\n").unwrap();
+        let mut line = String::new();
+        BufReader::new(stream.try_clone().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        assert_eq!(line, "{\"commands\":[\"help\"]}\n");
+        std::thread::sleep(Duration::from_millis(200));
+
+        // Both slots are taken, so a third connection is closed unanswered
+        let mut third = UnixStream::connect(&path).unwrap();
+        third
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let _ = third.write_all(b"help\n");
+        let mut answer = String::new();
+        assert_eq!(BufReader::new(third).read_line(&mut answer).unwrap_or(0), 0);
+        drop(stream);
+        handle.stop();
+    }
+}
@@ -0,0 +1,303 @@
+//! Exporter Endpoint Reachability Self-Check
+//!
+//! Periodically verifies that configured remote sinks (OTLP collector,
+//! Pushgateway, webhooks, StatsD agents over TCP) accept connections, so
+//! "dashboards are empty" incidents are detected from inside the process
+//! instead of by a human staring at a blank panel.
+//!
+//! A check is a TCP connect with timeout to the sink's host and port; it
+//! proves the network path and listener, not that the sink accepts data.
+//!
+//! # Reported Signals
+//!
+//! - `sink_up{sink}` gauge: 1 when reachable, 0 otherwise
+//! - `sink_check_seconds{sink}` gauge: connect latency of the last check
+//! - [`SinkHealth`]: shared status for health endpoints
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::reachability::ReachabilityChecker;
+//!
+//! let checker = ReachabilityChecker::new()
+//!     .sink("otlp", "http://otel-collector:4318/v1/traces")
+//!     .sink("pushgateway", "http://pushgateway:9091");
+//! let health = checker.health();
+//! let _handle = checker.spawn(Duration::from_secs(60), telemetry.clone());
+//!
+//! if !health.is_healthy() { /* report degraded */ }
+//! ```
+
+use crate::obs::logging;
+use crate::obs::process::{spawn_collector, CollectorHandle};
+use crate::obs::telemetry::Telemetry;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Result of the last check of one sink.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SinkStatus {
+    pub name: String,
+    pub target: String,
+    pub reachable: bool,
+    /// Connect latency when reachable
+    pub latency: Option<Duration>,
+    pub error: Option<String>,
+    pub checked_at: SystemTime,
+}
+
+/// Shared, cloneable view of the latest sink statuses.
+#[derive(Debug, Clone, Default)]
+pub struct SinkHealth {
+    statuses: Arc<Mutex<Vec<SinkStatus>>>,
+}
+
+impl SinkHealth {
+    /// Latest status of every sink (empty before the first check).
+    pub fn statuses(&self) -> Vec<SinkStatus> {
+        self.statuses.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// Sinks that failed their last check.
+    pub fn unreachable(&self) -> Vec<SinkStatus> {
+        self.statuses()
+            .into_iter()
+            .filter(|status| !status.reachable)
+            .collect()
+    }
+
+    /// True when every checked sink was reachable.
+    pub fn is_healthy(&self) -> bool {
+        self.unreachable().is_empty()
+    }
+}
+
+struct Sink {
+    name: String,
+    target: String,
+}
+
+/// Periodic reachability checker for remote sinks.
+pub struct ReachabilityChecker {
+    sinks: Vec<Sink>,
+    timeout: Duration,
+    health: SinkHealth,
+}
+
+impl Default for ReachabilityChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReachabilityChecker {
+    /// Checker with a 3 second connect timeout.
+    pub fn new() -> Self {
+        Self {
+            sinks: Vec::new(),
+            timeout: Duration::from_secs(3),
+            health: SinkHealth::default(),
+        }
+    }
+
+    /// Add a sink by URL (`http://host:port/path`) or `host:port`.
+    pub fn sink(mut self, name: impl Into<String>, target: impl Into<String>) -> Self {
+        self.sinks.push(Sink {
+            name: name.into(),
+            target: target.into(),
+        });
+        self
+    }
+
+    /// Connect timeout per sink.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Shared status handle; stays valid after [`spawn`](Self::spawn).
+    pub fn health(&self) -> SinkHealth {
+        self.health.clone()
+    }
+
+    /// Check every sink once and update the shared status.
+    pub fn check_all(&self) -> Vec<SinkStatus> {
+        let previous = self.health.statuses();
+        let statuses: Vec<SinkStatus> = self
+            .sinks
+            .iter()
+            .map(|sink| check_sink(sink, self.timeout))
+            .collect();
+
+        for status in &statuses {
+            let was_reachable = previous
+                .iter()
+                .find(|p| p.name == status.name)
+                .is_none_or(|p| p.reachable);
+            if was_reachable && !status.reachable {
+                logging::warn(&format!(
+                    "sink '{}' ({}) unreachable: {}",
+                    status.name,
+                    status.target,
+                    status.error.as_deref().unwrap_or("unknown error")
+                ));
+            }
+        }
+
+        if let Ok(mut shared) = self.health.statuses.lock() {
+            *shared = statuses.clone();
+        }
+        statuses
+    }
+
+    /// Check every sink and write `sink_up` / `sink_check_seconds` gauges.
+    pub fn collect_into(&self, telemetry: &mut Telemetry) -> Vec<SinkStatus> {
+        let statuses = self.check_all();
+        for status in &statuses {
+            let labels = [("sink", status.name.as_str())];
+            telemetry.set_gauge_with_labels(
+                "sink_up",
+                &labels,
+                if status.reachable { 1.0 } else { 0.0 },
+            );
+            if let Some(latency) = status.latency {
+                telemetry.set_gauge_with_labels(
+                    "sink_check_seconds",
+                    &labels,
+                    latency.as_secs_f64(),
+                );
+            }
+        }
+        statuses
+    }
+
+    /// Check into shared telemetry every `interval` on a background thread.
+    pub fn spawn(self, interval: Duration, telemetry: Arc<Mutex<Telemetry>>) -> CollectorHandle {
+        spawn_collector("obs-reachability", interval, telemetry, move |telemetry| {
+            self.collect_into(telemetry);
+        })
+    }
+}
+
+fn check_sink(sink: &Sink, timeout: Duration) -> SinkStatus {
+    let started = Instant::now();
+    let result = resolve_target(&sink.target).and_then(|addrs| {
+        let mut last_error = String::from("no addresses resolved");
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(_) => return Ok(started.elapsed()),
+                Err(e) => last_error = e.to_string(),
+            }
+        }
+        Err(last_error)
+    });
+
+    let (reachable, latency, error) = match result {
+        Ok(latency) => (true, Some(latency), None),
+        Err(error) => (false, None, Some(error)),
+    };
+    SinkStatus {
+        name: sink.name.clone(),
+        target: sink.target.clone(),
+        reachable,
+        latency,
+        error,
+        checked_at: SystemTime::now(),
+    }
+}
+
+fn resolve_target(target: &str) -> Result<Vec<std::net::SocketAddr>, String> {
+    let (host, port) = host_port(target).ok_or_else(|| format!("invalid target '{}'", target))?;
+    (host, port)
+        .to_socket_addrs()
+        .map(|addrs| addrs.collect())
+        .map_err(|e| e.to_string())
+}
+
+/// Extract host and port from a URL or `host:port`, defaulting the port
+/// from the scheme (80 for http, 443 for https).
+fn host_port(target: &str) -> Option<(&str, u16)> {
+    let (scheme, rest) = match target.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, target),
+    };
+    let authority = rest.split(['/', '?', '#']).next()?;
+    // Drop userinfo
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, a)| a);
+
+    let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+        // IPv6 literal: [::1]:8080
+        let (host, after) = bracketed.split_once(']')?;
+        (host, after.strip_prefix(':'))
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    if host.is_empty() {
+        return None;
+    }
+
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => match scheme {
+            Some("https") | Some("grpcs") => 443,
+            Some("http") | Some("grpc") => 80,
+            _ => return None,
+        },
+    };
+    Some((host, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_host_port() {
+        assert_eq!(
+            host_port("http://collector:4318/v1/traces"),
+            Some(("collector", 4318))
+        );
+        assert_eq!(
+            host_port("https://hooks.example.com/x"),
+            Some(("hooks.example.com", 443))
+        );
+        assert_eq!(host_port("http://user:pw@gw/metrics"), Some(("gw", 80)));
+        assert_eq!(host_port("[::1]:9091"), Some(("::1", 9091)));
+        assert_eq!(host_port("statsd:8125"), Some(("statsd", 8125)));
+        assert_eq!(host_port("no-port"), None);
+    }
+
+    #[test]
+    fn test_reachable_and_unreachable_sinks() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        let closed = {
+            let temp = TcpListener::bind("127.0.0.1:0").unwrap();
+            temp.local_addr().unwrap()
+        };
+
+        let checker = ReachabilityChecker::new()
+            .sink("up", format!("http://{}/v1/traces", open))
+            .sink("down", closed.to_string())
+            .with_timeout(Duration::from_secs(1));
+        let health = checker.health();
+        let mut telemetry = Telemetry::default_config();
+
+        let statuses = checker.collect_into(&mut telemetry);
+        assert!(statuses[0].reachable);
+        assert!(!statuses[1].reachable);
+        assert!(statuses[1].error.is_some());
+
+        assert!(!health.is_healthy());
+        assert_eq!(health.unreachable()[0].name, "down");
+
+        let gauges = telemetry.snapshot().gauges;
+        assert_eq!(gauges.get(r#"sink_up{sink="up"}"#), Some(&1.0));
+        assert_eq!(gauges.get(r#"sink_up{sink="down"}"#), Some(&0.0));
+    }
+}
@@ -0,0 +1,842 @@
+//! Sensitive Data Redaction for Logs
+//!
+//! An installed [`Redactor`] masks secrets in every log record before it
+//! is written anywhere: stderr, log files, journald and syslog, captured
+//! span events (and so OTLP export) and test log sinks. Redaction
+//! happens inside the logging pipeline, so call sites cannot forget it.
+//!
+//! - Field names: a field whose name contains a configured pattern
+//!   (case-insensitive, `-` and `_` equivalent) has its whole value
+//!   masked. [`Redactor::new`] starts with `token`, `password`,
+//!   `passwd`, `secret`, `api_key`, `authorization` and `cookie`.
+//! - Value patterns: matches of a [`Pattern`] are masked in messages and
+//!   field values, and in formatted `tracing` output from other crates.
+//!
+//! Patterns are a regular expression subset: literals, `.`, classes
+//! (`[a-z0-9_-]`, `[^"]`), `\d \w \s \D \W \S`, `\b`, anchors `^ $`,
+//! groups with alternation `(a|b)` and greedy quantifiers `* + ?
+//! {n} {n,} {n,m}`. Matching is case-sensitive and runs without
+//! backtracking, in time linear in the text for each match attempt.
+//! Only the first [`MAX_SCAN_BYTES`] of a message or value are scanned;
+//! the rest is replaced by the mask.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::redaction::Redactor;
+//!
+//! Redactor::new()
+//!     .redact_field("session")
+//!     .redact_pattern(r"Bearer [A-Za-z0-9._-]+")?
+//!     .redact_pattern(r"\b\d{4}-\d{4}-\d{4}-\d{4}\b")?
+//!     .install();
+//!
+//! info_kv("login", &[("user", "ada".into()), ("password", "hunter2".into())]);
+//! // login user=ada password=redacted
+//! ```
+
+use crate::obs::opentelemetry::AttributeValue;
+use crate::obs::privacy::REDACTED;
+use std::borrow::Cow;
+use std::sync::RwLock;
+
+static REDACTOR: RwLock<Option<Redactor>> = RwLock::new(None);
+
+/// Field names masked by [`Redactor::new`].
+const SENSITIVE_FIELDS: [&str; 7] = [
+    "token",
+    "password",
+    "passwd",
+    "secret",
+    "api_key",
+    "authorization",
+    "cookie",
+];
+
+/// Longest prefix of a message or value that patterns are run over.
+pub const MAX_SCAN_BYTES: usize = 64 * 1024;
+
+/// Rules for masking secrets in log records.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    /// Normalized field-name substrings
+    fields: Vec<String>,
+    patterns: Vec<Pattern>,
+    mask: String,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Redactor {
+    /// Mask fields named like common credentials.
+    pub fn new() -> Self {
+        SENSITIVE_FIELDS
+            .iter()
+            .fold(Self::empty(), |redactor, field| {
+                redactor.redact_field(field)
+            })
+    }
+
+    /// Mask nothing until rules are added.
+    pub fn empty() -> Self {
+        Self {
+            fields: Vec::new(),
+            patterns: Vec::new(),
+            mask: REDACTED.to_string(),
+        }
+    }
+
+    /// Mask values of fields whose name contains `pattern`.
+    pub fn redact_field(mut self, pattern: &str) -> Self {
+        let pattern = normalize_field(pattern);
+        if !pattern.is_empty() && !self.fields.contains(&pattern) {
+            self.fields.push(pattern);
+        }
+        self
+    }
+
+    /// Mask matches of `pattern` in messages and values.
+    pub fn redact_pattern(mut self, pattern: &str) -> Result<Self, PatternError> {
+        self.patterns.push(Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Replacement text; [`REDACTED`] by default.
+    pub fn with_mask(mut self, mask: impl Into<String>) -> Self {
+        self.mask = mask.into();
+        self
+    }
+
+    /// Apply to all log records from now on.
+    pub fn install(self) {
+        *REDACTOR.write().unwrap_or_else(|e| e.into_inner()) = Some(self);
+    }
+
+    /// Stop redacting.
+    pub fn uninstall() {
+        *REDACTOR.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// The installed redactor, if any.
+    pub fn installed() -> Option<Self> {
+        REDACTOR.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Whether values of field `name` are masked whole.
+    pub fn is_sensitive(&self, name: &str) -> bool {
+        let name = normalize_field(name);
+        self.fields
+            .iter()
+            .any(|pattern| name.contains(pattern.as_str()))
+    }
+
+    /// `text` with pattern matches masked. Only the first
+    /// [`MAX_SCAN_BYTES`] are scanned; anything past them is replaced by
+    /// the mask, since it may hold a secret.
+    pub fn redact_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.patterns.is_empty() {
+            return Cow::Borrowed(text);
+        }
+        let truncated = text.len() > MAX_SCAN_BYTES;
+        let mut text = if truncated {
+            let mut cut = MAX_SCAN_BYTES;
+            while !text.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            Cow::Owned(text[..cut].to_string())
+        } else {
+            Cow::Borrowed(text)
+        };
+        for pattern in &self.patterns {
+            if let Cow::Owned(replaced) = pattern.replace_all(&text, &self.mask) {
+                text = Cow::Owned(replaced);
+            }
+        }
+        if truncated {
+            text.to_mut().push_str(&self.mask);
+        }
+        text
+    }
+
+    /// Value of field `name`: masked whole if the name is sensitive, else
+    /// with pattern matches masked.
+    pub fn redact_value<'a>(&self, name: &str, value: &'a str) -> Cow<'a, str> {
+        if self.is_sensitive(name) {
+            Cow::Owned(self.mask.clone())
+        } else {
+            self.redact_text(value)
+        }
+    }
+
+    /// Typed value of field `name`; non-string values are only masked
+    /// for sensitive names.
+    pub fn redact_attribute(&self, name: &str, value: &AttributeValue) -> AttributeValue {
+        if self.is_sensitive(name) {
+            return AttributeValue::String(self.mask.clone());
+        }
+        match value {
+            AttributeValue::String(text) => {
+                AttributeValue::String(self.redact_text(text).into_owned())
+            }
+            AttributeValue::StringArray(items) => AttributeValue::StringArray(
+                items
+                    .iter()
+                    .map(|item| self.redact_text(item).into_owned())
+                    .collect(),
+            ),
+            value => value.clone(),
+        }
+    }
+}
+
+fn normalize_field(name: &str) -> String {
+    name.to_ascii_lowercase().replace('-', "_")
+}
+
+/// Message and fields of a redacted record.
+pub(crate) type RedactedRecord<'a> = (Cow<'a, str>, Vec<(&'a str, Cow<'a, str>)>);
+
+/// Message and fields of a record with the installed redactor applied;
+/// borrowed unchanged when none is installed.
+pub(crate) fn redact_record<'a>(
+    message: &'a str,
+    fields: &[(&'a str, &'a str)],
+) -> RedactedRecord<'a> {
+    let guard = REDACTOR.read().unwrap_or_else(|e| e.into_inner());
+    match &*guard {
+        Some(redactor) => (
+            redactor.redact_text(message),
+            fields
+                .iter()
+                .map(|(key, value)| (*key, redactor.redact_value(key, value)))
+                .collect(),
+        ),
+        None => (
+            Cow::Borrowed(message),
+            fields
+                .iter()
+                .map(|(key, value)| (*key, Cow::Borrowed(*value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Whether a redactor is installed.
+#[cfg(feature = "logging")]
+pub(crate) fn active() -> bool {
+    REDACTOR.read().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// `text` with the installed redactor's patterns applied.
+pub(crate) fn redact_text(text: &str) -> Cow<'_, str> {
+    match &*REDACTOR.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(redactor) => Cow::Owned(redactor.redact_text(text).into_owned()),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Typed fields with the installed redactor applied, if any.
+pub(crate) fn redact_attributes<'a>(
+    fields: &'a [(&'a str, AttributeValue)],
+) -> Cow<'a, [(&'a str, AttributeValue)]> {
+    match &*REDACTOR.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(redactor) => Cow::Owned(
+            fields
+                .iter()
+                .map(|(key, value)| (*key, redactor.redact_attribute(key, value)))
+                .collect(),
+        ),
+        None => Cow::Borrowed(fields),
+    }
+}
+
+/// Invalid [`Pattern`] syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternError {
+    pub pattern: String,
+    /// Character offset of the problem
+    pub position: usize,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid pattern {:?} at {}: {}",
+            self.pattern, self.position, self.reason
+        )
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// Compiled regular expression subset, see the module docs.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    source: String,
+    program: Vec<Inst>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+    Start,
+    End,
+    WordBoundary,
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+    },
+}
+
+impl Node {
+    /// Whether a single-character node accepts `c`.
+    fn accepts(&self, c: char) -> bool {
+        match self {
+            Node::Char(expected) => *expected == c,
+            Node::Any => c != '\n',
+            Node::Class { ranges, negated } => {
+                ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated
+            }
+            _ => false,
+        }
+    }
+}
+
+const DIGITS: [(char, char); 1] = [('0', '9')];
+const WORD: [(char, char); 4] = [('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+const SPACE: [(char, char); 4] = [(' ', ' '), ('\t', '\t'), ('\n', '\r'), ('\u{85}', '\u{85}')];
+
+fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &'static str) -> PatternError {
+        PatternError {
+            pattern: self.source.to_string(),
+            position: self.pos,
+            reason,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    /// Alternatives up to `)` or the end.
+    fn alternation(&mut self) -> Result<Vec<Vec<Node>>, PatternError> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, PatternError> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(nodes)
+    }
+
+    fn atom(&mut self) -> Result<Node, PatternError> {
+        let c = self.peek().ok_or_else(|| self.error("unexpected end"))?;
+        self.pos += 1;
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                let alternatives = self.alternation()?;
+                if self.peek() != Some(')') {
+                    return Err(self.error("unclosed group"));
+                }
+                self.pos += 1;
+                Node::Group(alternatives)
+            }
+            '[' => self.class()?,
+            '\\' => self.escape(false)?,
+            '*' | '+' | '?' | '{' => return Err(self.error("nothing to repeat")),
+            c => Node::Char(c),
+        })
+    }
+
+    /// Escape after `\`; inside a class `\b` is not a boundary.
+    fn escape(&mut self, in_class: bool) -> Result<Node, PatternError> {
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error("trailing backslash"))?;
+        self.pos += 1;
+        let class = |ranges: &[(char, char)], negated| Node::Class {
+            ranges: ranges.to_vec(),
+            negated,
+        };
+        Ok(match c {
+            'd' => class(&DIGITS, false),
+            'D' => class(&DIGITS, true),
+            'w' => class(&WORD, false),
+            'W' => class(&WORD, true),
+            's' => class(&SPACE, false),
+            'S' => class(&SPACE, true),
+            'b' if !in_class => Node::WordBoundary,
+            'n' => Node::Char('\n'),
+            't' => Node::Char('\t'),
+            'r' => Node::Char('\r'),
+            c if c.is_ascii_alphanumeric() => return Err(self.error("unsupported escape")),
+            c => Node::Char(c),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, PatternError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("unclosed class"))?;
+            self.pos += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let lo = if c == '\\' {
+                match self.escape(true)? {
+                    Node::Char(c) => c,
+                    Node::Class {
+                        ranges: escaped,
+                        negated: false,
+                    } => {
+                        ranges.extend(escaped);
+                        continue;
+                    }
+                    _ => return Err(self.error("negated escape inside class")),
+                }
+            } else {
+                c
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let hi = self.peek().ok_or_else(|| self.error("unclosed class"))?;
+                self.pos += 1;
+                if hi < lo {
+                    return Err(self.error("range out of order"));
+                }
+                ranges.push((lo, hi));
+            } else {
+                ranges.push((lo, lo));
+            }
+        }
+        Ok(Node::Class { ranges, negated })
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, PatternError> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                self.pos += 1;
+                let min = self
+                    .number()?
+                    .ok_or_else(|| self.error("expected a count"))?;
+                let max = match self.peek() {
+                    Some(',') => {
+                        self.pos += 1;
+                        self.number()?
+                    }
+                    _ => Some(min),
+                };
+                if self.peek() != Some('}') {
+                    return Err(self.error("unclosed count"));
+                }
+                if max.is_some_and(|max| max < min) {
+                    return Err(self.error("count out of order"));
+                }
+                (min, max)
+            }
+            _ => return Ok(atom),
+        };
+        self.pos += 1;
+        if matches!(atom, Node::Start | Node::End | Node::WordBoundary) {
+            return Err(self.error("nothing to repeat"));
+        }
+        Ok(Node::Repeat {
+            node: Box::new(atom),
+            min,
+            max,
+        })
+    }
+
+    fn number(&mut self) -> Result<Option<u32>, PatternError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Ok(None);
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits
+            .parse()
+            .map(Some)
+            .map_err(|_| self.error("count too large"))
+    }
+}
+
+impl Pattern {
+    /// Compile `source`.
+    pub fn new(source: &str) -> Result<Self, PatternError> {
+        let mut parser = Parser {
+            source,
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+        let alternatives = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unmatched )"));
+        }
+        let mut program = Vec::new();
+        compile_alternatives(&alternatives, &mut program);
+        program.push(Inst::Match);
+        if program.len() > MAX_PROGRAM {
+            return Err(parser.error("pattern too large"));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            program,
+        })
+    }
+
+    /// Source text of the pattern.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the pattern matches anywhere in `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        self.find_at(&chars, 0).is_some()
+    }
+
+    /// `text` with non-overlapping leftmost matches replaced by
+    /// `replacement`; borrowed if nothing matched.
+    pub fn replace_all<'a>(&self, text: &'a str, replacement: &str) -> Cow<'a, str> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::new();
+        let mut copied = 0;
+        let mut from = 0;
+        while from <= chars.len() {
+            match self.find_at(&chars, from) {
+                Some((start, end)) if end > start => {
+                    out.extend(&chars[copied..start]);
+                    out.push_str(replacement);
+                    copied = end;
+                    from = end;
+                }
+                Some((start, _)) => from = start + 1,
+                None => break,
+            }
+        }
+        if copied == 0 && out.is_empty() {
+            return Cow::Borrowed(text);
+        }
+        out.extend(&chars[copied..]);
+        Cow::Owned(out)
+    }
+
+    /// Leftmost match at or after `from` as `(start, end)`, preferring
+    /// alternatives and repeat counts in the order a backtracker would.
+    ///
+    /// Runs the program as a Pike VM: every live thread advances one
+    /// character per step, so a search costs O(text × program) time
+    /// with no recursion.
+    fn find_at(&self, text: &[char], from: usize) -> Option<(usize, usize)> {
+        let mut vm = Vm {
+            program: &self.program,
+            text,
+            marks: vec![0; self.program.len()],
+            stack: Vec::new(),
+        };
+        let mut current = Vec::new();
+        let mut next = Vec::new();
+        let mut found = None;
+        for pos in from..=text.len() {
+            let stamp = pos - from + 1;
+            if found.is_none() {
+                // New attempts rank below every thread started earlier
+                vm.add_thread(&mut current, 0, pos, pos, stamp);
+            }
+            if current.is_empty() {
+                if found.is_some() {
+                    break;
+                }
+                continue;
+            }
+            for &(pc, start) in &current {
+                match &self.program[pc] {
+                    Inst::Match => {
+                        // Lower-priority threads can no longer win
+                        found = Some((start, pos));
+                        break;
+                    }
+                    Inst::Consume(node) if pos < text.len() && node.accepts(text[pos]) => {
+                        vm.add_thread(&mut next, pc + 1, start, pos + 1, stamp + 1);
+                    }
+                    _ => {}
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+        found
+    }
+}
+
+/// Upper bound on compiled instructions, so counted repeats like
+/// `(\w{100}){100}` cannot blow up memory and match time.
+const MAX_PROGRAM: usize = 10_000;
+
+#[derive(Debug, Clone)]
+enum Inst {
+    /// Consume one character accepted by a single-character node
+    Consume(Node),
+    /// Zero-width `^`, `$` or `\b`
+    Assert(Node),
+    /// Try the first target, then the second
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+fn compile_alternatives(alternatives: &[Vec<Node>], program: &mut Vec<Inst>) {
+    let mut jumps = Vec::new();
+    for (i, alternative) in alternatives.iter().enumerate() {
+        if i + 1 < alternatives.len() {
+            let split = program.len();
+            program.push(Inst::Split(split + 1, 0));
+            compile_sequence(alternative, program);
+            jumps.push(program.len());
+            program.push(Inst::Jump(0));
+            program[split] = Inst::Split(split + 1, program.len());
+        } else {
+            compile_sequence(alternative, program);
+        }
+    }
+    let end = program.len();
+    for jump in jumps {
+        program[jump] = Inst::Jump(end);
+    }
+}
+
+fn compile_sequence(nodes: &[Node], program: &mut Vec<Inst>) {
+    for node in nodes {
+        // Stop expanding once too large; `Pattern::new` reports it
+        if program.len() > MAX_PROGRAM {
+            return;
+        }
+        compile_node(node, program);
+    }
+}
+
+fn compile_node(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Start | Node::End | Node::WordBoundary => program.push(Inst::Assert(node.clone())),
+        Node::Group(alternatives) => compile_alternatives(alternatives, program),
+        Node::Repeat { node, min, max } => {
+            for _ in 0..*min {
+                if program.len() > MAX_PROGRAM {
+                    return;
+                }
+                compile_node(node, program);
+            }
+            match max {
+                None => {
+                    // Greedy loop: prefer another iteration over leaving
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile_node(node, program);
+                    program.push(Inst::Jump(split));
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        if program.len() > MAX_PROGRAM {
+                            return;
+                        }
+                        splits.push(program.len());
+                        program.push(Inst::Split(0, 0));
+                        compile_node(node, program);
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(split + 1, end);
+                    }
+                }
+            }
+        }
+        single => program.push(Inst::Consume(single.clone())),
+    }
+}
+
+/// Scratch state for one [`Pattern::find_at`] search.
+struct Vm<'a> {
+    program: &'a [Inst],
+    text: &'a [char],
+    /// Stamp of the thread list each instruction was last added to
+    marks: Vec<usize>,
+    stack: Vec<usize>,
+}
+
+impl Vm<'_> {
+    /// Add the thread at `pc` to `list`, following jumps, splits and
+    /// assertions at `pos` in priority order. Each instruction joins a
+    /// list at most once; the first (highest-priority) arrival wins.
+    fn add_thread(
+        &mut self,
+        list: &mut Vec<(usize, usize)>,
+        pc: usize,
+        start: usize,
+        pos: usize,
+        stamp: usize,
+    ) {
+        self.stack.push(pc);
+        while let Some(pc) = self.stack.pop() {
+            if self.marks[pc] == stamp {
+                continue;
+            }
+            self.marks[pc] = stamp;
+            match &self.program[pc] {
+                Inst::Jump(target) => self.stack.push(*target),
+                Inst::Split(first, second) => {
+                    self.stack.push(*second);
+                    self.stack.push(*first);
+                }
+                Inst::Assert(node) => {
+                    let text = self.text;
+                    let holds = match node {
+                        Node::Start => pos == 0,
+                        Node::End => pos == text.len(),
+                        _ => {
+                            let before = pos > 0 && is_word(text[pos - 1]);
+                            let after = pos < text.len() && is_word(text[pos]);
+                            before != after
+                        }
+                    };
+                    if holds {
+                        self.stack.push(pc + 1);
+                    }
+                }
+                Inst::Consume(_) | Inst::Match => list.push((pc, start)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_matching() {
+        let card = Pattern::new(r"\b\d{4}(-\d{4}){3}\b").unwrap();
+        assert_eq!(
+            card.replace_all("paid with 1234-5678-9012-3456 today", "#"),
+            "paid with # today"
+        );
+        assert!(!card.is_match("x1234-5678-9012-3456"));
+
+        let bearer = Pattern::new(r"Bearer [A-Za-z0-9._-]+").unwrap();
+        assert_eq!(
+            bearer.replace_all("Authorization: Bearer ab.c_9-x, next", "***"),
+            "Authorization: ***, next"
+        );
+
+        let alt = Pattern::new(r"^(key|secret)=[^&]*").unwrap();
+        assert_eq!(alt.replace_all("secret=abc&x=1", "?"), "?&x=1");
+        assert!(!alt.is_match("a&key=1"));
+
+        let backtrack = Pattern::new(r"a(bc)*c").unwrap();
+        assert_eq!(backtrack.replace_all("abcbcc abc ac", "_"), "_ abc _");
+        assert!(matches!(
+            Pattern::new("ok").unwrap().replace_all("none here", "_"),
+            Cow::Borrowed(_)
+        ));
+
+        for invalid in ["(a", "a)", "[a-", "*a", r"\q", "a{3,1}", r"(\w{100}){200}"] {
+            assert!(Pattern::new(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_long_values() {
+        let key = Pattern::new(r"key=(\w|-)+").unwrap();
+        let value = format!("key={} rest", "a-".repeat(50_000));
+        assert_eq!(key.replace_all(&value, "#"), "# rest");
+        let pairs = Pattern::new("(ab)+").unwrap();
+        assert!(pairs.is_match(&"ab".repeat(100_000)));
+
+        let email = Pattern::new(r"[a-z]+@[a-z]+\.com").unwrap();
+        let line = "x".repeat(20_000) + " ada@example.com";
+        let started = std::time::Instant::now();
+        assert!(email.replace_all(&line, "#").ends_with(" #"));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+        let redactor = Redactor::empty().redact_pattern("secret").unwrap();
+        let long = "a".repeat(MAX_SCAN_BYTES) + "secret";
+        let redacted = redactor.redact_text(&long);
+        assert!(!redacted.contains("secret"));
+        assert!(redacted.ends_with(REDACTED));
+        assert_eq!(redacted.len(), MAX_SCAN_BYTES + REDACTED.len());
+    }
+
+    #[test]
+    fn test_redactor_fields_and_values() {
+        let redactor = Redactor::new().redact_pattern(r"sk_[a-z0-9]{6,}").unwrap();
+        assert!(redactor.is_sensitive("X-API-Key"));
+        assert!(redactor.is_sensitive("access_token"));
+        assert!(!redactor.is_sensitive("user"));
+
+        assert_eq!(redactor.redact_value("password", "hunter2"), REDACTED);
+        assert_eq!(
+            redactor.redact_value("note", "key sk_live42abc used"),
+            "key redacted used"
+        );
+        assert_eq!(
+            redactor.redact_attribute("session_token", &AttributeValue::I64(7)),
+            AttributeValue::String(REDACTED.to_string())
+        );
+        assert_eq!(
+            redactor.redact_attribute("shard", &AttributeValue::I64(7)),
+            AttributeValue::I64(7)
+        );
+        assert!(Redactor::empty().redact_pattern("(").is_err());
+    }
+}
@@ -0,0 +1,396 @@
+//! Metric Metadata Registry
+//!
+//! Declares each metric's type, unit, and description once, so exporters
+//! can emit real `# HELP` text and unit-suffixed names instead of generic
+//! placeholders, and JSON consumers can tell bytes from seconds.
+//!
+//! Descriptors are keyed by the metric name as recorded in [`Telemetry`]
+//! (for labeled keys, the part before `{`; for operations, the operation
+//! name).
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::registry::MetricDescriptor;
+//!
+//! let mut telemetry = Telemetry::default_config();
+//! telemetry.describe(MetricDescriptor::counter("bytes_read_total", "Bytes read from index files").with_unit("bytes"));
+//! telemetry.describe(MetricDescriptor::gauge("queue_depth", "Pending ingest batches"));
+//!
+//! // Prometheus output:
+//! // # HELP embeddenator_bytes_read_bytes_total Bytes read from index files
+//! // # TYPE embeddenator_bytes_read_bytes_total counter
+//! ```
+//!
+//! # Schema Dump
+//!
+//! [`MetricRegistry::schema_dump`] renders the registry as a JSON catalog
+//! (name, type, unit, labels, description, source module) for
+//! dashboard-as-code tooling. [`MetricRegistry::builtin`] describes every
+//! metric written by this crate's collectors, and
+//! [`Telemetry::schema_dump`] merges it with application descriptors and
+//! the keys recorded so far.
+//!
+//! ```json
+//! {
+//!   "metrics": [
+//!     {"name": "disk_available_bytes", "type": "gauge", "unit": "bytes", "labels": ["path"], "help": "Space available to unprivileged users", "source": "disk_watcher"}
+//!   ]
+//! }
+//! ```
+//!
+//! [`Telemetry`]: crate::obs::telemetry::Telemetry
+//! [`Telemetry::schema_dump`]: crate::obs::telemetry::Telemetry::schema_dump
+
+use crate::obs::telemetry::escape_json;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Declared metric type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+    Summary,
+}
+
+impl MetricKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+            MetricKind::Histogram => "histogram",
+            MetricKind::Summary => "summary",
+        }
+    }
+}
+
+/// Metadata for one metric name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricDescriptor {
+    pub name: String,
+    pub kind: MetricKind,
+    /// Base unit (`seconds`, `bytes`, `ratio`...), appended as a name suffix
+    pub unit: Option<String>,
+    pub help: String,
+    /// Label names the metric is recorded with
+    pub labels: Vec<String>,
+    /// Module or component emitting the metric
+    pub source: Option<String>,
+}
+
+impl MetricDescriptor {
+    pub fn new(name: impl Into<String>, kind: MetricKind, help: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            unit: None,
+            help: help.into(),
+            labels: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn counter(name: impl Into<String>, help: impl Into<String>) -> Self {
+        Self::new(name, MetricKind::Counter, help)
+    }
+
+    pub fn gauge(name: impl Into<String>, help: impl Into<String>) -> Self {
+        Self::new(name, MetricKind::Gauge, help)
+    }
+
+    pub fn histogram(name: impl Into<String>, help: impl Into<String>) -> Self {
+        Self::new(name, MetricKind::Histogram, help)
+    }
+
+    /// Set the unit; use base units (`seconds`, `bytes`) per Prometheus
+    /// naming conventions.
+    pub fn with_unit(mut self, unit: impl Into<String>) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+
+    /// Set the label names the metric is recorded with.
+    pub fn with_labels(mut self, labels: &[&str]) -> Self {
+        self.labels = labels.iter().map(|l| l.to_string()).collect();
+        self
+    }
+
+    /// Set the emitting module or component.
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// `name` with the unit suffix applied, before any `_total`:
+    /// `read_total` with unit `bytes` becomes `read_bytes_total`. Names
+    /// already carrying the suffix are unchanged.
+    pub fn unit_suffixed(&self, name: &str) -> String {
+        let Some(unit) = self.unit.as_deref().filter(|u| !u.is_empty()) else {
+            return name.to_string();
+        };
+        let (base, total) = match name.strip_suffix("_total") {
+            Some(base) => (base, "_total"),
+            None => (name, ""),
+        };
+        let suffix = format!("_{}", unit);
+        if base.ends_with(&suffix) {
+            name.to_string()
+        } else {
+            format!("{}{}{}", base, suffix, total)
+        }
+    }
+}
+
+/// Set of metric descriptors keyed by name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricRegistry {
+    descriptors: BTreeMap<String, MetricDescriptor>,
+}
+
+impl MetricRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a descriptor (builder style).
+    pub fn describe(mut self, descriptor: MetricDescriptor) -> Self {
+        self.register(descriptor);
+        self
+    }
+
+    /// Add or replace a descriptor.
+    pub fn register(&mut self, descriptor: MetricDescriptor) {
+        self.descriptors.insert(descriptor.name.clone(), descriptor);
+    }
+
+    /// Descriptor for a metric name (labels, if present, are ignored).
+    pub fn get(&self, name: &str) -> Option<&MetricDescriptor> {
+        let name = name.split_once('{').map_or(name, |(name, _)| name);
+        self.descriptors.get(name)
+    }
+
+    /// Descriptors sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = &MetricDescriptor> {
+        self.descriptors.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.descriptors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.descriptors.is_empty()
+    }
+
+    /// Record that `name` was seen with `labels`: adds a placeholder
+    /// descriptor for undeclared metrics and merges new label names.
+    pub(crate) fn observe(&mut self, name: &str, kind: MetricKind, labels: &[&str]) {
+        let descriptor = self
+            .descriptors
+            .entry(name.to_string())
+            .or_insert_with(|| MetricDescriptor::new(name, kind, "").with_source("application"));
+        for label in labels {
+            if !descriptor.labels.iter().any(|l| l == label) {
+                descriptor.labels.push(label.to_string());
+            }
+        }
+    }
+
+    /// Descriptors of every metric written by this crate's collectors.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        for line in BUILTIN_METRICS.lines().filter(|l| !l.trim().is_empty()) {
+            let mut fields = line.split_whitespace();
+            let (Some(source), Some(kind), Some(name), Some(labels)) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let kind = match kind {
+                "counter" => MetricKind::Counter,
+                _ => MetricKind::Gauge,
+            };
+            let help = fields.collect::<Vec<_>>().join(" ");
+            let mut descriptor = MetricDescriptor::new(name, kind, help).with_source(source);
+            if labels != "-" {
+                descriptor.labels = labels.split(',').map(str::to_string).collect();
+            }
+            if let Some(unit) = unit_from_name(name) {
+                descriptor = descriptor.with_unit(unit);
+            }
+            registry.register(descriptor);
+        }
+        registry
+    }
+
+    /// Machine-readable JSON catalog of all descriptors, sorted by name.
+    pub fn schema_dump(&self) -> String {
+        let mut json = String::new();
+        writeln!(json, "{{").unwrap();
+        writeln!(json, r#"  "metrics": ["#).unwrap();
+        for (i, descriptor) in self.iter().enumerate() {
+            let comma = if i < self.len() - 1 { "," } else { "" };
+            let optional = |value: &Option<String>| match value {
+                Some(value) => format!(r#""{}""#, escape_json(value)),
+                None => "null".to_string(),
+            };
+            let labels: Vec<String> = descriptor
+                .labels
+                .iter()
+                .map(|l| format!(r#""{}""#, escape_json(l)))
+                .collect();
+            writeln!(
+                json,
+                r#"    {{"name": "{}", "type": "{}", "unit": {}, "labels": [{}], "help": "{}", "source": {}}}{}"#,
+                escape_json(&descriptor.name),
+                descriptor.kind.as_str(),
+                optional(&descriptor.unit),
+                labels.join(", "),
+                escape_json(&descriptor.help),
+                optional(&descriptor.source),
+                comma
+            )
+            .unwrap();
+        }
+        writeln!(json, "  ]").unwrap();
+        writeln!(json, "}}").unwrap();
+        json
+    }
+}
+
+/// Base unit implied by a Prometheus-style name suffix.
+fn unit_from_name(name: &str) -> Option<&'static str> {
+    let base = name.strip_suffix("_total").unwrap_or(name);
+    ["bytes", "seconds", "ratio"]
+        .into_iter()
+        .find(|unit| base.ends_with(&format!("_{}", unit)))
+}
+
+/// Collector-written metrics: source module, kind, name, labels (`-` for
+/// none), help. Units follow from the name suffix.
+const BUILTIN_METRICS: &str = "
+telemetry        counter  cardinality_limited_total            metric         Label sets folded into the overflow series
+process          gauge    process_resident_memory_bytes        -              Resident set size
+process          gauge    process_virtual_memory_bytes         -              Virtual memory size
+process          gauge    process_cpu_seconds_total            -              User and system CPU time consumed
+process          gauge    process_threads                      -              OS threads in the process
+process          gauge    process_open_fds                     -              Open file descriptors
+process          gauge    process_max_fds                      -              File descriptor limit
+process          gauge    process_start_time_seconds           -              Process start time since the Unix epoch
+host             gauge    host_load1                           -              1 minute load average
+host             gauge    host_load5                           -              5 minute load average
+host             gauge    host_load15                          -              15 minute load average
+host             gauge    host_disk_total_bytes                path           Filesystem size
+host             gauge    host_disk_available_bytes            path           Filesystem space available
+host             gauge    host_disk_used_ratio                 path           Fraction of filesystem space used
+host             gauge    host_network_receive_bytes           interface      Bytes received per interface
+host             gauge    host_network_transmit_bytes          interface      Bytes transmitted per interface
+cgroup           gauge    cgroup_cpu_usage_seconds_total       -              CPU time consumed by the cgroup
+cgroup           gauge    cgroup_cpu_periods_total             -              Elapsed CFS enforcement periods
+cgroup           gauge    cgroup_cpu_throttled_periods_total   -              Periods in which the cgroup was throttled
+cgroup           gauge    cgroup_cpu_throttled_seconds_total   -              Time the cgroup spent throttled
+cgroup           gauge    cgroup_cpu_limit_cores               -              CPU quota in cores
+cgroup           gauge    cgroup_memory_current_bytes          -              Memory charged to the cgroup
+cgroup           gauge    cgroup_memory_max_bytes              -              Cgroup memory limit
+cgroup           gauge    cgroup_pressure_avg10                resource,kind  Cgroup stall percentage over 10s
+cgroup           gauge    cgroup_pressure_avg60                resource,kind  Cgroup stall percentage over 60s
+cgroup           gauge    cgroup_pressure_avg300               resource,kind  Cgroup stall percentage over 300s
+cgroup           gauge    cgroup_pressure_stall_seconds_total  resource,kind  Cgroup total stall time
+pressure         gauge    pressure_avg10                       resource,kind  System stall percentage over 10s
+pressure         gauge    pressure_avg60                       resource,kind  System stall percentage over 60s
+pressure         gauge    pressure_avg300                      resource,kind  System stall percentage over 300s
+pressure         gauge    pressure_stall_seconds_total         resource,kind  System total stall time
+pressure         counter  pressure_alerts_total                resource       Pressure threshold alerts fired
+disk_watcher     gauge    disk_total_bytes                     path           Filesystem size
+disk_watcher     gauge    disk_available_bytes                 path           Space available to unprivileged users
+disk_watcher     gauge    disk_available_ratio                 path           Fraction of space available
+disk_watcher     gauge    disk_inodes_free                     path           Free inodes
+disk_watcher     gauge    disk_inode_used_ratio                path           Fraction of inodes used
+disk_watcher     gauge    disk_write_probe_seconds             path           Latency of the write probe
+disk_watcher     counter  disk_alerts_total                    path,kind      Low space or inode alerts fired
+memory_watchdog  gauge    memory_usage_ratio                   -              Memory usage against the configured limit
+reachability     gauge    sink_up                              sink           1 when the sink accepted a connection
+reachability     gauge    sink_check_seconds                   sink           Connect latency of the last check
+index_build      gauge    index_build_progress                 -              Index build completion fraction
+index_build      gauge    index_build_bytes_written            -              Bytes written by the index build
+sampling         gauge    trace_sampling_probability           -              Current adaptive trace sampling probability
+sampling         gauge    trace_offered_spans_per_second       -              Spans offered to the sampler per second
+sampling         gauge    trace_sampled_spans_total            -              Spans kept by the sampling decision
+sampling         gauge    trace_error_spans_total              -              Unsampled error spans kept anyway
+concurrency      gauge    concurrency_in_flight                operation      Requests currently in flight
+concurrency      gauge    concurrency_limit_recommended        operation      Recommended maximum in-flight requests
+handshake        gauge    obs_build_info                       version,component  Linked embeddenator-obs copy serving a component
+handshake        gauge    obs_linked_copies                    -              Copies of embeddenator-obs registered in the process
+self_benchmark   gauge    obs_overhead_ns                      probe          Measured instrumentation cost per call at startup
+self_benchmark   gauge    obs_overhead_budget_exceeded         probe          1 when the probe exceeded its overhead budget
+span_processor   counter  spans_total                          span           Ended spans
+span_processor   counter  span_errors_total                    span           Ended spans with error status
+tracing          counter  operation_errors_total               operation      Operations marked failed through a timed span
+watchdog         gauge    heartbeat_age_seconds                component      Time since the component's last heartbeat
+watchdog         gauge    watchdog_stalled_components          -              Components past their heartbeat deadline
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_suffix() {
+        let bytes = MetricDescriptor::counter("read_total", "Bytes read").with_unit("bytes");
+        assert_eq!(bytes.unit_suffixed("read_total"), "read_bytes_total");
+        assert_eq!(bytes.unit_suffixed("read_bytes_total"), "read_bytes_total");
+
+        let seconds = MetricDescriptor::gauge("lag", "Replication lag").with_unit("seconds");
+        assert_eq!(seconds.unit_suffixed("lag"), "lag_seconds");
+        assert_eq!(
+            MetricDescriptor::gauge("depth", "Queue depth").unit_suffixed("depth"),
+            "depth"
+        );
+    }
+
+    #[test]
+    fn test_lookup_ignores_labels() {
+        let registry =
+            MetricRegistry::new().describe(MetricDescriptor::counter("hits_total", "Cache hits"));
+        assert_eq!(
+            registry.get(r#"hits_total{tier="l1"}"#).map(|d| d.kind),
+            Some(MetricKind::Counter)
+        );
+        assert!(registry.get("misses_total").is_none());
+    }
+
+    #[test]
+    fn test_builtin_catalog() {
+        let registry = MetricRegistry::builtin();
+        let disk = registry.get("disk_available_bytes").unwrap();
+        assert_eq!(disk.unit.as_deref(), Some("bytes"));
+        assert_eq!(disk.labels, vec!["path"]);
+        assert_eq!(disk.source.as_deref(), Some("disk_watcher"));
+        assert_eq!(
+            registry.get("pressure_alerts_total").unwrap().kind,
+            MetricKind::Counter
+        );
+        assert!(registry.iter().all(|d| !d.help.is_empty()));
+    }
+
+    #[test]
+    fn test_schema_dump_json() {
+        let registry = MetricRegistry::new().describe(
+            MetricDescriptor::counter("hits_total", "Cache \"hits\"")
+                .with_labels(&["tier"])
+                .with_source("cache"),
+        );
+        assert_eq!(
+            registry.schema_dump(),
+            concat!(
+                "{\n",
+                "  \"metrics\": [\n",
+                r#"    {"name": "hits_total", "type": "counter", "unit": null, "labels": ["tier"], "help": "Cache \"hits\"", "source": "cache"}"#,
+                "\n  ]\n",
+                "}\n"
+            )
+        );
+    }
+}
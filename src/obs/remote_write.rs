@@ -0,0 +1,543 @@
+//! Prometheus Remote-Write Export
+//!
+//! Pushes telemetry snapshots straight to a remote-write endpoint (Mimir,
+//! Thanos Receive, VictoriaMetrics, Prometheus with the receiver enabled),
+//! for short-lived jobs and hosts that no Prometheus server can scrape.
+//!
+//! # Wire Format
+//!
+//! Remote-write 1.0: a `WriteRequest` protobuf, snappy block-compressed,
+//! sent as `POST` with `Content-Encoding: snappy`. Both encoders are
+//! implemented here; only plain `http://` endpoints are supported, so put
+//! a TLS-terminating proxy or agent in front of `https` receivers.
+//!
+//! # Exported Series
+//!
+//! - Counters and gauges, with labeled keys mapped to series labels
+//! - Operation timings as `<op>_duration_us_count` / `_sum`
+//! - `uptime_seconds`
+//!
+//! Every series carries the configured external labels (e.g. `job`).
+//!
+//! # Retries
+//!
+//! Connection errors, HTTP 429 and 5xx responses are retried with
+//! exponential backoff; other 4xx responses are dropped, as the receiver
+//! will never accept the same payload.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::remote_write::RemoteWriteClient;
+//!
+//! let client = RemoteWriteClient::new("http://mimir:9009/api/v1/push")?
+//!     .with_prefix("embeddenator")
+//!     .with_label("job", "indexer")
+//!     .with_header("X-Scope-OrgID", "tenant-1");
+//!
+//! let _handle = client.spawn(Duration::from_secs(15), telemetry.clone());
+//! ```
+
+use crate::obs::http::{Backoff, HttpEndpoint};
+use crate::obs::logging;
+use crate::obs::privacy;
+use crate::obs::process::{spawn_sink, CollectorHandle};
+use crate::obs::telemetry::{parse_labels, split_labeled_key, Telemetry, TelemetrySnapshot};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// One sample of one series.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteSeries {
+    /// Sorted by name, including `__name__`
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+    pub timestamp_ms: i64,
+}
+
+/// Remote-write client over HTTP.
+pub struct RemoteWriteClient {
+    endpoint: HttpEndpoint,
+    prefix: Option<String>,
+    labels: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    backoff: Backoff,
+}
+
+impl RemoteWriteClient {
+    /// Client for an `http://host[:port]/path` endpoint, with a 10 second
+    /// timeout and 3 retries backing off from 500 ms up to 30 seconds.
+    pub fn new(url: &str) -> io::Result<Self> {
+        Ok(Self {
+            endpoint: HttpEndpoint::parse(url)?,
+            prefix: None,
+            labels: Vec::new(),
+            headers: Vec::new(),
+            timeout: Duration::from_secs(10),
+            backoff: Backoff {
+                max_retries: 3,
+                initial: Duration::from_millis(500),
+                max: Duration::from_secs(30),
+            },
+        })
+    }
+
+    /// Prefix metric names (`<prefix>_<name>`).
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Add an external label attached to every series.
+    pub fn with_label(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((name.into(), value.into()));
+        self
+    }
+
+    /// Add an HTTP header (tenant IDs, auth tokens).
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Connect, write and read timeout per attempt.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Retry a failed push up to `max_retries` times.
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.backoff.max_retries = max_retries;
+        self
+    }
+
+    /// Backoff before the first retry, doubling up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff.initial = initial;
+        self.backoff.max = max;
+        self
+    }
+
+    /// Flatten a snapshot into series, all stamped with the snapshot time.
+    pub fn series(&self, snapshot: &TelemetrySnapshot) -> Vec<RemoteSeries> {
+        let snapshot = privacy::enforce_snapshot(snapshot);
+        let snapshot = snapshot.as_ref();
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        let mut series = Vec::new();
+
+        for (key, &value) in &snapshot.counters {
+            series.push(self.make_series(key, value as f64, timestamp_ms));
+        }
+        for (key, &value) in &snapshot.gauges {
+            series.push(self.make_series(key, value, timestamp_ms));
+        }
+        for (name, stats) in &snapshot.operation_stats {
+            let base = format!("{}_duration_us", name);
+            series.push(self.make_series(
+                &format!("{}_count", base),
+                stats.count as f64,
+                timestamp_ms,
+            ));
+            series.push(self.make_series(
+                &format!("{}_sum", base),
+                stats.total_us as f64,
+                timestamp_ms,
+            ));
+        }
+        series.push(self.make_series("uptime_seconds", snapshot.uptime_secs as f64, timestamp_ms));
+
+        series.sort_by(|a, b| a.labels.cmp(&b.labels));
+        series
+    }
+
+    fn make_series(&self, key: &str, value: f64, timestamp_ms: i64) -> RemoteSeries {
+        let (name, body) = split_labeled_key(key);
+        let name = match &self.prefix {
+            Some(prefix) => format!("{}_{}", prefix, sanitize_name(name)),
+            None => sanitize_name(name),
+        };
+
+        let mut labels = vec![("__name__".to_string(), name)];
+        for (label, label_value) in body.map(parse_labels).unwrap_or_default() {
+            labels.push((sanitize_name(label), label_value));
+        }
+        // Series labels win over external labels of the same name
+        for (label, label_value) in &self.labels {
+            if !labels.iter().any(|(existing, _)| existing == label) {
+                labels.push((label.clone(), label_value.clone()));
+            }
+        }
+        labels.sort();
+
+        RemoteSeries {
+            labels,
+            value,
+            timestamp_ms,
+        }
+    }
+
+    /// Encode a snapshot as a snappy-compressed `WriteRequest`.
+    pub fn encode(&self, snapshot: &TelemetrySnapshot) -> Vec<u8> {
+        snappy_compress(&encode_write_request(&self.series(snapshot)))
+    }
+
+    /// Push a snapshot, retrying transient failures.
+    pub fn push(&self, snapshot: &TelemetrySnapshot) -> io::Result<()> {
+        let body = self.encode(snapshot);
+        let mut headers = vec![
+            (
+                "Content-Type".to_string(),
+                "application/x-protobuf".to_string(),
+            ),
+            ("Content-Encoding".to_string(), "snappy".to_string()),
+            (
+                "X-Prometheus-Remote-Write-Version".to_string(),
+                "0.1.0".to_string(),
+            ),
+        ];
+        headers.extend(self.headers.iter().cloned());
+        self.backoff.retry("remote write", || {
+            self.endpoint.post(&headers, &body, self.timeout)
+        })
+    }
+
+    /// Push shared telemetry every `interval` on a background thread.
+    ///
+    /// The lock is held only to take the snapshot, not during the push.
+    /// Failures after all retries are logged and the snapshot is dropped.
+    pub fn spawn(self, interval: Duration, telemetry: Arc<Mutex<Telemetry>>) -> CollectorHandle {
+        spawn_sink("obs-remote-write", interval, move || {
+            let Ok(snapshot) = telemetry.lock().map(|t| t.snapshot()) else {
+                return;
+            };
+            if let Err(e) = self.push(&snapshot) {
+                logging::warn(&format!(
+                    "remote write to {} failed: {}",
+                    self.endpoint.host, e
+                ));
+            }
+        })
+    }
+}
+
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Encode `prometheus.WriteRequest { repeated TimeSeries timeseries = 1; }`.
+fn encode_write_request(series: &[RemoteSeries]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for s in series {
+        // TimeSeries { repeated Label labels = 1; repeated Sample samples = 2; }
+        let mut ts = Vec::new();
+        for (name, value) in &s.labels {
+            // Label { string name = 1; string value = 2; }
+            let mut label = Vec::new();
+            put_bytes(&mut label, 1, name.as_bytes());
+            put_bytes(&mut label, 2, value.as_bytes());
+            put_bytes(&mut ts, 1, &label);
+        }
+        // Sample { double value = 1; int64 timestamp = 2; }
+        let mut sample = Vec::new();
+        put_varint(&mut sample, (1 << 3) | 1);
+        sample.extend_from_slice(&s.value.to_le_bytes());
+        put_varint(&mut sample, 2 << 3);
+        put_varint(&mut sample, s.timestamp_ms as u64);
+        put_bytes(&mut ts, 2, &sample);
+
+        put_bytes(&mut out, 1, &ts);
+    }
+    out
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Length-delimited field (wire type 2).
+fn put_bytes(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    put_varint(out, (field << 3) | 2);
+    put_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Snappy block format: greedy 4-byte hash matching with 2-byte-offset
+/// copies. Not as tight as the reference encoder, but any conforming
+/// decoder reads it.
+fn snappy_compress(input: &[u8]) -> Vec<u8> {
+    const TABLE_BITS: u32 = 14;
+    const MAX_OFFSET: usize = 65535;
+
+    let mut out = Vec::with_capacity(input.len() / 2 + 16);
+    put_varint(&mut out, input.len() as u64);
+
+    let mut table = vec![usize::MAX; 1 << TABLE_BITS];
+    let load = |i: usize| u32::from_le_bytes([input[i], input[i + 1], input[i + 2], input[i + 3]]);
+    let mut literal_start = 0;
+    let mut i = 0;
+
+    while i + 4 <= input.len() {
+        let word = load(i);
+        let slot = (word.wrapping_mul(0x1e35_a7bd) >> (32 - TABLE_BITS)) as usize;
+        let candidate = table[slot];
+        table[slot] = i;
+
+        if candidate == usize::MAX || i - candidate > MAX_OFFSET || load(candidate) != word {
+            i += 1;
+            continue;
+        }
+
+        let mut len = 4;
+        while i + len < input.len() && input[candidate + len] == input[i + len] {
+            len += 1;
+        }
+        emit_literal(&mut out, &input[literal_start..i]);
+        let offset = i - candidate;
+        let mut remaining = len;
+        while remaining > 0 {
+            let chunk = remaining.min(64);
+            out.push((((chunk - 1) as u8) << 2) | 0b10);
+            out.extend_from_slice(&(offset as u16).to_le_bytes());
+            remaining -= chunk;
+        }
+        i += len;
+        literal_start = i;
+    }
+
+    emit_literal(&mut out, &input[literal_start..]);
+    out
+}
+
+fn emit_literal(out: &mut Vec<u8>, literal: &[u8]) {
+    if literal.is_empty() {
+        return;
+    }
+    let n = literal.len() - 1;
+    if n < 60 {
+        out.push((n as u8) << 2);
+    } else {
+        let bytes = (n as u32).to_le_bytes();
+        let width = 4 - (n as u32).leading_zeros() as usize / 8;
+        out.push(((59 + width) as u8) << 2);
+        out.extend_from_slice(&bytes[..width]);
+    }
+    out.extend_from_slice(literal);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Reference snappy block decoder for the subset the encoder emits.
+    fn snappy_decompress(input: &[u8]) -> Vec<u8> {
+        let (mut len, mut shift, mut pos) = (0usize, 0, 0);
+        loop {
+            let b = input[pos];
+            pos += 1;
+            len |= ((b & 0x7f) as usize) << shift;
+            shift += 7;
+            if b < 0x80 {
+                break;
+            }
+        }
+        let mut out = Vec::with_capacity(len);
+        while pos < input.len() {
+            let tag = input[pos];
+            pos += 1;
+            match tag & 0b11 {
+                0b00 => {
+                    let mut n = (tag >> 2) as usize;
+                    if n >= 60 {
+                        let width = n - 59;
+                        let mut bytes = [0u8; 4];
+                        bytes[..width].copy_from_slice(&input[pos..pos + width]);
+                        n = u32::from_le_bytes(bytes) as usize;
+                        pos += width;
+                    }
+                    out.extend_from_slice(&input[pos..pos + n + 1]);
+                    pos += n + 1;
+                }
+                0b10 => {
+                    let n = (tag >> 2) as usize + 1;
+                    let offset = u16::from_le_bytes([input[pos], input[pos + 1]]) as usize;
+                    pos += 2;
+                    for _ in 0..n {
+                        out.push(out[out.len() - offset]);
+                    }
+                }
+                _ => panic!("unexpected tag"),
+            }
+        }
+        assert_eq!(out.len(), len);
+        out
+    }
+
+    /// Accept one request and answer with `status`; returns head and body.
+    fn serve_one(listener: &TcpListener, status: &str) -> (String, Vec<u8>) {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut head = String::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(len) = line.strip_prefix("Content-Length: ") {
+                content_length = len.trim().parse().unwrap();
+            }
+            head.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body).unwrap();
+        write!(reader.get_mut(), "HTTP/1.1 {}\r\n\r\n", status).unwrap();
+        (head, body)
+    }
+
+    #[test]
+    fn test_snappy_round_trip() {
+        let repetitive: Vec<u8> = b"embeddenator_query_total{shard=\"1\"} "
+            .iter()
+            .cycle()
+            .take(10_000)
+            .copied()
+            .collect();
+        let compressed = snappy_compress(&repetitive);
+        assert!(compressed.len() < repetitive.len() / 10);
+        assert_eq!(snappy_decompress(&compressed), repetitive);
+
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..5000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        assert_eq!(snappy_decompress(&snappy_compress(&noise)), noise);
+        assert_eq!(snappy_decompress(&snappy_compress(b"")), b"");
+    }
+
+    #[test]
+    fn test_series_labels_and_protobuf() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.add_to_counter_with_labels("queries_total", &[("shard", "1")], 7);
+        telemetry.record_operation("search", 250);
+
+        let client = RemoteWriteClient::new("http://localhost:9009/api/v1/push")
+            .unwrap()
+            .with_prefix("embeddenator")
+            .with_label("job", "indexer");
+        let series = client.series(&telemetry.snapshot());
+
+        let queries = series
+            .iter()
+            .find(|s| s.labels[0].1 == "embeddenator_queries_total")
+            .unwrap();
+        assert_eq!(
+            queries.labels,
+            vec![
+                (
+                    "__name__".to_string(),
+                    "embeddenator_queries_total".to_string()
+                ),
+                ("job".to_string(), "indexer".to_string()),
+                ("shard".to_string(), "1".to_string()),
+            ]
+        );
+        assert_eq!(queries.value, 7.0);
+        assert!(series
+            .iter()
+            .any(|s| s.labels[0].1 == "embeddenator_search_duration_us_sum" && s.value == 250.0));
+
+        let encoded = encode_write_request(&series[..1]);
+        // Field 1, wire type 2 (TimeSeries)
+        assert_eq!(encoded[0], 0x0a);
+        assert!(encoded.windows(b"__name__".len()).any(|w| w == b"__name__"));
+    }
+
+    #[test]
+    fn test_url_parsing() {
+        let client = RemoteWriteClient::new("http://[::1]:9009/api/v1/push").unwrap();
+        assert_eq!(
+            (client.endpoint.host.as_str(), client.endpoint.port),
+            ("::1", 9009)
+        );
+        let client = RemoteWriteClient::new("http://vm/api/v1/write").unwrap();
+        assert_eq!(
+            (client.endpoint.port, client.endpoint.path.as_str()),
+            (80, "/api/v1/write")
+        );
+        assert!(RemoteWriteClient::new("https://mimir/api/v1/push").is_err());
+    }
+
+    #[test]
+    fn test_push_retries_on_server_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            ["503 Service Unavailable", "204 No Content"].map(|status| serve_one(&listener, status))
+        });
+
+        let client = RemoteWriteClient::new(&format!("http://{}/api/v1/push", addr))
+            .unwrap()
+            .with_header("X-Scope-OrgID", "tenant-1")
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(1));
+        let mut telemetry = Telemetry::default_config();
+        telemetry.set_gauge("queue_depth", 3.0);
+        client.push(&telemetry.snapshot()).unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        let (head, body) = &requests[1];
+        assert!(head.starts_with("POST /api/v1/push HTTP/1.1\r\n"));
+        assert!(head.contains("Content-Encoding: snappy\r\n"));
+        assert!(head.contains("X-Scope-OrgID: tenant-1\r\n"));
+        let decoded = snappy_decompress(body);
+        assert!(decoded.windows(11).any(|w| w == b"queue_depth"));
+    }
+
+    #[test]
+    fn test_push_does_not_retry_client_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            serve_one(&listener, "400 Bad Request");
+            // A retry would connect within the backoff window
+            listener.set_nonblocking(true).unwrap();
+            std::thread::sleep(Duration::from_millis(50));
+            listener.accept().is_ok()
+        });
+
+        let client = RemoteWriteClient::new(&format!("http://{}/push", addr))
+            .unwrap()
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(1));
+        let err = client
+            .push(&Telemetry::default_config().snapshot())
+            .unwrap_err();
+        assert!(err.to_string().contains("HTTP 400"));
+        assert!(!server.join().unwrap());
+    }
+}
@@ -0,0 +1,272 @@
+//! OTLP Resource Detection
+//!
+//! Describes the process emitting telemetry, for the `resource` block of
+//! OTLP exports. [`Resource::detect`] fills in what the process can find
+//! out about itself:
+//!
+//! - `host.name`: kernel hostname, else `HOSTNAME` / `COMPUTERNAME`
+//! - `os.type`: `linux`, `darwin`, `windows`, ...
+//! - `process.pid`
+//! - `container.id`: from `/proc/self/cgroup`, falling back to
+//!   `/proc/self/mountinfo` under cgroup v2 namespaces
+//! - `telemetry.sdk.name`, `telemetry.sdk.language`,
+//!   `telemetry.sdk.version`
+//! - `OTEL_RESOURCE_ATTRIBUTES` (`key=value,...`), overriding the above
+//!
+//! `service.version` cannot be detected from inside this crate, since
+//! `CARGO_PKG_VERSION` would name this crate's version; set it from the
+//! application with [`Resource::with_service_version`].
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::resource::Resource;
+//!
+//! let exporter = OtelExporter::new()
+//!     .with_service_name("indexer")
+//!     .with_resource(
+//!         Resource::detect()
+//!             .with_service_version(env!("CARGO_PKG_VERSION"))
+//!             .with("deployment.environment", "prod"),
+//!     );
+//! ```
+
+use crate::obs::opentelemetry::AttributeValue;
+use crate::obs::telemetry::escape_json;
+use std::sync::OnceLock;
+
+/// Environment variable with user resource attributes.
+pub const RESOURCE_ATTRIBUTES_VAR: &str = "OTEL_RESOURCE_ATTRIBUTES";
+
+/// Attributes describing the telemetry source; later values win.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Resource {
+    attributes: Vec<(String, AttributeValue)>,
+}
+
+impl Resource {
+    /// Empty resource.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Host, OS, process and container attributes, then
+    /// `OTEL_RESOURCE_ATTRIBUTES`. Probing runs once per process.
+    pub fn detect() -> Self {
+        static DETECTED: OnceLock<Resource> = OnceLock::new();
+        DETECTED
+            .get_or_init(|| {
+                let mut resource = Self::new()
+                    .with("telemetry.sdk.name", "embeddenator-obs")
+                    .with("telemetry.sdk.language", "rust")
+                    .with("telemetry.sdk.version", env!("CARGO_PKG_VERSION"))
+                    .with("os.type", os_type())
+                    .with("process.pid", std::process::id());
+                if let Some(host) = host_name() {
+                    resource = resource.with("host.name", host);
+                }
+                if let Some(id) = container_id() {
+                    resource = resource.with("container.id", id);
+                }
+                resource
+            })
+            .clone()
+            .merge(&Self::from_env())
+    }
+
+    /// Attributes from `OTEL_RESOURCE_ATTRIBUTES`.
+    pub fn from_env() -> Self {
+        std::env::var(RESOURCE_ATTRIBUTES_VAR)
+            .map(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    /// Parse `key=value,...`; malformed pairs are skipped and `%XX`
+    /// escapes decoded.
+    pub fn parse(value: &str) -> Self {
+        value
+            .split(',')
+            .filter_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                let key = key.trim();
+                (!key.is_empty()).then(|| (key.to_string(), percent_decode(value.trim())))
+            })
+            .fold(Self::new(), |resource, (key, value)| {
+                resource.with(key, value)
+            })
+    }
+
+    /// Set `key`, replacing any earlier value.
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<AttributeValue>) -> Self {
+        let key = key.into();
+        self.attributes.retain(|(k, _)| *k != key);
+        self.attributes.push((key, value.into()));
+        self
+    }
+
+    pub fn with_service_name(self, name: impl Into<String>) -> Self {
+        self.with("service.name", name.into())
+    }
+
+    pub fn with_service_version(self, version: impl Into<String>) -> Self {
+        self.with("service.version", version.into())
+    }
+
+    /// This resource with `other`'s attributes added, `other` winning on
+    /// conflicts.
+    pub fn merge(mut self, other: &Resource) -> Self {
+        for (key, value) in &other.attributes {
+            self = self.with(key.clone(), value.clone());
+        }
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&AttributeValue> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn attributes(&self) -> &[(String, AttributeValue)] {
+        &self.attributes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+    }
+
+    /// OTLP JSON resource object, attributes sorted by key.
+    pub fn to_otlp_json(&self) -> String {
+        let mut attributes: Vec<&(String, AttributeValue)> = self.attributes.iter().collect();
+        attributes.sort_by(|a, b| a.0.cmp(&b.0));
+        let attributes: Vec<String> = attributes
+            .into_iter()
+            .map(|(key, value)| {
+                format!(
+                    r#"{{"key": "{}", "value": {}}}"#,
+                    escape_json(key),
+                    value.to_otlp_json()
+                )
+            })
+            .collect();
+        format!(r#"{{"attributes": [{}]}}"#, attributes.join(", "))
+    }
+}
+
+/// OpenTelemetry `os.type` value for the target OS.
+fn os_type() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "darwin",
+        "dragonfly" => "dragonflybsd",
+        "solaris" | "illumos" => "solaris",
+        other => other,
+    }
+}
+
+fn host_name() -> Option<String> {
+    let from_kernel = std::fs::read_to_string("/proc/sys/kernel/hostname").ok();
+    from_kernel
+        .into_iter()
+        .chain(std::env::var("HOSTNAME").ok())
+        .chain(std::env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_string())
+        .find(|name| !name.is_empty())
+}
+
+#[cfg(target_os = "linux")]
+fn container_id() -> Option<String> {
+    let read = |path| std::fs::read_to_string(path).unwrap_or_default();
+    parse_container_id(&read("/proc/self/cgroup")).or_else(|| {
+        // Only container runtime mounts; overlay layer ids look the same
+        let mountinfo = read("/proc/self/mountinfo");
+        let mounts: Vec<&str> = mountinfo
+            .lines()
+            .filter(|line| line.contains("/containers/"))
+            .collect();
+        parse_container_id(&mounts.join("\n"))
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn container_id() -> Option<String> {
+    None
+}
+
+/// First 64-hex-digit path segment, with runtime prefixes such as
+/// `docker-` and suffixes such as `.scope` removed.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_container_id(text: &str) -> Option<String> {
+    text.lines()
+        .flat_map(|line| line.split(['/', ' ']))
+        .map(|segment| {
+            let segment = segment.strip_suffix(".scope").unwrap_or(segment);
+            segment.rsplit(['-', ':']).next().unwrap_or(segment)
+        })
+        .find(|segment| segment.len() == 64 && segment.bytes().all(|b| b.is_ascii_hexdigit()))
+        .map(str::to_string)
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_container_id() {
+        let id = "3f4a9c2e1b7d8f6a5c4b3a2918d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a2b1c0d9";
+        let v1 = format!("12:memory:/docker/{}\n0::/\n", id);
+        assert_eq!(parse_container_id(&v1).as_deref(), Some(id));
+        let systemd = format!("0::/system.slice/docker-{}.scope\n", id);
+        assert_eq!(parse_container_id(&systemd).as_deref(), Some(id));
+        let mountinfo = format!(
+            "612 601 0:52 /var/lib/docker/containers/{}/hostname /etc/hostname rw\n",
+            id
+        );
+        assert_eq!(parse_container_id(&mountinfo).as_deref(), Some(id));
+        assert_eq!(parse_container_id("0::/user.slice/session-2.scope\n"), None);
+    }
+
+    #[test]
+    fn test_parse_and_merge() {
+        let user = Resource::parse(
+            "service.version=1.4.0, deployment.environment=prod,broken,team=search%20infra",
+        );
+        assert_eq!(user.attributes().len(), 3);
+        assert_eq!(user.get("team").unwrap(), "search infra");
+
+        let resource = Resource::detect().merge(&user).with_service_name("indexer");
+        assert_eq!(resource.get("service.version").unwrap(), "1.4.0");
+        assert_eq!(
+            resource.get("process.pid"),
+            Some(&AttributeValue::I64(std::process::id() as i64))
+        );
+        assert_eq!(resource.get("os.type").unwrap(), os_type());
+
+        let json = resource.to_otlp_json();
+        assert!(json.contains(r#"{"key": "deployment.environment""#));
+        assert!(json.contains(r#"{"key": "service.name", "value": {"stringValue": "indexer"}}"#));
+    }
+}
@@ -0,0 +1,459 @@
+//! Trace Sampling
+//!
+//! - [`AdaptiveSampler`] (head-based): keeps span export near a
+//!   spans-per-second budget; the sampling probability rises toward 1.0
+//!   when traffic is low and falls under load. Error spans are always
+//!   kept.
+//! - [`TailSampler`] (tail-based): buffers complete traces briefly after
+//!   their root span ends and keeps only slow traces or traces containing
+//!   errors.
+//!
+//! Decisions derive from the trace id, so every process sampling the same
+//! trace at the same probability agrees, and children inherit the root's
+//! decision through [`OtelSpan::sampled`]. The probability is re-evaluated
+//! once per adjustment window from a moving average of the offered rate;
+//! no background thread is needed.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::sampling::AdaptiveSampler;
+//!
+//! let sampler = AdaptiveSampler::new(100.0); // ~100 spans/s
+//!
+//! let mut span = OtelSpan::new("request");
+//! sampler.sample(&mut span);
+//! // ...
+//! span.end_with_error("timeout");
+//! sampler.on_end(&mut span); // error spans are always exported
+//!
+//! sampler.record_into(&mut telemetry); // trace_sampling_probability gauge
+//!
+//! // Tail-based: record every ended span, export what survives
+//! let tail = TailSampler::new(Duration::from_millis(250));
+//! tail.record(span);
+//! let kept = tail.flush();
+//! let json = exporter.export_spans(&kept);
+//! ```
+
+use crate::obs::opentelemetry::{OtelSpan, SpanStatus};
+use crate::obs::telemetry::Telemetry;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Window {
+    start: Instant,
+    offered: u64,
+    /// Moving average of offered spans per second
+    rate: Option<f64>,
+}
+
+/// Sampler targeting a spans-per-second budget.
+pub struct AdaptiveSampler {
+    target_per_sec: f64,
+    min_probability: f64,
+    window_len: Duration,
+    /// Current probability as `f64` bits
+    probability: AtomicU64,
+    window: Mutex<Window>,
+    sampled: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl AdaptiveSampler {
+    /// Sampler for about `target_per_sec` spans per second, adjusting every
+    /// second and never sampling below 0.1%.
+    pub fn new(target_per_sec: f64) -> Self {
+        Self {
+            target_per_sec: target_per_sec.max(0.0),
+            min_probability: 0.001,
+            window_len: Duration::from_secs(1),
+            probability: AtomicU64::new(1.0f64.to_bits()),
+            window: Mutex::new(Window {
+                start: Instant::now(),
+                offered: 0,
+                rate: None,
+            }),
+            sampled: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    /// Lower bound for the probability, so some traces always get through.
+    pub fn with_min_probability(mut self, probability: f64) -> Self {
+        self.min_probability = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    /// How often the probability is re-evaluated.
+    pub fn with_adjustment_interval(mut self, interval: Duration) -> Self {
+        self.window_len = interval.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Current sampling probability.
+    pub fn probability(&self) -> f64 {
+        f64::from_bits(self.probability.load(Ordering::Relaxed))
+    }
+
+    /// Smoothed offered spans per second (0 before the first window ends).
+    pub fn offered_rate(&self) -> f64 {
+        self.window.lock().ok().and_then(|w| w.rate).unwrap_or(0.0)
+    }
+
+    /// Spans kept by [`should_sample`](Self::should_sample).
+    pub fn sampled_spans(&self) -> u64 {
+        self.sampled.load(Ordering::Relaxed)
+    }
+
+    /// Unsampled error spans kept by [`on_end`](Self::on_end).
+    pub fn error_spans(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Count one offered span and decide whether its trace is sampled.
+    pub fn should_sample(&self, trace_id: u128) -> bool {
+        self.offer(Instant::now());
+        // Low 64 bits of the id are uniformly distributed
+        let position = ((trace_id as u64) >> 11) as f64 / (1u64 << 53) as f64;
+        let sampled = position < self.probability();
+        if sampled {
+            self.sampled.fetch_add(1, Ordering::Relaxed);
+        }
+        sampled
+    }
+
+    /// Decide sampling for a new span. Child spans keep the decision they
+    /// inherited from their parent but still count toward the rate.
+    pub fn sample(&self, span: &mut OtelSpan) {
+        let sampled = self.should_sample(span.trace_id);
+        if span.is_root() {
+            span.sampled = sampled;
+        }
+    }
+
+    /// Keep ended error spans regardless of the sampling decision.
+    pub fn on_end(&self, span: &mut OtelSpan) {
+        if span.status == SpanStatus::Error && !span.sampled {
+            span.sampled = true;
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn offer(&self, now: Instant) {
+        let Ok(mut window) = self.window.lock() else {
+            return;
+        };
+        window.offered += 1;
+        let elapsed = now.duration_since(window.start);
+        if elapsed < self.window_len {
+            return;
+        }
+
+        let observed = window.offered as f64 / elapsed.as_secs_f64();
+        let rate = window.rate.map_or(observed, |r| 0.5 * r + 0.5 * observed);
+        window.rate = Some(rate);
+        window.start = now;
+        window.offered = 0;
+
+        let probability = if rate > 0.0 {
+            (self.target_per_sec / rate).clamp(self.min_probability, 1.0)
+        } else {
+            1.0
+        };
+        self.probability
+            .store(probability.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Publish sampler state as telemetry gauges.
+    ///
+    /// Gauges: `trace_sampling_probability`, `trace_offered_spans_per_second`,
+    /// `trace_sampled_spans_total`, `trace_error_spans_total`.
+    pub fn record_into(&self, telemetry: &mut Telemetry) {
+        telemetry.set_gauge("trace_sampling_probability", self.probability());
+        telemetry.set_gauge("trace_offered_spans_per_second", self.offered_rate());
+        telemetry.set_gauge("trace_sampled_spans_total", self.sampled_spans() as f64);
+        telemetry.set_gauge("trace_error_spans_total", self.error_spans() as f64);
+    }
+}
+
+struct BufferedTrace {
+    spans: Vec<OtelSpan>,
+    first_seen: Instant,
+    root_ended: Option<Instant>,
+}
+
+/// Tail-based sampler keeping only slow or failed traces.
+///
+/// A trace is decided [`decision_wait`](Self::with_decision_wait) after
+/// its root span is recorded, leaving time for late child spans. Traces
+/// without a local root (continuing a remote parent) are decided once
+/// they are [`max_trace_age`](Self::with_max_trace_age) old. When the
+/// buffer is full the oldest trace is discarded undecided.
+pub struct TailSampler {
+    latency_threshold: Duration,
+    decision_wait: Duration,
+    max_trace_age: Duration,
+    max_traces: usize,
+    traces: Mutex<HashMap<u128, BufferedTrace>>,
+    kept: AtomicU64,
+    discarded: AtomicU64,
+    evicted: AtomicU64,
+}
+
+impl TailSampler {
+    /// Keep traces lasting at least `latency_threshold` or containing an
+    /// error span; decide 5s after the root ends and buffer up to 1000
+    /// traces.
+    pub fn new(latency_threshold: Duration) -> Self {
+        Self {
+            latency_threshold,
+            decision_wait: Duration::from_secs(5),
+            max_trace_age: Duration::from_secs(60),
+            max_traces: 1000,
+            traces: Mutex::new(HashMap::new()),
+            kept: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+            evicted: AtomicU64::new(0),
+        }
+    }
+
+    /// Time after the root span to wait for late child spans.
+    pub fn with_decision_wait(mut self, wait: Duration) -> Self {
+        self.decision_wait = wait;
+        self
+    }
+
+    /// Age at which traces without a local root are decided.
+    pub fn with_max_trace_age(mut self, age: Duration) -> Self {
+        self.max_trace_age = age;
+        self
+    }
+
+    /// Maximum traces buffered at once.
+    pub fn with_max_traces(mut self, traces: usize) -> Self {
+        self.max_traces = traces.max(1);
+        self
+    }
+
+    /// Buffer an ended span until its trace is decided.
+    pub fn record(&self, span: OtelSpan) {
+        let now = Instant::now();
+        let Ok(mut traces) = self.traces.lock() else {
+            return;
+        };
+        if !traces.contains_key(&span.trace_id) && traces.len() >= self.max_traces {
+            let oldest = traces
+                .iter()
+                .min_by_key(|(_, trace)| trace.first_seen)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                traces.remove(&oldest);
+                self.evicted.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let trace = traces
+            .entry(span.trace_id)
+            .or_insert_with(|| BufferedTrace {
+                spans: Vec::new(),
+                first_seen: now,
+                root_ended: None,
+            });
+        if span.is_root() {
+            trace.root_ended = Some(now);
+        }
+        trace.spans.push(span);
+    }
+
+    /// Whether a complete trace is worth keeping.
+    fn keep(&self, spans: &[OtelSpan]) -> bool {
+        let errored = spans.iter().any(|s| s.status == SpanStatus::Error);
+        let start = spans.iter().map(|s| s.start_time_ns).min().unwrap_or(0);
+        let end = spans.iter().map(|s| s.end_time_ns).max().unwrap_or(0);
+        errored || end.saturating_sub(start) >= self.latency_threshold.as_nanos() as u64
+    }
+
+    fn decide(&self, ready: impl Fn(&BufferedTrace) -> bool) -> Vec<OtelSpan> {
+        let decided: Vec<BufferedTrace> = {
+            let Ok(mut traces) = self.traces.lock() else {
+                return Vec::new();
+            };
+            let ids: Vec<u128> = traces
+                .iter()
+                .filter(|(_, trace)| ready(trace))
+                .map(|(id, _)| *id)
+                .collect();
+            ids.iter().filter_map(|id| traces.remove(id)).collect()
+        };
+
+        let mut kept = Vec::new();
+        for trace in decided {
+            if self.keep(&trace.spans) {
+                self.kept.fetch_add(1, Ordering::Relaxed);
+                // Tail decisions override head sampling for export
+                kept.extend(trace.spans.into_iter().map(|mut span| {
+                    span.sampled = true;
+                    span
+                }));
+            } else {
+                self.discarded.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        kept
+    }
+
+    /// Decide every trace that is due; returns the spans of kept traces.
+    pub fn flush(&self) -> Vec<OtelSpan> {
+        let now = Instant::now();
+        self.decide(|trace| match trace.root_ended {
+            Some(ended) => now.duration_since(ended) >= self.decision_wait,
+            None => now.duration_since(trace.first_seen) >= self.max_trace_age,
+        })
+    }
+
+    /// Decide every buffered trace now, e.g. at shutdown.
+    pub fn flush_all(&self) -> Vec<OtelSpan> {
+        self.decide(|_| true)
+    }
+
+    /// Traces waiting for a decision.
+    pub fn buffered_traces(&self) -> usize {
+        self.traces.lock().map(|t| t.len()).unwrap_or(0)
+    }
+
+    /// Traces kept so far.
+    pub fn kept_traces(&self) -> u64 {
+        self.kept.load(Ordering::Relaxed)
+    }
+
+    /// Traces dropped as fast and error-free.
+    pub fn discarded_traces(&self) -> u64 {
+        self.discarded.load(Ordering::Relaxed)
+    }
+
+    /// Traces dropped undecided because the buffer was full.
+    pub fn evicted_traces(&self) -> u64 {
+        self.evicted.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obs::opentelemetry::IdGenerator;
+
+    #[test]
+    fn test_probability_tracks_budget() {
+        let sampler =
+            AdaptiveSampler::new(1000.0).with_adjustment_interval(Duration::from_millis(20));
+        let ids = IdGenerator::with_seed(7);
+        let start = Instant::now();
+
+        // ~10x the budget: probability drops toward 0.1
+        let mut offered = 0u64;
+        while start.elapsed() < Duration::from_millis(120) {
+            for _ in 0..100 {
+                sampler.should_sample(ids.next_trace_id());
+                offered += 1;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let probability = sampler.probability();
+        assert!(probability < 0.5, "probability {}", probability);
+        assert!(sampler.sampled_spans() < offered);
+
+        // Traffic well under budget: back to sampling everything
+        for _ in 0..6 {
+            std::thread::sleep(Duration::from_millis(25));
+            sampler.should_sample(ids.next_trace_id());
+        }
+        assert_eq!(sampler.probability(), 1.0);
+    }
+
+    fn ended(name: &str, parent: Option<&OtelSpan>, duration_ms: u64) -> OtelSpan {
+        let mut span = match parent {
+            Some(parent) => OtelSpan::new_child(name, parent),
+            None => OtelSpan::new(name),
+        };
+        span.end();
+        span.end_time_ns = span.start_time_ns + duration_ms * 1_000_000;
+        span
+    }
+
+    #[test]
+    fn test_tail_sampler_keeps_slow_and_error_traces() {
+        let tail = TailSampler::new(Duration::from_millis(100)).with_decision_wait(Duration::ZERO);
+
+        let fast = ended("fast", None, 5);
+        let mut fast_child = ended("lookup", Some(&fast), 2);
+        fast_child.sampled = false;
+        let slow = ended("slow", None, 150);
+        let failed = ended("failed", None, 1);
+        let mut failed_child = OtelSpan::new_child("db", &failed);
+        failed_child.end_with_error("timeout");
+
+        // Children usually end before their root
+        for span in [fast_child, failed_child, fast, slow, failed] {
+            tail.record(span);
+        }
+        assert_eq!(tail.buffered_traces(), 3);
+
+        let mut kept: Vec<String> = tail.flush().into_iter().map(|s| s.name).collect();
+        kept.sort();
+        assert_eq!(kept, vec!["db", "failed", "slow"]);
+        assert_eq!((tail.kept_traces(), tail.discarded_traces()), (2, 1));
+        assert_eq!(tail.buffered_traces(), 0);
+    }
+
+    #[test]
+    fn test_tail_sampler_waits_and_bounds_buffer() {
+        let tail = TailSampler::new(Duration::ZERO)
+            .with_decision_wait(Duration::from_secs(60))
+            .with_max_traces(2);
+
+        let remote = OtelSpan::from_traceparent(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "remote",
+        )
+        .unwrap();
+        tail.record(remote);
+        tail.record(ended("a", None, 1));
+        tail.record(ended("b", None, 1));
+        assert_eq!((tail.buffered_traces(), tail.evicted_traces()), (2, 1));
+
+        // Roots ended but the decision wait has not passed
+        assert!(tail.flush().is_empty());
+        let kept = tail.flush_all();
+        assert_eq!(kept.len(), 2);
+        assert!(kept.iter().all(|s| s.sampled));
+    }
+
+    #[test]
+    fn test_errors_always_sampled() {
+        let sampler = AdaptiveSampler::new(0.0)
+            .with_min_probability(0.0)
+            .with_adjustment_interval(Duration::from_millis(1));
+        let ids = IdGenerator::with_seed(1);
+        sampler.should_sample(ids.next_trace_id());
+        std::thread::sleep(Duration::from_millis(2));
+        sampler.should_sample(ids.next_trace_id());
+        assert_eq!(sampler.probability(), 0.0);
+
+        let mut ok = OtelSpan::new("ok");
+        sampler.sample(&mut ok);
+        ok.end();
+        sampler.on_end(&mut ok);
+        assert!(!ok.sampled);
+
+        let mut failed = OtelSpan::new("failed");
+        sampler.sample(&mut failed);
+        let child = OtelSpan::new_child("child", &failed);
+        assert!(!child.sampled);
+        failed.end_with_error("timeout");
+        sampler.on_end(&mut failed);
+        assert!(failed.sampled);
+        assert_eq!(sampler.error_spans(), 1);
+    }
+}
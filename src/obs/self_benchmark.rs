@@ -0,0 +1,321 @@
+//! Startup Self-Benchmark
+//!
+//! Measures what instrumentation costs on the actual host, since the
+//! per-call figures quoted in the docs come from a different machine:
+//!
+//! - counter increment on a [`Telemetry`]
+//! - span creation ([`OtelSpan::new`], including process-wide attributes)
+//! - a [`HiResTimer`] start and read
+//!
+//! The run is opt-in and takes about 50ms. Results are logged and written
+//! as `obs_overhead_ns{probe}` gauges.
+//!
+//! # Budgets
+//!
+//! Each probe can be given a per-call budget. With auto-disable on, a
+//! probe over budget switches off the subsystem it measures:
+//!
+//! - counter increment: telemetry collection ([`Telemetry::set_enabled`])
+//! - span creation: new root spans start unsampled
+//!   ([`set_root_spans_sampled`])
+//! - timer: no switch; reported only
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::self_benchmark::{OverheadProbe, SelfBenchmark};
+//!
+//! let report = SelfBenchmark::new()
+//!     .with_budget(OverheadProbe::SpanCreation, Duration::from_micros(2))
+//!     .with_auto_disable(true)
+//!     .run_at_startup(&mut telemetry);
+//! ```
+
+use crate::obs::hires_timing::HiResTimer;
+use crate::obs::opentelemetry::{set_root_spans_sampled, OtelSpan};
+use crate::obs::telemetry::Telemetry;
+use crate::obs::tracing::{record_event, EventLevel};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// One measured instrumentation path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverheadProbe {
+    CounterIncrement,
+    SpanCreation,
+    Timer,
+}
+
+impl OverheadProbe {
+    pub const ALL: [OverheadProbe; 3] = [
+        OverheadProbe::CounterIncrement,
+        OverheadProbe::SpanCreation,
+        OverheadProbe::Timer,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverheadProbe::CounterIncrement => "counter_increment",
+            OverheadProbe::SpanCreation => "span_creation",
+            OverheadProbe::Timer => "timer",
+        }
+    }
+}
+
+/// Result of one probe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeResult {
+    pub probe: OverheadProbe,
+    /// Mean cost per call
+    pub ns_per_call: f64,
+    pub iterations: u64,
+    pub budget: Option<Duration>,
+}
+
+impl ProbeResult {
+    pub fn over_budget(&self) -> bool {
+        self.budget
+            .is_some_and(|budget| self.ns_per_call > budget.as_nanos() as f64)
+    }
+}
+
+/// Results of a self-benchmark run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverheadReport {
+    pub probes: Vec<ProbeResult>,
+    /// Probes whose subsystem was switched off
+    pub disabled: Vec<OverheadProbe>,
+}
+
+impl OverheadReport {
+    pub fn get(&self, probe: OverheadProbe) -> Option<&ProbeResult> {
+        self.probes.iter().find(|result| result.probe == probe)
+    }
+
+    /// Probes over their budget.
+    pub fn over_budget(&self) -> Vec<OverheadProbe> {
+        self.probes
+            .iter()
+            .filter(|result| result.over_budget())
+            .map(|result| result.probe)
+            .collect()
+    }
+
+    /// Write `obs_overhead_ns{probe}` and
+    /// `obs_overhead_budget_exceeded{probe}` gauges.
+    pub fn record_into(&self, telemetry: &mut Telemetry) {
+        for result in &self.probes {
+            let labels = [("probe", result.probe.as_str())];
+            telemetry.set_gauge_with_labels("obs_overhead_ns", &labels, result.ns_per_call);
+            if result.budget.is_some() {
+                telemetry.set_gauge_with_labels(
+                    "obs_overhead_budget_exceeded",
+                    &labels,
+                    if result.over_budget() { 1.0 } else { 0.0 },
+                );
+            }
+        }
+    }
+}
+
+/// Startup micro-benchmark of instrumentation overhead.
+#[derive(Debug, Clone)]
+pub struct SelfBenchmark {
+    duration: Duration,
+    budgets: Vec<(OverheadProbe, Duration)>,
+    auto_disable: bool,
+}
+
+impl Default for SelfBenchmark {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelfBenchmark {
+    /// 50ms run, no budgets.
+    pub fn new() -> Self {
+        Self {
+            duration: Duration::from_millis(50),
+            budgets: Vec::new(),
+            auto_disable: false,
+        }
+    }
+
+    /// Total run time, split evenly across probes.
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Per-call budget for `probe`.
+    pub fn with_budget(mut self, probe: OverheadProbe, budget: Duration) -> Self {
+        self.budgets.retain(|(p, _)| *p != probe);
+        self.budgets.push((probe, budget));
+        self
+    }
+
+    /// Switch off subsystems whose probe is over budget (default: off).
+    pub fn with_auto_disable(mut self, enabled: bool) -> Self {
+        self.auto_disable = enabled;
+        self
+    }
+
+    /// Measure every probe.
+    pub fn run(&self) -> OverheadReport {
+        let slice = self.duration / OverheadProbe::ALL.len() as u32;
+        let probes = OverheadProbe::ALL
+            .iter()
+            .map(|&probe| {
+                let (ns_per_call, iterations) = measure(probe, slice);
+                ProbeResult {
+                    probe,
+                    ns_per_call,
+                    iterations,
+                    budget: self.budget(probe),
+                }
+            })
+            .collect();
+        OverheadReport {
+            probes,
+            disabled: Vec::new(),
+        }
+    }
+
+    /// Measure, log the results, record gauges into `telemetry` and apply
+    /// budgets.
+    pub fn run_at_startup(&self, telemetry: &mut Telemetry) -> OverheadReport {
+        let mut report = self.run();
+        for result in &report.probes {
+            record_event(
+                EventLevel::Info,
+                "instrumentation overhead measured",
+                &[
+                    ("probe", result.probe.as_str()),
+                    ("ns_per_call", &format!("{:.1}", result.ns_per_call)),
+                    ("iterations", &result.iterations.to_string()),
+                ],
+            );
+        }
+
+        for probe in report.over_budget() {
+            let result = report.get(probe).cloned();
+            let disable = self.auto_disable && probe != OverheadProbe::Timer;
+            if let Some(result) = result {
+                let budget = result.budget.unwrap_or_default();
+                record_event(
+                    EventLevel::Warn,
+                    "instrumentation overhead over budget",
+                    &[
+                        ("probe", probe.as_str()),
+                        ("ns_per_call", &format!("{:.1}", result.ns_per_call)),
+                        ("budget_ns", &budget.as_nanos().to_string()),
+                        ("disabled", if disable { "true" } else { "false" }),
+                    ],
+                );
+            }
+            if disable {
+                match probe {
+                    OverheadProbe::CounterIncrement => telemetry.set_enabled(false),
+                    OverheadProbe::SpanCreation => set_root_spans_sampled(false),
+                    OverheadProbe::Timer => {}
+                }
+                report.disabled.push(probe);
+            }
+        }
+
+        // Gauges are recorded even when collection was just disabled
+        let enabled = telemetry.config().enabled;
+        telemetry.set_enabled(true);
+        report.record_into(telemetry);
+        telemetry.set_enabled(enabled);
+        report
+    }
+
+    fn budget(&self, probe: OverheadProbe) -> Option<Duration> {
+        self.budgets
+            .iter()
+            .find(|(p, _)| *p == probe)
+            .map(|(_, budget)| *budget)
+    }
+}
+
+/// Run `probe` in batches for about `slice`; returns ns per call and the
+/// number of calls.
+fn measure(probe: OverheadProbe, slice: Duration) -> (f64, u64) {
+    const BATCH: u64 = 256;
+    let mut telemetry = Telemetry::default_config();
+    let mut iterations = 0u64;
+    let started = Instant::now();
+    loop {
+        match probe {
+            OverheadProbe::CounterIncrement => {
+                for _ in 0..BATCH {
+                    telemetry.increment_counter(black_box("obs_self_benchmark"));
+                }
+            }
+            OverheadProbe::SpanCreation => {
+                for _ in 0..BATCH {
+                    black_box(OtelSpan::new(black_box("obs_self_benchmark")));
+                }
+            }
+            OverheadProbe::Timer => {
+                for _ in 0..BATCH {
+                    let timer = HiResTimer::start();
+                    black_box(timer.elapsed());
+                }
+            }
+        }
+        iterations += BATCH;
+        if started.elapsed() >= slice {
+            break;
+        }
+    }
+    let elapsed = started.elapsed();
+    (elapsed.as_nanos() as f64 / iterations as f64, iterations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measures_every_probe() {
+        let report = SelfBenchmark::new()
+            .with_duration(Duration::from_millis(15))
+            .with_budget(OverheadProbe::Timer, Duration::from_secs(1))
+            .run();
+
+        assert_eq!(report.probes.len(), 3);
+        for result in &report.probes {
+            assert!(result.ns_per_call > 0.0, "{:?}", result);
+            assert!(result.iterations >= 256);
+        }
+        assert!(report.over_budget().is_empty());
+        assert!(report.disabled.is_empty());
+    }
+
+    #[test]
+    fn test_over_budget_disables_telemetry() {
+        let mut telemetry = Telemetry::default_config();
+        // Only the counter probe has a budget, so the process-wide span
+        // sampling switch is left alone
+        let report = SelfBenchmark::new()
+            .with_duration(Duration::from_millis(6))
+            .with_budget(OverheadProbe::CounterIncrement, Duration::ZERO)
+            .with_auto_disable(true)
+            .run_at_startup(&mut telemetry);
+
+        assert_eq!(report.over_budget(), vec![OverheadProbe::CounterIncrement]);
+        assert_eq!(report.disabled, vec![OverheadProbe::CounterIncrement]);
+        assert!(!telemetry.config().enabled);
+
+        let gauges = telemetry.snapshot().gauges;
+        assert!(gauges[r#"obs_overhead_ns{probe="span_creation"}"#] > 0.0);
+        assert_eq!(
+            gauges[r#"obs_overhead_budget_exceeded{probe="counter_increment"}"#],
+            1.0
+        );
+        assert!(!gauges.contains_key(r#"obs_overhead_budget_exceeded{probe="timer"}"#));
+    }
+}
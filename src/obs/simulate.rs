@@ -0,0 +1,460 @@
+//! Simulated Workload for Demos
+//!
+//! Generates plausible query traffic through the real pipeline —
+//! [`Telemetry`] timings, counters and gauges, [`MetricEvent`]s and
+//! [`OtelSpan`] traces — so dashboards, alerts and exporters can be tried
+//! before integrating embeddenator itself.
+//!
+//! # Model
+//!
+//! - **Load** follows a diurnal curve between a trough and a peak request
+//!   rate, over a configurable (usually compressed) day.
+//! - **Cache** hit ratio warms up exponentially from zero; misses pay an
+//!   index search.
+//! - **Latency** is log-normal around a base, rising with load, with
+//!   occasional spike episodes multiplying it.
+//! - **Errors** occur at a small rate, higher during spikes.
+//!
+//! All randomness comes from a seed, so a simulation is reproducible.
+//!
+//! # Recorded Metrics
+//!
+//! - `query`, `cache_lookup`, `index_search` operation timings
+//! - `queries_total`, `cache_hits_total`, `cache_misses_total`,
+//!   `query_errors_total` counters
+//! - `query_load_rps`, `cache_hit_ratio` gauges
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::simulate::Simulation;
+//!
+//! // A full day every 10 minutes, one step per second
+//! let _handle = Simulation::new(42)
+//!     .with_day_length(Duration::from_secs(600))
+//!     .spawn(Duration::from_secs(1), telemetry.clone(), move |step| {
+//!         step.publish(&stream);
+//!         exporter.export_spans(&step.spans);
+//!     });
+//! ```
+
+use crate::obs::opentelemetry::{OtelSpan, SpanKind, SpanStatus};
+use crate::obs::process::{spawn_collector, CollectorHandle};
+use crate::obs::streaming::{MetricEvent, MetricStream};
+use crate::obs::telemetry::Telemetry;
+use std::f64::consts::TAU;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Synthetic query workload.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    rng: u64,
+    elapsed: Duration,
+    day_length: Duration,
+    trough_rps: f64,
+    peak_rps: f64,
+    cache_warmup: Duration,
+    max_hit_ratio: f64,
+    base_latency_us: f64,
+    spike_probability: f64,
+    spike_duration: Duration,
+    spike_multiplier: f64,
+    spike_remaining: Duration,
+    error_rate: f64,
+    traces_per_step: usize,
+    max_timings_per_step: usize,
+}
+
+/// What one [`Simulation::step`] generated.
+#[derive(Debug, Clone)]
+pub struct SimulationStep {
+    /// Simulated time since start, at the end of the step
+    pub elapsed: Duration,
+    pub load_rps: f64,
+    pub cache_hit_ratio: f64,
+    pub requests: u64,
+    pub errors: u64,
+    /// Whether a latency spike was active
+    pub spiking: bool,
+    /// Counter, gauge and timing events, as published to a stream
+    pub events: Vec<MetricEvent>,
+    /// Sampled request traces
+    pub spans: Vec<OtelSpan>,
+}
+
+impl SimulationStep {
+    /// Publish the step's events to `stream`, triggering its alerts.
+    pub fn publish(&self, stream: &MetricStream) {
+        for event in &self.events {
+            match event {
+                MetricEvent::Counter(name, value) => stream.publish_counter(name.as_str(), *value),
+                MetricEvent::Gauge(name, value) => stream.publish_gauge(name.as_str(), *value),
+                MetricEvent::Timing(name, us) => stream.publish_timing(name.as_str(), *us),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Simulation {
+    /// Simulation with a 24 hour day between 20 and 200 requests per
+    /// second, a 5 minute cache warm-up to 90% hits, 2ms base latency and
+    /// a spike starting in about 1 of 200 steps.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            // xorshift needs a non-zero state
+            rng: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1,
+            elapsed: Duration::ZERO,
+            day_length: Duration::from_secs(24 * 3600),
+            trough_rps: 20.0,
+            peak_rps: 200.0,
+            cache_warmup: Duration::from_secs(300),
+            max_hit_ratio: 0.9,
+            base_latency_us: 2000.0,
+            spike_probability: 0.005,
+            spike_duration: Duration::from_secs(30),
+            spike_multiplier: 8.0,
+            spike_remaining: Duration::ZERO,
+            error_rate: 0.002,
+            traces_per_step: 1,
+            max_timings_per_step: 1000,
+        }
+    }
+
+    /// Length of one load cycle; compress it to see a day in minutes.
+    pub fn with_day_length(mut self, day: Duration) -> Self {
+        self.day_length = day.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Request rate at night and at the daily peak.
+    pub fn with_load(mut self, trough_rps: f64, peak_rps: f64) -> Self {
+        self.trough_rps = trough_rps.max(0.0);
+        self.peak_rps = peak_rps.max(self.trough_rps);
+        self
+    }
+
+    /// Time constant of the cache warm-up and the hit ratio it approaches.
+    pub fn with_cache_warmup(mut self, warmup: Duration, max_hit_ratio: f64) -> Self {
+        self.cache_warmup = warmup;
+        self.max_hit_ratio = max_hit_ratio.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Median latency of a cache hit.
+    pub fn with_base_latency(mut self, latency: Duration) -> Self {
+        self.base_latency_us = latency.as_secs_f64() * 1e6;
+        self
+    }
+
+    /// Chance per step that a spike starts, how long it lasts and how
+    /// much it multiplies latency.
+    pub fn with_spikes(mut self, probability: f64, duration: Duration, multiplier: f64) -> Self {
+        self.spike_probability = probability.clamp(0.0, 1.0);
+        self.spike_duration = duration;
+        self.spike_multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Fraction of requests failing outside spikes.
+    pub fn with_error_rate(mut self, rate: f64) -> Self {
+        self.error_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Request traces generated per step.
+    pub fn with_traces_per_step(mut self, traces: usize) -> Self {
+        self.traces_per_step = traces;
+        self
+    }
+
+    /// Simulated time since start.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Request rate of the diurnal curve at simulated time `at`; the
+    /// trough is at the start of each day.
+    pub fn load_at(&self, at: Duration) -> f64 {
+        let phase = at.as_secs_f64() / self.day_length.as_secs_f64();
+        let daylight = (1.0 - (TAU * phase).cos()) / 2.0;
+        self.trough_rps + (self.peak_rps - self.trough_rps) * daylight
+    }
+
+    /// Cache hit ratio at simulated time `at`.
+    pub fn cache_hit_ratio_at(&self, at: Duration) -> f64 {
+        if self.cache_warmup.is_zero() {
+            return self.max_hit_ratio;
+        }
+        let progress = at.as_secs_f64() / self.cache_warmup.as_secs_f64();
+        self.max_hit_ratio * (1.0 - (-progress).exp())
+    }
+
+    /// Advance simulated time by `dt`, recording the generated traffic
+    /// into `telemetry`.
+    ///
+    /// Counters include every request; individual timings are recorded
+    /// for at most 1000 requests per step to bound the cost of large
+    /// steps.
+    pub fn step(&mut self, dt: Duration, telemetry: &mut Telemetry) -> SimulationStep {
+        let at = self.elapsed + dt / 2;
+        let load = self.load_at(at);
+        let hit_ratio = self.cache_hit_ratio_at(at);
+
+        if self.spike_remaining.is_zero() && self.uniform() < self.spike_probability {
+            self.spike_remaining = self.spike_duration;
+        }
+        let spiking = !self.spike_remaining.is_zero();
+        self.spike_remaining = self.spike_remaining.saturating_sub(dt);
+
+        let expected = load * dt.as_secs_f64();
+        // Normal approximation of Poisson arrivals
+        let requests = (expected + expected.sqrt() * self.normal())
+            .round()
+            .max(0.0) as u64;
+        let error_rate = if spiking {
+            (self.error_rate * 10.0).min(1.0)
+        } else {
+            self.error_rate
+        };
+        // Queueing: latency grows as load approaches the peak
+        let load_factor = 1.0 + 0.5 * load / self.peak_rps.max(1.0);
+        let spike_factor = if spiking { self.spike_multiplier } else { 1.0 };
+
+        let mut result = SimulationStep {
+            elapsed: self.elapsed + dt,
+            load_rps: load,
+            cache_hit_ratio: hit_ratio,
+            requests,
+            errors: 0,
+            spiking,
+            events: Vec::new(),
+            spans: Vec::new(),
+        };
+        let mut hits = 0;
+        let timed = requests.min(self.max_timings_per_step as u64);
+        for i in 0..requests {
+            let hit = self.uniform() < hit_ratio;
+            let failed = self.uniform() < error_rate;
+            hits += u64::from(hit);
+            result.errors += u64::from(failed);
+            if i >= timed {
+                continue;
+            }
+
+            let lookup_us = self.latency(self.base_latency_us * 0.2);
+            let search_us = if hit {
+                0.0
+            } else {
+                self.latency(self.base_latency_us * 3.0)
+            };
+            let rest_us = self.latency(self.base_latency_us * 0.8);
+            let total_us = (lookup_us + search_us + rest_us) * load_factor * spike_factor;
+
+            telemetry.record_operation("cache_lookup", lookup_us as u64);
+            if !hit {
+                telemetry.record_operation("index_search", search_us as u64);
+            }
+            telemetry.record_operation("query", total_us as u64);
+            result
+                .events
+                .push(MetricEvent::Timing("query".to_string(), total_us as u64));
+
+            if (i as usize) < self.traces_per_step {
+                let scale = load_factor * spike_factor;
+                result.spans.extend(request_trace(
+                    lookup_us * scale,
+                    search_us * scale,
+                    total_us,
+                    hit,
+                    failed,
+                ));
+            }
+        }
+
+        telemetry.add_to_counter("queries_total", requests);
+        telemetry.add_to_counter("cache_hits_total", hits);
+        telemetry.add_to_counter("cache_misses_total", requests - hits);
+        telemetry.add_to_counter("query_errors_total", result.errors);
+        telemetry.set_gauge("query_load_rps", load);
+        telemetry.set_gauge("cache_hit_ratio", hit_ratio);
+
+        let snapshot = telemetry.snapshot();
+        for name in [
+            "queries_total",
+            "cache_hits_total",
+            "cache_misses_total",
+            "query_errors_total",
+        ] {
+            let total = snapshot.counters.get(name).copied().unwrap_or(0);
+            result
+                .events
+                .push(MetricEvent::Counter(name.to_string(), total));
+        }
+        result
+            .events
+            .push(MetricEvent::Gauge("query_load_rps".to_string(), load));
+        result
+            .events
+            .push(MetricEvent::Gauge("cache_hit_ratio".to_string(), hit_ratio));
+
+        self.elapsed += dt;
+        result
+    }
+
+    /// Step every `interval` of wall time on a background thread, with
+    /// simulated time advancing at the same rate, and hand each step to
+    /// `on_step` (e.g. to publish it or export its spans).
+    pub fn spawn(
+        mut self,
+        interval: Duration,
+        telemetry: Arc<Mutex<Telemetry>>,
+        mut on_step: impl FnMut(&SimulationStep) + Send + 'static,
+    ) -> CollectorHandle {
+        spawn_collector("obs-simulation", interval, telemetry, move |telemetry| {
+            let step = self.step(interval, telemetry);
+            on_step(&step);
+        })
+    }
+
+    /// Log-normal sample with median `median_us`.
+    fn latency(&mut self, median_us: f64) -> f64 {
+        median_us * (0.35 * self.normal()).exp()
+    }
+
+    /// Uniform in [0, 1) (xorshift64).
+    fn uniform(&mut self) -> f64 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng = x;
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal sample (Box-Muller).
+    fn normal(&mut self) -> f64 {
+        let u1 = self.uniform().max(f64::MIN_POSITIVE);
+        let u2 = self.uniform();
+        (-2.0 * u1.ln()).sqrt() * (TAU * u2).cos()
+    }
+}
+
+/// Spans of one request ending now: `query` with `cache_lookup` and, on
+/// a miss, `index_search` children.
+fn request_trace(
+    lookup_us: f64,
+    search_us: f64,
+    total_us: f64,
+    hit: bool,
+    failed: bool,
+) -> Vec<OtelSpan> {
+    let end_ns = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let start_ns = end_ns.saturating_sub((total_us * 1000.0) as u64);
+
+    let mut root = OtelSpan::new("query");
+    root.set_kind(SpanKind::Server);
+    root.set_attribute("cache.hit", hit);
+    root.start_time_ns = start_ns;
+    root.end_time_ns = end_ns;
+    root.status = if failed {
+        root.set_attribute("error.message", "simulated failure");
+        SpanStatus::Error
+    } else {
+        SpanStatus::Ok
+    };
+
+    let mut spans = Vec::with_capacity(3);
+    let mut cursor = start_ns;
+    for (name, duration_us) in [("cache_lookup", lookup_us), ("index_search", search_us)] {
+        if name == "index_search" && hit {
+            continue;
+        }
+        let mut child = OtelSpan::new_child(name, &root);
+        child.start_time_ns = cursor;
+        cursor = (cursor + (duration_us * 1000.0) as u64).min(end_ns);
+        child.end_time_ns = cursor;
+        child.status = SpanStatus::Ok;
+        spans.push(child);
+    }
+    spans.push(root);
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_curve_and_cache_warmup() {
+        let sim = Simulation::new(1)
+            .with_day_length(Duration::from_secs(100))
+            .with_load(10.0, 110.0)
+            .with_cache_warmup(Duration::from_secs(10), 0.8);
+
+        assert!((sim.load_at(Duration::ZERO) - 10.0).abs() < 1e-9);
+        assert!((sim.load_at(Duration::from_secs(50)) - 110.0).abs() < 1e-9);
+        assert!((sim.load_at(Duration::from_secs(25)) - 60.0).abs() < 1e-9);
+        assert!((sim.load_at(Duration::from_secs(100)) - 10.0).abs() < 1e-9);
+
+        assert_eq!(sim.cache_hit_ratio_at(Duration::ZERO), 0.0);
+        assert!(sim.cache_hit_ratio_at(Duration::from_secs(10)) > 0.5);
+        assert!((sim.cache_hit_ratio_at(Duration::from_secs(100)) - 0.8).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_step_records_into_pipeline() {
+        let mut telemetry = Telemetry::default_config();
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        let published = Arc::new(Mutex::new(0));
+        let counter = published.clone();
+        stream.subscribe(move |_| *counter.lock().unwrap() += 1);
+
+        let mut sim = Simulation::new(7)
+            .with_load(100.0, 100.0)
+            .with_cache_warmup(Duration::ZERO, 0.5);
+        let step = sim.step(Duration::from_secs(1), &mut telemetry);
+        step.publish(&stream);
+
+        assert!(step.requests > 50 && step.requests < 150);
+        assert_eq!(sim.elapsed(), Duration::from_secs(1));
+        let snapshot = telemetry.snapshot();
+        assert_eq!(snapshot.counters["queries_total"], step.requests);
+        assert_eq!(
+            snapshot.counters["cache_hits_total"] + snapshot.counters["cache_misses_total"],
+            step.requests
+        );
+        assert_eq!(snapshot.operation_stats["query"].count, step.requests);
+        assert_eq!(snapshot.gauges["cache_hit_ratio"], 0.5);
+        assert_eq!(*published.lock().unwrap(), step.events.len());
+
+        let root = step.spans.iter().find(|s| s.is_root()).unwrap();
+        assert_eq!(root.name, "query");
+        assert!(step
+            .spans
+            .iter()
+            .filter(|s| !s.is_root())
+            .all(|s| s.trace_id == root.trace_id && s.end_time_ns <= root.end_time_ns));
+    }
+
+    #[test]
+    fn test_seeded_runs_are_reproducible() {
+        let run = |seed| {
+            let mut telemetry = Telemetry::default_config();
+            let mut sim = Simulation::new(seed).with_spikes(0.5, Duration::from_secs(2), 10.0);
+            (0..20)
+                .map(|_| {
+                    let step = sim.step(Duration::from_secs(1), &mut telemetry);
+                    (step.requests, step.errors, step.spiking)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(3), run(3));
+        assert_ne!(run(3), run(4));
+        assert!(run(3).iter().any(|&(_, _, spiking)| spiking));
+    }
+}
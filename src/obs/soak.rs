@@ -0,0 +1,335 @@
+//! Soak Testing with Leak Detection
+//!
+//! Runs a workload closure repeatedly for a long period while sampling
+//! resource probes (RSS, open file descriptors, internal map sizes such as
+//! telemetry keys or rate limiter entries), then flags probes that grew
+//! monotonically — automating leak detection for observability state.
+//!
+//! # Leak Criterion
+//!
+//! Post-warmup samples are split into four equal quarters. A probe leaks
+//! when the quarter means strictly increase (`q1 < q2 < q3 < q4`) and the
+//! total rise `q4 - q1` exceeds the probe's tolerance. Growth that levels
+//! off (caches warming up) fails the strict-increase check. The
+//! least-squares slope is reported for context.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::soak::SoakTest;
+//!
+//! let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+//! let probe_telemetry = telemetry.clone();
+//!
+//! let report = SoakTest::new("query_path")
+//!     .duration(Duration::from_secs(4 * 3600))
+//!     .sample_every(Duration::from_secs(30))
+//!     .warmup(Duration::from_secs(300))
+//!     .probe("telemetry_series", 0.0, move || {
+//!         probe_telemetry.lock().unwrap().series_count() as f64
+//!     })
+//!     .run(|| run_one_query(&telemetry));
+//!
+//! report.assert_no_leaks();
+//! ```
+
+use crate::obs::process::ProcessCollector;
+use std::time::{Duration, Instant};
+
+type ProbeFn = Box<dyn FnMut() -> Option<f64>>;
+
+struct Probe {
+    name: String,
+    tolerance: f64,
+    read: ProbeFn,
+}
+
+/// Long-running workload harness sampling resource probes.
+pub struct SoakTest {
+    name: String,
+    duration: Duration,
+    sample_interval: Duration,
+    warmup: Duration,
+    probes: Vec<Probe>,
+}
+
+impl SoakTest {
+    /// Soak test with RSS (16 MiB tolerance) and open-fd (8 tolerance)
+    /// probes, running 1 hour, sampling every 10 seconds after a 1 minute
+    /// warmup.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            duration: Duration::from_secs(3600),
+            sample_interval: Duration::from_secs(10),
+            warmup: Duration::from_secs(60),
+            probes: Vec::new(),
+        }
+        .probe_optional("rss_bytes", 16.0 * 1024.0 * 1024.0, || {
+            ProcessCollector::new()
+                .sample()
+                .resident_memory_bytes
+                .map(|v| v as f64)
+        })
+        .probe_optional("open_fds", 8.0, || {
+            ProcessCollector::new().sample().open_fds.map(|v| v as f64)
+        })
+    }
+
+    /// Total run time, including warmup.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Interval between probe samples.
+    pub fn sample_every(mut self, interval: Duration) -> Self {
+        self.sample_interval = interval;
+        self
+    }
+
+    /// Initial period whose samples are excluded from trend analysis.
+    pub fn warmup(mut self, warmup: Duration) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Add a probe; growth beyond `tolerance` (in probe units) with a
+    /// monotonic trend fails the soak.
+    pub fn probe<F>(self, name: impl Into<String>, tolerance: f64, mut read: F) -> Self
+    where
+        F: FnMut() -> f64 + 'static,
+    {
+        self.probe_optional(name, tolerance, move || Some(read()))
+    }
+
+    /// Add a probe that may be unavailable (`None` samples are skipped).
+    pub fn probe_optional<F>(mut self, name: impl Into<String>, tolerance: f64, read: F) -> Self
+    where
+        F: FnMut() -> Option<f64> + 'static,
+    {
+        let name = name.into();
+        self.probes.retain(|probe| probe.name != name);
+        self.probes.push(Probe {
+            name,
+            tolerance,
+            read: Box::new(read),
+        });
+        self
+    }
+
+    /// Run `workload` repeatedly until the duration elapses.
+    pub fn run<F: FnMut()>(mut self, mut workload: F) -> SoakReport {
+        let started = Instant::now();
+        let mut samples: Vec<Vec<(f64, f64)>> = vec![Vec::new(); self.probes.len()];
+        let mut iterations = 0u64;
+        let mut next_sample = Duration::ZERO;
+
+        loop {
+            let elapsed = started.elapsed();
+            if elapsed >= next_sample {
+                if elapsed >= self.warmup {
+                    let t = elapsed.as_secs_f64();
+                    for (probe, series) in self.probes.iter_mut().zip(&mut samples) {
+                        if let Some(value) = (probe.read)() {
+                            series.push((t, value));
+                        }
+                    }
+                }
+                next_sample = elapsed + self.sample_interval;
+            }
+            if elapsed >= self.duration {
+                break;
+            }
+            workload();
+            iterations += 1;
+        }
+
+        let probes = self
+            .probes
+            .iter()
+            .zip(samples)
+            .map(|(probe, samples)| ProbeTrend::analyze(&probe.name, probe.tolerance, samples))
+            .collect();
+
+        SoakReport {
+            name: self.name,
+            iterations,
+            elapsed: started.elapsed(),
+            probes,
+        }
+    }
+}
+
+/// Trend analysis of one probe.
+#[derive(Debug, Clone)]
+pub struct ProbeTrend {
+    pub name: String,
+    /// `(seconds since start, value)` pairs after warmup
+    pub samples: Vec<(f64, f64)>,
+    /// Least-squares slope in probe units per second
+    pub slope_per_sec: f64,
+    /// Mean of each post-warmup quarter
+    pub quarter_means: [f64; 4],
+    pub tolerance: f64,
+    pub leaking: bool,
+}
+
+impl ProbeTrend {
+    fn analyze(name: &str, tolerance: f64, samples: Vec<(f64, f64)>) -> Self {
+        let slope_per_sec = least_squares_slope(&samples);
+        let mut quarter_means = [0.0; 4];
+        let mut leaking = false;
+
+        if samples.len() >= 4 {
+            for (q, mean) in quarter_means.iter_mut().enumerate() {
+                let lo = q * samples.len() / 4;
+                let hi = (q + 1) * samples.len() / 4;
+                let quarter = &samples[lo..hi];
+                *mean = quarter.iter().map(|&(_, v)| v).sum::<f64>() / quarter.len() as f64;
+            }
+            let strictly_increasing = quarter_means.windows(2).all(|w| w[0] < w[1]);
+            leaking = strictly_increasing && quarter_means[3] - quarter_means[0] > tolerance;
+        }
+
+        Self {
+            name: name.to_string(),
+            samples,
+            slope_per_sec,
+            quarter_means,
+            tolerance,
+            leaking,
+        }
+    }
+
+    /// Total rise between the first and last quarter means.
+    pub fn growth(&self) -> f64 {
+        self.quarter_means[3] - self.quarter_means[0]
+    }
+}
+
+fn least_squares_slope(samples: &[(f64, f64)]) -> f64 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+    let n = samples.len() as f64;
+    let mean_t = samples.iter().map(|&(t, _)| t).sum::<f64>() / n;
+    let mean_v = samples.iter().map(|&(_, v)| v).sum::<f64>() / n;
+    let (mut num, mut den) = (0.0, 0.0);
+    for &(t, v) in samples {
+        num += (t - mean_t) * (v - mean_v);
+        den += (t - mean_t) * (t - mean_t);
+    }
+    if den == 0.0 {
+        0.0
+    } else {
+        num / den
+    }
+}
+
+/// Result of a soak run.
+#[derive(Debug, Clone)]
+pub struct SoakReport {
+    pub name: String,
+    pub iterations: u64,
+    pub elapsed: Duration,
+    pub probes: Vec<ProbeTrend>,
+}
+
+impl SoakReport {
+    /// True when no probe shows monotonic growth beyond its tolerance.
+    pub fn passed(&self) -> bool {
+        self.probes.iter().all(|probe| !probe.leaking)
+    }
+
+    /// Probes flagged as leaking.
+    pub fn leaks(&self) -> Vec<&ProbeTrend> {
+        self.probes.iter().filter(|probe| probe.leaking).collect()
+    }
+
+    /// Panic with the summary if any probe leaks.
+    pub fn assert_no_leaks(&self) {
+        assert!(self.passed(), "{}", self.summary());
+    }
+
+    /// Format as human-readable summary.
+    pub fn summary(&self) -> String {
+        let mut output = format!(
+            "=== Soak '{}' ({:.1}s, {} iterations): {} ===\n",
+            self.name,
+            self.elapsed.as_secs_f64(),
+            self.iterations,
+            if self.passed() { "PASS" } else { "LEAK" }
+        );
+        for probe in &self.probes {
+            output.push_str(&format!(
+                "  {}{}: samples={}, growth={:.1} (tolerance {:.1}), slope={:.3}/h\n",
+                if probe.leaking { "[LEAK] " } else { "" },
+                probe.name,
+                probe.samples.len(),
+                probe.growth(),
+                probe.tolerance,
+                probe.slope_per_sec * 3600.0
+            ));
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn short(name: &str) -> SoakTest {
+        SoakTest::new(name)
+            .duration(Duration::from_millis(200))
+            .sample_every(Duration::from_millis(10))
+            .warmup(Duration::ZERO)
+    }
+
+    #[test]
+    fn test_growing_probe_is_flagged() {
+        let map = Rc::new(RefCell::new(Vec::new()));
+        let probe_map = map.clone();
+
+        let report = short("leaky")
+            .probe("entries", 10.0, move || probe_map.borrow().len() as f64)
+            .run(|| {
+                map.borrow_mut().push(0u8);
+                std::thread::sleep(Duration::from_micros(200));
+            });
+
+        assert!(!report.passed(), "{}", report.summary());
+        assert_eq!(report.leaks()[0].name, "entries");
+        assert!(report.leaks()[0].slope_per_sec > 0.0);
+        assert!(report.summary().contains("[LEAK] entries"));
+    }
+
+    #[test]
+    fn test_plateau_is_not_flagged() {
+        let map = Rc::new(RefCell::new(Vec::new()));
+        let probe_map = map.clone();
+
+        let report = short("warming_cache")
+            .probe("entries", 0.0, move || probe_map.borrow().len() as f64)
+            .run(|| {
+                let mut map = map.borrow_mut();
+                if map.len() < 50 {
+                    map.push(0u8);
+                }
+                std::thread::sleep(Duration::from_micros(200));
+            });
+
+        let entries = report.probes.iter().find(|p| p.name == "entries").unwrap();
+        assert!(!entries.leaking, "{}", report.summary());
+    }
+
+    #[test]
+    fn test_least_squares_slope() {
+        let samples: Vec<(f64, f64)> = (0..10).map(|i| (i as f64, 3.0 * i as f64 + 1.0)).collect();
+        assert!((least_squares_slope(&samples) - 3.0).abs() < 1e-9);
+        assert_eq!(least_squares_slope(&[(1.0, 5.0)]), 0.0);
+    }
+}
@@ -0,0 +1,555 @@
+//! Span Processing Pipeline
+//!
+//! Routes spans to registered [`SpanProcessor`]s as they start and end,
+//! instead of callers collecting finished spans into a `Vec` and handing
+//! it to an exporter. A [`TracerProvider`] holds the processors; once
+//! installed, every [`OtelSpan`] created with `new`, `new_child`,
+//! `from_context` or the builder is reported on start, and again when
+//! `end` or `end_with_error` is called.
+//!
+//! # Processors
+//!
+//! - [`SimpleSpanProcessor`]: exports each sampled span as it ends
+//! - [`BatchSpanProcessor`]: queues sampled spans and exports them in
+//!   batches from a background thread
+//! - [`SpanMetricsProcessor`]: rate, error and duration metrics per span
+//!   name, written into a [`Telemetry`]
+//! - [`RecentSpans`]: the last N ended spans in memory, for inspecting a
+//!   running process
+//! - any `Fn(&OtelSpan)` closure, called for every ended span (metrics
+//!   derivation, logging)
+//!
+//! Processors run on the thread ending the span, so they should be cheap;
+//! exporters that do I/O belong behind a [`BatchSpanProcessor`].
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::span_processor::{BatchSpanProcessor, TracerProvider};
+//!
+//! let zipkin = ZipkinExporter::new("http://zipkin:9411/api/v2/spans")?;
+//! let batcher = BatchSpanProcessor::new(move |spans| zipkin.push(spans));
+//! let _worker = batcher.spawn(Duration::from_secs(5));
+//!
+//! TracerProvider::new()
+//!     .with_processor(batcher)
+//!     .with_processor(SpanMetricsProcessor::new(telemetry.clone()))
+//!     .with_processor(|span: &OtelSpan| eprintln!("{} ended", span.name))
+//!     .install();
+//!
+//! let mut span = OtelSpan::new("query");
+//! span.end(); // queued for Zipkin and logged
+//!
+//! TracerProvider::uninstall(); // flushes and shuts down processors
+//! ```
+
+use crate::obs::opentelemetry::{OtelSpan, SpanStatus};
+use crate::obs::process::{spawn_sink, CollectorHandle};
+use crate::obs::telemetry::Telemetry;
+use crate::obs::tracing::{record_event, EventLevel};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// Installed provider; `ACTIVE` keeps the no-provider path lock-free.
+static PROVIDER: RwLock<Option<Arc<TracerProvider>>> = RwLock::new(None);
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Receives spans from the installed [`TracerProvider`].
+pub trait SpanProcessor: Send + Sync {
+    /// Called when a span is created.
+    fn on_start(&self, _span: &OtelSpan) {}
+
+    /// Called when a span ends, with its final state.
+    fn on_end(&self, span: &OtelSpan);
+
+    /// Export anything buffered.
+    fn force_flush(&self) {}
+
+    /// Flush and release resources; called when the provider is replaced
+    /// or uninstalled.
+    fn shutdown(&self) {
+        self.force_flush();
+    }
+}
+
+impl<F> SpanProcessor for F
+where
+    F: Fn(&OtelSpan) + Send + Sync,
+{
+    fn on_end(&self, span: &OtelSpan) {
+        self(span)
+    }
+}
+
+/// Called when a span is created.
+pub(crate) fn span_started(span: &OtelSpan) {
+    if let Some(provider) = installed() {
+        for processor in &provider.processors {
+            processor.on_start(span);
+        }
+    }
+}
+
+/// Called when a span ends.
+pub(crate) fn span_ended(span: &OtelSpan) {
+    if let Some(provider) = installed() {
+        for processor in &provider.processors {
+            processor.on_end(span);
+        }
+    }
+}
+
+fn installed() -> Option<Arc<TracerProvider>> {
+    if !ACTIVE.load(Ordering::Acquire) {
+        return None;
+    }
+    // Cloned out so processors may create spans without holding the lock
+    PROVIDER.read().ok().and_then(|provider| provider.clone())
+}
+
+/// Ordered set of span processors, installed process-wide.
+#[derive(Default)]
+pub struct TracerProvider {
+    processors: Vec<Box<dyn SpanProcessor>>,
+}
+
+impl TracerProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a processor; processors are called in the order added.
+    pub fn with_processor(mut self, processor: impl SpanProcessor + 'static) -> Self {
+        self.processors.push(Box::new(processor));
+        self
+    }
+
+    pub fn processor_count(&self) -> usize {
+        self.processors.len()
+    }
+
+    /// Route every span to this provider's processors, shutting down the
+    /// previously installed provider.
+    pub fn install(self) {
+        let previous = match PROVIDER.write() {
+            Ok(mut provider) => {
+                ACTIVE.store(!self.processors.is_empty(), Ordering::Release);
+                provider.replace(Arc::new(self))
+            }
+            Err(_) => return,
+        };
+        if let Some(previous) = previous {
+            previous.shutdown();
+        }
+    }
+
+    /// Stop routing spans, then flush and shut down the installed
+    /// processors.
+    pub fn uninstall() {
+        let previous = match PROVIDER.write() {
+            Ok(mut provider) => {
+                ACTIVE.store(false, Ordering::Release);
+                provider.take()
+            }
+            Err(_) => return,
+        };
+        if let Some(previous) = previous {
+            previous.shutdown();
+        }
+    }
+
+    /// Whether a provider with at least one processor is installed.
+    pub fn is_installed() -> bool {
+        ACTIVE.load(Ordering::Acquire)
+    }
+
+    /// Flush every processor of the installed provider.
+    pub fn flush() {
+        if let Some(provider) = installed() {
+            for processor in &provider.processors {
+                processor.force_flush();
+            }
+        }
+    }
+
+    fn shutdown(&self) {
+        for processor in &self.processors {
+            processor.shutdown();
+        }
+    }
+}
+
+type ExportFn = dyn Fn(&[OtelSpan]) -> io::Result<()> + Send + Sync;
+
+/// Exports each sampled span as soon as it ends.
+pub struct SimpleSpanProcessor {
+    export: Box<ExportFn>,
+}
+
+impl SimpleSpanProcessor {
+    pub fn new(export: impl Fn(&[OtelSpan]) -> io::Result<()> + Send + Sync + 'static) -> Self {
+        Self {
+            export: Box::new(export),
+        }
+    }
+}
+
+impl SpanProcessor for SimpleSpanProcessor {
+    fn on_end(&self, span: &OtelSpan) {
+        if !span.sampled {
+            return;
+        }
+        if let Err(e) = (self.export)(std::slice::from_ref(span)) {
+            record_event(
+                EventLevel::Warn,
+                "span export failed",
+                &[("error", &e.to_string())],
+            );
+        }
+    }
+}
+
+struct BatchState {
+    export: Box<ExportFn>,
+    queue: Mutex<VecDeque<OtelSpan>>,
+    max_queue_size: usize,
+    max_batch_size: usize,
+    dropped: AtomicU64,
+    exported: AtomicU64,
+}
+
+/// Queues sampled spans and exports them in batches.
+///
+/// Batches go out when [`spawn`](Self::spawn)'s worker ticks, when the
+/// queue reaches the batch size, and on flush or shutdown. When the queue
+/// is full, newly ended spans are dropped and counted. Clones share the
+/// queue, so one clone can be registered while another drives the worker.
+#[derive(Clone)]
+pub struct BatchSpanProcessor {
+    state: Arc<BatchState>,
+}
+
+impl BatchSpanProcessor {
+    /// Batcher with a 2048 span queue and batches of up to 512 spans.
+    pub fn new(export: impl Fn(&[OtelSpan]) -> io::Result<()> + Send + Sync + 'static) -> Self {
+        Self {
+            state: Arc::new(BatchState {
+                export: Box::new(export),
+                queue: Mutex::new(VecDeque::new()),
+                max_queue_size: 2048,
+                max_batch_size: 512,
+                dropped: AtomicU64::new(0),
+                exported: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Spans held before new ones are dropped.
+    pub fn with_max_queue_size(self, size: usize) -> Self {
+        self.reconfigure(|state| state.max_queue_size = size.max(1))
+    }
+
+    /// Spans per export call; a full batch is exported right away.
+    pub fn with_max_batch_size(self, size: usize) -> Self {
+        self.reconfigure(|state| state.max_batch_size = size.max(1))
+    }
+
+    fn reconfigure(mut self, apply: impl FnOnce(&mut BatchState)) -> Self {
+        if let Some(state) = Arc::get_mut(&mut self.state) {
+            apply(state);
+        }
+        self
+    }
+
+    /// Export queued spans every `interval` on a background thread. Also
+    /// drained by [`force_flush`](crate::obs::process::force_flush).
+    pub fn spawn(&self, interval: Duration) -> CollectorHandle {
+        let batcher = self.clone();
+        spawn_sink("obs-span-batch", interval, move || batcher.export_all())
+    }
+
+    /// Spans waiting for export.
+    pub fn queued(&self) -> usize {
+        self.state.queue.lock().map(|q| q.len()).unwrap_or(0)
+    }
+
+    /// Spans dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.state.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Spans handed to the export function.
+    pub fn exported(&self) -> u64 {
+        self.state.exported.load(Ordering::Relaxed)
+    }
+
+    /// Export everything queued, one batch at a time.
+    fn export_all(&self) {
+        while self.export_batch(0) {}
+    }
+
+    /// Export one batch if more than `min` spans are queued.
+    fn export_batch(&self, min: usize) -> bool {
+        let batch: Vec<OtelSpan> = match self.state.queue.lock() {
+            Ok(mut queue) if queue.len() > min => {
+                let n = queue.len().min(self.state.max_batch_size);
+                queue.drain(..n).collect()
+            }
+            _ => return false,
+        };
+        self.state
+            .exported
+            .fetch_add(batch.len() as u64, Ordering::Relaxed);
+        if let Err(e) = (self.state.export)(&batch) {
+            record_event(
+                EventLevel::Warn,
+                "span batch export failed",
+                &[
+                    ("spans", &batch.len().to_string()),
+                    ("error", &e.to_string()),
+                ],
+            );
+        }
+        true
+    }
+}
+
+impl SpanProcessor for BatchSpanProcessor {
+    fn on_end(&self, span: &OtelSpan) {
+        if !span.sampled {
+            return;
+        }
+        let full_batch = match self.state.queue.lock() {
+            Ok(mut queue) => {
+                if queue.len() >= self.state.max_queue_size {
+                    self.state.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                queue.push_back(span.clone());
+                queue.len() >= self.state.max_batch_size
+            }
+            Err(_) => return,
+        };
+        if full_batch {
+            self.export_batch(self.state.max_batch_size - 1);
+        }
+    }
+
+    fn force_flush(&self) {
+        self.export_all();
+    }
+}
+
+/// Derives RED metrics from every ended span, sampled or not:
+///
+/// - `spans_total{span}` and `span_errors_total{span}` counters
+/// - the span's duration as an operation timing named after the span,
+///   with the trace id as exemplar for sampled spans
+///
+/// Instrumented operations get latency histograms without separate
+/// [`Telemetry::record_operation`] calls.
+pub struct SpanMetricsProcessor {
+    telemetry: Arc<Mutex<Telemetry>>,
+    operation_prefix: String,
+}
+
+impl SpanMetricsProcessor {
+    pub fn new(telemetry: Arc<Mutex<Telemetry>>) -> Self {
+        Self {
+            telemetry,
+            operation_prefix: String::new(),
+        }
+    }
+
+    /// Prefix operation timings (e.g. `span.`) to keep them apart from
+    /// operations recorded directly.
+    pub fn with_operation_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.operation_prefix = prefix.into();
+        self
+    }
+}
+
+impl SpanProcessor for SpanMetricsProcessor {
+    fn on_end(&self, span: &OtelSpan) {
+        let duration_us = span.duration_ns() / 1000;
+        let operation = format!("{}{}", self.operation_prefix, span.name);
+        let labels = [("span", span.name.as_str())];
+        let Ok(mut telemetry) = self.telemetry.lock() else {
+            return;
+        };
+        telemetry.add_to_counter_with_labels("spans_total", &labels, 1);
+        if span.status == SpanStatus::Error {
+            telemetry.add_to_counter_with_labels("span_errors_total", &labels, 1);
+        }
+        if span.sampled {
+            let trace_id = format!("{:032x}", span.trace_id);
+            telemetry.record_operation_with_exemplar(&operation, duration_us, &trace_id);
+        } else {
+            telemetry.record_operation(&operation, duration_us);
+        }
+    }
+}
+
+/// Keeps the most recently ended spans, sampled or not. Clones share the
+/// buffer, so one clone can be registered while another is read.
+#[derive(Clone)]
+pub struct RecentSpans {
+    spans: Arc<Mutex<VecDeque<OtelSpan>>>,
+    capacity: usize,
+}
+
+impl Default for RecentSpans {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl RecentSpans {
+    /// Buffer of the last `capacity` spans (at least one).
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            spans: Arc::new(Mutex::new(VecDeque::new())),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Buffered spans, in end order.
+    pub fn spans(&self) -> Vec<OtelSpan> {
+        self.spans
+            .lock()
+            .map(|spans| spans.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl SpanProcessor for RecentSpans {
+    fn on_end(&self, span: &OtelSpan) {
+        if let Ok(mut spans) = self.spans.lock() {
+            if spans.len() == self.capacity {
+                spans.pop_front();
+            }
+            spans.push_back(span.clone());
+        }
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl SpanProcessor for crate::obs::test_util::InMemorySpanExporter {
+    fn on_end(&self, span: &OtelSpan) {
+        if span.sampled {
+            self.export(std::slice::from_ref(span));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collecting() -> (BatchSpanProcessor, Arc<Mutex<Vec<usize>>>) {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let sink = batches.clone();
+        let batcher = BatchSpanProcessor::new(move |spans| {
+            sink.lock().unwrap().push(spans.len());
+            Ok(())
+        });
+        (batcher, batches)
+    }
+
+    #[test]
+    fn test_batch_processor_batches_and_drops() {
+        let (batcher, batches) = collecting();
+        let batcher = batcher.with_max_batch_size(3).with_max_queue_size(4);
+
+        let mut span = OtelSpan::new("op");
+        span.end();
+        for _ in 0..7 {
+            batcher.on_end(&span);
+        }
+        // Full batches leave immediately; the rest waits for a flush
+        assert_eq!(*batches.lock().unwrap(), vec![3, 3]);
+        assert_eq!(batcher.queued(), 1);
+
+        let mut unsampled = OtelSpan::new("hidden");
+        unsampled.sampled = false;
+        batcher.on_end(&unsampled);
+        batcher.force_flush();
+        assert_eq!(*batches.lock().unwrap(), vec![3, 3, 1]);
+        assert_eq!(batcher.exported(), 7);
+        assert_eq!(batcher.dropped(), 0);
+
+        // Queue bound holds when no batch fills
+        let (batcher, _) = collecting();
+        let batcher = batcher.with_max_batch_size(10).with_max_queue_size(2);
+        for _ in 0..5 {
+            batcher.on_end(&span);
+        }
+        assert_eq!(batcher.queued(), 2);
+        assert_eq!(batcher.dropped(), 3);
+    }
+
+    #[test]
+    fn test_span_metrics() {
+        let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+        let processor = SpanMetricsProcessor::new(telemetry.clone()).with_operation_prefix("span.");
+
+        let mut ok = OtelSpan::new("query");
+        ok.end();
+        ok.end_time_ns = ok.start_time_ns + 2_500_000;
+        let mut failed = OtelSpan::new("query");
+        failed.sampled = false;
+        failed.end_with_error("timeout");
+        processor.on_end(&ok);
+        processor.on_end(&failed);
+
+        let snapshot = telemetry.lock().unwrap().snapshot();
+        assert_eq!(snapshot.counters[r#"spans_total{span="query"}"#], 2);
+        assert_eq!(snapshot.counters[r#"span_errors_total{span="query"}"#], 1);
+        let stats = &snapshot.operation_stats["span.query"];
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.max_us, 2500);
+        // Exemplar from the sampled span only
+        let exemplar = stats.exemplar.as_ref().unwrap();
+        assert_eq!(exemplar.trace_id, format!("{:032x}", ok.trace_id));
+    }
+
+    #[test]
+    fn test_recent_spans_keeps_latest() {
+        let recent = RecentSpans::new(2);
+        for name in ["a", "b", "c"] {
+            let mut span = OtelSpan::new(name);
+            span.sampled = name != "c";
+            span.end();
+            recent.clone().on_end(&span);
+        }
+        let names: Vec<String> = recent.spans().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, ["b", "c"]);
+    }
+
+    #[test]
+    fn test_worker_exports_queue() {
+        let (batcher, batches) = collecting();
+        let mut span = OtelSpan::new("op");
+        span.end();
+        batcher.on_end(&span);
+        batcher.on_end(&span);
+
+        let worker = batcher.spawn(Duration::from_secs(60));
+        // The first tick runs immediately
+        for _ in 0..100 {
+            if batcher.queued() == 0 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        worker.stop();
+        assert_eq!(*batches.lock().unwrap(), vec![2]);
+    }
+}
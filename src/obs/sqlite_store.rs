@@ -0,0 +1,531 @@
+//! SQLite Local Metrics Store
+//!
+//! Durable local history for single-node deployments without a monitoring
+//! stack: snapshots are appended to an embedded SQLite database with
+//! time-based retention and can be queried with plain SQL.
+//!
+//! # Schema
+//!
+//! ```sql
+//! snapshots  (id, timestamp_ms, uptime_secs)
+//! operations (snapshot_id, timestamp_ms, operation, count, total_us, min_us,
+//!             max_us, mean_us, p50_us, p95_us, p99_us)
+//! metrics    (snapshot_id, timestamp_ms, kind, name, labels, value)
+//! ```
+//!
+//! `kind` is `counter` or `gauge`; `labels` holds the label body of labeled
+//! keys (`tier="l1"`), empty when unlabeled.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::sqlite_store::SqliteStore;
+//!
+//! let store = SqliteStore::open("metrics.db")?
+//!     .with_retention(Duration::from_secs(7 * 86_400));
+//! let _handle = store.spawn(Duration::from_secs(60), telemetry.clone());
+//!
+//! let reader = SqliteStore::open("metrics.db")?;
+//! let result = reader.query(
+//!     "SELECT operation, max(p99_us) FROM operations GROUP BY operation",
+//! )?;
+//! print!("{}", result.format_table());
+//!
+//! // Typed history of one operation or metric over a trailing window
+//! for point in reader.operation_history("retrieval_query", Duration::from_secs(3600))? {
+//!     println!("{:?} p99={}us", point.timestamp, point.p99_us);
+//! }
+//! ```
+//!
+//! From the command line:
+//!
+//! ```text
+//! obs-cli history metrics.db retrieval_query --last 6h
+//! obs-cli sql metrics.db "SELECT operation, max(p99_us) FROM operations GROUP BY operation"
+//! ```
+
+use crate::obs::logging;
+use crate::obs::privacy;
+use crate::obs::process::{spawn_sink, CollectorHandle};
+use crate::obs::telemetry::{split_labeled_key, Telemetry, TelemetrySnapshot};
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snapshots (
+    id INTEGER PRIMARY KEY,
+    timestamp_ms INTEGER NOT NULL,
+    uptime_secs REAL NOT NULL
+);
+CREATE TABLE IF NOT EXISTS operations (
+    snapshot_id INTEGER NOT NULL REFERENCES snapshots(id) ON DELETE CASCADE,
+    timestamp_ms INTEGER NOT NULL,
+    operation TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    total_us INTEGER NOT NULL,
+    min_us INTEGER NOT NULL,
+    max_us INTEGER NOT NULL,
+    mean_us REAL NOT NULL,
+    p50_us INTEGER NOT NULL,
+    p95_us INTEGER NOT NULL,
+    p99_us INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS metrics (
+    snapshot_id INTEGER NOT NULL REFERENCES snapshots(id) ON DELETE CASCADE,
+    timestamp_ms INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    name TEXT NOT NULL,
+    labels TEXT NOT NULL,
+    value REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS snapshots_time ON snapshots(timestamp_ms);
+CREATE INDEX IF NOT EXISTS operations_name_time ON operations(operation, timestamp_ms);
+CREATE INDEX IF NOT EXISTS metrics_name_time ON metrics(name, timestamp_ms);
+";
+
+fn sql_error(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// One cell of a query result.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl fmt::Display for SqlValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SqlValue::Null => write!(f, "NULL"),
+            SqlValue::Integer(v) => write!(f, "{}", v),
+            SqlValue::Real(v) => write!(f, "{}", v),
+            SqlValue::Text(v) => write!(f, "{}", v),
+            SqlValue::Blob(v) => write!(f, "<{} bytes>", v.len()),
+        }
+    }
+}
+
+/// Rows returned by [`SqliteStore::query`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<SqlValue>>,
+}
+
+impl QueryResult {
+    /// Format as an aligned text table with a header row.
+    pub fn format_table(&self) -> String {
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|v| v.to_string()).collect())
+            .collect();
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].len())
+                    .chain([name.len()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let mut output = String::new();
+        let mut push_row = |row: &[String]| {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &w)| format!("{:<w$}", cell, w = w))
+                .collect();
+            output.push_str(line.join("  ").trim_end());
+            output.push('\n');
+        };
+        push_row(&self.columns);
+        push_row(&widths.iter().map(|&w| "-".repeat(w)).collect::<Vec<_>>());
+        for row in &cells {
+            push_row(row);
+        }
+        output
+    }
+}
+
+/// One snapshot's statistics for an operation, from
+/// [`SqliteStore::operation_history`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationPoint {
+    pub timestamp: SystemTime,
+    pub count: u64,
+    pub mean_us: f64,
+    pub p50_us: u64,
+    pub p95_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+}
+
+/// One snapshot's value of a counter or gauge series, from
+/// [`SqliteStore::metric_history`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricPoint {
+    pub timestamp: SystemTime,
+    /// Label body (`tier="l1"`), empty when unlabeled
+    pub labels: String,
+    pub value: f64,
+}
+
+fn from_ms(timestamp_ms: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(timestamp_ms.max(0) as u64)
+}
+
+/// Embedded SQLite sink for telemetry snapshots.
+pub struct SqliteStore {
+    conn: Connection,
+    retention: Option<Duration>,
+}
+
+impl SqliteStore {
+    /// Open (or create) a database file and ensure the schema exists.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::init(Connection::open(path).map_err(sql_error)?)
+    }
+
+    /// Store backed by a private in-memory database.
+    pub fn open_in_memory() -> io::Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(sql_error)?)
+    }
+
+    fn init(conn: Connection) -> io::Result<Self> {
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")
+            .and_then(|_| conn.execute_batch(SCHEMA))
+            .map_err(sql_error)?;
+        Ok(Self {
+            conn,
+            retention: None,
+        })
+    }
+
+    /// Drop snapshots older than `retention` on each insert (default: keep
+    /// everything).
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Append a snapshot; returns its id.
+    pub fn insert_snapshot(&mut self, snapshot: &TelemetrySnapshot) -> io::Result<i64> {
+        self.insert_snapshot_at(snapshot, now_ms())
+    }
+
+    fn insert_snapshot_at(
+        &mut self,
+        snapshot: &TelemetrySnapshot,
+        timestamp_ms: i64,
+    ) -> io::Result<i64> {
+        let snapshot = privacy::enforce_snapshot(snapshot);
+        let snapshot = snapshot.as_ref();
+        let tx = self.conn.transaction().map_err(sql_error)?;
+        tx.execute(
+            "INSERT INTO snapshots (timestamp_ms, uptime_secs) VALUES (?1, ?2)",
+            params![timestamp_ms, snapshot.uptime_secs as f64],
+        )
+        .map_err(sql_error)?;
+        let id = tx.last_insert_rowid();
+
+        {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT INTO operations VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                )
+                .map_err(sql_error)?;
+            for (name, stats) in &snapshot.operation_stats {
+                insert
+                    .execute(params![
+                        id,
+                        timestamp_ms,
+                        name,
+                        stats.count as i64,
+                        stats.total_us as i64,
+                        if stats.count == 0 {
+                            0
+                        } else {
+                            stats.min_us as i64
+                        },
+                        stats.max_us as i64,
+                        stats.avg_us(),
+                        stats.median_us() as i64,
+                        stats.p95_us() as i64,
+                        stats.p99_us() as i64,
+                    ])
+                    .map_err(sql_error)?;
+            }
+
+            let mut insert = tx
+                .prepare_cached("INSERT INTO metrics VALUES (?1, ?2, ?3, ?4, ?5, ?6)")
+                .map_err(sql_error)?;
+            let counters = snapshot
+                .counters
+                .iter()
+                .map(|(key, &v)| ("counter", key, v as f64));
+            let gauges = snapshot.gauges.iter().map(|(key, &v)| ("gauge", key, v));
+            for (kind, key, value) in counters.chain(gauges) {
+                let (name, labels) = split_labeled_key(key);
+                insert
+                    .execute(params![
+                        id,
+                        timestamp_ms,
+                        kind,
+                        name,
+                        labels.unwrap_or(""),
+                        value
+                    ])
+                    .map_err(sql_error)?;
+            }
+        }
+
+        if let Some(retention) = self.retention {
+            let cutoff = timestamp_ms - retention.as_millis() as i64;
+            tx.execute(
+                "DELETE FROM snapshots WHERE timestamp_ms < ?1",
+                params![cutoff],
+            )
+            .map_err(sql_error)?;
+        }
+        tx.commit().map_err(sql_error)?;
+        Ok(id)
+    }
+
+    /// Number of stored snapshots.
+    pub fn snapshot_count(&self) -> io::Result<u64> {
+        self.conn
+            .query_row("SELECT count(*) FROM snapshots", [], |row| row.get(0))
+            .map(|n: i64| n as u64)
+            .map_err(sql_error)
+    }
+
+    /// Run an arbitrary SQL statement and collect every row.
+    pub fn query(&self, sql: &str) -> io::Result<QueryResult> {
+        let mut stmt = self.conn.prepare(sql).map_err(sql_error)?;
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+        let width = columns.len();
+
+        let rows = stmt
+            .query_map([], |row| {
+                (0..width)
+                    .map(|i| {
+                        Ok(match row.get_ref(i)? {
+                            ValueRef::Null => SqlValue::Null,
+                            ValueRef::Integer(v) => SqlValue::Integer(v),
+                            ValueRef::Real(v) => SqlValue::Real(v),
+                            ValueRef::Text(v) => {
+                                SqlValue::Text(String::from_utf8_lossy(v).into_owned())
+                            }
+                            ValueRef::Blob(v) => SqlValue::Blob(v.to_vec()),
+                        })
+                    })
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .map_err(sql_error)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(sql_error)?;
+
+        Ok(QueryResult { columns, rows })
+    }
+
+    /// Statistics of `operation` from snapshots taken in the `last` window,
+    /// oldest first.
+    pub fn operation_history(
+        &self,
+        operation: &str,
+        last: Duration,
+    ) -> io::Result<Vec<OperationPoint>> {
+        self.operation_history_since(operation, now_ms() - last.as_millis() as i64)
+    }
+
+    fn operation_history_since(
+        &self,
+        operation: &str,
+        since_ms: i64,
+    ) -> io::Result<Vec<OperationPoint>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached(
+                "SELECT timestamp_ms, count, mean_us, p50_us, p95_us, p99_us, max_us \
+                 FROM operations WHERE operation = ?1 AND timestamp_ms >= ?2 \
+                 ORDER BY timestamp_ms",
+            )
+            .map_err(sql_error)?;
+        let points = stmt
+            .query_map(params![operation, since_ms], |row| {
+                Ok(OperationPoint {
+                    timestamp: from_ms(row.get(0)?),
+                    count: row.get::<_, i64>(1)? as u64,
+                    mean_us: row.get(2)?,
+                    p50_us: row.get::<_, i64>(3)? as u64,
+                    p95_us: row.get::<_, i64>(4)? as u64,
+                    p99_us: row.get::<_, i64>(5)? as u64,
+                    max_us: row.get::<_, i64>(6)? as u64,
+                })
+            })
+            .map_err(sql_error)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(sql_error)?;
+        Ok(points)
+    }
+
+    /// Values of the counter or gauge `name` (every label set) from
+    /// snapshots taken in the `last` window, oldest first.
+    pub fn metric_history(&self, name: &str, last: Duration) -> io::Result<Vec<MetricPoint>> {
+        self.metric_history_since(name, now_ms() - last.as_millis() as i64)
+    }
+
+    fn metric_history_since(&self, name: &str, since_ms: i64) -> io::Result<Vec<MetricPoint>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached(
+                "SELECT timestamp_ms, labels, value FROM metrics \
+                 WHERE name = ?1 AND timestamp_ms >= ?2 ORDER BY timestamp_ms, labels",
+            )
+            .map_err(sql_error)?;
+        let points = stmt
+            .query_map(params![name, since_ms], |row| {
+                Ok(MetricPoint {
+                    timestamp: from_ms(row.get(0)?),
+                    labels: row.get(1)?,
+                    value: row.get(2)?,
+                })
+            })
+            .map_err(sql_error)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(sql_error)?;
+        Ok(points)
+    }
+
+    /// Store shared telemetry every `interval` on a background thread.
+    ///
+    /// The lock is held only to take the snapshot.
+    pub fn spawn(
+        mut self,
+        interval: Duration,
+        telemetry: Arc<Mutex<Telemetry>>,
+    ) -> CollectorHandle {
+        spawn_sink("obs-sqlite-store", interval, move || {
+            let Ok(snapshot) = telemetry.lock().map(|t| t.snapshot()) else {
+                return;
+            };
+            if let Err(e) = self.insert_snapshot(&snapshot) {
+                logging::warn(&format!("sqlite store insert failed: {}", e));
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_snapshot() -> TelemetrySnapshot {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.record_operation("query", 100);
+        telemetry.record_operation("query", 300);
+        telemetry.add_to_counter_with_labels("hits_total", &[("tier", "l1")], 4);
+        telemetry.set_gauge("depth", 1.5);
+        telemetry.snapshot()
+    }
+
+    #[test]
+    fn test_insert_and_query() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.insert_snapshot(&sample_snapshot()).unwrap();
+        store.insert_snapshot(&sample_snapshot()).unwrap();
+        assert_eq!(store.snapshot_count().unwrap(), 2);
+
+        let result = store
+            .query("SELECT operation, sum(count), max(max_us) FROM operations GROUP BY operation")
+            .unwrap();
+        assert_eq!(
+            result.rows,
+            vec![vec![
+                SqlValue::Text("query".into()),
+                SqlValue::Integer(4),
+                SqlValue::Integer(300)
+            ]]
+        );
+
+        let result = store
+            .query("SELECT labels, value FROM metrics WHERE name = 'hits_total' LIMIT 1")
+            .unwrap();
+        assert_eq!(result.rows[0][0], SqlValue::Text(r#"tier="l1""#.into()));
+        assert_eq!(result.rows[0][1], SqlValue::Real(4.0));
+
+        assert!(store.query("SELECT * FROM missing").is_err());
+    }
+
+    #[test]
+    fn test_retention_prunes_old_snapshots() {
+        let mut store = SqliteStore::open_in_memory()
+            .unwrap()
+            .with_retention(Duration::from_secs(60));
+        let snapshot = sample_snapshot();
+        store.insert_snapshot_at(&snapshot, 0).unwrap();
+        store.insert_snapshot_at(&snapshot, 30_000).unwrap();
+        store.insert_snapshot_at(&snapshot, 90_000).unwrap();
+
+        assert_eq!(store.snapshot_count().unwrap(), 2);
+        let orphans = store
+            .query("SELECT count(*) FROM operations WHERE timestamp_ms = 0")
+            .unwrap();
+        assert_eq!(orphans.rows[0][0], SqlValue::Integer(0));
+    }
+
+    #[test]
+    fn test_typed_history_window() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let snapshot = sample_snapshot();
+        store.insert_snapshot_at(&snapshot, 1_000).unwrap();
+        store.insert_snapshot_at(&snapshot, 5_000).unwrap();
+
+        let points = store.operation_history_since("query", 2_000).unwrap();
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].timestamp, UNIX_EPOCH + Duration::from_secs(5));
+        assert_eq!((points[0].count, points[0].max_us), (2, 300));
+        assert_eq!(points[0].mean_us, 200.0);
+
+        let points = store.metric_history_since("hits_total", 0).unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[1].labels, r#"tier="l1""#);
+        assert_eq!(points[1].value, 4.0);
+
+        assert!(store
+            .operation_history("missing", Duration::from_secs(60))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_format_table() {
+        let result = QueryResult {
+            columns: vec!["op".into(), "p99".into()],
+            rows: vec![vec![SqlValue::Text("query".into()), SqlValue::Integer(7)]],
+        };
+        assert_eq!(result.format_table(), "op     p99\n-----  ---\nquery  7\n");
+    }
+}
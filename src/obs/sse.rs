@@ -0,0 +1,475 @@
+//! Server-Sent Events Streaming
+//!
+//! Streams metric events and telemetry snapshots as `text/event-stream`
+//! for lightweight browser dashboards (`new EventSource(url)`) that cannot
+//! use WebSockets.
+//!
+//! # Events
+//!
+//! - `event: metric`: one [`MetricEvent`] as JSON (see
+//!   [`MetricEvent::to_json`])
+//! - `event: snapshot`: a [`TelemetrySnapshot`] as JSON, sent to clients
+//!   whose filter has no kinds or includes `snapshot`
+//! - `: keepalive` comments every 15 seconds keep proxies from closing idle
+//!   streams
+//!
+//! # Integration
+//!
+//! [`SseHub::serve`] writes a stream body into any [`Write`], so it plugs
+//! into an existing HTTP server that exposes the response body as a
+//! writer (send [`SSE_HEADERS`] first). [`SseServer`] is a minimal
+//! built-in server for processes without one.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::sse::SseServer;
+//!
+//! let server = SseServer::bind("0.0.0.0:9898")?;
+//! let hub = server.hub();
+//! hub.attach(&mut stream);
+//! let _snapshots = hub.spawn_snapshots(Duration::from_secs(5), telemetry.clone());
+//! let _handle = server.spawn();
+//!
+//! // Browser: new EventSource("http://host:9898/events?kinds=gauge,snapshot")
+//! ```
+
+use crate::obs::health::{DeadlineReader, MAX_REQUEST_BYTES, REQUEST_TIMEOUT};
+use crate::obs::logging;
+use crate::obs::process::{spawn_periodic, CollectorHandle};
+use crate::obs::streaming::{EventFilter, Fanout, MetricEvent, MetricStream, SubscriptionId};
+use crate::obs::telemetry::{Telemetry, TelemetrySnapshot};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Response headers for an event stream, without the status line.
+pub const SSE_HEADERS: &[(&str, &str)] = &[
+    ("Content-Type", "text/event-stream"),
+    ("Cache-Control", "no-cache"),
+    ("Connection", "keep-alive"),
+    ("Access-Control-Allow-Origin", "*"),
+];
+
+const KEEPALIVE: Duration = Duration::from_secs(15);
+/// Longest a write may block before the client is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Format one SSE message; multi-line data becomes several `data:` lines.
+pub fn sse_message(event: &str, id: Option<u64>, data: &str) -> String {
+    let mut message = String::with_capacity(data.len() + 32);
+    if let Some(id) = id {
+        message.push_str(&format!("id: {}\n", id));
+    }
+    message.push_str(&format!("event: {}\n", event));
+    for line in data.lines() {
+        message.push_str("data: ");
+        message.push_str(line);
+        message.push('\n');
+    }
+    message.push('\n');
+    message
+}
+
+/// Cloneable publisher feeding every connected event stream.
+#[derive(Clone)]
+pub struct SseHub {
+    fanout: Arc<Fanout<Arc<str>>>,
+    next_id: Arc<AtomicU64>,
+    queue_capacity: usize,
+    keepalive: Duration,
+}
+
+impl Default for SseHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SseHub {
+    /// Hub buffering up to 256 messages per client.
+    pub fn new() -> Self {
+        Self {
+            fanout: Arc::new(Fanout::new()),
+            next_id: Arc::new(AtomicU64::new(1)),
+            queue_capacity: 256,
+            keepalive: KEEPALIVE,
+        }
+    }
+
+    /// Messages buffered per client before dropping.
+    pub fn with_queue_capacity(mut self, messages: usize) -> Self {
+        self.queue_capacity = messages.max(1);
+        self
+    }
+
+    fn message(&self, event: &str, data: &str) -> Arc<str> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        Arc::from(sse_message(event, Some(id), data))
+    }
+
+    /// Send a metric event to clients whose filter matches it.
+    pub fn publish(&self, event: &MetricEvent) {
+        self.fanout.send(
+            |filter| filter.matches(event),
+            || self.message("metric", &event.to_json()),
+        );
+    }
+
+    /// Send a snapshot to clients that accept `snapshot` events.
+    pub fn publish_snapshot(&self, snapshot: &TelemetrySnapshot) {
+        self.fanout.send(
+            |filter| filter.kinds.is_empty() || filter.kinds.iter().any(|k| k == "snapshot"),
+            || self.message("snapshot", &snapshot.to_json()),
+        );
+    }
+
+    /// Forward every event of `stream` to connected clients.
+    pub fn attach(&self, stream: &mut MetricStream) -> SubscriptionId {
+        let hub = self.clone();
+        stream.subscribe(move |event| hub.publish(event))
+    }
+
+    /// Publish a snapshot of shared telemetry every `interval`.
+    ///
+    /// The lock is held only to take the snapshot.
+    pub fn spawn_snapshots(
+        &self,
+        interval: Duration,
+        telemetry: Arc<Mutex<Telemetry>>,
+    ) -> CollectorHandle {
+        let hub = self.clone();
+        spawn_periodic("obs-sse-snapshots", interval, move || {
+            if hub.client_count() == 0 {
+                return;
+            }
+            if let Ok(snapshot) = telemetry.lock().map(|t| t.snapshot()) {
+                hub.publish_snapshot(&snapshot);
+            }
+        })
+    }
+
+    /// Connected event streams.
+    pub fn client_count(&self) -> usize {
+        self.fanout.len()
+    }
+
+    /// Messages skipped because a client's queue was full.
+    pub fn dropped_messages(&self) -> u64 {
+        self.fanout.dropped()
+    }
+
+    /// Stream messages matching `filter` into `out` until a write fails
+    /// (the client went away). Blocks the calling thread.
+    ///
+    /// Writes the stream body only; the HTTP server sends status and
+    /// [`SSE_HEADERS`].
+    pub fn serve(&self, out: &mut impl Write, filter: EventFilter) -> io::Result<()> {
+        let (id, messages) = self.fanout.register(filter, self.queue_capacity);
+        let result = (|| {
+            out.write_all(b"retry: 3000\n\n")?;
+            out.flush()?;
+            loop {
+                match messages.recv_timeout(self.keepalive) {
+                    Ok(message) => out.write_all(message.as_bytes())?,
+                    Err(RecvTimeoutError::Timeout) => out.write_all(b": keepalive\n\n")?,
+                    Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                }
+                out.flush()?;
+            }
+        })();
+        self.fanout.unregister(id);
+        result
+    }
+}
+
+/// Minimal HTTP server answering every `GET` with an event stream.
+///
+/// The query string selects events (see [`EventFilter::from_query`]).
+pub struct SseServer {
+    listener: TcpListener,
+    hub: SseHub,
+    max_connections: usize,
+}
+
+impl SseServer {
+    /// Bind the listening socket.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            hub: SseHub::new(),
+            max_connections: 64,
+        })
+    }
+
+    /// Serve streams from an existing hub.
+    pub fn with_hub(mut self, hub: SseHub) -> Self {
+        self.hub = hub;
+        self
+    }
+
+    /// Clients served at once (default 64); further connections are
+    /// closed unanswered.
+    pub fn with_max_connections(mut self, connections: usize) -> Self {
+        self.max_connections = connections.max(1);
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Publisher for this server; stays valid after [`spawn`](Self::spawn).
+    pub fn hub(&self) -> SseHub {
+        self.hub.clone()
+    }
+
+    /// Accept connections on a background thread.
+    ///
+    /// Stopping the handle stops accepting; open streams end when their
+    /// clients disconnect, or stop reading for longer than the write
+    /// timeout.
+    pub fn spawn(self) -> CollectorHandle {
+        let active = Arc::new(AtomicUsize::new(0));
+        spawn_periodic("obs-sse-accept", Duration::from_millis(50), move || {
+            while let Ok((stream, peer)) = self.listener.accept() {
+                if active.fetch_add(1, Ordering::SeqCst) >= self.max_connections {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    logging::debug(&format!("sse server full, dropping {}", peer));
+                    continue;
+                }
+                let hub = self.hub.clone();
+                let connection_active = active.clone();
+                let spawned = std::thread::Builder::new()
+                    .name("obs-sse-conn".to_string())
+                    .spawn(move || {
+                        if let Err(e) = serve_connection(stream, &hub) {
+                            logging::debug(&format!("sse client {} closed: {}", peer, e));
+                        }
+                        connection_active.fetch_sub(1, Ordering::SeqCst);
+                    });
+                if let Err(e) = spawned {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    logging::warn(&format!("failed to spawn sse thread: {}", e));
+                }
+            }
+        })
+    }
+}
+
+fn serve_connection(mut stream: TcpStream, hub: &SseHub) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    let deadline = DeadlineReader::new(stream.try_clone()?, REQUEST_TIMEOUT);
+    let mut reader = BufReader::new(deadline).take(MAX_REQUEST_BYTES);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut line = String::new();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 || line.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let Some(target) = request_line
+        .strip_prefix("GET ")
+        .and_then(|rest| rest.split_whitespace().next())
+    else {
+        stream.write_all(
+            b"HTTP/1.1 405 Method Not Allowed\r\nAllow: GET\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )?;
+        return Ok(());
+    };
+    let filter = EventFilter::from_query(target.split_once('?').map_or("", |(_, q)| q));
+
+    let mut head = String::from("HTTP/1.1 200 OK\r\n");
+    for (name, value) in SSE_HEADERS {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    let mut out = MessageWriter {
+        stream,
+        deadline: None,
+    };
+    hub.serve(&mut out, filter)
+}
+
+/// Stream writer giving each message, up to its flush, [`WRITE_TIMEOUT`]
+/// in total, so a client draining a few bytes at a time is still dropped.
+struct MessageWriter {
+    stream: TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl Write for MessageWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let deadline = *self
+            .deadline
+            .get_or_insert_with(|| Instant::now() + WRITE_TIMEOUT);
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "client stopped reading",
+            ));
+        }
+        self.stream.set_write_timeout(Some(remaining))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.deadline = None;
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_sse_message_format() {
+        assert_eq!(
+            sse_message("metric", Some(7), r#"{"a":1}"#),
+            "id: 7\nevent: metric\ndata: {\"a\":1}\n\n"
+        );
+        assert_eq!(
+            sse_message("snapshot", None, "{\n  \"x\": 1\n}\n"),
+            "event: snapshot\ndata: {\ndata:   \"x\": 1\ndata: }\n\n"
+        );
+    }
+
+    fn wait_for(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn read_until(stream: &mut TcpStream, needle: &str) -> String {
+        let mut received = Vec::new();
+        let mut buf = [0u8; 512];
+        while !String::from_utf8_lossy(&received).contains(needle) {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0, "stream closed");
+            received.extend_from_slice(&buf[..n]);
+        }
+        String::from_utf8(received).unwrap()
+    }
+
+    #[test]
+    fn test_builtin_server_streams_filtered_events() {
+        let server = SseServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let hub = server.hub();
+        let _handle = server.spawn();
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client
+            .write_all(b"GET /events?kinds=gauge HTTP/1.1\r\nAccept: text/event-stream\r\n\r\n")
+            .unwrap();
+        let head = read_until(&mut client, "retry: 3000\n\n");
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Content-Type: text/event-stream\r\n"));
+        wait_for(|| hub.client_count() == 1);
+
+        hub.publish(&MetricEvent::Timing("query".into(), 10));
+        hub.publish_snapshot(&Telemetry::default_config().snapshot());
+        hub.publish(&MetricEvent::Gauge("cpu".into(), 1.5));
+
+        let body = read_until(&mut client, "\n\n");
+        assert_eq!(
+            body,
+            "id: 1\nevent: metric\ndata: {\"type\":\"gauge\",\"name\":\"cpu\",\"value\":1.5}\n\n"
+        );
+
+        drop(client);
+        hub.publish(&MetricEvent::Gauge("cpu".into(), 2.0));
+        hub.publish(&MetricEvent::Gauge("cpu".into(), 3.0));
+        wait_for(|| hub.client_count() == 0);
+    }
+
+    #[test]
+    fn test_drops_client_that_stops_reading() {
+        let server = SseServer::bind("127.0.0.1:0")
+            .unwrap()
+            .with_max_connections(1);
+        let addr = server.local_addr().unwrap();
+        let hub = server.hub();
+        let _handle = server.spawn();
+
+        // Never reads its stream
+        let mut stalled = TcpStream::connect(addr).unwrap();
+        stalled.write_all(b"GET /events HTTP/1.1\r\n\r\n").unwrap();
+        wait_for(|| hub.client_count() == 1);
+
+        // Over the cap while the stalled client is connected
+        let mut refused = TcpStream::connect(addr).unwrap();
+        refused
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let _ = refused.write_all(b"GET /events HTTP/1.1\r\n\r\n");
+        let _ = refused.read_to_end(&mut Vec::new());
+        assert_eq!(hub.client_count(), 1);
+
+        // Fill the socket buffers until the blocked write times out
+        let name = "x".repeat(64 * 1024);
+        let deadline = Instant::now() + WRITE_TIMEOUT * 4;
+        while hub.client_count() == 1 {
+            assert!(Instant::now() < deadline, "stalled client kept");
+            hub.publish(&MetricEvent::Gauge(name.clone(), 1.0));
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        drop(stalled);
+    }
+
+    #[test]
+    fn test_serve_into_any_writer() {
+        struct Capped(Vec<u8>, usize);
+        impl Write for Capped {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.0.len() >= self.1 {
+                    return Err(io::ErrorKind::BrokenPipe.into());
+                }
+                self.0.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let hub = SseHub::new();
+        let publisher = hub.clone();
+        let writer = std::thread::spawn(move || {
+            let mut out = Capped(Vec::new(), 20);
+            let result = hub.serve(&mut out, EventFilter::default());
+            (result, String::from_utf8(out.0).unwrap())
+        });
+
+        wait_for(|| publisher.client_count() == 1);
+        publisher.publish_snapshot(&Telemetry::default_config().snapshot());
+        // Keep publishing until the capped writer fails
+        while publisher.client_count() == 1 {
+            publisher.publish(&MetricEvent::Counter("requests".into(), 1));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let (result, body) = writer.join().unwrap();
+        assert!(result.is_err());
+        assert!(body.starts_with("retry: 3000\n\nid: 1\nevent: snapshot\ndata: {"));
+    }
+}
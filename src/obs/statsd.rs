@@ -0,0 +1,457 @@
+//! StatsD / DogStatsD Export
+//!
+//! Pushes counters, gauges, and timings over UDP in StatsD line format,
+//! optionally with DogStatsD `|#tag:value` tags, for monitoring stacks that
+//! only accept StatsD.
+//!
+//! # Sources
+//!
+//! - Direct recording: [`count`](StatsdExporter::count),
+//!   [`gauge`](StatsdExporter::gauge), [`timing_us`](StatsdExporter::timing_us)
+//!   buffer lines until [`flush`](StatsdExporter::flush)
+//! - Telemetry snapshots: [`export`](StatsdExporter::export) sends counter
+//!   deltas since the previous export, current gauges, and new timing samples
+//!
+//! Labeled telemetry keys (`name{k="v"}`) become DogStatsD tags, or are
+//! folded into the metric name (`name.v`) in plain StatsD mode.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::statsd::StatsdExporter;
+//!
+//! let exporter = StatsdExporter::new("embeddenator", "127.0.0.1:8125")?
+//!     .dogstatsd()
+//!     .with_tag("service", "indexer")
+//!     .with_sample_rate(0.25);
+//!
+//! // Flush telemetry every 10 seconds
+//! let _handle = exporter.spawn(Duration::from_secs(10), telemetry.clone());
+//! ```
+
+use crate::obs::privacy;
+use crate::obs::process::{spawn_sink_collector, CollectorHandle};
+use crate::obs::telemetry::{parse_labels, split_labeled_key, Telemetry, TelemetrySnapshot};
+use std::collections::HashMap;
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Conservative payload size that fits a 1500-byte MTU with headers.
+const DEFAULT_MAX_PACKET_SIZE: usize = 1432;
+
+/// StatsD exporter over UDP.
+pub struct StatsdExporter {
+    prefix: String,
+    socket: UdpSocket,
+    dogstatsd: bool,
+    tags: Vec<(String, String)>,
+    sample_rate: f64,
+    max_packet_size: usize,
+    rng_state: u64,
+    buffer: Vec<String>,
+    /// Counter values at the previous `export`, for delta computation
+    last_counters: HashMap<String, u64>,
+    /// Sample count and total microseconds already sent per operation
+    last_timings: HashMap<String, (u64, u64)>,
+}
+
+impl StatsdExporter {
+    /// Create an exporter sending to `addr` (e.g. `"127.0.0.1:8125"`).
+    pub fn new(prefix: impl Into<String>, addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let target = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address resolved"))?;
+        let bind = if target.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(bind)?;
+        socket.connect(target)?;
+
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        Ok(Self {
+            prefix: prefix.into(),
+            socket,
+            dogstatsd: false,
+            tags: Vec::new(),
+            sample_rate: 1.0,
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            rng_state: seed | 1,
+            buffer: Vec::new(),
+            last_counters: HashMap::new(),
+            last_timings: HashMap::new(),
+        })
+    }
+
+    /// Emit DogStatsD tags (`|#key:value`) instead of plain StatsD.
+    pub fn dogstatsd(mut self) -> Self {
+        self.dogstatsd = true;
+        self
+    }
+
+    /// Add a tag sent with every metric (DogStatsD only).
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.push((key.into(), value.into()));
+        self
+    }
+
+    /// Send only this fraction of counter increments and timings (0.0 to 1.0).
+    ///
+    /// Sampled lines carry `|@rate` so the server scales them back up.
+    /// Gauges are never sampled.
+    pub fn with_sample_rate(mut self, rate: f64) -> Self {
+        self.sample_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Maximum UDP payload size; lines are packed up to this size.
+    pub fn with_max_packet_size(mut self, bytes: usize) -> Self {
+        self.max_packet_size = bytes;
+        self
+    }
+
+    /// Buffer a counter increment.
+    pub fn count(&mut self, name: &str, value: u64) {
+        if self.sampled_out() {
+            return;
+        }
+        let line = self.format_line(name, &value.to_string(), "c", true);
+        self.buffer.push(line);
+    }
+
+    /// Buffer a gauge value.
+    pub fn gauge(&mut self, name: &str, value: f64) {
+        let line = self.format_line(name, &value.to_string(), "g", false);
+        self.buffer.push(line);
+    }
+
+    /// Buffer a timing sample (sent in milliseconds).
+    pub fn timing_us(&mut self, name: &str, duration_us: u64) {
+        if self.sampled_out() {
+            return;
+        }
+        let ms = duration_us as f64 / 1000.0;
+        let line = self.format_line(name, &ms.to_string(), "ms", true);
+        self.buffer.push(line);
+    }
+
+    /// Number of buffered, unsent lines.
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Send buffered lines, packed into as few packets as fit.
+    ///
+    /// Returns the number of packets sent.
+    pub fn flush(&mut self) -> io::Result<usize> {
+        let mut packets = 0;
+        let mut packet = String::with_capacity(self.max_packet_size);
+
+        for line in self.buffer.drain(..) {
+            if !packet.is_empty() && packet.len() + 1 + line.len() > self.max_packet_size {
+                self.socket.send(packet.as_bytes())?;
+                packets += 1;
+                packet.clear();
+            }
+            if !packet.is_empty() {
+                packet.push('\n');
+            }
+            packet.push_str(&line);
+        }
+        if !packet.is_empty() {
+            self.socket.send(packet.as_bytes())?;
+            packets += 1;
+        }
+        Ok(packets)
+    }
+
+    /// Buffer a telemetry snapshot and flush.
+    ///
+    /// Counters are sent as deltas since the previous export; operation
+    /// timings send the samples recorded since then. Samples past the
+    /// bounded per-operation history are sent as one line carrying their
+    /// mean with `|@1/n`, so the server still counts all `n` of them.
+    pub fn export(&mut self, snapshot: &TelemetrySnapshot) -> io::Result<usize> {
+        let snapshot = privacy::enforce_snapshot(snapshot);
+        let snapshot = snapshot.as_ref();
+        for (name, &value) in &snapshot.counters {
+            let last = self.last_counters.insert(name.clone(), value).unwrap_or(0);
+            // A decrease means the telemetry was reset
+            let delta = if value >= last { value - last } else { value };
+            if delta > 0 {
+                self.count(name, delta);
+            }
+        }
+
+        for (name, &value) in &snapshot.gauges {
+            self.gauge(name, value);
+        }
+
+        for (name, stats) in &snapshot.operation_stats {
+            let (sent, sent_us) = self
+                .last_timings
+                .insert(name.clone(), (stats.count, stats.total_us))
+                .unwrap_or((0, 0));
+            let (sent, sent_us) = if stats.count >= sent {
+                (sent, sent_us)
+            } else {
+                (0, 0)
+            };
+            let start = (sent as usize).min(stats.histogram.len());
+            let samples = &stats.histogram[start..];
+            for &sample in samples {
+                self.timing_us(name, sample);
+            }
+
+            let unrecorded = (stats.count - sent).saturating_sub(samples.len() as u64);
+            if unrecorded > 0 {
+                let recorded_us: u64 = samples.iter().sum();
+                let unrecorded_us = stats
+                    .total_us
+                    .saturating_sub(sent_us)
+                    .saturating_sub(recorded_us);
+                let mean_ms = unrecorded_us as f64 / unrecorded as f64 / 1000.0;
+                let line =
+                    self.format_line_at(name, &mean_ms.to_string(), "ms", 1.0 / unrecorded as f64);
+                self.buffer.push(line);
+            }
+        }
+
+        self.flush()
+    }
+
+    /// Export shared telemetry every `interval` on a background thread.
+    pub fn spawn(
+        mut self,
+        interval: Duration,
+        telemetry: Arc<Mutex<Telemetry>>,
+    ) -> CollectorHandle {
+        spawn_sink_collector(
+            "obs-statsd-exporter",
+            interval,
+            telemetry,
+            move |telemetry| {
+                let snapshot = telemetry.snapshot();
+                // UDP is fire-and-forget; a missing agent must not stop the loop
+                let _ = self.export(&snapshot);
+            },
+        )
+    }
+
+    fn format_line(&self, key: &str, value: &str, kind: &str, sampled: bool) -> String {
+        let rate = if sampled { self.sample_rate } else { 1.0 };
+        self.format_line_at(key, value, kind, rate)
+    }
+
+    /// Line with an explicit `|@rate` when `rate` is below 1.
+    fn format_line_at(&self, key: &str, value: &str, kind: &str, rate: f64) -> String {
+        let (name, labels) = split_labeled_key(key);
+        let labels = labels.map(parse_labels).unwrap_or_default();
+
+        let mut line = String::with_capacity(64);
+        if !self.prefix.is_empty() {
+            line.push_str(&self.prefix);
+            line.push('.');
+        }
+        line.push_str(&sanitize(name));
+        if !self.dogstatsd {
+            for (_, value) in &labels {
+                line.push('.');
+                line.push_str(&sanitize(value));
+            }
+        }
+
+        line.push(':');
+        line.push_str(value);
+        line.push('|');
+        line.push_str(kind);
+
+        if rate < 1.0 {
+            line.push_str(&format!("|@{}", rate));
+        }
+
+        if self.dogstatsd && (!self.tags.is_empty() || !labels.is_empty()) {
+            line.push_str("|#");
+            let tags = self
+                .tags
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .chain(labels.iter().map(|(k, v)| (*k, v.as_str())));
+            for (i, (key, value)) in tags.enumerate() {
+                if i > 0 {
+                    line.push(',');
+                }
+                line.push_str(&sanitize_tag(key));
+                line.push(':');
+                line.push_str(&sanitize_tag(value));
+            }
+        }
+        line
+    }
+
+    fn sampled_out(&mut self) -> bool {
+        if self.sample_rate >= 1.0 {
+            return false;
+        }
+        // xorshift64; statistical quality is ample for sampling
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+        (x >> 11) as f64 / (1u64 << 53) as f64 >= self.sample_rate
+    }
+}
+
+/// StatsD reserves `:`, `|` and `@`; also replace whitespace.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            ':' | '|' | '@' | '#' | ',' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Tag keys and values additionally may not contain `,`.
+fn sanitize_tag(tag: &str) -> String {
+    tag.chars()
+        .map(|c| match c {
+            '|' | '@' | '#' | ',' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receiver() -> (UdpSocket, String) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let addr = socket.local_addr().unwrap().to_string();
+        (socket, addr)
+    }
+
+    fn recv(socket: &UdpSocket) -> String {
+        let mut buf = vec![0u8; 65536];
+        let n = socket.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..n]).into_owned()
+    }
+
+    #[test]
+    fn test_plain_line_format() {
+        let (socket, addr) = receiver();
+        let mut exporter = StatsdExporter::new("app", addr).unwrap();
+        exporter.count("requests", 3);
+        exporter.gauge("queue_depth", 12.5);
+        exporter.timing_us("query", 1500);
+        exporter.gauge(r#"disk_free{path="/data"}"#, 1.0);
+
+        assert_eq!(exporter.flush().unwrap(), 1);
+        assert_eq!(
+            recv(&socket),
+            "app.requests:3|c\napp.queue_depth:12.5|g\napp.query:1.5|ms\napp.disk_free./data:1|g"
+        );
+    }
+
+    #[test]
+    fn test_dogstatsd_tags_and_sample_rate() {
+        let (socket, addr) = receiver();
+        let mut exporter = StatsdExporter::new("app", addr)
+            .unwrap()
+            .dogstatsd()
+            .with_tag("env", "prod")
+            .with_sample_rate(0.5);
+
+        let line = exporter.format_line(r#"net_rx{interface="eth0"}"#, "1", "c", true);
+        assert_eq!(line, "app.net_rx:1|c|@0.5|#env:prod,interface:eth0");
+
+        exporter.gauge("g", 2.0);
+        exporter.flush().unwrap();
+        assert_eq!(recv(&socket), "app.g:2|g|#env:prod");
+    }
+
+    #[test]
+    fn test_export_sends_counter_deltas() {
+        let (socket, addr) = receiver();
+        let mut exporter = StatsdExporter::new("", addr).unwrap();
+        let mut telemetry = Telemetry::default_config();
+
+        telemetry.add_to_counter("hits", 5);
+        telemetry.record_operation("op", 2000);
+        exporter.export(&telemetry.snapshot()).unwrap();
+        let first = recv(&socket);
+        assert!(first.contains("hits:5|c"));
+        assert!(first.contains("op:2|ms"));
+
+        telemetry.add_to_counter("hits", 2);
+        exporter.export(&telemetry.snapshot()).unwrap();
+        let second = recv(&socket);
+        assert!(second.contains("hits:2|c"));
+        assert!(!second.contains("op:"));
+    }
+
+    #[test]
+    fn test_export_past_sample_history_cap() {
+        let (socket, addr) = receiver();
+        let mut exporter = StatsdExporter::new("", addr)
+            .unwrap()
+            .with_max_packet_size(60_000);
+        let mut telemetry = Telemetry::default_config();
+
+        for _ in 0..9_990 {
+            telemetry.record_operation("op", 1000);
+        }
+        let packets = exporter.export(&telemetry.snapshot()).unwrap();
+        for _ in 0..packets {
+            recv(&socket);
+        }
+
+        // 10 samples fit the history, 490 more are past the cap
+        for _ in 0..500 {
+            telemetry.record_operation("op", 3000);
+        }
+        exporter.export(&telemetry.snapshot()).unwrap();
+        let lines = recv(&socket);
+        assert_eq!(lines.matches("op:3|ms").count(), 11);
+        assert!(lines.contains(&format!("op:3|ms|@{}", 1.0 / 490.0)));
+
+        for _ in 0..4 {
+            telemetry.record_operation("op", 2000);
+        }
+        exporter.export(&telemetry.snapshot()).unwrap();
+        assert_eq!(recv(&socket), "op:2|ms|@0.25");
+    }
+
+    #[test]
+    fn test_packing_respects_max_packet_size() {
+        let (socket, addr) = receiver();
+        let mut exporter = StatsdExporter::new("app", addr)
+            .unwrap()
+            .with_max_packet_size(40);
+        for i in 0..4 {
+            exporter.count(&format!("counter_{}", i), 1);
+        }
+
+        let packets = exporter.flush().unwrap();
+        assert!(packets >= 2);
+        for _ in 0..packets {
+            assert!(recv(&socket).len() <= 40);
+        }
+        assert_eq!(exporter.pending(), 0);
+    }
+}
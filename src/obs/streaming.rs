@@ -6,10 +6,14 @@
 //! # Features
 //!
 //! - Callback-based metric updates
-//! - Threshold-based alerting
+//! - Threshold-based alerting, optionally sustained over a duration or
+//!   sample count
 //! - Metric change detection
 //! - Rate limiting for high-frequency metrics
-//! - Multiple subscriber support
+//! - Pluggable [`Clock`] for the rate limiter and alert windows, so
+//!   tests can advance a [`MockClock`](crate::obs::clock::MockClock)
+//! - Multiple subscriber support, with unsubscribe by id or RAII guard
+//! - Bounded channel receivers with drop policies
 //!
 //! # Usage
 //!
@@ -36,11 +40,26 @@
 //! // Publish metrics
 //! stream.publish_counter("requests", 42);
 //! stream.publish_gauge("cpu_usage", 75.5);
+//!
+//! // Detach a single subscriber
+//! let id = stream.subscribe(|event| println!("{:?}", event));
+//! stream.unsubscribe(id);
+//!
+//! // Or tie it to a scope
+//! {
+//!     let _subscription = stream.subscribe_scoped(|event| println!("{:?}", event));
+//! } // unsubscribed here
 //! ```
 
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::{Arc, Mutex, Weak};
 use std::time::{Duration, Instant};
 
+use crate::obs::clock::{system_clock, Clock};
+use crate::obs::telemetry::escape_json;
+
 /// Type of metric event.
 #[derive(Debug, Clone, PartialEq)]
 pub enum MetricEvent {
@@ -52,23 +71,365 @@ pub enum MetricEvent {
     Timing(String, u64),
     /// Threshold exceeded (metric, value, threshold)
     ThresholdExceeded(String, f64, f64),
+    /// Threshold alert changed state (metric, new state, value, threshold)
+    AlertTransition(String, AlertState, f64, f64),
+    /// Value deviating from its moving average (metric, value, mean, z-score)
+    Anomaly(String, f64, f64, f64),
+}
+
+impl MetricEvent {
+    /// Metric name the event refers to.
+    pub fn name(&self) -> &str {
+        match self {
+            MetricEvent::Counter(name, _)
+            | MetricEvent::Gauge(name, _)
+            | MetricEvent::Timing(name, _)
+            | MetricEvent::ThresholdExceeded(name, _, _)
+            | MetricEvent::AlertTransition(name, ..)
+            | MetricEvent::Anomaly(name, ..) => name,
+        }
+    }
+
+    /// Event kind: `counter`, `gauge`, `timing`, `threshold_exceeded`,
+    /// `alert` or `anomaly`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MetricEvent::Counter(..) => "counter",
+            MetricEvent::Gauge(..) => "gauge",
+            MetricEvent::Timing(..) => "timing",
+            MetricEvent::ThresholdExceeded(..) => "threshold_exceeded",
+            MetricEvent::AlertTransition(..) => "alert",
+            MetricEvent::Anomaly(..) => "anomaly",
+        }
+    }
+
+    /// Encode as a single-line JSON object, e.g.
+    /// `{"type":"gauge","name":"cpu","value":75.5}`.
+    pub fn to_json(&self) -> String {
+        let number = |v: f64| {
+            if v.is_finite() {
+                v.to_string()
+            } else {
+                "null".to_string()
+            }
+        };
+        let value = match self {
+            MetricEvent::Counter(_, v) => v.to_string(),
+            MetricEvent::Gauge(_, v) => number(*v),
+            MetricEvent::Timing(_, v) => format!(r#"{},"unit":"us""#, v),
+            MetricEvent::ThresholdExceeded(_, v, threshold) => {
+                format!(r#"{},"threshold":{}"#, number(*v), number(*threshold))
+            }
+            MetricEvent::AlertTransition(_, state, v, threshold) => format!(
+                r#"{},"state":"{}","threshold":{}"#,
+                number(*v),
+                state.as_str(),
+                number(*threshold)
+            ),
+            MetricEvent::Anomaly(_, v, mean, z) => format!(
+                r#"{},"mean":{},"z_score":{}"#,
+                number(*v),
+                number(*mean),
+                number(*z)
+            ),
+        };
+        format!(
+            r#"{{"type":"{}","name":"{}","value":{}}}"#,
+            self.kind(),
+            escape_json(self.name()),
+            value
+        )
+    }
+}
+
+/// Per-consumer selection of events by name prefix and kind.
+///
+/// Empty lists match everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    /// Metric name prefixes
+    pub names: Vec<String>,
+    /// Event kinds as returned by [`MetricEvent::kind`]
+    pub kinds: Vec<String>,
+}
+
+impl EventFilter {
+    /// Parse a URL query such as `names=query,cache_&kinds=gauge,timing`.
+    /// Unknown parameters are ignored.
+    pub fn from_query(query: &str) -> Self {
+        let mut filter = Self::default();
+        for pair in query.split('&') {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let values = percent_decode(value)
+                .split(',')
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            match key {
+                "names" => filter.names.extend(values),
+                "kinds" => filter.kinds.extend(values),
+                _ => {}
+            }
+        }
+        filter
+    }
+
+    pub fn matches(&self, event: &MetricEvent) -> bool {
+        (self.names.is_empty()
+            || self
+                .names
+                .iter()
+                .any(|p| event.name().starts_with(p.as_str())))
+            && (self.kinds.is_empty() || self.kinds.iter().any(|k| k == event.kind()))
+    }
+}
+
+/// Decode `%XX` escapes and `+` in a query component.
+fn percent_decode(value: &str) -> String {
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%' && i + 2 < bytes.len())
+            .then(|| Some(hex(bytes[i + 1])? << 4 | hex(bytes[i + 2])?))
+            .flatten();
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+            (None, b'+') => out.push(b' '),
+            (None, byte) => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Fan-out of rendered messages to filtered, bounded per-client queues,
+/// shared by the network transports.
+pub(crate) struct Fanout<T> {
+    clients: Mutex<Vec<FanoutClient<T>>>,
+    next_id: AtomicU64,
+    dropped: AtomicU64,
+}
+
+struct FanoutClient<T> {
+    id: u64,
+    filter: EventFilter,
+    queue: mpsc::SyncSender<T>,
+}
+
+impl<T: Clone> Fanout<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            clients: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Add a client with a queue of `capacity` messages.
+    pub(crate) fn register(
+        &self,
+        filter: EventFilter,
+        capacity: usize,
+    ) -> (u64, mpsc::Receiver<T>) {
+        let (queue, receiver) = mpsc::sync_channel(capacity.max(1));
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.push(FanoutClient { id, filter, queue });
+        }
+        (id, receiver)
+    }
+
+    pub(crate) fn unregister(&self, id: u64) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.retain(|client| client.id != id);
+        }
+    }
+
+    /// Queue a message for every client whose filter `wants` it; the
+    /// message is rendered once, on first match. Full queues drop the
+    /// message; disconnected clients are removed.
+    pub(crate) fn send(&self, wants: impl Fn(&EventFilter) -> bool, render: impl FnOnce() -> T) {
+        let Ok(mut clients) = self.clients.lock() else {
+            return;
+        };
+        let mut render = Some(render);
+        let mut message: Option<T> = None;
+        clients.retain(|client| {
+            if !wants(&client.filter) {
+                return true;
+            }
+            let message = message.get_or_insert_with(|| (render.take().unwrap())());
+            match client.queue.try_send(message.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.clients.lock().map(|c| c.len()).unwrap_or(0)
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
 }
 
 /// Metric subscriber callback.
 pub type MetricCallback = Arc<dyn Fn(&MetricEvent) + Send + Sync>;
 
+type Subscribers = Mutex<Vec<(SubscriptionId, MetricCallback)>>;
+
+/// Identifies one subscriber of a [`MetricStream`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+/// RAII guard that unsubscribes its callback when dropped.
+#[must_use = "dropping the subscription unsubscribes immediately"]
+#[derive(Debug)]
+pub struct Subscription {
+    id: SubscriptionId,
+    subscribers: Weak<Subscribers>,
+}
+
+impl Subscription {
+    pub fn id(&self) -> SubscriptionId {
+        self.id
+    }
+
+    /// Keep the callback subscribed past the guard's lifetime.
+    pub fn detach(self) -> SubscriptionId {
+        let id = self.id;
+        std::mem::forget(self);
+        id
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(subscribers) = self.subscribers.upgrade() {
+            if let Ok(mut subscribers) = subscribers.lock() {
+                subscribers.retain(|(id, _)| *id != self.id);
+            }
+        }
+    }
+}
+
 /// Real-time metric streaming system.
 pub struct MetricStream {
     /// Active subscribers
-    subscribers: Arc<Mutex<Vec<MetricCallback>>>,
+    subscribers: Arc<Subscribers>,
+    /// Next subscription id
+    next_id: AtomicU64,
     /// Threshold alerts
     thresholds: Arc<Mutex<Vec<ThresholdAlert>>>,
+    /// Alert states by (alert index, metric name)
+    alert_states: Arc<Mutex<HashMap<(usize, String), AlertTracker>>>,
     /// Rate limiter state
     rate_limiter: Arc<Mutex<RateLimiter>>,
+    /// Events dropped by full channel receivers
+    dropped: Arc<AtomicU64>,
+    /// Time source for rate limiting and alert windows
+    clock: Arc<dyn Clock>,
+}
+
+/// What a channel receiver does when its buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// Discard the new event and count it as dropped; never blocks
+    #[default]
+    DropNewest,
+    /// Block the publisher until the consumer catches up
+    Block,
+}
+
+/// Weak emit handle, see [`MetricStream::emitter`].
+pub(crate) struct Emitter {
+    subscribers: Weak<Subscribers>,
+}
+
+impl Emitter {
+    pub(crate) fn emit(&self, event: &MetricEvent) {
+        if let Some(subscribers) = self.subscribers.upgrade() {
+            emit_to(&subscribers, event);
+        }
+    }
+}
+
+/// Call every subscriber without holding the lock, so callbacks may
+/// unsubscribe or emit themselves.
+fn emit_to(subscribers: &Subscribers, event: &MetricEvent) {
+    let callbacks: Vec<MetricCallback> = subscribers
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(_, callback)| Arc::clone(callback))
+        .collect();
+    for callback in callbacks {
+        callback(event);
+    }
+}
+
+/// State of a threshold alert for one metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlertState {
+    /// Within the threshold
+    #[default]
+    Inactive,
+    /// Past the threshold, waiting for the sustain window
+    Pending,
+    /// Past the threshold for the whole sustain window
+    Firing,
+    /// Back within the threshold after firing
+    Resolved,
+}
+
+impl AlertState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertState::Inactive => "inactive",
+            AlertState::Pending => "pending",
+            AlertState::Firing => "firing",
+            AlertState::Resolved => "resolved",
+        }
+    }
+
+    /// Ordering for summarizing several alerts: firing, pending, resolved,
+    /// inactive.
+    fn severity(&self) -> u8 {
+        match self {
+            AlertState::Inactive => 0,
+            AlertState::Resolved => 1,
+            AlertState::Pending => 2,
+            AlertState::Firing => 3,
+        }
+    }
 }
 
 /// Threshold-based alert configuration.
-#[derive(Debug, Clone)]
+///
+/// Each alert tracks an [`AlertState`] per matching metric: a sample past
+/// the threshold makes it pending, staying past it for
+/// [`for_duration`](Self::for_duration) and
+/// [`for_samples`](Self::for_samples) makes it firing (immediately by
+/// default), and one sample back within the threshold resolves it.
+/// Transitions are emitted as [`MetricEvent::AlertTransition`].
+///
+/// While firing, `ThresholdExceeded` is emitted for every sample, or at
+/// most once per [`repeat_interval`](Self::repeat_interval). After
+/// resolving, the alert cannot fire again before its
+/// [`cooldown`](Self::cooldown) has passed.
+#[derive(Debug, Clone, PartialEq)]
 pub struct ThresholdAlert {
     /// Metric name pattern
     pub metric_pattern: String,
@@ -76,6 +437,167 @@ pub struct ThresholdAlert {
     pub threshold: f64,
     /// Alert when above (true) or below (false)
     pub above: bool,
+    /// Minimum time past the threshold before alerting
+    pub for_duration: Option<Duration>,
+    /// Minimum consecutive samples past the threshold before alerting
+    pub for_samples: u32,
+    /// Minimum time between `ThresholdExceeded` events while firing
+    pub repeat_interval: Option<Duration>,
+    /// Minimum time after resolving before firing again
+    pub cooldown: Option<Duration>,
+    /// Severity label for notifications and generated rules
+    pub severity: String,
+}
+
+impl ThresholdAlert {
+    /// Alert on metrics containing `metric` above or below `threshold`.
+    pub fn new(metric: impl Into<String>, threshold: f64, above: bool) -> Self {
+        Self {
+            metric_pattern: metric.into(),
+            threshold,
+            above,
+            for_duration: None,
+            for_samples: 1,
+            repeat_interval: None,
+            cooldown: None,
+            severity: "warning".to_string(),
+        }
+    }
+
+    pub fn above(metric: impl Into<String>, threshold: f64) -> Self {
+        Self::new(metric, threshold, true)
+    }
+
+    pub fn below(metric: impl Into<String>, threshold: f64) -> Self {
+        Self::new(metric, threshold, false)
+    }
+
+    /// Only alert once the metric stayed past the threshold for `duration`.
+    pub fn for_duration(mut self, duration: Duration) -> Self {
+        self.for_duration = Some(duration);
+        self
+    }
+
+    /// Only alert after `samples` consecutive samples past the threshold.
+    pub fn for_samples(mut self, samples: u32) -> Self {
+        self.for_samples = samples.max(1);
+        self
+    }
+
+    /// Re-emit `ThresholdExceeded` at most every `interval` while firing.
+    pub fn repeat_interval(mut self, interval: Duration) -> Self {
+        self.repeat_interval = Some(interval);
+        self
+    }
+
+    /// Keep the alert from firing again within `cooldown` of resolving.
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    /// Severity label, `warning` by default.
+    pub fn severity(mut self, severity: impl Into<String>) -> Self {
+        self.severity = severity.into();
+        self
+    }
+
+    /// Same condition, so state can carry over when alerts are replaced.
+    fn same_condition(&self, other: &ThresholdAlert) -> bool {
+        self.metric_pattern == other.metric_pattern
+            && self.threshold == other.threshold
+            && self.above == other.above
+    }
+
+    fn exceeded_by(&self, value: f64) -> bool {
+        if self.above {
+            value > self.threshold
+        } else {
+            value < self.threshold
+        }
+    }
+
+    fn may_fire(&self, tracker: &AlertTracker, now: Instant) -> bool {
+        tracker.samples >= self.for_samples
+            && self
+                .for_duration
+                .is_none_or(|d| now.duration_since(tracker.since) >= d)
+            && self.cooldown.is_none_or(|c| {
+                tracker
+                    .resolved_at
+                    .is_none_or(|t| now.duration_since(t) >= c)
+            })
+    }
+
+    fn notify_due(&self, tracker: &AlertTracker, now: Instant) -> bool {
+        self.repeat_interval.is_none_or(|r| {
+            tracker
+                .last_notified
+                .is_none_or(|t| now.duration_since(t) >= r)
+        })
+    }
+}
+
+/// Weak handle to a stream's alerts, see [`MetricStream::alerts_handle`].
+pub(crate) struct AlertsHandle {
+    thresholds: Weak<Mutex<Vec<ThresholdAlert>>>,
+    alert_states: Weak<Mutex<HashMap<(usize, String), AlertTracker>>>,
+}
+
+impl AlertsHandle {
+    /// Replace the alerts; returns false once the stream is dropped.
+    pub(crate) fn replace(&self, alerts: Vec<ThresholdAlert>) -> bool {
+        match (self.thresholds.upgrade(), self.alert_states.upgrade()) {
+            (Some(thresholds), Some(alert_states)) => {
+                replace_alerts(&thresholds, &alert_states, alerts);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Severity of the first alert matching `metric` at `threshold`.
+    pub(crate) fn severity(&self, metric: &str, threshold: f64) -> Option<String> {
+        let thresholds = self.thresholds.upgrade()?;
+        let thresholds = thresholds.lock().unwrap();
+        thresholds
+            .iter()
+            .find(|alert| metric.contains(&alert.metric_pattern) && alert.threshold == threshold)
+            .map(|alert| alert.severity.clone())
+    }
+}
+
+/// Swap in new alerts, keeping the state of alerts whose condition did
+/// not change so firing alerts still resolve after a reload.
+fn replace_alerts(
+    thresholds: &Mutex<Vec<ThresholdAlert>>,
+    alert_states: &Mutex<HashMap<(usize, String), AlertTracker>>,
+    alerts: Vec<ThresholdAlert>,
+) {
+    let mut thresholds = thresholds.lock().unwrap();
+    let mut trackers = alert_states.lock().unwrap();
+    let mut kept = HashMap::new();
+    for ((index, metric), tracker) in trackers.drain() {
+        let Some(old) = thresholds.get(index) else {
+            continue;
+        };
+        if let Some(new_index) = alerts.iter().position(|alert| alert.same_condition(old)) {
+            kept.insert((new_index, metric), tracker);
+        }
+    }
+    *trackers = kept;
+    *thresholds = alerts;
+}
+
+/// State of one alert for one metric.
+struct AlertTracker {
+    state: AlertState,
+    /// Start of the current breach
+    since: Instant,
+    /// Consecutive samples past the threshold
+    samples: u32,
+    last_notified: Option<Instant>,
+    resolved_at: Option<Instant>,
 }
 
 /// Rate limiter to prevent callback flooding.
@@ -94,8 +616,7 @@ impl RateLimiter {
         }
     }
 
-    fn should_emit(&mut self, key: &str) -> bool {
-        let now = Instant::now();
+    fn should_emit(&mut self, key: &str, now: Instant) -> bool {
         if let Some(last) = self.last_emit.get(key) {
             if now.duration_since(*last) < self.min_interval {
                 return false;
@@ -111,8 +632,12 @@ impl MetricStream {
     pub fn new() -> Self {
         Self {
             subscribers: Arc::new(Mutex::new(Vec::new())),
+            next_id: AtomicU64::new(0),
             thresholds: Arc::new(Mutex::new(Vec::new())),
+            alert_states: Arc::new(Mutex::new(HashMap::new())),
             rate_limiter: Arc::new(Mutex::new(RateLimiter::new(Duration::from_millis(100)))),
+            dropped: Arc::new(AtomicU64::new(0)),
+            clock: system_clock(),
         }
     }
 
@@ -120,28 +645,112 @@ impl MetricStream {
     pub fn with_rate_limit(min_interval: Duration) -> Self {
         Self {
             subscribers: Arc::new(Mutex::new(Vec::new())),
+            next_id: AtomicU64::new(0),
             thresholds: Arc::new(Mutex::new(Vec::new())),
+            alert_states: Arc::new(Mutex::new(HashMap::new())),
             rate_limiter: Arc::new(Mutex::new(RateLimiter::new(min_interval))),
+            dropped: Arc::new(AtomicU64::new(0)),
+            clock: system_clock(),
         }
     }
 
-    /// Subscribe to metric events.
-    pub fn subscribe<F>(&mut self, callback: F)
+    /// Read time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Subscribe to metric events; the callback stays registered until
+    /// [`unsubscribe`](Self::unsubscribe) or
+    /// [`clear_subscribers`](Self::clear_subscribers).
+    pub fn subscribe<F>(&mut self, callback: F) -> SubscriptionId
     where
         F: Fn(&MetricEvent) + Send + Sync + 'static,
     {
+        let id = SubscriptionId(self.next_id.fetch_add(1, Ordering::Relaxed));
         let mut subscribers = self.subscribers.lock().unwrap();
-        subscribers.push(Arc::new(callback));
+        subscribers.push((id, Arc::new(callback)));
+        id
+    }
+
+    /// Subscribe for as long as the returned guard lives.
+    pub fn subscribe_scoped<F>(&mut self, callback: F) -> Subscription
+    where
+        F: Fn(&MetricEvent) + Send + Sync + 'static,
+    {
+        Subscription {
+            id: self.subscribe(callback),
+            subscribers: Arc::downgrade(&self.subscribers),
+        }
+    }
+
+    /// Remove one subscriber; returns false if it was already removed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let before = subscribers.len();
+        subscribers.retain(|(sub, _)| *sub != id);
+        subscribers.len() != before
+    }
+
+    /// Subscribe through a bounded channel of `capacity` events.
+    ///
+    /// Consumers pull events at their own pace instead of running on the
+    /// publishing thread. With [`DropPolicy::DropNewest`] a full channel
+    /// discards events (see [`dropped_events`](Self::dropped_events));
+    /// with [`DropPolicy::Block`] publishers wait, so never publish from
+    /// the consuming thread under that policy.
+    pub fn receiver(&mut self, capacity: usize, policy: DropPolicy) -> mpsc::Receiver<MetricEvent> {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let dropped = Arc::clone(&self.dropped);
+        self.subscribe(move |event| match policy {
+            DropPolicy::DropNewest => {
+                if let Err(TrySendError::Full(_)) = sender.try_send(event.clone()) {
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            DropPolicy::Block => {
+                // A disconnected receiver just stops receiving
+                let _ = sender.send(event.clone());
+            }
+        });
+        receiver
+    }
+
+    /// Events discarded because a channel receiver was full.
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
     }
 
     /// Add threshold alert.
     pub fn add_threshold_alert(&mut self, metric: impl Into<String>, threshold: f64, above: bool) {
+        self.add_alert(ThresholdAlert::new(metric, threshold, above));
+    }
+
+    /// Add a threshold alert, e.g. one with a sustain window.
+    ///
+    /// ```rust,ignore
+    /// stream.add_alert(ThresholdAlert::above("cpu", 80.0).for_duration(Duration::from_secs(60)));
+    /// ```
+    pub fn add_alert(&mut self, alert: ThresholdAlert) {
         let mut thresholds = self.thresholds.lock().unwrap();
-        thresholds.push(ThresholdAlert {
-            metric_pattern: metric.into(),
-            threshold,
-            above,
-        });
+        thresholds.push(alert);
+    }
+
+    /// Replace all threshold alerts, e.g. after reloading a rules file.
+    ///
+    /// Alerts whose metric pattern, threshold and direction are unchanged
+    /// keep their state; the others start inactive.
+    pub fn replace_alerts(&mut self, alerts: Vec<ThresholdAlert>) {
+        replace_alerts(&self.thresholds, &self.alert_states, alerts);
+    }
+
+    /// Handle that replaces this stream's alerts without borrowing it,
+    /// for background reloaders.
+    pub(crate) fn alerts_handle(&self) -> AlertsHandle {
+        AlertsHandle {
+            thresholds: Arc::downgrade(&self.thresholds),
+            alert_states: Arc::downgrade(&self.alert_states),
+        }
     }
 
     /// Publish counter metric.
@@ -179,40 +788,123 @@ impl MetricStream {
     }
 
     /// Emit event to all subscribers.
+    ///
+    /// Callbacks run without the subscriber lock held, so they may
+    /// unsubscribe themselves.
     fn emit(&self, event: &MetricEvent) {
-        let subscribers = self.subscribers.lock().unwrap();
-        for callback in subscribers.iter() {
-            callback(event);
+        emit_to(&self.subscribers, event);
+    }
+
+    /// Handle that emits into this stream without borrowing it, for
+    /// subscribers that derive events; it stops working once the stream
+    /// is dropped.
+    pub(crate) fn emitter(&self) -> Emitter {
+        Emitter {
+            subscribers: Arc::downgrade(&self.subscribers),
         }
     }
 
     /// Check if rate limiter allows emission.
     fn should_emit(&self, key: &str) -> bool {
         let mut limiter = self.rate_limiter.lock().unwrap();
-        limiter.should_emit(key)
+        limiter.should_emit(key, self.clock.now())
     }
 
     /// Check threshold alerts for a metric.
     fn check_thresholds(&self, name: &str, value: f64) {
         let thresholds = self.thresholds.lock().unwrap();
+        let mut trackers = self.alert_states.lock().unwrap();
+        let now = self.clock.now();
+        let mut transitions = Vec::new();
+        let mut exceeded = None;
 
-        for alert in thresholds.iter() {
-            if name.contains(&alert.metric_pattern) {
-                let exceeded = if alert.above {
-                    value > alert.threshold
-                } else {
-                    value < alert.threshold
-                };
-
-                if exceeded {
-                    let event =
-                        MetricEvent::ThresholdExceeded(name.to_string(), value, alert.threshold);
-                    drop(thresholds); // Release lock before emitting
-                    self.emit(&event);
-                    break;
+        // Every matching alert advances its state; the first firing one
+        // that is due reports the sample
+        for (index, alert) in thresholds.iter().enumerate() {
+            if !name.contains(&alert.metric_pattern) {
+                continue;
+            }
+            let key = (index, name.to_string());
+            if !alert.exceeded_by(value) {
+                if let Some(tracker) = trackers.get_mut(&key) {
+                    let next = match tracker.state {
+                        AlertState::Pending => AlertState::Inactive,
+                        AlertState::Firing => {
+                            tracker.resolved_at = Some(now);
+                            AlertState::Resolved
+                        }
+                        state => state,
+                    };
+                    if next != tracker.state {
+                        tracker.state = next;
+                        transitions.push((next, alert.threshold));
+                    }
+                    tracker.samples = 0;
                 }
+                continue;
+            }
+
+            let tracker = trackers.entry(key).or_insert(AlertTracker {
+                state: AlertState::Inactive,
+                since: now,
+                samples: 0,
+                last_notified: None,
+                resolved_at: None,
+            });
+            let previous = tracker.state;
+            if !matches!(previous, AlertState::Pending | AlertState::Firing) {
+                tracker.since = now;
+                tracker.samples = 0;
+            }
+            tracker.samples = tracker.samples.saturating_add(1);
+            let next = if previous == AlertState::Firing || alert.may_fire(tracker, now) {
+                AlertState::Firing
+            } else {
+                AlertState::Pending
+            };
+            if next != previous {
+                tracker.state = next;
+                if next == AlertState::Firing {
+                    tracker.last_notified = None;
+                }
+                transitions.push((next, alert.threshold));
+            }
+            if next == AlertState::Firing && exceeded.is_none() && alert.notify_due(tracker, now) {
+                tracker.last_notified = Some(now);
+                exceeded = Some(alert.threshold);
             }
         }
+        drop(trackers);
+        drop(thresholds); // Release locks before emitting
+
+        for (state, threshold) in transitions {
+            self.emit(&MetricEvent::AlertTransition(
+                name.to_string(),
+                state,
+                value,
+                threshold,
+            ));
+        }
+        if let Some(threshold) = exceeded {
+            self.emit(&MetricEvent::ThresholdExceeded(
+                name.to_string(),
+                value,
+                threshold,
+            ));
+        }
+    }
+
+    /// Most severe alert state of `metric` across matching alerts
+    /// (firing, pending, resolved, inactive).
+    pub fn alert_state(&self, metric: &str) -> AlertState {
+        self.alert_states
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((_, name), _)| name == metric)
+            .map(|(_, tracker)| tracker.state)
+            .max_by_key(AlertState::severity)
+            .unwrap_or_default()
     }
 
     /// Get subscriber count.
@@ -220,6 +912,16 @@ impl MetricStream {
         self.subscribers.lock().unwrap().len()
     }
 
+    /// Configured threshold alerts.
+    pub fn threshold_alerts(&self) -> Vec<ThresholdAlert> {
+        self.thresholds.lock().unwrap().clone()
+    }
+
+    /// Number of metric names tracked by the rate limiter.
+    pub fn rate_limiter_entries(&self) -> usize {
+        self.rate_limiter.lock().unwrap().last_emit.len()
+    }
+
     /// Clear all subscribers.
     pub fn clear_subscribers(&mut self) {
         let mut subscribers = self.subscribers.lock().unwrap();
@@ -236,6 +938,7 @@ impl Default for MetricStream {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::obs::clock::MockClock;
     use std::sync::atomic::{AtomicU64, Ordering};
 
     #[test]
@@ -283,7 +986,8 @@ mod tests {
 
     #[test]
     fn test_threshold_alert() {
-        let mut stream = MetricStream::new();
+        let clock = Arc::new(MockClock::new());
+        let mut stream = MetricStream::new().with_clock(clock.clone());
         stream.add_threshold_alert("cpu", 80.0, true);
 
         let alerted = Arc::new(AtomicU64::new(0));
@@ -296,20 +1000,143 @@ mod tests {
         });
 
         stream.publish_gauge("cpu_usage", 50.0); // No alert
-        std::thread::sleep(Duration::from_millis(10));
         assert_eq!(alerted.load(Ordering::Relaxed), 0);
 
-        // Wait to bypass rate limiter (default 100ms)
-        std::thread::sleep(Duration::from_millis(110));
+        // Within the rate limit (default 100ms) the sample is dropped
+        clock.advance(Duration::from_millis(50));
+        stream.publish_gauge("cpu_usage", 85.0);
+        assert_eq!(alerted.load(Ordering::Relaxed), 0);
 
+        clock.advance(Duration::from_millis(60));
         stream.publish_gauge("cpu_usage", 85.0); // Alert!
-        std::thread::sleep(Duration::from_millis(10));
         assert_eq!(alerted.load(Ordering::Relaxed), 1);
     }
 
+    fn alerts_for(stream: &mut MetricStream) -> mpsc::Receiver<MetricEvent> {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        stream.subscribe(move |event| {
+            if matches!(event, MetricEvent::ThresholdExceeded(..)) {
+                let _ = sender.lock().unwrap().send(event.clone());
+            }
+        });
+        receiver
+    }
+
+    #[test]
+    fn test_sustained_threshold_samples() {
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        stream.add_alert(ThresholdAlert::above("queue", 10.0).for_samples(3));
+        let alerts = alerts_for(&mut stream);
+
+        // A flapping metric never alerts
+        for value in [20.0, 20.0, 5.0, 20.0, 20.0, 5.0] {
+            stream.publish_gauge("queue_depth", value);
+        }
+        assert!(alerts.try_recv().is_err());
+
+        for value in [20.0, 21.0, 22.0, 23.0] {
+            stream.publish_gauge("queue_depth", value);
+        }
+        let fired: Vec<_> = alerts.try_iter().collect();
+        assert_eq!(
+            fired,
+            vec![
+                MetricEvent::ThresholdExceeded("queue_depth".into(), 22.0, 10.0),
+                MetricEvent::ThresholdExceeded("queue_depth".into(), 23.0, 10.0),
+            ]
+        );
+
+        // Breaches are tracked per metric name
+        stream.publish_gauge("queue_len", 50.0);
+        assert!(alerts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_sustained_threshold_duration() {
+        let clock = Arc::new(MockClock::new());
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO).with_clock(clock.clone());
+        stream
+            .add_alert(ThresholdAlert::below("free", 0.1).for_duration(Duration::from_millis(50)));
+        let alerts = alerts_for(&mut stream);
+
+        stream.publish_gauge("disk_free", 0.05);
+        stream.publish_gauge("disk_free", 0.04);
+        assert!(alerts.try_recv().is_err());
+
+        clock.advance(Duration::from_millis(49));
+        stream.publish_gauge("disk_free", 0.035);
+        assert!(alerts.try_recv().is_err());
+
+        clock.advance(Duration::from_millis(1));
+        stream.publish_gauge("disk_free", 0.03);
+        assert!(alerts.try_recv().is_ok());
+
+        // Recovery resets the window
+        stream.publish_gauge("disk_free", 0.5);
+        stream.publish_gauge("disk_free", 0.02);
+        assert!(alerts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_alert_state_transitions() {
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        stream.add_alert(
+            ThresholdAlert::above("cpu", 80.0)
+                .for_samples(2)
+                .repeat_interval(Duration::from_secs(3600)),
+        );
+        let (sender, events) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        stream.subscribe(move |event| match event {
+            MetricEvent::AlertTransition(_, state, ..) => {
+                let _ = sender.lock().unwrap().send(state.as_str());
+            }
+            MetricEvent::ThresholdExceeded(..) => {
+                let _ = sender.lock().unwrap().send("exceeded");
+            }
+            _ => {}
+        });
+
+        for value in [50.0, 90.0, 50.0, 90.0, 91.0, 92.0, 93.0] {
+            stream.publish_gauge("cpu", value);
+        }
+        assert_eq!(stream.alert_state("cpu"), AlertState::Firing);
+        stream.publish_gauge("cpu", 40.0);
+        assert_eq!(stream.alert_state("cpu"), AlertState::Resolved);
+        assert_eq!(stream.alert_state("memory"), AlertState::Inactive);
+
+        // Notified once while firing despite further samples
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec!["pending", "inactive", "pending", "firing", "exceeded", "resolved"]
+        );
+    }
+
+    #[test]
+    fn test_alert_cooldown_after_resolve() {
+        let clock = Arc::new(MockClock::new());
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO).with_clock(clock.clone());
+        stream.add_alert(ThresholdAlert::above("cpu", 80.0).cooldown(Duration::from_millis(50)));
+        let alerts = alerts_for(&mut stream);
+
+        stream.publish_gauge("cpu", 90.0);
+        stream.publish_gauge("cpu", 10.0);
+        stream.publish_gauge("cpu", 95.0);
+        assert_eq!(stream.alert_state("cpu"), AlertState::Pending);
+        assert_eq!(alerts.try_iter().count(), 1);
+
+        clock.advance(Duration::from_millis(60));
+        stream.publish_gauge("cpu", 96.0);
+        assert_eq!(stream.alert_state("cpu"), AlertState::Firing);
+        assert_eq!(alerts.try_iter().count(), 1);
+    }
+
     #[test]
     fn test_rate_limiting() {
-        let stream = MetricStream::with_rate_limit(Duration::from_millis(50));
+        let clock = Arc::new(MockClock::new());
+        let stream =
+            MetricStream::with_rate_limit(Duration::from_millis(50)).with_clock(clock.clone());
         let count = Arc::new(AtomicU64::new(0));
         let count_clone = count.clone();
 
@@ -323,9 +1150,12 @@ mod tests {
             stream_mut.publish_counter("test", 1);
         }
 
-        std::thread::sleep(Duration::from_millis(10));
         // Should only receive 1 due to rate limiting
-        assert!(count.load(Ordering::Relaxed) < 10);
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+
+        clock.advance(Duration::from_millis(50));
+        stream_mut.publish_counter("test", 1);
+        assert_eq!(count.load(Ordering::Relaxed), 2);
     }
 
     #[test]
@@ -360,4 +1190,121 @@ mod tests {
         let recorded = events.lock().unwrap();
         assert_eq!(recorded.len(), 2);
     }
+
+    #[test]
+    fn test_receiver_drops_when_full() {
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        let receiver = stream.receiver(2, DropPolicy::DropNewest);
+
+        for i in 0..5 {
+            stream.publish_counter("requests", i);
+        }
+        assert_eq!(stream.dropped_events(), 3);
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                MetricEvent::Counter("requests".to_string(), 0),
+                MetricEvent::Counter("requests".to_string(), 1),
+            ]
+        );
+
+        // Space frees up once the consumer catches up
+        stream.publish_gauge("cpu", 1.0);
+        assert_eq!(
+            receiver.try_recv(),
+            Ok(MetricEvent::Gauge("cpu".to_string(), 1.0))
+        );
+
+        drop(receiver);
+        stream.publish_counter("requests", 9);
+        assert_eq!(stream.dropped_events(), 3);
+    }
+
+    #[test]
+    fn test_blocking_receiver_applies_backpressure() {
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        let receiver = stream.receiver(1, DropPolicy::Block);
+
+        let consumer = std::thread::spawn(move || receiver.iter().take(100).count());
+        for i in 0..100 {
+            stream.publish_timing("query", i);
+        }
+
+        assert_eq!(consumer.join().unwrap(), 100);
+        assert_eq!(stream.dropped_events(), 0);
+    }
+
+    #[test]
+    fn test_unsubscribe_by_id() {
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        let count = Arc::new(AtomicU64::new(0));
+        let count_clone = count.clone();
+
+        let id = stream.subscribe(move |_| {
+            count_clone.fetch_add(1, Ordering::Relaxed);
+        });
+        stream.subscribe(|_| {});
+        stream.publish_counter("requests", 1);
+
+        assert!(stream.unsubscribe(id));
+        assert!(!stream.unsubscribe(id));
+        assert_eq!(stream.subscriber_count(), 1);
+
+        stream.publish_counter("requests", 2);
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_scoped_subscription_unsubscribes_on_drop() {
+        let mut stream = MetricStream::new();
+        let subscription = stream.subscribe_scoped(|_| {});
+        assert_eq!(stream.subscriber_count(), 1);
+        drop(subscription);
+        assert_eq!(stream.subscriber_count(), 0);
+
+        let id = stream.subscribe_scoped(|_| {}).detach();
+        assert_eq!(stream.subscriber_count(), 1);
+        assert!(stream.unsubscribe(id));
+
+        // Outliving the stream is harmless
+        let subscription = stream.subscribe_scoped(|_| {});
+        drop(stream);
+        drop(subscription);
+    }
+
+    #[test]
+    fn test_event_json() {
+        assert_eq!(
+            MetricEvent::Gauge("cpu".into(), 75.5).to_json(),
+            r#"{"type":"gauge","name":"cpu","value":75.5}"#
+        );
+        assert_eq!(
+            MetricEvent::Timing("query".into(), 120).to_json(),
+            r#"{"type":"timing","name":"query","value":120,"unit":"us"}"#
+        );
+        assert_eq!(
+            MetricEvent::ThresholdExceeded("a\"b".into(), f64::NAN, 1.0).to_json(),
+            r#"{"type":"threshold_exceeded","name":"a\"b","value":null,"threshold":1}"#
+        );
+        assert_eq!(
+            MetricEvent::Anomaly("query".into(), 900.0, 120.5, 6.25).to_json(),
+            r#"{"type":"anomaly","name":"query","value":900,"mean":120.5,"z_score":6.25}"#
+        );
+        assert_eq!(
+            MetricEvent::AlertTransition("cpu".into(), AlertState::Firing, 95.0, 80.0).to_json(),
+            r#"{"type":"alert","name":"cpu","value":95,"state":"firing","threshold":80}"#
+        );
+    }
+
+    #[test]
+    fn test_event_filter() {
+        let filter = EventFilter::from_query("names=query,cache%5F&kinds=timing&x=1");
+        assert_eq!(filter.names, vec!["query", "cache_"]);
+        assert!(filter.matches(&MetricEvent::Timing("query_embed".into(), 1)));
+        assert!(filter.matches(&MetricEvent::Timing("cache_hit".into(), 1)));
+        assert!(!filter.matches(&MetricEvent::Gauge("query".into(), 1.0)));
+        assert!(!filter.matches(&MetricEvent::Timing("ingest".into(), 1)));
+        assert!(EventFilter::from_query("").matches(&MetricEvent::Counter("x".into(), 1)));
+        assert_eq!(percent_decode("a+b%2c%zz%4"), "a b,%zz%4");
+    }
 }
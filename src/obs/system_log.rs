@@ -0,0 +1,490 @@
+//! journald and syslog Backends
+//!
+//! Sends log records to the local system logger instead of stderr, for
+//! systemd-managed and syslog-based deployments. Select a backend with
+//! `EMBEDDENATOR_LOG_OUTPUT` (`stderr`, `journald` or `syslog`) before
+//! [`logging::init`](crate::obs::logging::init), or with
+//! [`logging::set_output`](crate::obs::logging::set_output).
+//!
+//! - [`Journald`]: the journal's native protocol on
+//!   `/run/systemd/journal/socket`. Fields become journal fields, upper
+//!   cased with other characters replaced by `_` (`user_id` is
+//!   `USER_ID`), next to `MESSAGE`, `PRIORITY` and `SYSLOG_IDENTIFIER`.
+//! - [`Syslog`]: RFC 5424 datagrams on `/dev/log` (or `/var/run/syslog`
+//!   on macOS), with fields as structured data
+//!   `[fields@32473 user_id="42"]`.
+//!
+//! Levels map to syslog severities: error 3, warn 4, info 6, debug and
+//! trace 7. Both backends need a Unix domain socket; elsewhere
+//! connecting fails with `ErrorKind::Unsupported`.
+//!
+//! With the `logging` feature, [`SystemLogLayer`] forwards `tracing`
+//! events from other crates too, with their fields.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::logging::{self, LogOutput};
+//!
+//! // EMBEDDENATOR_LOG_OUTPUT=journald, or explicitly:
+//! logging::set_output(LogOutput::Journald)?;
+//! logging::init();
+//!
+//! record_event(EventLevel::Warn, "slow query", &[("query_id", "q-17")]);
+//! // journalctl -o verbose: PRIORITY=4 MESSAGE=slow query QUERY_ID=q-17
+//! ```
+
+use crate::obs::tracing::EventLevel;
+use std::io;
+use std::path::Path;
+
+/// Socket of the journal's native protocol.
+pub const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Local syslog sockets, tried in order.
+pub const SYSLOG_SOCKETS: [&str; 3] = ["/dev/log", "/var/run/syslog", "/var/run/log"];
+
+/// Syslog severity of `level`.
+pub fn syslog_priority(level: EventLevel) -> u8 {
+    match level {
+        EventLevel::Error => 3,
+        EventLevel::Warn => 4,
+        EventLevel::Info => 6,
+        EventLevel::Debug | EventLevel::Trace => 7,
+    }
+}
+
+/// Name of the running program, for identifiers.
+fn program_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|path| path.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "embeddenator".to_string())
+}
+
+/// Datagram connection to a local logging socket.
+struct Socket {
+    #[cfg(unix)]
+    inner: std::os::unix::net::UnixDatagram,
+}
+
+impl Socket {
+    #[cfg(unix)]
+    fn connect(path: &Path) -> io::Result<Self> {
+        let inner = std::os::unix::net::UnixDatagram::unbound()?;
+        inner.connect(path)?;
+        Ok(Self { inner })
+    }
+
+    #[cfg(not(unix))]
+    fn connect(path: &Path) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{}: Unix domain sockets are unavailable", path.display()),
+        ))
+    }
+
+    #[cfg(unix)]
+    fn send(&self, datagram: &[u8]) -> io::Result<()> {
+        self.inner.send(datagram).map(|_| ())
+    }
+
+    #[cfg(not(unix))]
+    fn send(&self, _datagram: &[u8]) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// journald native protocol sender.
+pub struct Journald {
+    socket: Socket,
+    identifier: String,
+}
+
+impl Journald {
+    /// Connect to the system journal.
+    pub fn connect() -> io::Result<Self> {
+        Self::connect_to(JOURNAL_SOCKET)
+    }
+
+    /// Connect to a journal socket at `path`.
+    pub fn connect_to(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            socket: Socket::connect(path.as_ref())?,
+            identifier: program_name(),
+        })
+    }
+
+    /// `SYSLOG_IDENTIFIER` of sent records; the program name by default.
+    pub fn with_identifier(mut self, identifier: impl Into<String>) -> Self {
+        self.identifier = identifier.into();
+        self
+    }
+
+    /// Send one record. Records larger than the socket's datagram limit
+    /// (usually a few hundred KiB) fail.
+    pub fn send(
+        &self,
+        level: EventLevel,
+        message: &str,
+        fields: &[(&str, &str)],
+    ) -> io::Result<()> {
+        self.socket
+            .send(&encode_journal(&self.identifier, level, message, fields))
+    }
+}
+
+/// Journal field name for `key`: upper case, other characters as `_`,
+/// no leading `_` or digit (those are reserved or invalid).
+fn journal_field_name(key: &str) -> String {
+    let name: String = key
+        .chars()
+        .map(|c| match c {
+            'a'..='z' => c.to_ascii_uppercase(),
+            'A'..='Z' | '0'..='9' => c,
+            _ => '_',
+        })
+        .skip_while(|c| *c == '_' || c.is_ascii_digit())
+        .take(64)
+        .collect();
+    if name.is_empty() {
+        "FIELD".to_string()
+    } else {
+        name
+    }
+}
+
+/// Native protocol datagram: `NAME=value\n` per field, or, for values
+/// containing a newline, `NAME\n`, the value's length as little-endian
+/// u64, the value and `\n`.
+pub(crate) fn encode_journal(
+    identifier: &str,
+    level: EventLevel,
+    message: &str,
+    fields: &[(&str, &str)],
+) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(64 + message.len());
+    let mut push = |name: &str, value: &str| {
+        datagram.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            datagram.push(b'\n');
+            datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            datagram.push(b'=');
+        }
+        datagram.extend_from_slice(value.as_bytes());
+        datagram.push(b'\n');
+    };
+    push("MESSAGE", message);
+    push("PRIORITY", &syslog_priority(level).to_string());
+    push("SYSLOG_IDENTIFIER", identifier);
+    for (key, value) in fields {
+        let name = journal_field_name(key);
+        // Protocol fields come from the record itself
+        if matches!(name.as_str(), "MESSAGE" | "PRIORITY" | "SYSLOG_IDENTIFIER") {
+            continue;
+        }
+        push(&name, value);
+    }
+    datagram
+}
+
+/// RFC 5424 syslog sender.
+pub struct Syslog {
+    socket: Socket,
+    app_name: String,
+    facility: u8,
+}
+
+impl Syslog {
+    /// Facility `user`.
+    pub const USER: u8 = 1;
+    /// Facility `daemon`.
+    pub const DAEMON: u8 = 3;
+
+    /// Connect to the first available local syslog socket, logging as
+    /// facility `user`.
+    pub fn connect() -> io::Result<Self> {
+        let mut last = io::Error::from(io::ErrorKind::NotFound);
+        for path in SYSLOG_SOCKETS {
+            match Self::connect_to(path) {
+                Ok(syslog) => return Ok(syslog),
+                Err(e) => last = e,
+            }
+        }
+        Err(last)
+    }
+
+    /// Connect to a syslog socket at `path`.
+    pub fn connect_to(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            socket: Socket::connect(path.as_ref())?,
+            app_name: program_name(),
+            facility: Self::USER,
+        })
+    }
+
+    /// APP-NAME of sent records; the program name by default.
+    pub fn with_app_name(mut self, app_name: impl Into<String>) -> Self {
+        self.app_name = app_name.into();
+        self
+    }
+
+    /// Facility code (0 to 23), e.g. [`Syslog::DAEMON`].
+    pub fn with_facility(mut self, facility: u8) -> Self {
+        self.facility = facility.min(23);
+        self
+    }
+
+    /// Send one record.
+    pub fn send(
+        &self,
+        level: EventLevel,
+        message: &str,
+        fields: &[(&str, &str)],
+    ) -> io::Result<()> {
+        let timestamp = crate::obs::telemetry::rfc3339(std::time::SystemTime::now());
+        self.socket.send(
+            encode_syslog(
+                self.facility,
+                &self.app_name,
+                &timestamp,
+                level,
+                message,
+                fields,
+            )
+            .as_bytes(),
+        )
+    }
+}
+
+/// Printable ASCII without spaces, at most `max` characters; `-` if empty.
+fn syslog_token(value: &str, max: usize, excluded: &[char]) -> String {
+    let token: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic() && !excluded.contains(c))
+        .take(max)
+        .collect();
+    if token.is_empty() {
+        "-".to_string()
+    } else {
+        token
+    }
+}
+
+/// `<PRI>1 TIMESTAMP - APP-NAME PROCID - [fields@32473 k="v"] MSG`; the
+/// host name is left to the local daemon.
+pub(crate) fn encode_syslog(
+    facility: u8,
+    app_name: &str,
+    timestamp: &str,
+    level: EventLevel,
+    message: &str,
+    fields: &[(&str, &str)],
+) -> String {
+    let structured = if fields.is_empty() {
+        "-".to_string()
+    } else {
+        let mut sd = "[fields@32473".to_string();
+        for (key, value) in fields {
+            let escaped = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace(']', "\\]");
+            sd.push_str(&format!(
+                " {}=\"{}\"",
+                syslog_token(key, 32, &['=', ']', '"']),
+                escaped
+            ));
+        }
+        sd.push(']');
+        sd
+    };
+    format!(
+        "<{}>1 {} - {} {} - {} {}",
+        u16::from(facility) * 8 + u16::from(syslog_priority(level)),
+        timestamp,
+        syslog_token(app_name, 48, &[]),
+        std::process::id(),
+        structured,
+        message
+    )
+}
+
+/// Connected system logger.
+pub enum SystemLog {
+    Journald(Journald),
+    Syslog(Syslog),
+}
+
+impl SystemLog {
+    /// Send one record.
+    pub fn send(
+        &self,
+        level: EventLevel,
+        message: &str,
+        fields: &[(&str, &str)],
+    ) -> io::Result<()> {
+        match self {
+            SystemLog::Journald(journald) => journald.send(level, message, fields),
+            SystemLog::Syslog(syslog) => syslog.send(level, message, fields),
+        }
+    }
+}
+
+/// Modules whose `tracing` events were already sent by
+/// [`logging::forward`](crate::obs::logging::forward).
+#[cfg(feature = "logging")]
+const FORWARDED_TARGETS: [&str; 2] = [
+    "embeddenator_obs::obs::logging",
+    "embeddenator_obs::obs::tracing",
+];
+
+/// `tracing_subscriber` layer forwarding events to the system logger
+/// selected with [`logging::set_output`](crate::obs::logging::set_output).
+///
+/// Events from this crate's own logging functions are skipped: they
+/// reach the system logger directly, with their fields intact.
+#[cfg(feature = "logging")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemLogLayer {
+    _private: (),
+}
+
+#[cfg(feature = "logging")]
+impl SystemLogLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(feature = "logging")]
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SystemLogLayer {
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let target = event.metadata().target();
+        if FORWARDED_TARGETS.contains(&target) {
+            return;
+        }
+        let level = match *event.metadata().level() {
+            tracing::Level::ERROR => EventLevel::Error,
+            tracing::Level::WARN => EventLevel::Warn,
+            tracing::Level::INFO => EventLevel::Info,
+            tracing::Level::DEBUG => EventLevel::Debug,
+            tracing::Level::TRACE => EventLevel::Trace,
+        };
+        let mut visitor = EventFields::default();
+        event.record(&mut visitor);
+        let fields: Vec<(&str, &str)> = visitor
+            .fields
+            .iter()
+            .map(|(k, v)| (*k, v.as_str()))
+            .collect();
+        let (message, redacted) = crate::obs::redaction::redact_record(&visitor.message, &fields);
+        let mut fields: Vec<(&str, &str)> =
+            redacted.iter().map(|(k, v)| (*k, v.as_ref())).collect();
+        fields.push(("target", target));
+        crate::obs::logging::forward(level, &message, &fields);
+    }
+}
+
+/// Message and remaining fields of one event.
+#[cfg(feature = "logging")]
+#[derive(Default)]
+struct EventFields {
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+#[cfg(feature = "logging")]
+impl tracing::field::Visit for EventFields {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            name => self.fields.push((name, value.to_string())),
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            name => self.fields.push((name, format!("{:?}", value))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_encoding() {
+        let datagram = encode_journal(
+            "indexer",
+            EventLevel::Warn,
+            "slow query",
+            &[
+                ("query-id", "q-17"),
+                ("_pid", "1"),
+                ("sql", "SELECT 1\nFROM t"),
+            ],
+        );
+        let mut expected = b"MESSAGE=slow query\nPRIORITY=4\nSYSLOG_IDENTIFIER=indexer\n\
+QUERY_ID=q-17\nPID=1\nSQL\n"
+            .to_vec();
+        expected.extend_from_slice(&15u64.to_le_bytes());
+        expected.extend_from_slice(b"SELECT 1\nFROM t\n");
+        assert_eq!(datagram, expected);
+    }
+
+    #[test]
+    fn test_syslog_encoding() {
+        let line = encode_syslog(
+            Syslog::DAEMON,
+            "index er",
+            "2026-10-16T08:30:00.000Z",
+            EventLevel::Error,
+            "disk full",
+            &[("path", "/var/\"data\"]")],
+        );
+        assert_eq!(
+            line,
+            format!(
+                "<27>1 2026-10-16T08:30:00.000Z - indexer {} - \
+[fields@32473 path=\"/var/\\\"data\\\"\\]\"] disk full",
+                std::process::id()
+            )
+        );
+        assert!(
+            encode_syslog(1, "app", "-", EventLevel::Info, "hi", &[]).starts_with("<14>1 - - app ")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_journald_sends_datagram() {
+        use std::os::unix::net::UnixDatagram;
+
+        let path = std::env::temp_dir().join(format!(
+            "embeddenator_obs_journal_{}.sock",
+            std::process::id()
+        ));
+        std::fs::remove_file(&path).ok();
+        let server = UnixDatagram::bind(&path).unwrap();
+
+        let journald = Journald::connect_to(&path).unwrap().with_identifier("test");
+        journald
+            .send(EventLevel::Info, "started", &[("port", "8080")])
+            .unwrap();
+        let mut buf = [0u8; 256];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(
+            &buf[..len],
+            b"MESSAGE=started\nPRIORITY=6\nSYSLOG_IDENTIFIER=test\nPORT=8080\n"
+        );
+        std::fs::remove_file(&path).ok();
+    }
+}
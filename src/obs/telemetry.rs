@@ -28,9 +28,20 @@
 //! println!("{}", snapshot.to_json());
 //! ```
 
+use crate::clock::{system_clock, Clock};
+use crate::error_tracking::{ErrorGroup, TOP_ERRORS};
+use crate::hires_timing::HiResMetricsSnapshot;
+use crate::history::HistoryEntry;
 use crate::metrics::MetricsSnapshot;
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use crate::prometheus::collides_with_builtin;
+use crate::quality::QualitySnapshot;
+use crate::registry::{MetricDescriptor, MetricKind, MetricRegistry};
+use crate::tracing::{record_event, EventLevel};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// Telemetry aggregation configuration.
 #[derive(Debug, Clone)]
@@ -43,6 +54,9 @@ pub struct TelemetryConfig {
     pub snapshot_interval: Duration,
     /// Maximum history to retain
     pub max_history_entries: usize,
+    /// Maximum distinct label sets per counter or gauge name; further
+    /// label sets are folded into `name{overflow="true"}`
+    pub max_label_sets_per_metric: usize,
 }
 
 impl Default for TelemetryConfig {
@@ -52,10 +66,14 @@ impl Default for TelemetryConfig {
             sample_rate: 1.0,
             snapshot_interval: Duration::from_secs(60),
             max_history_entries: 100,
+            max_label_sets_per_metric: 1000,
         }
     }
 }
 
+/// Self-metric counting label sets folded into overflow series.
+pub const CARDINALITY_LIMITED_METRIC: &str = "cardinality_limited_total";
+
 /// Main telemetry collector.
 pub struct Telemetry {
     config: TelemetryConfig,
@@ -64,26 +82,67 @@ pub struct Telemetry {
     counters: HashMap<String, u64>,
     gauges: HashMap<String, f64>,
     last_snapshot: Instant,
+    registry: Arc<MetricRegistry>,
+    /// Distinct label sets admitted per metric name
+    label_sets: HashMap<String, usize>,
+    /// Per-name overrides of `max_label_sets_per_metric`
+    cardinality_limits: HashMap<String, usize>,
+    /// Names colliding with built-in metrics already warned about
+    builtin_collisions: HashSet<String>,
+    /// Time source for uptime, snapshot intervals and exemplars
+    clock: Arc<dyn Clock>,
 }
 
 impl Telemetry {
     /// Create new telemetry collector.
     pub fn new(config: TelemetryConfig) -> Self {
+        let clock = system_clock();
+        let now = clock.now();
         Self {
             config,
-            start_time: Instant::now(),
+            start_time: now,
             operation_timings: HashMap::new(),
             counters: HashMap::new(),
             gauges: HashMap::new(),
-            last_snapshot: Instant::now(),
+            last_snapshot: now,
+            registry: Arc::new(MetricRegistry::new()),
+            label_sets: HashMap::new(),
+            cardinality_limits: HashMap::new(),
+            builtin_collisions: HashSet::new(),
+            clock,
         }
     }
 
+    /// Read time from `clock` instead of the system clock; uptime
+    /// restarts from the clock's current time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
+        self.start_time = now;
+        self.last_snapshot = now;
+        self.clock = clock;
+        self
+    }
+
     /// Create with default configuration.
     pub fn default_config() -> Self {
         Self::new(TelemetryConfig::default())
     }
 
+    /// Current configuration.
+    pub fn config(&self) -> &TelemetryConfig {
+        &self.config
+    }
+
+    /// Turn collection on or off at runtime.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.config.enabled = enabled;
+    }
+
+    /// Change the sample rate at runtime (clamped to 0.0 to 1.0).
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.config.sample_rate = sample_rate.clamp(0.0, 1.0);
+    }
+
     /// Record operation timing (microseconds).
     pub fn record_operation(&mut self, name: &str, duration_us: u64) {
         if !self.config.enabled {
@@ -98,13 +157,37 @@ impl Telemetry {
         stats.record(duration_us);
     }
 
+    /// Record operation timing with an exemplar linking it to a trace.
+    ///
+    /// The most recent exemplar per operation is kept and exported in
+    /// OpenMetrics mode.
+    pub fn record_operation_with_exemplar(&mut self, name: &str, duration_us: u64, trace_id: &str) {
+        if !self.config.enabled {
+            return;
+        }
+
+        self.record_operation(name, duration_us);
+        if let Some(stats) = self.operation_timings.get_mut(name) {
+            stats.exemplar = Some(Exemplar {
+                trace_id: trace_id.to_string(),
+                value_us: duration_us,
+                timestamp_secs: self
+                    .clock
+                    .system_time()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs_f64())
+                    .unwrap_or(0.0),
+            });
+        }
+    }
+
     /// Increment a counter.
     pub fn increment_counter(&mut self, name: &str) {
         if !self.config.enabled {
             return;
         }
 
-        *self.counters.entry(name.to_string()).or_insert(0) += 1;
+        self.add_to_counter(name, 1);
     }
 
     /// Add to a counter.
@@ -113,7 +196,12 @@ impl Telemetry {
             return;
         }
 
-        *self.counters.entry(name.to_string()).or_insert(0) += value;
+        if let Some(value_ref) = self.counters.get_mut(name) {
+            *value_ref += value;
+            return;
+        }
+        let key = self.admit_key(name);
+        *self.counters.entry(key).or_insert(0) += value;
     }
 
     /// Set gauge value.
@@ -122,13 +210,72 @@ impl Telemetry {
             return;
         }
 
-        self.gauges.insert(name.to_string(), value);
+        if let Some(value_ref) = self.gauges.get_mut(name) {
+            *value_ref = value;
+            return;
+        }
+        let key = self.admit_key(name);
+        self.gauges.insert(key, value);
+    }
+
+    /// Limit distinct label sets for one metric name, overriding
+    /// [`TelemetryConfig::max_label_sets_per_metric`].
+    pub fn limit_cardinality(&mut self, name: &str, max_label_sets: usize) {
+        self.cardinality_limits
+            .insert(name.to_string(), max_label_sets);
+    }
+
+    /// Key under which a new series is stored.
+    ///
+    /// Label sets beyond the metric's limit map to the overflow series and
+    /// count in `cardinality_limited_total{metric}`, so unbounded label
+    /// values (user IDs, paths) cannot grow memory or exporter output
+    /// without bound. Names colliding with built-in metrics are logged
+    /// once as a warning.
+    fn admit_key(&mut self, key: &str) -> String {
+        let (name, labels) = split_labeled_key(key);
+        // Exporters resolve the collision; warn where the name is introduced
+        if collides_with_builtin(name) && self.builtin_collisions.insert(name.to_string()) {
+            record_event(
+                EventLevel::Warn,
+                "telemetry metric collides with built-in metric",
+                &[("metric", name)],
+            );
+        }
+        if labels.is_none() {
+            return key.to_string();
+        }
+        let limit = self
+            .cardinality_limits
+            .get(name)
+            .copied()
+            .unwrap_or(self.config.max_label_sets_per_metric);
+        let admitted = self.label_sets.entry(name.to_string()).or_insert(0);
+        if *admitted < limit {
+            *admitted += 1;
+            return key.to_string();
+        }
+
+        let limited = labeled_key(CARDINALITY_LIMITED_METRIC, &[("metric", name)]);
+        *self.counters.entry(limited).or_insert(0) += 1;
+        labeled_key(name, &[("overflow", "true")])
+    }
+
+    /// Set a gauge value for one label set (see [`labeled_key`]).
+    pub fn set_gauge_with_labels(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.set_gauge(&labeled_key(name, labels), value);
+    }
+
+    /// Add to a counter for one label set (see [`labeled_key`]).
+    pub fn add_to_counter_with_labels(&mut self, name: &str, labels: &[(&str, &str)], value: u64) {
+        self.add_to_counter(&labeled_key(name, labels), value);
     }
 
     /// Get current snapshot.
     pub fn snapshot(&self) -> TelemetrySnapshot {
-        let uptime = self.start_time.elapsed();
-        let since_last = self.last_snapshot.elapsed();
+        let now = self.clock.now();
+        let uptime = now.saturating_duration_since(self.start_time);
+        let since_last = now.saturating_duration_since(self.last_snapshot);
 
         TelemetrySnapshot {
             timestamp_secs: uptime.as_secs(),
@@ -137,24 +284,207 @@ impl Telemetry {
             operation_stats: self.operation_timings.clone(),
             counters: self.counters.clone(),
             gauges: self.gauges.clone(),
-            metrics: crate::metrics::metrics().snapshot(),
+            metrics: crate::handshake::shared_metrics(),
+            quality: crate::quality::quality().snapshot(),
+            hires: crate::hires_timing::hires_registry().snapshot_all(),
+            errors: crate::error_tracking::error_tracker().top(TOP_ERRORS),
+            registry: Arc::clone(&self.registry),
         }
     }
 
+    /// Snapshots persisted by a
+    /// [`HistoryFile`](crate::history::HistoryFile), oldest first. A record
+    /// torn by a crash ends the history.
+    pub fn load_history(path: impl AsRef<Path>) -> io::Result<Vec<HistoryEntry>> {
+        crate::history::load(path.as_ref())
+    }
+
+    /// Declare a metric's type, unit and help text for exporters.
+    pub fn describe(&mut self, descriptor: MetricDescriptor) {
+        Arc::make_mut(&mut self.registry).register(descriptor);
+    }
+
+    /// Metric metadata declared so far.
+    pub fn registry(&self) -> &MetricRegistry {
+        &self.registry
+    }
+
+    /// JSON catalog of every metric this process can emit; see
+    /// [`schema_registry`](Self::schema_registry).
+    pub fn schema_dump(&self) -> String {
+        self.schema_registry().schema_dump()
+    }
+
+    /// Registry of every metric this process can emit: built-in collector
+    /// metrics, declared descriptors, and keys recorded so far.
+    pub fn schema_registry(&self) -> MetricRegistry {
+        let mut catalog = MetricRegistry::builtin();
+        for descriptor in self.registry.iter() {
+            catalog.register(descriptor.clone());
+        }
+        let keys = self
+            .counters
+            .keys()
+            .map(|key| (key, MetricKind::Counter))
+            .chain(self.gauges.keys().map(|key| (key, MetricKind::Gauge)))
+            .chain(
+                self.operation_timings
+                    .keys()
+                    .map(|key| (key, MetricKind::Histogram)),
+            );
+        for (key, kind) in keys {
+            let (name, body) = split_labeled_key(key);
+            let labels: Vec<&str> = body
+                .map(|body| parse_labels(body).into_iter().map(|(k, _)| k).collect())
+                .unwrap_or_default();
+            catalog.observe(name, kind, &labels);
+        }
+        catalog
+    }
+
     /// Reset all collected data (useful for testing or periodic resets).
     pub fn reset(&mut self) {
         self.operation_timings.clear();
         self.counters.clear();
         self.gauges.clear();
-        self.last_snapshot = Instant::now();
+        self.label_sets.clear();
+        self.last_snapshot = self.clock.now();
+    }
+
+    /// Number of distinct operation, counter and gauge keys held.
+    pub fn series_count(&self) -> usize {
+        self.operation_timings.len() + self.counters.len() + self.gauges.len()
     }
 
     /// Get uptime in seconds.
     pub fn uptime_secs(&self) -> u64 {
-        self.start_time.elapsed().as_secs()
+        self.clock
+            .now()
+            .saturating_duration_since(self.start_time)
+            .as_secs()
     }
 }
 
+/// Build a counter/gauge key carrying labels: `name{key="value",...}`.
+///
+/// Labeled keys are stored alongside plain ones; the Prometheus exporter
+/// groups all label sets of a name under a single metric family.
+pub fn labeled_key(name: &str, labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return name.to_string();
+    }
+    let mut key = String::with_capacity(name.len() + 16 * labels.len());
+    key.push_str(name);
+    key.push('{');
+    for (i, (label, value)) in labels.iter().enumerate() {
+        if i > 0 {
+            key.push(',');
+        }
+        key.push_str(label);
+        key.push_str("=\"");
+        for c in value.chars() {
+            match c {
+                '\\' => key.push_str("\\\\"),
+                '"' => key.push_str("\\\""),
+                '\n' => key.push_str("\\n"),
+                c => key.push(c),
+            }
+        }
+        key.push('"');
+    }
+    key.push('}');
+    key
+}
+
+/// `s` escaped for a JSON string literal, including every control
+/// character.
+pub(crate) fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// (year, month, day) for days since the Unix epoch (proleptic Gregorian).
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// RFC 3339 UTC timestamp with milliseconds, e.g. `2026-10-16T08:30:00.000Z`.
+pub(crate) fn rfc3339(time: SystemTime) -> String {
+    let millis = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    };
+    let secs = millis.div_euclid(1000);
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let of_day = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        of_day / 3600,
+        of_day % 3600 / 60,
+        of_day % 60,
+        millis.rem_euclid(1000)
+    )
+}
+
+/// Split a key produced by [`labeled_key`] into name and label body.
+pub fn split_labeled_key(key: &str) -> (&str, Option<&str>) {
+    match key.split_once('{') {
+        Some((name, rest)) => (name, rest.strip_suffix('}')),
+        None => (key, None),
+    }
+}
+
+/// Parse a label body `k="v",k2="v2"` into pairs, unescaping values.
+pub(crate) fn parse_labels(body: &str) -> Vec<(&str, String)> {
+    let mut labels = Vec::new();
+    let mut rest = body;
+    while let Some((key, after)) = rest.split_once("=\"") {
+        let mut value = String::new();
+        let mut chars = after.char_indices();
+        let mut end = after.len();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, escaped)) => value.push(escaped),
+                    None => {}
+                },
+                '"' => {
+                    end = i + 1;
+                    break;
+                }
+                c => value.push(c),
+            }
+        }
+        labels.push((key.trim_start_matches(','), value));
+        rest = &after[end..];
+    }
+    labels
+}
+
 /// Statistics for a single operation type.
 #[derive(Debug, Clone)]
 pub struct OperationStats {
@@ -167,6 +497,17 @@ pub struct OperationStats {
     pub histogram: Vec<u64>,
     /// Sum of squares for variance calculation
     pub sum_of_squares: f64,
+    /// Most recent exemplar, if recorded with one
+    pub exemplar: Option<Exemplar>,
+}
+
+/// A sampled observation linked to the trace that produced it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exemplar {
+    pub trace_id: String,
+    pub value_us: u64,
+    /// Unix timestamp of the observation
+    pub timestamp_secs: f64,
 }
 
 impl OperationStats {
@@ -179,6 +520,7 @@ impl OperationStats {
             last_us: 0,
             histogram: Vec::new(),
             sum_of_squares: 0.0,
+            exemplar: None,
         }
     }
 
@@ -272,12 +614,26 @@ pub struct TelemetrySnapshot {
     pub counters: HashMap<String, u64>,
     pub gauges: HashMap<String, f64>,
     pub metrics: MetricsSnapshot,
+    pub quality: QualitySnapshot,
+    /// Metrics of the global [`hires_registry`](crate::hires_timing::hires_registry),
+    /// sorted by name
+    pub hires: Vec<(String, HiResMetricsSnapshot)>,
+    /// Most frequent groups of the global
+    /// [`error_tracker`](crate::error_tracking::error_tracker)
+    pub errors: Vec<ErrorGroup>,
+    /// Metric metadata declared on the source `Telemetry`
+    pub registry: Arc<MetricRegistry>,
 }
 
 impl TelemetrySnapshot {
     /// Export as JSON string (requires serde feature).
     #[cfg(feature = "telemetry")]
     pub fn to_json(&self) -> String {
+        crate::privacy::enforce_snapshot(self).write_json()
+    }
+
+    #[cfg(feature = "telemetry")]
+    fn write_json(&self) -> String {
         use std::fmt::Write;
 
         let mut json = String::new();
@@ -299,7 +655,7 @@ impl TelemetrySnapshot {
             } else {
                 ""
             };
-            writeln!(json, r#"    "{}": {{"#, name).unwrap();
+            writeln!(json, r#"    "{}": {{"#, escape_json(name)).unwrap();
             writeln!(json, r#"      "count": {},"#, stats.count).unwrap();
             writeln!(json, r#"      "avg_us": {:.2},"#, stats.avg_us()).unwrap();
             writeln!(json, r#"      "min_us": {},"#, stats.min_us).unwrap();
@@ -312,7 +668,7 @@ impl TelemetrySnapshot {
         writeln!(json, r#"  "counters": {{"#).unwrap();
         for (i, (name, value)) in self.counters.iter().enumerate() {
             let comma = if i < self.counters.len() - 1 { "," } else { "" };
-            writeln!(json, r#"    "{}": {}{}"#, name, value, comma).unwrap();
+            writeln!(json, r#"    "{}": {}{}"#, escape_json(name), value, comma).unwrap();
         }
         writeln!(json, r#"  }},"#).unwrap();
 
@@ -320,7 +676,75 @@ impl TelemetrySnapshot {
         writeln!(json, r#"  "gauges": {{"#).unwrap();
         for (i, (name, value)) in self.gauges.iter().enumerate() {
             let comma = if i < self.gauges.len() - 1 { "," } else { "" };
-            writeln!(json, r#"    "{}": {:.4}{}"#, name, value, comma).unwrap();
+            writeln!(
+                json,
+                r#"    "{}": {:.4}{}"#,
+                escape_json(name),
+                value,
+                comma
+            )
+            .unwrap();
+        }
+        writeln!(json, r#"  }},"#).unwrap();
+
+        // High-resolution metrics
+        writeln!(json, r#"  "hires": {{"#).unwrap();
+        for (i, (name, stats)) in self.hires.iter().enumerate() {
+            let comma = if i < self.hires.len() - 1 { "," } else { "" };
+            writeln!(
+                json,
+                r#"    "{}": {{"count": {}, "mean_ps": {}, "min_ps": {}, "max_ps": {}, "p50_ps": {}, "p99_ps": {}, "p999_ps": {}}}{}"#,
+                escape_json(name),
+                stats.count,
+                stats.mean_ps,
+                stats.min_ps,
+                stats.max_ps,
+                stats.p50_ps,
+                stats.p99_ps,
+                stats.p999_ps,
+                comma
+            )
+            .unwrap();
+        }
+        writeln!(json, r#"  }},"#).unwrap();
+
+        // Top error groups
+        writeln!(json, r#"  "errors": ["#).unwrap();
+        for (i, group) in self.errors.iter().enumerate() {
+            let comma = if i < self.errors.len() - 1 { "," } else { "" };
+            writeln!(
+                json,
+                r#"    {{"fingerprint": "{:016x}", "type": "{}", "message": "{}", "count": {}, "first_seen": "{}", "last_seen": "{}"}}{}"#,
+                group.fingerprint,
+                escape_json(&group.error_type),
+                escape_json(&group.message),
+                group.count,
+                rfc3339(group.first_seen),
+                rfc3339(group.last_seen),
+                comma
+            )
+            .unwrap();
+        }
+        writeln!(json, r#"  ],"#).unwrap();
+
+        // Metric metadata
+        writeln!(json, r#"  "metadata": {{"#).unwrap();
+        for (i, descriptor) in self.registry.iter().enumerate() {
+            let comma = if i < self.registry.len() - 1 { "," } else { "" };
+            let unit = match &descriptor.unit {
+                Some(unit) => format!(r#""{}""#, escape_json(unit)),
+                None => "null".to_string(),
+            };
+            writeln!(
+                json,
+                r#"    "{}": {{"type": "{}", "unit": {}, "help": "{}"}}{}"#,
+                escape_json(&descriptor.name),
+                descriptor.kind.as_str(),
+                unit,
+                escape_json(&descriptor.help),
+                comma
+            )
+            .unwrap();
         }
         writeln!(json, r#"  }}"#).unwrap();
 
@@ -330,7 +754,15 @@ impl TelemetrySnapshot {
 
     #[cfg(not(feature = "telemetry"))]
     pub fn to_json(&self) -> String {
-        "{{}}".to_string()
+        "{}".to_string()
+    }
+
+    /// Up to `n` operations, slowest p99 first (ties by name).
+    pub fn top_operations(&self, n: usize) -> Vec<(&String, &OperationStats)> {
+        let mut operations: Vec<_> = self.operation_stats.iter().collect();
+        operations.sort_by(|a, b| b.1.p99_us().cmp(&a.1.p99_us()).then(a.0.cmp(b.0)));
+        operations.truncate(n);
+        operations
     }
 
     /// Format as human-readable summary.
@@ -369,6 +801,23 @@ impl TelemetrySnapshot {
             }
         }
 
+        if !self.hires.is_empty() {
+            output.push_str("\nHigh-resolution:\n");
+            for (name, stats) in &self.hires {
+                output.push_str(&format!("  {}: {}\n", name, stats.format()));
+            }
+        }
+
+        if !self.errors.is_empty() {
+            output.push_str("\nErrors:\n");
+            for group in &self.errors {
+                output.push_str(&format!(
+                    "  {}x {}: {}\n",
+                    group.count, group.error_type, group.message
+                ));
+            }
+        }
+
         output
     }
 }
@@ -377,6 +826,19 @@ impl TelemetrySnapshot {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rfc3339() {
+        assert_eq!(rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            rfc3339(UNIX_EPOCH + Duration::from_millis(1_792_147_845_123)),
+            "2026-10-16T10:50:45.123Z"
+        );
+        assert_eq!(
+            rfc3339(UNIX_EPOCH - Duration::from_millis(1)),
+            "1969-12-31T23:59:59.999Z"
+        );
+    }
+
     #[test]
     fn test_telemetry_basic() {
         let mut telemetry = Telemetry::default_config();
@@ -465,16 +927,39 @@ mod tests {
         assert_eq!(telemetry.snapshot().counters.get("test"), None);
     }
 
+    #[test]
+    fn test_mock_clock_drives_uptime_and_exemplars() {
+        let clock = Arc::new(crate::clock::MockClock::at(UNIX_EPOCH));
+        let mut telemetry = Telemetry::default_config().with_clock(clock.clone());
+
+        clock.advance(Duration::from_secs(90));
+        telemetry.record_operation_with_exemplar("query", 1250, "abc123");
+        let snapshot = telemetry.snapshot();
+        assert_eq!(snapshot.uptime_secs, 90);
+        assert_eq!(snapshot.since_last_snapshot_secs, 90);
+        let exemplar = snapshot.operation_stats["query"].exemplar.as_ref().unwrap();
+        assert_eq!(exemplar.timestamp_secs, 90.0);
+
+        telemetry.reset();
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(telemetry.snapshot().since_last_snapshot_secs, 5);
+        assert_eq!(telemetry.uptime_secs(), 95);
+    }
+
     #[test]
     fn test_snapshot_summary() {
         let mut telemetry = Telemetry::default_config();
         telemetry.record_operation("test_op", 500);
+        crate::hires_timing::hires_registry()
+            .register("telemetry_test_hires")
+            .record(crate::hires_timing::HiResTimestamp::from_nanos(42));
 
         let snapshot = telemetry.snapshot();
         let summary = snapshot.summary();
 
         assert!(summary.contains("Telemetry Snapshot"));
         assert!(summary.contains("test_op"));
+        assert!(summary.contains("telemetry_test_hires: n=1"));
     }
 
     #[test]
@@ -492,4 +977,126 @@ mod tests {
         assert!(snapshot.operation_stats.is_empty());
         assert!(snapshot.counters.is_empty());
     }
+
+    #[test]
+    #[cfg(feature = "telemetry")]
+    fn test_json_includes_metadata() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry
+            .describe(MetricDescriptor::counter("read_total", "Bytes \"read\"").with_unit("bytes"));
+        telemetry.describe(MetricDescriptor::gauge("depth", "Queue depth"));
+        telemetry.add_to_counter("read_total", 10);
+
+        let json = telemetry.snapshot().to_json();
+        assert!(json.contains(
+            r#""read_total": {"type": "counter", "unit": "bytes", "help": "Bytes \"read\""}"#
+        ));
+        assert!(
+            json.contains(r#""depth": {"type": "gauge", "unit": null, "help": "Queue depth"},"#)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "telemetry")]
+    fn test_json_escapes_control_characters() {
+        let awkward = "op\tx \"q\" \\ \r\n\u{1}\u{1f} é";
+        assert_eq!(escape_json("a\tb\u{7}"), r"a\tb\u0007");
+
+        let mut telemetry = Telemetry::default_config();
+        telemetry.record_operation(awkward, 10);
+        telemetry.add_to_counter(&format!("hits{{path=\"{}\"}}", awkward), 1);
+        telemetry.set_gauge(awkward, 1.0);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&telemetry.snapshot().to_json()).unwrap();
+        assert_eq!(json["operations"][awkward]["count"], 1);
+        assert_eq!(json["gauges"][awkward], 1.0);
+        let counter = json["counters"].as_object().unwrap().keys().next().unwrap();
+        assert!(counter.contains(awkward));
+    }
+
+    #[test]
+    fn test_cardinality_limit_overflow() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.limit_cardinality("requests_total", 2);
+
+        for user in ["a", "b", "c", "d"] {
+            telemetry.add_to_counter_with_labels("requests_total", &[("user", user)], 1);
+        }
+        // Existing label sets keep updating past the limit
+        telemetry.add_to_counter_with_labels("requests_total", &[("user", "a")], 1);
+        telemetry.set_gauge_with_labels("latency", &[("path", "/x")], 1.0);
+
+        let counters = telemetry.snapshot().counters;
+        assert_eq!(counters.get(r#"requests_total{user="a"}"#), Some(&2));
+        assert_eq!(counters.get(r#"requests_total{user="b"}"#), Some(&1));
+        assert!(!counters.contains_key(r#"requests_total{user="c"}"#));
+        assert_eq!(counters.get(r#"requests_total{overflow="true"}"#), Some(&2));
+        assert_eq!(
+            counters.get(r#"cardinality_limited_total{metric="requests_total"}"#),
+            Some(&2)
+        );
+        // Other metrics use the default limit
+        assert_eq!(telemetry.snapshot().gauges.len(), 1);
+    }
+
+    #[test]
+    fn test_default_cardinality_limit() {
+        let config = TelemetryConfig {
+            max_label_sets_per_metric: 10,
+            ..TelemetryConfig::default()
+        };
+        let mut telemetry = Telemetry::new(config);
+        for i in 0..1000 {
+            telemetry.set_gauge_with_labels("session_bytes", &[("id", &i.to_string())], 1.0);
+        }
+        // 10 admitted plus the overflow series
+        assert_eq!(telemetry.snapshot().gauges.len(), 11);
+
+        telemetry.reset();
+        telemetry.set_gauge_with_labels("session_bytes", &[("id", "new")], 1.0);
+        assert!(telemetry
+            .snapshot()
+            .gauges
+            .contains_key(r#"session_bytes{id="new"}"#));
+    }
+
+    #[test]
+    fn test_labeled_keys() {
+        let key = labeled_key("net_rx_bytes", &[("interface", "eth0"), ("note", "a\"b")]);
+        assert_eq!(key, r#"net_rx_bytes{interface="eth0",note="a\"b"}"#);
+        assert_eq!(
+            split_labeled_key(&key),
+            ("net_rx_bytes", Some(r#"interface="eth0",note="a\"b""#))
+        );
+        assert_eq!(labeled_key("plain", &[]), "plain");
+        assert_eq!(split_labeled_key("plain"), ("plain", None));
+
+        let mut telemetry = Telemetry::default_config();
+        telemetry.set_gauge_with_labels("net_rx_bytes", &[("interface", "eth0")], 5.0);
+        assert_eq!(
+            telemetry
+                .snapshot()
+                .gauges
+                .get(r#"net_rx_bytes{interface="eth0"}"#),
+            Some(&5.0)
+        );
+    }
+
+    #[test]
+    fn test_schema_dump_merges_recorded_keys() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.describe(MetricDescriptor::counter("hits_total", "Cache hits"));
+        telemetry.add_to_counter_with_labels("hits_total", &[("tier", "l1")], 1);
+        telemetry.record_operation("query", 10);
+
+        let schema = telemetry.schema_dump();
+        assert!(schema.contains(
+            r#"{"name": "hits_total", "type": "counter", "unit": null, "labels": ["tier"], "help": "Cache hits", "source": null}"#
+        ));
+        assert!(schema.contains(
+            r#"{"name": "query", "type": "histogram", "unit": null, "labels": [], "help": "", "source": "application"}"#
+        ));
+        assert!(schema.contains(r#""name": "process_open_fds""#));
+    }
 }
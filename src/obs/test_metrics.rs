@@ -9,8 +9,10 @@
 //! - Operation counting by category
 //! - Custom metric recording
 //! - Memory usage tracking
+//! - Per-operation allocation counts (`alloc-tracking` feature)
 //! - Error/warning counting
 //! - Automatic statistical analysis
+//! - Baseline persistence and regression detection
 //!
 //! # Usage
 //!
@@ -25,8 +27,25 @@
 //!
 //! println!("{}", metrics.summary());
 //! ```
+//!
+//! # Regression Detection
+//!
+//! ```rust,ignore
+//! // On a known-good build:
+//! metrics.save_baseline("target/baselines/bind_operation.json")?;
+//!
+//! // In CI, fail if any percentile regressed by more than 10%:
+//! let report = metrics.compare_to_baseline("target/baselines/bind_operation.json", 0.10)?;
+//! assert!(report.passed, "{}", report.summary());
+//! ```
 
+use crate::obs::comparison::Comparison;
+use crate::obs::hires_timing::probe_resolution;
+use crate::obs::telemetry::escape_json;
 use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
 /// Granular performance metrics for test operations.
@@ -38,12 +57,19 @@ pub struct TestMetrics {
     pub timings_ns: Vec<u64>,
     /// Start time for current measurement
     start: Option<Instant>,
+    /// Thread allocation counters at start of current measurement
+    #[cfg(feature = "alloc-tracking")]
+    alloc_start: Option<crate::obs::alloc_tracking::AllocStats>,
     /// Operation counts by category
     pub op_counts: HashMap<String, u64>,
     /// Custom numeric metrics
     pub custom_metrics: HashMap<String, f64>,
     /// Memory snapshots (bytes)
     pub memory_samples: Vec<usize>,
+    /// Allocations per timed operation (`alloc-tracking` feature)
+    pub alloc_counts: Vec<u64>,
+    /// Bytes allocated per timed operation (`alloc-tracking` feature)
+    pub alloc_bytes: Vec<u64>,
     /// Error/warning counts
     pub error_count: u64,
     pub warning_count: u64,
@@ -56,9 +82,13 @@ impl TestMetrics {
             name: name.to_string(),
             timings_ns: Vec::new(),
             start: None,
+            #[cfg(feature = "alloc-tracking")]
+            alloc_start: None,
             op_counts: HashMap::new(),
             custom_metrics: HashMap::new(),
             memory_samples: Vec::new(),
+            alloc_counts: Vec::new(),
+            alloc_bytes: Vec::new(),
             error_count: 0,
             warning_count: 0,
         }
@@ -67,6 +97,11 @@ impl TestMetrics {
     /// Start timing measurement.
     #[inline]
     pub fn start_timing(&mut self) {
+        #[cfg(feature = "alloc-tracking")]
+        {
+            self.alloc_start = crate::obs::alloc_tracking::is_installed()
+                .then(crate::obs::alloc_tracking::thread_alloc_stats);
+        }
         self.start = Some(Instant::now());
     }
 
@@ -74,7 +109,16 @@ impl TestMetrics {
     #[inline]
     pub fn stop_timing(&mut self) {
         if let Some(start) = self.start.take() {
-            self.timings_ns.push(start.elapsed().as_nanos() as u64);
+            let elapsed_ns = start.elapsed().as_nanos() as u64;
+
+            #[cfg(feature = "alloc-tracking")]
+            if let Some(alloc_start) = self.alloc_start.take() {
+                let delta = crate::obs::alloc_tracking::thread_alloc_stats() - alloc_start;
+                self.alloc_counts.push(delta.allocations);
+                self.alloc_bytes.push(delta.bytes_allocated);
+            }
+
+            self.timings_ns.push(elapsed_ns);
         }
     }
 
@@ -215,6 +259,17 @@ impl TestMetrics {
             ));
         }
 
+        if !self.alloc_counts.is_empty() {
+            let n = self.alloc_counts.len() as f64;
+            let avg_allocs = self.alloc_counts.iter().sum::<u64>() as f64 / n;
+            let avg_bytes = self.alloc_bytes.iter().sum::<u64>() as f64 / n;
+            let max_allocs = self.alloc_counts.iter().max().unwrap_or(&0);
+            report.push_str(&format!(
+                "Allocations: avg={:.1}/op, max={}/op, bytes avg={:.0}B/op\n",
+                avg_allocs, max_allocs, avg_bytes,
+            ));
+        }
+
         if self.error_count > 0 || self.warning_count > 0 {
             report.push_str(&format!(
                 "Issues: errors={}, warnings={}\n",
@@ -232,9 +287,207 @@ impl TestMetrics {
         self.op_counts.clear();
         self.custom_metrics.clear();
         self.memory_samples.clear();
+        self.alloc_counts.clear();
+        self.alloc_bytes.clear();
         self.error_count = 0;
         self.warning_count = 0;
     }
+
+    /// Save current timing statistics as a baseline file.
+    ///
+    /// The file is a flat JSON object that [`compare_to_baseline`](Self::compare_to_baseline)
+    /// can read back. Parent directories are not created.
+    pub fn save_baseline(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let stats = self.timing_stats();
+        let mut json = String::new();
+        writeln!(json, "{{").unwrap();
+        writeln!(json, r#"  "name": "{}","#, escape_json(&self.name)).unwrap();
+        writeln!(json, r#"  "count": {},"#, stats.count).unwrap();
+        writeln!(json, r#"  "min_ns": {},"#, stats.min_ns).unwrap();
+        writeln!(json, r#"  "max_ns": {},"#, stats.max_ns).unwrap();
+        writeln!(json, r#"  "mean_ns": {:.3},"#, stats.mean_ns).unwrap();
+        writeln!(json, r#"  "p50_ns": {},"#, stats.p50_ns).unwrap();
+        writeln!(json, r#"  "p95_ns": {},"#, stats.p95_ns).unwrap();
+        writeln!(json, r#"  "p99_ns": {},"#, stats.p99_ns).unwrap();
+        // Timer fingerprint, so baselines from coarse clocks stand out
+        let timer = probe_resolution();
+        writeln!(json, r#"  "timer_backend": "{}","#, timer.backend).unwrap();
+        writeln!(
+            json,
+            r#"  "timer_resolution_ps": {},"#,
+            timer.min_delta.picoseconds
+        )
+        .unwrap();
+        writeln!(
+            json,
+            r#"  "timer_overhead_ps": {},"#,
+            timer.overhead.picoseconds
+        )
+        .unwrap();
+        writeln!(
+            json,
+            r#"  "timer_monotonicity_violations": {}"#,
+            timer.monotonicity_violations
+        )
+        .unwrap();
+        writeln!(json, "}}").unwrap();
+        std::fs::write(path, json)
+    }
+
+    /// Compare current timing statistics against a saved baseline.
+    ///
+    /// `tolerance` is the allowed relative slowdown (e.g. `0.10` = 10%). The
+    /// report fails if mean, p50, p95 or p99 exceeds its baseline by more
+    /// than the tolerance.
+    pub fn compare_to_baseline(
+        &self,
+        path: impl AsRef<Path>,
+        tolerance: f64,
+    ) -> io::Result<RegressionReport> {
+        let content = std::fs::read_to_string(path)?;
+        let baseline = parse_baseline(&content)?;
+        let stats = self.timing_stats();
+
+        let deltas = vec![
+            PercentileDelta::new("mean", baseline.mean_ns, stats.mean_ns, tolerance),
+            PercentileDelta::new("p50", baseline.p50_ns, stats.p50_ns as f64, tolerance),
+            PercentileDelta::new("p95", baseline.p95_ns, stats.p95_ns as f64, tolerance),
+            PercentileDelta::new("p99", baseline.p99_ns, stats.p99_ns as f64, tolerance),
+        ];
+        let passed = deltas.iter().all(|d| !d.regressed);
+
+        Ok(RegressionReport {
+            name: self.name.clone(),
+            tolerance,
+            baseline_count: baseline.count,
+            current_count: stats.count,
+            deltas,
+            passed,
+        })
+    }
+}
+
+/// Timing values read back from a baseline file.
+struct Baseline {
+    count: usize,
+    mean_ns: f64,
+    p50_ns: f64,
+    p95_ns: f64,
+    p99_ns: f64,
+}
+
+/// Parse the flat JSON object written by [`TestMetrics::save_baseline`].
+fn parse_baseline(content: &str) -> io::Result<Baseline> {
+    let mut fields: HashMap<&str, f64> = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim().trim_end_matches(',');
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().trim_matches('"');
+        if let Ok(v) = value.trim().parse::<f64>() {
+            fields.insert(key, v);
+        }
+    }
+
+    let get = |key: &str| {
+        fields.get(key).copied().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("baseline missing field '{}'", key),
+            )
+        })
+    };
+
+    Ok(Baseline {
+        count: get("count")? as usize,
+        mean_ns: get("mean_ns")?,
+        p50_ns: get("p50_ns")?,
+        p95_ns: get("p95_ns")?,
+        p99_ns: get("p99_ns")?,
+    })
+}
+
+/// Change in a single statistic relative to the baseline.
+#[derive(Clone, Debug)]
+pub struct PercentileDelta {
+    /// Statistic name ("mean", "p50", "p95", "p99")
+    pub metric: &'static str,
+    /// Baseline value (nanoseconds)
+    pub baseline_ns: f64,
+    /// Current value (nanoseconds)
+    pub current_ns: f64,
+    /// Relative change (0.25 = 25% slower, negative = faster)
+    pub delta_ratio: f64,
+    /// Whether the change exceeds the tolerance
+    pub regressed: bool,
+}
+
+impl PercentileDelta {
+    fn new(metric: &'static str, baseline_ns: f64, current_ns: f64, tolerance: f64) -> Self {
+        let delta_ratio = if baseline_ns > 0.0 {
+            (current_ns - baseline_ns) / baseline_ns
+        } else {
+            0.0
+        };
+        Self {
+            metric,
+            baseline_ns,
+            current_ns,
+            delta_ratio,
+            regressed: delta_ratio > tolerance,
+        }
+    }
+}
+
+/// Result of comparing a run against a saved baseline.
+#[derive(Clone, Debug)]
+pub struct RegressionReport {
+    /// Operation name
+    pub name: String,
+    /// Allowed relative slowdown
+    pub tolerance: f64,
+    /// Sample count in the baseline
+    pub baseline_count: usize,
+    /// Sample count in the current run
+    pub current_count: usize,
+    /// Per-statistic deltas
+    pub deltas: Vec<PercentileDelta>,
+    /// True if no statistic regressed beyond the tolerance
+    pub passed: bool,
+}
+
+impl RegressionReport {
+    /// Look up the delta for a statistic by name.
+    pub fn delta(&self, metric: &str) -> Option<&PercentileDelta> {
+        self.deltas.iter().find(|d| d.metric == metric)
+    }
+
+    /// Statistics that regressed beyond the tolerance.
+    pub fn regressions(&self) -> impl Iterator<Item = &PercentileDelta> {
+        self.deltas.iter().filter(|d| d.regressed)
+    }
+
+    /// Generate summary report.
+    pub fn summary(&self) -> String {
+        let mut report = format!(
+            "=== {} Regression Check ({}, tolerance {:.1}%) ===\n",
+            self.name,
+            if self.passed { "PASS" } else { "FAIL" },
+            self.tolerance * 100.0,
+        );
+        for d in &self.deltas {
+            report.push_str(&format!(
+                "  {}: baseline={:.2}µs, current={:.2}µs, delta={:+.1}%{}\n",
+                d.metric,
+                d.baseline_ns / 1000.0,
+                d.current_ns / 1000.0,
+                d.delta_ratio * 100.0,
+                if d.regressed { " REGRESSED" } else { "" },
+            ));
+        }
+        report
+    }
 }
 
 /// Timing statistics.
@@ -285,6 +538,53 @@ impl TimingStats {
     pub fn p99_latency_us(&self) -> f64 {
         self.p99_ns as f64 / 1000.0
     }
+
+    /// Combine with stats of disjoint samples, e.g. from another thread.
+    ///
+    /// Count, total, min, max and mean are exact and the standard
+    /// deviation combines by the parallel variance formula. Percentiles
+    /// become the larger of the two, an upper bound; concatenate
+    /// `timings_ns` instead for exact percentiles.
+    pub fn merge(&mut self, other: &TimingStats) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = other.clone();
+            return;
+        }
+        let (na, nb) = (self.count as f64, other.count as f64);
+        let n = na + nb;
+        let delta = other.mean_ns - self.mean_ns;
+        // Population variances, as in `TestMetrics::timing_stats`
+        let m2 = self.std_dev_ns.powi(2) * na
+            + other.std_dev_ns.powi(2) * nb
+            + delta * delta * na * nb / n;
+
+        self.count += other.count;
+        self.min_ns = self.min_ns.min(other.min_ns);
+        self.max_ns = self.max_ns.max(other.max_ns);
+        self.mean_ns += delta * nb / n;
+        self.std_dev_ns = (m2 / n).sqrt();
+        self.p50_ns = self.p50_ns.max(other.p50_ns);
+        self.p95_ns = self.p95_ns.max(other.p95_ns);
+        self.p99_ns = self.p99_ns.max(other.p99_ns);
+        self.total_ns += other.total_ns;
+    }
+
+    /// Welch's t-test of `other` against these stats (nanoseconds); use
+    /// [`compare_samples`](crate::obs::comparison::compare_samples) on
+    /// `timings_ns` for the Mann-Whitney test as well.
+    pub fn compare(&self, other: &TimingStats) -> Comparison {
+        Comparison::welch(
+            self.count as u64,
+            self.mean_ns,
+            self.std_dev_ns,
+            other.count as u64,
+            other.mean_ns,
+            other.std_dev_ns,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -431,4 +731,72 @@ mod tests {
 
         assert_eq!(stats.ops_per_sec(), 1000.0);
     }
+
+    #[test]
+    fn test_baseline_roundtrip_and_regression() {
+        let path = std::env::temp_dir().join(format!(
+            "embeddenator_obs_baseline_{}.json",
+            std::process::id()
+        ));
+
+        let mut baseline = TestMetrics::new("baseline");
+        baseline.timings_ns = (1..=100).map(|i| i * 1000).collect();
+        baseline.save_baseline(&path).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(saved.contains(r#""timer_backend": ""#));
+        assert!(saved.contains(r#""timer_resolution_ps": "#));
+
+        // Same distribution passes
+        let report = baseline.compare_to_baseline(&path, 0.05).unwrap();
+        assert!(report.passed, "{}", report.summary());
+        assert_eq!(report.baseline_count, 100);
+
+        // 50% slower fails on p95
+        let mut slower = TestMetrics::new("baseline");
+        slower.timings_ns = (1..=100).map(|i| i * 1500).collect();
+        let report = slower.compare_to_baseline(&path, 0.10).unwrap();
+        assert!(!report.passed);
+        let p95 = report.delta("p95").unwrap();
+        assert!(p95.regressed);
+        assert!((p95.delta_ratio - 0.5).abs() < 0.01);
+        assert!(report.summary().contains("FAIL"));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_timing_stats_merge() {
+        let mut a = TestMetrics::new("a");
+        a.timings_ns = vec![100, 200, 300];
+        let mut b = TestMetrics::new("b");
+        b.timings_ns = vec![1_000, 2_000];
+        let mut all = TestMetrics::new("all");
+        all.timings_ns = vec![100, 200, 300, 1_000, 2_000];
+
+        let mut merged = a.timing_stats();
+        merged.merge(&b.timing_stats());
+        let expected = all.timing_stats();
+        assert_eq!(merged.count, 5);
+        assert_eq!(merged.total_ns, 3_600);
+        assert_eq!((merged.min_ns, merged.max_ns), (100, 2_000));
+        assert!((merged.mean_ns - expected.mean_ns).abs() < 1e-9);
+        assert!((merged.std_dev_ns - expected.std_dev_ns).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_timing_stats_compare() {
+        let mut baseline = TestMetrics::new("baseline");
+        baseline.timings_ns = (1..=100).map(|i| 1_000 + i % 10).collect();
+        let mut slower = TestMetrics::new("slower");
+        slower.timings_ns = (1..=100).map(|i| 1_100 + i % 10).collect();
+
+        let comparison = baseline.timing_stats().compare(&slower.timing_stats());
+        assert!(comparison.significant);
+        assert!((comparison.difference - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_baseline_missing_field() {
+        assert!(parse_baseline("{\n  \"count\": 3\n}\n").is_err());
+    }
 }
@@ -0,0 +1,331 @@
+//! In-Memory Exporters for Integration Tests
+//!
+//! Captures spans, metric events and log records in memory so crates
+//! using embeddenator-obs can assert on their instrumentation end to end
+//! without a collector or network.
+//!
+//! # Capture
+//!
+//! - [`InMemorySpanExporter`]: sampled spans, as they end
+//! - [`InMemoryMetricSink`]: events of the [`MetricStream`]s it is attached to
+//! - [`InMemoryLogSink`]: records from [`logging`](crate::obs::logging) and
+//!   [`record_event`](crate::obs::tracing::record_event)
+//!
+//! Span and log capture is process-wide while a [`TestObservability`] is
+//! installed. Installs are serialized, so tests running in parallel wait
+//! for each other instead of seeing each other's data.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::test_util::TestObservability;
+//!
+//! #[test]
+//! fn query_is_instrumented() {
+//!     let obs = TestObservability::install();
+//!     let mut stream = MetricStream::new();
+//!     obs.metrics.attach(&mut stream);
+//!
+//!     run_query(&mut stream);
+//!
+//!     assert!(obs.spans.span("query").is_some());
+//!     assert_eq!(obs.metrics.counter("queries_total"), Some(1));
+//!     assert!(obs.logs.contains("WARN", "cache miss"));
+//! } // uninstalled here
+//! ```
+
+use crate::obs::opentelemetry::OtelSpan;
+use crate::obs::streaming::{MetricEvent, MetricStream, SubscriptionId};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Exporters receiving spans and logs while installed.
+static SPAN_EXPORTER: Mutex<Option<InMemorySpanExporter>> = Mutex::new(None);
+static LOG_SINK: Mutex<Option<InMemoryLogSink>> = Mutex::new(None);
+/// Held by the installed [`TestObservability`].
+static INSTALL_LOCK: Mutex<()> = Mutex::new(());
+
+/// Called when a span ends.
+pub(crate) fn capture_span(span: &OtelSpan) {
+    if !span.sampled {
+        return;
+    }
+    if let Ok(exporter) = SPAN_EXPORTER.lock() {
+        if let Some(exporter) = exporter.as_ref() {
+            exporter.export(std::slice::from_ref(span));
+        }
+    }
+}
+
+/// Called for every log record.
+pub(crate) fn capture_log(level: &str, message: &str, fields: &[(&str, &str)]) {
+    if let Ok(sink) = LOG_SINK.lock() {
+        if let Some(sink) = sink.as_ref() {
+            sink.record(LogRecord {
+                level: level.to_string(),
+                message: message.to_string(),
+                fields: fields
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            });
+        }
+    }
+}
+
+/// Span exporter keeping finished spans in memory; clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct InMemorySpanExporter {
+    spans: Arc<Mutex<Vec<OtelSpan>>>,
+}
+
+impl InMemorySpanExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store spans, as an exporter would send them.
+    pub fn export(&self, spans: &[OtelSpan]) {
+        if let Ok(mut stored) = self.spans.lock() {
+            stored.extend(spans.iter().cloned());
+        }
+    }
+
+    /// Spans captured so far, in end order.
+    pub fn finished_spans(&self) -> Vec<OtelSpan> {
+        self.spans.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// Most recently ended span named `name`.
+    pub fn span(&self, name: &str) -> Option<OtelSpan> {
+        let spans = self.spans.lock().ok()?;
+        spans.iter().rev().find(|s| s.name == name).cloned()
+    }
+
+    pub fn reset(&self) {
+        if let Ok(mut spans) = self.spans.lock() {
+            spans.clear();
+        }
+    }
+}
+
+/// Metric sink recording stream events in memory; clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryMetricSink {
+    events: Arc<Mutex<Vec<MetricEvent>>>,
+}
+
+impl InMemoryMetricSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record every event published to `stream`.
+    pub fn attach(&self, stream: &mut MetricStream) -> SubscriptionId {
+        let events = self.events.clone();
+        stream.subscribe(move |event| {
+            if let Ok(mut events) = events.lock() {
+                events.push(event.clone());
+            }
+        })
+    }
+
+    /// Events recorded so far, in publish order.
+    pub fn events(&self) -> Vec<MetricEvent> {
+        self.events.lock().map(|e| e.clone()).unwrap_or_default()
+    }
+
+    /// Last published value of counter `name`.
+    pub fn counter(&self, name: &str) -> Option<u64> {
+        self.events()
+            .into_iter()
+            .rev()
+            .find_map(|event| match event {
+                MetricEvent::Counter(n, value) if n == name => Some(value),
+                _ => None,
+            })
+    }
+
+    /// Last published value of gauge `name`.
+    pub fn gauge(&self, name: &str) -> Option<f64> {
+        self.events()
+            .into_iter()
+            .rev()
+            .find_map(|event| match event {
+                MetricEvent::Gauge(n, value) if n == name => Some(value),
+                _ => None,
+            })
+    }
+
+    /// All published durations of timing `name`, in microseconds.
+    pub fn timings(&self, name: &str) -> Vec<u64> {
+        self.events()
+            .into_iter()
+            .filter_map(|event| match event {
+                MetricEvent::Timing(n, us) if n == name => Some(us),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn reset(&self) {
+        if let Ok(mut events) = self.events.lock() {
+            events.clear();
+        }
+    }
+}
+
+/// One captured log record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// `ERROR`, `WARN`, `INFO`, `DEBUG` or `TRACE`
+    pub level: String,
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+}
+
+/// Log sink keeping records in memory; clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryLogSink {
+    records: Arc<Mutex<Vec<LogRecord>>>,
+}
+
+impl InMemoryLogSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, record: LogRecord) {
+        if let Ok(mut records) = self.records.lock() {
+            records.push(record);
+        }
+    }
+
+    /// Records captured so far, in emit order.
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.records.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+
+    /// Whether a record at `level` has a message containing `text`.
+    pub fn contains(&self, level: &str, text: &str) -> bool {
+        self.records()
+            .iter()
+            .any(|r| r.level == level && r.message.contains(text))
+    }
+
+    pub fn reset(&self) {
+        if let Ok(mut records) = self.records.lock() {
+            records.clear();
+        }
+    }
+}
+
+/// Installed in-memory exporters; uninstalls them on drop.
+pub struct TestObservability {
+    pub spans: InMemorySpanExporter,
+    pub metrics: InMemoryMetricSink,
+    pub logs: InMemoryLogSink,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl TestObservability {
+    /// Capture spans and logs process-wide until dropped, waiting for any
+    /// other installed instance to be dropped first.
+    pub fn install() -> Self {
+        // A test that panicked while installed must not block the rest
+        let lock = INSTALL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let installed = Self {
+            spans: InMemorySpanExporter::new(),
+            metrics: InMemoryMetricSink::new(),
+            logs: InMemoryLogSink::new(),
+            _lock: lock,
+        };
+        set(&SPAN_EXPORTER, Some(installed.spans.clone()));
+        set(&LOG_SINK, Some(installed.logs.clone()));
+        installed
+    }
+
+    /// Clear everything captured so far.
+    pub fn reset(&self) {
+        self.spans.reset();
+        self.metrics.reset();
+        self.logs.reset();
+    }
+}
+
+impl Drop for TestObservability {
+    fn drop(&mut self) {
+        set(&SPAN_EXPORTER, None);
+        set(&LOG_SINK, None);
+    }
+}
+
+fn set<T>(slot: &Mutex<Option<T>>, value: Option<T>) {
+    *slot.lock().unwrap_or_else(|e| e.into_inner()) = value;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obs::logging;
+    use crate::obs::tracing::{record_event, EventLevel};
+    use std::time::Duration;
+
+    #[test]
+    fn test_captures_spans_metrics_and_logs() {
+        let obs = TestObservability::install();
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        obs.metrics.attach(&mut stream);
+
+        let mut parent = OtelSpan::new("query");
+        let mut child = OtelSpan::new_child("lookup", &parent);
+        child.end();
+        parent.end_with_error("timeout");
+        let mut unsampled = OtelSpan::new("dropped");
+        unsampled.sampled = false;
+        unsampled.end();
+
+        stream.publish_counter("queries_total", 1);
+        stream.publish_counter("queries_total", 2);
+        stream.publish_timing("query", 120);
+        logging::warn("cache miss for shard 2");
+        record_event(EventLevel::Info, "merged", &[("segments", "4")]);
+
+        let names: Vec<String> = obs
+            .spans
+            .finished_spans()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, ["lookup", "query"]);
+        let query = obs.spans.span("query").unwrap();
+        assert_eq!(query.attributes["error.message"], "timeout");
+
+        assert_eq!(obs.metrics.counter("queries_total"), Some(2));
+        assert_eq!(obs.metrics.timings("query"), vec![120]);
+        assert_eq!(obs.metrics.gauge("query"), None);
+
+        assert!(obs.logs.contains("WARN", "cache miss"));
+        let merged = obs.logs.records().pop().unwrap();
+        assert_eq!(
+            merged.fields,
+            vec![("segments".to_string(), "4".to_string())]
+        );
+
+        obs.reset();
+        assert!(obs.spans.finished_spans().is_empty());
+    }
+
+    #[test]
+    fn test_uninstalled_on_drop() {
+        let spans = {
+            let obs = TestObservability::install();
+            obs.spans.clone()
+        };
+        OtelSpan::new("after").end();
+        logging::warn("after uninstall");
+        assert!(spans.span("after").is_none());
+
+        let obs = TestObservability::install();
+        assert!(obs.logs.records().is_empty());
+    }
+}
@@ -14,13 +14,13 @@
 //! # Usage
 //!
 //! ```rust,ignore
-//! use embeddenator_obs::tracing::{init_tracing, span_operation};
+//! use embeddenator_obs::tracing::init_tracing;
 //!
 //! // Initialize once at startup
 //! init_tracing();
 //!
-//! // Instrument a function
-//! #[span_operation]
+//! // Instrument a function (feature `macros`)
+//! #[embeddenator_obs::trace]
 //! fn process_query(query: &str) -> Result<Vec<u8>> {
 //!     // Automatically creates span with function name and arguments
 //!     // ...
@@ -29,6 +29,9 @@
 //! // Manual span creation
 //! let _span = create_span("custom_operation", &[("key", "value")]);
 //! // Work happens here, timing is automatic
+//!
+//! // Entered span whose duration lands in a Telemetry on drop
+//! let _timer = create_timed_span("compact").record_into(telemetry.clone());
 //! ```
 //!
 //! # Performance
@@ -36,6 +39,10 @@
 //! When the `tracing` feature is disabled, all instrumentation compiles
 //! to zero-cost. With the feature enabled, typical overhead is <100ns per span.
 
+use crate::obs::hires_timing::{HiResMetrics, HiResTimer, HiResTimestamp};
+use crate::obs::opentelemetry::record_log;
+use crate::obs::telemetry::Telemetry;
+use std::sync::{Arc, Mutex};
 #[cfg(feature = "tracing")]
 use tracing::{span, Level, Span};
 
@@ -137,6 +144,103 @@ pub type SpanGuard = Span;
 #[cfg(not(feature = "tracing"))]
 pub type SpanGuard = ();
 
+/// Entered span that records its elapsed time when dropped.
+///
+/// Created by [`create_timed_span`]. Attach destinations with
+/// [`record_into`](Self::record_into) (operation timing in a
+/// [`Telemetry`]) and [`record_into_hires`](Self::record_into_hires);
+/// without either, the guard only times and scopes the span.
+///
+/// ```rust,ignore
+/// let mut timer = create_timed_span("compact").record_into(telemetry.clone());
+/// if let Err(e) = compact() {
+///     timer.fail(&e); // also counts operation_errors_total{operation="compact"}
+/// }
+/// ```
+#[must_use = "the duration is recorded when the guard is dropped"]
+pub struct SpanTimer {
+    name: String,
+    timer: HiResTimer,
+    telemetry: Option<Arc<Mutex<Telemetry>>>,
+    hires: Option<Arc<HiResMetrics>>,
+    error: Option<String>,
+    #[cfg(feature = "tracing")]
+    _entered: tracing::span::EnteredSpan,
+}
+
+/// Create and enter a span named `name`, timed until the guard drops.
+pub fn create_timed_span(name: &str) -> SpanTimer {
+    SpanTimer {
+        name: name.to_string(),
+        timer: HiResTimer::start(),
+        telemetry: None,
+        hires: None,
+        error: None,
+        #[cfg(feature = "tracing")]
+        _entered: create_span(name, &[]).entered(),
+    }
+}
+
+impl SpanTimer {
+    /// Record the duration as operation `name` in `telemetry`.
+    pub fn record_into(mut self, telemetry: Arc<Mutex<Telemetry>>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Record the duration into `metrics`.
+    pub fn record_into_hires(mut self, metrics: Arc<HiResMetrics>) -> Self {
+        self.hires = Some(metrics);
+        self
+    }
+
+    /// Mark the operation failed: logs an error event inside the span and
+    /// counts `operation_errors_total{operation}` on drop.
+    pub fn fail(&mut self, error: &dyn std::fmt::Display) {
+        let error = error.to_string();
+        record_event(
+            EventLevel::Error,
+            "operation failed",
+            &[("operation", &self.name), ("error", &error)],
+        );
+        self.error = Some(error);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// Time since the guard was created.
+    pub fn elapsed(&self) -> HiResTimestamp {
+        self.timer.elapsed()
+    }
+}
+
+impl Drop for SpanTimer {
+    fn drop(&mut self) {
+        let elapsed = self.timer.elapsed();
+        if let Some(hires) = &self.hires {
+            hires.record(elapsed);
+        }
+        if let Some(telemetry) = &self.telemetry {
+            if let Ok(mut telemetry) = telemetry.lock() {
+                telemetry.record_operation(&self.name, elapsed.as_micros());
+                if self.error.is_some() {
+                    telemetry.add_to_counter_with_labels(
+                        "operation_errors_total",
+                        &[("operation", &self.name)],
+                        1,
+                    );
+                }
+            }
+        }
+    }
+}
+
 /// Macro for quick span creation with automatic entry.
 ///
 /// # Example
@@ -170,6 +274,13 @@ macro_rules! span_scope {
 /// Record an event in the current span.
 #[cfg(feature = "tracing")]
 pub fn record_event(level: EventLevel, message: &str, fields: &[(&str, &str)]) {
+    let Some((message, redacted)) = crate::obs::logging::admit(level, message, fields) else {
+        return;
+    };
+    let fields: Vec<(&str, &str)> = redacted.iter().map(|(k, v)| (*k, v.as_ref())).collect();
+    let (message, fields) = (message.as_ref(), fields.as_slice());
+    record_log(level.as_str(), message, fields);
+    crate::obs::logging::forward(level, message, fields);
     match level {
         EventLevel::Error => tracing::error!(message = %message, ?fields),
         EventLevel::Warn => tracing::warn!(message = %message, ?fields),
@@ -180,9 +291,17 @@ pub fn record_event(level: EventLevel, message: &str, fields: &[(&str, &str)]) {
 }
 
 #[cfg(not(feature = "tracing"))]
-pub fn record_event(_level: EventLevel, message: &str, _fields: &[(&str, &str)]) {
-    if matches!(_level, EventLevel::Error | EventLevel::Warn) {
-        eprintln!("[{}] {}", _level.as_str(), message);
+pub fn record_event(level: EventLevel, message: &str, fields: &[(&str, &str)]) {
+    let Some((message, redacted)) = crate::obs::logging::admit(level, message, fields) else {
+        return;
+    };
+    let fields: Vec<(&str, &str)> = redacted.iter().map(|(k, v)| (*k, v.as_ref())).collect();
+    let (message, fields) = (message.as_ref(), fields.as_slice());
+    record_log(level.as_str(), message, fields);
+    if !crate::obs::logging::forward(level, message, fields)
+        && matches!(level, EventLevel::Error | EventLevel::Warn)
+    {
+        eprintln!("[{}] {}", level.as_str(), message);
     }
 }
 
@@ -206,6 +325,17 @@ impl EventLevel {
             EventLevel::Trace => "TRACE",
         }
     }
+    /// Level named `name`, case-insensitive (`warning` is accepted).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "error" => Some(EventLevel::Error),
+            "warn" | "warning" => Some(EventLevel::Warn),
+            "info" => Some(EventLevel::Info),
+            "debug" => Some(EventLevel::Debug),
+            "trace" => Some(EventLevel::Trace),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -230,6 +360,30 @@ mod tests {
         // Should compile and not panic
     }
 
+    #[test]
+    fn test_timed_span_records_on_drop() {
+        let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+        let hires = Arc::new(HiResMetrics::new());
+        {
+            let _timer = create_timed_span("compact")
+                .record_into(telemetry.clone())
+                .record_into_hires(hires.clone());
+        }
+        {
+            let mut timer = create_timed_span("compact").record_into(telemetry.clone());
+            timer.fail(&"disk full");
+            assert!(timer.is_failed());
+        }
+
+        let snapshot = telemetry.lock().unwrap().snapshot();
+        assert_eq!(snapshot.operation_stats["compact"].count, 2);
+        assert_eq!(
+            snapshot.counters[r#"operation_errors_total{operation="compact"}"#],
+            1
+        );
+        assert_eq!(hires.snapshot().count, 1);
+    }
+
     #[test]
     fn test_event_level_str() {
         assert_eq!(EventLevel::Error.as_str(), "ERROR");
@@ -0,0 +1,487 @@
+//! Terminal Live Dashboard
+//!
+//! Renders live telemetry in the terminal with ratatui, for debugging on
+//! headless boxes without Grafana:
+//!
+//! - top operations by p99 latency
+//! - counters and gauges
+//! - alert state, from the [`MetricStream`] the dashboard is attached to
+//! - with the `sqlite-store` feature, the p99 trend of the top operations
+//!   over a trailing window, read from a [`SqliteStore`](crate::sqlite_store::SqliteStore)
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::tui::DashboardApp;
+//!
+//! let telemetry = Arc::new(Mutex::new(Telemetry::default_config()));
+//! let mut dashboard = DashboardApp::new().with_title("indexer");
+//! dashboard.attach(&mut stream);
+//!
+//! // Optional: history panel backed by the SQLite store
+//! let dashboard = dashboard.with_history(SqliteStore::open("metrics.db")?, Duration::from_secs(3600));
+//!
+//! // Blocks until `q` or Esc is pressed
+//! dashboard.run(&telemetry)?;
+//! ```
+
+#[cfg(feature = "sqlite-store")]
+use crate::obs::logging;
+#[cfg(feature = "sqlite-store")]
+use crate::obs::sqlite_store::SqliteStore;
+use crate::obs::streaming::{AlertState, MetricEvent, MetricStream, SubscriptionId};
+use crate::obs::telemetry::{Telemetry, TelemetrySnapshot};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Row, Table};
+use ratatui::Frame;
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Last transition of one alert.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AlertRow {
+    state: AlertState,
+    value: f64,
+    threshold: f64,
+}
+
+/// Live telemetry dashboard.
+pub struct DashboardApp {
+    title: String,
+    top_n: usize,
+    refresh_interval: Duration,
+    snapshot: Option<TelemetrySnapshot>,
+    alerts: Arc<Mutex<BTreeMap<String, AlertRow>>>,
+    #[cfg(feature = "sqlite-store")]
+    history: Option<History>,
+}
+
+/// Stored p99 trend of the top operations.
+#[cfg(feature = "sqlite-store")]
+struct History {
+    store: SqliteStore,
+    window: Duration,
+    rows: Vec<(String, Vec<u64>)>,
+}
+
+impl Default for DashboardApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DashboardApp {
+    pub fn new() -> Self {
+        Self {
+            title: "embeddenator".to_string(),
+            top_n: 10,
+            refresh_interval: Duration::from_secs(1),
+            snapshot: None,
+            alerts: Arc::new(Mutex::new(BTreeMap::new())),
+            #[cfg(feature = "sqlite-store")]
+            history: None,
+        }
+    }
+
+    /// Title shown in the header.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Operations listed, slowest p99 first (default: 10).
+    pub fn with_top_n(mut self, top_n: usize) -> Self {
+        self.top_n = top_n;
+        self
+    }
+
+    /// Time between snapshots in [`run`](Self::run) (default: 1s).
+    pub fn with_refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval = interval;
+        self
+    }
+
+    /// Show the p99 trend of the top operations over the trailing `window`,
+    /// read from `store` on every update.
+    #[cfg(feature = "sqlite-store")]
+    pub fn with_history(mut self, store: SqliteStore, window: Duration) -> Self {
+        self.history = Some(History {
+            store,
+            window,
+            rows: Vec::new(),
+        });
+        self
+    }
+
+    /// Track alert transitions published to `stream`.
+    pub fn attach(&self, stream: &mut MetricStream) -> SubscriptionId {
+        let alerts = self.alerts.clone();
+        stream.subscribe(move |event| {
+            if let MetricEvent::AlertTransition(name, state, value, threshold) = event {
+                if let Ok(mut alerts) = alerts.lock() {
+                    alerts.insert(
+                        name.clone(),
+                        AlertRow {
+                            state: *state,
+                            value: *value,
+                            threshold: *threshold,
+                        },
+                    );
+                }
+            }
+        })
+    }
+
+    /// Show `snapshot` on the next render.
+    pub fn update(&mut self, snapshot: TelemetrySnapshot) {
+        #[cfg(feature = "sqlite-store")]
+        if let Some(history) = self.history.as_mut() {
+            history.rows = snapshot
+                .top_operations(self.top_n)
+                .into_iter()
+                .map(|(name, _)| {
+                    let p99 = match history.store.operation_history(name, history.window) {
+                        Ok(points) => points.iter().map(|p| p.p99_us).collect(),
+                        Err(e) => {
+                            logging::warn(&format!("history of {} unavailable: {}", name, e));
+                            Vec::new()
+                        }
+                    };
+                    (name.clone(), p99)
+                })
+                .collect();
+        }
+        self.snapshot = Some(snapshot);
+    }
+
+    /// Draw the dashboard into `frame`.
+    pub fn render(&self, frame: &mut Frame) {
+        let [header, operations, bottom] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Percentage(45),
+            Constraint::Min(0),
+        ])
+        .areas(frame.area());
+        let [counters, gauges, alerts] = Layout::horizontal([
+            Constraint::Ratio(1, 3),
+            Constraint::Ratio(1, 3),
+            Constraint::Ratio(1, 3),
+        ])
+        .areas(bottom);
+
+        let uptime = self.snapshot.as_ref().map_or(0, |s| s.uptime_secs);
+        frame.render_widget(
+            Line::from(format!("{}  uptime {}s  (q to quit)", self.title, uptime))
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+            header,
+        );
+        #[cfg(feature = "sqlite-store")]
+        let operations = match &self.history {
+            Some(history) => {
+                let [live, stored] =
+                    Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                        .areas(operations);
+                self.render_history(frame, stored, history);
+                live
+            }
+            None => operations,
+        };
+        self.render_operations(frame, operations);
+        self.render_values(frame, counters, "Counters", self.counter_rows());
+        self.render_values(frame, gauges, "Gauges", self.gauge_rows());
+        self.render_alerts(frame, alerts);
+    }
+
+    /// Refresh from `telemetry` and redraw until `q` or Esc is pressed.
+    ///
+    /// Takes over the terminal (alternate screen, raw mode) and restores
+    /// it on return.
+    pub fn run(&mut self, telemetry: &Arc<Mutex<Telemetry>>) -> io::Result<()> {
+        let mut terminal = ratatui::init();
+        let result = self.run_loop(&mut terminal, telemetry);
+        ratatui::restore();
+        result
+    }
+
+    fn run_loop(
+        &mut self,
+        terminal: &mut ratatui::DefaultTerminal,
+        telemetry: &Arc<Mutex<Telemetry>>,
+    ) -> io::Result<()> {
+        let mut next_refresh = Instant::now();
+        loop {
+            if Instant::now() >= next_refresh {
+                if let Ok(telemetry) = telemetry.lock() {
+                    self.update(telemetry.snapshot());
+                }
+                terminal.draw(|frame| self.render(frame))?;
+                next_refresh = Instant::now() + self.refresh_interval;
+            }
+
+            let wait = next_refresh.saturating_duration_since(Instant::now());
+            if event::poll(wait)? {
+                match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => {
+                        if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                            return Ok(());
+                        }
+                    }
+                    Event::Resize(..) => next_refresh = Instant::now(),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn render_operations(&self, frame: &mut Frame, area: Rect) {
+        let operations = self
+            .snapshot
+            .as_ref()
+            .map(|s| s.top_operations(self.top_n))
+            .unwrap_or_default();
+
+        let rows = operations.into_iter().map(|(name, stats)| {
+            Row::new(vec![
+                name.clone(),
+                stats.count.to_string(),
+                format!("{:.0}", stats.avg_us()),
+                stats.median_us().to_string(),
+                stats.p99_us().to_string(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Min(20),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Length(10),
+            ],
+        )
+        .header(header_row(&[
+            "operation",
+            "count",
+            "avg us",
+            "p50 us",
+            "p99 us",
+        ]))
+        .block(titled("Top operations by p99"));
+        frame.render_widget(table, area);
+    }
+
+    #[cfg(feature = "sqlite-store")]
+    fn render_history(&self, frame: &mut Frame, area: Rect, history: &History) {
+        let rows = history.rows.iter().map(|(name, p99)| {
+            let range = match (p99.iter().min(), p99.iter().max()) {
+                (Some(min), Some(max)) => format!("{}..{}", min, max),
+                _ => "-".to_string(),
+            };
+            Row::new(vec![name.clone(), sparkline(p99, 24), range])
+        });
+        let title = format!("p99 history, last {}s", history.window.as_secs());
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Min(12),
+                Constraint::Length(24),
+                Constraint::Length(14),
+            ],
+        )
+        .header(header_row(&["operation", "p99 trend", "p99 us"]))
+        .block(titled(&title));
+        frame.render_widget(table, area);
+    }
+
+    fn counter_rows(&self) -> Vec<(String, String)> {
+        let mut rows: Vec<(String, String)> = self
+            .snapshot
+            .iter()
+            .flat_map(|s| s.counters.iter())
+            .map(|(name, value)| (name.clone(), value.to_string()))
+            .collect();
+        rows.sort();
+        rows
+    }
+
+    fn gauge_rows(&self) -> Vec<(String, String)> {
+        let mut rows: Vec<(String, String)> = self
+            .snapshot
+            .iter()
+            .flat_map(|s| s.gauges.iter())
+            .map(|(name, value)| (name.clone(), format_value(*value)))
+            .collect();
+        rows.sort();
+        rows
+    }
+
+    fn render_values(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        title: &str,
+        rows: Vec<(String, String)>,
+    ) {
+        let rows = rows
+            .into_iter()
+            .map(|(name, value)| Row::new(vec![name, value]));
+        let table = Table::new(rows, [Constraint::Min(10), Constraint::Length(12)])
+            .header(header_row(&["name", "value"]))
+            .block(titled(title));
+        frame.render_widget(table, area);
+    }
+
+    fn render_alerts(&self, frame: &mut Frame, area: Rect) {
+        let alerts = self
+            .alerts
+            .lock()
+            .map(|alerts| alerts.clone())
+            .unwrap_or_default();
+        let rows = alerts.into_iter().map(|(name, alert)| {
+            let color = match alert.state {
+                AlertState::Firing => Color::Red,
+                AlertState::Pending => Color::Yellow,
+                AlertState::Resolved => Color::Green,
+                AlertState::Inactive => Color::Reset,
+            };
+            Row::new(vec![
+                name,
+                alert.state.as_str().to_string(),
+                format!(
+                    "{} / {}",
+                    format_value(alert.value),
+                    format_value(alert.threshold)
+                ),
+            ])
+            .style(Style::default().fg(color))
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Min(10),
+                Constraint::Length(9),
+                Constraint::Length(16),
+            ],
+        )
+        .header(header_row(&["alert", "state", "value / limit"]))
+        .block(titled("Alerts"));
+        frame.render_widget(table, area);
+    }
+}
+
+fn titled(title: &str) -> Block<'_> {
+    Block::default().borders(Borders::ALL).title(title)
+}
+
+fn header_row(columns: &[&'static str]) -> Row<'static> {
+    Row::new(columns.to_vec()).style(Style::default().add_modifier(Modifier::BOLD))
+}
+
+/// The last `width` values as block characters scaled to their maximum.
+#[cfg(feature = "sqlite-store")]
+fn sparkline(values: &[u64], width: usize) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let values = &values[values.len().saturating_sub(width)..];
+    let max = values.iter().copied().max().unwrap_or(0).max(1);
+    values
+        .iter()
+        .map(|&v| BARS[(v.min(max) * 7 / max) as usize])
+        .collect()
+}
+
+/// Gauge value with at most three decimals.
+fn format_value(value: f64) -> String {
+    let formatted = format!("{:.3}", value);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obs::streaming::ThresholdAlert;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn screen(app: &DashboardApp) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let mut text = String::new();
+        for y in 0..buffer.area.height {
+            for x in 0..buffer.area.width {
+                text.push_str(buffer[(x, y)].symbol());
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    #[test]
+    fn test_renders_telemetry() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.record_operation("fast_op", 10);
+        telemetry.record_operation("slow_op", 9000);
+        telemetry.increment_counter("queries_total");
+        telemetry.set_gauge("queue_depth", 2.5);
+
+        let mut app = DashboardApp::new().with_title("indexer").with_top_n(1);
+        app.update(telemetry.snapshot());
+        let text = screen(&app);
+
+        assert!(text.contains("indexer"));
+        assert!(text.contains("slow_op"));
+        // Only the slowest operation fits the top 1
+        assert!(!text.contains("fast_op"));
+        assert!(text.contains("queries_total"));
+        assert!(text.contains("queue_depth"));
+        assert!(text.contains("2.5"));
+    }
+
+    #[test]
+    fn test_tracks_alert_state() {
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        stream.add_alert(ThresholdAlert::above("cpu_usage", 90.0));
+        let app = DashboardApp::new();
+        app.attach(&mut stream);
+
+        stream.publish_gauge("cpu_usage", 95.0);
+        let text = screen(&app);
+        assert!(text.contains("cpu_usage"));
+        assert!(text.contains("firing"));
+
+        stream.publish_gauge("cpu_usage", 50.0);
+        assert!(screen(&app).contains("resolved"));
+        assert_eq!(format_value(95.0), "95");
+    }
+
+    #[cfg(feature = "sqlite-store")]
+    #[test]
+    fn test_renders_stored_history() {
+        let mut telemetry = Telemetry::default_config();
+        telemetry.record_operation("slow_op", 9000);
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.insert_snapshot(&telemetry.snapshot()).unwrap();
+        telemetry.record_operation("slow_op", 90_000);
+        store.insert_snapshot(&telemetry.snapshot()).unwrap();
+
+        let mut app = DashboardApp::new().with_history(store, Duration::from_secs(3600));
+        app.update(telemetry.snapshot());
+        let text = screen(&app);
+
+        assert!(text.contains("p99 history"));
+        let rows = &app.history.as_ref().unwrap().rows;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].1.len(), 2);
+        assert!(rows[0].1[1] > rows[0].1[0]);
+        assert_eq!(sparkline(&[0, 50, 100], 8), "▁▄█");
+        assert_eq!(sparkline(&[1, 2, 3], 2), "▅█");
+    }
+}
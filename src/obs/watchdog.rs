@@ -0,0 +1,378 @@
+//! Heartbeat Watchdog
+//!
+//! Detects stuck background workers, not just slow ones: components call
+//! [`Watchdog::heartbeat`] as they make progress, and a component whose
+//! last heartbeat is older than its deadline is reported as stalled.
+//!
+//! # Reporting
+//!
+//! When a component stalls the watchdog logs an
+//! [`EventLevel::Error`] record and, if attached to a [`MetricStream`],
+//! emits [`MetricEvent::AlertTransition`] to firing followed by
+//! [`MetricEvent::ThresholdExceeded`] for
+//! `heartbeat_age_seconds{component="..."}` (age against deadline, in
+//! seconds), so alert notifiers pick it up. The next heartbeat resolves it
+//! with an info log and a resolved transition.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::watchdog::Watchdog;
+//!
+//! let watchdog = Watchdog::new(Duration::from_secs(30));
+//! watchdog.register("compactor", Duration::from_secs(300));
+//! watchdog.attach(&mut stream);
+//! let _handle = watchdog.clone().spawn(Duration::from_secs(1));
+//!
+//! // In the worker loop
+//! loop {
+//!     index_next_batch()?;
+//!     watchdog.heartbeat("indexer");
+//! }
+//! ```
+
+use crate::obs::process::{spawn_periodic, CollectorHandle};
+use crate::obs::streaming::{AlertState, Emitter, MetricEvent, MetricStream};
+use crate::obs::telemetry::{labeled_key, Telemetry};
+use crate::obs::tracing::{record_event, EventLevel};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Heartbeat state of one component.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeartbeatStatus {
+    /// Time since the last heartbeat (or registration)
+    pub age: Duration,
+    pub deadline: Duration,
+    pub heartbeats: u64,
+    pub stalled: bool,
+}
+
+/// A component that missed its deadline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stall {
+    pub component: String,
+    pub age: Duration,
+    pub deadline: Duration,
+}
+
+struct Component {
+    deadline: Duration,
+    last_beat: Instant,
+    heartbeats: u64,
+    stalled: bool,
+}
+
+type StallCallback = Arc<dyn Fn(&Stall) + Send + Sync>;
+
+#[derive(Default)]
+struct Shared {
+    components: Mutex<HashMap<String, Component>>,
+    emitter: Mutex<Option<Emitter>>,
+    callbacks: Mutex<Vec<StallCallback>>,
+}
+
+/// Heartbeat monitor; clones share the same components.
+#[derive(Clone)]
+pub struct Watchdog {
+    shared: Arc<Shared>,
+    default_deadline: Duration,
+}
+
+impl Watchdog {
+    /// Watchdog applying `default_deadline` to components that heartbeat
+    /// without being registered.
+    pub fn new(default_deadline: Duration) -> Self {
+        Self {
+            shared: Arc::new(Shared::default()),
+            default_deadline,
+        }
+    }
+
+    /// Watch `component` with its own deadline, starting the clock now.
+    pub fn register(&self, component: impl Into<String>, deadline: Duration) {
+        if let Ok(mut components) = self.shared.components.lock() {
+            components.insert(
+                component.into(),
+                Component {
+                    deadline,
+                    last_beat: Instant::now(),
+                    heartbeats: 0,
+                    stalled: false,
+                },
+            );
+        }
+    }
+
+    /// Stop watching `component`, e.g. when a worker exits normally.
+    pub fn unregister(&self, component: &str) -> bool {
+        self.shared
+            .components
+            .lock()
+            .map(|mut components| components.remove(component).is_some())
+            .unwrap_or(false)
+    }
+
+    /// Record progress of `component`, registering it with the default
+    /// deadline on first use and resolving a stall.
+    pub fn heartbeat(&self, component: &str) {
+        let recovered = {
+            let Ok(mut components) = self.shared.components.lock() else {
+                return;
+            };
+            let now = Instant::now();
+            match components.get_mut(component) {
+                Some(state) => {
+                    let recovered = state
+                        .stalled
+                        .then(|| (now - state.last_beat, state.deadline));
+                    state.last_beat = now;
+                    state.heartbeats += 1;
+                    state.stalled = false;
+                    recovered
+                }
+                None => {
+                    components.insert(
+                        component.to_string(),
+                        Component {
+                            deadline: self.default_deadline,
+                            last_beat: now,
+                            heartbeats: 1,
+                            stalled: false,
+                        },
+                    );
+                    None
+                }
+            }
+        };
+
+        if let Some((age, deadline)) = recovered {
+            record_event(
+                EventLevel::Info,
+                "heartbeat resumed",
+                &[
+                    ("component", component),
+                    ("stalled_ms", &age.as_millis().to_string()),
+                ],
+            );
+            self.emit(&MetricEvent::AlertTransition(
+                age_metric(component),
+                AlertState::Resolved,
+                0.0,
+                deadline.as_secs_f64(),
+            ));
+        }
+    }
+
+    /// Current state of `component`.
+    pub fn status(&self, component: &str) -> Option<HeartbeatStatus> {
+        let components = self.shared.components.lock().ok()?;
+        components.get(component).map(|state| HeartbeatStatus {
+            age: state.last_beat.elapsed(),
+            deadline: state.deadline,
+            heartbeats: state.heartbeats,
+            stalled: state.stalled,
+        })
+    }
+
+    /// Names of components currently stalled.
+    pub fn stalled(&self) -> Vec<String> {
+        let mut stalled: Vec<String> = self
+            .shared
+            .components
+            .lock()
+            .map(|components| {
+                components
+                    .iter()
+                    .filter(|(_, state)| state.stalled)
+                    .map(|(name, _)| name.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        stalled.sort();
+        stalled
+    }
+
+    /// Publish stall events into `stream`.
+    pub fn attach(&self, stream: &mut MetricStream) {
+        if let Ok(mut emitter) = self.shared.emitter.lock() {
+            *emitter = Some(stream.emitter());
+        }
+    }
+
+    /// Call `callback` for every new stall.
+    pub fn on_stall<F>(self, callback: F) -> Self
+    where
+        F: Fn(&Stall) + Send + Sync + 'static,
+    {
+        if let Ok(mut callbacks) = self.shared.callbacks.lock() {
+            callbacks.push(Arc::new(callback));
+        }
+        self
+    }
+
+    /// Find components past their deadline and report the ones that just
+    /// stalled; a component is reported once per stall.
+    pub fn check(&self) -> Vec<Stall> {
+        let stalls: Vec<Stall> = {
+            let Ok(mut components) = self.shared.components.lock() else {
+                return Vec::new();
+            };
+            let now = Instant::now();
+            let mut stalls: Vec<Stall> = components
+                .iter_mut()
+                .filter(|(_, state)| !state.stalled && now - state.last_beat > state.deadline)
+                .map(|(name, state)| {
+                    state.stalled = true;
+                    Stall {
+                        component: name.clone(),
+                        age: now - state.last_beat,
+                        deadline: state.deadline,
+                    }
+                })
+                .collect();
+            stalls.sort_by(|a, b| a.component.cmp(&b.component));
+            stalls
+        };
+
+        let callbacks: Vec<StallCallback> = self
+            .shared
+            .callbacks
+            .lock()
+            .map(|callbacks| callbacks.clone())
+            .unwrap_or_default();
+        for stall in &stalls {
+            record_event(
+                EventLevel::Error,
+                "heartbeat missed",
+                &[
+                    ("component", &stall.component),
+                    ("age_ms", &stall.age.as_millis().to_string()),
+                    ("deadline_ms", &stall.deadline.as_millis().to_string()),
+                ],
+            );
+            let metric = age_metric(&stall.component);
+            let (age, deadline) = (stall.age.as_secs_f64(), stall.deadline.as_secs_f64());
+            self.emit(&MetricEvent::AlertTransition(
+                metric.clone(),
+                AlertState::Firing,
+                age,
+                deadline,
+            ));
+            self.emit(&MetricEvent::ThresholdExceeded(metric, age, deadline));
+            for callback in &callbacks {
+                callback(stall);
+            }
+        }
+        stalls
+    }
+
+    /// Check every `interval` on a background thread.
+    pub fn spawn(self, interval: Duration) -> CollectorHandle {
+        spawn_periodic("obs-watchdog", interval, move || {
+            self.check();
+        })
+    }
+
+    /// Write `heartbeat_age_seconds{component}` and
+    /// `watchdog_stalled_components` gauges.
+    pub fn record_into(&self, telemetry: &mut Telemetry) {
+        let Ok(components) = self.shared.components.lock() else {
+            return;
+        };
+        for (name, state) in components.iter() {
+            telemetry.set_gauge_with_labels(
+                "heartbeat_age_seconds",
+                &[("component", name)],
+                state.last_beat.elapsed().as_secs_f64(),
+            );
+        }
+        let stalled = components.values().filter(|state| state.stalled).count();
+        telemetry.set_gauge("watchdog_stalled_components", stalled as f64);
+    }
+
+    fn emit(&self, event: &MetricEvent) {
+        if let Ok(emitter) = self.shared.emitter.lock() {
+            if let Some(emitter) = emitter.as_ref() {
+                emitter.emit(event);
+            }
+        }
+    }
+}
+
+fn age_metric(component: &str) -> String {
+    labeled_key("heartbeat_age_seconds", &[("component", component)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_stall_once_and_resolves() {
+        let stalls = Arc::new(Mutex::new(Vec::new()));
+        let sink = stalls.clone();
+        let watchdog = Watchdog::new(Duration::from_secs(60))
+            .on_stall(move |stall| sink.lock().unwrap().push(stall.component.clone()));
+        watchdog.register("indexer", Duration::from_millis(20));
+        watchdog.heartbeat("merger");
+
+        assert!(watchdog.check().is_empty());
+        std::thread::sleep(Duration::from_millis(40));
+        let found = watchdog.check();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].component, "indexer");
+        assert!(found[0].age > found[0].deadline);
+        // Reported once per stall
+        assert!(watchdog.check().is_empty());
+        assert_eq!(watchdog.stalled(), vec!["indexer".to_string()]);
+        assert_eq!(*stalls.lock().unwrap(), vec!["indexer".to_string()]);
+
+        watchdog.heartbeat("indexer");
+        let status = watchdog.status("indexer").unwrap();
+        assert!(!status.stalled);
+        assert_eq!(status.heartbeats, 1);
+        assert!(watchdog.stalled().is_empty());
+        assert_eq!(
+            watchdog.status("merger").unwrap().deadline,
+            Duration::from_secs(60)
+        );
+
+        let mut telemetry = Telemetry::default_config();
+        watchdog.record_into(&mut telemetry);
+        let gauges = telemetry.snapshot().gauges;
+        assert_eq!(gauges["watchdog_stalled_components"], 0.0);
+        assert!(gauges.contains_key(r#"heartbeat_age_seconds{component="indexer"}"#));
+    }
+
+    #[test]
+    fn test_emits_stream_events() {
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        stream.subscribe(move |event| sink.lock().unwrap().push(event.clone()));
+
+        let watchdog = Watchdog::new(Duration::from_millis(10));
+        watchdog.attach(&mut stream);
+        watchdog.heartbeat("indexer");
+        std::thread::sleep(Duration::from_millis(30));
+        watchdog.check();
+        watchdog.heartbeat("indexer");
+
+        let events = events.lock().unwrap();
+        let metric = r#"heartbeat_age_seconds{component="indexer"}"#;
+        assert!(matches!(
+            &events[0],
+            MetricEvent::AlertTransition(name, AlertState::Firing, _, _) if name == metric
+        ));
+        assert!(matches!(
+            &events[1],
+            MetricEvent::ThresholdExceeded(name, age, deadline) if name == metric && age > deadline
+        ));
+        assert!(matches!(
+            &events[2],
+            MetricEvent::AlertTransition(_, AlertState::Resolved, _, _)
+        ));
+    }
+}
@@ -0,0 +1,558 @@
+//! WebSocket Live Metrics Streaming
+//!
+//! Pushes [`MetricEvent`]s to connected WebSocket clients as JSON text
+//! frames, so a live dashboard can follow metrics without polling the
+//! Prometheus endpoint.
+//!
+//! # Filters
+//!
+//! Each connection selects events through its request query string, parsed
+//! by [`EventFilter::from_query`]:
+//!
+//! ```text
+//! ws://host:9899/?names=query,cache_&kinds=timing,gauge
+//! ```
+//!
+//! # Delivery
+//!
+//! Every connection has a bounded queue drained by its own writer thread.
+//! A slow client whose queue is full misses events (counted in
+//! [`WsBroadcaster::dropped_events`]) instead of stalling publishers. A
+//! client that stops reading altogether is disconnected once a write
+//! blocks for longer than the write timeout.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::ws_streaming::WsServer;
+//!
+//! let mut stream = MetricStream::new();
+//! let server = WsServer::bind("127.0.0.1:9899")?;
+//! server.attach(&mut stream);
+//! let _handle = server.spawn();
+//!
+//! stream.publish_gauge("cpu_usage", 75.5);
+//! // client receives {"type":"gauge","name":"cpu_usage","value":75.5}
+//! ```
+
+use crate::obs::logging;
+use crate::obs::process::{spawn_periodic, CollectorHandle};
+use crate::obs::streaming::{EventFilter, Fanout, MetricEvent, MetricStream, SubscriptionId};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::Shutdown;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Largest client frame accepted; clients only send control frames
+const MAX_CLIENT_FRAME: u64 = 64 * 1024;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest upgrade request, request line plus headers
+const MAX_HANDSHAKE_BYTES: u64 = 16 * 1024;
+/// Longest a frame write may block before the client is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Cloneable handle publishing events to every connected client.
+#[derive(Clone)]
+pub struct WsBroadcaster {
+    fanout: Arc<Fanout<String>>,
+}
+
+impl Default for WsBroadcaster {
+    fn default() -> Self {
+        Self {
+            fanout: Arc::new(Fanout::new()),
+        }
+    }
+}
+
+impl WsBroadcaster {
+    /// Send `event` to every client whose filter matches it.
+    pub fn publish(&self, event: &MetricEvent) {
+        self.fanout
+            .send(|filter| filter.matches(event), || event.to_json());
+    }
+
+    /// Currently connected clients.
+    pub fn connection_count(&self) -> usize {
+        self.fanout.len()
+    }
+
+    /// Events skipped because a client's queue was full.
+    pub fn dropped_events(&self) -> u64 {
+        self.fanout.dropped()
+    }
+}
+
+/// WebSocket server streaming metric events.
+pub struct WsServer {
+    listener: TcpListener,
+    broadcaster: WsBroadcaster,
+    queue_capacity: usize,
+    max_connections: usize,
+}
+
+impl WsServer {
+    /// Bind the listening socket.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            broadcaster: WsBroadcaster::default(),
+            queue_capacity: 1024,
+            max_connections: 64,
+        })
+    }
+
+    /// Events buffered per connection before dropping (default 1024).
+    pub fn with_queue_capacity(mut self, events: usize) -> Self {
+        self.queue_capacity = events.max(1);
+        self
+    }
+
+    /// Clients served at once (default 64); further connections are
+    /// closed unanswered.
+    pub fn with_max_connections(mut self, connections: usize) -> Self {
+        self.max_connections = connections.max(1);
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Handle for publishing events; stays valid after [`spawn`](Self::spawn).
+    pub fn broadcaster(&self) -> WsBroadcaster {
+        self.broadcaster.clone()
+    }
+
+    /// Forward every event of `stream` to connected clients.
+    pub fn attach(&self, stream: &mut MetricStream) -> SubscriptionId {
+        let broadcaster = self.broadcaster();
+        stream.subscribe(move |event| broadcaster.publish(event))
+    }
+
+    /// Accept connections on a background thread.
+    ///
+    /// Stopping the handle stops accepting; open connections end when their
+    /// clients disconnect.
+    pub fn spawn(self) -> CollectorHandle {
+        let active = Arc::new(AtomicUsize::new(0));
+        spawn_periodic("obs-ws-accept", Duration::from_millis(50), move || {
+            while let Ok((stream, peer)) = self.listener.accept() {
+                if active.fetch_add(1, Ordering::SeqCst) >= self.max_connections {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    logging::debug(&format!("websocket server full, dropping {}", peer));
+                    continue;
+                }
+                let broadcaster = self.broadcaster.clone();
+                let capacity = self.queue_capacity;
+                let connection_active = active.clone();
+                let spawned = std::thread::Builder::new()
+                    .name("obs-ws-conn".to_string())
+                    .spawn(move || {
+                        if let Err(e) = serve_connection(stream, broadcaster, capacity) {
+                            logging::debug(&format!("websocket client {} closed: {}", peer, e));
+                        }
+                        connection_active.fetch_sub(1, Ordering::SeqCst);
+                    });
+                if let Err(e) = spawned {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    logging::warn(&format!("failed to spawn websocket thread: {}", e));
+                }
+            }
+        })
+    }
+}
+
+fn serve_connection(
+    stream: TcpStream,
+    broadcaster: WsBroadcaster,
+    capacity: usize,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    // Shuts the socket down without waiting on the writer lock
+    let closer = stream.try_clone()?;
+    let writer = Arc::new(Mutex::new(stream));
+
+    let filter = match handshake(&mut reader) {
+        Ok((key, filter)) => {
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(&key)
+            );
+            lock(&writer)?.write_all(response.as_bytes())?;
+            filter
+        }
+        Err(e) => {
+            let _ = lock(&writer)?.write_all(
+                b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+            );
+            return Err(e);
+        }
+    };
+    reader.get_ref().set_read_timeout(None)?;
+
+    let (id, events) = broadcaster.fanout.register(filter, capacity);
+
+    let frame_writer = Arc::clone(&writer);
+    let sender_closer = closer.try_clone()?;
+    let sender = std::thread::Builder::new()
+        .name("obs-ws-send".to_string())
+        .spawn(move || {
+            for json in events {
+                let sent = lock(&frame_writer)
+                    .and_then(|mut stream| write_frame(&mut *stream, 0x1, json.as_bytes()));
+                if sent.is_err() {
+                    // Also ends the reader blocked on the client
+                    let _ = sender_closer.shutdown(Shutdown::Both);
+                    break;
+                }
+            }
+        })?;
+
+    let result = read_control_frames(&mut reader, &writer);
+    broadcaster.fanout.unregister(id);
+    let _ = closer.shutdown(Shutdown::Both);
+    let _ = sender.join();
+    result
+}
+
+fn lock(writer: &Mutex<TcpStream>) -> io::Result<std::sync::MutexGuard<'_, TcpStream>> {
+    writer
+        .lock()
+        .map_err(|_| io::Error::other("websocket writer poisoned"))
+}
+
+/// Read the upgrade request; returns the client key and event filter.
+fn handshake(reader: &mut impl BufRead) -> io::Result<(String, EventFilter)> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let mut reader = reader.take(MAX_HANDSHAKE_BYTES);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let target = request_line
+        .strip_prefix("GET ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .ok_or_else(|| invalid("expected GET request"))?;
+    let filter = EventFilter::from_query(target.split_once('?').map_or("", |(_, q)| q));
+
+    let mut key = None;
+    let mut upgrade = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            if reader.limit() == 0 {
+                return Err(invalid("handshake headers too large"));
+            }
+            return Err(invalid("connection closed during handshake"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("upgrade") {
+                upgrade = value.eq_ignore_ascii_case("websocket");
+            } else if name.eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.to_string());
+            }
+        }
+    }
+
+    match key {
+        Some(key) if upgrade => Ok((key, filter)),
+        _ => Err(invalid("missing websocket upgrade headers")),
+    }
+}
+
+/// Handle client frames until close: answer pings, ignore data.
+fn read_control_frames(reader: &mut impl Read, writer: &Mutex<TcpStream>) -> io::Result<()> {
+    loop {
+        let mut header = [0u8; 2];
+        reader.read_exact(&mut header)?;
+        let opcode = header[0] & 0x0f;
+        let masked = header[1] & 0x80 != 0;
+        let len = match header[1] & 0x7f {
+            126 => {
+                let mut ext = [0u8; 2];
+                reader.read_exact(&mut ext)?;
+                u16::from_be_bytes(ext) as u64
+            }
+            127 => {
+                let mut ext = [0u8; 8];
+                reader.read_exact(&mut ext)?;
+                u64::from_be_bytes(ext)
+            }
+            len => len as u64,
+        };
+        if len > MAX_CLIENT_FRAME {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "client frame too large",
+            ));
+        }
+        let mut mask = [0u8; 4];
+        if masked {
+            reader.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0u8; len as usize];
+        reader.read_exact(&mut payload)?;
+        if masked {
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+        }
+
+        match opcode {
+            0x8 => {
+                let _ = write_frame(&mut *lock(writer)?, 0x8, &payload);
+                return Ok(());
+            }
+            0x9 => write_frame(&mut *lock(writer)?, 0xA, &payload)?,
+            _ => {}
+        }
+    }
+}
+
+/// Write one unmasked, unfragmented server frame.
+fn write_frame(out: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    out.write_all(&frame)
+}
+
+/// `Sec-WebSocket-Accept` value for a client key (RFC 6455 section 4.2.2).
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, word) in digest.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_accept_key() {
+        // Example from RFC 6455 section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"a"), "YQ==");
+    }
+
+    fn connect(addr: SocketAddr, path: &str) -> (TcpStream, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: test\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path
+        )
+        .unwrap();
+        let mut response = Vec::new();
+        let mut byte = [0u8; 1];
+        while !response.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            response.push(byte[0]);
+        }
+        (stream, String::from_utf8(response).unwrap())
+    }
+
+    fn read_text(stream: &mut TcpStream) -> String {
+        let mut header = [0u8; 2];
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(header[0], 0x81);
+        let mut payload = vec![0u8; (header[1] & 0x7f) as usize];
+        stream.read_exact(&mut payload).unwrap();
+        String::from_utf8(payload).unwrap()
+    }
+
+    fn wait_for(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_streams_filtered_events() {
+        let mut stream = MetricStream::with_rate_limit(Duration::ZERO);
+        let server = WsServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        server.attach(&mut stream);
+        let broadcaster = server.broadcaster();
+        let _handle = server.spawn();
+
+        let (mut all, response) = connect(addr, "/");
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+        let (mut timings, _) = connect(addr, "/live?kinds=timing");
+        wait_for(|| broadcaster.connection_count() == 2);
+
+        stream.publish_gauge("cpu", 75.5);
+        stream.publish_timing("query", 120);
+
+        assert_eq!(
+            read_text(&mut all),
+            r#"{"type":"gauge","name":"cpu","value":75.5}"#
+        );
+        assert_eq!(
+            read_text(&mut all),
+            r#"{"type":"timing","name":"query","value":120,"unit":"us"}"#
+        );
+        assert_eq!(
+            read_text(&mut timings),
+            r#"{"type":"timing","name":"query","value":120,"unit":"us"}"#
+        );
+
+        // Masked close frame from the client
+        all.write_all(&[0x88, 0x80, 1, 2, 3, 4]).unwrap();
+        let mut close = [0u8; 2];
+        all.read_exact(&mut close).unwrap();
+        assert_eq!(close[0], 0x88);
+        wait_for(|| broadcaster.connection_count() == 1);
+    }
+
+    #[test]
+    fn test_rejects_plain_http() {
+        let server = WsServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let _handle = server.spawn();
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: test\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400"));
+    }
+
+    #[test]
+    fn test_bounds_handshake_read() {
+        // A header line that never ends must not be buffered without limit
+        let endless = b"GET / HTTP/1.1\r\nX-Pad: ".chain(io::repeat(b'a'));
+        let err = handshake(&mut BufReader::new(endless)).unwrap_err();
+        assert_eq!(err.to_string(), "handshake headers too large");
+    }
+
+    #[test]
+    fn test_caps_concurrent_connections() {
+        let server = WsServer::bind("127.0.0.1:0")
+            .unwrap()
+            .with_max_connections(1);
+        let addr = server.local_addr().unwrap();
+        let broadcaster = server.broadcaster();
+        let _handle = server.spawn();
+
+        let (_first, _) = connect(addr, "/");
+        wait_for(|| broadcaster.connection_count() == 1);
+
+        let mut second = TcpStream::connect(addr).unwrap();
+        second
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let _ = second.write_all(b"GET / HTTP/1.1\r\n\r\n");
+        let mut response = Vec::new();
+        let _ = second.read_to_end(&mut response);
+        assert!(response.is_empty());
+        assert_eq!(broadcaster.connection_count(), 1);
+    }
+}
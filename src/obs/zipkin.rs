@@ -0,0 +1,263 @@
+//! Zipkin Export
+//!
+//! Pushes finished [`OtelSpan`]s to a Zipkin collector as v2 JSON
+//! (`POST /api/v2/spans`), for services whose tracing backend speaks
+//! Zipkin rather than OTLP. Propagate context to them with the B3 headers
+//! on [`OtelSpan`] ([`to_b3_single`](OtelSpan::to_b3_single),
+//! [`to_b3_headers`](OtelSpan::to_b3_headers)).
+//!
+//! # Mapping
+//!
+//! - timestamps and durations in microseconds
+//! - `Server`, `Client`, `Producer` and `Consumer` kinds; internal spans
+//!   have no kind
+//! - attributes as `tags`, span events as `annotations`
+//! - error status as the `error` tag, carrying `error.message` if set
+//!
+//! Unsampled and unfinished spans are skipped. Only plain `http://`
+//! endpoints are supported.
+//!
+//! # Usage
+//!
+//! ```rust,ignore
+//! use embeddenator_obs::zipkin::ZipkinExporter;
+//!
+//! let exporter = ZipkinExporter::new("http://zipkin:9411/api/v2/spans")?
+//!     .with_service_name("indexer");
+//! exporter.push(&spans)?;
+//! ```
+
+use crate::obs::http::{Backoff, HttpEndpoint};
+use crate::obs::opentelemetry::{OtelSpan, SpanKind, SpanStatus};
+use crate::obs::privacy;
+use crate::obs::telemetry::escape_json;
+use std::io;
+use std::time::Duration;
+
+/// Zipkin v2 JSON exporter over HTTP.
+pub struct ZipkinExporter {
+    endpoint: HttpEndpoint,
+    service_name: String,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    backoff: Backoff,
+}
+
+impl ZipkinExporter {
+    /// Exporter for a collector URL such as
+    /// `http://zipkin:9411/api/v2/spans`, with a 10 second timeout and 3
+    /// retries backing off from 500 ms up to 30 seconds.
+    pub fn new(url: &str) -> io::Result<Self> {
+        Ok(Self {
+            endpoint: HttpEndpoint::parse(url)?,
+            service_name: "embeddenator".to_string(),
+            headers: Vec::new(),
+            timeout: Duration::from_secs(10),
+            backoff: Backoff {
+                max_retries: 3,
+                initial: Duration::from_millis(500),
+                max: Duration::from_secs(30),
+            },
+        })
+    }
+
+    /// Service name of the local endpoint.
+    pub fn with_service_name(mut self, name: impl Into<String>) -> Self {
+        self.service_name = name.into();
+        self
+    }
+
+    /// Add an HTTP header (auth tokens).
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Connect, write and read timeout per attempt.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Retry a failed push up to `max_retries` times.
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.backoff.max_retries = max_retries;
+        self
+    }
+
+    /// Zipkin v2 JSON array for `spans`.
+    pub fn encode(&self, spans: &[OtelSpan]) -> String {
+        let spans = privacy::enforce_spans(spans);
+        let encoded: Vec<String> = spans
+            .iter()
+            .filter(|s| s.sampled && s.end_time_ns != 0 && s.end_time_ns >= s.start_time_ns)
+            .map(|span| self.span_json(span))
+            .collect();
+        format!("[{}]", encoded.join(","))
+    }
+
+    /// Push `spans`, retrying transient failures. Nothing is sent when no
+    /// span is exportable.
+    pub fn push(&self, spans: &[OtelSpan]) -> io::Result<()> {
+        let body = self.encode(spans);
+        if body == "[]" {
+            return Ok(());
+        }
+        let mut headers = vec![("Content-Type".to_string(), "application/json".to_string())];
+        headers.extend(self.headers.iter().cloned());
+        self.backoff.retry("zipkin export", || {
+            self.endpoint.post(&headers, body.as_bytes(), self.timeout)
+        })
+    }
+
+    fn span_json(&self, span: &OtelSpan) -> String {
+        let mut fields = vec![
+            format!(r#""traceId":"{:032x}""#, span.trace_id),
+            format!(r#""id":"{:016x}""#, span.span_id),
+        ];
+        if span.parent_span_id != 0 {
+            fields.push(format!(r#""parentId":"{:016x}""#, span.parent_span_id));
+        }
+        fields.push(format!(r#""name":"{}""#, escape_json(&span.name)));
+        let kind = match span.kind {
+            SpanKind::Server => Some("SERVER"),
+            SpanKind::Client => Some("CLIENT"),
+            SpanKind::Producer => Some("PRODUCER"),
+            SpanKind::Consumer => Some("CONSUMER"),
+            SpanKind::Internal => None,
+        };
+        if let Some(kind) = kind {
+            fields.push(format!(r#""kind":"{}""#, kind));
+        }
+        fields.push(format!(r#""timestamp":{}"#, span.start_time_ns / 1000));
+        // Zipkin drops zero durations; round sub-microsecond spans up
+        let duration_us = ((span.end_time_ns - span.start_time_ns) / 1000).max(1);
+        fields.push(format!(r#""duration":{}"#, duration_us));
+        fields.push(format!(
+            r#""localEndpoint":{{"serviceName":"{}"}}"#,
+            escape_json(&self.service_name)
+        ));
+
+        // Zipkin tags are strings
+        let mut tags: Vec<(&str, String)> = span
+            .attributes
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.to_string()))
+            .collect();
+        if span.status == SpanStatus::Error {
+            let message = span
+                .attributes
+                .get("error.message")
+                .map_or("true".to_string(), |m| m.to_string());
+            tags.push(("error", message));
+        }
+        tags.sort();
+        if !tags.is_empty() {
+            let tags: Vec<String> = tags
+                .iter()
+                .map(|(k, v)| format!(r#""{}":"{}""#, escape_json(k), escape_json(v)))
+                .collect();
+            fields.push(format!(r#""tags":{{{}}}"#, tags.join(",")));
+        }
+
+        if !span.events.is_empty() {
+            let annotations: Vec<String> = span
+                .events
+                .iter()
+                .map(|event| {
+                    format!(
+                        r#"{{"timestamp":{},"value":"{}"}}"#,
+                        event.timestamp_ns / 1000,
+                        escape_json(&event.name)
+                    )
+                })
+                .collect();
+            fields.push(format!(r#""annotations":[{}]"#, annotations.join(",")));
+        }
+
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    fn finished_span(name: &str) -> OtelSpan {
+        let mut span = OtelSpan::new(name);
+        span.start_time_ns = 1_700_000_000_000_000_000;
+        span.end_time_ns = span.start_time_ns + 2_500_000;
+        span
+    }
+
+    #[test]
+    fn test_encode_v2_json() {
+        let mut root = finished_span("query");
+        root.set_kind(SpanKind::Server);
+        root.set_attribute("index", "main");
+        root.status = SpanStatus::Error;
+        root.set_attribute("error.message", "shard \"2\" timed out");
+        let mut child = OtelSpan::new_child("shard", &root);
+        child.start_time_ns = root.start_time_ns + 1000;
+        child.end_time_ns = child.start_time_ns + 200;
+        child.add_event("cache miss");
+        let mut unsampled = finished_span("hidden");
+        unsampled.sampled = false;
+
+        let exporter = ZipkinExporter::new("http://127.0.0.1:9411/api/v2/spans")
+            .unwrap()
+            .with_service_name("indexer");
+        let json = exporter.encode(&[root.clone(), child, unsampled]);
+
+        assert!(json.starts_with(&format!(
+            r#"[{{"traceId":"{:032x}","id":"{:016x}","name":"query","kind":"SERVER","timestamp":1700000000000000,"duration":2500,"localEndpoint":{{"serviceName":"indexer"}}"#,
+            root.trace_id, root.span_id
+        )));
+        assert!(json.contains(r#""error":"shard \"2\" timed out""#));
+        assert!(json.contains(r#""index":"main""#));
+        assert!(json.contains(&format!(r#""parentId":"{:016x}""#, root.span_id)));
+        // Sub-microsecond child rounded up; internal spans have no kind
+        assert!(json.contains(r#""name":"shard","timestamp":1700000000000001,"duration":1,"#));
+        assert!(json.contains(r#""annotations":[{"timestamp":"#));
+        assert!(!json.contains("hidden"));
+    }
+
+    #[test]
+    fn test_push_posts_spans() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/api/v2/spans", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(len) = line.strip_prefix("Content-Length: ") {
+                    content_length = len.trim().parse().unwrap();
+                }
+                head.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body).unwrap();
+            write!(reader.get_mut(), "HTTP/1.1 202 Accepted\r\n\r\n").unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+
+        let exporter = ZipkinExporter::new(&url).unwrap().with_retries(0);
+        exporter.push(&[finished_span("query")]).unwrap();
+        // Nothing to send: no request
+        exporter.push(&[]).unwrap();
+
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("POST /api/v2/spans HTTP/1.1\r\n"));
+        assert!(head.contains("Content-Type: application/json\r\n"));
+        assert!(body.contains(r#""name":"query""#));
+    }
+}
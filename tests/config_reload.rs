@@ -0,0 +1,98 @@
+//! Config file reloaded while running: on SIGHUP, on the `reload` query
+//! command and on request
+//!
+//! Installs a process-wide SIGHUP handler and tracer provider, so it runs
+//! in its own test binary.
+
+#![cfg(unix)]
+
+use embeddenator_obs::observability::Observability;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const CONFIG: &str = r#"
+[telemetry]
+sample_rate = 0.5
+
+[exporters]
+prometheus = "127.0.0.1:0"
+otlp = "http://127.0.0.1:4318"
+"#;
+
+fn get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: test\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+/// Replace `path` keeping its size and modification time, so only an
+/// explicit reload picks the change up.
+fn rewrite_unnoticed(path: &Path, contents: &str) {
+    let metadata = fs::metadata(path).unwrap();
+    assert_eq!(contents.len() as u64, metadata.len());
+    let staged = path.with_extension("toml.tmp");
+    fs::write(&staged, contents).unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&staged)
+        .unwrap()
+        .set_modified(metadata.modified().unwrap())
+        .unwrap();
+    fs::rename(&staged, path).unwrap();
+}
+
+#[test]
+fn test_reload_on_sighup_query_and_request() {
+    let path = std::env::temp_dir().join(format!(
+        "embeddenator_obs_config_reload_{}.toml",
+        std::process::id()
+    ));
+    fs::write(&path, CONFIG).unwrap();
+
+    let obs = Observability::builder()
+        .with_config_file(&path)
+        .with_config_poll(Duration::from_millis(10))
+        .init()
+        .unwrap();
+    let telemetry = obs.telemetry();
+    let sample_rate = || telemetry.lock().unwrap().config().sample_rate;
+    assert_eq!(sample_rate(), 0.5);
+    let addr = obs.metrics_addr().unwrap();
+
+    rewrite_unnoticed(
+        &path,
+        &CONFIG
+            .replace("0.5", "0.4")
+            .replace("127.0.0.1:0", "127.0.0.1:1"),
+    );
+    let status = std::process::Command::new("kill")
+        .args(["-HUP", &std::process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while sample_rate() != 0.4 {
+        assert!(Instant::now() < deadline, "SIGHUP did not reload");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    // The listening address stays until restart
+    let response = get(addr, "/query/reload");
+    assert!(response.contains(
+        r#"{"applied":[],"rejected":[{"key":"exporters.prometheus","reason":"requires restart: "#
+    ));
+
+    rewrite_unnoticed(&path, &CONFIG.replace("0.5", "0.4").replace("4318", "4319"));
+    let report = obs.reload_config().unwrap();
+    let applied: Vec<&str> = report.applied.iter().map(|c| c.key.as_str()).collect();
+    assert_eq!(applied, ["exporters.otlp"]);
+    assert!(report.rejected.is_empty());
+
+    drop(obs);
+    let _ = fs::remove_file(&path);
+}
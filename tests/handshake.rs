@@ -0,0 +1,61 @@
+//! Version handshake between copies of the crate
+//!
+//! Edits the process environment, so it runs in its own test binary.
+
+use embeddenator_obs::handshake::{self, INSTANCES_VAR, VERSION};
+use embeddenator_obs::{metrics, Telemetry};
+
+/// Stands in for the export function of an older copy.
+fn older_copy_metrics() -> Vec<(String, u64)> {
+    vec![
+        ("sub_cache_hits".to_string(), 5),
+        ("retrieval_query_ns_max".to_string(), u64::MAX / 2),
+        ("field_from_the_future".to_string(), 1),
+    ]
+}
+
+#[test]
+fn test_mixed_versions_are_reported_and_aggregated() {
+    let export = older_copy_metrics as fn() -> Vec<(String, u64)> as usize;
+    std::env::set_var(
+        INSTANCES_VAR,
+        format!(
+            "1|{}|0.19.0|embeddenator-fs|{:x};1|1|0.1.0|inherited|{:x}",
+            std::process::id(),
+            export,
+            export
+        ),
+    );
+
+    let instances = handshake::register_component("embeddenator-retrieval");
+    handshake::register_component("embeddenator-core");
+    assert_eq!(instances.len(), 2);
+    assert!(instances[0].is_self);
+    assert_eq!(instances[0].version, VERSION);
+
+    let instances = handshake::linked_instances();
+    assert_eq!(
+        instances[0].components,
+        vec!["embeddenator-retrieval", "embeddenator-core"]
+    );
+    assert_eq!(instances[1].version, "0.19.0");
+    assert_eq!(instances[1].components, vec!["embeddenator-fs"]);
+    assert!(!handshake::versions_consistent());
+    // The entry inherited from another process was dropped
+    assert!(!std::env::var(INSTANCES_VAR).unwrap().contains("inherited"));
+
+    let local = metrics().snapshot();
+    let shared = handshake::shared_metrics();
+    assert_eq!(shared.sub_cache_hits, local.sub_cache_hits + 5);
+    assert_eq!(shared.retrieval_query_ns_max, u64::MAX / 2);
+
+    let mut telemetry = Telemetry::default_config();
+    handshake::record_build_info(&mut telemetry);
+    let snapshot = telemetry.snapshot();
+    assert_eq!(snapshot.gauges["obs_linked_copies"], 2.0);
+    assert_eq!(
+        snapshot.gauges[r#"obs_build_info{version="0.19.0",component="embeddenator-fs"}"#],
+        1.0
+    );
+    assert_eq!(snapshot.metrics.sub_cache_hits, shared.sub_cache_hits);
+}
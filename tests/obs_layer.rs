@@ -0,0 +1,53 @@
+//! `tracing` spans mirrored into the span pipeline
+//!
+//! Installs a process-wide tracer provider, so it runs in its own test
+//! binary.
+#![cfg(feature = "tracing")]
+
+use embeddenator_obs::obs_layer::ObsLayer;
+use embeddenator_obs::span_processor::TracerProvider;
+use embeddenator_obs::tracing::create_span;
+use embeddenator_obs::{AttributeValue, OtelSpan, SpanKind, SpanStatus};
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn test_tracing_spans_become_otel_spans() {
+    let ended = Arc::new(Mutex::new(Vec::<OtelSpan>::new()));
+    let sink = ended.clone();
+    TracerProvider::new()
+        .with_processor(move |span: &OtelSpan| sink.lock().unwrap().push(span.clone()))
+        .install();
+
+    let subscriber = tracing_subscriber::registry().with(ObsLayer::new());
+    tracing::subscriber::with_default(subscriber, || {
+        let request = tracing::info_span!(
+            "request",
+            route = "/query",
+            shard = 3u64,
+            otel.kind = "server"
+        );
+        let _entered = request.enter();
+        {
+            let _op = create_span("lookup", &[]).entered();
+            tracing::error!(shard = 3, "shard timed out");
+        }
+        request.record("route", "/search");
+    });
+    TracerProvider::uninstall();
+
+    let spans = ended.lock().unwrap().clone();
+    assert_eq!(spans.len(), 2);
+    let (lookup, request) = (&spans[0], &spans[1]);
+    assert_eq!(lookup.name, "lookup");
+    assert_eq!(lookup.trace_id, request.trace_id);
+    assert_eq!(lookup.parent_span_id, request.span_id);
+    assert_eq!(lookup.status, SpanStatus::Error);
+    assert_eq!(lookup.events[0].name, "shard timed out");
+    assert_eq!(lookup.events[0].attributes["level"], "error");
+
+    assert_eq!(request.kind, SpanKind::Server);
+    assert_eq!(request.status, SpanStatus::Ok);
+    assert_eq!(request.attributes["route"], "/search");
+    assert_eq!(request.attributes["shard"], AttributeValue::I64(3));
+}
@@ -0,0 +1,74 @@
+//! One builder wiring endpoints and span export, flushed on drop
+//!
+//! Installs a process-wide tracer provider, so it runs in its own test
+//! binary.
+
+use embeddenator_obs::health::CheckResult;
+use embeddenator_obs::observability::Observability;
+use embeddenator_obs::span_processor::TracerProvider;
+use embeddenator_obs::OtelSpan;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+fn get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: test\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_builder_serves_and_flushes_spans_on_drop() {
+    let collector = TcpListener::bind("127.0.0.1:0").unwrap();
+    let otlp = format!("http://{}", collector.local_addr().unwrap());
+
+    let obs = Observability::builder()
+        .with_service_name("indexer")
+        .with_prometheus("127.0.0.1:0")
+        .with_otlp(otlp)
+        // Only the flush on drop exports
+        .with_export_interval(Duration::from_secs(3600))
+        .init()
+        .unwrap();
+    let addr = obs.metrics_addr().unwrap();
+    obs.health()
+        .register_readiness("index", || CheckResult::degraded("warming"));
+    obs.telemetry()
+        .lock()
+        .unwrap()
+        .set_gauge("queue_depth", 4.0);
+    let mut span = OtelSpan::new("lookup");
+    span.end();
+
+    assert!(get(addr, "/metrics").contains("queue_depth 4"));
+    assert!(get(addr, "/readyz").contains(r#""details":"warming""#));
+    assert!(get(addr, "/query/spans").contains(r#""name":"lookup""#));
+
+    let receiver = std::thread::spawn(move || {
+        let (mut stream, _) = collector.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        while !String::from_utf8_lossy(&request).contains("\"lookup\"") {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0, "request ended early");
+            request.extend_from_slice(&buf[..n]);
+        }
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        String::from_utf8(request).unwrap()
+    });
+
+    drop(obs);
+    let request = receiver.join().unwrap();
+    assert!(request.starts_with("POST /v1/traces HTTP/1.1\r\n"));
+    assert!(request.contains(r#"{"key": "service.name", "value": {"stringValue": "indexer"}}"#));
+    assert!(!TracerProvider::is_installed());
+    // The endpoint stopped with the guard
+    assert!(TcpStream::connect(addr).is_err());
+}
@@ -0,0 +1,68 @@
+//! Panics reported as metrics, logs and spans
+//!
+//! Installs a process-wide panic hook and tracer provider, so it runs in
+//! its own test binary.
+
+use embeddenator_obs::instrument::TracedCall;
+use embeddenator_obs::logging;
+use embeddenator_obs::metrics::metrics;
+use embeddenator_obs::panic_hook::install_panic_hook;
+use embeddenator_obs::span_processor::TracerProvider;
+use embeddenator_obs::tracing::EventLevel;
+use embeddenator_obs::{AttributeValue, OtelSpan, SpanStatus};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn test_panic_reaches_metrics_logs_and_spans() {
+    let ended = Arc::new(Mutex::new(Vec::<OtelSpan>::new()));
+    let sink = ended.clone();
+    TracerProvider::new()
+        .with_processor(move |span: &OtelSpan| sink.lock().unwrap().push(span.clone()))
+        .install();
+    install_panic_hook();
+    let before = metrics().snapshot().panics_total;
+
+    let worker = std::thread::Builder::new()
+        .name("ingest-worker".to_string())
+        .spawn(|| {
+            let _outer = TracedCall::enter("ingest", vec![]);
+            let _inner = TracedCall::enter("decode", vec![]);
+            panic!("bad header in shard {}", 3);
+        })
+        .unwrap();
+    assert!(worker.join().is_err());
+
+    #[cfg(feature = "metrics")]
+    assert_eq!(metrics().snapshot().panics_total, before + 1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = before;
+
+    let ended = ended.lock().unwrap();
+    assert_eq!(ended.len(), 2);
+    for span in ended.iter() {
+        assert_eq!(span.status, SpanStatus::Error);
+        assert_eq!(
+            span.attributes["error.message"],
+            AttributeValue::from("bad header in shard 3")
+        );
+    }
+
+    let record = logging::recent_records()
+        .into_iter()
+        .find(|record| record.level == EventLevel::Error)
+        .unwrap();
+    assert_eq!(
+        record.message,
+        "thread 'ingest-worker' panicked: bad header in shard 3"
+    );
+    let field = |name: &str| {
+        record
+            .fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+            .unwrap()
+    };
+    assert!(field("location").starts_with("tests/panic_hook.rs:"));
+    assert!(!field("backtrace").is_empty());
+}
@@ -0,0 +1,61 @@
+//! Aggregation-only export across exporters
+//!
+//! Installs a process-wide policy, so it runs in its own test binary.
+
+use embeddenator_obs::chrome_trace::ChromeTraceExporter;
+use embeddenator_obs::error_tracking::{error_tracker, ErrorEvent};
+use embeddenator_obs::opentelemetry::{OtelExporter, OtelSpan};
+use embeddenator_obs::privacy::PrivacyPolicy;
+use embeddenator_obs::prometheus::PrometheusExporter;
+use embeddenator_obs::Telemetry;
+
+const SECRETS: &[&str] = &["alice@example.com", "4111-1111", "/home/alice/notes.txt"];
+
+fn assert_no_secrets(exporter: &str, output: &str) {
+    for secret in SECRETS {
+        assert!(
+            !output.contains(secret),
+            "{} output leaked {:?}:\n{}",
+            exporter,
+            secret,
+            output
+        );
+    }
+}
+
+#[test]
+fn test_no_raw_strings_escape_any_exporter() {
+    let mut telemetry = Telemetry::default_config();
+    telemetry.add_to_counter_with_labels("logins_total", &[("user", "alice@example.com")], 3);
+    telemetry.set_gauge_with_labels("open_files", &[("path", "/home/alice/notes.txt")], 1.0);
+    telemetry.record_operation_with_exemplar("query", 1234, "4111-1111");
+    error_tracker().record(ErrorEvent::new("LoginError", "no user alice@example.com"));
+    let snapshot = telemetry.snapshot();
+
+    let mut span = OtelSpan::new("search");
+    span.set_attribute("query", "alice@example.com");
+    span.set_attribute("card", "4111-1111");
+    span.add_event("opened /home/alice/notes.txt");
+    span.end();
+    let spans = vec![span];
+
+    // Raw export carries the strings
+    let raw = PrometheusExporter::new("app").export(&snapshot);
+    assert!(raw.contains("alice@example.com"));
+
+    PrivacyPolicy::aggregation_only().install();
+
+    let prometheus = PrometheusExporter::new("app").export(&snapshot);
+    assert_no_secrets("prometheus", &prometheus);
+    assert!(prometheus.contains(r#"user="redacted""#));
+    assert_no_secrets("otlp", &OtelExporter::new().export_spans(&spans));
+    assert_no_secrets("chrome trace", &ChromeTraceExporter::new().export(&spans));
+    #[cfg(feature = "telemetry")]
+    assert_no_secrets("snapshot json", &snapshot.to_json());
+
+    PrivacyPolicy::uninstall();
+    assert!(PrivacyPolicy::installed().is_none());
+    assert!(ChromeTraceExporter::new()
+        .export(&spans)
+        .contains("alice@example.com"));
+}
@@ -0,0 +1,38 @@
+//! Recent log records kept below the active level
+//!
+//! Changes the process-wide level and buffer, so it runs in its own test
+//! binary.
+
+use embeddenator_obs::logging;
+use embeddenator_obs::redaction::Redactor;
+use embeddenator_obs::tracing::EventLevel;
+
+#[test]
+fn test_filtered_records_are_kept_for_dumps() {
+    logging::set_recent_capacity(4);
+    logging::set_max_level(EventLevel::Warn);
+    logging::install_crash_dump();
+    Redactor::new().install();
+
+    logging::debug("loading shard 3");
+    logging::info_kv("opened", &[("shard", 3.into()), ("token", "t-1".into())]);
+    for i in 0..3 {
+        logging::debug(&format!("step {}", i));
+    }
+
+    let records = logging::recent_records();
+    let messages: Vec<&str> = records.iter().map(|r| r.message.as_str()).collect();
+    assert_eq!(messages, ["opened", "step 0", "step 1", "step 2"]);
+    assert_eq!(records[0].level, EventLevel::Info);
+    // Redacted before it is kept
+    assert!(records[0]
+        .fields
+        .contains(&("token".to_string(), "redacted".to_string())));
+
+    // A panicking thread still reaches the previous hook and unwinds
+    assert!(std::thread::spawn(|| panic!("boom")).join().is_err());
+
+    logging::set_recent_capacity(0);
+    logging::debug("not kept");
+    assert!(logging::recent_records().is_empty());
+}
@@ -0,0 +1,46 @@
+//! Secrets masked in log records before capture
+//!
+//! Installs a process-wide redactor, so it runs in its own test binary.
+#![cfg(feature = "test-util")]
+
+use embeddenator_obs::logging;
+use embeddenator_obs::redaction::Redactor;
+use embeddenator_obs::test_util::TestObservability;
+use embeddenator_obs::tracing::{record_event, EventLevel};
+
+#[test]
+fn test_installed_redactor_masks_every_log_path() {
+    let obs = TestObservability::install();
+    Redactor::new()
+        .redact_pattern(r"Bearer [A-Za-z0-9._-]+")
+        .unwrap()
+        .install();
+
+    logging::warn("rejected Bearer eyJhbGci.x-y");
+    logging::warn_kv(
+        "login",
+        &[("user", "ada".into()), ("password", "hunter2".into())],
+    );
+    record_event(
+        EventLevel::Warn,
+        "retry",
+        &[("api-key", "k-123"), ("n", "2")],
+    );
+
+    Redactor::uninstall();
+    logging::warn("after Bearer visible");
+
+    let records = obs.logs.records();
+    let dump = format!("{:?}", records);
+    for secret in ["eyJhbGci", "hunter2", "k-123"] {
+        assert!(!dump.contains(secret), "leaked {:?}: {}", secret, dump);
+    }
+    assert_eq!(records[0].message, "rejected redacted");
+    assert!(records[1]
+        .fields
+        .contains(&("user".to_string(), "ada".to_string())));
+    assert!(records[2]
+        .fields
+        .contains(&("n".to_string(), "2".to_string())));
+    assert_eq!(records[3].message, "after Bearer visible");
+}
@@ -0,0 +1,46 @@
+//! Span processor pipeline
+//!
+//! Installs a process-wide tracer provider, so it runs in its own test
+//! binary.
+
+use embeddenator_obs::span_processor::{BatchSpanProcessor, TracerProvider};
+use embeddenator_obs::OtelSpan;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn test_spans_flow_to_installed_processors() {
+    let exported = Arc::new(Mutex::new(Vec::<OtelSpan>::new()));
+    let sink = exported.clone();
+    let batcher = BatchSpanProcessor::new(move |spans| {
+        sink.lock().unwrap().extend(spans.iter().cloned());
+        Ok(())
+    });
+    let ended = Arc::new(Mutex::new(Vec::<String>::new()));
+    let log = ended.clone();
+
+    TracerProvider::new()
+        .with_processor(batcher.clone())
+        .with_processor(move |span: &OtelSpan| log.lock().unwrap().push(span.name.clone()))
+        .install();
+    assert!(TracerProvider::is_installed());
+
+    let mut root = OtelSpan::builder("request").attr("route", "/query").start();
+    let mut child = OtelSpan::new_child("lookup", &root);
+    child.end();
+    root.end();
+    assert_eq!(*ended.lock().unwrap(), vec!["lookup", "request"]);
+    // Batched until flushed
+    assert!(exported.lock().unwrap().is_empty());
+
+    TracerProvider::uninstall();
+    assert!(!TracerProvider::is_installed());
+    let spans = exported.lock().unwrap().clone();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[1].attributes["route"], "/query");
+    assert_eq!(spans[0].parent_span_id, root.span_id);
+
+    // Spans ending after uninstall are not routed
+    let mut late = OtelSpan::new("late");
+    late.end();
+    assert_eq!(ended.lock().unwrap().len(), 2);
+}
@@ -0,0 +1,81 @@
+//! `#[trace]` attribute macro
+//!
+//! Installs a process-wide tracer provider, so it runs in its own test
+//! binary.
+#![cfg(feature = "macros")]
+
+use embeddenator_obs as obs;
+use embeddenator_obs::span_processor::TracerProvider;
+use embeddenator_obs::{AttributeValue, OtelSpan, SpanStatus, Telemetry};
+use std::sync::{Arc, LazyLock, Mutex};
+
+static TELEMETRY: LazyLock<Arc<Mutex<Telemetry>>> =
+    LazyLock::new(|| Arc::new(Mutex::new(Telemetry::default_config())));
+
+#[obs::trace(telemetry = TELEMETRY, skip(payload), err)]
+fn ingest(shard: u32, name: &str, payload: &[u8]) -> Result<usize, String> {
+    if payload.is_empty() {
+        return Err(format!("empty payload for {}", name));
+    }
+    let parsed = parse(payload.len())?;
+    Ok(parsed)
+}
+
+#[obs::trace(name = "parse_payload")]
+fn parse(len: usize) -> Result<usize, String> {
+    Ok(len * 2)
+}
+
+struct Index {
+    docs: Vec<String>,
+}
+
+impl Index {
+    #[obs::trace(skip(self))]
+    fn add(&mut self, doc: String) -> usize {
+        self.docs.push(doc);
+        self.docs.len()
+    }
+}
+
+#[obs::trace]
+async fn fetch(url: &str) -> usize {
+    url.len()
+}
+
+#[test]
+fn test_trace_macro() {
+    let ended = Arc::new(Mutex::new(Vec::<OtelSpan>::new()));
+    let sink = ended.clone();
+    TracerProvider::new()
+        .with_processor(move |span: &OtelSpan| sink.lock().unwrap().push(span.clone()))
+        .install();
+
+    assert_eq!(ingest(3, "docs", b"abc"), Ok(6));
+    assert!(ingest(4, "logs", b"").is_err());
+    let mut index = Index { docs: Vec::new() };
+    assert_eq!(index.add("doc".to_string()), 1);
+    drop(fetch("http://x"));
+    TracerProvider::uninstall();
+
+    let spans = ended.lock().unwrap().clone();
+    let names: Vec<&str> = spans.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["parse_payload", "ingest", "ingest", "add"]);
+
+    let (parse, ok, failed) = (&spans[0], &spans[1], &spans[2]);
+    assert_eq!(parse.parent_span_id, ok.span_id);
+    assert_eq!(parse.trace_id, ok.trace_id);
+    assert_eq!(parse.attributes["len"], AttributeValue::I64(3));
+    assert_eq!(ok.attributes["shard"], AttributeValue::I64(3));
+    assert_eq!(ok.attributes["name"], "docs");
+    assert!(!ok.attributes.contains_key("payload"));
+    assert_eq!(ok.status, SpanStatus::Ok);
+    assert_eq!(failed.status, SpanStatus::Error);
+    assert_eq!(failed.attributes["error.message"], "empty payload for logs");
+    assert!(failed.is_root());
+    assert_eq!(spans[3].attributes["doc"], "doc");
+
+    let snapshot = TELEMETRY.lock().unwrap().snapshot();
+    assert_eq!(snapshot.operation_stats["ingest"].count, 2);
+    assert!(!snapshot.operation_stats.contains_key("parse_payload"));
+}